@@ -188,10 +188,10 @@ impl RenderBackend for HayroRenderBackend {
             let cache = hayro::RenderCache::new();
 
             for page in document.pages().iter() {
-                let pixmap = hayro::render(page, &cache, &interpreter_settings, &render_settings);
+                let image = hayro::render(page, &cache, &interpreter_settings, &render_settings);
 
                 if iteration == 0 {
-                    total_bytes += pixmap.width() as usize * pixmap.height() as usize * 4;
+                    total_bytes += image.data.len();
                     page_count += 1;
                 }
             }
@@ -201,27 +201,24 @@ impl RenderBackend for HayroRenderBackend {
             let document = hayro::hayro_syntax::Pdf::new(pdf_bytes)
                 .map_err(|err| format!("load failed: {err:?}"))?;
             let cache = hayro::RenderCache::new();
+            let render_settings = hayro::RenderSettings {
+                alpha_mode: hayro::AlphaMode::Straight,
+                ..render_settings
+            };
 
             document
                 .pages()
                 .iter()
                 .enumerate()
                 .map(|(page_index, page)| {
-                    let pixmap =
+                    let image =
                         hayro::render(page, &cache, &interpreter_settings, &render_settings);
-                    let width = pixmap.width() as u32;
-                    let height = pixmap.height() as u32;
-                    let rgba = pixmap
-                        .take_unpremultiplied()
-                        .into_iter()
-                        .flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a])
-                        .collect();
 
                     PageBitmap {
                         page_index,
-                        width,
-                        height,
-                        rgba,
+                        width: image.width as u32,
+                        height: image.height as u32,
+                        rgba: image.data,
                     }
                 })
                 .collect()
@@ -139,4 +139,27 @@ impl BitReader<'_> {
             return count;
         }
     }
+
+    /// Return whether an EOL code (with optional fill bits) follows.
+    #[inline(always)]
+    pub(crate) fn has_eol(&self) -> bool {
+        self.clone().read_eol_if_available() > 0
+    }
+
+    /// Skip bits until the reader is positioned at the start of an EOL code.
+    ///
+    /// Returns `false` if there is no EOL in the remaining data.
+    pub(crate) fn skip_to_eol(&mut self) -> bool {
+        const EOL: u32 = 0x001;
+
+        loop {
+            match self.peak_bits(12) {
+                Ok(EOL) => return true,
+                Ok(_) => {
+                    let _ = self.read_bit();
+                }
+                Err(_) => return false,
+            }
+        }
+    }
 }
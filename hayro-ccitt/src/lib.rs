@@ -108,6 +108,12 @@ pub struct DecodeSettings {
     pub encoding: EncodingMode,
     /// Whether black and white should be inverted.
     pub invert_black: bool,
+    /// The number of damaged rows that are tolerated before decoding fails.
+    ///
+    /// This only applies to Group 3 images with end-of-line markers. A row
+    /// that can't be decoded is completed with the pixels of the previous row,
+    /// and decoding continues after the next end-of-line marker.
+    pub damaged_rows_before_error: u32,
 }
 
 /// A decoder for CCITT images.
@@ -194,7 +200,12 @@ fn decode_group3_1d(
     let _ = reader.read_eol_if_available();
 
     loop {
-        decode_1d_line(ctx, reader, decoder)?;
+        let row_start = reader.clone();
+
+        if let Err(e) = decode_1d_line(ctx, reader, decoder) {
+            recover_damaged_row(ctx, reader, row_start, decoder, e)?;
+        }
+
         ctx.next_line(reader, decoder)?;
 
         if group3_check_eob(ctx, reader) {
@@ -216,12 +227,17 @@ fn decode_group3_2d(
     let _ = reader.read_eol_if_available();
 
     loop {
+        let row_start = reader.clone();
         let tag_bit = reader.read_bit()?;
 
-        if tag_bit == 1 {
-            decode_1d_line(ctx, reader, decoder)?;
+        let result = if tag_bit == 1 {
+            decode_1d_line(ctx, reader, decoder)
         } else {
-            decode_2d_line(ctx, reader, decoder)?;
+            decode_2d_line(ctx, reader, decoder)
+        };
+
+        if let Err(e) = result {
+            recover_damaged_row(ctx, reader, row_start, decoder, e)?;
         }
 
         ctx.next_line(reader, decoder)?;
@@ -234,6 +250,36 @@ fn decode_group3_2d(
     Ok(())
 }
 
+/// Try to recover from an error in the current row by skipping to the next
+/// EOL (T.4 Section 4.1.2) and completing the row with the reference line.
+///
+/// If there are no EOLs or too many rows have been damaged already, the
+/// original error is returned.
+fn recover_damaged_row<'a>(
+    ctx: &mut DecoderContext,
+    reader: &mut BitReader<'a>,
+    row_start: BitReader<'a>,
+    decoder: &mut impl Decoder,
+    error: DecodeError,
+) -> Result<()> {
+    if !ctx.settings.end_of_line || ctx.damaged_rows >= ctx.settings.damaged_rows_before_error {
+        return Err(error);
+    }
+
+    // The invalid code might have consumed some of the zeroes of the next EOL,
+    // so we need to start searching from the beginning of the row.
+    *reader = row_start;
+
+    if !reader.skip_to_eol() {
+        return Err(error);
+    }
+
+    ctx.damaged_rows += 1;
+    ctx.fill_from_reference(decoder);
+
+    Ok(())
+}
+
 /// Check for end-of-block, including RTC (T.4 Section 4.1.4).
 fn group3_check_eob(ctx: &mut DecoderContext, reader: &mut BitReader<'_>) -> bool {
     let eol_count = reader.read_eol_if_available();
@@ -283,6 +329,7 @@ fn decode_1d_line(
     decoder: &mut impl Decoder,
 ) -> Result<()> {
     while !ctx.at_eol() {
+        ctx.check_premature_eol(reader)?;
         let run_length = reader.decode_run(ctx.color)?;
         ctx.push_pixels(decoder, run_length);
         ctx.color = ctx.color.opposite();
@@ -299,6 +346,7 @@ fn decode_2d_line(
     decoder: &mut impl Decoder,
 ) -> Result<()> {
     while !ctx.at_eol() {
+        ctx.check_premature_eol(reader)?;
         let mode = reader.decode_mode()?;
 
         match mode {
@@ -360,6 +408,8 @@ pub struct DecoderContext {
     color: Color,
     /// How many rows have been decoded so far.
     decoded_rows: u32,
+    /// How many damaged rows have been encountered so far.
+    damaged_rows: u32,
     /// The settings to apply during decoding.
     settings: DecodeSettings,
     /// Whether to invert black and white.
@@ -379,6 +429,7 @@ impl DecoderContext {
             // Each run starts with an imaginary white pixel on the left.
             color: Color::White,
             decoded_rows: 0,
+            damaged_rows: 0,
             settings,
             invert_black: settings.invert_black,
         }
@@ -393,6 +444,7 @@ impl DecoderContext {
         self.line_width = self.settings.columns;
         self.color = Color::White;
         self.decoded_rows = 0;
+        self.damaged_rows = 0;
         self.invert_black = self.settings.invert_black;
     }
 
@@ -498,6 +550,47 @@ impl DecoderContext {
         }
     }
 
+    /// Return an error if an EOL starts before the current row is complete.
+    ///
+    /// This is only checked if damaged rows can be recovered. Otherwise, the
+    /// zeroes are just read as an invalid code.
+    #[inline(always)]
+    fn check_premature_eol(&self, reader: &mut BitReader<'_>) -> Result<()> {
+        // No code starts with 11 zeroes, so they can only belong to an EOL.
+        if self.settings.end_of_line
+            && self.settings.damaged_rows_before_error > 0
+            && reader.peak_bits(11) == Ok(0)
+        {
+            return Err(DecodeError::InvalidCode);
+        }
+
+        Ok(())
+    }
+
+    /// Push the remaining pixels of the current line with the colors of the
+    /// reference line.
+    fn fill_from_reference(&mut self, decoder: &mut impl Decoder) {
+        let changes = core::mem::take(&mut self.ref_changes);
+        let mut color = Color::White;
+
+        for i in 0..=changes.len() {
+            let end = changes
+                .get(i)
+                .map_or(self.line_width, |c| c.idx.min(self.line_width));
+
+            if end > self.pixels_decoded {
+                self.color = color;
+                self.push_pixels(decoder, end - self.pixels_decoded);
+            }
+
+            if let Some(change) = changes.get(i) {
+                color = change.color;
+            }
+        }
+
+        self.ref_changes = changes;
+    }
+
     #[inline(always)]
     fn at_eol(&self) -> bool {
         self.a0().unwrap_or(0) == self.line_width
@@ -518,7 +611,12 @@ impl DecoderContext {
         self.decoded_rows += 1;
         decoder.next_line();
 
-        if self.settings.rows_are_byte_aligned {
+        // In Group 3, if the row is followed by an EOL, the fill bits in front
+        // of it already take care of the alignment. Aligning the reader before
+        // it could skip some of the zeroes of the EOL itself.
+        if self.settings.rows_are_byte_aligned
+            && (self.settings.encoding == EncodingMode::Group4 || !reader.has_eol())
+        {
             reader.align();
         }
 
@@ -48,9 +48,9 @@ const SEGMENT_BF_SINGLE_4U: u8 = 0x14;
 const SEGMENT_BF_RANGE_1U: u8 = 0x15;
 const SEGMENT_BF_RANGE_2U: u8 = 0x16;
 
-pub(crate) fn parse<'a>(
+pub(crate) fn parse<D: AsRef<[u8]>>(
     data: &[u8],
-    get_cmap: impl Fn(CMapName<'_>) -> Option<&'a [u8]> + Clone + 'a,
+    get_cmap: impl Fn(CMapName<'_>) -> Option<D> + Clone,
     depth: u32,
 ) -> Option<CMap> {
     // While in theory we can assume that all binary cmaps are valid, it can
@@ -110,9 +110,9 @@ pub(crate) fn parse<'a>(
                 let base_data = get_cmap(CMapName::from_bytes(payload))?;
 
                 base = Some(Box::new(parse::parse_inner(
-                    base_data,
+                    base_data.as_ref(),
                     get_cmap.clone(),
-                    depth,
+                    depth + 1,
                 )?));
             }
             SEGMENT_WMODE => {
@@ -188,12 +188,18 @@ pub(crate) fn parse<'a>(
     notdef_ranges.sort();
     bf_entries.sort_by(|a, b| a.range.start.cmp(&b.range.start));
 
+    let mut metadata = Metadata {
+        character_collection,
+        name: cmap_name,
+        writing_mode,
+    };
+
+    if let Some(base) = &base {
+        metadata.inherit(&base.metadata);
+    }
+
     Some(CMap {
-        metadata: Metadata {
-            character_collection,
-            name: cmap_name,
-            writing_mode,
-        },
+        metadata,
         _codespace_ranges,
         cid_ranges,
         notdef_ranges,
@@ -372,14 +372,30 @@ impl CMap {
     /// Parse a cmap from raw bytes.
     ///
     /// The `get_cmap` callback is used to recursively resolve cmaps that
-    /// are referenced via `usecmap`.
-    pub fn parse<'a>(
+    /// are referenced via `usecmap`. It can return borrowed data like the
+    /// result of [`load_embedded`], or owned data like a `Vec<u8>`.
+    pub fn parse<D: AsRef<[u8]>>(
         data: &[u8],
-        get_cmap: impl Fn(CMapName<'_>) -> Option<&'a [u8]> + Clone + 'a,
+        get_cmap: impl Fn(CMapName<'_>) -> Option<D> + Clone,
     ) -> Option<Self> {
         parse::parse_inner(data, get_cmap, 0)
     }
 
+    /// Use `base` for all codes that aren't mapped by this cmap, in the same way
+    /// as the `usecmap` operator does.
+    ///
+    /// This is needed for the `UseCMap` entry of cmap streams in PDF files. If
+    /// the cmap already references another cmap via `usecmap`, it is kept and
+    /// `base` is ignored.
+    pub fn with_base(mut self, base: Self) -> Self {
+        if self.base.is_none() {
+            self.metadata.inherit(&base.metadata);
+            self.base = Some(Box::new(base));
+        }
+
+        self
+    }
+
     /// Create an Identity-H cmap.
     pub fn identity_h() -> Self {
         Self::identity(WritingMode::Horizontal, b"Identity-H")
@@ -488,7 +504,7 @@ impl CMap {
                 Some(decode_utf16(&entry.dst_base)?)
             } else {
                 let mut units = entry.dst_base.clone();
-                *units.last_mut()? = units.last()?.checked_add(offset)?;
+                increment_last_char(&mut units, offset)?;
                 Some(decode_utf16(&units)?)
             };
         }
@@ -501,6 +517,24 @@ impl CMap {
     }
 }
 
+/// Add `offset` to the last character of a UTF-16 string.
+///
+/// For characters outside of the BMP, the offset is added to the code point instead
+/// of the low surrogate, so that ranges can cross a surrogate boundary.
+fn increment_last_char(units: &mut Vec<u16>, offset: u16) -> Option<()> {
+    if let [.., high @ 0xD800..=0xDBFF, low @ 0xDC00..=0xDFFF] = units[..] {
+        let code_point = 0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(low) - 0xDC00);
+        let c = char::from_u32(code_point.checked_add(u32::from(offset))?)?;
+
+        units.truncate(units.len() - 2);
+        units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+    } else {
+        *units.last_mut()? = units.last()?.checked_add(offset)?;
+    }
+
+    Some(())
+}
+
 trait HasRange {
     fn range(&self) -> &Range;
 }
@@ -605,6 +639,15 @@ pub enum BfString {
     String(String),
 }
 
+impl core::fmt::Display for BfString {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Char(c) => write!(f, "{c}"),
+            Self::String(s) => f.write_str(s),
+        }
+    }
+}
+
 /// Metadata extracted from a cmap file.
 #[derive(Debug, Clone)]
 pub struct Metadata {
@@ -616,6 +659,18 @@ pub struct Metadata {
     pub writing_mode: Option<WritingMode>,
 }
 
+impl Metadata {
+    /// Take over the character collection and writing mode of the cmap referenced
+    /// via `usecmap`, unless they are defined by this cmap itself.
+    pub(crate) fn inherit(&mut self, base: &Self) {
+        if self.character_collection.is_none() {
+            self.character_collection = base.character_collection.clone();
+        }
+
+        self.writing_mode = self.writing_mode.or(base.writing_mode);
+    }
+}
+
 /// The registry+ordering family of a CID character collection.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CidFamily {
@@ -729,7 +784,7 @@ endcodespacerange
         let mut data = Vec::new();
         data.extend_from_slice(PREAMBLE);
         data.extend_from_slice(body);
-        CMap::parse(&data, |_| None).unwrap()
+        CMap::parse(&data, |_| None::<&[u8]>).unwrap()
     }
 
     #[test]
@@ -748,7 +803,7 @@ end def
 /WMode 0 def
 endcmap"#;
 
-        let cmap = CMap::parse(data, |_| None).unwrap();
+        let cmap = CMap::parse(data, |_| None::<&[u8]>).unwrap();
         let cc = cmap.metadata().character_collection.as_ref().unwrap();
         assert_eq!(cc.family, CidFamily::AdobeJapan1);
         assert_eq!(cc.supplement, 6);
@@ -771,7 +826,7 @@ end def
 /WMode 1 def
 "#;
 
-        let cmap = CMap::parse(data, |_| None).unwrap();
+        let cmap = CMap::parse(data, |_| None::<&[u8]>).unwrap();
         assert_eq!(cmap.metadata().writing_mode, Some(WritingMode::Vertical));
         assert_eq!(
             cmap.metadata().name.as_deref(),
@@ -899,7 +954,7 @@ CMapName currentdict /CMap defineresource pop
 end
 end
 "#;
-        let cmap = CMap::parse(data, |_| None).unwrap();
+        let cmap = CMap::parse(data, |_| None::<&[u8]>).unwrap();
         let cc = cmap.metadata().character_collection.as_ref().unwrap();
         assert_eq!(
             cc.family,
@@ -1027,6 +1082,75 @@ endcidrange
         assert_eq!(cmap.lookup_cid_code(0x00FF, 2), Some(0xFF));
     }
 
+    #[test]
+    fn usecmap_inherits_metadata() {
+        let base_data = br#"
+/CIDSystemInfo 3 dict dup begin
+  /Registry (Adobe) def
+  /Ordering (Japan1) def
+  /Supplement 2 def
+end def
+/CMapName /Base def
+/WMode 1 def
+1 begincidrange
+<0000> <00FF> 0
+endcidrange
+"#;
+
+        let child_data = br#"
+/Base usecmap
+/CMapName /Child def
+1 begincidrange
+<0100> <01FF> 256
+endcidrange
+"#;
+
+        let cmap = CMap::parse(child_data, |name| {
+            (name.to_bytes() == b"Base").then_some(base_data.as_slice())
+        })
+        .unwrap();
+
+        assert_eq!(cmap.metadata().name.as_deref(), Some(b"Child".as_slice()));
+        assert_eq!(cmap.metadata().writing_mode, Some(WritingMode::Vertical));
+        assert_eq!(
+            cmap.metadata().character_collection,
+            Some(CharacterCollection {
+                family: CidFamily::AdobeJapan1,
+                supplement: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn usecmap_unresolved_with_base() {
+        let base_data = br#"
+/WMode 1 def
+1 begincidrange
+<0000> <00FF> 0
+endcidrange
+"#;
+
+        let child_data = br#"
+/Embedded usecmap
+/WMode 0 def
+1 begincidrange
+<0040> <007F> 500
+endcidrange
+"#;
+
+        // The referenced cmap can't be resolved by name, so it has to be
+        // provided separately.
+        let cmap = CMap::parse(child_data, |_| None::<&[u8]>).unwrap();
+        assert_eq!(cmap.lookup_cid_code(0x0000, 2), None);
+
+        let base = CMap::parse(base_data, |_| None::<&[u8]>).unwrap();
+        let cmap = cmap.with_base(base);
+
+        assert_eq!(cmap.lookup_cid_code(0x0000, 2), Some(0));
+        assert_eq!(cmap.lookup_cid_code(0x0040, 2), Some(500));
+        assert_eq!(cmap.metadata().writing_mode, Some(WritingMode::Horizontal));
+    }
+
     #[test]
     fn notdef_char_lookup() {
         let cmap = parse_with_preamble(
@@ -1123,6 +1247,51 @@ endbfrange
         assert_eq!(cmap.lookup_bf_string(0x0005), None);
     }
 
+    #[test]
+    fn bfrange_incrementing_surrogate_pair() {
+        let cmap = parse_with_preamble(
+            br#"
+1 beginbfrange
+<0000> <0002> <D835DFFE>
+endbfrange
+"#,
+        );
+
+        assert_eq!(
+            cmap.lookup_bf_string(0x0000),
+            Some(BfString::Char('\u{1D7FE}'))
+        );
+        assert_eq!(
+            cmap.lookup_bf_string(0x0001),
+            Some(BfString::Char('\u{1D7FF}'))
+        );
+        // The increment carries over into the high surrogate.
+        assert_eq!(
+            cmap.lookup_bf_string(0x0002),
+            Some(BfString::Char('\u{1D800}'))
+        );
+    }
+
+    #[test]
+    fn bfrange_incrementing_multiple_chars() {
+        let cmap = parse_with_preamble(
+            br#"
+1 beginbfrange
+<0010> <0011> <00660066>
+endbfrange
+"#,
+        );
+
+        assert_eq!(
+            cmap.lookup_bf_string(0x0010),
+            Some(BfString::String(String::from("ff")))
+        );
+        assert_eq!(
+            cmap.lookup_bf_string(0x0011),
+            Some(BfString::String(String::from("fg")))
+        );
+    }
+
     #[test]
     fn bfrange_array() {
         let cmap = parse_with_preamble(
@@ -1208,7 +1377,7 @@ endcodespacerange
 endbfrange
 endcmap
 "#;
-        let cmap = CMap::parse(data, |_| None).unwrap();
+        let cmap = CMap::parse(data, |_| None::<&[u8]>).unwrap();
         assert_eq!(cmap.metadata().name, None);
         assert_eq!(cmap.metadata().character_collection, None);
         assert_eq!(cmap.metadata().writing_mode, None);
@@ -1234,7 +1403,7 @@ endcodespacerange
 <0000> <0000> <6881>
 endbfrange
 "#;
-        let cmap = CMap::parse(data, |_| None).unwrap();
+        let cmap = CMap::parse(data, |_| None::<&[u8]>).unwrap();
         let cc = cmap.metadata().character_collection.as_ref().unwrap();
         assert_eq!(
             cc.family,
@@ -15,9 +15,9 @@ struct Context<F> {
     get_cmap: F,
 }
 
-pub(crate) fn parse_inner<'a>(
+pub(crate) fn parse_inner<D: AsRef<[u8]>>(
     data: &[u8],
-    get_cmap: impl Fn(CMapName<'_>) -> Option<&'a [u8]> + Clone + 'a,
+    get_cmap: impl Fn(CMapName<'_>) -> Option<D> + Clone,
     depth: u32,
 ) -> Option<CMap> {
     // Prevent stack overflow for malicious cmap files or circular references.
@@ -102,13 +102,19 @@ pub(crate) fn parse_inner<'a>(
                     parse_bf_range(&mut scanner, &mut bf_entries, &mut ctx)?;
                 }
                 Some("usecmap") => {
-                    let nested_data = (ctx.get_cmap)(CMapName::from_bytes(last_name.as_deref()?))?;
-
-                    base = Some(Box::new(parse_inner(
-                        nested_data,
-                        ctx.get_cmap.clone(),
-                        depth + 1,
-                    )?));
+                    // Embedded cmaps might reference another embedded cmap that can only
+                    // be found via the `UseCMap` entry of their stream dictionary, so
+                    // don't fail if it can't be resolved.
+                    if let Some(nested_data) = last_name
+                        .as_deref()
+                        .and_then(|name| (ctx.get_cmap)(CMapName::from_bytes(name)))
+                    {
+                        base = Some(Box::new(parse_inner(
+                            nested_data.as_ref(),
+                            ctx.get_cmap.clone(),
+                            depth + 1,
+                        )?));
+                    }
                 }
                 _ => {}
             }
@@ -133,12 +139,16 @@ pub(crate) fn parse_inner<'a>(
         None
     };
 
-    let metadata = Metadata {
+    let mut metadata = Metadata {
         character_collection,
         name: cmap_name,
         writing_mode,
     };
 
+    if let Some(base) = &base {
+        metadata.inherit(&base.metadata);
+    }
+
     Some(CMap {
         metadata,
         _codespace_ranges,
@@ -1,7 +1,7 @@
 use console_error_panic_hook;
 use hayro::hayro_interpret::InterpreterSettings;
 use hayro::hayro_syntax::Pdf;
-use hayro::{RenderCache, RenderSettings};
+use hayro::{AlphaMode, RenderCache, RenderSettings};
 use js_sys;
 use vello_cpu::color::palette::css::WHITE;
 use wasm_bindgen::prelude::*;
@@ -118,21 +118,18 @@ impl PdfViewer {
             x_scale: scale,
             y_scale: scale,
             bg_color: WHITE,
+            alpha_mode: AlphaMode::Straight,
             ..Default::default()
         };
 
         let cache = RenderCache::new();
-        let pixmap = hayro::render(page, &cache, &interpreter_settings, &render_settings);
+        let image = hayro::render(page, &cache, &interpreter_settings, &render_settings);
 
         // Return array: [width, height, pixel_data]
         let result = js_sys::Array::new_with_length(3);
-        result.set(0, JsValue::from(pixmap.width()));
-        result.set(1, JsValue::from(pixmap.height()));
-
-        // Cast Vec<Rgba8> to Vec<u8>
-        let rgba_data = pixmap.take_unpremultiplied();
-        let byte_data: Vec<u8> = bytemuck::cast_vec(rgba_data);
-        result.set(2, JsValue::from(byte_data));
+        result.set(0, JsValue::from(image.width));
+        result.set(1, JsValue::from(image.height));
+        result.set(2, JsValue::from(image.data));
 
         Ok(result)
     }
@@ -13,7 +13,7 @@ impl hayro_ccitt::Decoder for Decoder {
     fn next_line(&mut self) {}
 }
 
-// Header layout (10 bytes):
+// Header layout (11 bytes):
 // [0..2]  columns (u16 LE)
 // [2..4]  rows (u16 LE)
 // [4]     end_of_block (bool)
@@ -22,9 +22,10 @@ impl hayro_ccitt::Decoder for Decoder {
 // [7]     encoding_mode (0=Group4, 1=Group3_1D, 2+=Group3_2D)
 // [8]     k parameter for Group3_2D
 // [9]     invert_black (bool)
-// [10..]  CCITT encoded data
+// [10]    damaged_rows_before_error (u8)
+// [11..]  CCITT encoded data
 
-const HEADER_SIZE: usize = 10;
+const HEADER_SIZE: usize = 11;
 
 fuzz_target!(|data: &[u8]| {
     if data.len() < HEADER_SIZE {
@@ -44,6 +45,7 @@ fuzz_target!(|data: &[u8]| {
         },
     };
     let invert_black = data[9] != 0;
+    let damaged_rows_before_error = data[10] as u32;
 
     let settings = hayro_ccitt::DecodeSettings {
         columns,
@@ -53,6 +55,7 @@ fuzz_target!(|data: &[u8]| {
         rows_are_byte_aligned,
         encoding,
         invert_black,
+        damaged_rows_before_error,
     };
 
     let mut decoder = Decoder;
@@ -0,0 +1,49 @@
+//! A small example that shows how you can extract the text of a page using
+//! the `TextDevice`. Please note that Unicode extraction is still experimental,
+//! so it might not work fully correctly in certain cases.
+
+use hayro_interpret::text::TextDevice;
+use hayro_interpret::{Context, InterpreterCache, InterpreterSettings, interpret_page};
+use hayro_syntax::Pdf;
+use kurbo::{Affine, Rect};
+use std::path::PathBuf;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let relative_path = args
+        .get(1)
+        .expect("Please provide a relative path to the PDF file as the first argument");
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(relative_path);
+    let data = std::fs::read(path).unwrap();
+
+    let pdf = Pdf::new(data).unwrap();
+    let settings = InterpreterSettings::default();
+    let cache = InterpreterCache::new();
+
+    for (idx, page) in pdf.pages().iter().enumerate() {
+        // Pass dummy values for the bbox and use the identity as the initial transform,
+        // so that all positions are in the default user space of the page.
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 1.0, 1.0),
+            &cache,
+            pdf.xref(),
+            settings.clone(),
+        );
+        let mut device = TextDevice::new();
+        interpret_page(page, &mut context, &mut device);
+
+        println!("--- page {idx} ---");
+
+        for run in device.runs() {
+            let origin = run.transform * run.origin();
+            println!(
+                "({:.1}, {:.1}) {:.1}pt: {}",
+                origin.x,
+                origin.y,
+                run.font_size,
+                run.text()
+            );
+        }
+    }
+}
@@ -1,14 +1,21 @@
+use crate::font::GlyphCache;
 use crate::util::hash128;
 use hayro_syntax::object::{Array, Dict, MaybeRef, Name, Null, ObjRef, Object, Stream};
 use kurbo::{Affine, Rect};
 use rustc_hash::FxHashMap;
 use std::any::Any;
 use std::collections::hash_map::Entry;
+use std::fmt::{Debug, Formatter};
 use std::sync::{Arc, Mutex};
 
 type CacheMap = FxHashMap<u128, Option<Box<dyn Any + Send + Sync>>>;
+
+/// The number of shards of the object cache. Threads only contend for a lock if
+/// they access entries that are stored in the same shard.
+const SHARDS: usize = 16;
+
 #[derive(Clone)]
-pub(crate) struct Cache(Arc<Mutex<CacheMap>>);
+pub(crate) struct Cache(Arc<[Mutex<CacheMap>; SHARDS]>);
 
 impl Default for Cache {
     fn default() -> Self {
@@ -18,7 +25,14 @@ impl Default for Cache {
 
 impl Cache {
     pub(crate) fn new() -> Self {
-        Self(Arc::new(Mutex::new(FxHashMap::default())))
+        Self(Arc::new(std::array::from_fn(|_| {
+            Mutex::new(FxHashMap::default())
+        })))
+    }
+
+    fn shard(&self, id: u128) -> &Mutex<CacheMap> {
+        // The keys are hashes, so their lowest bits are evenly distributed.
+        &self.0[(id % SHARDS as u128) as usize]
     }
 
     pub(crate) fn get_or_insert_with<T: Clone + Send + Sync + 'static>(
@@ -26,10 +40,12 @@ impl Cache {
         id: u128,
         f: impl FnOnce() -> Option<T>,
     ) -> Option<T> {
-        let mut locked = self.0.lock().unwrap();
+        let shard = self.shard(id);
+        let mut locked = shard.lock().unwrap();
 
         // We can't use `get_or_insert_with` here, because if the closure makes another access to the
-        // cache, we end up with a deadlock.
+        // cache, we end up with a deadlock. Not holding the lock also allows other threads to use
+        // the shard in the meantime.
         match locked.entry(id) {
             Entry::Occupied(o) => o
                 .get()
@@ -38,7 +54,7 @@ impl Cache {
             Entry::Vacant(_) => {
                 drop(locked);
                 let val = f();
-                self.0.lock().unwrap().insert(
+                shard.lock().unwrap().insert(
                     id,
                     val.clone()
                         .map(|val| Box::new(val) as Box<dyn Any + Send + Sync>),
@@ -50,6 +66,242 @@ impl Cache {
     }
 }
 
+/// The part of an [`InterpreterCache`](crate::InterpreterCache) that can be shared
+/// between threads.
+///
+/// It stores parsed color spaces, cmaps, shadings and embedded font programs as well
+/// as glyph outlines, decoded images and form contents. When rendering several pages
+/// of a document in parallel, create the interpreter cache of each thread with
+/// [`InterpreterCache::with_shared_cache`](crate::InterpreterCache::with_shared_cache),
+/// so that the threads reuse each other's work. Only the font objects that refer to
+/// the font programs are still created per interpreter cache, which is cheap.
+///
+/// Cloning it only creates a new handle to the same cache.
+#[derive(Clone, Default)]
+pub struct SharedCache {
+    pub(crate) object_cache: Cache,
+    pub(crate) glyph_cache: GlyphCache,
+    pub(crate) image_cache: ImageCache,
+    pub(crate) form_cache: FormCache,
+}
+
+impl Debug for SharedCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedCache")
+            .field("glyph_cache", &self.glyph_cache)
+            .field("image_cache", &self.image_cache)
+            .field("form_cache", &self.form_cache)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SharedCache {
+    /// Create a new shared cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new shared cache that uses the given cache for glyph outlines.
+    ///
+    /// Use this to configure the memory limit of the glyph cache.
+    pub fn with_glyph_cache(glyph_cache: GlyphCache) -> Self {
+        Self {
+            glyph_cache,
+            ..Self::default()
+        }
+    }
+
+    /// Return the cache for glyph outlines.
+    pub fn glyph_cache(&self) -> &GlyphCache {
+        &self.glyph_cache
+    }
+
+    /// Return the cache for decoded images.
+    pub fn image_cache(&self) -> &ImageCache {
+        &self.image_cache
+    }
+
+    /// Return the cache for decoded form contents.
+    pub fn form_cache(&self) -> &FormCache {
+        &self.form_cache
+    }
+}
+
+/// A cache for decoded images.
+///
+/// Documents often draw the same image several times, for example a logo on every
+/// page. The cache is part of the [`InterpreterCache`](crate::InterpreterCache), so
+/// decoded images are reused across interpreter runs on the same document, and cloning
+/// it only creates a new handle to the same cache.
+///
+/// Only images that are objects of their own are cached. Inline images and images
+/// that are drawn with a transfer function are decoded every time they are drawn.
+/// The decoded data is shared, so taking an image from the cache doesn't copy it.
+///
+/// The memory limit is given by
+/// [`InterpreterSettings::image_cache_max_bytes`](crate::InterpreterSettings::image_cache_max_bytes).
+/// Once the images that are stored exceed it, the ones that were used least recently
+/// are evicted.
+#[derive(Clone, Default)]
+pub struct ImageCache(pub(crate) LruCache);
+
+impl Debug for ImageCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageCache")
+            .field("memory_usage", &self.memory_usage())
+            .finish()
+    }
+}
+
+impl ImageCache {
+    /// Return the number of bytes that are currently used by the cached images.
+    pub fn memory_usage(&self) -> usize {
+        self.0.memory_usage()
+    }
+
+    /// Remove all images from the cache.
+    pub fn clear(&self) {
+        self.0.clear();
+    }
+}
+
+/// A cache for the decoded content streams of form `XObject`s.
+///
+/// Forms are often drawn several times, for example a letterhead on every page, so
+/// their content streams are only decoded once. Like the [`ImageCache`], it is part
+/// of the [`InterpreterCache`](crate::InterpreterCache).
+///
+/// The memory limit is given by
+/// [`InterpreterSettings::form_cache_max_bytes`](crate::InterpreterSettings::form_cache_max_bytes).
+/// Once the content streams that are stored exceed it, the ones that were used least
+/// recently are evicted.
+#[derive(Clone, Default)]
+pub struct FormCache(pub(crate) LruCache);
+
+impl Debug for FormCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FormCache")
+            .field("memory_usage", &self.memory_usage())
+            .finish()
+    }
+}
+
+impl FormCache {
+    /// Return the number of bytes that are currently used by the cached content
+    /// streams.
+    pub fn memory_usage(&self) -> usize {
+        self.0.memory_usage()
+    }
+
+    /// Remove all content streams from the cache.
+    pub fn clear(&self) {
+        self.0.clear();
+    }
+}
+
+/// A cache with a memory limit, which evicts the entries that were used least
+/// recently once they exceed it.
+#[derive(Clone, Default)]
+pub(crate) struct LruCache(Arc<Mutex<LruCacheInner>>);
+
+impl LruCache {
+    fn memory_usage(&self) -> usize {
+        self.0.lock().unwrap().used_bytes
+    }
+
+    fn clear(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.entries.clear();
+        inner.used_bytes = 0;
+    }
+
+    pub(crate) fn get_or_insert_with<T: Clone + Send + Sync + 'static>(
+        &self,
+        key: u128,
+        max_bytes: usize,
+        f: impl FnOnce() -> Option<T>,
+        size: impl FnOnce(&T) -> usize,
+    ) -> Option<T> {
+        {
+            let mut inner = self.0.lock().unwrap();
+            inner.clock += 1;
+            let clock = inner.clock;
+
+            if let Some(entry) = inner.entries.get_mut(&key)
+                && let Some(value) = entry.value.downcast_ref::<T>()
+            {
+                entry.last_used = clock;
+
+                return Some(value.clone());
+            }
+        }
+
+        // Don't hold the lock while computing the value, since it can be computed
+        // from several threads.
+        let value = f()?;
+        let bytes = size(&value);
+
+        if bytes <= max_bytes {
+            let mut inner = self.0.lock().unwrap();
+            let last_used = inner.clock;
+            inner.used_bytes += bytes;
+
+            if let Some(old) = inner.entries.insert(
+                key,
+                LruCacheEntry {
+                    value: Box::new(value.clone()),
+                    bytes,
+                    last_used,
+                },
+            ) {
+                inner.used_bytes -= old.bytes;
+            }
+
+            if inner.used_bytes > max_bytes {
+                inner.evict(max_bytes);
+            }
+        }
+
+        Some(value)
+    }
+}
+
+#[derive(Default)]
+struct LruCacheInner {
+    entries: FxHashMap<u128, LruCacheEntry>,
+    used_bytes: usize,
+    /// Increases with every access, used to determine which entries were used least
+    /// recently.
+    clock: u64,
+}
+
+impl LruCacheInner {
+    fn evict(&mut self, max_bytes: usize) {
+        let mut by_age = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (entry.last_used, *key))
+            .collect::<Vec<_>>();
+        by_age.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+        for (_, key) in by_age {
+            if self.used_bytes <= max_bytes {
+                break;
+            }
+
+            if let Some(entry) = self.entries.remove(&key) {
+                self.used_bytes -= entry.bytes;
+            }
+        }
+    }
+}
+
+struct LruCacheEntry {
+    value: Box<dyn Any + Send + Sync>,
+    bytes: usize,
+    last_used: u64,
+}
+
 /// A trait for objects that can generate a unique cache key.
 pub trait CacheKey {
     /// Returns the cache key for this object.
@@ -2,7 +2,6 @@
 
 use crate::cache::{Cache, CacheKey};
 use crate::function::Function;
-use hayro_syntax::object;
 use hayro_syntax::object::Array;
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Name;
@@ -22,6 +21,55 @@ use std::sync::{Arc, LazyLock, OnceLock};
 /// A storage for the components of colors.
 pub type ColorComponents = SmallVec<[f32; 4]>;
 
+/// A rendering intent, which determines how colors in CIE-based color spaces
+/// are mapped to the colors of the output device.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RenderingIntent {
+    /// Reproduce the colors exactly, without adjusting them to the white point
+    /// of the output device.
+    AbsoluteColorimetric,
+    /// Reproduce the colors exactly, relative to the white point of the output
+    /// device.
+    #[default]
+    RelativeColorimetric,
+    /// Preserve the saturation of the colors.
+    Saturation,
+    /// Preserve the visual relationships between the colors.
+    Perceptual,
+}
+
+impl RenderingIntent {
+    /// Create a rendering intent from its name.
+    ///
+    /// Unknown names fall back to relative colorimetric, as required by the
+    /// specification.
+    pub(crate) fn from_name(name: &[u8]) -> Self {
+        match name {
+            b"AbsoluteColorimetric" => Self::AbsoluteColorimetric,
+            b"Saturation" => Self::Saturation,
+            b"Perceptual" => Self::Perceptual,
+            _ => Self::RelativeColorimetric,
+        }
+    }
+
+    fn to_moxcms(self) -> moxcms::RenderingIntent {
+        match self {
+            Self::AbsoluteColorimetric => moxcms::RenderingIntent::AbsoluteColorimetric,
+            Self::RelativeColorimetric => moxcms::RenderingIntent::RelativeColorimetric,
+            Self::Saturation => moxcms::RenderingIntent::Saturation,
+            Self::Perceptual => moxcms::RenderingIntent::Perceptual,
+        }
+    }
+
+    fn transform_options(self) -> TransformOptions {
+        TransformOptions {
+            rendering_intent: self.to_moxcms(),
+            ..TransformOptions::default()
+        }
+    }
+}
+
 /// An RGB color with an alpha channel.
 #[derive(Debug, Copy, Clone)]
 pub struct AlphaColor {
@@ -90,7 +138,7 @@ pub(crate) enum ColorSpaceType {
     DeviceCmyk,
     DeviceGray,
     DeviceRgb,
-    Pattern(ColorSpace),
+    Pattern(Option<ColorSpace>),
     Indexed(Indexed),
     ICCBased(ICCProfile),
     CalGray(CalGray),
@@ -173,11 +221,9 @@ impl ColorSpaceType {
                     return Some(Self::DeviceN(DeviceN::new(&color_array, cache)?));
                 }
                 PATTERN => {
-                    let _ = iter.next::<Name<'_>>();
                     let cs = iter
                         .next::<Object<'_>>()
-                        .and_then(|o| ColorSpace::new(o, cache))
-                        .unwrap_or(ColorSpace::device_rgb());
+                        .and_then(|o| ColorSpace::new(o, cache));
                     return Some(Self::Pattern(cs));
                 }
                 _ => {
@@ -196,67 +242,144 @@ impl ColorSpaceType {
             DEVICE_GRAY | G => Some(Self::DeviceGray),
             DEVICE_CMYK | CMYK => Some(Self::DeviceCmyk),
             CALCMYK => Some(Self::DeviceCmyk),
-            PATTERN => Some(Self::Pattern(ColorSpace::device_rgb())),
+            PATTERN => Some(Self::Pattern(None)),
             _ => None,
         }
     }
 }
 
+#[derive(Debug)]
+struct ColorSpaceRepr {
+    color_space: ColorSpaceType,
+    /// The rendering intent of the ICC-based conversions of the color space.
+    intent: RenderingIntent,
+    /// The versions of the color space with a specific rendering intent, created on
+    /// demand, or `None` if the rendering intent doesn't affect the color space.
+    intents: [OnceLock<Option<ColorSpace>>; 4],
+}
+
 /// A PDF color space.
 #[derive(Debug, Clone)]
-pub struct ColorSpace(Arc<ColorSpaceType>);
+pub struct ColorSpace(Arc<ColorSpaceRepr>);
 
 impl ColorSpace {
+    fn from_type(color_space: ColorSpaceType, intent: RenderingIntent) -> Self {
+        Self(Arc::new(ColorSpaceRepr {
+            color_space,
+            intent,
+            intents: Default::default(),
+        }))
+    }
+
     /// Create a new color space from the given object.
     pub(crate) fn new(object: Object<'_>, cache: &Cache) -> Option<Self> {
-        Some(Self(Arc::new(ColorSpaceType::new(object, cache)?)))
+        Some(Self::from_type(
+            ColorSpaceType::new(object, cache)?,
+            RenderingIntent::default(),
+        ))
     }
 
     /// Create a new color space from the name.
     pub(crate) fn new_from_name(name: &Name<'_>) -> Option<Self> {
-        ColorSpaceType::new_from_name(name).map(|c| Self(Arc::new(c)))
+        ColorSpaceType::new_from_name(name).map(|c| Self::from_type(c, RenderingIntent::default()))
     }
 
     /// Return the device gray color space.
     pub(crate) fn device_gray() -> Self {
-        Self(Arc::new(ColorSpaceType::DeviceGray))
+        Self::from_type(ColorSpaceType::DeviceGray, RenderingIntent::default())
     }
 
     /// Return the device RGB color space.
     pub(crate) fn device_rgb() -> Self {
-        Self(Arc::new(ColorSpaceType::DeviceRgb))
+        Self::from_type(ColorSpaceType::DeviceRgb, RenderingIntent::default())
     }
 
     /// Return the device CMYK color space.
     pub(crate) fn device_cmyk() -> Self {
-        Self(Arc::new(ColorSpaceType::DeviceCmyk))
+        Self::from_type(ColorSpaceType::DeviceCmyk, RenderingIntent::default())
     }
 
     /// Return the pattern color space.
     pub(crate) fn pattern() -> Self {
-        Self(Arc::new(ColorSpaceType::Pattern(Self::device_gray())))
+        Self::from_type(ColorSpaceType::Pattern(None), RenderingIntent::default())
     }
 
+    fn color_space_type(&self) -> &ColorSpaceType {
+        &self.0.color_space
+    }
+
+    /// Return the underlying color space of the pattern color space, if
+    /// one was specified.
     pub(crate) fn pattern_cs(&self) -> Option<Self> {
-        match self.0.as_ref() {
-            ColorSpaceType::Pattern(cs) => Some(cs.clone()),
+        match self.color_space_type() {
+            ColorSpaceType::Pattern(cs) => cs.clone(),
             _ => None,
         }
     }
 
     /// Return `true` if the current color space is the pattern color space.
     pub(crate) fn is_pattern(&self) -> bool {
-        matches!(self.0.as_ref(), ColorSpaceType::Pattern(_))
+        matches!(self.color_space_type(), ColorSpaceType::Pattern(_))
     }
 
     /// Return `true` if the current color space is an indexed color space.
     pub(crate) fn is_indexed(&self) -> bool {
-        matches!(self.0.as_ref(), ColorSpaceType::Indexed(_))
+        matches!(self.color_space_type(), ColorSpaceType::Indexed(_))
+    }
+
+    /// Return a version of the color space whose ICC-based conversions use the given
+    /// rendering intent.
+    ///
+    /// Device color spaces and color spaces that are converted without ICC profiles
+    /// are returned unchanged. The converted color spaces are cached, so this is cheap
+    /// to call repeatedly.
+    pub(crate) fn with_rendering_intent(&self, intent: RenderingIntent) -> Self {
+        if intent == self.0.intent {
+            return self.clone();
+        }
+
+        self.0.intents[intent as usize]
+            .get_or_init(|| self.convert_rendering_intent(intent))
+            .clone()
+            .unwrap_or_else(|| self.clone())
+    }
+
+    fn convert_rendering_intent(&self, intent: RenderingIntent) -> Option<Self> {
+        let converted = match self.color_space_type() {
+            ColorSpaceType::ICCBased(icc) => {
+                ColorSpaceType::ICCBased(icc.with_rendering_intent(intent))
+            }
+            ColorSpaceType::Lab(lab) => ColorSpaceType::Lab(Lab {
+                range: lab.range,
+                profile: lab.profile.with_rendering_intent(intent),
+            }),
+            ColorSpaceType::Indexed(indexed) => ColorSpaceType::Indexed(Indexed {
+                base: Box::new(indexed.base.with_rendering_intent(intent)),
+                ..indexed.clone()
+            }),
+            ColorSpaceType::Separation(separation) => ColorSpaceType::Separation(Separation {
+                alternate_space: separation.alternate_space.with_rendering_intent(intent),
+                ..separation.clone()
+            }),
+            ColorSpaceType::DeviceN(device_n) => ColorSpaceType::DeviceN(DeviceN {
+                tint_transform: device_n
+                    .tint_transform
+                    .as_ref()
+                    .map(|(cs, f)| (cs.with_rendering_intent(intent), f.clone())),
+                ..device_n.clone()
+            }),
+            ColorSpaceType::Pattern(Some(cs)) => {
+                ColorSpaceType::Pattern(Some(cs.with_rendering_intent(intent)))
+            }
+            _ => return None,
+        };
+
+        Some(Self::from_type(converted, intent))
     }
 
     /// Get the default decode array for the color space.
     pub(crate) fn default_decode_arr(&self, n: f32) -> SmallVec<[(f32, f32); 4]> {
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::DeviceCmyk => smallvec![(0.0, 1.0), (0.0, 1.0), (0.0, 1.0), (0.0, 1.0)],
             ColorSpaceType::DeviceGray => smallvec![(0.0, 1.0)],
             ColorSpaceType::DeviceRgb => smallvec![(0.0, 1.0), (0.0, 1.0), (0.0, 1.0)],
@@ -285,7 +408,7 @@ impl ColorSpace {
 
     /// Get the initial color of the color space.
     pub(crate) fn initial_color(&self) -> ColorComponents {
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::DeviceCmyk => smallvec![0.0, 0.0, 0.0, 1.0],
             ColorSpaceType::DeviceGray => smallvec![0.0],
             ColorSpaceType::DeviceRgb => smallvec![0.0, 0.0, 0.0],
@@ -300,22 +423,74 @@ impl ColorSpace {
             ColorSpaceType::Lab(_) => smallvec![0.0, 0.0, 0.0],
             ColorSpaceType::Indexed(_) => smallvec![0.0],
             ColorSpaceType::Separation(_) => smallvec![1.0],
-            ColorSpaceType::Pattern(c) => c.initial_color(),
+            ColorSpaceType::Pattern(Some(c)) => c.initial_color(),
+            ColorSpaceType::Pattern(None) => smallvec![0.0],
             ColorSpaceType::DeviceN(d) => smallvec![1.0; d.num_components as usize],
         }
     }
 
+    /// Return the name of the color space family, like `DeviceRGB` or `ICCBased`.
+    pub fn family(&self) -> &'static str {
+        match self.color_space_type() {
+            ColorSpaceType::DeviceCmyk => "DeviceCMYK",
+            ColorSpaceType::DeviceGray => "DeviceGray",
+            ColorSpaceType::DeviceRgb => "DeviceRGB",
+            ColorSpaceType::Pattern(_) => "Pattern",
+            ColorSpaceType::Indexed(_) => "Indexed",
+            ColorSpaceType::ICCBased(_) => "ICCBased",
+            ColorSpaceType::CalGray(_) => "CalGray",
+            ColorSpaceType::CalRgb(_) => "CalRGB",
+            ColorSpaceType::Lab(_) => "Lab",
+            ColorSpaceType::Separation(_) => "Separation",
+            ColorSpaceType::DeviceN(_) => "DeviceN",
+        }
+    }
+
     pub(crate) fn is_device_gray(&self) -> bool {
-        matches!(self.0.as_ref(), ColorSpaceType::DeviceGray)
+        matches!(self.color_space_type(), ColorSpaceType::DeviceGray)
     }
 
     pub(crate) fn is_device_rgb(&self) -> bool {
-        matches!(self.0.as_ref(), ColorSpaceType::DeviceRgb)
+        matches!(self.color_space_type(), ColorSpaceType::DeviceRgb)
+    }
+
+    pub(crate) fn is_device_cmyk(&self) -> bool {
+        matches!(self.color_space_type(), ColorSpaceType::DeviceCmyk)
+    }
+
+    /// Return the names of the colorants for `Separation` and `DeviceN` color spaces.
+    ///
+    /// The components of colors in these color spaces are the tint values of
+    /// the colorants, in the same order.
+    pub fn colorants(&self) -> Option<&[String]> {
+        match self.color_space_type() {
+            ColorSpaceType::Separation(s) => Some(&s.colorant),
+            ColorSpaceType::DeviceN(d) => Some(&d.colorants),
+            _ => None,
+        }
+    }
+
+    /// Return `true` if the color space is `DeviceCMYK` or an ICC-based CMYK color space.
+    pub(crate) fn is_cmyk(&self) -> bool {
+        match self.color_space_type() {
+            ColorSpaceType::DeviceCmyk => true,
+            ColorSpaceType::ICCBased(icc) => icc.0.number_components == 4,
+            _ => false,
+        }
+    }
+
+    /// Return `true` if the color space paints individual colorants, i.e. is a
+    /// `Separation` or `DeviceN` color space.
+    pub(crate) fn is_separation(&self) -> bool {
+        matches!(
+            self.color_space_type(),
+            ColorSpaceType::Separation(_) | ColorSpaceType::DeviceN(_)
+        )
     }
 
     /// Get the number of components of the color space.
-    pub(crate) fn num_components(&self) -> u8 {
-        match self.0.as_ref() {
+    pub fn num_components(&self) -> u8 {
+        match self.color_space_type() {
             ColorSpaceType::DeviceCmyk => 4,
             ColorSpaceType::DeviceGray => 1,
             ColorSpaceType::DeviceRgb => 3,
@@ -325,7 +500,8 @@ impl ColorSpace {
             ColorSpaceType::Lab(_) => 3,
             ColorSpaceType::Indexed(_) => 1,
             ColorSpaceType::Separation(_) => 1,
-            ColorSpaceType::Pattern(p) => p.num_components(),
+            ColorSpaceType::Pattern(Some(p)) => p.num_components(),
+            ColorSpaceType::Pattern(None) => 1,
             ColorSpaceType::DeviceN(d) => d.num_components,
         }
     }
@@ -335,7 +511,7 @@ impl ColorSpace {
     pub fn to_rgba(&self, c: &[f32], opacity: f32, manual_scale: bool) -> AlphaColor {
         let alpha = f32_to_u8(opacity);
 
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::DeviceGray => {
                 let gray = c.first().copied().map(f32_to_u8).unwrap_or(0);
                 AlphaColor::from_rgba8(gray, gray, gray, alpha)
@@ -370,7 +546,7 @@ impl ColorSpace {
 
 impl ToRgb for ColorSpace {
     fn convert_f32(&self, input: &[f32], output: &mut [u8], manual_scale: bool) -> Option<()> {
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::DeviceCmyk => {
                 if input.len() == 4 {
                     let converted = [
@@ -400,7 +576,8 @@ impl ToRgb for ColorSpace {
 
                 Some(())
             }
-            ColorSpaceType::Pattern(i) => i.convert_f32(input, output, manual_scale),
+            ColorSpaceType::Pattern(Some(i)) => i.convert_f32(input, output, manual_scale),
+            ColorSpaceType::Pattern(None) => None,
             ColorSpaceType::Indexed(i) => i.convert_f32(input, output, manual_scale),
             ColorSpaceType::ICCBased(i) => i.convert_f32(input, output, manual_scale),
             ColorSpaceType::CalGray(i) => i.convert_f32(input, output, manual_scale),
@@ -412,11 +589,12 @@ impl ToRgb for ColorSpace {
     }
 
     fn supports_u8(&self) -> bool {
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::DeviceCmyk => true,
             ColorSpaceType::DeviceGray => true,
             ColorSpaceType::DeviceRgb => true,
-            ColorSpaceType::Pattern(i) => i.supports_u8(),
+            ColorSpaceType::Pattern(Some(i)) => i.supports_u8(),
+            ColorSpaceType::Pattern(None) => false,
             ColorSpaceType::Indexed(i) => i.supports_u8(),
             ColorSpaceType::ICCBased(i) => i.supports_u8(),
             ColorSpaceType::CalGray(i) => i.supports_u8(),
@@ -429,7 +607,7 @@ impl ToRgb for ColorSpace {
 
     #[inline]
     fn convert_u8(&self, input: &[u8], output: &mut [u8]) -> Option<()> {
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::DeviceCmyk => CMYK_TRANSFORM.convert_u8(input, output),
             ColorSpaceType::DeviceGray => {
                 for (input, output) in input.iter().zip(output.chunks_exact_mut(3)) {
@@ -445,7 +623,8 @@ impl ToRgb for ColorSpace {
 
                 Some(())
             }
-            ColorSpaceType::Pattern(i) => i.convert_u8(input, output),
+            ColorSpaceType::Pattern(Some(i)) => i.convert_u8(input, output),
+            ColorSpaceType::Pattern(None) => None,
             ColorSpaceType::Indexed(i) => i.convert_u8(input, output),
             ColorSpaceType::ICCBased(i) => i.convert_u8(input, output),
             ColorSpaceType::CalGray(i) => i.convert_u8(input, output),
@@ -457,7 +636,7 @@ impl ToRgb for ColorSpace {
     }
 
     fn is_none(&self) -> bool {
-        match self.0.as_ref() {
+        match self.color_space_type() {
             ColorSpaceType::Separation(s) => s.is_none(),
             ColorSpaceType::DeviceN(d) => d.is_none(),
             _ => false,
@@ -710,11 +889,14 @@ impl Lab {
         );
 
         let profile = ICCProfile::new_from_src_profile(
-            profile, false,
+            profile,
+            false,
             // This flag is only used to scale the values to [0.0, 1.0], but
             // we already take care of this in the `convert_f32` method.
             // Therefore, leave this as false, even though this is a LAB profile.
-            false, 3,
+            false,
+            3,
+            RenderingIntent::default(),
         )?;
 
         Some(Self { range, profile })
@@ -761,29 +943,38 @@ impl Indexed {
         let hival = iter.next::<u32>()?.min(u8::MAX as u32) as u8;
 
         let values = {
-            let data = iter
-                .next::<Stream<'_>>()
-                .and_then(|s| s.decoded().ok())
-                .or_else(|| {
-                    iter.next::<object::String<'_>>()
-                        .map(|s| Cow::Owned(s.to_vec()))
-                })?;
+            // The lookup table can be a stream or a string, which might also be stored
+            // in an indirect object.
+            let data = match iter.next::<Object<'_>>()? {
+                Object::Stream(s) => s.decoded().ok()?,
+                Object::String(s) => Cow::Owned(s.to_vec()),
+                _ => return None,
+            };
 
-            let num_components = base_color_space.num_components();
+            // The lookup table contains bytes that are mapped to the range of the
+            // corresponding component in the base color space.
+            let ranges = base_color_space.default_decode_arr(8.0);
 
-            let mut byte_iter = data.iter().copied();
+            // Like other viewers, treat missing entries at the end of a truncated lookup
+            // table as zero.
+            let mut byte_iter = data.iter().copied().chain(std::iter::repeat(0));
 
             let mut vals = vec![];
             for _ in 0..=hival {
                 let mut temp = vec![];
 
-                for _ in 0..num_components {
-                    temp.push(byte_iter.next()? as f32 / 255.0);
+                for (min, max) in ranges.iter().copied() {
+                    let byte = byte_iter.next()?;
+                    temp.push(min + byte as f32 * (max - min) / 255.0);
                 }
 
                 vals.push(temp);
             }
 
+            if data.len() < vals.len() * ranges.len() {
+                warn!("lookup table of indexed color space is too short");
+            }
+
             vals
         };
 
@@ -808,7 +999,7 @@ impl ToRgb for Indexed {
             output.copy_from_slice(&self.values[idx]);
         }
 
-        self.base.convert_f32(&indexed, output, true)
+        self.base.convert_f32(&indexed, output, false)
     }
 }
 
@@ -816,6 +1007,8 @@ impl ToRgb for Indexed {
 pub(crate) struct Separation {
     alternate_space: ColorSpace,
     tint_transform: Function,
+    /// The name of the colorant.
+    colorant: [String; 1],
     is_none_separation: bool,
 }
 
@@ -834,6 +1027,7 @@ impl Separation {
         Some(Self {
             alternate_space,
             tint_transform,
+            colorant: [name.as_str().to_string()],
             is_none_separation,
         })
     }
@@ -859,9 +1053,13 @@ impl ToRgb for Separation {
 
 #[derive(Debug, Clone)]
 pub(crate) struct DeviceN {
-    alternate_space: ColorSpace,
     num_components: u8,
-    tint_transform: Function,
+    /// The names of the colorants of the components.
+    colorants: Vec<String>,
+    /// The alternate color space and the tint transform into it.
+    tint_transform: Option<(ColorSpace, Function)>,
+    /// The colorants of the components, used if the tint transform is unusable.
+    colorants_fallback: Option<Colorants>,
     is_none: bool,
 }
 
@@ -877,17 +1075,35 @@ impl DeviceN {
             .collect::<Vec<_>>();
         let num_components = u8::try_from(names.len()).ok()?;
         let all_none = names.iter().all(|n| n.as_str() == "None");
-        let alternate_space = ColorSpace::new(iter.next::<Object<'_>>()?, cache)?;
-        let tint_transform = Function::new(&iter.next::<Object<'_>>()?)?;
+        let alternate_space = iter
+            .next::<Object<'_>>()
+            .and_then(|o| ColorSpace::new(o, cache));
+        let tint_transform = iter.next::<Object<'_>>().and_then(|o| Function::new(&o));
+        let attributes = iter.next::<Dict<'_>>();
 
         if num_components == 0 {
             return None;
         }
 
+        let tint_transform = alternate_space.zip(tint_transform);
+
+        let colorants = if tint_transform.is_none() {
+            warn!("DeviceN color space has an invalid tint transform");
+
+            Colorants::new(&names, attributes.as_ref(), cache)
+        } else {
+            None
+        };
+
+        if tint_transform.is_none() && colorants.is_none() {
+            return None;
+        }
+
         Some(Self {
-            alternate_space,
             num_components,
+            colorants: names.iter().map(|n| n.as_str().to_string()).collect(),
             tint_transform,
+            colorants_fallback: colorants,
             is_none: all_none,
         })
     }
@@ -895,15 +1111,26 @@ impl DeviceN {
 
 impl ToRgb for DeviceN {
     fn convert_f32(&self, input: &[f32], output: &mut [u8], _: bool) -> Option<()> {
-        let evaluated = input
-            .chunks_exact(self.num_components as usize)
-            .flat_map(|n| {
-                self.tint_transform
-                    .eval(n.to_smallvec())
-                    .unwrap_or(self.alternate_space.initial_color())
-            })
-            .collect::<Vec<_>>();
-        self.alternate_space.convert_f32(&evaluated, output, false)
+        let inputs = input.chunks_exact(self.num_components as usize);
+
+        if let Some((alternate_space, tint_transform)) = &self.tint_transform {
+            let evaluated = inputs
+                .flat_map(|n| {
+                    tint_transform
+                        .eval(n.to_smallvec())
+                        .unwrap_or(alternate_space.initial_color())
+                })
+                .collect::<Vec<_>>();
+            alternate_space.convert_f32(&evaluated, output, false)
+        } else {
+            let colorants = self.colorants_fallback.as_ref()?;
+
+            for (input, output) in inputs.zip(output.chunks_exact_mut(3)) {
+                output.copy_from_slice(&colorants.convert(input)?);
+            }
+
+            Some(())
+        }
     }
 
     fn is_none(&self) -> bool {
@@ -911,14 +1138,141 @@ impl ToRgb for DeviceN {
     }
 }
 
+/// The colorants of a `DeviceN` color space.
+///
+/// The attributes of a `DeviceN` color space, in particular those of `NChannel` color
+/// spaces, describe the process and spot colorants that make up the individual
+/// components. This allows approximating the color if the tint transform is
+/// unusable, by mixing the process color with the color of each spot colorant.
+#[derive(Debug, Clone)]
+struct Colorants {
+    /// The color space of the process colorants.
+    process: Option<ColorSpace>,
+    /// The colorant of each component.
+    components: Vec<Colorant>,
+}
+
+#[derive(Debug, Clone)]
+enum Colorant {
+    /// A component of the process color space.
+    Process(usize),
+    /// A spot colorant, with the separation color space describing it.
+    Spot(ColorSpace),
+    /// A colorant whose appearance is unknown.
+    Unknown,
+}
+
+impl Colorants {
+    fn new(names: &[Name<'_>], attributes: Option<&Dict<'_>>, cache: &Cache) -> Option<Self> {
+        const CMYK_COMPONENTS: [&str; 4] = ["Cyan", "Magenta", "Yellow", "Black"];
+
+        let process_dict = attributes.and_then(|a| a.get::<Dict<'_>>(PROCESS));
+        let spots = attributes.and_then(|a| a.get::<Dict<'_>>(COLORANTS));
+
+        let (process, process_names) = match &process_dict {
+            Some(dict) => (
+                dict.get::<Object<'_>>(COLORSPACE)
+                    .and_then(|o| ColorSpace::new(o, cache)),
+                dict.get::<Array<'_>>(COMPONENTS)
+                    .map(|a| {
+                        a.iter::<Name<'_>>()
+                            .map(|n| n.as_str().to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            // Without explicit process colorants, the CMYK colorants still have a
+            // well-defined meaning.
+            None => (
+                Some(ColorSpace::device_cmyk()),
+                CMYK_COMPONENTS.map(|n| n.to_string()).to_vec(),
+            ),
+        };
+
+        let components = names
+            .iter()
+            .map(|name| {
+                if let Some(index) = process_names
+                    .iter()
+                    .position(|p| p == name.as_str())
+                    .filter(|_| process.is_some())
+                {
+                    Colorant::Process(index)
+                } else if let Some(spot) = spots
+                    .as_ref()
+                    .and_then(|s| s.get::<Object<'_>>(name.deref()))
+                    .and_then(|o| ColorSpace::new(o, cache))
+                    .filter(|cs| cs.num_components() == 1)
+                {
+                    Colorant::Spot(spot)
+                } else {
+                    Colorant::Unknown
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if components.iter().all(|c| matches!(c, Colorant::Unknown)) {
+            return None;
+        }
+
+        Some(Self {
+            process,
+            components,
+        })
+    }
+
+    fn convert(&self, input: &[f32]) -> Option<[u8; 3]> {
+        let mut rgb = [255.0_f32; 3];
+
+        if let Some(process) = &self.process {
+            // Absent process colorants don't contribute anything, which is a value of
+            // zero for subtractive and one for additive color spaces.
+            let num_components = process.num_components() as usize;
+            let absent = if num_components == 4 { 0.0 } else { 1.0 };
+            let mut values = vec![absent; num_components];
+
+            for (colorant, value) in self.components.iter().zip(input) {
+                if let Colorant::Process(index) = colorant
+                    && let Some(v) = values.get_mut(*index)
+                {
+                    *v = *value;
+                }
+            }
+
+            let mut converted = [0; 3];
+            process.convert_f32(&values, &mut converted, false)?;
+            rgb = converted.map(|c| c as f32);
+        }
+
+        // Spot colorants are approximated by multiplying their colors, similarly
+        // to how inks are overprinted.
+        for (colorant, value) in self.components.iter().zip(input) {
+            if let Colorant::Spot(spot) = colorant {
+                let mut converted = [0; 3];
+                spot.convert_f32(&[*value], &mut converted, false)?;
+
+                for (rgb, c) in rgb.iter_mut().zip(converted) {
+                    *rgb *= c as f32 / 255.0;
+                }
+            }
+        }
+
+        Some(rgb.map(|c| (c + 0.5) as u8))
+    }
+}
+
 struct ICCColorRepr {
     src_profile: ColorProfile,
     src_layout: Layout,
     number_components: usize,
     is_srgb: bool,
     is_lab: bool,
+    intent: RenderingIntent,
+    options: TransformOptions,
     transform_u8: Arc<Transform8BitExecutor>,
     transform_f32: OnceLock<Arc<TransformF32Executor>>,
+    /// The versions of the profile with a specific rendering intent, created on demand.
+    intents: [OnceLock<Option<ICCProfile>>; 4],
 }
 
 #[derive(Clone)]
@@ -942,7 +1296,13 @@ impl ICCProfile {
             .unwrap_or(false);
         let is_lab = src_profile.color_space == DataColorSpace::Lab;
 
-        Self::new_from_src_profile(src_profile, is_srgb, is_lab, number_components)
+        Self::new_from_src_profile(
+            src_profile,
+            is_srgb,
+            is_lab,
+            number_components,
+            RenderingIntent::default(),
+        )
     }
 
     fn new_from_src_profile(
@@ -950,6 +1310,7 @@ impl ICCProfile {
         is_srgb: bool,
         is_lab: bool,
         number_components: usize,
+        intent: RenderingIntent,
     ) -> Option<Self> {
         let src_layout = match number_components {
             1 => Layout::Gray,
@@ -963,15 +1324,20 @@ impl ICCProfile {
         };
 
         let dest_profile = ColorProfile::new_srgb();
-        let transform_u8 = src_profile
-            .clone()
-            .create_transform_8bit(
-                src_layout,
-                &dest_profile,
-                Layout::Rgb,
-                TransformOptions::default(),
-            )
-            .ok()?;
+        // Profiles that don't have a table for the intent are used with their
+        // perceptual table instead, as required by the ICC specification.
+        let (options, transform_u8) =
+            [intent, RenderingIntent::Perceptual]
+                .into_iter()
+                .find_map(|intent| {
+                    let options = intent.transform_options();
+                    let transform = src_profile
+                        .clone()
+                        .create_transform_8bit(src_layout, &dest_profile, Layout::Rgb, options)
+                        .ok()?;
+
+                    Some((options, transform))
+                })?;
 
         Some(Self(Arc::new(ICCColorRepr {
             src_profile,
@@ -979,11 +1345,36 @@ impl ICCProfile {
             number_components,
             is_srgb,
             is_lab,
+            intent,
+            options,
             transform_u8,
             transform_f32: OnceLock::new(),
+            intents: Default::default(),
         })))
     }
 
+    /// Return a version of the profile that uses the given rendering intent.
+    ///
+    /// If the profile doesn't support the intent, the profile is returned unchanged.
+    fn with_rendering_intent(&self, intent: RenderingIntent) -> Self {
+        if intent == self.0.intent {
+            return self.clone();
+        }
+
+        self.0.intents[intent as usize]
+            .get_or_init(|| {
+                Self::new_from_src_profile(
+                    self.0.src_profile.clone(),
+                    self.0.is_srgb,
+                    self.0.is_lab,
+                    self.0.number_components,
+                    intent,
+                )
+            })
+            .clone()
+            .unwrap_or_else(|| self.clone())
+    }
+
     fn is_srgb(&self) -> bool {
         self.0.is_srgb
     }
@@ -1009,7 +1400,7 @@ impl ICCProfile {
                     self.0.src_layout,
                     &dest_profile,
                     Layout::Rgb,
-                    TransformOptions::default(),
+                    self.0.options,
                 )
                 // Since the u8 version was valid, hopefully this should never panic?
                 .unwrap()
@@ -1071,6 +1462,7 @@ pub struct Color {
     color_space: ColorSpace,
     components: ColorComponents,
     opacity: f32,
+    rendering_intent: RenderingIntent,
 }
 
 impl Color {
@@ -1079,9 +1471,44 @@ impl Color {
             color_space,
             components,
             opacity,
+            rendering_intent: RenderingIntent::default(),
         }
     }
 
+    /// Use the given rendering intent when converting the color.
+    ///
+    /// If no intent is given, the default conversion is used.
+    pub(crate) fn with_rendering_intent(mut self, intent: Option<RenderingIntent>) -> Self {
+        if let Some(intent) = intent {
+            self.color_space = self.color_space.with_rendering_intent(intent);
+            self.rendering_intent = intent;
+        }
+
+        self
+    }
+
+    /// Return the color space of the color.
+    pub fn color_space(&self) -> &ColorSpace {
+        &self.color_space
+    }
+
+    /// Return the components of the color in its color space.
+    ///
+    /// For `Separation` and `DeviceN` color spaces, these are the tint values of the
+    /// colorants returned by [`ColorSpace::colorants`], which can for example be used
+    /// to compute the ink coverage of a page.
+    pub fn components(&self) -> &[f32] {
+        &self.components
+    }
+
+    /// Return the rendering intent that was active when the color was set.
+    ///
+    /// This allows color-managed devices to do their own conversion. The RGBA
+    /// values returned by [`Color::to_rgba`] already take it into account.
+    pub fn rendering_intent(&self) -> RenderingIntent {
+        self.rendering_intent
+    }
+
     /// Return the color as an RGBA color.
     #[inline]
     pub fn to_rgba(&self) -> AlphaColor {
@@ -1097,6 +1524,7 @@ impl Color {
             color_space: ColorSpace::device_rgb(),
             components: smallvec![c[0], c[1], c[2]],
             opacity: c[3],
+            rendering_intent: RenderingIntent::default(),
         }
     }
 }
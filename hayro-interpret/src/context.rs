@@ -1,33 +1,42 @@
-use crate::cache::{Cache, CacheKey};
+use crate::cache::{Cache, CacheKey, FormCache, ImageCache, SharedCache};
 use crate::color::{Color, ColorSpace};
 use crate::convert::convert_transform;
-use crate::font::{Font, StandardFont};
-use crate::interpret::state::{ClipType, State, TextStateFont};
+use crate::font::{Font, GlyphCache, StandardFont};
+use crate::interpret::state::{ActiveTransferFunction, ClipType, State, TextStateFont};
 use crate::ocg::OcgState;
-use crate::util::{BezPathExt, Float64Ext};
-use crate::{ClipPath, Device, DrawProps, FillRule, InterpreterSettings, Paint, StrokeProps};
+use crate::stats::{InterpreterStats, Phase, StatsCollector};
+use crate::util::{BezPathExt, Float64Ext, now};
+use crate::{
+    BlendMode, ClipPath, CorruptFontPolicy, Device, DrawProps, FillRule, InterpreterSettings,
+    InterpreterWarning, Paint, StrokeProps, ZeroWidthLinePolicy,
+};
 use hayro_syntax::content::ops::Transform;
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Name;
+use hayro_syntax::object::ObjectIdentifier;
+use hayro_syntax::object::dict::keys::{DEFAULT_CMYK, DEFAULT_GRAY, DEFAULT_RGB};
 use hayro_syntax::page::Resources;
 use hayro_syntax::xref::XRef;
 use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape};
 use rustc_hash::FxHashMap;
 use smallvec::smallvec;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-
-/// Maximum nesting depth for interpreting `XObject`'s/patterns/streams.
-pub(crate) const MAX_NESTED_INTERPRETATION_DEPTH: u32 = 50;
+use std::time::Instant;
 
 /// A cache used by the interpreter.
 ///
 /// Ideally, such a cache should be constructed once per PDF and then reused across
-/// multiple interpreter invocations on the same document.
+/// multiple interpreter invocations on the same document. It can't be sent to other
+/// threads, but the parts of it that are thread-safe can be shared with
+/// [`SharedCache`].
 #[derive(Clone)]
 pub struct InterpreterCache<'a> {
     pub(crate) font_cache: Rc<RefCell<FxHashMap<u128, Option<Font<'a>>>>>,
     pub(crate) object_cache: Cache,
+    pub(crate) glyph_cache: GlyphCache,
+    pub(crate) image_cache: ImageCache,
+    pub(crate) form_cache: FormCache,
 }
 
 impl<'a> Default for InterpreterCache<'a> {
@@ -39,11 +48,120 @@ impl<'a> Default for InterpreterCache<'a> {
 impl<'a> InterpreterCache<'a> {
     /// Create a new interpreter cache.
     pub fn new() -> Self {
+        Self::with_glyph_cache(GlyphCache::default())
+    }
+
+    /// Create a new interpreter cache that uses the given cache for glyph outlines.
+    ///
+    /// Use this to configure the memory limit of the glyph cache.
+    pub fn with_glyph_cache(glyph_cache: GlyphCache) -> Self {
+        Self::with_shared_cache(SharedCache::with_glyph_cache(glyph_cache))
+    }
+
+    /// Create a new interpreter cache that stores everything that is thread-safe in
+    /// the given shared cache.
+    ///
+    /// Use this to share work between threads that render pages of the same document.
+    pub fn with_shared_cache(shared: SharedCache) -> Self {
         Self {
             font_cache: Rc::new(RefCell::new(FxHashMap::default())),
-            object_cache: Cache::new(),
+            object_cache: shared.object_cache,
+            glyph_cache: shared.glyph_cache,
+            image_cache: shared.image_cache,
+            form_cache: shared.form_cache,
+        }
+    }
+
+    /// Return the part of the cache that can be shared with other threads.
+    pub fn shared_cache(&self) -> SharedCache {
+        SharedCache {
+            object_cache: self.object_cache.clone(),
+            glyph_cache: self.glyph_cache.clone(),
+            image_cache: self.image_cache.clone(),
+            form_cache: self.form_cache.clone(),
         }
     }
+
+    /// Return the cache for glyph outlines.
+    pub fn glyph_cache(&self) -> &GlyphCache {
+        &self.glyph_cache
+    }
+
+    /// Return the cache for decoded images.
+    pub fn image_cache(&self) -> &ImageCache {
+        &self.image_cache
+    }
+
+    /// Return the cache for decoded form contents.
+    pub fn form_cache(&self) -> &FormCache {
+        &self.form_cache
+    }
+}
+
+/// The instructions that have been interpreted so far and, if enabled, statistics
+/// about them, shared between a page and all content streams that are interpreted
+/// for it, like the ones of patterns, soft masks and type3 glyphs.
+#[derive(Clone, Default)]
+pub(crate) struct Budget(Rc<BudgetRepr>);
+
+#[derive(Default)]
+struct BudgetRepr {
+    instructions: Cell<u64>,
+    start: Option<Instant>,
+    exhausted: Cell<bool>,
+    stats: Option<StatsCollector>,
+}
+
+impl Budget {
+    fn new(settings: &InterpreterSettings) -> Self {
+        Self(Rc::new(BudgetRepr {
+            // Only query the time if necessary, since it isn't available on all
+            // platforms.
+            start: settings.max_duration.and_then(|_| now()),
+            stats: settings.collect_stats.then(StatsCollector::default),
+            ..Default::default()
+        }))
+    }
+
+    pub(crate) fn stats(&self) -> Option<&StatsCollector> {
+        self.0.stats.as_ref()
+    }
+
+    /// Account for one more instruction and return whether one of the limits
+    /// of the settings has been exceeded.
+    fn consume(&self, settings: &InterpreterSettings) -> bool {
+        let repr = &self.0;
+
+        if repr.exhausted.get() {
+            return true;
+        }
+
+        let instructions = repr.instructions.get() + 1;
+        repr.instructions.set(instructions);
+
+        let exceeds_instructions = settings
+            .max_instructions
+            .is_some_and(|max| instructions > max);
+        let exceeds_duration = settings
+            .max_duration
+            .zip(repr.start)
+            .is_some_and(|(max, start)| start.elapsed() > max);
+
+        if exceeds_instructions || exceeds_duration {
+            warn!("interpreter limits exceeded, stopping interpretation");
+            (settings.warning_sink)(InterpreterWarning::LimitExceeded.into());
+            repr.exhausted.set(true);
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Stop the interpretation of all content streams sharing the budget.
+    fn exhaust(&self) {
+        self.0.exhausted.set(true);
+    }
 }
 
 /// A per-page interpretation context that borrows shared data from an [`InterpreterCache`].
@@ -55,11 +173,16 @@ pub struct Context<'a> {
     clip: Option<FillRule>,
     root_transforms: Vec<Affine>,
     bbox: Vec<Rect>,
+    /// The bounding boxes of the active clip paths, intersected with each other.
+    clip_bounds: Vec<Rect>,
     pub(crate) settings: InterpreterSettings,
     pub(crate) interpreter_cache: InterpreterCache<'a>,
     pub(crate) xref: &'a XRef,
     pub(crate) ocg_state: OcgState,
     nesting_depth: u32,
+    /// The form `XObject`s that are currently being drawn.
+    x_object_stack: Vec<ObjectIdentifier>,
+    pub(crate) budget: Budget,
 }
 
 impl<'a> Context<'a> {
@@ -72,8 +195,9 @@ impl<'a> Context<'a> {
         settings: InterpreterSettings,
     ) -> Self {
         let state = State::new(initial_transform);
+        let budget = Budget::new(&settings);
 
-        Self::new_with(initial_transform, bbox, cache, xref, settings, state, 0)
+        Self::new_with(initial_transform, bbox, cache, xref, settings, state, 0).with_budget(budget)
     }
 
     pub(crate) fn new_with(
@@ -88,7 +212,7 @@ impl<'a> Context<'a> {
         let ocg_state = {
             let root_ref = xref.root_id();
             xref.get::<Dict<'_>>(root_ref)
-                .map(|catalog| OcgState::from_catalog(&catalog))
+                .map(|catalog| OcgState::from_catalog(&catalog, &settings.optional_content))
                 .unwrap_or_default()
         };
 
@@ -101,13 +225,66 @@ impl<'a> Context<'a> {
             sub_path_start: Point::default(),
             clip: None,
             bbox: vec![bbox],
+            clip_bounds: vec![],
             path: BezPath::new(),
             interpreter_cache: cache.clone(),
             ocg_state,
             nesting_depth,
+            x_object_stack: vec![],
+            budget: Budget::default(),
+        }
+    }
+
+    /// Share the given budget with the context, so that the instructions it
+    /// interprets count towards the limits of the parent context.
+    pub(crate) fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Start with the given form `XObject`s being drawn, so that a nested context
+    /// doesn't draw the forms that its parent context is drawing again.
+    pub(crate) fn with_x_object_stack(mut self, x_object_stack: Vec<ObjectIdentifier>) -> Self {
+        self.x_object_stack = x_object_stack;
+        self
+    }
+
+    /// Return the statistics that have been collected so far.
+    ///
+    /// Returns `None` unless [`InterpreterSettings::collect_stats`] is enabled.
+    pub fn stats(&self) -> Option<InterpreterStats> {
+        self.budget.stats().map(StatsCollector::stats)
+    }
+
+    pub(crate) fn stats_collector(&self) -> Option<&StatsCollector> {
+        self.budget.stats()
+    }
+
+    /// Run `f`, attributing the time it takes to the given phase if statistics
+    /// are collected.
+    pub(crate) fn measure<T>(&mut self, phase: Phase, f: impl FnOnce(&mut Self) -> T) -> T {
+        let budget = self.budget.clone();
+
+        match budget.stats() {
+            Some(stats) => stats.measure(phase, || f(self)),
+            None => f(self),
         }
     }
 
+    /// Return whether interpretation should stop, either because it was
+    /// cancelled or because one of the limits was exceeded.
+    ///
+    /// This should be called once for each instruction.
+    pub(crate) fn should_stop(&self) -> bool {
+        (self.settings.is_cancelled)() || self.budget.consume(&self.settings)
+    }
+
+    /// Stop the interpretation of the page, including all content streams that
+    /// are nested in it.
+    pub(crate) fn abort(&self) {
+        self.budget.exhaust();
+    }
+
     pub(crate) fn save_state(&mut self) {
         let Some(cur) = self.states.last().cloned() else {
             warn!("attempted to save state without existing state");
@@ -128,6 +305,28 @@ impl<'a> Context<'a> {
     fn push_bbox(&mut self, bbox: Rect) {
         let new = self.bbox().intersect(bbox);
         self.bbox.push(new);
+
+        let clip_bounds = self
+            .clip_bounds
+            .last()
+            .map_or(bbox, |clip_bounds| clip_bounds.intersect(bbox));
+        self.clip_bounds.push(clip_bounds);
+    }
+
+    /// Return whether drawing something with the given bounds in device space
+    /// would be completely clipped away by the active clip paths.
+    ///
+    /// The bounds are allowed to be a bit too tight, for example because of
+    /// anti-aliasing or the minimum width of hairlines.
+    pub(crate) fn is_clipped(&self, bounds: Rect) -> bool {
+        const MARGIN: f64 = 1.0;
+
+        let Some(clip) = self.clip_bounds.last() else {
+            return false;
+        };
+        let bounds = bounds.inflate(MARGIN, MARGIN);
+
+        bounds.x1 < clip.x0 || bounds.x0 > clip.x1 || bounds.y1 < clip.y0 || bounds.y0 > clip.y1
     }
 
     pub(crate) fn push_clip_path(
@@ -177,6 +376,7 @@ impl<'a> Context<'a> {
 
     fn pop_bbox(&mut self) {
         self.bbox.pop();
+        self.clip_bounds.pop();
     }
 
     pub(crate) fn push_root_transform(&mut self) {
@@ -219,10 +419,53 @@ impl<'a> Context<'a> {
             transform: self.get().ctm,
             paint: self.get_paint(is_stroke),
             soft_mask: self.get().graphics_state.soft_mask.clone(),
-            blend_mode: self.get().graphics_state.blend_mode,
+            blend_mode: self.blend_mode(is_stroke),
+            flatness: self.get().graphics_state.flatness,
+        }
+    }
+
+    /// Return the blend mode for painting, which approximates overprinting with the
+    /// multiply blend mode if enabled.
+    fn blend_mode(&self, is_stroke: bool) -> BlendMode {
+        let gs = &self.get().graphics_state;
+
+        if !self.settings.approximate_overprint || gs.blend_mode != BlendMode::Normal {
+            return gs.blend_mode;
+        }
+
+        let (overprint, color_space) = if is_stroke {
+            (gs.stroke_overprint, &gs.stroke_cs)
+        } else {
+            (gs.non_stroke_overprint, &gs.none_stroke_cs)
+        };
+
+        // Separation and DeviceN colors only paint their own colorants, while CMYK
+        // colors only leave the other colorants untouched for zero components if
+        // the overprint mode is 1.
+        if overprint
+            && (color_space.is_separation() || (color_space.is_cmyk() && gs.overprint_mode == 1))
+        {
+            BlendMode::Multiply
+        } else {
+            gs.blend_mode
         }
     }
 
+    /// Return the smoothness tolerance that should be used for shadings.
+    pub(crate) fn smoothness(&self) -> f32 {
+        let smoothness = self.settings.shading_smoothness;
+
+        self.get()
+            .graphics_state
+            .smoothness
+            .map_or(smoothness, |sm| sm.min(smoothness))
+    }
+
+    /// Return the flatness tolerance that should be used for patches.
+    pub(crate) fn flatness(&self) -> f32 {
+        self.get().graphics_state.flatness.unwrap_or(1.0)
+    }
+
     pub(crate) fn get_paint(&self, is_stroke: bool) -> Paint<'a> {
         let data = if is_stroke {
             self.get().stroke_data()
@@ -236,21 +479,60 @@ impl<'a> Context<'a> {
                     pattern.set_transfer_function(tf.clone());
                 }
 
+                pattern.set_tolerances(self.smoothness(), self.flatness());
                 pattern.pre_concat_transform(self.root_transform());
 
                 Paint::Pattern(Box::new(pattern))
+            } else if let Some(base) = data.color_space.pattern_cs() {
+                // No pattern was selected (for example because `sc` was used
+                // with only numeric operands), so treat the components as a color
+                // in the underlying color space.
+                Self::color_paint(
+                    Color::new(base, data.color, data.alpha)
+                        .with_rendering_intent(data.rendering_intent),
+                    data.transfer_function.as_ref(),
+                )
             } else {
                 // Pattern was likely invalid, use transparent paint.
                 Paint::Color(Color::new(ColorSpace::device_gray(), smallvec![0.0], 0.0))
             }
         } else {
-            let color = Color::new(data.color_space, data.color, data.alpha);
+            Self::color_paint(
+                Color::new(data.color_space, data.color, data.alpha)
+                    .with_rendering_intent(data.rendering_intent),
+                data.transfer_function.as_ref(),
+            )
+        }
+    }
+
+    /// Return `false` if the current color space is a pattern color space without
+    /// an underlying color space, but no pattern has been selected, in which case
+    /// there is nothing to paint with.
+    pub(crate) fn has_paint(&self, is_stroke: bool) -> bool {
+        let gs = &self.get().graphics_state;
+        let (color_space, has_pattern) = if is_stroke {
+            (&gs.stroke_cs, gs.stroke_pattern.is_some())
+        } else {
+            (&gs.none_stroke_cs, gs.non_stroke_pattern.is_some())
+        };
 
-            if let Some(tf) = &data.transfer_function {
-                Paint::Color(Color::from_rgba(tf.apply(&color.to_rgba())))
-            } else {
-                Paint::Color(color)
-            }
+        if color_space.is_pattern() && !has_pattern && color_space.pattern_cs().is_none() {
+            warn!("pattern color space is active, but no pattern was selected");
+
+            false
+        } else {
+            true
+        }
+    }
+
+    fn color_paint(color: Color, transfer_function: Option<&ActiveTransferFunction>) -> Paint<'a> {
+        if let Some(tf) = transfer_function {
+            Paint::Color(
+                Color::from_rgba(tf.apply(&color.to_rgba()))
+                    .with_rendering_intent(Some(color.rendering_intent())),
+            )
+        } else {
+            Paint::Color(color)
         }
     }
 
@@ -315,8 +597,48 @@ impl<'a> Context<'a> {
             })
     }
 
+    /// Return the color space that the given device color space is remapped to by the
+    /// `DefaultGray`, `DefaultRGB` or `DefaultCMYK` color space resources.
+    ///
+    /// Other color spaces, and device color spaces without a valid default, are
+    /// returned unchanged.
+    pub(crate) fn device_color_space(
+        &mut self,
+        resources: &Resources<'_>,
+        color_space: ColorSpace,
+    ) -> ColorSpace {
+        let name = if color_space.is_device_gray() {
+            DEFAULT_GRAY
+        } else if color_space.is_device_rgb() {
+            DEFAULT_RGB
+        } else if color_space.is_device_cmyk() {
+            DEFAULT_CMYK
+        } else {
+            return color_space;
+        };
+
+        match self.get_color_space(resources, &Name::new_unescaped(name)) {
+            Some(default)
+                if !default.is_pattern()
+                    && default.num_components() == color_space.num_components() =>
+            {
+                default
+            }
+            _ => color_space,
+        }
+    }
+
     pub(crate) fn stroke_props(&self) -> StrokeProps {
-        self.get().graphics_state.stroke_props.clone()
+        let mut stroke_props = self.get().graphics_state.stroke_props.clone();
+
+        if stroke_props.line_width == 0.0 {
+            match self.settings.zero_width_lines {
+                ZeroWidthLinePolicy::Hairline => stroke_props.hairline = true,
+                ZeroWidthLinePolicy::MinimumWidth(width) => stroke_props.line_width = width,
+            }
+        }
+
+        stroke_props
     }
 
     pub(crate) fn num_states(&self) -> usize {
@@ -327,21 +649,47 @@ impl<'a> Context<'a> {
         self.nesting_depth
     }
 
-    pub(crate) fn begin_nested_interpretation(&mut self) -> bool {
-        if self.nesting_depth >= MAX_NESTED_INTERPRETATION_DEPTH {
+    /// Return the form `XObject`s that are currently being drawn.
+    pub(crate) fn x_object_stack(&self) -> &[ObjectIdentifier] {
+        &self.x_object_stack
+    }
+
+    /// Begin interpreting the form `XObject` with the given identifier.
+    ///
+    /// Returns `false` if the maximum nesting depth was reached or the form is
+    /// already being drawn, in which case it must not be interpreted, since it
+    /// references itself.
+    pub(crate) fn begin_nested_interpretation(&mut self, id: Option<ObjectIdentifier>) -> bool {
+        if self.nesting_depth >= self.settings.max_nesting_depth {
             warn!("interpreter nesting depth exceeded");
 
             return false;
         }
 
+        if let Some(id) = id {
+            if self.x_object_stack.contains(&id) {
+                warn!("form XObject {:?} references itself", id);
+
+                return false;
+            }
+
+            self.x_object_stack.push(id);
+        }
+
         self.nesting_depth += 1;
 
         true
     }
 
-    pub(crate) fn end_nested_interpretation(&mut self) {
+    /// End interpreting the form `XObject` with the given identifier.
+    pub(crate) fn end_nested_interpretation(&mut self, id: Option<ObjectIdentifier>) {
+        if id.is_some() {
+            self.x_object_stack.pop();
+        }
+
         self.nesting_depth = self.nesting_depth.saturating_sub(1);
     }
+
     pub(crate) fn resolve_font(&mut self, font_dict: &Dict<'a>) -> Option<TextStateFont<'a>> {
         let cache_key = font_dict.cache_key();
 
@@ -353,17 +701,38 @@ impl<'a> Context<'a> {
                     Font::new(
                         font_dict,
                         &self.settings.font_resolver,
+                        &self.settings.font_fallbacks,
                         &self.settings.cmap_resolver,
+                        &self.interpreter_cache.object_cache,
+                        &self.settings.warning_sink,
                     )
                 })
                 .clone()
         };
 
-        if let Some(resolved) = resolved {
-            Some(TextStateFont::Font(resolved))
-        } else {
-            Font::new_standard(StandardFont::Helvetica, &self.settings.font_resolver)
-                .map(TextStateFont::Fallback)
+        let Some(resolved) = resolved else {
+            return Font::new_standard(StandardFont::Helvetica, &self.settings.font_resolver)
+                .map(TextStateFont::Fallback);
+        };
+
+        if !resolved.is_corrupt() {
+            return Some(TextStateFont::Font(resolved));
+        }
+
+        // The substitute font of a corrupt font program is still used for advancing
+        // the text position if its text is skipped.
+        match self.settings.corrupt_fonts {
+            CorruptFontPolicy::Fallback => Some(TextStateFont::Font(resolved)),
+            CorruptFontPolicy::Skip => {
+                (self.settings.warning_sink)(InterpreterWarning::skipped_font(font_dict).into());
+
+                Some(TextStateFont::Skipped(resolved))
+            }
+            CorruptFontPolicy::Error => {
+                self.abort();
+
+                None
+            }
         }
     }
 }
@@ -1,7 +1,9 @@
-use crate::font::Glyph;
+use crate::color::ColorSpace;
+use crate::font::{Glyph, GlyphRun};
+use crate::gradient::{AxialGradient, Mesh, RadialGradient};
 use crate::soft_mask::SoftMask;
-use crate::{BlendMode, ClipPath, FillRule, Image};
-use crate::{DrawMode, DrawProps, ImageDrawProps};
+use crate::{BlendMode, ClipPath, FillRule, Image, TextRenderingMode};
+use crate::{DrawMode, DrawProps, ImageDrawProps, MarkedContent};
 use kurbo::{Affine, BezPath, Rect, Shape};
 
 /// A trait for a device that can be used to process PDF drawing instructions.
@@ -24,6 +26,25 @@ pub trait Device<'a> {
         mask: Option<SoftMask<'a>>,
         blend_mode: BlendMode,
     );
+    /// Push a new transparency group whose contents are blended in the given
+    /// color space.
+    ///
+    /// This is called instead of [`Device::push_transparency_group`] if the group
+    /// or one of its parent groups declares a color space, like a CMYK color space.
+    /// The interpreter only determines the blending color space and exposes it to
+    /// the device, it doesn't blend in it itself. Devices that can composite in
+    /// color spaces other than sRGB can override this method, the default
+    /// implementation ignores the color space, so that the group is blended in
+    /// sRGB. This is currently the case for all devices of `hayro`.
+    fn push_transparency_group_with_color_space(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+        _color_space: &ColorSpace,
+    ) {
+        self.push_transparency_group(opacity, mask, blend_mode);
+    }
     /// Draw a glyph.
     fn draw_glyph(
         &mut self,
@@ -32,8 +53,47 @@ pub trait Device<'a> {
         props: DrawProps<'a>,
         draw_mode: &DrawMode,
     );
+    /// Draw a run of glyphs.
+    ///
+    /// This is called once for each string that is shown with a text operator, and
+    /// gives access to the text state (like the text matrix and the rendering mode) of
+    /// the run. Devices that want to preserve the text, for example to emit real text
+    /// elements, can override this method. The default implementation calls
+    /// [`Device::draw_glyph`] for each glyph, first filling and then stroking it.
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'a>) {
+        let fill_mode = if run.render_mode == TextRenderingMode::Invisible {
+            DrawMode::Invisible
+        } else {
+            DrawMode::Fill(FillRule::NonZero)
+        };
+
+        for glyph in &run.glyphs {
+            if let Some(props) = &run.fill {
+                self.draw_glyph(&glyph.glyph, glyph.transform, props.clone(), &fill_mode);
+            }
+
+            if let Some((props, stroke_props)) = &run.stroke {
+                self.draw_glyph(
+                    &glyph.glyph,
+                    glyph.transform,
+                    props.clone(),
+                    &DrawMode::Stroke(stroke_props.clone()),
+                );
+            }
+        }
+    }
     /// Draw an image.
     fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>);
+    /// Draw an image that is an `XObject`.
+    ///
+    /// This is called instead of [`Device::draw_image`] for images that are objects
+    /// of their own. Unlike inline images, which borrow from the content stream,
+    /// they live as long as the document, so devices that keep images around can
+    /// store them without decoding them first. The default implementation calls
+    /// [`Device::draw_image`].
+    fn draw_xobject_image(&mut self, image: Image<'a, 'a>, props: ImageDrawProps<'a>) {
+        self.draw_image(image, props);
+    }
     /// Pop the last clip path or clip rectangle from the clip stack.
     fn pop_clip(&mut self);
     /// Pop the last transparency group from the blend stack.
@@ -42,13 +102,52 @@ pub trait Device<'a> {
     fn draw_rect(&mut self, rect: &Rect, props: DrawProps<'a>, draw_mode: &DrawMode) {
         self.draw_path(&rect.to_path(0.1), props, draw_mode);
     }
-    /// Called at the beginning of a marked content sequence (BMC/BDC).
+    /// Draw a path that is painted with an axial shading.
     ///
-    /// The tag is the marked content tag (e.g. b"P", b"Span"). The mcid is
-    /// the marked content identifier from the properties dict, if present.
-    fn begin_marked_content(&mut self, _tag: &[u8], _mcid: Option<i32>) {}
+    /// Devices that support native gradients can override this method to draw
+    /// the gradient directly instead of sampling the shading. The default
+    /// implementation calls [`Device::draw_path`], where the shading pattern is
+    /// still available as the paint of `props`.
+    fn draw_axial_gradient(
+        &mut self,
+        path: &BezPath,
+        _gradient: &AxialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.draw_path(path, props, draw_mode);
+    }
+    /// Draw a path that is painted with a radial shading.
+    ///
+    /// See [`Device::draw_axial_gradient`] for more information.
+    fn draw_radial_gradient(
+        &mut self,
+        path: &BezPath,
+        _gradient: &RadialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.draw_path(path, props, draw_mode);
+    }
+    /// Draw a path that is painted with a triangle or patch mesh shading.
+    ///
+    /// See [`Device::draw_axial_gradient`] for more information.
+    fn draw_mesh(
+        &mut self,
+        path: &BezPath,
+        _mesh: &Mesh,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.draw_path(path, props, draw_mode);
+    }
+    /// Called at the beginning of a marked content sequence (BMC/BDC).
+    fn begin_marked_content(&mut self, _marked_content: &MarkedContent<'_>) {}
     /// Called at the end of a marked content sequence (EMC).
     fn end_marked_content(&mut self) {}
+    /// Called for a marked content point (MP/DP), which marks a single place in
+    /// the content stream instead of a sequence.
+    fn marked_content_point(&mut self, _marked_content: &MarkedContent<'_>) {}
 }
 
 /// A device that discards all drawing operations.
@@ -63,3 +162,172 @@ impl Device<'_> for DummyDevice {
     fn pop_clip(&mut self) {}
     fn pop_transparency_group(&mut self) {}
 }
+
+/// A device that forwards all drawing operations to two other devices.
+///
+/// This allows processing a page with different devices in a single pass,
+/// for example to render it and to extract its text at the same time.
+/// Operations are forwarded to the first device before the second one.
+pub struct TeeDevice<A, B> {
+    /// The first device.
+    pub first: A,
+    /// The second device.
+    pub second: B,
+}
+
+impl<A, B> TeeDevice<A, B> {
+    /// Create a new device that forwards to the two given devices.
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// Return the two underlying devices.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<'a, A: Device<'a>, B: Device<'a>> Device<'a> for TeeDevice<A, B> {
+    fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        self.first.draw_path(path, props.clone(), draw_mode);
+        self.second.draw_path(path, props, draw_mode);
+    }
+
+    fn push_clip_path(&mut self, clip_path: &ClipPath) {
+        self.first.push_clip_path(clip_path);
+        self.second.push_clip_path(clip_path);
+    }
+
+    fn push_clip_rect(&mut self, rect: &Rect) {
+        self.first.push_clip_rect(rect);
+        self.second.push_clip_rect(rect);
+    }
+
+    fn push_transparency_group(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+    ) {
+        self.first
+            .push_transparency_group(opacity, mask.clone(), blend_mode);
+        self.second
+            .push_transparency_group(opacity, mask, blend_mode);
+    }
+
+    fn push_transparency_group_with_color_space(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+        color_space: &ColorSpace,
+    ) {
+        self.first.push_transparency_group_with_color_space(
+            opacity,
+            mask.clone(),
+            blend_mode,
+            color_space,
+        );
+        self.second.push_transparency_group_with_color_space(
+            opacity,
+            mask,
+            blend_mode,
+            color_space,
+        );
+    }
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.first
+            .draw_glyph(glyph, glyph_transform, props.clone(), draw_mode);
+        self.second
+            .draw_glyph(glyph, glyph_transform, props, draw_mode);
+    }
+
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'a>) {
+        self.first.draw_glyph_run(run);
+        self.second.draw_glyph_run(run);
+    }
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        self.first.draw_image(image.clone(), props.clone());
+        self.second.draw_image(image, props);
+    }
+
+    fn draw_xobject_image(&mut self, image: Image<'a, 'a>, props: ImageDrawProps<'a>) {
+        self.first.draw_xobject_image(image.clone(), props.clone());
+        self.second.draw_xobject_image(image, props);
+    }
+
+    fn pop_clip(&mut self) {
+        self.first.pop_clip();
+        self.second.pop_clip();
+    }
+
+    fn pop_transparency_group(&mut self) {
+        self.first.pop_transparency_group();
+        self.second.pop_transparency_group();
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        self.first.draw_rect(rect, props.clone(), draw_mode);
+        self.second.draw_rect(rect, props, draw_mode);
+    }
+
+    fn draw_axial_gradient(
+        &mut self,
+        path: &BezPath,
+        gradient: &AxialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.first
+            .draw_axial_gradient(path, gradient, props.clone(), draw_mode);
+        self.second
+            .draw_axial_gradient(path, gradient, props, draw_mode);
+    }
+
+    fn draw_radial_gradient(
+        &mut self,
+        path: &BezPath,
+        gradient: &RadialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.first
+            .draw_radial_gradient(path, gradient, props.clone(), draw_mode);
+        self.second
+            .draw_radial_gradient(path, gradient, props, draw_mode);
+    }
+
+    fn draw_mesh(
+        &mut self,
+        path: &BezPath,
+        mesh: &Mesh,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.first.draw_mesh(path, mesh, props.clone(), draw_mode);
+        self.second.draw_mesh(path, mesh, props, draw_mode);
+    }
+
+    fn begin_marked_content(&mut self, marked_content: &MarkedContent<'_>) {
+        self.first.begin_marked_content(marked_content);
+        self.second.begin_marked_content(marked_content);
+    }
+
+    fn end_marked_content(&mut self) {
+        self.first.end_marked_content();
+        self.second.end_marked_content();
+    }
+
+    fn marked_content_point(&mut self, marked_content: &MarkedContent<'_>) {
+        self.first.marked_content_point(marked_content);
+        self.second.marked_content_point(marked_content);
+    }
+}
@@ -0,0 +1,339 @@
+//! Recording drawing operations so that they can be replayed later on.
+
+use crate::color::{ColorSpace, RenderingIntent};
+use crate::device::Device;
+use crate::font::{Glyph, GlyphRun};
+use crate::gradient::{AxialGradient, Mesh, RadialGradient};
+use crate::soft_mask::SoftMask;
+use crate::x_object::DecodedRaster;
+use crate::{
+    BlendMode, ClipPath, DrawMode, DrawProps, Image, ImageDrawProps, LumaData, MarkedContent,
+    RasterImage, StencilImage,
+};
+use hayro_syntax::object::Stream;
+use kurbo::{Affine, BezPath, Rect};
+
+mod owned;
+
+pub use owned::OwnedDisplayList;
+pub(crate) use owned::RecordedContent;
+
+/// A device that records drawing operations, so that they can be replayed
+/// into other devices without interpreting the page again.
+///
+/// This is useful if the same page is drawn several times, for example at
+/// different zoom levels, in tiles or into different output formats. Image
+/// `XObject`s are recorded as they are and only decoded when they are drawn,
+/// while inline images borrow from the content stream and are therefore decoded
+/// when they are recorded, after which they no longer provide access to their
+/// stream. The properties dictionaries of marked content are not recorded.
+#[derive(Clone, Default)]
+pub struct DisplayList<'a> {
+    commands: Vec<Command<'a>>,
+}
+
+impl<'a> DisplayList<'a> {
+    /// Create a new, empty display list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of recorded drawing operations.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Return whether no drawing operations have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Replay the recorded drawing operations into the given device, in the
+    /// order they were recorded.
+    pub fn replay(&self, device: &mut impl Device<'a>) {
+        for command in &self.commands {
+            match command {
+                Command::DrawPath(path, props, mode) => {
+                    device.draw_path(path, props.clone(), mode);
+                }
+                Command::PushClipPath(clip_path) => device.push_clip_path(clip_path),
+                Command::PushClipRect(rect) => device.push_clip_rect(rect),
+                Command::PushTransparencyGroup(opacity, mask, blend_mode, color_space) => {
+                    match color_space {
+                        Some(cs) => device.push_transparency_group_with_color_space(
+                            *opacity,
+                            mask.clone(),
+                            *blend_mode,
+                            cs,
+                        ),
+                        None => device.push_transparency_group(*opacity, mask.clone(), *blend_mode),
+                    }
+                }
+                Command::DrawGlyph(glyph, transform, props, mode) => {
+                    device.draw_glyph(glyph, *transform, props.clone(), mode);
+                }
+                Command::DrawGlyphRun(run) => device.draw_glyph_run(run),
+                Command::DrawImage(image, props) => device.draw_image(image.clone(), props.clone()),
+                Command::DrawXObjectImage(image, props) => {
+                    device.draw_xobject_image(image.clone(), props.clone());
+                }
+                Command::PopClip => device.pop_clip(),
+                Command::PopTransparencyGroup => device.pop_transparency_group(),
+                Command::DrawRect(rect, props, mode) => {
+                    device.draw_rect(rect, props.clone(), mode);
+                }
+                Command::DrawAxialGradient(path, gradient, props, mode) => {
+                    device.draw_axial_gradient(path, gradient, props.clone(), mode);
+                }
+                Command::DrawRadialGradient(path, gradient, props, mode) => {
+                    device.draw_radial_gradient(path, gradient, props.clone(), mode);
+                }
+                Command::DrawMesh(path, mesh, props, mode) => {
+                    device.draw_mesh(path, mesh, props.clone(), mode);
+                }
+                Command::BeginMarkedContent(marked_content) => {
+                    device.begin_marked_content(&marked_content.to_marked_content());
+                }
+                Command::EndMarkedContent => device.end_marked_content(),
+                Command::MarkedContentPoint(marked_content) => {
+                    device.marked_content_point(&marked_content.to_marked_content());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Command<'a> {
+    DrawPath(BezPath, DrawProps<'a>, DrawMode),
+    PushClipPath(ClipPath),
+    PushClipRect(Rect),
+    PushTransparencyGroup(f32, Option<SoftMask<'a>>, BlendMode, Option<ColorSpace>),
+    DrawGlyph(Glyph<'a>, Affine, DrawProps<'a>, DrawMode),
+    DrawGlyphRun(GlyphRun<'a>),
+    DrawImage(Image<'a, 'static>, ImageDrawProps<'a>),
+    DrawXObjectImage(Image<'a, 'a>, ImageDrawProps<'a>),
+    PopClip,
+    PopTransparencyGroup,
+    DrawRect(Rect, DrawProps<'a>, DrawMode),
+    DrawAxialGradient(BezPath, AxialGradient, DrawProps<'a>, DrawMode),
+    DrawRadialGradient(BezPath, RadialGradient, DrawProps<'a>, DrawMode),
+    DrawMesh(BezPath, Mesh, DrawProps<'a>, DrawMode),
+    BeginMarkedContent(RecordedMarkedContent),
+    EndMarkedContent,
+    MarkedContentPoint(RecordedMarkedContent),
+}
+
+/// A marked content sequence without its properties dictionary, which borrows
+/// from the content stream.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct RecordedMarkedContent {
+    tag: Vec<u8>,
+    mcid: Option<i32>,
+    actual_text: Option<String>,
+    alt: Option<String>,
+}
+
+impl RecordedMarkedContent {
+    fn new(marked_content: &MarkedContent<'_>) -> Self {
+        Self {
+            tag: marked_content.tag.to_vec(),
+            mcid: marked_content.mcid,
+            actual_text: marked_content.actual_text.clone(),
+            alt: marked_content.alt.clone(),
+        }
+    }
+
+    fn to_marked_content(&self) -> MarkedContent<'_> {
+        MarkedContent {
+            tag: &self.tag,
+            mcid: self.mcid,
+            actual_text: self.actual_text.clone(),
+            alt: self.alt.clone(),
+            properties: None,
+        }
+    }
+}
+
+/// An image that was decoded when it was recorded.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct RecordedImage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Color spaces can't be serialized, so deserialized images don't have one.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) color_space: Option<ColorSpace>,
+    pub(crate) bits_per_component: Option<u8>,
+    pub(crate) rendering_intent: RenderingIntent,
+    pub(crate) cache_key: u128,
+    pub(crate) object_key: u128,
+    /// Always empty, the data has already been decoded.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) stream: Stream<'static>,
+    pub(crate) data: RecordedImageData,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum RecordedImageData {
+    Mask(LumaData),
+    Raster(DecodedRaster),
+}
+
+impl<'a> Device<'a> for DisplayList<'a> {
+    fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        self.commands
+            .push(Command::DrawPath(path.clone(), props, draw_mode.clone()));
+    }
+
+    fn push_clip_path(&mut self, clip_path: &ClipPath) {
+        self.commands.push(Command::PushClipPath(clip_path.clone()));
+    }
+
+    fn push_clip_rect(&mut self, rect: &Rect) {
+        self.commands.push(Command::PushClipRect(*rect));
+    }
+
+    fn push_transparency_group(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+    ) {
+        self.commands.push(Command::PushTransparencyGroup(
+            opacity, mask, blend_mode, None,
+        ));
+    }
+
+    fn push_transparency_group_with_color_space(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+        color_space: &ColorSpace,
+    ) {
+        self.commands.push(Command::PushTransparencyGroup(
+            opacity,
+            mask,
+            blend_mode,
+            Some(color_space.clone()),
+        ));
+    }
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.commands.push(Command::DrawGlyph(
+            glyph.clone(),
+            glyph_transform,
+            props,
+            draw_mode.clone(),
+        ));
+    }
+
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'a>) {
+        self.commands.push(Command::DrawGlyphRun(run.clone()));
+    }
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        // Inline images borrow from the content stream, so they have to be decoded
+        // to outlive it.
+        let image = match image {
+            Image::Stencil(s) => s.source.to_recorded().map(|source| {
+                Image::Stencil(StencilImage {
+                    paint: s.paint,
+                    source,
+                })
+            }),
+            Image::Raster(r) => r.0.to_recorded().map(|r| Image::Raster(RasterImage(r))),
+        };
+
+        if let Some(image) = image {
+            self.commands.push(Command::DrawImage(image, props));
+        }
+    }
+
+    fn draw_xobject_image(&mut self, image: Image<'a, 'a>, props: ImageDrawProps<'a>) {
+        self.commands.push(Command::DrawXObjectImage(image, props));
+    }
+
+    fn pop_clip(&mut self) {
+        self.commands.push(Command::PopClip);
+    }
+
+    fn pop_transparency_group(&mut self) {
+        self.commands.push(Command::PopTransparencyGroup);
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        self.commands
+            .push(Command::DrawRect(*rect, props, draw_mode.clone()));
+    }
+
+    fn draw_axial_gradient(
+        &mut self,
+        path: &BezPath,
+        gradient: &AxialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.commands.push(Command::DrawAxialGradient(
+            path.clone(),
+            gradient.clone(),
+            props,
+            draw_mode.clone(),
+        ));
+    }
+
+    fn draw_radial_gradient(
+        &mut self,
+        path: &BezPath,
+        gradient: &RadialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.commands.push(Command::DrawRadialGradient(
+            path.clone(),
+            gradient.clone(),
+            props,
+            draw_mode.clone(),
+        ));
+    }
+
+    fn draw_mesh(
+        &mut self,
+        path: &BezPath,
+        mesh: &Mesh,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        self.commands.push(Command::DrawMesh(
+            path.clone(),
+            mesh.clone(),
+            props,
+            draw_mode.clone(),
+        ));
+    }
+
+    fn begin_marked_content(&mut self, marked_content: &MarkedContent<'_>) {
+        self.commands
+            .push(Command::BeginMarkedContent(RecordedMarkedContent::new(
+                marked_content,
+            )));
+    }
+
+    fn end_marked_content(&mut self) {
+        self.commands.push(Command::EndMarkedContent);
+    }
+
+    fn marked_content_point(&mut self, marked_content: &MarkedContent<'_>) {
+        self.commands
+            .push(Command::MarkedContentPoint(RecordedMarkedContent::new(
+                marked_content,
+            )));
+    }
+}
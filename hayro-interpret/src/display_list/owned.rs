@@ -0,0 +1,1271 @@
+//! Display lists that own all of their data.
+
+use super::{DisplayList, RecordedImage, RecordedMarkedContent};
+use crate::color::{AlphaColor, Color, ColorComponents, ColorSpace};
+use crate::device::Device;
+use crate::font::Glyph;
+use crate::function::Function;
+use crate::gradient::{AxialGradient, Mesh, RadialGradient, gradient_stops};
+use crate::pattern::{Pattern, ShadingPattern, TilingPattern};
+use crate::shading::{Shading, ShadingFunction, ShadingType, Triangle, TriangleVertex};
+use crate::soft_mask::{MaskType, SoftMask, TransferFunction};
+use crate::types::ImageSource;
+use crate::util::hash128;
+use crate::{
+    BlendMode, CacheKey, ClipPath, DrawMode, DrawProps, FillRule, Image, ImageDrawProps,
+    MarkedContent, Paint, RasterImage, StencilImage,
+};
+use hayro_syntax::object::ObjectIdentifier;
+use kurbo::{Affine, BezPath, Point, Rect, Shape};
+use rustc_hash::FxHashMap;
+use smallvec::{SmallVec, smallvec};
+use std::cell::OnceCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The maximum number of cells in each direction of the grid that
+/// function-based shadings are sampled on.
+const MAX_FUNCTION_SHADING_GRID_SIZE: usize = 128;
+
+/// A display list that owns all of its data, so that it can outlive the PDF
+/// it was recorded from.
+///
+/// In contrast to a [`DisplayList`], everything that refers to the PDF is
+/// converted into a self-contained form when it is recorded:
+/// - Glyphs are converted into paths, so the text of the page can't be
+///   extracted from the list anymore.
+/// - Shadings are approximated in the `DeviceRGB` color space, with the
+///   precision given by their smoothness. Function-based shadings are sampled
+///   into a triangle mesh.
+/// - The contents of tiling patterns and soft masks are recorded as well.
+/// - Images are decoded, like in a [`DisplayList`].
+///
+/// With the `serde` feature, owned display lists can be serialized, so that a
+/// page can be interpreted once and then be drawn by thin clients or be cached
+/// on disk. The serialized data contains [`OwnedDisplayList::VERSION`], and
+/// deserializing data with a different version fails. The color spaces of
+/// images are not serialized.
+#[derive(Clone, Default)]
+pub struct OwnedDisplayList {
+    data: Arc<ListData>,
+    keys: ResourceKeys,
+}
+
+impl OwnedDisplayList {
+    /// The version of the serialized format. It is increased whenever the
+    /// format changes.
+    pub const VERSION: u32 = 1;
+
+    /// Create a new, empty display list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of recorded drawing operations, excluding the ones
+    /// in patterns and soft masks.
+    pub fn len(&self) -> usize {
+        self.data.commands.len()
+    }
+
+    /// Return whether no drawing operations have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.data.commands.is_empty()
+    }
+
+    /// Replay the recorded drawing operations into the given device, in the
+    /// order they were recorded.
+    pub fn replay<'a>(&self, device: &mut impl Device<'a>) {
+        let replayer = Rc::new(Replayer::new(self.data.clone()));
+        replay(&replayer, ContentId::Page, Affine::IDENTITY, device);
+    }
+
+    fn data(&mut self) -> &mut ListData {
+        Arc::make_mut(&mut self.data)
+    }
+
+    fn push(&mut self, command: Command) {
+        self.data().commands.push(command);
+    }
+
+    /// Return the commands that `f` draws into the list, without adding them
+    /// to the list itself.
+    fn record_nested(&mut self, f: impl FnOnce(&mut Self)) -> Vec<Command> {
+        let outer = std::mem::take(&mut self.data().commands);
+        f(self);
+
+        std::mem::replace(&mut self.data().commands, outer)
+    }
+
+    fn record_props(&mut self, props: &DrawProps<'_>) -> Props {
+        Props {
+            transform: props.transform,
+            paint: self.record_paint(&props.paint),
+            soft_mask: props.soft_mask.as_ref().map(|m| self.record_mask(m)),
+            blend_mode: props.blend_mode,
+            flatness: props.flatness,
+        }
+    }
+
+    fn record_image_props(&mut self, props: &ImageDrawProps<'_>) -> ImageProps {
+        ImageProps {
+            transform: props.transform,
+            soft_mask: props.soft_mask.as_ref().map(|m| self.record_mask(m)),
+            blend_mode: props.blend_mode,
+        }
+    }
+
+    fn record_paint(&mut self, paint: &Paint<'_>) -> PaintData {
+        match paint {
+            Paint::Color(color) => PaintData::Color(color.to_rgba().components()),
+            Paint::Pattern(pattern) => match pattern.as_ref() {
+                Pattern::Shading(shading) => PaintData::Shading(self.record_shading(shading)),
+                Pattern::Tiling(tiling) => PaintData::Pattern(self.record_pattern(tiling)),
+            },
+        }
+    }
+
+    fn record_shading(&mut self, pattern: &ShadingPattern) -> usize {
+        let key = pattern.cache_key();
+
+        if let Some(index) = self.keys.shadings.get(&key) {
+            return *index;
+        }
+
+        let shadings = &mut self.data().shadings;
+        shadings.push(ShadingData::new(pattern));
+        let index = shadings.len() - 1;
+        self.keys.shadings.insert(key, index);
+
+        index
+    }
+
+    fn record_pattern(&mut self, pattern: &TilingPattern<'_>) -> usize {
+        let key = hash128(&(pattern.cache_key(), pattern.matrix.cache_key()));
+
+        if let Some(index) = self.keys.patterns.get(&key) {
+            return *index;
+        }
+
+        // The contents are recorded in the coordinate space of the pattern,
+        // the matrix is applied when they are replayed.
+        let fill = self.record_nested(|list| {
+            pattern.interpret(list, Affine::IDENTITY, false);
+        });
+        let stroke = (!pattern.is_colored()).then(|| {
+            self.record_nested(|list| {
+                pattern.interpret(list, Affine::IDENTITY, true);
+            })
+        });
+
+        let patterns = &mut self.data().patterns;
+        patterns.push(PatternData {
+            cache_key: pattern.cache_key(),
+            bbox: pattern.bbox,
+            x_step: pattern.x_step,
+            y_step: pattern.y_step,
+            matrix: pattern.matrix,
+            fill,
+            stroke,
+        });
+        let index = patterns.len() - 1;
+        self.keys.patterns.insert(key, index);
+
+        index
+    }
+
+    fn record_mask(&mut self, mask: &SoftMask<'_>) -> usize {
+        let key = mask.cache_key();
+
+        if let Some(index) = self.keys.masks.get(&key) {
+            return *index;
+        }
+
+        let content = self.record_nested(|list| mask.interpret(list));
+        let id = mask.id();
+
+        let masks = &mut self.data().masks;
+        masks.push(MaskData {
+            cache_key: key,
+            id: (id.obj_number, id.gen_number),
+            mask_type: mask.mask_type(),
+            background: mask.background_color().to_rgba().components(),
+            transfer_function: mask.transfer_function().map(TransferFunction::samples),
+            content,
+        });
+        let index = masks.len() - 1;
+        self.keys.masks.insert(key, index);
+
+        index
+    }
+
+    fn record_image(&mut self, source: &ImageSource<'_>) -> Option<usize> {
+        let key = source.cache_key();
+
+        if let Some(index) = self.keys.images.get(&key) {
+            return Some(*index);
+        }
+
+        let ImageSource::Recorded(image) = source.to_recorded()? else {
+            return None;
+        };
+
+        let images = &mut self.data().images;
+        images.push(image);
+        let index = images.len() - 1;
+        self.keys.images.insert(key, index);
+
+        Some(index)
+    }
+
+    fn record_outline(
+        &mut self,
+        path: BezPath,
+        props: &DrawProps<'_>,
+        transform: Affine,
+        draw_mode: DrawMode,
+    ) {
+        let props = Props {
+            transform: props.transform * transform,
+            ..self.record_props(props)
+        };
+        self.push(Command::DrawPath(path, props, draw_mode));
+    }
+}
+
+impl From<&DisplayList<'_>> for OwnedDisplayList {
+    fn from(list: &DisplayList<'_>) -> Self {
+        let mut owned = Self::new();
+        list.replay(&mut owned);
+
+        owned
+    }
+}
+
+impl<'a> Device<'a> for OwnedDisplayList {
+    fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        let props = self.record_props(&props);
+        self.push(Command::DrawPath(path.clone(), props, draw_mode.clone()));
+    }
+
+    fn push_clip_path(&mut self, clip_path: &ClipPath) {
+        self.push(Command::PushClipPath(clip_path.clone()));
+    }
+
+    fn push_clip_rect(&mut self, rect: &Rect) {
+        self.push(Command::PushClipRect(*rect));
+    }
+
+    fn push_transparency_group(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+    ) {
+        let mask = mask.map(|m| self.record_mask(&m));
+        self.push(Command::PushTransparencyGroup(
+            opacity, mask, blend_mode, None,
+        ));
+    }
+
+    fn push_transparency_group_with_color_space(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+        color_space: &ColorSpace,
+    ) {
+        let mask = mask.map(|m| self.record_mask(&m));
+        self.push(Command::PushTransparencyGroup(
+            opacity,
+            mask,
+            blend_mode,
+            GroupColorSpace::new(color_space),
+        ));
+    }
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let outline = match glyph {
+            Glyph::Outline(outline) => outline,
+            Glyph::Type3(glyph) => {
+                if !matches!(draw_mode, DrawMode::Invisible) {
+                    glyph.interpret(self, props.transform, glyph_transform, &props.paint);
+                }
+
+                return;
+            }
+        };
+
+        let (fill, stroke) = match draw_mode {
+            DrawMode::Fill(_) => (true, None),
+            DrawMode::Stroke(stroke_props) => (false, Some(stroke_props)),
+            DrawMode::FillAndStroke(_, stroke_props) => (true, Some(stroke_props)),
+            DrawMode::Invisible => (false, None),
+        };
+
+        if fill {
+            if outline.is_color() {
+                outline.interpret_color(self, glyph_transform, &props);
+            } else {
+                self.record_outline(
+                    outline.outline(),
+                    &props,
+                    glyph_transform,
+                    DrawMode::Fill(FillRule::NonZero),
+                );
+            }
+        }
+
+        if let Some(stroke_props) = stroke {
+            // The line width is given in the coordinate space of the text, so
+            // the glyph transform can't be applied to the stroke.
+            self.record_outline(
+                glyph_transform * outline.outline(),
+                &props,
+                Affine::IDENTITY,
+                DrawMode::Stroke(stroke_props.clone()),
+            );
+        }
+    }
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        let image = match &image {
+            Image::Stencil(stencil) => self
+                .record_image(&stencil.source)
+                .map(|index| ImageRef::Stencil(index, self.record_paint(&stencil.paint))),
+            Image::Raster(raster) => self.record_image(&raster.0).map(ImageRef::Raster),
+        };
+
+        if let Some(image) = image {
+            let props = self.record_image_props(&props);
+            self.push(Command::DrawImage(image, props));
+        }
+    }
+
+    fn pop_clip(&mut self) {
+        self.push(Command::PopClip);
+    }
+
+    fn pop_transparency_group(&mut self) {
+        self.push(Command::PopTransparencyGroup);
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        let props = self.record_props(&props);
+        self.push(Command::DrawRect(*rect, props, draw_mode.clone()));
+    }
+
+    fn draw_axial_gradient(
+        &mut self,
+        path: &BezPath,
+        _: &AxialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let props = self.record_props(&props);
+        self.push(Command::DrawShading(path.clone(), props, draw_mode.clone()));
+    }
+
+    fn draw_radial_gradient(
+        &mut self,
+        path: &BezPath,
+        _: &RadialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let props = self.record_props(&props);
+        self.push(Command::DrawShading(path.clone(), props, draw_mode.clone()));
+    }
+
+    fn draw_mesh(&mut self, path: &BezPath, _: &Mesh, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        let props = self.record_props(&props);
+        self.push(Command::DrawShading(path.clone(), props, draw_mode.clone()));
+    }
+
+    fn begin_marked_content(&mut self, marked_content: &MarkedContent<'_>) {
+        self.push(Command::BeginMarkedContent(RecordedMarkedContent::new(
+            marked_content,
+        )));
+    }
+
+    fn end_marked_content(&mut self) {
+        self.push(Command::EndMarkedContent);
+    }
+
+    fn marked_content_point(&mut self, marked_content: &MarkedContent<'_>) {
+        self.push(Command::MarkedContentPoint(RecordedMarkedContent::new(
+            marked_content,
+        )));
+    }
+}
+
+/// Maps the cache keys of recorded resources to their index, so that resources
+/// that are used several times are only recorded once.
+#[derive(Clone, Default)]
+struct ResourceKeys {
+    shadings: FxHashMap<u128, usize>,
+    patterns: FxHashMap<u128, usize>,
+    masks: FxHashMap<u128, usize>,
+    images: FxHashMap<u128, usize>,
+}
+
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ListData {
+    commands: Vec<Command>,
+    shadings: Vec<ShadingData>,
+    patterns: Vec<PatternData>,
+    masks: Vec<MaskData>,
+    images: Vec<Arc<RecordedImage>>,
+}
+
+/// A drawing operation. Resources like shadings or images are referenced by
+/// their index in the list data.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Command {
+    DrawPath(BezPath, Props, DrawMode),
+    DrawRect(Rect, Props, DrawMode),
+    /// A path painted with a shading that devices can draw natively.
+    DrawShading(BezPath, Props, DrawMode),
+    PushClipPath(ClipPath),
+    PushClipRect(Rect),
+    PushTransparencyGroup(f32, Option<usize>, BlendMode, Option<GroupColorSpace>),
+    DrawImage(ImageRef, ImageProps),
+    PopClip,
+    PopTransparencyGroup,
+    BeginMarkedContent(RecordedMarkedContent),
+    EndMarkedContent,
+    MarkedContentPoint(RecordedMarkedContent),
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Props {
+    transform: Affine,
+    paint: PaintData,
+    soft_mask: Option<usize>,
+    blend_mode: BlendMode,
+    flatness: Option<f32>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ImageProps {
+    transform: Affine,
+    soft_mask: Option<usize>,
+    blend_mode: BlendMode,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum PaintData {
+    /// A color in sRGB with an alpha channel.
+    Color([f32; 4]),
+    Shading(usize),
+    Pattern(usize),
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum ImageRef {
+    Stencil(usize, PaintData),
+    Raster(usize),
+}
+
+/// The device color space with the same number of components as the color
+/// space of a transparency group.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum GroupColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+}
+
+impl GroupColorSpace {
+    fn new(color_space: &ColorSpace) -> Option<Self> {
+        match color_space.num_components() {
+            1 => Some(Self::Gray),
+            3 => Some(Self::Rgb),
+            4 => Some(Self::Cmyk),
+            _ => None,
+        }
+    }
+
+    fn to_color_space(self) -> ColorSpace {
+        match self {
+            Self::Gray => ColorSpace::device_gray(),
+            Self::Rgb => ColorSpace::device_rgb(),
+            Self::Cmyk => ColorSpace::device_cmyk(),
+        }
+    }
+}
+
+/// A shading pattern, with colors in the `DeviceRGB` color space that already
+/// take the transfer function into account.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ShadingData {
+    cache_key: u128,
+    kind: ShadingKind,
+    matrix: Affine,
+    opacity: f32,
+    clip_path: Option<BezPath>,
+    background: Option<[f32; 3]>,
+    smoothness: f32,
+    flatness: f32,
+    gradient_subdivisions: u8,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum ShadingKind {
+    /// An axial or radial shading, whose function is approximated by linear
+    /// segments between the stops.
+    RadialAxial {
+        coords: [f32; 6],
+        extend: [bool; 2],
+        axial: bool,
+        stops: Vec<(f32, [f32; 3])>,
+    },
+    Mesh(Vec<[(Point, [f32; 3]); 3]>),
+    Dummy,
+}
+
+impl ShadingData {
+    fn new(pattern: &ShadingPattern) -> Self {
+        let shading = &pattern.shading;
+        let rgb = |c: [f32; 4]| [c[0], c[1], c[2]];
+
+        let kind = match shading.shading_type.as_ref() {
+            ShadingType::RadialAxial {
+                coords,
+                extend,
+                axial,
+                ..
+            } => ShadingKind::RadialAxial {
+                coords: *coords,
+                extend: *extend,
+                axial: *axial,
+                stops: gradient_stops(pattern)
+                    .into_iter()
+                    .map(|stop| (stop.offset, rgb(stop.color)))
+                    .collect(),
+            },
+            ShadingType::FunctionBased {
+                domain,
+                matrix,
+                function,
+            } => ShadingKind::Mesh(sample_function_shading(pattern, *domain, *matrix, function)),
+            ShadingType::Dummy => ShadingKind::Dummy,
+            _ => ShadingKind::Mesh(
+                Mesh::new(pattern)
+                    .map(|mesh| mesh.triangles())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|t| std::array::from_fn(|i| (t.points[i], rgb(t.colors[i]))))
+                    .collect(),
+            ),
+        };
+
+        let background = shading.background.as_ref().map(|background| {
+            rgb(shading
+                .color_space
+                .to_rgba(background, 1.0, false)
+                .components())
+        });
+
+        Self {
+            cache_key: pattern.cache_key(),
+            kind,
+            matrix: pattern.matrix,
+            opacity: pattern.opacity,
+            clip_path: shading.clip_path.clone(),
+            background,
+            smoothness: pattern.smoothness,
+            flatness: pattern.flatness,
+            gradient_subdivisions: pattern.gradient_subdivisions,
+        }
+    }
+
+    fn to_pattern(&self) -> ShadingPattern {
+        let shading_type = match &self.kind {
+            ShadingKind::RadialAxial {
+                coords,
+                extend,
+                axial,
+                stops,
+            } => {
+                let stops = stops
+                    .iter()
+                    .map(|(offset, color)| (*offset, SmallVec::from_slice(color)))
+                    .collect::<Vec<_>>();
+
+                match Function::linear_stops(&stops) {
+                    Some(function) => ShadingType::RadialAxial {
+                        coords: *coords,
+                        domain: [0.0, 1.0],
+                        function: ShadingFunction::Single(function),
+                        extend: *extend,
+                        axial: *axial,
+                    },
+                    None => ShadingType::Dummy,
+                }
+            }
+            ShadingKind::Mesh(triangles) => ShadingType::TriangleMesh {
+                triangles: triangles
+                    .iter()
+                    .map(|vertices| {
+                        let [p0, p1, p2] = vertices.each_ref().map(|(point, color)| {
+                            TriangleVertex::new(*point, ColorComponents::from_slice(color))
+                        });
+
+                        Triangle::new(p0, p1, p2)
+                    })
+                    .collect(),
+                function: None,
+            },
+            ShadingKind::Dummy => ShadingType::Dummy,
+        };
+
+        let shading = Shading::new_rgb(
+            self.cache_key,
+            shading_type,
+            self.clip_path.clone(),
+            self.background.map(|b| SmallVec::from_slice(&b)),
+        );
+
+        ShadingPattern {
+            shading: Arc::new(shading),
+            matrix: self.matrix,
+            opacity: self.opacity,
+            transfer_function: None,
+            smoothness: self.smoothness,
+            flatness: self.flatness,
+            gradient_subdivisions: self.gradient_subdivisions,
+        }
+    }
+}
+
+/// Sample a function-based shading into a triangle mesh in the coordinate
+/// space of the shading.
+fn sample_function_shading(
+    pattern: &ShadingPattern,
+    domain: [f32; 4],
+    matrix: Affine,
+    function: &ShadingFunction,
+) -> Vec<[(Point, [f32; 3]); 3]> {
+    let encoded = pattern.encode();
+    let [x0, x1, y0, y1] = domain;
+    let size = function_shading_grid_size(pattern, domain, matrix);
+
+    let mut vertices = Vec::with_capacity((size + 1) * (size + 1));
+    for row in 0..=size {
+        for col in 0..=size {
+            let x = x0 + (x1 - x0) * col as f32 / size as f32;
+            let y = y0 + (y1 - y0) * row as f32 / size as f32;
+            let [r, g, b, _] = function
+                .eval(&smallvec![x, y])
+                .map(|c| {
+                    encoded.finish_color(
+                        pattern
+                            .shading
+                            .color_space
+                            .to_rgba(&c, 1.0, false)
+                            .components(),
+                    )
+                })
+                .unwrap_or([0.0, 0.0, 0.0, 0.0]);
+
+            vertices.push((matrix * Point::new(x as f64, y as f64), [r, g, b]));
+        }
+    }
+
+    let vertex = |row: usize, col: usize| vertices[row * (size + 1) + col];
+    let mut triangles = Vec::with_capacity(size * size * 2);
+    for row in 0..size {
+        for col in 0..size {
+            let (top_left, top_right) = (vertex(row, col), vertex(row, col + 1));
+            let (bottom_left, bottom_right) = (vertex(row + 1, col), vertex(row + 1, col + 1));
+
+            triangles.push([top_left, top_right, bottom_left]);
+            triangles.push([top_right, bottom_right, bottom_left]);
+        }
+    }
+
+    triangles
+}
+
+/// Return the number of cells in each direction of the grid that a
+/// function-based shading is sampled on.
+///
+/// The cells are made small enough that the colors of neighboring vertices
+/// differ by about the smoothness of the pattern, but never smaller than a unit
+/// of the space that the pattern is drawn in.
+fn function_shading_grid_size(pattern: &ShadingPattern, domain: [f32; 4], matrix: Affine) -> usize {
+    let [x0, x1, y0, y1] = domain.map(f64::from);
+    let mut bbox = (pattern.matrix * matrix).transform_rect_bbox(Rect::new(x0, y0, x1, y1).abs());
+
+    if let Some(clip_path) = &pattern.shading.clip_path {
+        bbox = bbox.intersect(pattern.matrix.transform_rect_bbox(clip_path.bounding_box()));
+    }
+
+    let extent = bbox.width().max(bbox.height()).max(0.0).ceil() as usize;
+    let steps = (1.0 / pattern.smoothness.max(f32::EPSILON)).ceil() as usize;
+
+    extent.min(steps).clamp(1, MAX_FUNCTION_SHADING_GRID_SIZE)
+}
+
+/// A tiling pattern, whose contents are given in the coordinate space of the
+/// pattern.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PatternData {
+    cache_key: u128,
+    bbox: Rect,
+    x_step: f32,
+    y_step: f32,
+    matrix: Affine,
+    fill: Vec<Command>,
+    /// The contents for stroking uncolored patterns, which are painted with
+    /// the stroke color.
+    stroke: Option<Vec<Command>>,
+}
+
+/// A soft mask, whose contents are given in device space.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct MaskData {
+    cache_key: u128,
+    id: (i32, i32),
+    mask_type: MaskType,
+    background: [f32; 4],
+    transfer_function: Option<Vec<f32>>,
+    content: Vec<Command>,
+}
+
+/// The data of an owned display list while it is being replayed, together
+/// with the shadings that have been rebuilt so far.
+struct Replayer {
+    data: Arc<ListData>,
+    shadings: Vec<OnceCell<ShadingPattern>>,
+}
+
+impl Replayer {
+    fn new(data: Arc<ListData>) -> Self {
+        Self {
+            shadings: data.shadings.iter().map(|_| OnceCell::new()).collect(),
+            data,
+        }
+    }
+
+    fn commands(&self, content: ContentId) -> &[Command] {
+        let commands = match content {
+            ContentId::Page => Some(&self.data.commands),
+            ContentId::PatternFill(index) => self.data.patterns.get(index).map(|p| &p.fill),
+            ContentId::PatternStroke(index) => self
+                .data
+                .patterns
+                .get(index)
+                .and_then(|p| p.stroke.as_ref()),
+            ContentId::Mask(index) => self.data.masks.get(index).map(|m| &m.content),
+        };
+
+        commands.map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn shading(&self, index: usize) -> Option<&ShadingPattern> {
+        let data = self.data.shadings.get(index)?;
+
+        Some(self.shadings[index].get_or_init(|| data.to_pattern()))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ContentId {
+    Page,
+    PatternFill(usize),
+    PatternStroke(usize),
+    Mask(usize),
+}
+
+/// The contents of a tiling pattern or soft mask that is replayed from an
+/// [`OwnedDisplayList`].
+#[derive(Clone)]
+pub(crate) struct RecordedContent {
+    replayer: Rc<Replayer>,
+    content: ContentId,
+    transform: Affine,
+    cache_key: u128,
+}
+
+impl RecordedContent {
+    /// Replay the contents into the given device, applying an additional
+    /// transform.
+    pub(crate) fn replay<'a>(&self, device: &mut impl Device<'a>, transform: Affine) {
+        replay(
+            &self.replayer,
+            self.content,
+            transform * self.transform,
+            device,
+        );
+    }
+
+    pub(crate) fn cache_key(&self) -> u128 {
+        self.cache_key
+    }
+}
+
+fn replay<'a>(
+    replayer: &Rc<Replayer>,
+    content: ContentId,
+    transform: Affine,
+    device: &mut impl Device<'a>,
+) {
+    let ctx = ReplayContext {
+        replayer,
+        transform,
+    };
+
+    for command in replayer.commands(content) {
+        match command {
+            Command::DrawPath(path, props, draw_mode) => {
+                device.draw_path(path, ctx.props(props), draw_mode);
+            }
+            Command::DrawRect(rect, props, draw_mode) => {
+                device.draw_rect(rect, ctx.props(props), draw_mode);
+            }
+            Command::DrawShading(path, props, draw_mode) => {
+                draw_shading(device, path, ctx.props(props), draw_mode);
+            }
+            Command::PushClipPath(clip_path) => device.push_clip_path(&ClipPath {
+                path: transform * clip_path.path.clone(),
+                fill: clip_path.fill,
+            }),
+            Command::PushClipRect(rect) => {
+                let [_, b, c, _, _, _] = transform.as_coeffs();
+
+                if b == 0.0 && c == 0.0 {
+                    device.push_clip_rect(&transform.transform_rect_bbox(*rect));
+                } else {
+                    device.push_clip_path(&ClipPath {
+                        path: transform * rect.to_path(0.1),
+                        fill: FillRule::NonZero,
+                    });
+                }
+            }
+            Command::PushTransparencyGroup(opacity, mask, blend_mode, color_space) => {
+                let mask = mask.and_then(|m| ctx.mask(m));
+
+                match color_space {
+                    Some(cs) => device.push_transparency_group_with_color_space(
+                        *opacity,
+                        mask,
+                        *blend_mode,
+                        &cs.to_color_space(),
+                    ),
+                    None => device.push_transparency_group(*opacity, mask, *blend_mode),
+                }
+            }
+            Command::DrawImage(image, props) => {
+                if let Some(image) = ctx.image(image) {
+                    device.draw_image(image, ctx.image_props(props));
+                }
+            }
+            Command::PopClip => device.pop_clip(),
+            Command::PopTransparencyGroup => device.pop_transparency_group(),
+            Command::BeginMarkedContent(marked_content) => {
+                device.begin_marked_content(&marked_content.to_marked_content());
+            }
+            Command::EndMarkedContent => device.end_marked_content(),
+            Command::MarkedContentPoint(marked_content) => {
+                device.marked_content_point(&marked_content.to_marked_content());
+            }
+        }
+    }
+}
+
+/// Pass a path painted with a shading to the method of the device that
+/// corresponds to the type of the shading.
+fn draw_shading<'a>(
+    device: &mut impl Device<'a>,
+    path: &BezPath,
+    props: DrawProps<'a>,
+    draw_mode: &DrawMode,
+) {
+    let Paint::Pattern(pattern) = &props.paint else {
+        device.draw_path(path, props, draw_mode);
+        return;
+    };
+    let Pattern::Shading(pattern) = pattern.as_ref() else {
+        device.draw_path(path, props, draw_mode);
+        return;
+    };
+
+    if let Some(gradient) = AxialGradient::new(pattern) {
+        device.draw_axial_gradient(path, &gradient, props, draw_mode);
+    } else if let Some(gradient) = RadialGradient::new(pattern) {
+        device.draw_radial_gradient(path, &gradient, props, draw_mode);
+    } else if let Some(mesh) = Mesh::new(pattern) {
+        device.draw_mesh(path, &mesh, props, draw_mode);
+    } else {
+        device.draw_path(path, props, draw_mode);
+    }
+}
+
+/// Converts the recorded data back into the types that are passed to devices,
+/// applying the transform of the content that is replayed.
+struct ReplayContext<'r> {
+    replayer: &'r Rc<Replayer>,
+    transform: Affine,
+}
+
+impl ReplayContext<'_> {
+    fn props<'a>(&self, props: &Props) -> DrawProps<'a> {
+        DrawProps {
+            transform: self.transform * props.transform,
+            paint: self.paint(&props.paint),
+            soft_mask: props.soft_mask.and_then(|m| self.mask(m)),
+            blend_mode: props.blend_mode,
+            flatness: props.flatness,
+        }
+    }
+
+    fn image_props<'a>(&self, props: &ImageProps) -> ImageDrawProps<'a> {
+        ImageDrawProps {
+            transform: self.transform * props.transform,
+            soft_mask: props.soft_mask.and_then(|m| self.mask(m)),
+            blend_mode: props.blend_mode,
+        }
+    }
+
+    fn paint<'a>(&self, paint: &PaintData) -> Paint<'a> {
+        let transparent = || Paint::Color(Color::from_rgba(AlphaColor::TRANSPARENT));
+
+        match paint {
+            PaintData::Color(color) => Paint::Color(Color::from_rgba(AlphaColor::new(*color))),
+            PaintData::Shading(index) => {
+                let Some(pattern) = self.replayer.shading(*index) else {
+                    return transparent();
+                };
+
+                let mut pattern = pattern.clone();
+                if self.transform != Affine::IDENTITY {
+                    pattern.matrix = self.transform * pattern.matrix;
+                    let clip_path = pattern
+                        .shading
+                        .clip_path
+                        .as_ref()
+                        .map(|path| self.transform * path.clone());
+                    Arc::make_mut(&mut pattern.shading).clip_path = clip_path;
+                }
+
+                Paint::Pattern(Box::new(Pattern::Shading(pattern)))
+            }
+            PaintData::Pattern(index) => {
+                let Some(data) = self.replayer.data.patterns.get(*index) else {
+                    return transparent();
+                };
+
+                let content = |content| self.content(content, Affine::IDENTITY, data.cache_key);
+                let pattern = TilingPattern::recorded(
+                    data.cache_key,
+                    data.bbox,
+                    data.x_step,
+                    data.y_step,
+                    self.transform * data.matrix,
+                    content(ContentId::PatternFill(*index)),
+                    data.stroke
+                        .as_ref()
+                        .map(|_| content(ContentId::PatternStroke(*index))),
+                );
+
+                Paint::Pattern(Box::new(Pattern::Tiling(Box::new(pattern))))
+            }
+        }
+    }
+
+    fn mask<'a>(&self, index: usize) -> Option<SoftMask<'a>> {
+        let data = self.replayer.data.masks.get(index)?;
+
+        Some(SoftMask::recorded(
+            ObjectIdentifier::new(data.id.0, data.id.1),
+            data.mask_type,
+            data.transfer_function
+                .clone()
+                .and_then(TransferFunction::from_samples),
+            Color::from_rgba(AlphaColor::new(data.background)),
+            self.content(ContentId::Mask(index), self.transform, data.cache_key),
+        ))
+    }
+
+    fn image<'a>(&self, image: &ImageRef) -> Option<Image<'a, 'static>> {
+        let source = |index: usize| {
+            self.replayer
+                .data
+                .images
+                .get(index)
+                .map(|image| ImageSource::Recorded(image.clone()))
+        };
+
+        Some(match image {
+            ImageRef::Stencil(index, paint) => Image::Stencil(StencilImage {
+                paint: self.paint(paint),
+                source: source(*index)?,
+            }),
+            ImageRef::Raster(index) => Image::Raster(RasterImage(source(*index)?)),
+        })
+    }
+
+    fn content(&self, content: ContentId, transform: Affine, key: u128) -> RecordedContent {
+        RecordedContent {
+            replayer: self.replayer.clone(),
+            content,
+            transform,
+            cache_key: hash128(&(key, transform.cache_key())),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use super::{
+        Command, ContentId, ImageRef, ListData, OwnedDisplayList, PaintData, ResourceKeys,
+    };
+    use serde::de::{self, MapAccess, SeqAccess, Visitor};
+    use serde::ser::SerializeStruct;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::fmt::Formatter;
+    use std::sync::Arc;
+
+    impl Serialize for OwnedDisplayList {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("OwnedDisplayList", 2)?;
+            state.serialize_field("version", &Self::VERSION)?;
+            state.serialize_field("data", self.data.as_ref())?;
+            state.end()
+        }
+    }
+
+    impl<'de> Deserialize<'de> for OwnedDisplayList {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_struct("OwnedDisplayList", &["version", "data"], ListVisitor)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "lowercase")]
+    enum Field {
+        Version,
+        Data,
+    }
+
+    /// Checks the version before reading the data, so that data of other
+    /// versions is rejected with a meaningful error.
+    struct ListVisitor;
+
+    impl<'de> Visitor<'de> for ListVisitor {
+        type Value = OwnedDisplayList;
+
+        fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+            formatter.write_str("a display list")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let version = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            check_version(version)?;
+            let data = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+            from_data(data)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut has_version = false;
+            let mut data = None;
+
+            while let Some(field) = map.next_key()? {
+                match field {
+                    Field::Version => {
+                        check_version(map.next_value()?)?;
+                        has_version = true;
+                    }
+                    Field::Data => {
+                        if !has_version {
+                            return Err(de::Error::custom(
+                                "the version of a display list must precede its data",
+                            ));
+                        }
+
+                        data = Some(map.next_value()?);
+                    }
+                }
+            }
+
+            let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
+
+            from_data(data)
+        }
+    }
+
+    fn check_version<E: de::Error>(version: u32) -> Result<(), E> {
+        if version == OwnedDisplayList::VERSION {
+            Ok(())
+        } else {
+            Err(E::custom(format_args!(
+                "unsupported display list version {version}, expected {}",
+                OwnedDisplayList::VERSION
+            )))
+        }
+    }
+
+    fn from_data<E: de::Error>(data: ListData) -> Result<OwnedDisplayList, E> {
+        check_nesting(&data)?;
+
+        Ok(OwnedDisplayList {
+            data: Arc::new(data),
+            keys: ResourceKeys::default(),
+        })
+    }
+
+    /// Check that the contents of patterns and soft masks don't contain
+    /// themselves, so that replaying the list can't recurse endlessly.
+    ///
+    /// Nested contents are recorded before the pattern or soft mask that uses
+    /// them, so they must have a smaller index than their owner if they are of
+    /// the same kind. Patterns and soft masks can still refer to each other, so
+    /// these references are checked for cycles as well.
+    fn check_nesting<E: de::Error>(data: &ListData) -> Result<(), E> {
+        let num_patterns = data.patterns.len();
+        // Patterns come first, followed by the soft masks.
+        let node = |content: ContentId| match content {
+            ContentId::PatternFill(index) | ContentId::PatternStroke(index) => {
+                (index < num_patterns).then_some(index)
+            }
+            ContentId::Mask(index) => (index < data.masks.len()).then_some(num_patterns + index),
+            ContentId::Page => None,
+        };
+        let children = |index: usize| {
+            let (owner, commands) = if index < num_patterns {
+                let pattern = &data.patterns[index];
+                let stroke = pattern.stroke.iter().flatten();
+
+                (
+                    ContentId::PatternFill(index),
+                    pattern.fill.iter().chain(stroke).collect::<Vec<_>>(),
+                )
+            } else {
+                let index = index - num_patterns;
+
+                (
+                    ContentId::Mask(index),
+                    data.masks[index].content.iter().collect(),
+                )
+            };
+
+            let mut children = vec![];
+
+            for command in commands {
+                nested_contents(command, &mut children);
+            }
+
+            for child in &children {
+                let invalid = match (owner, *child) {
+                    (ContentId::PatternFill(owner), ContentId::PatternFill(child))
+                    | (ContentId::Mask(owner), ContentId::Mask(child)) => child >= owner,
+                    _ => false,
+                };
+
+                if invalid {
+                    return Err(E::custom(format_args!(
+                        "{owner:?} contains {child:?}, which isn't recorded before it"
+                    )));
+                }
+            }
+
+            Ok(children.into_iter().filter_map(node).collect::<Vec<_>>())
+        };
+
+        // An iterative depth-first search, since the nesting of untrusted data
+        // can be arbitrarily deep.
+        let mut visited = vec![false; num_patterns + data.masks.len()];
+        let mut on_stack = vec![false; visited.len()];
+
+        for root in 0..visited.len() {
+            if visited[root] {
+                continue;
+            }
+
+            visited[root] = true;
+            on_stack[root] = true;
+            let mut stack = vec![(root, children(root)?)];
+
+            while let Some((node, children_of_node)) = stack.last_mut() {
+                let Some(child) = children_of_node.pop() else {
+                    on_stack[*node] = false;
+                    stack.pop();
+
+                    continue;
+                };
+
+                if on_stack[child] {
+                    return Err(E::custom(
+                        "the contents of a pattern or soft mask contain themselves",
+                    ));
+                }
+
+                if !visited[child] {
+                    visited[child] = true;
+                    on_stack[child] = true;
+                    stack.push((child, children(child)?));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collect the contents of the patterns and soft masks that a command uses.
+    fn nested_contents(command: &Command, contents: &mut Vec<ContentId>) {
+        let mut paint = |paint: &PaintData| {
+            if let PaintData::Pattern(index) = paint {
+                contents.push(ContentId::PatternFill(*index));
+            }
+        };
+
+        let mask = match command {
+            Command::DrawPath(_, props, _)
+            | Command::DrawRect(_, props, _)
+            | Command::DrawShading(_, props, _) => {
+                paint(&props.paint);
+                props.soft_mask
+            }
+            Command::DrawImage(image, props) => {
+                if let ImageRef::Stencil(_, stencil_paint) = image {
+                    paint(stencil_paint);
+                }
+
+                props.soft_mask
+            }
+            Command::PushTransparencyGroup(_, mask, _, _) => *mask,
+            Command::PushClipPath(_)
+            | Command::PushClipRect(_)
+            | Command::PopClip
+            | Command::PopTransparencyGroup
+            | Command::BeginMarkedContent(_)
+            | Command::EndMarkedContent
+            | Command::MarkedContentPoint(_) => None,
+        };
+
+        contents.extend(mask.map(ContentId::Mask));
+    }
+}
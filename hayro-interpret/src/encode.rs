@@ -1,12 +1,13 @@
 //! Encoding shading patterns for easy sampling.
 
+use crate::MIN_FLATNESS;
 use crate::color::{AlphaColor, ColorComponents, ColorSpace};
 use crate::interpret::state::ActiveTransferFunction;
 use crate::pattern::ShadingPattern;
-use crate::shading::{ShadingFunction, ShadingType, Triangle};
+use crate::shading::{ShadingFunction, ShadingLut, ShadingType, Triangle};
 use kurbo::{Affine, Point};
 use rustc_hash::FxHashMap;
-use smallvec::{ToSmallVec, smallvec};
+use smallvec::{SmallVec, ToSmallVec, smallvec};
 use std::sync::Arc;
 
 /// A shading pattern that was encoded so it can be sampled.
@@ -40,6 +41,7 @@ pub enum EncodedShadingType {
 pub struct EncodedFunctionBasedShading {
     pub(crate) domain: kurbo::Rect,
     pub(crate) function: ShadingFunction,
+    pub(crate) ranges: SmallVec<[(f32, f32); 4]>,
 }
 
 /// Encoded axial or radial shading.
@@ -49,6 +51,7 @@ pub struct EncodedRadialAxialShading {
     pub(crate) params: RadialAxialParams,
     pub(crate) domain: [f32; 2],
     pub(crate) extend: [bool; 2],
+    pub(crate) lut: Option<Arc<ShadingLut>>,
 }
 
 /// Encoded sampled mesh shading.
@@ -56,6 +59,7 @@ pub struct EncodedRadialAxialShading {
 pub struct EncodedSampledShading {
     pub(crate) samples: Arc<FxHashMap<(u16, u16), ColorComponents>>,
     pub(crate) function: Option<ShadingFunction>,
+    pub(crate) lut: Option<Arc<ShadingLut>>,
 }
 
 /// Encoded dummy shading.
@@ -68,21 +72,22 @@ impl EncodedShadingPattern {
     pub fn sample(&self, pos: Point) -> [f32; 4] {
         self.shading_type
             .eval(pos, self.background_color, &self.color_space)
-            .map(|v| {
-                let mut components = v.components();
-                components[3] *= self.opacity;
-
-                if let Some(tf) = &self.transfer_function {
-                    return tf.apply(&AlphaColor::new(components)).components();
-                }
-
-                components
-            })
+            .map(|v| self.finish_color(v.components()))
             .unwrap_or([0.0, 0.0, 0.0, 0.0])
     }
+
+    /// Apply the opacity and the transfer function of the pattern to a color.
+    pub(crate) fn finish_color(&self, components: [f32; 4]) -> [f32; 4] {
+        finish_color(components, self.opacity, self.transfer_function.as_ref())
+    }
 }
 
 impl ShadingPattern {
+    /// Apply the opacity and the transfer function of the pattern to a color.
+    pub(crate) fn finish_color(&self, components: [f32; 4]) -> [f32; 4] {
+        finish_color(components, self.opacity, self.transfer_function.as_ref())
+    }
+
     /// Encode the shading pattern.
     pub fn encode(&self) -> EncodedShadingPattern {
         let base_transform;
@@ -94,7 +99,7 @@ impl ShadingPattern {
                 function,
             } => {
                 base_transform = (self.matrix * *matrix).inverse();
-                encode_function_shading(domain, function)
+                encode_function_shading(domain, function, &self.shading.color_space)
             }
             ShadingType::RadialAxial {
                 coords,
@@ -103,8 +108,14 @@ impl ShadingPattern {
                 extend,
                 axial,
             } => {
-                let (encoded, initial_transform) =
-                    encode_axial_shading(*coords, *domain, function, *extend, *axial);
+                let (encoded, initial_transform) = encode_axial_shading(
+                    *coords,
+                    *domain,
+                    function,
+                    *extend,
+                    *axial,
+                    self.shading.lut.clone(),
+                );
 
                 base_transform = initial_transform * self.matrix.inverse();
 
@@ -122,12 +133,13 @@ impl ShadingPattern {
                 EncodedShadingType::Sampled(EncodedSampledShading {
                     samples: Arc::new(samples),
                     function: function.clone(),
+                    lut: self.shading.lut.clone(),
                 })
             }
             ShadingType::CoonsPatchMesh { patches, function } => {
                 let mut triangles = vec![];
                 for patch in patches {
-                    patch.to_triangles(&mut triangles);
+                    patch.to_triangles(self.patch_grid_size(&patch.control_points), &mut triangles);
                 }
 
                 let full_transform = self.matrix;
@@ -138,12 +150,13 @@ impl ShadingPattern {
                 EncodedShadingType::Sampled(EncodedSampledShading {
                     samples: Arc::new(samples),
                     function: function.clone(),
+                    lut: self.shading.lut.clone(),
                 })
             }
             ShadingType::TensorProductPatchMesh { patches, function } => {
                 let mut triangles = vec![];
                 for patch in patches {
-                    patch.to_triangles(&mut triangles);
+                    patch.to_triangles(self.patch_grid_size(&patch.control_points), &mut triangles);
                 }
 
                 let full_transform = self.matrix;
@@ -154,6 +167,7 @@ impl ShadingPattern {
                 EncodedShadingType::Sampled(EncodedSampledShading {
                     samples: Arc::new(samples),
                     function: function.clone(),
+                    lut: self.shading.lut.clone(),
                 })
             }
             ShadingType::Dummy => {
@@ -181,6 +195,54 @@ impl ShadingPattern {
             transfer_function: self.transfer_function.clone(),
         }
     }
+
+    /// Return the number of points per side of the grid that the patch with the
+    /// given control points is approximated with.
+    pub(crate) fn patch_grid_size(&self, control_points: &[Point]) -> usize {
+        // With the default smoothness of 0.01, the colors of patches are
+        // approximated by a grid of 20x20 points.
+        let color = (0.2 / self.smoothness).round();
+
+        // Strongly curved edges need a finer grid, especially when zooming in,
+        // so that they stay within the flatness tolerance in device space. The
+        // first twelve control points describe the edges of both kinds of patches.
+        let tolerance = self.flatness.max(MIN_FLATNESS) as f64;
+        let geometry = [[0, 1, 2, 3], [3, 4, 5, 6], [6, 7, 8, 9], [9, 10, 11, 0]]
+            .iter()
+            .filter_map(|edge| {
+                let points =
+                    edge.map(|i| control_points.get(i).map(|p| (self.matrix * *p).to_vec2()));
+                let [Some(p0), Some(p1), Some(p2), Some(p3)] = points else {
+                    return None;
+                };
+
+                // The number of line segments that approximate a cubic Bézier curve
+                // within the tolerance, according to Wang's formula.
+                let dd = (p0 - p1 * 2.0 + p2)
+                    .hypot()
+                    .max((p1 - p2 * 2.0 + p3).hypot());
+                let segments = (0.75 * dd / tolerance).sqrt().ceil();
+
+                Some(segments + 1.0)
+            })
+            .fold(0.0, f64::max);
+
+        (color.max(geometry as f32) as usize).clamp(2, 100)
+    }
+}
+
+fn finish_color(
+    mut components: [f32; 4],
+    opacity: f32,
+    transfer_function: Option<&ActiveTransferFunction>,
+) -> [f32; 4] {
+    components[3] *= opacity;
+
+    if let Some(tf) = transfer_function {
+        return tf.apply(&AlphaColor::new(components)).components();
+    }
+
+    components
 }
 
 fn encode_axial_shading(
@@ -189,6 +251,7 @@ fn encode_axial_shading(
     function: &ShadingFunction,
     extend: [bool; 2],
     is_axial: bool,
+    lut: Option<Arc<ShadingLut>>,
 ) -> (EncodedShadingType, Affine) {
     let initial_transform;
 
@@ -222,6 +285,7 @@ fn encode_axial_shading(
             params,
             domain,
             extend,
+            lut,
         }),
         initial_transform,
     )
@@ -264,7 +328,11 @@ fn sample_triangles(
     map
 }
 
-fn encode_function_shading(domain: &[f32; 4], function: &ShadingFunction) -> EncodedShadingType {
+fn encode_function_shading(
+    domain: &[f32; 4],
+    function: &ShadingFunction,
+    color_space: &ColorSpace,
+) -> EncodedShadingType {
     let domain = kurbo::Rect::new(
         domain[0] as f64,
         domain[2] as f64,
@@ -275,6 +343,7 @@ fn encode_function_shading(domain: &[f32; 4], function: &ShadingFunction) -> Enc
     EncodedShadingType::FunctionBased(EncodedFunctionBasedShading {
         domain,
         function: function.clone(),
+        ranges: color_space.default_decode_arr(8.0),
     })
 }
 
@@ -292,12 +361,23 @@ impl EncodedShadingType {
         color_space: &ColorSpace,
     ) -> Option<AlphaColor> {
         match self {
-            Self::FunctionBased(EncodedFunctionBasedShading { domain, function }) => {
-                if !domain.contains(pos) {
+            Self::FunctionBased(EncodedFunctionBasedShading {
+                domain,
+                function,
+                ranges,
+            }) => {
+                // The domain is inclusive on all sides, so we can't use
+                // `Rect::contains` here.
+                if pos.x < domain.x0 || pos.x > domain.x1 || pos.y < domain.y0 || pos.y > domain.y1
+                {
                     Some(bg_color)
                 } else {
-                    let out = function.eval(&smallvec![pos.x as f32, pos.y as f32])?;
-                    // TODO: Clamp out-of-range values.
+                    let mut out = function.eval(&smallvec![pos.x as f32, pos.y as f32])?;
+
+                    for (val, (min, max)) in out.iter_mut().zip(ranges) {
+                        *val = val.max(*min).min(*max);
+                    }
+
                     Some(color_space.to_rgba(&out, 1.0, false))
                 }
             }
@@ -306,6 +386,7 @@ impl EncodedShadingType {
                 params,
                 domain,
                 extend,
+                lut,
             }) => {
                 let (t0, t1) = (domain[0], domain[1]);
 
@@ -336,15 +417,25 @@ impl EncodedShadingType {
 
                 let t = t0 + (t1 - t0) * t;
 
+                if let Some(lut) = lut {
+                    return Some(lut.get(t));
+                }
+
                 let val = function.eval(&smallvec![t])?;
 
                 Some(color_space.to_rgba(&val, 1.0, false))
             }
-            Self::Sampled(EncodedSampledShading { samples, function }) => {
+            Self::Sampled(EncodedSampledShading {
+                samples,
+                function,
+                lut,
+            }) => {
                 let sample_point = (pos.x as u16, pos.y as u16);
 
                 if let Some(color) = samples.get(&sample_point) {
-                    if let Some(function) = function {
+                    if let Some(lut) = lut {
+                        Some(lut.get(*color.first()?))
+                    } else if let Some(function) = function {
                         let val = function.eval(&color.to_smallvec())?;
                         Some(color_space.to_rgba(&val, 1.0, false))
                     } else {
@@ -385,65 +476,58 @@ fn radial_pos(
     min_extend: bool,
     max_extend: bool,
 ) -> Option<f32> {
-    let r0 = r.x as f32;
-    let dx = p1.x as f32;
-    let dy = p1.y as f32;
-    let dr = r.y as f32 - r0;
+    // We solve the equation in double precision, since the coefficients
+    // suffer from catastrophic cancellation otherwise, for example if the start
+    // circle touches the end circle from the inside.
+    let r0 = r.x;
+    let dx = p1.x;
+    let dy = p1.y;
+    let dr = r.y - r0;
 
-    let px = pos.x as f32;
-    let py = pos.y as f32;
+    let px = pos.x;
+    let py = pos.y;
 
     let a = dx * dx + dy * dy - dr * dr;
     let b = -2.0 * (px * dx + py * dy + r0 * dr);
     let c = px * px + py * py - r0 * r0;
 
-    let discriminant = b * b - 4.0 * a * c;
+    let is_valid =
+        |t: f64| (min_extend || t >= 0.0) && (max_extend || t <= 1.0) && r0 + dr * t >= 0.0;
 
-    // No solution available.
-    if discriminant < 0.0 {
-        return None;
-    }
-
-    if a.abs() < 1e-6 {
-        if b.abs() < 1e-6 {
+    // If the start circle touches the end circle, the equation becomes linear.
+    // Since the coordinates are stored with single precision, we need to use
+    // a tolerance relative to the size of the circles for this.
+    if a.abs() <= 1e-6 * (dx * dx + dy * dy + dr * dr) {
+        if b.abs() < 1e-12 {
             return None;
         }
 
         let t = -c / b;
 
-        if (!min_extend && t < 0.0) || (!max_extend && t > 1.0) {
-            return None;
-        }
-
-        let r_t = r0 + dr * t;
-        if r_t < 0.0 {
-            return None;
-        }
-
-        return Some(t);
+        return is_valid(t).then_some(t as f32);
     }
 
-    let sqrt_d = discriminant.sqrt();
-    let t1 = (-b - sqrt_d) / (2.0 * a);
-    let t2 = (-b + sqrt_d) / (2.0 * a);
-
-    let max = t1.max(t2);
-    let mut take_max = Some(max);
-    let min = t1.min(t2);
-    let mut take_min = Some(min);
+    let discriminant = b * b - 4.0 * a * c;
 
-    if (!min_extend && min < 0.0) || r0 + dr * min < 0.0 {
-        take_min = None;
+    // No solution available.
+    if discriminant < 0.0 {
+        return None;
     }
 
-    if (!max_extend && max > 1.0) || r0 + dr * max < 0.0 {
-        take_max = None;
-    }
+    // Use the numerically stable variant of the quadratic formula.
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    let t1 = q / a;
+    let t2 = if q == 0.0 { t1 } else { c / q };
+
+    // The circle with the larger t is painted on top, so prefer it if it is
+    // valid.
+    let (min, max) = (t1.min(t2), t1.max(t2));
 
-    match (take_min, take_max) {
-        (Some(_), Some(max)) => Some(max),
-        (Some(min), None) => Some(min),
-        (None, Some(max)) => Some(max),
-        (None, None) => None,
+    if is_valid(max) {
+        Some(max as f32)
+    } else if is_valid(min) {
+        Some(min as f32)
+    } else {
+        None
     }
 }
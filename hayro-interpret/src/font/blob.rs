@@ -2,7 +2,9 @@ use crate::font::UNITS_PER_EM;
 use crate::font::outline::OutlinePath;
 use kurbo::BezPath;
 use rustc_hash::FxHashMap;
-use skrifa::instance::{LocationRef, Size};
+use skrifa::attribute::{Attributes, Stretch, Style, Weight};
+use skrifa::color::ColorGlyph;
+use skrifa::instance::{Location, LocationRef, Size};
 use skrifa::metrics::GlyphMetrics;
 use skrifa::outline::{DrawSettings, Engine, HintingInstance, HintingOptions, Target};
 use skrifa::raw::TableProvider;
@@ -11,13 +13,14 @@ use skrifa::raw::ps::string::Sid;
 use skrifa::raw::ps::type1::Type1Font;
 use skrifa::raw::tables::post::DEFAULT_GLYPH_NAMES;
 use skrifa::raw::{FontData as ReadFontData, FontRead};
+use skrifa::setting::VariationSetting;
 use skrifa::{FontRef, GlyphId, MetadataProvider, OutlineGlyphCollection};
 use std::fmt::{Debug, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use yoke::{Yoke, Yokeable};
 
 type FontData = Arc<dyn AsRef<[u8]> + Send + Sync>;
-type OpenTypeFontYoke = Yoke<OTFYoke<'static>, FontData>;
+type OpenTypeFontYoke = Yoke<OTFYoke<'static>, Arc<OpenTypeFontCart>>;
 type CffFontYoke = Yoke<CFFYoke<'static>, FontData>;
 
 /// A font blob for type 1 fonts.
@@ -160,11 +163,48 @@ impl CffFontBlob {
     }
 }
 
+/// Check whether the font has any outline source we can read glyphs from.
+///
+/// Fonts without a CFF table need a `glyf` table with at least one glyph
+/// whose `loca` range actually lies within it, otherwise the font is most
+/// likely truncated and would render no text at all.
+fn has_usable_outlines(font: &FontRef<'_>) -> bool {
+    if font.cff().is_ok() || font.cff2().is_ok() {
+        return true;
+    }
+
+    let (Ok(glyf), Ok(loca)) = (font.glyf(), font.loca(None)) else {
+        return false;
+    };
+
+    let glyf_len = glyf.offset_data().len();
+    let mut has_outlines = false;
+
+    for idx in 0..loca.len() {
+        let (Some(start), Some(end)) = (loca.get_raw(idx), loca.get_raw(idx + 1)) else {
+            break;
+        };
+
+        if start < end {
+            if end as usize <= glyf_len {
+                return true;
+            }
+
+            has_outlines = true;
+        }
+    }
+
+    // A font where all glyphs are empty (for example a subset only containing
+    // spaces) is still valid.
+    !has_outlines
+}
+
 /// A font blob for OpenType fonts.
 #[derive(Clone)]
 pub(crate) struct OpenTypeFontBlob {
     yoke: Arc<OpenTypeFontYoke>,
     cff_blob: Option<CffFontBlob>,
+    glyph_to_unicode: Arc<OnceLock<FxHashMap<GlyphId, char>>>,
 }
 
 impl Debug for OpenTypeFontBlob {
@@ -175,6 +215,18 @@ impl Debug for OpenTypeFontBlob {
 
 impl OpenTypeFontBlob {
     pub(crate) fn new(data: FontData, index: u32) -> Option<Self> {
+        Self::new_with_variations(data, index, |_| Vec::new())
+    }
+
+    /// Create a new blob for an instance of a variable font.
+    ///
+    /// The callback returns the user coordinates of the axes the font should be
+    /// instantiated with. For fonts that aren't variable, it is never called.
+    pub(crate) fn new_with_variations(
+        data: FontData,
+        index: u32,
+        variations: impl FnOnce(&FontRef<'_>) -> Vec<VariationSetting>,
+    ) -> Option<Self> {
         // Check first whether the font is valid so we can unwrap in the closure.
         let f = FontRef::from_index(data.as_ref().as_ref(), index).ok()?;
         // Reject fonts with invalid post table version, fixes pdf.js issue 9462. Not sure if there
@@ -186,7 +238,7 @@ impl OpenTypeFontBlob {
             )
         });
 
-        if invalid {
+        if invalid || !has_usable_outlines(&f) {
             return None;
         }
 
@@ -199,15 +251,37 @@ impl OpenTypeFontBlob {
             .map(|cff| Arc::new(cff.offset_data().as_ref().to_vec()) as FontData)
             .and_then(CffFontBlob::new);
 
+        let axes = f.axes();
+        let variations = if axes.is_empty() {
+            Vec::new()
+        } else {
+            axes.filter(variations(&f))
+                .filter_map(|mut setting| {
+                    let axis = axes.get_by_tag(setting.selector)?;
+                    setting.value = setting.value.clamp(axis.min_value(), axis.max_value());
+
+                    Some(setting)
+                })
+                .collect::<Vec<_>>()
+        };
+        let location = axes.location(&variations);
+
+        let cart = Arc::new(OpenTypeFontCart {
+            data,
+            variations,
+            location,
+        });
+
         let font_ref_yoke =
-            Yoke::<OTFYoke<'static>, FontData>::attach_to_cart(data.clone(), |data| {
-                let font_ref = FontRef::from_index(data.as_ref(), index).unwrap();
+            Yoke::<OTFYoke<'static>, Arc<OpenTypeFontCart>>::attach_to_cart(cart, |cart| {
+                let font_ref = FontRef::from_index(cart.data.as_ref().as_ref(), index).unwrap();
+                let location = LocationRef::from(&cart.location);
 
                 let hinting_instance = if font_ref.outline_glyphs().require_interpreter() {
                     HintingInstance::new(
                         &font_ref.outline_glyphs(),
                         Size::new(UNITS_PER_EM),
-                        LocationRef::default(),
+                        location,
                         HintingOptions {
                             engine: Engine::Interpreter,
                             target: Target::Mono,
@@ -222,25 +296,54 @@ impl OpenTypeFontBlob {
                     font_ref: font_ref.clone(),
                     outline_glyphs: font_ref.outline_glyphs(),
                     hinting_instance,
-                    glyph_metrics: font_ref
-                        .glyph_metrics(Size::new(UNITS_PER_EM), LocationRef::default()),
+                    glyph_metrics: font_ref.glyph_metrics(Size::new(UNITS_PER_EM), location),
                 }
             });
 
         Some(Self {
             yoke: Arc::new(font_ref_yoke),
             cff_blob,
+            glyph_to_unicode: Arc::new(OnceLock::new()),
         })
     }
 
     pub(crate) fn font_data(&self) -> FontData {
-        self.yoke.backing_cart().clone()
+        self.yoke.backing_cart().data.clone()
     }
 
     pub(crate) fn font_ref(&self) -> &FontRef<'_> {
         &self.yoke.as_ref().get().font_ref
     }
 
+    /// Return the location of the instance in the design space of a variable font.
+    pub(crate) fn location(&self) -> LocationRef<'_> {
+        LocationRef::from(&self.yoke.backing_cart().location)
+    }
+
+    /// Return the attributes of the font, taking the selected instance of
+    /// variable fonts into account.
+    pub(crate) fn attributes(&self) -> Attributes {
+        let mut attributes = self.font_ref().attributes();
+
+        for setting in &self.yoke.backing_cart().variations {
+            match &setting.selector.to_be_bytes() {
+                b"wght" => attributes.weight = Weight::new(setting.value),
+                b"wdth" => attributes.stretch = Stretch::new(setting.value / 100.0),
+                b"ital" if setting.value >= 0.5 => attributes.style = Style::Italic,
+                b"slnt" if setting.value != 0.0 => {
+                    attributes.style = Style::Oblique(Some(setting.value));
+                }
+                _ => {}
+            }
+        }
+
+        attributes
+    }
+
+    pub(crate) fn color_glyph(&self, glyph: GlyphId) -> Option<ColorGlyph<'_>> {
+        self.font_ref().color_glyphs().get(glyph)
+    }
+
     pub(crate) fn glyph_metrics(&self) -> &GlyphMetrics<'_> {
         &self.yoke.as_ref().get().glyph_metrics
     }
@@ -289,6 +392,31 @@ impl OpenTypeFontBlob {
         glyph_names
     }
 
+    /// Map a glyph back to Unicode using the `cmap` table of the font.
+    pub(crate) fn glyph_to_unicode(&self, glyph: GlyphId) -> Option<char> {
+        self.glyph_to_unicode
+            .get_or_init(|| {
+                let mut map = FxHashMap::default();
+
+                for (codepoint, gid) in self.font_ref().charmap().mappings() {
+                    // Symbol fonts usually map into the private use area, which is
+                    // of no use for text extraction.
+                    if gid != GlyphId::NOTDEF
+                        && let Some(c) = char::from_u32(codepoint)
+                        && !('\u{E000}'..='\u{F8FF}').contains(&c)
+                    {
+                        // Mappings are sorted by code point, so if several code
+                        // points map to the same glyph we keep the lowest one.
+                        map.entry(gid).or_insert(c);
+                    }
+                }
+
+                map
+            })
+            .get(&glyph)
+            .copied()
+    }
+
     fn outline_glyphs(&self) -> &OutlineGlyphCollection<'_> {
         &self.yoke.as_ref().get().outline_glyphs
     }
@@ -306,7 +434,7 @@ impl OpenTypeFontBlob {
             // actually render wrongly if hinting is disabled!
             DrawSettings::hinted(instance, false)
         } else {
-            DrawSettings::unhinted(Size::new(UNITS_PER_EM), LocationRef::default())
+            DrawSettings::unhinted(Size::new(UNITS_PER_EM), self.location())
         };
 
         let Some(outline) = self.outline_glyphs().get(glyph) else {
@@ -318,6 +446,14 @@ impl OpenTypeFontBlob {
     }
 }
 
+/// The data an OpenType font blob borrows from.
+struct OpenTypeFontCart {
+    data: FontData,
+    /// The axis settings of the selected instance, in user coordinates.
+    variations: Vec<VariationSetting>,
+    location: Location,
+}
+
 #[derive(Yokeable, Clone)]
 struct OTFYoke<'a> {
     font_ref: FontRef<'a>,
@@ -1,10 +1,13 @@
+use crate::cache::Cache;
 use crate::font::blob::{CffFontBlob, OpenTypeFontBlob, Type1FontBlob};
 use crate::font::generated::glyph_names;
 use crate::font::standard_font::select_standard_font;
 use crate::font::{
-    FallbackFontQuery, FontFlags, FontQuery, read_to_unicode, stretch_glyph, strip_subset_prefix,
+    FallbackFontQuery, FontData, FontFallback, FontFlags, FontQuery, Script, SyntheticStyle,
+    load_fallback_font, read_embedded_cmap, read_font_program, read_predefined_cmap,
+    read_to_unicode, stretch_glyph, strip_subset_prefix,
 };
-use crate::{CMapResolverFn, CacheKey, FontResolverFn};
+use crate::{CMapResolverFn, CacheKey, FontResolverFn, InterpreterWarning, WarningSinkFn};
 use hayro_cmap::{BfString, CMap, CharacterCollection, CidFamily, WritingMode};
 use hayro_syntax::object;
 use hayro_syntax::object::Dict;
@@ -28,8 +31,8 @@ pub(crate) struct Type0Font {
     dw: f32,
     dw2: (f32, f32),
     widths: FxHashMap<u32, f32>,
-    encoding: CMap,
-    to_unicode: Option<CMap>,
+    encoding: Arc<CMap>,
+    to_unicode: Option<Arc<CMap>>,
     widths2: FxHashMap<u32, [f32; 3]>,
     cid_to_gid_map: CidToGIdMap,
     /// PostScript name from the PDF.
@@ -38,6 +41,8 @@ pub(crate) struct Type0Font {
     font_flags: Option<FontFlags>,
     /// Whether this font is using a fallback (non-embedded) font.
     fallback: bool,
+    /// The styles the fallback font lacks.
+    synthetic: SyntheticStyle,
     /// Whether the `to_unicode` map is a UCS2 `CMap` (CID-indexed) rather than
     /// a `ToUnicode` `CMap` (code-indexed).
     to_unicode_is_cid_indexed: bool,
@@ -47,9 +52,19 @@ impl Type0Font {
     pub(crate) fn new(
         dict: &Dict<'_>,
         font_resolver: &FontResolverFn,
+        font_fallbacks: &[FontFallback],
         cmap_resolver: &CMapResolverFn,
+        cache: &Cache,
+        warning_sink: &WarningSinkFn,
+        corrupt: &mut bool,
     ) -> Option<Self> {
-        let cmap = read_encoding(&dict.get::<Object<'_>>(ENCODING)?, cmap_resolver)?;
+        let cmap = read_encoding(
+            &dict.get::<Object<'_>>(ENCODING)?,
+            dict,
+            cmap_resolver,
+            cache,
+            warning_sink,
+        )?;
 
         let horizontal = cmap.metadata().writing_mode != Some(WritingMode::Vertical);
 
@@ -68,15 +83,34 @@ impl Type0Font {
             .filter(|cc| cc.family != CidFamily::AdobeIdentity)
             .or_else(|| read_cid_system_info(&descendant_font));
 
-        let (font_type, fallback, _is_standard_fallback) = match FontType::new(&font_descriptor) {
-            Some(ft) => (ft, false, false),
+        let (font_type, fallback, synthetic) = match FontType::new(&font_descriptor, cache) {
+            Some(ft) => (ft, false, SyntheticStyle::default()),
             None => {
-                let (query, is_standard) =
+                if [FONT_FILE, FONT_FILE2, FONT_FILE3]
+                    .iter()
+                    .any(|key| font_descriptor.contains_key(key))
+                {
+                    warning_sink(InterpreterWarning::corrupt_embedded_font(dict).into());
+                    *corrupt = true;
+                }
+
+                let script = character_collection
+                    .as_ref()
+                    .and_then(Script::from_character_collection);
+                // The font descriptor with the style of the font belongs to the descendant font.
+                let fallback_query = FallbackFontQuery::new(&descendant_font);
+
+                let (query, bold, italic) =
                     if let Some((standard, _)) = select_standard_font(dict, &font_descriptor) {
-                        (FontQuery::Standard(standard), true)
+                        (
+                            FontQuery::Standard(standard),
+                            standard.is_bold(),
+                            standard.is_italic(),
+                        )
                     } else {
-                        let mut query = FallbackFontQuery::new(dict);
+                        let mut query = fallback_query.clone();
                         query.character_collection = character_collection.clone();
+                        query.script = script;
 
                         warn!(
                             "unable to load CID font {} ({:?}), attempting fallback",
@@ -84,14 +118,36 @@ impl Type0Font {
                             dict.obj_id()
                         );
 
-                        (FontQuery::Fallback(query), false)
+                        let (bold, italic) = (query.is_bold, query.is_italic);
+
+                        (FontQuery::Fallback(query), bold, italic)
                     };
 
-                let (data, index) = font_resolver(&query)?;
-                let blob = OpenTypeFontBlob::new(data.clone(), index)
+                // Substitutes can be variable fonts, in which case we pick the instance
+                // that best matches the font descriptor.
+                let load = |data: FontData, index| {
+                    OpenTypeFontBlob::new_with_variations(data.clone(), index, |font| {
+                        fallback_query.variations(font)
+                    })
                     .map(FontType::OpenType)
-                    .or_else(|| CffFontBlob::new(data).map(FontType::Cff))?;
-                (blob, true, is_standard)
+                    .or_else(|| CffFontBlob::new(data).map(FontType::Cff))
+                };
+
+                let blob = match script
+                    .and_then(|script| load_fallback_font(font_fallbacks, script, load))
+                {
+                    Some(blob) => blob,
+                    None => {
+                        let (data, index) = font_resolver(&query)?;
+                        load(data, index)?
+                    }
+                };
+                let attributes = match &blob {
+                    FontType::OpenType(t) => Some(t.attributes()),
+                    FontType::Cff(_) | FontType::Type1(_) => None,
+                };
+
+                (blob, true, SyntheticStyle::new(bold, italic, attributes))
             }
         };
 
@@ -112,21 +168,17 @@ impl Type0Font {
         let cid_to_gid_map = CidToGIdMap::new(&descendant_font).unwrap_or_default();
         let cache_key = dict.cache_key();
 
-        let mut to_unicode = read_to_unicode(dict, cmap_resolver);
+        let mut to_unicode = read_to_unicode(dict, cmap_resolver, cache);
         let mut to_unicode_is_cid_indexed = false;
 
         // If there is no ToUnicode map, try to get the UCS2 CMap.
-        if fallback
-            && to_unicode.is_none()
+        if to_unicode.is_none()
             && let Some(cc) = character_collection.as_ref()
             && let Some(ucs2_name) = cc.family.ucs2_cmap()
-            && let Some(data) = (cmap_resolver)(ucs2_name)
+            && let Some(ucs2_cmap) = read_predefined_cmap(ucs2_name, cmap_resolver, cache)
         {
-            let resolver = cmap_resolver.clone();
-            if let Some(ucs2_cmap) = CMap::parse(data, move |n| (resolver)(n)) {
-                to_unicode = Some(ucs2_cmap);
-                to_unicode_is_cid_indexed = true;
-            }
+            to_unicode = Some(ucs2_cmap);
+            to_unicode_is_cid_indexed = true;
         }
 
         let postscript_name = dict
@@ -152,6 +204,7 @@ impl Type0Font {
             postscript_name,
             font_flags,
             fallback,
+            synthetic,
             to_unicode_is_cid_indexed,
         })
     }
@@ -258,12 +311,23 @@ impl Type0Font {
         None
     }
 
-    pub(crate) fn outline_glyph(&self, glyph: GlyphId, code: u32) -> BezPath {
-        let path = match &self.font_type {
+    /// Return the outline of the glyph, without adjusting it to the widths of the
+    /// PDF font.
+    pub(crate) fn base_outline_glyph(&self, glyph: GlyphId) -> BezPath {
+        match &self.font_type {
             FontType::OpenType(t) => t.outline_glyph(glyph),
             FontType::Cff(c) => c.outline_glyph(glyph),
             FontType::Type1(t) => t.outline_glyph(glyph),
-        };
+        }
+    }
+
+    pub(crate) fn outline_glyph(&self, glyph: GlyphId, code: u32) -> BezPath {
+        self.synthetic
+            .apply(self.stretched_outline_glyph(glyph, code))
+    }
+
+    fn stretched_outline_glyph(&self, glyph: GlyphId, code: u32) -> BezPath {
+        let path = self.base_outline_glyph(glyph);
 
         if self.fallback
             && let FontType::OpenType(t) = &self.font_type
@@ -279,7 +343,18 @@ impl Type0Font {
         path
     }
 
-    pub(crate) fn font_data(&self) -> Option<crate::font::FontData> {
+    pub(crate) fn synthetic_style(&self) -> SyntheticStyle {
+        self.synthetic
+    }
+
+    pub(crate) fn open_type_blob(&self) -> Option<&OpenTypeFontBlob> {
+        match &self.font_type {
+            FontType::OpenType(t) => Some(t),
+            FontType::Cff(_) | FontType::Type1(_) => None,
+        }
+    }
+
+    pub(crate) fn font_data(&self) -> Option<FontData> {
         match &self.font_type {
             FontType::OpenType(t) => Some(t.font_data()),
             FontType::Cff(c) => Some(c.font_data()),
@@ -298,7 +373,7 @@ impl Type0Font {
     pub(crate) fn weight(&self) -> Option<u32> {
         match &self.font_type {
             FontType::OpenType(t) => {
-                let weight = t.font_ref().attributes().weight.value().round() as u32;
+                let weight = t.attributes().weight.value().round() as u32;
                 if weight > 0 { Some(weight) } else { None }
             }
             FontType::Cff(_) | FontType::Type1(_) => None,
@@ -313,7 +388,7 @@ impl Type0Font {
             return true;
         }
         match &self.font_type {
-            FontType::OpenType(t) => t.font_ref().attributes().style != Style::Normal,
+            FontType::OpenType(t) => t.attributes().style != Style::Normal,
             FontType::Cff(_) | FontType::Type1(_) => false,
         }
     }
@@ -398,10 +473,19 @@ impl Type0Font {
             } else {
                 code
             };
-            return to_unicode.lookup_bf_string(key);
+
+            if let Some(c) = to_unicode.lookup_bf_string(key) {
+                return Some(c);
+            }
         }
 
-        None
+        // As a last resort, try to map the glyph back to Unicode using the
+        // cmap table of the font. This is mostly useful for subsetted CID fonts
+        // with an `Identity` encoding.
+        match &self.font_type {
+            FontType::OpenType(o) => o.glyph_to_unicode(self.map_code(code)).map(BfString::Char),
+            _ => None,
+        }
     }
 }
 
@@ -411,7 +495,7 @@ impl CacheKey for Type0Font {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum FontType {
     /// An OpenType font.
     OpenType(OpenTypeFontBlob),
@@ -423,36 +507,34 @@ enum FontType {
 }
 
 impl FontType {
-    fn new(descriptor: &Dict<'_>) -> Option<Self> {
+    fn new(descriptor: &Dict<'_>, cache: &Cache) -> Option<Self> {
         // PDF has a distinction between CIDFontType0 and CIDFontType 2, with
         // specific requirements what font type can appear where. However, some
         // PDFs use wrong metadata, so we simply bruteforce without even looking
         // at the metadata.
 
-        let data = descriptor
+        let stream = descriptor
             .get::<Stream<'_>>(FONT_FILE2)
             .or_else(|| descriptor.get::<Stream<'_>>(FONT_FILE3))
             // See PDFBOX-2599. Apparently, Acrobat accepts this as well.
-            .or_else(|| descriptor.get::<Stream<'_>>(FONT_FILE))?
-            .decoded()
-            .ok()?;
-
-        let data = Arc::new(data.into_owned());
-
-        let parsed = if let Ok(_font_ref) = FontRef::from_index(data.as_ref(), 0) {
-            // It's an OpenType font, either TrueType or CFF.
-            Self::OpenType(OpenTypeFontBlob::new(data, 0)?)
-        } else if let Some(cff) = CffFontBlob::new(data.clone()) {
-            // It's a CFF font.
-            Self::Cff(cff)
-        } else if let Some(t1) = Type1FontBlob::new(data) {
-            // It's a Type1 (PFB) font.
-            Self::Type1(t1)
-        } else {
-            return None;
-        };
+            .or_else(|| descriptor.get::<Stream<'_>>(FONT_FILE))?;
+
+        read_font_program(&stream, cache, |data| {
+            let parsed = if let Ok(_font_ref) = FontRef::from_index(data.as_ref(), 0) {
+                // It's an OpenType font, either TrueType or CFF.
+                Self::OpenType(OpenTypeFontBlob::new(data, 0)?)
+            } else if let Some(cff) = CffFontBlob::new(data.clone()) {
+                // It's a CFF font.
+                Self::Cff(cff)
+            } else if let Some(t1) = Type1FontBlob::new(data) {
+                // It's a Type1 (PFB) font.
+                Self::Type1(t1)
+            } else {
+                return None;
+            };
 
-        Some(parsed)
+            Some(parsed)
+        })
     }
 }
 
@@ -567,26 +649,63 @@ fn read_cid_system_info(descendant_font: &Dict<'_>) -> Option<CharacterCollectio
     Some(CharacterCollection { family, supplement })
 }
 
-fn read_encoding(object: &Object<'_>, cmap_resolver: &CMapResolverFn) -> Option<CMap> {
-    // TODO: Support fetching CMaps referenced via `usecmap` in the PDF.
+fn read_encoding(
+    object: &Object<'_>,
+    font: &Dict<'_>,
+    cmap_resolver: &CMapResolverFn,
+    cache: &Cache,
+    warning_sink: &WarningSinkFn,
+) -> Option<Arc<CMap>> {
+    read_cmap(object, font, cmap_resolver, cache, warning_sink, 0)
+}
+
+/// The maximum number of nested `UseCMap` entries, the same limit as the
+/// one for the `usecmap` operator.
+const MAX_USE_CMAP_DEPTH: u32 = 16;
+
+fn read_cmap(
+    object: &Object<'_>,
+    font: &Dict<'_>,
+    cmap_resolver: &CMapResolverFn,
+    cache: &Cache,
+    warning_sink: &WarningSinkFn,
+    depth: u32,
+) -> Option<Arc<CMap>> {
     match object {
         Object::Name(n) => {
             let cmap_type = hayro_cmap::CMapName::from_bytes(n.deref());
             match cmap_type {
-                hayro_cmap::CMapName::IdentityH => Some(CMap::identity_h()),
-                hayro_cmap::CMapName::IdentityV => Some(CMap::identity_v()),
+                hayro_cmap::CMapName::IdentityH => Some(Arc::new(CMap::identity_h())),
+                hayro_cmap::CMapName::IdentityV => Some(Arc::new(CMap::identity_v())),
                 _ => {
-                    let data = (cmap_resolver)(cmap_type)?;
-                    let resolver = cmap_resolver.clone();
-                    CMap::parse(data, move |n| (resolver)(n))
+                    let cmap = read_predefined_cmap(cmap_type, cmap_resolver, cache);
+
+                    if cmap.is_none() {
+                        // Without the predefined cmap, we can't tell which glyphs to draw.
+                        warning_sink(InterpreterWarning::unsupported_font(font).into());
+                    }
+
+                    cmap
                 }
             }
         }
-        Object::Stream(s) => {
-            let decoded = s.decoded().ok()?;
+        Object::Stream(s) => read_embedded_cmap(s, cache, |data| {
             let resolver = cmap_resolver.clone();
-            CMap::parse(&decoded, move |n| (resolver)(n))
-        }
+            let cmap = CMap::parse(data, move |n| (resolver)(n))?;
+
+            // Embedded cmaps can reference their base cmap in the stream dictionary
+            // instead of using the `usecmap` operator.
+            let base = s
+                .dict()
+                .get::<Object<'_>>(USE_CMAP)
+                .filter(|_| depth < MAX_USE_CMAP_DEPTH)
+                .and_then(|o| read_cmap(&o, font, cmap_resolver, cache, warning_sink, depth + 1));
+
+            Some(match base {
+                Some(base) => cmap.with_base(Arc::unwrap_or_clone(base)),
+                None => cmap,
+            })
+        }),
         _ => None,
     }
 }
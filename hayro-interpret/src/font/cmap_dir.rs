@@ -0,0 +1,90 @@
+//! Loading predefined cmaps from a directory.
+
+use crate::CMapResolverFn;
+use hayro_cmap::CMapName;
+use rustc_hash::FxHashMap;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A cmap resolver that loads predefined cmaps from the files in a directory.
+///
+/// This is an alternative to the `embed-cmaps` feature for applications that
+/// want to keep their binary small, but still need to support all CJK encodings.
+/// The directory is expected to contain cmap files as distributed by Adobe in the
+/// `cmap-resources` repository, named after the cmap (like `UniGB-UCS2-H`) with
+/// an optional `.cmap` extension. Files in the binary format of `embed-cmaps`
+/// are accepted as well, provided that the feature is enabled.
+///
+/// Files are only read the first time their cmap is requested, and their contents
+/// are kept for as long as the resolver is alive. Cmaps that can't be found are
+/// looked up again on the next request, so that names from untrusted documents
+/// don't accumulate in memory.
+///
+/// Use [`DirectoryCMapResolver::into_resolver`] to turn it into a
+/// [`CMapResolverFn`] for the [`InterpreterSettings`](crate::InterpreterSettings).
+pub struct DirectoryCMapResolver {
+    dir: PathBuf,
+    /// The data of the cmaps that have been loaded so far.
+    cmaps: Mutex<FxHashMap<Vec<u8>, Arc<[u8]>>>,
+}
+
+impl DirectoryCMapResolver {
+    /// Create a new resolver for the cmap files in the given directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            cmaps: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// Return the directory the cmaps are loaded from.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Return the data of the cmap with the given name, loading it if necessary.
+    pub fn resolve(&self, name: CMapName<'_>) -> Option<Arc<[u8]>> {
+        let name = name.to_bytes();
+        let mut cmaps = self.cmaps.lock().unwrap();
+
+        if let Some(data) = cmaps.get(name) {
+            return Some(data.clone());
+        }
+
+        let data: Arc<[u8]> = self.load(name)?.into();
+        cmaps.insert(name.to_vec(), data.clone());
+
+        Some(data)
+    }
+
+    /// Convert the resolver into a callback that can be used in the interpreter
+    /// settings.
+    ///
+    /// The callback returns a copy of the loaded data, since the interpreter only
+    /// parses each cmap once per [`InterpreterCache`](crate::InterpreterCache).
+    pub fn into_resolver(self) -> CMapResolverFn {
+        Arc::new(move |name| self.resolve(name).map(|data| Cow::Owned(data.to_vec())))
+    }
+
+    fn load(&self, name: &[u8]) -> Option<Vec<u8>> {
+        // The name comes from the PDF, so make sure it can't be used to read files
+        // outside of the directory.
+        let name = std::str::from_utf8(name).ok()?;
+
+        if name.is_empty()
+            || name.starts_with('.')
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+        {
+            warn!("ignoring invalid cmap name {}", name);
+
+            return None;
+        }
+
+        [name.to_string(), format!("{name}.cmap")]
+            .iter()
+            .find_map(|file| std::fs::read(self.dir.join(file)).ok())
+    }
+}
@@ -0,0 +1,709 @@
+//! Drawing color glyphs defined in the `COLR` table of OpenType fonts.
+//!
+//! Glyphs that are only defined in the `SVG ` table of a font are not supported
+//! and are drawn with their outline instead.
+
+use crate::color::{AlphaColor, Color, ColorComponents};
+use crate::device::Device;
+use crate::font::UNITS_PER_EM;
+use crate::font::blob::OpenTypeFontBlob;
+use crate::function::{Function, Values};
+use crate::gradient::{AxialGradient, Mesh, RadialGradient};
+use crate::pattern::{Pattern, ShadingPattern};
+use crate::shading::{Shading, ShadingFunction, ShadingType, Triangle, TriangleVertex};
+use crate::util::hash128;
+use crate::{BlendMode, ClipPath, DrawMode, DrawProps, FillRule, Paint, RenderQuality};
+use kurbo::{Affine, BezPath, Point, Rect, Shape, Vec2};
+use skrifa::color::{
+    Brush, ColorPainter, ColorPalette, ColorStop, CompositeMode, Extend, Transform,
+};
+use skrifa::raw::TableProvider;
+use skrifa::raw::types::BoundingBox;
+use skrifa::{GlyphId, MetadataProvider};
+use std::sync::Arc;
+
+/// The palette index that refers to the current text color instead of a palette entry.
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+/// The maximum number of periods that a repeating or reflecting color line is
+/// unrolled into.
+const MAX_PERIODS: f64 = 64.0;
+/// The maximum angle in degrees that is covered by a single triangle of a
+/// sweep gradient.
+const SWEEP_STEP: f64 = 5.0;
+
+/// The offset and the RGB color of a stop of a color line.
+type Stop = (f32, [f32; 3]);
+
+/// Draw the color glyph with the given ID to the device.
+///
+/// The soft mask and the blend mode of `props` are applied to the glyph as a
+/// whole, by drawing its layers into a transparency group.
+///
+/// Returns `None` if the font doesn't define a color glyph for it.
+pub(crate) fn draw_color_glyph<'a>(
+    blob: &OpenTypeFontBlob,
+    glyph: GlyphId,
+    glyph_transform: Affine,
+    props: &DrawProps<'a>,
+    smoothness: f32,
+    device: &mut impl Device<'a>,
+) -> Option<()> {
+    let font_ref = blob.font_ref();
+    let color_glyph = blob.color_glyph(glyph)?;
+    let upem = font_ref.head().ok()?.units_per_em();
+    let palettes = font_ref.color_palettes();
+
+    let transform = props.transform * glyph_transform;
+    let group = props.soft_mask.is_some() || props.blend_mode != BlendMode::Normal;
+
+    if group {
+        device.push_transparency_group(1.0, props.soft_mask.clone(), props.blend_mode);
+    }
+
+    let mut painter = Painter {
+        device,
+        blob,
+        palette: palettes.get(0),
+        paint: &props.paint,
+        flatness: props.flatness,
+        smoothness,
+        upem: upem as f64,
+        transforms: vec![transform * Affine::scale(UNITS_PER_EM as f64 / upem as f64)],
+        clips: vec![],
+        layers: 0,
+    };
+
+    if color_glyph.paint(blob.location(), &mut painter).is_err() {
+        warn!("failed to draw color glyph {}", glyph.to_u32());
+    }
+
+    // Make sure that the clip and blend stacks are balanced even if painting was aborted.
+    while painter.clips.pop().is_some() {
+        painter.device.pop_clip();
+    }
+
+    for _ in 0..painter.layers {
+        painter.device.pop_transparency_group();
+    }
+
+    if group {
+        painter.device.pop_transparency_group();
+    }
+
+    Some(())
+}
+
+struct Painter<'a, 'b, D: Device<'a>> {
+    device: &'b mut D,
+    blob: &'b OpenTypeFontBlob,
+    palette: Option<ColorPalette<'b>>,
+    paint: &'b Paint<'a>,
+    flatness: Option<f32>,
+    smoothness: f32,
+    upem: f64,
+    /// The transforms from font units to user space.
+    transforms: Vec<Affine>,
+    /// The bounding boxes of the active clips in device space.
+    clips: Vec<Rect>,
+    /// The number of transparency groups that have been pushed.
+    layers: usize,
+}
+
+impl<'a, D: Device<'a>> Painter<'a, '_, D> {
+    fn transform(&self) -> Affine {
+        *self.transforms.last().unwrap()
+    }
+
+    /// Return the outline of a glyph in font units.
+    fn glyph_outline(&self, glyph: GlyphId) -> BezPath {
+        Affine::scale(self.upem / UNITS_PER_EM as f64) * self.blob.outline_glyph(glyph)
+    }
+
+    fn push_clip(&mut self, path: BezPath) {
+        let path = self.transform() * path;
+        let bbox = path.bounding_box();
+        let bbox = self.clips.last().map_or(bbox, |last| last.intersect(bbox));
+
+        self.device.push_clip_path(&ClipPath {
+            path,
+            fill: FillRule::NonZero,
+        });
+        self.clips.push(bbox);
+    }
+
+    /// Fill a path in font units with a brush, whose coordinates are mapped into
+    /// font units by `brush_transform`.
+    fn fill_path(&mut self, path: &BezPath, brush: &Brush<'_>, brush_transform: Affine) {
+        // The soft mask and the blend mode of the text are applied by the group
+        // around the whole glyph instead of to each layer.
+        let props = |paint| DrawProps {
+            transform: self.transform(),
+            paint,
+            soft_mask: None,
+            blend_mode: BlendMode::Normal,
+            flatness: self.flatness,
+        };
+        let draw_mode = DrawMode::Fill(FillRule::NonZero);
+
+        let color_stops = match brush {
+            Brush::Solid {
+                palette_index,
+                alpha,
+            } => {
+                self.fill_solid(path, *palette_index, *alpha);
+
+                return;
+            }
+            Brush::LinearGradient { color_stops, .. }
+            | Brush::RadialGradient { color_stops, .. }
+            | Brush::SweepGradient { color_stops, .. } => color_stops,
+        };
+
+        let Some(pattern) = self.gradient_pattern(path, brush, brush_transform) else {
+            // The gradient can't be represented by a shading, for example because
+            // the foreground color is a pattern, so use the color of its last stop.
+            let (palette_index, alpha) = color_stops
+                .last()
+                .map_or((FOREGROUND_PALETTE_INDEX, 1.0), |stop| {
+                    (stop.palette_index, stop.alpha)
+                });
+            self.fill_solid(path, palette_index, alpha);
+
+            return;
+        };
+
+        let paint = Paint::Pattern(Box::new(Pattern::Shading(pattern.clone())));
+
+        if let Some(gradient) = AxialGradient::new(&pattern) {
+            self.device
+                .draw_axial_gradient(path, &gradient, props(paint), &draw_mode);
+        } else if let Some(gradient) = RadialGradient::new(&pattern) {
+            self.device
+                .draw_radial_gradient(path, &gradient, props(paint), &draw_mode);
+        } else if let Some(mesh) = Mesh::new(&pattern) {
+            self.device.draw_mesh(path, &mesh, props(paint), &draw_mode);
+        } else {
+            self.device.draw_path(path, props(paint), &draw_mode);
+        }
+    }
+
+    /// Convert a gradient brush into a shading pattern that covers `path`.
+    ///
+    /// Linear and radial gradients are converted into axial and radial shadings,
+    /// with repeating and reflecting color lines unrolled over the area of the
+    /// path. Sweep gradients are approximated with a triangle mesh, see
+    /// [`sweep_triangles`]. Since shadings can't vary their opacity, the alpha of
+    /// the stops is averaged.
+    fn gradient_pattern(
+        &self,
+        path: &BezPath,
+        brush: &Brush<'_>,
+        brush_transform: Affine,
+    ) -> Option<ShadingPattern> {
+        if brush_transform.determinant().abs() < 1e-9 {
+            return None;
+        }
+
+        // The corners of the area that needs to be covered, in brush space.
+        let area = brush_transform
+            .inverse()
+            .transform_rect_bbox(path.bounding_box());
+        let corners = [
+            Point::new(area.x0, area.y0),
+            Point::new(area.x1, area.y0),
+            Point::new(area.x0, area.y1),
+            Point::new(area.x1, area.y1),
+        ];
+
+        // The parameters of the shading, which identify it together with its stops.
+        let (shading_type, params, stops, alpha) = match brush {
+            Brush::Solid { .. } => return None,
+            Brush::LinearGradient {
+                p0,
+                p1,
+                color_stops,
+                extend,
+            } => {
+                let (stops, alpha) = self.resolve_stops(color_stops)?;
+                let p0 = Point::new(p0.x as f64, p0.y as f64);
+                let axis = Point::new(p1.x as f64, p1.y as f64) - p0;
+
+                if axis.hypot2() < 1e-9 {
+                    return None;
+                }
+
+                let (t0, t1) = if *extend == Extend::Pad {
+                    (0.0, 1.0)
+                } else {
+                    let t = corners.map(|c| (c - p0).dot(axis) / axis.hypot2());
+                    let t0 = t.into_iter().fold(f64::MAX, f64::min).max(-MAX_PERIODS);
+                    let t1 = t.into_iter().fold(f64::MIN, f64::max).min(MAX_PERIODS);
+
+                    if t1 - t0 < 1e-6 { (0.0, 1.0) } else { (t0, t1) }
+                };
+
+                let (start, end) = (p0 + axis * t0, p0 + axis * t1);
+                let shading_type = ShadingType::RadialAxial {
+                    coords: [start.x, start.y, end.x, end.y, 0.0, 0.0].map(|c| c as f32),
+                    domain: [0.0, 1.0],
+                    function: unrolled_color_line(&stops, *extend, t0, t1)?,
+                    extend: [true, true],
+                    axial: true,
+                };
+
+                (
+                    shading_type,
+                    vec![t0, t1, start.x, start.y, end.x, end.y],
+                    stops,
+                    alpha,
+                )
+            }
+            Brush::RadialGradient {
+                c0,
+                r0,
+                c1,
+                r1,
+                color_stops,
+                extend,
+            } => {
+                let (stops, alpha) = self.resolve_stops(color_stops)?;
+                let c0 = Point::new(c0.x as f64, c0.y as f64);
+                let delta = Point::new(c1.x as f64, c1.y as f64) - c0;
+                let (r0, dr) = (*r0 as f64, (*r1 - *r0) as f64);
+                let center = |t: f64| c0 + delta * t;
+                let radius = |t: f64| r0 + dr * t;
+
+                let (mut t0, mut t1) = (0.0, 1.0);
+
+                if *extend != Extend::Pad {
+                    // Unroll the color line until the circles cover the whole area.
+                    let covers = |t: f64| {
+                        corners
+                            .iter()
+                            .all(|c| (*c - center(t)).hypot() <= radius(t))
+                    };
+
+                    while t1 < MAX_PERIODS && !covers(t1) {
+                        t1 += 1.0;
+                    }
+
+                    if dr > 0.0 {
+                        t0 = (-r0 / dr).max(-MAX_PERIODS);
+                    }
+                }
+
+                // Circles with a negative radius aren't drawn, so the color line
+                // is cut off where the radius becomes zero.
+                if dr != 0.0 {
+                    let zero = -r0 / dr;
+
+                    if radius(t0) < 0.0 {
+                        t0 = zero;
+                    }
+
+                    if radius(t1) < 0.0 {
+                        t1 = zero;
+                    }
+                }
+
+                if t1 - t0 < 1e-6 || radius(t0) < 0.0 {
+                    return None;
+                }
+
+                let (start, end) = (center(t0), center(t1));
+                let shading_type = ShadingType::RadialAxial {
+                    coords: [start.x, start.y, radius(t0), end.x, end.y, radius(t1)]
+                        .map(|c| c as f32),
+                    domain: [0.0, 1.0],
+                    function: unrolled_color_line(&stops, *extend, t0, t1)?,
+                    extend: [true, true],
+                    axial: false,
+                };
+                let params = vec![
+                    t0,
+                    t1,
+                    start.x,
+                    start.y,
+                    end.x,
+                    end.y,
+                    radius(t0),
+                    radius(t1),
+                ];
+
+                (shading_type, params, stops, alpha)
+            }
+            Brush::SweepGradient {
+                c0,
+                start_angle,
+                end_angle,
+                color_stops,
+                extend,
+            } => {
+                let (stops, alpha) = self.resolve_stops(color_stops)?;
+                let center = Point::new(c0.x as f64, c0.y as f64);
+                let shading_type = ShadingType::TriangleMesh {
+                    triangles: sweep_triangles(
+                        center,
+                        &corners,
+                        (*start_angle as f64, *end_angle as f64),
+                        &stops,
+                        *extend,
+                    ),
+                    function: None,
+                };
+                let mut params = vec![center.x, center.y, *start_angle as f64, *end_angle as f64];
+                params.extend(corners.iter().flat_map(|c| [c.x, c.y]));
+
+                (shading_type, params, stops, alpha)
+            }
+        };
+
+        let extend = match brush {
+            Brush::LinearGradient { extend, .. }
+            | Brush::RadialGradient { extend, .. }
+            | Brush::SweepGradient { extend, .. } => *extend as u8,
+            Brush::Solid { .. } => 0,
+        };
+        let cache_key = hash128(&(
+            params.iter().map(|p| p.to_bits()).collect::<Vec<_>>(),
+            extend,
+            stops
+                .iter()
+                .map(|(offset, color)| (offset.to_bits(), color.map(f32::to_bits)))
+                .collect::<Vec<_>>(),
+        ));
+        let shading = Shading::new_rgb(cache_key, shading_type, None, None);
+
+        Some(ShadingPattern {
+            shading: Arc::new(shading),
+            matrix: self.transform() * brush_transform,
+            opacity: alpha,
+            transfer_function: None,
+            smoothness: self.smoothness,
+            flatness: self.flatness.unwrap_or(1.0),
+            gradient_subdivisions: RenderQuality::default().gradient_subdivisions,
+        })
+    }
+
+    /// Fill a path in font units with a palette color, whose alpha is multiplied
+    /// by `alpha`.
+    fn fill_solid(&mut self, path: &BezPath, palette_index: u16, alpha: f32) {
+        let draw_mode = DrawMode::Fill(FillRule::NonZero);
+        let props = |paint| DrawProps {
+            transform: self.transform(),
+            paint,
+            soft_mask: None,
+            blend_mode: BlendMode::Normal,
+            flatness: self.flatness,
+        };
+
+        match self.palette_color(palette_index) {
+            Some(color) => {
+                let [r, g, b, a] = color.components();
+                let paint = Paint::Color(Color::from_rgba(AlphaColor::new([r, g, b, a * alpha])));
+                self.device.draw_path(path, props(paint), &draw_mode);
+            }
+            // A foreground pattern can't carry the alpha, so it is applied by a group.
+            None if alpha < 1.0 => {
+                let props = props(self.paint.clone());
+                self.device
+                    .push_transparency_group(alpha, None, BlendMode::Normal);
+                self.device.draw_path(path, props, &draw_mode);
+                self.device.pop_transparency_group();
+            }
+            None => {
+                let props = props(self.paint.clone());
+                self.device.draw_path(path, props, &draw_mode);
+            }
+        }
+    }
+
+    /// Return the RGB colors of the stops and their average alpha, or `None` if
+    /// the foreground color is a pattern or there are no stops.
+    fn resolve_stops(&self, stops: &[ColorStop]) -> Option<(Vec<Stop>, f32)> {
+        let mut resolved = Vec::with_capacity(stops.len());
+        let mut alpha = 0.0;
+
+        for stop in stops {
+            let [r, g, b, a] = self.palette_color(stop.palette_index)?.components();
+            resolved.push((stop.offset, [r, g, b]));
+            alpha += a * stop.alpha / stops.len() as f32;
+        }
+
+        (!resolved.is_empty()).then_some((resolved, alpha))
+    }
+
+    /// Return the color for the given palette index, or `None` if the glyph should be
+    /// painted with a foreground pattern.
+    fn palette_color(&self, palette_index: u16) -> Option<AlphaColor> {
+        if palette_index != FOREGROUND_PALETTE_INDEX {
+            if let Some(c) = self
+                .palette
+                .as_ref()
+                .and_then(|p| p.colors().get(palette_index as usize))
+            {
+                return Some(AlphaColor::from_rgba8(c.red, c.green, c.blue, c.alpha));
+            }
+
+            warn!("missing palette entry {}", palette_index);
+        }
+
+        match self.paint {
+            Paint::Color(c) => Some(c.to_rgba()),
+            Paint::Pattern(_) => None,
+        }
+    }
+}
+
+impl<'a, D: Device<'a>> ColorPainter for Painter<'a, '_, D> {
+    fn push_transform(&mut self, transform: Transform) {
+        self.transforms
+            .push(self.transform() * convert_transform(transform));
+    }
+
+    fn pop_transform(&mut self) {
+        if self.transforms.len() > 1 {
+            self.transforms.pop();
+        }
+    }
+
+    fn push_clip_glyph(&mut self, glyph_id: GlyphId) {
+        let outline = self.glyph_outline(glyph_id);
+        self.push_clip(outline);
+    }
+
+    fn push_clip_box(&mut self, clip_box: BoundingBox<f32>) {
+        let rect = Rect::new(
+            clip_box.x_min as f64,
+            clip_box.y_min as f64,
+            clip_box.x_max as f64,
+            clip_box.y_max as f64,
+        );
+        self.push_clip(rect.to_path(0.1));
+    }
+
+    fn pop_clip(&mut self) {
+        if self.clips.pop().is_some() {
+            self.device.pop_clip();
+        }
+    }
+
+    fn fill(&mut self, brush: Brush<'_>) {
+        // Fill the whole clip area, mapped back into the current coordinate system.
+        let Some(clip) = self.clips.last() else {
+            return;
+        };
+
+        let transform = self.transform();
+
+        if transform.determinant().abs() < 1e-9 {
+            return;
+        }
+
+        let path = transform.inverse() * clip.to_path(0.1);
+        self.fill_path(&path, &brush, Affine::IDENTITY);
+    }
+
+    fn fill_glyph(
+        &mut self,
+        glyph_id: GlyphId,
+        brush_transform: Option<Transform>,
+        brush: Brush<'_>,
+    ) {
+        let brush_transform = brush_transform.map_or(Affine::IDENTITY, convert_transform);
+        let outline = self.glyph_outline(glyph_id);
+        self.fill_path(&outline, &brush, brush_transform);
+    }
+
+    fn push_layer(&mut self, composite_mode: CompositeMode) {
+        // Porter-Duff modes other than "source over" can't be represented with
+        // PDF blend modes, so they are approximated by normal compositing.
+        let blend_mode = match composite_mode {
+            CompositeMode::Multiply => BlendMode::Multiply,
+            CompositeMode::Screen => BlendMode::Screen,
+            CompositeMode::Overlay => BlendMode::Overlay,
+            CompositeMode::Darken => BlendMode::Darken,
+            CompositeMode::Lighten => BlendMode::Lighten,
+            CompositeMode::ColorDodge => BlendMode::ColorDodge,
+            CompositeMode::ColorBurn => BlendMode::ColorBurn,
+            CompositeMode::HardLight => BlendMode::HardLight,
+            CompositeMode::SoftLight => BlendMode::SoftLight,
+            CompositeMode::Difference => BlendMode::Difference,
+            CompositeMode::Exclusion => BlendMode::Exclusion,
+            CompositeMode::HslHue => BlendMode::Hue,
+            CompositeMode::HslSaturation => BlendMode::Saturation,
+            CompositeMode::HslColor => BlendMode::Color,
+            CompositeMode::HslLuminosity => BlendMode::Luminosity,
+            _ => BlendMode::Normal,
+        };
+
+        self.device.push_transparency_group(1.0, None, blend_mode);
+        self.layers += 1;
+    }
+
+    fn pop_layer(&mut self) {
+        if self.layers > 0 {
+            self.device.pop_transparency_group();
+            self.layers -= 1;
+        }
+    }
+}
+
+fn convert_transform(transform: Transform) -> Affine {
+    Affine::new([
+        transform.xx as f64,
+        transform.yx as f64,
+        transform.xy as f64,
+        transform.yy as f64,
+        transform.dx as f64,
+        transform.dy as f64,
+    ])
+}
+
+/// Map a position on the color line to the interval [0, 1] of the stops,
+/// according to the extend mode.
+fn wrap(t: f64, extend: Extend) -> f32 {
+    let t = match extend {
+        Extend::Repeat => t - t.floor(),
+        Extend::Reflect => {
+            let t = t.rem_euclid(2.0);
+
+            if t > 1.0 { 2.0 - t } else { t }
+        }
+        _ => t.clamp(0.0, 1.0),
+    };
+
+    t as f32
+}
+
+/// Return the color of the color line at the given position in [0, 1].
+fn sample(stops: &[Stop], t: f32) -> [f32; 3] {
+    let Some(next) = stops.iter().position(|(offset, _)| *offset > t) else {
+        return stops[stops.len() - 1].1;
+    };
+
+    if next == 0 {
+        return stops[0].1;
+    }
+
+    let ((o0, c0), (o1, c1)) = (stops[next - 1], stops[next]);
+    let frac = (t - o0) / (o1 - o0);
+
+    std::array::from_fn(|i| c0[i] + (c1[i] - c0[i]) * frac)
+}
+
+/// Create a function for inputs in [0, 1] that is equivalent to the color line on
+/// the interval [t0, t1], which might span several periods of a repeating or
+/// reflecting color line.
+fn unrolled_color_line(
+    stops: &[Stop],
+    extend: Extend,
+    t0: f64,
+    t1: f64,
+) -> Option<ShadingFunction> {
+    let mut unrolled: Vec<(f32, Values)> = vec![];
+    let mut push = |t: f64, color: [f32; 3]| {
+        unrolled.push((((t - t0) / (t1 - t0)) as f32, Values::from_slice(&color)));
+    };
+
+    push(t0, sample(stops, wrap(t0, extend)));
+
+    let periods = if extend == Extend::Pad {
+        0..1
+    } else {
+        t0.floor() as i32..t1.ceil() as i32
+    };
+
+    for period in periods {
+        let reflected = extend == Extend::Reflect && period % 2 != 0;
+        let mut period_stops = stops.to_vec();
+
+        if reflected {
+            period_stops.reverse();
+        }
+
+        for (offset, color) in period_stops {
+            let offset = offset as f64;
+            let t = period as f64 + if reflected { 1.0 - offset } else { offset };
+
+            if t > t0 && t < t1 {
+                push(t, color);
+            }
+        }
+    }
+
+    push(t1, sample(stops, wrap(t1, extend)));
+
+    Function::linear_stops(&unrolled).map(ShadingFunction::Single)
+}
+
+/// Approximate a sweep gradient around `center` with a fan of triangles that
+/// covers the given corners.
+///
+/// The angles are in degrees and, as reported by skrifa, go in clockwise
+/// direction in font units. For example, a sweep that the font defines
+/// counter-clockwise from 180 to 360 degrees is reported as going clockwise from
+/// 0 to 180 degrees with reversed stops. Only the range from 0 to 360 degrees is
+/// drawn.
+fn sweep_triangles(
+    center: Point,
+    corners: &[Point],
+    (start, end): (f64, f64),
+    stops: &[Stop],
+    extend: Extend,
+) -> Vec<Triangle> {
+    let color_at = |angle: f64| {
+        let t = if (end - start).abs() < 1e-9 {
+            if angle < start { 0.0 } else { 1.0 }
+        } else {
+            (angle - start) / (end - start)
+        };
+
+        sample(stops, wrap(t, extend))
+    };
+    let max_distance = corners
+        .iter()
+        .map(|c| (*c - center).hypot())
+        .fold(0.0, f64::max);
+    // Make sure that the chords of the triangles are outside of the area as well.
+    let radius = max_distance / (SWEEP_STEP.to_radians() / 2.0).cos() + 1.0;
+    let point = |angle: f64| {
+        let angle = angle.to_radians();
+        center + Vec2::new(angle.cos(), -angle.sin()) * radius
+    };
+    let vertex =
+        |point, color: [f32; 3]| TriangleVertex::new(point, ColorComponents::from_slice(&color));
+
+    // Split the circle at every stop, so that hard stops stay sharp.
+    let mut angles = (0..=(360.0 / SWEEP_STEP) as usize)
+        .map(|i| i as f64 * SWEEP_STEP)
+        .collect::<Vec<_>>();
+
+    if extend == Extend::Pad {
+        angles.extend(
+            stops
+                .iter()
+                .map(|(offset, _)| start + (end - start) * *offset as f64),
+        );
+    }
+
+    angles.retain(|a| (0.0..=360.0).contains(a));
+    angles.sort_by(f64::total_cmp);
+    angles.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+
+    angles
+        .windows(2)
+        .map(|pair| {
+            let (a0, a1) = (pair[0], pair[1]);
+            // Sample just inside the triangle, so that the colors on both sides of
+            // a hard stop are picked correctly.
+            let epsilon = (a1 - a0) * 1e-3;
+
+            Triangle::new(
+                vertex(center, color_at((a0 + a1) / 2.0)),
+                vertex(point(a0), color_at(a0 + epsilon)),
+                vertex(point(a1), color_at(a1 - epsilon)),
+            )
+        })
+        .collect()
+}
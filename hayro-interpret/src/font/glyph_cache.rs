@@ -0,0 +1,182 @@
+//! Caching the outlines of glyphs across pages.
+
+use crate::CacheKey;
+use crate::font::OutlineGlyph;
+use kurbo::BezPath;
+use rustc_hash::FxHashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+/// A cache for the outlines of glyphs.
+///
+/// Extracting the outline of a glyph from its font program is relatively expensive,
+/// and text-heavy documents draw the same glyphs over and over again. The cache is
+/// part of the [`SharedCache`](crate::SharedCache), so it is shared by the
+/// interpreter, all devices and all threads that work on the same document, and
+/// cloning it only creates a new handle to the same cache.
+///
+/// The cache has a memory limit. Once the outlines that are stored exceed it, the
+/// ones that were used least recently are evicted.
+#[derive(Clone)]
+pub struct GlyphCache {
+    inner: Arc<Mutex<Inner>>,
+    max_bytes: usize,
+}
+
+impl Debug for GlyphCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GlyphCache")
+            .field("memory_usage", &self.memory_usage())
+            .field("max_bytes", &self.max_bytes)
+            .finish()
+    }
+}
+
+impl Default for GlyphCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_BYTES)
+    }
+}
+
+impl GlyphCache {
+    /// The memory limit of the cache used by default, 32 MiB.
+    pub const DEFAULT_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+    /// Create a new glyph cache that uses at most `max_bytes` bytes of memory.
+    ///
+    /// A limit of zero disables caching.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+            max_bytes,
+        }
+    }
+
+    /// Return the outline of the glyph, assuming an upem value of 1000.
+    ///
+    /// This is the same as [`OutlineGlyph::outline`], except that the outline is
+    /// shared instead of copied.
+    pub fn outline(&self, glyph: &OutlineGlyph) -> Arc<BezPath> {
+        self.get_or_insert_with(GlyphKey::new(glyph), || {
+            glyph.font.outline_glyph(glyph.id, glyph.char_code)
+        })
+    }
+
+    /// Return the number of bytes that are currently used by the cached outlines.
+    pub fn memory_usage(&self) -> usize {
+        self.inner.lock().unwrap().used_bytes
+    }
+
+    /// Return the memory limit of the cache.
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Remove all outlines from the cache.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.used_bytes = 0;
+    }
+
+    fn get_or_insert_with(&self, key: GlyphKey, f: impl FnOnce() -> BezPath) -> Arc<BezPath> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.clock += 1;
+            let clock = inner.clock;
+
+            if let Some(entry) = inner.entries.get_mut(&key) {
+                entry.last_used = clock;
+
+                return entry.path.clone();
+            }
+        }
+
+        // Don't hold the lock while computing the outline, `f` might access the
+        // cache as well, and other threads can use the cache in the meantime.
+        let path = Arc::new(f());
+        let bytes = entry_size(&path);
+
+        if bytes <= self.max_bytes {
+            let mut inner = self.inner.lock().unwrap();
+            let last_used = inner.clock;
+            inner.used_bytes += bytes;
+
+            if let Some(old) = inner.entries.insert(
+                key,
+                Entry {
+                    path: path.clone(),
+                    bytes,
+                    last_used,
+                },
+            ) {
+                inner.used_bytes -= old.bytes;
+            }
+
+            if inner.used_bytes > self.max_bytes {
+                inner.evict(self.max_bytes);
+            }
+        }
+
+        path
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: FxHashMap<GlyphKey, Entry>,
+    used_bytes: usize,
+    /// Increases with every access, used to determine which entries were used least
+    /// recently.
+    clock: u64,
+}
+
+impl Inner {
+    fn evict(&mut self, max_bytes: usize) {
+        // Evicting down to a lower watermark instead of the limit itself avoids having
+        // to sort the entries on every insertion once the cache is full.
+        let target = max_bytes / 4 * 3;
+        let mut by_age = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (entry.last_used, *key))
+            .collect::<Vec<_>>();
+        by_age.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+        for (_, key) in by_age {
+            if self.used_bytes <= target {
+                break;
+            }
+
+            if let Some(entry) = self.entries.remove(&key) {
+                self.used_bytes -= entry.bytes;
+            }
+        }
+    }
+}
+
+struct Entry {
+    path: Arc<BezPath>,
+    bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    glyph: u128,
+    // For CID fonts, the outline depends on the width of the character code as well.
+    char_code: u32,
+}
+
+impl GlyphKey {
+    fn new(glyph: &OutlineGlyph) -> Self {
+        Self {
+            glyph: glyph.identifier().cache_key(),
+            char_code: glyph.char_code,
+        }
+    }
+}
+
+fn entry_size(path: &BezPath) -> usize {
+    size_of::<Entry>() + size_of::<GlyphKey>() + size_of::<BezPath>() + size_of_val(path.elements())
+}
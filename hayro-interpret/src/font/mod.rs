@@ -1,7 +1,6 @@
 //! Interacting with the different kinds of PDF fonts.
 
-use crate::context::Context;
-use crate::context::InterpreterCache;
+use crate::context::{Budget, Context, InterpreterCache};
 use crate::device::Device;
 use crate::font::cid::Type0Font;
 use crate::font::generated::{
@@ -11,18 +10,22 @@ use crate::font::true_type::TrueTypeFont;
 use crate::font::type1::Type1Font;
 use crate::font::type3::Type3;
 use crate::interpret::state::State;
-use crate::{CMapResolverFn, CacheKey, FontResolverFn, InterpreterSettings, Paint};
+use crate::{
+    CMapResolverFn, CacheKey, CorruptFontPolicy, DrawProps, FontResolverFn, InterpreterSettings,
+    Paint, StrokeProps, TextRenderingMode, WarningSinkFn,
+};
 use bitflags::bitflags;
 use hayro_syntax::object::Name;
 use hayro_syntax::object::dict::keys::SUBTYPE;
 use hayro_syntax::object::dict::keys::*;
-use hayro_syntax::object::{Dict, Stream};
+use hayro_syntax::object::{Dict, ObjectIdentifier, Stream};
 use hayro_syntax::page::Resources;
 use hayro_syntax::xref::XRef;
 use kurbo::{Affine, BezPath, Vec2};
 use outline::OutlineFont;
-use skrifa::GlyphId;
-use std::borrow::Cow;
+use skrifa::attribute::{Attributes, Style};
+use skrifa::setting::VariationSetting;
+use skrifa::{FontRef, MetadataProvider, NamedInstance};
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::rc::Rc;
@@ -30,10 +33,15 @@ use std::sync::Arc;
 
 mod blob;
 mod cid;
+mod cmap_dir;
+mod color_glyph;
 mod generated;
+mod glyph_cache;
 mod glyph_simulator;
 pub(crate) mod outline;
 mod standard_font;
+#[cfg(feature = "system-fonts")]
+mod system;
 mod true_type;
 mod type1;
 pub(crate) mod type3;
@@ -49,6 +57,52 @@ pub(crate) fn stretch_glyph(path: BezPath, expected_width: f32, actual_width: f3
     }
 }
 
+/// Styles that are requested by a PDF but missing in the font program that is used as a
+/// substitute, and that therefore need to be simulated.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SyntheticStyle {
+    pub(crate) bold: bool,
+    pub(crate) oblique: bool,
+}
+
+impl SyntheticStyle {
+    /// The horizontal skew of synthetic oblique glyphs, corresponding to roughly 12 degrees.
+    const OBLIQUE_SKEW: f64 = 0.2126;
+    /// The stroke width for emboldening glyphs, relative to the em size.
+    const EMBOLDEN_STRENGTH: f64 = 1.0 / 24.0;
+
+    /// Determine the styles that need to be simulated. If the attributes of the substitute
+    /// font are unknown, it's assumed to have the correct style already.
+    pub(crate) fn new(bold: bool, italic: bool, attributes: Option<Attributes>) -> Self {
+        let Some(attributes) = attributes else {
+            return Self::default();
+        };
+
+        Self {
+            bold: bold && attributes.weight.value() < 600.0,
+            oblique: italic && attributes.style == Style::Normal,
+        }
+    }
+
+    pub(crate) fn apply(&self, path: BezPath) -> BezPath {
+        if self.oblique {
+            Affine::skew(Self::OBLIQUE_SKEW, 0.0) * path
+        } else {
+            path
+        }
+    }
+
+    /// Return the stroke width in user space that is used to embolden a glyph with the
+    /// given glyph transform, if it needs to be emboldened.
+    pub(crate) fn embolden_width(&self, glyph_transform: Affine) -> Option<f32> {
+        let [a, b, c, d, _, _] = glyph_transform.as_coeffs();
+        let scale = (a * d - b * c).abs().sqrt();
+
+        self.bold
+            .then_some((scale * UNITS_PER_EM as f64 * Self::EMBOLDEN_STRENGTH) as f32)
+    }
+}
+
 /// A container for the bytes of a PDF file.
 pub type FontData = Arc<dyn AsRef<[u8]> + Send + Sync>;
 
@@ -64,12 +118,19 @@ pub(crate) fn strip_subset_prefix(name: &str) -> &str {
     }
 }
 
+use crate::cache::Cache;
 use crate::util::hash128;
-use hayro_cmap::{BfString, CMap, CMapName, CharacterCollection};
+pub use cmap_dir::DirectoryCMapResolver;
+pub use glyph_cache::GlyphCache;
+use hayro_cmap::{BfString, CMap, CMapName, CharacterCollection, CidFamily};
 pub use outline::OutlineFontData;
+pub use skrifa::GlyphId;
 pub use standard_font::StandardFont;
+#[cfg(feature = "system-fonts")]
+pub use system::SystemFontResolver;
 
 /// A glyph that can be drawn.
+#[derive(Clone)]
 pub enum Glyph<'a> {
     /// A glyph defined by an outline.
     Outline(OutlineGlyph),
@@ -77,7 +138,7 @@ pub enum Glyph<'a> {
     Type3(Box<Type3Glyph<'a>>),
 }
 
-impl Glyph<'_> {
+impl<'a> Glyph<'a> {
     /// Returns the Unicode code point for this glyph, if available.
     ///
     /// This method attempts to determine the Unicode character that this glyph
@@ -87,10 +148,12 @@ impl Glyph<'_> {
     /// 1. `ToUnicode` cmap
     /// 2. Glyph name → Unicode (via Adobe Glyph List)
     /// 3. Unicode naming conventions (e.g., "uni0041", "u0041")
+    /// 4. Reverse lookup in the `cmap` table of the font (TrueType only)
     ///
     /// **For CID Fonts (Type0):**
     /// 1. `ToUnicode` cmap
-    ///
+    /// 2. The UCS2 cmap of the character collection (e.g. `Adobe-Japan1-UCS2`)
+    /// 3. Reverse lookup in the `cmap` table of the font
     ///
     /// **For Type3 Fonts:**
     /// 1. `ToUnicode` cmap
@@ -105,6 +168,68 @@ impl Glyph<'_> {
             Glyph::Type3(g) => g.as_unicode(),
         }
     }
+
+    /// Returns the character code that was used to select this glyph.
+    pub fn char_code(&self) -> u32 {
+        match self {
+            Glyph::Outline(g) => g.char_code,
+            Glyph::Type3(g) => g.char_code,
+        }
+    }
+
+    /// Returns the font of this glyph.
+    pub fn font(&self) -> Font<'a> {
+        match self {
+            Glyph::Outline(g) => {
+                let font = match &g.font {
+                    OutlineFont::Type1(t) => FontType::Type1(t.clone()),
+                    OutlineFont::TrueType(t) => FontType::TrueType(t.clone()),
+                    OutlineFont::Type0(t) => FontType::Type0(t.clone()),
+                };
+
+                Font(g.font.cache_key(), font, false)
+            }
+            Glyph::Type3(g) => Font(g.font.cache_key(), FontType::Type3(g.font.clone()), false),
+        }
+    }
+}
+
+/// A run of glyphs that is shown by a single text string, for example the operand of
+/// a `Tj` operator or one of the strings of a `TJ` operator.
+///
+/// All glyphs of a run use the same font, which can be accessed through the glyphs
+/// themselves (for example via [`OutlineGlyph::font_data`]).
+#[derive(Clone)]
+pub struct GlyphRun<'a> {
+    /// The glyphs of the run.
+    pub glyphs: Vec<RunGlyph<'a>>,
+    /// The text matrix at the start of the run.
+    pub text_matrix: Affine,
+    /// The font size.
+    pub font_size: f32,
+    /// The text rendering mode.
+    pub render_mode: TextRenderingMode,
+    /// The properties for filling the glyphs, or `None` if the glyphs aren't filled.
+    ///
+    /// This is also set for invisible text, so that it can for example still be
+    /// extracted.
+    pub fill: Option<DrawProps<'a>>,
+    /// The properties for stroking the glyphs, or `None` if the glyphs aren't stroked.
+    ///
+    /// Filled glyphs are stroked with the fill paint as well if the font lacks the
+    /// requested bold style and it has to be simulated.
+    pub stroke: Option<(DrawProps<'a>, StrokeProps)>,
+}
+
+/// A glyph of a [`GlyphRun`].
+#[derive(Clone)]
+pub struct RunGlyph<'a> {
+    /// The glyph.
+    pub glyph: Glyph<'a>,
+    /// The transform from glyph space (assuming an upem value of 1000) to user space.
+    pub transform: Affine,
+    /// The advance of the glyph in text space, including character and word spacing.
+    pub advance: Vec2,
 }
 
 /// An identifier that uniquely identifies a glyph, for caching purposes.
@@ -126,12 +251,22 @@ pub struct OutlineGlyph {
     pub(crate) id: GlyphId,
     pub(crate) font: OutlineFont,
     pub(crate) char_code: u32,
+    pub(crate) glyph_cache: GlyphCache,
+    /// The smoothness tolerance for the gradients of color glyphs.
+    pub(crate) smoothness: f32,
 }
 
 impl OutlineGlyph {
     /// Return the outline of the glyph, assuming an upem value of 1000.
     pub fn outline(&self) -> BezPath {
-        self.font.outline_glyph(self.id, self.char_code)
+        self.glyph_cache.outline(self).as_ref().clone()
+    }
+
+    /// Return the glyph cache of the document the glyph belongs to.
+    ///
+    /// Use [`GlyphCache::outline`] to access the outline without copying it.
+    pub fn glyph_cache(&self) -> &GlyphCache {
+        &self.glyph_cache
     }
 
     /// Return the identifier of the glyph. You can use this to calculate the cache key
@@ -173,6 +308,70 @@ impl OutlineGlyph {
         self.font.glyph_advance_width(self.char_code)
     }
 
+    /// Get the vertical advance for this glyph.
+    ///
+    /// This is how far to move vertically after drawing this glyph, which is
+    /// usually negative. Returns `None` if the font doesn't use a vertical
+    /// writing mode (e.g. via the `Identity-V` cmap).
+    pub fn vertical_advance(&self) -> Option<f32> {
+        self.font.glyph_vertical_advance(self.char_code)
+    }
+
+    /// Get the displacement from the position of the glyph to the origin of its
+    /// outline, assuming an upem value of 1000.
+    ///
+    /// In a vertical writing mode, glyphs are positioned by their top center
+    /// instead of their origin. The glyph transform that is passed to a device
+    /// already includes this displacement, so the position of the glyph is
+    /// `glyph_transform * -origin_displacement()`. For horizontal fonts, the
+    /// displacement is always zero.
+    pub fn origin_displacement(&self) -> Vec2 {
+        self.font.origin_displacement(self.char_code)
+    }
+
+    pub(crate) fn synthetic_style(&self) -> SyntheticStyle {
+        self.font.synthetic_style()
+    }
+
+    /// Return whether the glyph has a color representation.
+    ///
+    /// Color glyphs, like emojis, are defined by the `COLR` table of an OpenType font
+    /// and should be drawn with [`OutlineGlyph::interpret_color`] instead of filling
+    /// their outline. Glyphs that are only defined by the `SVG ` table of a font
+    /// aren't supported and are drawn with their outline.
+    pub fn is_color(&self) -> bool {
+        self.font
+            .open_type_blob()
+            .is_some_and(|blob| blob.color_glyph(self.id).is_some())
+    }
+
+    /// Draw the color layers of the glyph to the given device.
+    ///
+    /// Layers that use the foreground color are drawn with the paint of `props`,
+    /// and the soft mask and blend mode of `props` are applied to the glyph as a
+    /// whole by drawing it into a transparency group. Linear and
+    /// radial gradients are drawn with [`Device::draw_axial_gradient`] and
+    /// [`Device::draw_radial_gradient`], and sweep gradients are approximated by a
+    /// triangle mesh that is drawn with [`Device::draw_mesh`]. If the glyph isn't a
+    /// color glyph, nothing is drawn.
+    pub fn interpret_color<'a>(
+        &self,
+        device: &mut impl Device<'a>,
+        glyph_transform: Affine,
+        props: &DrawProps<'a>,
+    ) {
+        if let Some(blob) = self.font.open_type_blob() {
+            color_glyph::draw_color_glyph(
+                blob,
+                self.id,
+                glyph_transform,
+                props,
+                self.smoothness,
+                device,
+            );
+        }
+    }
+
     /// Get the cache key for this glyph's font.
     ///
     /// This identifies the font uniquely, even when `font_data()` returns `None`
@@ -193,6 +392,8 @@ pub struct Type3Glyph<'a> {
     pub(crate) xref: &'a XRef,
     pub(crate) settings: InterpreterSettings,
     pub(crate) nesting_depth: u32,
+    pub(crate) x_object_stack: Vec<ObjectIdentifier>,
+    pub(crate) budget: Budget,
     pub(crate) char_code: u32,
 }
 
@@ -224,27 +425,97 @@ impl CacheKey for Type3Glyph<'_> {
     }
 }
 
+/// A PDF font.
+///
+/// The last field is set if the embedded font program is corrupt and a
+/// substitute font is used instead.
 #[derive(Clone, Debug)]
-pub(crate) struct Font<'a>(u128, FontType<'a>);
+pub struct Font<'a>(u128, FontType<'a>, bool);
 
 impl<'a> Font<'a> {
+    /// Load a font from a font dictionary, using the resolvers of the given settings
+    /// for fonts and cmaps that aren't embedded.
+    ///
+    /// Returns `None` if the font couldn't be loaded, which includes fonts with a
+    /// corrupt font program unless [`InterpreterSettings::corrupt_fonts`] is
+    /// [`CorruptFontPolicy::Fallback`].
+    pub fn from_dict(dict: &Dict<'a>, settings: &InterpreterSettings) -> Option<Self> {
+        Self::new(
+            dict,
+            &settings.font_resolver,
+            &settings.font_fallbacks,
+            &settings.cmap_resolver,
+            &Cache::new(),
+            &settings.warning_sink,
+        )
+        .filter(|font| !font.is_corrupt() || settings.corrupt_fonts == CorruptFontPolicy::Fallback)
+    }
+
+    /// Return the outline of a glyph, assuming an upem value of 1000.
+    ///
+    /// Use [`Font::font_matrix`] to transform the outline into text space. Returns
+    /// `None` for type3 fonts, whose glyphs are defined by PDF drawing instructions
+    /// instead of outlines.
+    pub fn glyph_outline(&self, glyph: GlyphId) -> Option<BezPath> {
+        match &self.1 {
+            FontType::Type1(t) => Some(t.outline_glyph(glyph)),
+            FontType::TrueType(t) => Some(t.outline_glyph(glyph)),
+            FontType::Type0(t) => Some(t.base_outline_glyph(glyph)),
+            FontType::Type3(_) => None,
+        }
+    }
+
+    /// Return the font matrix, which maps glyph space to text space.
+    ///
+    /// For fonts with outlines, glyph space is the coordinate system of the outlines
+    /// returned by [`Font::glyph_outline`]. For type3 fonts, this is the `FontMatrix`
+    /// of the font.
+    pub fn font_matrix(&self) -> Affine {
+        match &self.1 {
+            FontType::Type3(t) => t.matrix(),
+            _ => Affine::scale(1.0 / UNITS_PER_EM as f64),
+        }
+    }
+
     pub(crate) fn new(
         dict: &Dict<'a>,
         font_resolver: &FontResolverFn,
+        font_fallbacks: &[FontFallback],
         cmap_resolver: &CMapResolverFn,
+        cache: &Cache,
+        warning_sink: &WarningSinkFn,
     ) -> Option<Self> {
+        let mut corrupt = false;
         let f_type = match dict.get::<Name<'_>>(SUBTYPE)?.deref() {
-            TYPE1 | MM_TYPE1 => {
-                FontType::Type1(Rc::new(Type1Font::new(dict, font_resolver, cmap_resolver)?))
-            }
+            TYPE1 | MM_TYPE1 => FontType::Type1(Rc::new(Type1Font::new(
+                dict,
+                font_resolver,
+                font_fallbacks,
+                cmap_resolver,
+                cache,
+                warning_sink,
+                &mut corrupt,
+            )?)),
             // PDFBOX-5463: PDF viewers seem to accept OpenType as well.
             TRUE_TYPE | OPEN_TYPE => FontType::TrueType(Rc::new(TrueTypeFont::new(
                 dict,
                 font_resolver,
+                font_fallbacks,
+                cmap_resolver,
+                cache,
+                warning_sink,
+                &mut corrupt,
+            )?)),
+            TYPE0 => FontType::Type0(Rc::new(Type0Font::new(
+                dict,
+                font_resolver,
+                font_fallbacks,
                 cmap_resolver,
+                cache,
+                warning_sink,
+                &mut corrupt,
             )?)),
-            TYPE0 => FontType::Type0(Rc::new(Type0Font::new(dict, font_resolver, cmap_resolver)?)),
-            TYPE3 => FontType::Type3(Rc::new(Type3::new(dict, cmap_resolver)?)),
+            TYPE3 => FontType::Type3(Rc::new(Type3::new(dict, cmap_resolver, cache)?)),
             f => {
                 warn!(
                     "unimplemented font type {:?}",
@@ -257,7 +528,7 @@ impl<'a> Font<'a> {
 
         let cache_key = dict.cache_key();
 
-        Some(Self(cache_key, f_type))
+        Some(Self(cache_key, f_type, corrupt))
     }
 
     pub(crate) fn new_standard(
@@ -266,7 +537,13 @@ impl<'a> Font<'a> {
     ) -> Option<Self> {
         let font = Type1Font::new_standard(standard_font, font_resolver)?;
 
-        Some(Self(0, FontType::Type1(Rc::new(font))))
+        Some(Self(0, FontType::Type1(Rc::new(font)), false))
+    }
+
+    /// Whether the embedded font program is corrupt, in which case a substitute
+    /// font is used instead.
+    pub(crate) fn is_corrupt(&self) -> bool {
+        self.2
     }
 
     pub(crate) fn map_code(&self, code: u32) -> GlyphId {
@@ -309,6 +586,8 @@ impl<'a> Font<'a> {
                     id: glyph,
                     font,
                     char_code,
+                    glyph_cache: ctx.interpreter_cache.glyph_cache.clone(),
+                    smoothness: ctx.smoothness(),
                 })
             }
             FontType::TrueType(t) => {
@@ -317,6 +596,8 @@ impl<'a> Font<'a> {
                     id: glyph,
                     font,
                     char_code,
+                    glyph_cache: ctx.interpreter_cache.glyph_cache.clone(),
+                    smoothness: ctx.smoothness(),
                 })
             }
             FontType::Type0(t) => {
@@ -325,6 +606,8 @@ impl<'a> Font<'a> {
                     id: glyph,
                     font,
                     char_code,
+                    glyph_cache: ctx.interpreter_cache.glyph_cache.clone(),
+                    smoothness: ctx.smoothness(),
                 })
             }
             FontType::Type3(t) => {
@@ -338,6 +621,8 @@ impl<'a> Font<'a> {
                     xref: ctx.xref,
                     settings: ctx.settings.clone(),
                     nesting_depth,
+                    x_object_stack: ctx.x_object_stack().to_vec(),
+                    budget: ctx.budget.clone(),
                     char_code,
                 };
 
@@ -472,6 +757,22 @@ impl FontStretch {
             _ => Self::Normal,
         }
     }
+
+    /// Return the width of the stretch in percent of the normal width, as used by the
+    /// `wdth` axis of variable fonts.
+    pub(crate) fn to_width(self) -> f32 {
+        match self {
+            Self::UltraCondensed => 50.0,
+            Self::ExtraCondensed => 62.5,
+            Self::Condensed => 75.0,
+            Self::SemiCondensed => 87.5,
+            Self::Normal => 100.0,
+            Self::SemiExpanded => 112.5,
+            Self::Expanded => 125.0,
+            Self::ExtraExpanded => 150.0,
+            Self::UltraExpanded => 200.0,
+        }
+    }
 }
 
 bitflags! {
@@ -520,12 +821,17 @@ pub struct FallbackFontQuery {
     pub is_serif: bool,
     /// Whether the font is italic.
     pub is_italic: bool,
+    /// The angle of the font in degrees counterclockwise from the vertical, which is
+    /// negative for fonts that slope to the right.
+    pub italic_angle: f32,
     /// Whether the font is bold.
     pub is_bold: bool,
     /// Whether the font is small cap.
     pub is_small_cap: bool,
     /// The character collection (registry/ordering) if this is a CID font.
     pub character_collection: Option<CharacterCollection>,
+    /// The script of the text, if it could be determined.
+    pub script: Option<Script>,
 }
 
 impl FallbackFontQuery {
@@ -551,6 +857,7 @@ impl FallbackFontQuery {
                 .map(|n| FontStretch::from_string(n.as_str()))
                 .unwrap_or(FontStretch::Normal);
             data.font_weight = descriptor.get::<u32>(FONT_WEIGHT).unwrap_or(400);
+            data.italic_angle = descriptor.get::<f32>(ITALIC_ANGLE).unwrap_or(0.0);
 
             if let Some(flags) = descriptor
                 .get::<u32>(FLAGS)
@@ -575,6 +882,47 @@ impl FallbackFontQuery {
         data
     }
 
+    /// Return the axis settings for instantiating a variable font so that it matches
+    /// the query.
+    ///
+    /// If the PostScript name of the query belongs to one of the named instances of the
+    /// font, the coordinates of that instance are used. Otherwise, the weight, width
+    /// and slant are derived from the font descriptor.
+    pub(crate) fn variations(&self, font: &FontRef<'_>) -> Vec<VariationSetting> {
+        let axes = font.axes();
+
+        if let Some(name) = self.post_script_name.as_deref()
+            && let Some(instance) = font
+                .named_instances()
+                .iter()
+                .find(|instance| instance_matches_name(font, instance, name))
+        {
+            return axes
+                .iter()
+                .zip(instance.user_coords())
+                .map(|(axis, value)| (axis.tag(), value).into())
+                .collect();
+        }
+
+        let weight = if self.is_bold && self.font_weight < 600 {
+            700
+        } else {
+            self.font_weight
+        };
+
+        let mut variations = vec![
+            ("wght", weight as f32).into(),
+            ("wdth", self.font_stretch.to_width()).into(),
+            ("ital", if self.is_italic { 1.0 } else { 0.0 }).into(),
+        ];
+
+        if self.italic_angle != 0.0 {
+            variations.push(("slnt", self.italic_angle).into());
+        }
+
+        variations
+    }
+
     /// Do a best-effort fallback to the 14 standard fonts based on the query.
     pub fn pick_standard_font(&self) -> StandardFont {
         if self.is_fixed_pitch {
@@ -602,6 +950,25 @@ impl FallbackFontQuery {
     }
 }
 
+/// Check whether a named instance of a variable font has the given PostScript name.
+///
+/// Instances without an explicit PostScript name match if their subfamily name (like
+/// "Bold Italic") corresponds to the style suffix of the name (like "Font-BoldItalic").
+fn instance_matches_name(font: &FontRef<'_>, instance: &NamedInstance<'_>, name: &str) -> bool {
+    let string = |id| font.localized_strings(id).english_or_first();
+
+    if let Some(id) = instance.postscript_name_id() {
+        return string(id).is_some_and(|s| s.chars().eq(name.chars()));
+    }
+
+    let Some((_, style)) = name.rsplit_once('-') else {
+        return false;
+    };
+
+    string(instance.subfamily_name_id())
+        .is_some_and(|s| s.chars().filter(|c| *c != ' ').eq(style.chars()))
+}
+
 impl Default for FallbackFontQuery {
     fn default() -> Self {
         Self {
@@ -613,54 +980,315 @@ impl Default for FallbackFontQuery {
             is_fixed_pitch: false,
             is_serif: false,
             is_italic: false,
+            italic_angle: 0.0,
             is_bold: false,
             is_small_cap: false,
             character_collection: None,
+            script: None,
         }
     }
 }
 
-/// Convert a glyph name to a Unicode character, if possible.
-/// An incomplete implementation of the Adobe Glyph List Specification
-/// <https://github.com/adobe-type-tools/agl-specification>
-pub(crate) fn glyph_name_to_unicode(name: &str) -> Option<char> {
-    if let Some(unicode_str) = glyph_names::get(name) {
-        return unicode_str.chars().next();
+/// A script that text in a non-embedded font can be written in.
+///
+/// Used to select a font from the [`InterpreterSettings::font_fallbacks`] chain.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Script {
+    /// Cyrillic.
+    Cyrillic,
+    /// Greek.
+    Greek,
+    /// Arabic.
+    Arabic,
+    /// Hebrew.
+    Hebrew,
+    /// Thai.
+    Thai,
+    /// Japanese.
+    Japanese,
+    /// Simplified Chinese.
+    SimplifiedChinese,
+    /// Traditional Chinese.
+    TraditionalChinese,
+    /// Korean.
+    Korean,
+}
+
+impl Script {
+    /// Return the script of a character, if it belongs to one of the supported scripts.
+    ///
+    /// Han characters are shared between Chinese, Japanese and Korean, so they don't
+    /// determine a script on their own.
+    pub fn from_char(c: char) -> Option<Self> {
+        match c as u32 {
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Some(Self::Greek),
+            0x0400..=0x052F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Some(Self::Cyrillic),
+            0x0590..=0x05FF | 0xFB1D..=0xFB4F => Some(Self::Hebrew),
+            0x0600..=0x06FF | 0x0750..=0x077F | 0x08A0..=0x08FF => Some(Self::Arabic),
+            0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Some(Self::Arabic),
+            0x0E00..=0x0E7F => Some(Self::Thai),
+            0x3040..=0x30FF | 0x31F0..=0x31FF => Some(Self::Japanese),
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some(Self::Korean),
+            _ => None,
+        }
+    }
+
+    /// Return the script of a CID font with the given character collection.
+    pub(crate) fn from_character_collection(cc: &CharacterCollection) -> Option<Self> {
+        match cc.family {
+            CidFamily::AdobeJapan1 => Some(Self::Japanese),
+            CidFamily::AdobeGB1 => Some(Self::SimplifiedChinese),
+            CidFamily::AdobeCNS1 => Some(Self::TraditionalChinese),
+            CidFamily::AdobeKorea1 => Some(Self::Korean),
+            CidFamily::AdobeIdentity | CidFamily::Custom { .. } => None,
+        }
+    }
+
+    /// Return the most common script among the given glyph names, ignoring the
+    /// names of glyphs that don't belong to any of the supported scripts.
+    pub(crate) fn from_glyph_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut counts: Vec<(Self, usize)> = Vec::new();
+
+        for name in names {
+            if let Some(script) = glyph_name_to_char(name).and_then(Self::from_char) {
+                match counts.iter_mut().find(|(s, _)| *s == script) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((script, 1)),
+                }
+            }
+        }
+
+        counts
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(script, _)| script)
     }
+}
 
-    unicode_from_name(name).or_else(|| {
+/// A font that should be used for text in a specific script if the font of the
+/// PDF isn't embedded.
+///
+/// See [`InterpreterSettings::font_fallbacks`].
+#[derive(Clone)]
+pub struct FontFallback {
+    /// The script the font should be used for.
+    pub script: Script,
+    /// The data of the font, either a TTF/OTF font or a CFF font program.
+    pub data: FontData,
+    /// The index of the font in case the data is a font collection.
+    pub index: u32,
+}
+
+/// Load the first font of the fallback chain that is meant for the given script
+/// and can be loaded successfully.
+pub(crate) fn load_fallback_font<T>(
+    fallbacks: &[FontFallback],
+    script: Script,
+    load: impl Fn(FontData, u32) -> Option<T>,
+) -> Option<T> {
+    fallbacks
+        .iter()
+        .filter(|f| f.script == script)
+        .find_map(|f| {
+            load(f.data.clone(), f.index).or_else(|| {
+                warn!("failed to load fallback font for {script:?}");
+
+                None
+            })
+        })
+}
+
+/// Convert a glyph name to its Unicode text, following the Adobe Glyph List Specification
+/// <https://github.com/adobe-type-tools/agl-specification>.
+///
+/// Names that aren't part of the glyph list can encode their value as `uniXXXX` (with
+/// one or more groups of four hex digits) or `uXXXX` to `uXXXXXX`. Suffixes like the
+/// one in `a.sc` are ignored, and the components of ligature names like `f_f_i` are
+/// mapped individually.
+pub(crate) fn glyph_name_to_text(name: &str) -> Option<BfString> {
+    if let Some(text) = glyph_names::get(name) {
+        return Some(to_bf_string(text.to_string()));
+    }
+
+    let base = name.split('.').next().unwrap_or(name);
+    let mut text = String::new();
+
+    for component in base.split('_') {
+        if let Some(mapped) = glyph_names::get(component) {
+            text.push_str(mapped);
+        } else if let Some(hex) = component.strip_prefix("uni")
+            && hex.len() > 4
+            && hex.len() % 4 == 0
+        {
+            let chars = (0..hex.len())
+                .step_by(4)
+                .map(|i| hex.get(i..i + 4).and_then(parse_code_point))
+                .collect::<Option<String>>();
+            text.push_str(&chars.unwrap_or_default());
+        } else if let Some(c) = unicode_from_name(component) {
+            text.push(c);
+        }
+    }
+
+    (!text.is_empty()).then(|| to_bf_string(text))
+}
+
+/// Convert a glyph name to a Unicode character, if it maps to exactly one.
+pub(crate) fn glyph_name_to_char(name: &str) -> Option<char> {
+    match glyph_name_to_text(name)? {
+        BfString::Char(c) => Some(c),
+        BfString::String(_) => None,
+    }
+}
+
+/// Convert a glyph name to its Unicode text, warning if that's not possible.
+///
+/// See [`glyph_name_to_text`] for the supported conventions.
+pub(crate) fn glyph_name_to_unicode(name: &str) -> Option<BfString> {
+    glyph_name_to_text(name).or_else(|| {
         warn!("failed to map glyph name {} to unicode", name);
 
         None
     })
 }
 
-pub(crate) fn unicode_from_name(name: &str) -> Option<char> {
-    let convert = |input: &str| u32::from_str_radix(input, 16).ok().and_then(char::from_u32);
+/// Return other names that fonts commonly use for the glyph with the given name, namely
+/// the name from the Adobe Glyph List and the `uniXXXX` and `uXXXX` names of its Unicode
+/// value.
+///
+/// This allows finding for example the glyph `uni0410` of a font if the PDF refers to it
+/// as `afii10017`, and vice versa.
+pub(crate) fn glyph_name_alternatives(name: &str) -> Vec<String> {
+    let Some(c) = glyph_name_to_char(name) else {
+        return Vec::new();
+    };
+
+    let code_point = c as u32;
+
+    glyph_names::get_reverse(c)
+        .map(str::to_string)
+        .into_iter()
+        .chain((code_point <= 0xFFFF).then(|| format!("uni{code_point:04X}")))
+        .chain(std::iter::once(format!("u{code_point:04X}")))
+        .filter(|n| n != name)
+        .collect()
+}
+
+/// Look up a glyph by its name using `lookup`, trying the
+/// [alternative names](glyph_name_alternatives) of the glyph if that fails.
+pub(crate) fn glyph_by_name(
+    name: &str,
+    lookup: impl Fn(&str) -> Option<GlyphId>,
+) -> Option<GlyphId> {
+    lookup(name).or_else(|| {
+        glyph_name_alternatives(name)
+            .iter()
+            .find_map(|alternative| lookup(alternative))
+    })
+}
 
+pub(crate) fn unicode_from_name(name: &str) -> Option<char> {
     name.starts_with("uni")
-        .then(|| name.get(3..).and_then(convert))
+        .then(|| name.get(3..).and_then(parse_code_point))
         .or_else(|| {
             name.starts_with("u")
-                .then(|| name.get(1..).and_then(convert))
+                .then(|| name.get(1..).and_then(parse_code_point))
         })
         .flatten()
 }
 
-pub(crate) fn read_to_unicode(dict: &Dict<'_>, cmap_resolver: &CMapResolverFn) -> Option<CMap> {
-    dict.get::<Stream<'_>>(TO_UNICODE)
-        .and_then(|s| s.decoded().ok())
-        // See PDFJS-11915, where `Identity-H` is used for `ToUnicode`. I don't
-        // believe it's valid, but at least mupdf seems to be able to deal with it.
-        .or_else(|| {
-            dict.get::<Name<'_>>(TO_UNICODE)
-                .and_then(|name| (cmap_resolver)(CMapName::from_bytes(name.as_ref())))
-                .map(|d| Cow::Owned(d.to_vec()))
-        })
-        .and_then(|data| {
+fn parse_code_point(hex: &str) -> Option<char> {
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+fn to_bf_string(text: String) -> BfString {
+    let mut chars = text.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => BfString::Char(c),
+        _ => BfString::String(text),
+    }
+}
+
+pub(crate) fn read_to_unicode(
+    dict: &Dict<'_>,
+    cmap_resolver: &CMapResolverFn,
+    cache: &Cache,
+) -> Option<Arc<CMap>> {
+    if let Some(stream) = dict.get::<Stream<'_>>(TO_UNICODE) {
+        return read_embedded_cmap(&stream, cache, |data| {
             let cmap_resolver = cmap_resolver.clone();
-            CMap::parse(&data, move |name| (cmap_resolver)(name))
-        })
+            CMap::parse(data, move |name| (cmap_resolver)(name))
+        });
+    }
+
+    // See PDFJS-11915, where `Identity-H` is used for `ToUnicode`. I don't
+    // believe it's valid, but at least mupdf seems to be able to deal with it.
+    dict.get::<Name<'_>>(TO_UNICODE).and_then(|name| {
+        read_predefined_cmap(CMapName::from_bytes(name.as_ref()), cmap_resolver, cache)
+    })
+}
+
+/// Parse the predefined cmap with the given name.
+///
+/// Parsed cmaps are stored in the object cache, so a cmap that is used by many fonts
+/// of a document is only parsed once.
+pub(crate) fn read_predefined_cmap(
+    name: CMapName<'_>,
+    cmap_resolver: &CMapResolverFn,
+    cache: &Cache,
+) -> Option<Arc<CMap>> {
+    cache.get_or_insert_with(hash128(&("cmap", name.to_bytes())), || {
+        let data = (cmap_resolver)(name)?;
+        let cmap_resolver = cmap_resolver.clone();
+
+        CMap::parse(&data, move |name| (cmap_resolver)(name)).map(Arc::new)
+    })
+}
+
+/// Parse an embedded font program with `parse`, unless the same stream has been
+/// parsed before.
+///
+/// Parsed font programs are stored in the object cache, so they are shared by all
+/// fonts that embed the same program, and by all threads that share a
+/// [`SharedCache`](crate::SharedCache).
+pub(crate) fn read_font_program<T: Clone + Send + Sync + 'static>(
+    stream: &Stream<'_>,
+    cache: &Cache,
+    parse: impl FnOnce(Arc<Vec<u8>>) -> Option<T>,
+) -> Option<T> {
+    // The same stream can be parsed as different kinds of font programs, depending
+    // on the font that uses it.
+    let key = hash128(&(
+        "font program",
+        stream.obj_id(),
+        stream.cache_key(),
+        std::any::type_name::<T>(),
+    ));
+
+    cache.get_or_insert_with(key, || {
+        let data = stream.decoded().ok()?;
+
+        parse(Arc::new(data.into_owned()))
+    })
+}
+
+/// Parse an embedded cmap with `parse`, unless the same stream has been parsed before.
+pub(crate) fn read_embedded_cmap(
+    stream: &Stream<'_>,
+    cache: &Cache,
+    parse: impl FnOnce(&[u8]) -> Option<CMap>,
+) -> Option<Arc<CMap>> {
+    // Many streams have the same dictionary, so the object number needs to be part
+    // of the key as well.
+    let key = hash128(&("cmap", stream.obj_id(), stream.cache_key()));
+
+    cache.get_or_insert_with(key, || {
+        let data = stream.decoded().ok()?;
+
+        parse(&data).map(Arc::new)
+    })
 }
 
 // When mapping to glyphs, some fonts might only have a glyph for the "normalized"
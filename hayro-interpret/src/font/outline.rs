@@ -1,9 +1,11 @@
 use crate::CacheKey;
+use crate::font::SyntheticStyle;
+use crate::font::blob::OpenTypeFontBlob;
 use crate::font::cid::Type0Font;
 use crate::font::true_type::TrueTypeFont;
 use crate::font::type1::Type1Font;
 use hayro_cmap::BfString;
-use kurbo::BezPath;
+use kurbo::{BezPath, Vec2};
 use skrifa::GlyphId;
 use skrifa::outline::OutlinePen;
 use std::rc::Rc;
@@ -100,6 +102,14 @@ impl OutlineFont {
         }
     }
 
+    pub(crate) fn synthetic_style(&self) -> SyntheticStyle {
+        match self {
+            Self::Type1(t) => t.synthetic_style(),
+            Self::TrueType(t) => t.synthetic_style(),
+            Self::Type0(t) => t.synthetic_style(),
+        }
+    }
+
     pub(crate) fn char_code_to_unicode(&self, char_code: u32) -> Option<BfString> {
         match self {
             Self::Type1(t) => t.char_code_to_unicode(char_code),
@@ -117,6 +127,31 @@ impl OutlineFont {
         }
     }
 
+    /// Get the vertical advance for a glyph by character code, or `None` if the font
+    /// doesn't use a vertical writing mode.
+    pub(crate) fn glyph_vertical_advance(&self, char_code: u32) -> Option<f32> {
+        match self {
+            Self::Type0(t) if !t.is_horizontal() => Some(t.code_advance(char_code).y as f32),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn origin_displacement(&self, char_code: u32) -> Vec2 {
+        match self {
+            Self::Type1(_) | Self::TrueType(_) => Vec2::ZERO,
+            Self::Type0(t) => t.origin_displacement(char_code),
+        }
+    }
+
+    /// Get the OpenType font that color glyphs can be read from.
+    pub(crate) fn open_type_blob(&self) -> Option<&OpenTypeFontBlob> {
+        match self {
+            Self::Type1(_) => None,
+            Self::TrueType(t) => t.open_type_blob(),
+            Self::Type0(t) => t.open_type_blob(),
+        }
+    }
+
     /// Get raw font bytes and metadata.
     ///
     /// Returns None for Type1 fonts and non-embedded TrueType fonts.
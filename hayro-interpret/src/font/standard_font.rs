@@ -1,17 +1,20 @@
 use crate::FontResolverFn;
 use crate::font::blob::{CffFontBlob, OpenTypeFontBlob};
-use crate::font::generated::{glyph_names, metrics, standard, symbol, zapf_dings};
+use crate::font::generated::{metrics, standard, symbol, zapf_dings};
 use crate::font::true_type::{Width, read_encoding, read_widths};
 use crate::font::{
-    Encoding, FontData, FontQuery, glyph_name_to_unicode, normalized_glyph_name, stretch_glyph,
-    strip_subset_prefix,
+    Encoding, FontData, FontFallback, FontQuery, Script, SyntheticStyle, glyph_by_name,
+    glyph_name_to_char, glyph_name_to_unicode, load_fallback_font, normalized_glyph_name,
+    stretch_glyph, strip_subset_prefix,
 };
+use hayro_cmap::BfString;
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Name;
 use hayro_syntax::object::dict::keys::{BASE_FONT, FONT_DESC, FONT_WEIGHT, ITALIC_ANGLE};
 use kurbo::BezPath;
 use rustc_hash::FxHashMap;
 use skrifa::GlyphId;
+use skrifa::attribute::Attributes;
 use skrifa::raw::TableProvider;
 use std::cell::RefCell;
 
@@ -326,6 +329,13 @@ impl StandardFontBlob {
         }
     }
 
+    pub(crate) fn attributes(&self) -> Option<Attributes> {
+        match self {
+            Self::Cff(_) => None,
+            Self::Otf(blob, _) => Some(blob.attributes()),
+        }
+    }
+
     pub(crate) fn advance_width(&self, glyph: GlyphId) -> Option<f32> {
         match self {
             Self::Cff(_) => None,
@@ -357,13 +367,18 @@ pub(crate) struct StandardKind {
     fallback: bool,
     glyph_to_code: RefCell<FxHashMap<GlyphId, u8>>,
     encodings: FxHashMap<u8, String>,
+    synthetic: SyntheticStyle,
 }
 
 impl StandardKind {
-    pub(crate) fn new(dict: &Dict<'_>, resolver: &FontResolverFn) -> Option<Self> {
+    pub(crate) fn new(
+        dict: &Dict<'_>,
+        resolver: &FontResolverFn,
+        font_fallbacks: &[FontFallback],
+    ) -> Option<Self> {
         let descriptor = dict.get::<Dict<'_>>(FONT_DESC).unwrap_or_default();
         let (font, exact) = select_standard_font(dict, &descriptor)?;
-        Self::new_with_standard(dict, font, !exact, resolver)
+        Self::new_with_standard(dict, font, !exact, resolver, font_fallbacks)
     }
 
     pub(crate) fn new_with_standard(
@@ -371,6 +386,7 @@ impl StandardKind {
         base_font: StandardFont,
         fallback: bool,
         resolver: &FontResolverFn,
+        font_fallbacks: &[FontFallback],
     ) -> Option<Self> {
         let descriptor = dict.get::<Dict<'_>>(FONT_DESC).unwrap_or_default();
         let (widths, missing_width) = read_widths(dict, &descriptor)?;
@@ -382,8 +398,26 @@ impl StandardKind {
             encoding = Encoding::BuiltIn;
         }
 
-        let (blob, index) = resolver(&FontQuery::Standard(base_font))?;
-        let base_font_blob = StandardFontBlob::from_data(blob, index)?;
+        // The standard fonts only cover latin scripts, so prefer a font from the
+        // fallback chain if the encoding indicates that the text uses a different one.
+        let substitute = Script::from_glyph_names(encoding_map.values().map(String::as_str))
+            .and_then(|script| {
+                load_fallback_font(font_fallbacks, script, StandardFontBlob::from_data)
+            });
+
+        let (base_font_blob, fallback) = match substitute {
+            // The metrics of the standard font don't apply to the substitute.
+            Some(blob) => (blob, true),
+            None => {
+                let (blob, index) = resolver(&FontQuery::Standard(base_font))?;
+                (StandardFontBlob::from_data(blob, index)?, fallback)
+            }
+        };
+        let synthetic = SyntheticStyle::new(
+            base_font.is_bold(),
+            base_font.is_italic(),
+            base_font_blob.attributes(),
+        );
 
         Some(Self {
             base_font,
@@ -394,6 +428,7 @@ impl StandardKind {
             glyph_to_code: RefCell::new(FxHashMap::default()),
             fallback,
             encoding,
+            synthetic,
         })
     }
 
@@ -413,12 +448,10 @@ impl StandardKind {
         let result = self
             .code_to_ps_name(code)
             .and_then(|c| {
-                self.base_font_blob.name_to_glyph(c).or_else(|| {
+                glyph_by_name(c, |name| self.base_font_blob.name_to_glyph(name)).or_else(|| {
                     // If the font doesn't have a POST table, try to map via unicode instead.
-                    glyph_names::get(c).and_then(|c| {
-                        self.base_font_blob
-                            .unicode_to_glyph(c.chars().nth(0).unwrap() as u32)
-                    })
+                    glyph_name_to_char(c)
+                        .and_then(|c| self.base_font_blob.unicode_to_glyph(c as u32))
                 })
             })
             .unwrap_or(GlyphId::NOTDEF);
@@ -428,6 +461,10 @@ impl StandardKind {
     }
 
     pub(crate) fn outline_glyph(&self, glyph: GlyphId) -> BezPath {
+        self.synthetic.apply(self.stretched_outline_glyph(glyph))
+    }
+
+    fn stretched_outline_glyph(&self, glyph: GlyphId) -> BezPath {
         let path = self.base_font_blob.outline_glyph(glyph);
 
         // If the font is not embedded, we might need to stretch it so that
@@ -475,7 +512,7 @@ impl StandardKind {
         }
     }
 
-    pub(crate) fn char_code_to_unicode(&self, code: u8) -> Option<char> {
+    pub(crate) fn char_code_to_unicode(&self, code: u8) -> Option<BfString> {
         self.code_to_ps_name(code).and_then(glyph_name_to_unicode)
     }
 
@@ -494,4 +531,8 @@ impl StandardKind {
     pub(crate) fn is_monospace(&self) -> bool {
         self.base_font.is_monospace()
     }
+
+    pub(crate) fn synthetic_style(&self) -> SyntheticStyle {
+        self.synthetic
+    }
 }
@@ -0,0 +1,280 @@
+//! Resolving fonts that aren't embedded in the PDF with the fonts installed on the system.
+
+use crate::FontResolverFn;
+use crate::font::{FallbackFontQuery, FontData, FontQuery, FontStretch, StandardFont};
+use fontdb::{Database, Family, ID, Query, Stretch, Style, Weight};
+use rustc_hash::FxHashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A font resolver that looks up fonts in a database of system fonts.
+///
+/// Fonts are matched by their PostScript name first, then by their family name,
+/// taking the requested weight, stretch and style into account. For the 14 standard
+/// fonts, the common metric-compatible families (like Arial and Liberation Sans for
+/// Helvetica) are tried as well. If nothing matches, the embedded substitute fonts
+/// are used if the `embed-fonts` feature is enabled, and a generic serif, sans-serif
+/// or monospace font otherwise.
+///
+/// Use [`SystemFontResolver::into_resolver`] to turn it into a
+/// [`FontResolverFn`] for the [`InterpreterSettings`](crate::InterpreterSettings).
+pub struct SystemFontResolver {
+    db: Database,
+    /// The data of the faces that have been resolved so far.
+    fonts: Mutex<FxHashMap<ID, (FontData, u32)>>,
+}
+
+impl Default for SystemFontResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemFontResolver {
+    /// Create a new resolver without any fonts.
+    pub fn new() -> Self {
+        Self {
+            db: Database::new(),
+            fonts: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// Create a new resolver and load all fonts installed on the system.
+    pub fn with_system_fonts() -> Self {
+        let mut resolver = Self::new();
+        resolver.load_system_fonts();
+
+        resolver
+    }
+
+    /// Load all fonts installed on the system.
+    pub fn load_system_fonts(&mut self) {
+        self.db.load_system_fonts();
+    }
+
+    /// Load all fonts in the given directory and its subdirectories.
+    pub fn load_fonts_dir(&mut self, dir: impl AsRef<Path>) {
+        self.db.load_fonts_dir(dir);
+    }
+
+    /// Load a font from its data. Font collections are supported as well.
+    pub fn load_font_data(&mut self, data: Vec<u8>) {
+        self.db.load_font_data(data);
+    }
+
+    /// Return the number of faces that have been loaded.
+    pub fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    /// Return whether no faces have been loaded.
+    pub fn is_empty(&self) -> bool {
+        self.db.is_empty()
+    }
+
+    /// Resolve the font for the given query.
+    pub fn resolve(&self, query: &FontQuery) -> Option<(FontData, u32)> {
+        let (id, standard_font) = match query {
+            FontQuery::Standard(s) => (self.find_standard(*s), *s),
+            FontQuery::Fallback(f) => (self.find_fallback(f), f.pick_standard_font()),
+        };
+
+        match id {
+            Some(id) => self.load(id),
+            None => self.fallback(query, standard_font),
+        }
+    }
+
+    /// Convert the resolver into a callback that can be used in the interpreter
+    /// settings.
+    pub fn into_resolver(self) -> FontResolverFn {
+        Arc::new(move |query| self.resolve(query))
+    }
+
+    fn find_standard(&self, font: StandardFont) -> Option<ID> {
+        if let Some(id) = self.find_by_postscript_name(font.postscript_name()) {
+            return Some(id);
+        }
+
+        let families: &[&str] = if font.is_monospace() {
+            &[
+                "Courier",
+                "Courier New",
+                "Liberation Mono",
+                "Nimbus Mono PS",
+            ]
+        } else if font.is_serif() {
+            &[
+                "Times",
+                "Times New Roman",
+                "Liberation Serif",
+                "Nimbus Roman",
+            ]
+        } else if matches!(font, StandardFont::Symbol | StandardFont::ZapfDingBats) {
+            // These have their own encodings, so only the actual fonts will do.
+            return None;
+        } else {
+            &["Helvetica", "Arial", "Liberation Sans", "Nimbus Sans"]
+        };
+
+        let weight = standard_weight(font);
+
+        families.iter().find_map(|family| {
+            self.query(
+                Family::Name(family),
+                weight,
+                FontStretch::Normal,
+                font.is_italic(),
+            )
+        })
+    }
+
+    fn find_fallback(&self, query: &FallbackFontQuery) -> Option<ID> {
+        let names = [&query.post_script_name, &query.font_name];
+        let names = names.iter().filter_map(|n| n.as_deref());
+
+        // Names like `Arial,Bold` are common for non-embedded TrueType fonts.
+        if let Some(id) = names
+            .clone()
+            .find_map(|n| self.find_by_postscript_name(&n.replace(',', "-")))
+        {
+            return Some(id);
+        }
+
+        let weight = fallback_weight(query);
+
+        // Otherwise, try to derive the family name from the font name.
+        let families = query
+            .font_family
+            .as_deref()
+            .into_iter()
+            .chain(names.map(|n| n.split([',', '-']).next().unwrap_or(n)));
+
+        families
+            .filter_map(|family| self.find_family(family))
+            .find_map(|family| {
+                self.query(
+                    Family::Name(&family),
+                    weight,
+                    query.font_stretch,
+                    query.is_italic,
+                )
+            })
+    }
+
+    #[cfg(feature = "embed-fonts")]
+    fn fallback(&self, _: &FontQuery, standard_font: StandardFont) -> Option<(FontData, u32)> {
+        Some(standard_font.get_font_data())
+    }
+
+    #[cfg(not(feature = "embed-fonts"))]
+    fn fallback(&self, query: &FontQuery, standard_font: StandardFont) -> Option<(FontData, u32)> {
+        let (weight, stretch, italic) = match query {
+            FontQuery::Standard(s) => (standard_weight(*s), FontStretch::Normal, s.is_italic()),
+            FontQuery::Fallback(f) => (fallback_weight(f), f.font_stretch, f.is_italic),
+        };
+
+        self.load(self.query(generic_family(standard_font), weight, stretch, italic)?)
+    }
+
+    fn find_by_postscript_name(&self, name: &str) -> Option<ID> {
+        self.db
+            .faces()
+            .find(|f| f.post_script_name == name)
+            .map(|f| f.id)
+    }
+
+    /// Find the name of a family that matches the given name, ignoring case,
+    /// whitespace and common suffixes like `MT` or `PS`.
+    fn find_family(&self, name: &str) -> Option<String> {
+        let name = normalize_family(name);
+
+        self.db
+            .faces()
+            .flat_map(|f| f.families.iter())
+            .find(|(family, _)| normalize_family(family) == name)
+            .map(|(family, _)| family.clone())
+    }
+
+    fn query(
+        &self,
+        family: Family<'_>,
+        weight: u32,
+        stretch: FontStretch,
+        italic: bool,
+    ) -> Option<ID> {
+        self.db.query(&Query {
+            families: &[family],
+            weight: Weight(weight.min(u16::MAX as u32) as u16),
+            stretch: convert_stretch(stretch),
+            style: if italic { Style::Italic } else { Style::Normal },
+        })
+    }
+
+    fn load(&self, id: ID) -> Option<(FontData, u32)> {
+        let mut fonts = self.fonts.lock().unwrap();
+
+        if let Some(font) = fonts.get(&id) {
+            return Some(font.clone());
+        }
+
+        let (data, index) = self
+            .db
+            .with_face_data(id, |data, index| (data.to_vec(), index))?;
+        let font: (FontData, u32) = (Arc::new(data), index);
+        fonts.insert(id, font.clone());
+
+        Some(font)
+    }
+}
+
+fn standard_weight(font: StandardFont) -> u32 {
+    if font.is_bold() { 700 } else { 400 }
+}
+
+fn fallback_weight(query: &FallbackFontQuery) -> u32 {
+    if query.is_bold {
+        query.font_weight.max(700)
+    } else {
+        query.font_weight
+    }
+}
+
+fn normalize_family(name: &str) -> String {
+    let name = name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    ["psmt", "mt", "ps"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .map(str::to_string)
+        .unwrap_or(name)
+}
+
+#[cfg(not(feature = "embed-fonts"))]
+fn generic_family(font: StandardFont) -> Family<'static> {
+    if font.is_monospace() {
+        Family::Monospace
+    } else if font.is_serif() {
+        Family::Serif
+    } else {
+        Family::SansSerif
+    }
+}
+
+fn convert_stretch(stretch: FontStretch) -> Stretch {
+    match stretch {
+        FontStretch::Normal => Stretch::Normal,
+        FontStretch::UltraCondensed => Stretch::UltraCondensed,
+        FontStretch::ExtraCondensed => Stretch::ExtraCondensed,
+        FontStretch::Condensed => Stretch::Condensed,
+        FontStretch::SemiCondensed => Stretch::SemiCondensed,
+        FontStretch::SemiExpanded => Stretch::SemiExpanded,
+        FontStretch::Expanded => Stretch::Expanded,
+        FontStretch::ExtraExpanded => Stretch::ExtraExpanded,
+        FontStretch::UltraExpanded => Stretch::UltraExpanded,
+    }
+}
@@ -1,12 +1,14 @@
+use crate::cache::Cache;
 use crate::font::blob::{CffFontBlob, OpenTypeFontBlob};
-use crate::font::generated::{glyph_names, mac_os_roman, mac_roman, standard};
+use crate::font::generated::{mac_os_roman, mac_roman, standard};
 use crate::font::standard_font::StandardKind;
 use crate::font::{
-    Encoding, FallbackFontQuery, FontFlags, glyph_name_to_unicode, read_to_unicode,
-    strip_subset_prefix, unicode_from_name,
+    Encoding, FallbackFontQuery, FontFallback, FontFlags, SyntheticStyle, glyph_by_name,
+    glyph_name_to_char, glyph_name_to_unicode, read_font_program, read_to_unicode,
+    strip_subset_prefix,
 };
 use crate::util::OptionLog;
-use crate::{CMapResolverFn, CacheKey, FontResolverFn};
+use crate::{CMapResolverFn, CacheKey, FontResolverFn, InterpreterWarning, WarningSinkFn};
 use hayro_cmap::{BfString, CMap};
 use hayro_syntax::object::Array;
 use hayro_syntax::object::Dict;
@@ -18,7 +20,7 @@ use kurbo::BezPath;
 use rustc_hash::FxHashMap;
 use skrifa::attribute::Style;
 use skrifa::raw::TableProvider;
-use skrifa::raw::tables::cmap::PlatformId;
+use skrifa::raw::tables::cmap::{CmapSubtable, PlatformId};
 use skrifa::{GlyphId, MetadataProvider};
 use std::cell::RefCell;
 use std::ops::Deref;
@@ -28,7 +30,7 @@ use std::sync::Arc;
 pub(crate) struct TrueTypeFont {
     cache_key: u128,
     kind: Kind,
-    to_unicode: Option<CMap>,
+    to_unicode: Option<Arc<CMap>>,
 }
 
 #[derive(Debug)]
@@ -41,17 +43,35 @@ impl TrueTypeFont {
     pub(crate) fn new(
         dict: &Dict<'_>,
         font_resolver: &FontResolverFn,
+        font_fallbacks: &[FontFallback],
         cmap_resolver: &CMapResolverFn,
+        cache: &Cache,
+        warning_sink: &WarningSinkFn,
+        corrupt: &mut bool,
     ) -> Option<Self> {
         let cache_key = dict.cache_key();
-        let to_unicode = read_to_unicode(dict, cmap_resolver);
+        let to_unicode = read_to_unicode(dict, cmap_resolver, cache);
 
-        if let Some(embedded) = EmbeddedKind::new(dict) {
-            return Some(Self {
-                cache_key,
-                kind: Kind::Embedded(embedded),
-                to_unicode,
-            });
+        let descriptor = dict.get::<Dict<'_>>(FONT_DESC).unwrap_or_default();
+
+        if descriptor.contains_key(FONT_FILE2) {
+            // Only a font program that can't be read is reported as corrupt, not
+            // fonts that are rejected because of other entries of their dictionary.
+            match read_font_file(&descriptor, cache) {
+                Some(base_font) => {
+                    if let Some(embedded) = EmbeddedKind::new(dict, &descriptor, base_font) {
+                        return Some(Self {
+                            cache_key,
+                            kind: Kind::Embedded(embedded),
+                            to_unicode,
+                        });
+                    }
+                }
+                None => {
+                    warning_sink(InterpreterWarning::corrupt_embedded_font(dict).into());
+                    *corrupt = true;
+                }
+            }
         }
 
         let fallback = || {
@@ -73,12 +93,13 @@ impl TrueTypeFont {
                     standard_font,
                     true,
                     font_resolver,
+                    font_fallbacks,
                 )?),
                 to_unicode: to_unicode.clone(),
             })
         };
 
-        if let Some(standard) = StandardKind::new(dict, font_resolver) {
+        if let Some(standard) = StandardKind::new(dict, font_resolver, font_fallbacks) {
             Some(Self {
                 cache_key,
                 kind: Kind::Standard(standard),
@@ -96,6 +117,20 @@ impl TrueTypeFont {
         }
     }
 
+    pub(crate) fn synthetic_style(&self) -> SyntheticStyle {
+        match &self.kind {
+            Kind::Embedded(_) => SyntheticStyle::default(),
+            Kind::Standard(s) => s.synthetic_style(),
+        }
+    }
+
+    pub(crate) fn open_type_blob(&self) -> Option<&OpenTypeFontBlob> {
+        match &self.kind {
+            Kind::Embedded(e) => Some(&e.base_font),
+            Kind::Standard(_) => None,
+        }
+    }
+
     pub(crate) fn font_data(&self) -> Option<crate::font::FontData> {
         match &self.kind {
             Kind::Embedded(e) => Some(e.base_font.font_data()),
@@ -113,7 +148,7 @@ impl TrueTypeFont {
     pub(crate) fn weight(&self) -> Option<u32> {
         match &self.kind {
             Kind::Embedded(e) => {
-                let weight = e.base_font.font_ref().attributes().weight.value().round() as u32;
+                let weight = e.base_font.attributes().weight.value().round() as u32;
                 if weight > 0 { Some(weight) } else { None }
             }
             Kind::Standard(s) => Some(if s.is_bold() { 700 } else { 400 }),
@@ -130,7 +165,7 @@ impl TrueTypeFont {
                     return true;
                 }
                 // Check skrifa font attributes
-                e.base_font.font_ref().attributes().style != Style::Normal
+                e.base_font.attributes().style != Style::Normal
             }
             Kind::Standard(s) => s.is_italic(),
         }
@@ -193,8 +228,12 @@ impl TrueTypeFont {
             Kind::Embedded(e) => e
                 .code_to_name(code as u8)
                 .and_then(glyph_name_to_unicode)
-                .map(BfString::Char),
-            Kind::Standard(s) => s.char_code_to_unicode(code as u8).map(BfString::Char),
+                .or_else(|| {
+                    e.base_font
+                        .glyph_to_unicode(e.map_code(code as u8))
+                        .map(BfString::Char)
+                }),
+            Kind::Standard(s) => s.char_code_to_unicode(code as u8),
         }
 
         // TODO: The test PDFs below fail (but mutool can render them correctly).
@@ -222,17 +261,11 @@ struct EmbeddedKind {
 }
 
 impl EmbeddedKind {
-    fn new(dict: &Dict<'_>) -> Option<Self> {
-        let descriptor = dict.get::<Dict<'_>>(FONT_DESC).unwrap_or_default();
-
+    fn new(dict: &Dict<'_>, descriptor: &Dict<'_>, base_font: OpenTypeFontBlob) -> Option<Self> {
         let font_flags = descriptor.get::<u32>(FLAGS).and_then(FontFlags::from_bits);
 
-        let (widths, missing_width) = read_widths(dict, &descriptor)?;
+        let (widths, missing_width) = read_widths(dict, descriptor)?;
         let (encoding, differences) = read_encoding(dict);
-        let base_font = descriptor
-            .get::<Stream<'_>>(FONT_FILE2)
-            .and_then(|s| s.decoded().ok())
-            .and_then(|d| OpenTypeFontBlob::new(Arc::new(d.to_vec()), 0))?;
 
         let glyph_names = base_font.glyph_names();
 
@@ -289,7 +322,7 @@ impl EmbeddedKind {
         if let Some(blob) = self.cff_blob.as_ref() {
             return self
                 .code_to_name(code)
-                .and_then(|name| blob.glyph_index_by_name(name))
+                .and_then(|name| glyph_by_name(name, |name| blob.glyph_index_by_name(name)))
                 .unwrap_or(GlyphId::NOTDEF);
         }
 
@@ -307,10 +340,7 @@ impl EmbeddedKind {
                         && let Ok(subtable) = record.subtable(cmap.offset_data())
                     {
                         glyph = glyph.or_else(|| {
-                            glyph_names::get(lookup)
-                                .map(|n| n.to_string())
-                                .or_else(|| unicode_from_name(lookup).map(|n| n.to_string()))
-                                .and_then(|n| n.chars().next())
+                            glyph_name_to_char(lookup)
                                 .and_then(|c| subtable.map_codepoint(c))
                                 .filter(|g| *g != GlyphId::NOTDEF)
                         });
@@ -333,35 +363,15 @@ impl EmbeddedKind {
             }
 
             if glyph.is_none() {
-                if let Some(gid) = self.glyph_names.get(lookup) {
-                    glyph = Some(*gid);
+                if let Some(gid) = glyph_by_name(lookup, |name| self.glyph_names.get(name).copied())
+                {
+                    glyph = Some(gid);
                 } else if let Some(gid) = glyph_num_string(lookup) {
                     glyph = Some(GlyphId::new(gid));
                 }
             }
-        } else if let Ok(cmap) = self.base_font.font_ref().cmap() {
-            for record in cmap.encoding_records() {
-                if record.platform_id() == PlatformId::Windows
-                    && matches!(record.encoding_id(), 0 | 1)
-                {
-                    if let Ok(subtable) = record.subtable(cmap.offset_data()) {
-                        for offset in [0x0000_u32, 0xF000, 0xF100, 0xF200] {
-                            glyph = glyph
-                                .or_else(|| subtable.map_codepoint(code as u32 + offset))
-                                .filter(|g| *g != GlyphId::NOTDEF);
-                        }
-                    }
-                } else if matches!(
-                    record.platform_id(),
-                    PlatformId::Macintosh | PlatformId::Unicode
-                ) && record.encoding_id() == 0
-                    && let Ok(subtable) = record.subtable(cmap.offset_data())
-                {
-                    glyph = glyph
-                        .or_else(|| subtable.map_codepoint(code))
-                        .filter(|g| *g != GlyphId::NOTDEF);
-                }
-            }
+        } else {
+            glyph = self.map_symbolic_code(code);
         }
 
         let glyph = glyph.unwrap_or(GlyphId::NOTDEF);
@@ -370,6 +380,61 @@ impl EmbeddedKind {
         glyph
     }
 
+    /// Map a code of a symbolic font, following the rules of Acrobat and pdf.js.
+    fn map_symbolic_code(&self, code: u8) -> Option<GlyphId> {
+        let font_ref = self.base_font.font_ref();
+        let cmap = font_ref.cmap().ok();
+        let subtable = |platform_id: PlatformId, encoding_id: u16| {
+            let cmap = cmap.as_ref()?;
+
+            cmap.encoding_records()
+                .iter()
+                .find(|r| r.platform_id() == platform_id && r.encoding_id() == encoding_id)
+                .and_then(|r| r.subtable(cmap.offset_data()).ok())
+        };
+        let lookup = |subtable: &CmapSubtable<'_>, codepoint: u32| {
+            subtable
+                .map_codepoint(codepoint)
+                .filter(|g| *g != GlyphId::NOTDEF)
+        };
+
+        // Glyph names from the `Differences` array take precedence over the
+        // cmap, see PDFJS-13433.
+        if let Some(name) = self.differences.get(&code) {
+            if let Some(gid) = glyph_by_name(name, |name| self.glyph_names.get(name).copied()) {
+                return Some(gid);
+            }
+
+            if let Some(glyph) = subtable(PlatformId::Windows, 1)
+                .zip(glyph_name_to_char(name))
+                .and_then(|(subtable, c)| lookup(&subtable, c as u32))
+            {
+                return Some(glyph);
+            }
+        }
+
+        // Symbol fonts usually place their glyphs in the private use area, so
+        // the codes need to be offset for the (3, 0) cmap. Some fonts use the
+        // (3, 1) cmap the same way. The (1, 0) cmap is indexed by the codes
+        // directly.
+        let with_offsets = |subtable: &CmapSubtable<'_>| {
+            [0x0000_u32, 0xF000, 0xF100, 0xF200]
+                .into_iter()
+                .find_map(|offset| lookup(subtable, code as u32 + offset))
+        };
+
+        subtable(PlatformId::Windows, 0)
+            .and_then(|s| with_offsets(&s))
+            .or_else(|| subtable(PlatformId::Macintosh, 0).and_then(|s| lookup(&s, code as u32)))
+            .or_else(|| subtable(PlatformId::Windows, 1).and_then(|s| with_offsets(&s)))
+            .or_else(|| subtable(PlatformId::Unicode, 0).and_then(|s| lookup(&s, code as u32)))
+            .or_else(|| {
+                self.code_to_name(code)
+                    .and_then(|name| self.glyph_names.get(name))
+                    .copied()
+            })
+    }
+
     fn glyph_width(&self, code: u8) -> f32 {
         match self.widths.get(code as usize).copied() {
             Some(Width::Value(w)) => w,
@@ -390,6 +455,13 @@ pub(crate) enum Width {
     Missing,
 }
 
+/// Decode and parse the embedded font program of a TrueType font.
+fn read_font_file(descriptor: &Dict<'_>, cache: &Cache) -> Option<OpenTypeFontBlob> {
+    let stream = descriptor.get::<Stream<'_>>(FONT_FILE2)?;
+
+    read_font_program(&stream, cache, |data| OpenTypeFontBlob::new(data, 0))
+}
+
 pub(crate) fn read_widths(dict: &Dict<'_>, descriptor: &Dict<'_>) -> Option<(Vec<Width>, f32)> {
     let mut widths = Vec::new();
 
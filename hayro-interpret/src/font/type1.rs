@@ -1,31 +1,37 @@
+use crate::cache::Cache;
 use crate::font::blob::{CffFontBlob, Type1FontBlob};
 use crate::font::standard_font::{StandardFont, StandardKind, select_standard_font};
 use crate::font::true_type::{Width, read_encoding, read_widths};
 use crate::font::{
-    Encoding, FallbackFontQuery, glyph_name_to_unicode, normalized_glyph_name, read_to_unicode,
+    Encoding, FallbackFontQuery, FontFallback, SyntheticStyle, glyph_by_name,
+    glyph_name_to_unicode, normalized_glyph_name, read_font_program, read_to_unicode,
 };
-use crate::{CMapResolverFn, CacheKey, FontResolverFn};
+use crate::{CMapResolverFn, CacheKey, FontResolverFn, InterpreterWarning, WarningSinkFn};
 use hayro_cmap::{BfString, CMap};
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Stream;
-use hayro_syntax::object::dict::keys::{FONT_DESC, FONT_FILE, FONT_FILE3};
+use hayro_syntax::object::dict::keys::{FONT_DESC, FONT_FILE, FONT_FILE3, LENGTH1, LENGTH2};
 use kurbo::BezPath;
 use rustc_hash::FxHashMap;
 use skrifa::GlyphId;
 use std::sync::Arc;
 
 #[derive(Debug)]
-pub(crate) struct Type1Font(u128, Kind, Option<CMap>);
+pub(crate) struct Type1Font(u128, Kind, Option<Arc<CMap>>);
 
 impl Type1Font {
     pub(crate) fn new(
         dict: &Dict<'_>,
         resolver: &FontResolverFn,
+        font_fallbacks: &[FontFallback],
         cmap_resolver: &CMapResolverFn,
+        cache: &Cache,
+        warning_sink: &WarningSinkFn,
+        corrupt: &mut bool,
     ) -> Option<Self> {
         let cache_key = dict.cache_key();
 
-        let to_unicode = read_to_unicode(dict, cmap_resolver);
+        let to_unicode = read_to_unicode(dict, cmap_resolver, cache);
 
         let fallback = || {
             // TODO: Actually use fallback fonts
@@ -47,24 +53,31 @@ impl Type1Font {
                     standard_font,
                     true,
                     resolver,
+                    font_fallbacks,
                 )?),
                 to_unicode.clone(),
             ))
         };
 
         let inner = if is_cff(dict) {
-            if let Some(cff) = CffKind::new(dict) {
+            if let Some(cff) = CffKind::new(dict, cache) {
                 Self(cache_key, Kind::Cff(cff), to_unicode)
             } else {
+                warning_sink(InterpreterWarning::corrupt_embedded_font(dict).into());
+                *corrupt = true;
+
                 return fallback();
             }
         } else if is_type1(dict) {
-            if let Some(f) = Type1Kind::new(dict) {
+            if let Some(f) = Type1Kind::new(dict, cache) {
                 Self(cache_key, Kind::Type1(f), to_unicode)
             } else {
+                warning_sink(InterpreterWarning::corrupt_embedded_font(dict).into());
+                *corrupt = true;
+
                 return fallback();
             }
-        } else if let Some(standard) = StandardKind::new(dict, resolver) {
+        } else if let Some(standard) = StandardKind::new(dict, resolver, font_fallbacks) {
             Self(cache_key, Kind::Standard(standard), to_unicode)
         } else {
             return fallback();
@@ -75,7 +88,7 @@ impl Type1Font {
 
     pub(crate) fn new_standard(font: StandardFont, resolver: &FontResolverFn) -> Option<Self> {
         let dict = Dict::default();
-        let standard = StandardKind::new_with_standard(&dict, font, true, resolver)?;
+        let standard = StandardKind::new_with_standard(&dict, font, true, resolver, &[])?;
 
         Some(Self(0, Kind::Standard(standard), None))
     }
@@ -96,6 +109,13 @@ impl Type1Font {
         }
     }
 
+    pub(crate) fn synthetic_style(&self) -> SyntheticStyle {
+        match &self.1 {
+            Kind::Standard(s) => s.synthetic_style(),
+            Kind::Cff(_) | Kind::Type1(_) => SyntheticStyle::default(),
+        }
+    }
+
     pub(crate) fn glyph_width(&self, code: u8) -> Option<f32> {
         match &self.1 {
             Kind::Standard(s) => s.glyph_width(code),
@@ -118,9 +138,9 @@ impl Type1Font {
 
         let code = char_code as u8;
         match &self.1 {
-            Kind::Standard(s) => s.char_code_to_unicode(code).map(BfString::Char),
-            Kind::Cff(c) => c.char_code_to_unicode(code).map(BfString::Char),
-            Kind::Type1(t) => t.char_code_to_unicode(code).map(BfString::Char),
+            Kind::Standard(s) => s.char_code_to_unicode(code),
+            Kind::Cff(c) => c.char_code_to_unicode(code),
+            Kind::Type1(t) => t.char_code_to_unicode(code),
         }
     }
 }
@@ -162,10 +182,15 @@ struct Type1Kind {
 }
 
 impl Type1Kind {
-    fn new(dict: &Dict<'_>) -> Option<Self> {
+    fn new(dict: &Dict<'_>, cache: &Cache) -> Option<Self> {
         let descriptor = dict.get::<Dict<'_>>(FONT_DESC)?;
-        let data = descriptor.get::<Stream<'_>>(FONT_FILE)?;
-        let font = Type1FontBlob::new(Arc::new(data.decoded().ok()?.to_vec()))?;
+        let stream = descriptor.get::<Stream<'_>>(FONT_FILE)?;
+        let length1 = stream.dict().get::<usize>(LENGTH1);
+        let length2 = stream.dict().get::<usize>(LENGTH2);
+        let font = read_font_program(&stream, cache, |data| {
+            Type1FontBlob::new(data.clone())
+                .or_else(|| Type1FontBlob::new(Arc::new(to_pfb(&data, length1?, length2)?)))
+        })?;
 
         let (encoding, encodings) = read_encoding(dict);
         let (widths, missing_width) = read_widths(dict, &descriptor)?;
@@ -189,7 +214,7 @@ impl Type1Kind {
     }
 
     fn name_to_glyph(&self, name: &str) -> Option<GlyphId> {
-        self.name_to_gid.get(name).copied()
+        glyph_by_name(name, |name| self.name_to_gid.get(name).copied())
     }
 
     fn map_code(&self, code: u8) -> GlyphId {
@@ -238,7 +263,7 @@ impl Type1Kind {
         }
     }
 
-    fn char_code_to_unicode(&self, code: u8) -> Option<char> {
+    fn char_code_to_unicode(&self, code: u8) -> Option<BfString> {
         self.code_to_ps_name(code).and_then(glyph_name_to_unicode)
     }
 }
@@ -256,10 +281,10 @@ struct CffKind {
 }
 
 impl CffKind {
-    fn new(dict: &Dict<'_>) -> Option<Self> {
+    fn new(dict: &Dict<'_>, cache: &Cache) -> Option<Self> {
         let descriptor = dict.get::<Dict<'_>>(FONT_DESC)?;
-        let data = descriptor.get::<Stream<'_>>(FONT_FILE3)?;
-        let font = CffFontBlob::new(Arc::new(data.decoded().ok()?.to_vec()))?;
+        let stream = descriptor.get::<Stream<'_>>(FONT_FILE3)?;
+        let font = read_font_program(&stream, cache, |data| CffFontBlob::new(data))?;
 
         let (encoding, encodings) = read_encoding(dict);
         let (widths, missing_width) = read_widths(dict, &descriptor)?;
@@ -286,9 +311,7 @@ impl CffKind {
 
     fn map_code(&self, code: u8) -> GlyphId {
         let get_glyph = |entry: &str| {
-            self.name_to_gid
-                .get(entry)
-                .copied()
+            glyph_by_name(entry, |name| self.name_to_gid.get(name).copied())
                 .or_else(|| self.name_to_gid.get(normalized_glyph_name(entry)).copied())
         };
 
@@ -335,7 +358,64 @@ impl CffKind {
         }
     }
 
-    fn char_code_to_unicode(&self, code: u8) -> Option<char> {
+    fn char_code_to_unicode(&self, code: u8) -> Option<BfString> {
         self.code_to_ps_name(code).and_then(glyph_name_to_unicode)
     }
 }
+
+/// Split a type1 font program into its cleartext and encrypted portion based on the
+/// `Length1` and `Length2` entries of the font stream and wrap them into PFB segments.
+///
+/// This allows us to read font programs with a non-standard header, or where the
+/// start of the encrypted portion can't be determined by looking for `eexec`.
+fn to_pfb(data: &[u8], length1: usize, length2: Option<usize>) -> Option<Vec<u8>> {
+    const HEADER: &[u8] = b"%!FontType1\n";
+
+    let (cleartext, rest) = data.split_at_checked(length1)?;
+    let encrypted = length2.and_then(|l| rest.get(..l)).unwrap_or(rest);
+
+    // Some font programs have garbage in front of the header.
+    let cleartext = cleartext
+        .windows(2)
+        .position(|w| w == b"%!")
+        .map_or(cleartext, |pos| &cleartext[pos..]);
+
+    // In PFB files, the encrypted portion is always binary.
+    let trimmed = encrypted.trim_ascii_start();
+    let encrypted = if trimmed.len() >= 4 && trimmed[..4].iter().all(u8::is_ascii_hexdigit) {
+        decode_hex(trimmed)
+    } else {
+        encrypted.to_vec()
+    };
+
+    let mut pfb = Vec::with_capacity(HEADER.len() + cleartext.len() + encrypted.len() + 14);
+    let mut push_segment = |tag: u8, data: &[&[u8]]| {
+        let len = data.iter().map(|d| d.len()).sum::<usize>();
+        pfb.extend([0x80, tag]);
+        pfb.extend(u32::try_from(len).ok()?.to_le_bytes());
+        data.iter().for_each(|d| pfb.extend_from_slice(d));
+
+        Some(())
+    };
+
+    // The header makes sure that the font program is recognized as such, the
+    // original one (if any) is treated as a comment.
+    push_segment(1, &[HEADER, cleartext])?;
+    push_segment(2, &[&encrypted])?;
+    pfb.extend([0x80, 3]);
+
+    Some(pfb)
+}
+
+fn decode_hex(data: &[u8]) -> Vec<u8> {
+    let digits = data
+        .iter()
+        .filter_map(|b| (*b as char).to_digit(16))
+        .map(|d| d as u8)
+        .collect::<Vec<_>>();
+
+    digits
+        .chunks(2)
+        .map(|c| (c[0] << 4) | c.get(1).copied().unwrap_or(0))
+        .collect()
+}
@@ -1,9 +1,10 @@
 use crate::CMapResolverFn;
+use crate::cache::Cache;
 use crate::context::Context;
 use crate::device::Device;
 use crate::font::glyph_simulator::GlyphSimulator;
 use crate::font::true_type::{Width, read_encoding, read_widths};
-use crate::font::{Encoding, Glyph, Type3Glyph, UNITS_PER_EM, read_to_unicode};
+use crate::font::{Encoding, Glyph, GlyphRun, Type3Glyph, UNITS_PER_EM, read_to_unicode};
 use crate::interpret::state::TextState;
 use crate::soft_mask::SoftMask;
 use crate::util::RectExt;
@@ -20,6 +21,7 @@ use hayro_syntax::page::Resources;
 use kurbo::{Affine, BezPath, Rect};
 use rustc_hash::FxHashMap;
 use skrifa::GlyphId;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub(crate) struct Type3<'a> {
@@ -32,11 +34,15 @@ pub(crate) struct Type3<'a> {
     glyph_simulator: GlyphSimulator,
     font_bbox: Rect,
     matrix: Affine,
-    to_unicode: Option<CMap>,
+    to_unicode: Option<Arc<CMap>>,
 }
 
 impl<'a> Type3<'a> {
-    pub(crate) fn new(dict: &Dict<'a>, cmap_resolver: &CMapResolverFn) -> Option<Self> {
+    pub(crate) fn new(
+        dict: &Dict<'a>,
+        cmap_resolver: &CMapResolverFn,
+        cache: &Cache,
+    ) -> Option<Self> {
         let (encoding, encodings) = read_encoding(dict);
         let (widths, missing_width) = read_widths(dict, dict)?;
         let font_bbox = dict
@@ -62,7 +68,7 @@ impl<'a> Type3<'a> {
             procs
         };
 
-        let to_unicode = read_to_unicode(dict, cmap_resolver);
+        let to_unicode = read_to_unicode(dict, cmap_resolver, cache);
 
         Some(Self {
             glyph_simulator: GlyphSimulator::new(),
@@ -78,6 +84,15 @@ impl<'a> Type3<'a> {
         })
     }
 
+    pub(crate) fn matrix(&self) -> Affine {
+        self.matrix
+    }
+
+    /// Return the font bounding box in the same units as the glyph widths.
+    pub(crate) fn bbox(&self) -> Rect {
+        (self.matrix * Affine::scale(UNITS_PER_EM as f64)).transform_rect_bbox(self.font_bbox)
+    }
+
     pub(crate) fn map_code(&self, code: u8) -> GlyphId {
         self.encodings
             .get(&code)
@@ -152,7 +167,9 @@ impl<'a> Type3<'a> {
             glyph.settings.clone(),
             state,
             glyph.nesting_depth,
-        );
+        )
+        .with_budget(glyph.budget.clone())
+        .with_x_object_stack(glyph.x_object_stack.clone());
 
         let mut resources = Resources::from_parent(
             self.dict.get(RESOURCES).unwrap_or_default(),
@@ -221,6 +238,10 @@ impl<'a, T: Device<'a>> Device<'a> for Type3ShapeGlyphDevice<'a, '_, T> {
         self.inner.draw_glyph(g, glyph_transform, props, draw_mode);
     }
 
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'a>) {
+        self.inner.draw_glyph_run(run);
+    }
+
     fn pop_clip(&mut self) {
         self.inner.pop_clip();
     }
@@ -15,7 +15,7 @@ use crate::function::type4::Type4;
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::dict::keys::{DOMAIN, FUNCTION_TYPE, RANGE};
 use hayro_syntax::object::{Object, dict_or_stream};
-use smallvec::SmallVec;
+use smallvec::{SmallVec, smallvec};
 use std::sync::Arc;
 
 /// The input/output type of functions.
@@ -32,6 +32,11 @@ enum FunctionType {
 }
 
 /// A PDF function.
+///
+/// Functions are used for example as tint transforms of `Separation` and `DeviceN`
+/// color spaces, as transfer functions and in shadings. All four function types
+/// (sampled, exponential, stitching and PostScript calculator functions) are
+/// supported.
 #[derive(Debug, Clone)]
 pub struct Function(Arc<FunctionType>);
 
@@ -51,6 +56,42 @@ impl Function {
         Some(Self(Arc::new(function_type)))
     }
 
+    /// Create a function for inputs in the interval [0, 1] that interpolates
+    /// linearly between the given stops, which need to be sorted by their offset.
+    ///
+    /// If two stops have the same offset, the function jumps from the color of
+    /// the first one to the color of the second one.
+    pub(crate) fn linear_stops(stops: &[(f32, Values)]) -> Option<Self> {
+        let mut functions = vec![];
+        let mut bounds = vec![];
+
+        for pair in stops.windows(2) {
+            let ((start, c0), (end, c1)) = (&pair[0], &pair[1]);
+
+            if *end - *start <= f32::EPSILON {
+                continue;
+            }
+
+            if !functions.is_empty() {
+                bounds.push(*start);
+            }
+
+            functions.push(Self(Arc::new(FunctionType::Type2(Type2::linear(
+                c0.clone(),
+                c1.clone(),
+            )))));
+        }
+
+        let function_type = if functions.is_empty() {
+            let (_, color) = stops.last()?;
+            FunctionType::Type2(Type2::linear(color.clone(), color.clone()))
+        } else {
+            FunctionType::Type3(Type3::stitch(functions, &bounds))
+        };
+
+        Some(Self(Arc::new(function_type)))
+    }
+
     /// Evaluate the function with the given input.
     pub fn eval(&self, input: Values) -> Option<Values> {
         match self.0.as_ref() {
@@ -61,6 +102,49 @@ impl Function {
         }
     }
 
+    /// Return the domain of the function, i.e. the interval of valid values for
+    /// each input.
+    ///
+    /// Inputs outside of the domain are clamped before evaluating the function.
+    pub fn domain(&self) -> &[(f32, f32)] {
+        &self.clamper().domain
+    }
+
+    /// Return the range of the function, i.e. the interval of each output, if
+    /// the function defines one.
+    pub fn range(&self) -> Option<&[(f32, f32)]> {
+        self.clamper().range.as_deref()
+    }
+
+    /// Return the number of inputs of the function.
+    pub fn num_inputs(&self) -> usize {
+        self.domain().len()
+    }
+
+    /// Return the number of outputs of the function, if it is known without
+    /// evaluating the function.
+    pub fn num_outputs(&self) -> Option<usize> {
+        if let Some(range) = self.range() {
+            return Some(range.len());
+        }
+
+        match self.0.as_ref() {
+            FunctionType::Type2(t2) => Some(t2.num_outputs()),
+            FunctionType::Type3(t3) => t3.num_outputs(),
+            // Sampled and PostScript functions always have a range.
+            FunctionType::Type0(_) | FunctionType::Type4(_) => None,
+        }
+    }
+
+    fn clamper(&self) -> &Clamper {
+        match self.0.as_ref() {
+            FunctionType::Type0(t0) => &t0.clamper,
+            FunctionType::Type2(t2) => &t2.clamper,
+            FunctionType::Type3(t3) => &t3.clamper,
+            FunctionType::Type4(t4) => &t4.clamper,
+        }
+    }
+
     pub(crate) fn stitching_bounds(&self) -> StitchingBounds {
         match self.0.as_ref() {
             FunctionType::Type3(t3) => t3.stitching_bounds(),
@@ -83,6 +167,14 @@ impl Clamper {
         Some(Self { domain, range })
     }
 
+    /// A clamper for a single input in the interval [0, 1], without a range.
+    fn unit() -> Self {
+        Self {
+            domain: smallvec![(0.0, 1.0)],
+            range: None,
+        }
+    }
+
     fn clamp_input(&self, input: &mut [f32]) {
         if input.len() != self.domain.len() {
             warn!("the domain of the function didn't match the input arguments");
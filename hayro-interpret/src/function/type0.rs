@@ -3,15 +3,15 @@ use hayro_syntax::bit_reader::BitReader;
 use hayro_syntax::object::Array;
 use hayro_syntax::object::Stream;
 use hayro_syntax::object::dict::keys::{BITS_PER_SAMPLE, DECODE, ENCODE, SIZE};
-use rustc_hash::FxHashMap;
-use smallvec::{SmallVec, ToSmallVec, smallvec};
+use smallvec::{SmallVec, smallvec};
 
 /// A type 0 function (sampled function).
 #[derive(Debug)]
 pub(crate) struct Type0 {
     sizes: IntVec,
-    table: FxHashMap<Key, IntVec>,
-    clamper: Clamper,
+    /// The samples, with the first input dimension varying fastest.
+    table: Vec<u32>,
+    pub(super) clamper: Clamper,
     range: TupleVec,
     bits_per_sample: u8,
     encode: TupleVec,
@@ -44,13 +44,19 @@ impl Type0 {
             .iter::<u32>()
             .collect::<IntVec>();
 
+        if sizes.is_empty() || sizes.contains(&0) {
+            warn!("encountered Type0 function with invalid size");
+
+            return None;
+        }
+
         let encode = dict
             .get::<TupleVec>(ENCODE)
             .unwrap_or(sizes.iter().map(|s| (0.0, (*s - 1) as f32)).collect());
 
         let decode = dict.get::<TupleVec>(DECODE).unwrap_or(range.clone());
 
-        let mut data = {
+        let table = {
             let decoded = stream.decoded().ok()?;
             let mut buf = vec![];
             let mut reader = BitReader::new(&decoded);
@@ -62,15 +68,15 @@ impl Type0 {
             buf
         };
 
-        let num_expected_entries = sizes.iter().fold(1, |i1, i2| i1 * *i2 as usize) * range.len();
+        let num_expected_entries = sizes
+            .iter()
+            .try_fold(range.len(), |n, size| n.checked_mul(*size as usize));
 
-        if data.len() != num_expected_entries {
+        if num_expected_entries != Some(table.len()) {
+            // Missing samples are treated as zero during evaluation.
             warn!("Type0 function didn't have the expected number of sample entries.");
-            data.truncate(num_expected_entries);
         }
 
-        let table = build_table(&data, &sizes, range.len())?;
-
         Some(Self {
             sizes,
             clamper,
@@ -107,15 +113,16 @@ impl Type0 {
         let in_prev = key.iter().map(|v| v.floor() as u32).collect::<IntVec>();
         let in_next = key.iter().map(|v| v.ceil() as u32).collect::<IntVec>();
 
-        let interpolator = Interpolator::new(
-            key.clone().to_smallvec(),
+        let interpolator = Interpolator {
+            input: key,
             in_prev,
             in_next,
-            self.sizes.clone(),
-            self.range.len(),
-        );
+            sizes: &self.sizes,
+            out_len: self.range.len(),
+            table: &self.table,
+        };
 
-        let interpolated = interpolator.interpolate(&self.table)?;
+        let interpolated = interpolator.interpolate();
 
         let mut out = interpolated
             .iter()
@@ -124,7 +131,7 @@ impl Type0 {
                 interpolate(
                     *x,
                     0.0,
-                    (2_u32.pow(self.bits_per_sample as u32) - 1) as f32,
+                    ((1_u64 << self.bits_per_sample) - 1) as f32,
                     decode.0,
                     decode.1,
                 )
@@ -140,163 +147,105 @@ impl Type0 {
 type FloatVec = SmallVec<[f32; 4]>;
 type IntVec = SmallVec<[u32; 4]>;
 
+/// Multilinear interpolation between the samples surrounding the input.
 // See <https://github.com/apache/pdfbox/blob/bb778d4784f354c36ce032e91a0cee2169a4c598/pdfbox/src/main/java/org/apache/pdfbox/pdmodel/common/function/PDFunctionType0.java#L252>
-struct Interpolator {
-    input: FloatVec,
-    sizes: IntVec,
+struct Interpolator<'a> {
+    input: Values,
+    sizes: &'a [u32],
     in_prev: IntVec,
     in_next: IntVec,
     out_len: usize,
+    table: &'a [u32],
 }
 
-impl Interpolator {
-    fn new(
-        input: FloatVec,
-        in_prev: IntVec,
-        in_next: IntVec,
-        sizes: IntVec,
-        out_len: usize,
-    ) -> Self {
-        Self {
-            input,
-            in_prev,
-            in_next,
-            sizes,
-            out_len,
-        }
-    }
-
-    fn interpolate(&self, table: &FxHashMap<Key, IntVec>) -> Option<FloatVec> {
-        self.interpolate_inner(smallvec![0; self.input.len()], 0, table)
+impl Interpolator<'_> {
+    fn interpolate(&self) -> FloatVec {
+        self.interpolate_inner(smallvec![0; self.input.len()], 0)
     }
 
-    fn interpolate_inner(
-        &self,
-        mut coord: IntVec,
-        step: usize,
-        table: &FxHashMap<Key, IntVec>,
-    ) -> Option<FloatVec> {
-        if step == self.input.len() - 1 {
-            if self.in_prev[step] == self.in_next[step] {
-                coord[step] = self.in_prev[step];
-
-                Some(
-                    table
-                        .get(&Key::from_raw(&self.sizes, &coord))?
-                        .clone()
-                        .iter()
-                        .map(|n| *n as f32)
-                        .collect(),
-                )
-            } else {
-                coord[step] = self.in_prev[step];
-                let val1 = table.get(&Key::from_raw(&self.sizes, &coord))?;
-                coord[step] = self.in_next[step];
-                let val2 = table.get(&Key::from_raw(&self.sizes, &coord))?;
-                let mut out = smallvec![0.0; self.out_len];
-
-                for i in 0..self.out_len {
-                    out[i] = interpolate(
-                        self.input[step],
-                        self.in_prev[step] as f32,
-                        self.in_next[step] as f32,
-                        val1[i] as f32,
-                        val2[i] as f32,
-                    );
-                }
-
-                Some(out)
-            }
-        } else if self.in_prev[step] == self.in_next[step] {
-            coord[step] = self.in_prev[step];
-            self.interpolate_inner(coord, step + 1, table)
-        } else {
-            coord[step] = self.in_prev[step];
-            let val1 = self.interpolate_inner(coord.clone(), step + 1, table)?;
-            coord[step] = self.in_next[step];
-            let val2 = self.interpolate_inner(coord, step + 1, table)?;
-
-            let mut out = smallvec![0.0; self.out_len];
-
-            for i in 0..self.out_len {
-                out[i] = interpolate(
-                    self.input[step],
-                    self.in_prev[step] as f32,
-                    self.in_next[step] as f32,
-                    val1[i],
-                    val2[i],
-                );
-            }
-
-            Some(out)
+    fn interpolate_inner(&self, mut coord: IntVec, step: usize) -> FloatVec {
+        if step == self.input.len() {
+            return self.sample(&coord);
         }
-    }
-}
-
-fn build_table(data: &[u32], sizes: &[u32], n: usize) -> Option<FxHashMap<Key, IntVec>> {
-    let mut key = Key::new(sizes);
-    let mut table = FxHashMap::default();
 
-    let mut first = true;
+        coord[step] = self.in_prev[step];
 
-    for b in data.chunks_exact(n) {
-        if !first {
-            key.increment();
+        if self.in_prev[step] == self.in_next[step] {
+            return self.interpolate_inner(coord, step + 1);
         }
 
-        table.insert(key.clone(), b.to_smallvec());
+        let val1 = self.interpolate_inner(coord.clone(), step + 1);
+        coord[step] = self.in_next[step];
+        let val2 = self.interpolate_inner(coord, step + 1);
 
-        first = false;
+        val1.iter()
+            .zip(val2.iter())
+            .map(|(v1, v2)| {
+                interpolate(
+                    self.input[step],
+                    self.in_prev[step] as f32,
+                    self.in_next[step] as f32,
+                    *v1,
+                    *v2,
+                )
+            })
+            .collect()
     }
 
-    Some(table)
-}
-
-/// A sampled function consists of a (possibly) multi-dimensional table that we can index
-/// into. We do this by representing the entries as a flat list of vectors, where each
-/// element in the vector represents the value of the key in that specific dimension.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Key {
-    sizes: SmallVec<[u32; 4]>,
-    parts: SmallVec<[u32; 4]>,
-}
-
-impl Key {
-    fn new(sizes: &[u32]) -> Self {
-        let parts = smallvec![0; sizes.len()];
-
-        Self {
-            sizes: sizes.to_smallvec(),
-            parts,
-        }
-    }
+    /// Return the sample at the given coordinates, with missing values being zero.
+    fn sample(&self, coord: &[u32]) -> FloatVec {
+        let mut index = 0;
+        let mut stride = self.out_len;
 
-    fn from_raw(sizes: &[u32], parts: &[u32]) -> Self {
-        Self {
-            sizes: sizes.to_smallvec(),
-            parts: parts.to_smallvec(),
+        for (c, size) in coord.iter().zip(self.sizes) {
+            index += *c as usize * stride;
+            stride = stride.saturating_mul(*size as usize);
         }
-    }
 
-    fn increment(&mut self) -> Option<()> {
-        self.increment_index(0)
+        (0..self.out_len)
+            .map(|i| {
+                index
+                    .checked_add(i)
+                    .and_then(|i| self.table.get(i))
+                    .map_or(0.0, |n| *n as f32)
+            })
+            .collect()
     }
+}
 
-    fn increment_index(&mut self, index: usize) -> Option<()> {
-        let size = *self.sizes.get(index).or_else(|| {
-            error!("overflowed key in sampled function");
+#[cfg(test)]
+mod tests {
+    use super::Interpolator;
+    use smallvec::smallvec;
+
+    #[test]
+    fn bilinear_interpolation() {
+        // A 2x2 table with two outputs, the first input varies fastest.
+        let table = [0, 100, 10, 110, 20, 120, 30, 130];
+        let interpolator = Interpolator {
+            input: smallvec![0.5, 0.25],
+            sizes: &[2, 2],
+            in_prev: smallvec![0, 0],
+            in_next: smallvec![1, 1],
+            out_len: 2,
+            table: &table,
+        };
 
-            None
-        })?;
-        let val = self.parts.get_mut(index)?;
+        assert_eq!(interpolator.interpolate().to_vec(), vec![10.0, 110.0]);
+    }
 
-        if *val >= (size - 1) {
-            *val = 0;
-            self.increment_index(index + 1)?;
-        } else {
-            *val += 1;
-        }
+    #[test]
+    fn missing_samples() {
+        let table = [0, 100];
+        let interpolator = Interpolator {
+            input: smallvec![1.0],
+            sizes: &[2],
+            in_prev: smallvec![1],
+            in_next: smallvec![1],
+            out_len: 2,
+            table: &table,
+        };
 
-        Some(())
+        assert_eq!(interpolator.interpolate().to_vec(), vec![0.0, 0.0]);
     }
 }
@@ -9,7 +9,7 @@ use smallvec::{SmallVec, smallvec};
 pub(crate) struct Type2 {
     c0: Values,
     c1: Values,
-    clamper: Clamper,
+    pub(super) clamper: Clamper,
     n: f32,
 }
 
@@ -24,6 +24,22 @@ impl Type2 {
         Some(Self { c0, c1, clamper, n })
     }
 
+    /// Create a function that interpolates linearly between `c0` and `c1` for
+    /// inputs in the interval [0, 1].
+    pub(crate) fn linear(c0: Values, c1: Values) -> Self {
+        Self {
+            c0,
+            c1,
+            clamper: Clamper::unit(),
+            n: 1.0,
+        }
+    }
+
+    /// Return the number of outputs of the function.
+    pub(crate) fn num_outputs(&self) -> usize {
+        self.c0.len().min(self.c1.len())
+    }
+
     /// Evaluate the function with the given input.
     pub(crate) fn eval(&self, input: f32) -> Values {
         let mut input = [input];
@@ -88,6 +104,10 @@ mod tests {
         .unwrap();
 
         assert_eq!(func.eval(smallvec![0.5]), Some(smallvec![7.5]));
+        assert_eq!(func.domain(), &[(0.0, 1.0)]);
+        assert_eq!(func.range(), None);
+        assert_eq!(func.num_inputs(), 1);
+        assert_eq!(func.num_outputs(), Some(1));
     }
 
     #[test]
@@ -11,7 +11,7 @@ pub(crate) struct Type3 {
     functions: Vec<Function>,
     bounds: Vec<f32>,
     encode: TupleVec,
-    clamper: Clamper,
+    pub(super) clamper: Clamper,
 }
 
 impl Type3 {
@@ -40,6 +40,22 @@ impl Type3 {
         })
     }
 
+    /// Create a stitching function for inputs in the interval [0, 1], where the
+    /// subfunctions are separated by the given bounds and each of them is
+    /// evaluated for inputs in the interval [0, 1].
+    pub(crate) fn stitch(functions: Vec<Function>, inner_bounds: &[f32]) -> Self {
+        let mut bounds = vec![-0.0001];
+        bounds.extend_from_slice(inner_bounds);
+        bounds.push(1.0001);
+
+        Self {
+            encode: functions.iter().map(|_| (0.0, 1.0)).collect(),
+            functions,
+            clamper: Clamper::unit(),
+            bounds,
+        }
+    }
+
     /// Evaluate the function with the given input.
     pub(crate) fn eval(&self, input: f32) -> Option<Values> {
         let mut input = [input];
@@ -62,6 +78,11 @@ impl Type3 {
         Some(evaluated)
     }
 
+    /// Return the number of outputs of the function, based on its first subfunction.
+    pub(crate) fn num_outputs(&self) -> Option<usize> {
+        self.functions.first()?.num_outputs()
+    }
+
     pub(crate) fn stitching_bounds(&self) -> StitchingBounds {
         let mut stitching_bounds = StitchingBounds::new();
         if self.bounds.len() > 2 {
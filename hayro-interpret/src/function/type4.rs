@@ -12,7 +12,7 @@ use std::ops::Rem;
 #[derive(Debug)]
 pub(crate) struct Type4 {
     program: Vec<PostScriptOp>,
-    clamper: Clamper,
+    pub(super) clamper: Clamper,
 }
 
 impl Type4 {
@@ -414,12 +414,21 @@ fn eval_inner(procedure: &[PostScriptOp], arg_stack: &mut InterpreterStack) -> O
     Some(())
 }
 
+/// The maximum nesting depth of procedures, to prevent stack overflows.
+const MAX_NESTING_DEPTH: usize = 32;
+
 fn parse_procedure(data: &[u8]) -> Option<Vec<PostScriptOp>> {
     let mut r = Reader::new(data);
-    parse_procedure_inner(&mut r)
+    parse_procedure_inner(&mut r, 0)
 }
 
-fn parse_procedure_inner(r: &mut Reader<'_>) -> Option<Vec<PostScriptOp>> {
+fn parse_procedure_inner(r: &mut Reader<'_>, depth: usize) -> Option<Vec<PostScriptOp>> {
+    if depth > MAX_NESTING_DEPTH {
+        warn!("postscript procedures are nested too deeply");
+
+        return None;
+    }
+
     let mut stack = ParseStack::new();
 
     let mut ops = vec![];
@@ -434,7 +443,7 @@ fn parse_procedure_inner(r: &mut Reader<'_>) -> Option<Vec<PostScriptOp>> {
 
             break;
         } else if r.peek_byte()? == b'{' {
-            stack.push(parse_procedure_inner(r)?);
+            stack.push(parse_procedure_inner(r, depth + 1)?);
         } else {
             let op = PostScriptOp::from_reader(r, &mut stack)?;
             ops.push(op);
@@ -584,6 +593,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lex_deeply_nested() {
+        let nested = |depth: usize| {
+            let mut program = "{ ".repeat(depth + 1);
+            program.push_str(&"} if ".repeat(depth));
+            program.push('}');
+            program
+        };
+
+        assert!(parse_procedure(nested(32).as_bytes()).is_some());
+        assert!(parse_procedure(nested(100_000).as_bytes()).is_none());
+    }
+
     #[test]
     fn lex_3() {
         let program = b" {  {dup exch} if {0} {1} ifelse }";
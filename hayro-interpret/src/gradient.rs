@@ -3,14 +3,20 @@
 //! PDF shadings are very expensive to evaluate. Because of this, at least for
 //! radial-axial shadings it's useful if we can instead approximate them by
 //! SVG-like sRGB gradients.
+//!
+//! This module also contains the gradient and mesh primitives that are passed
+//! to devices that can draw shadings natively, see
+//! [`Device::draw_axial_gradient`](crate::Device::draw_axial_gradient).
 
-use crate::color::AlphaColor;
-use crate::encode::{EncodedRadialAxialShading, EncodedShadingPattern, RadialAxialParams};
+use crate::encode::{
+    EncodedRadialAxialShading, EncodedShadingPattern, EncodedShadingType, RadialAxialParams,
+};
 use crate::function::StitchingBounds;
+use crate::pattern::ShadingPattern;
+use crate::shading::{ShadingType, TriangleVertex};
 use kurbo::{Affine, Circle, Point, Rect, Shape};
 use smallvec::smallvec;
 
-const MAX_GRADIENT_SUBDIVISION_DEPTH: u8 = 10;
 const RADIAL_EPSILON: f64 = 1.0e-6;
 
 /// An SVG-like gradient.
@@ -53,13 +59,244 @@ pub struct SvgGradientStop {
     pub color: [f32; 4],
 }
 
+/// An axial gradient that is painted by an axial shading.
+#[derive(Clone)]
+pub struct AxialGradient {
+    /// The start point of the gradient axis.
+    pub start: Point,
+    /// The end point of the gradient axis.
+    pub end: Point,
+    /// Whether the gradient is extended beyond the start and the end point.
+    pub extend: [bool; 2],
+    /// The transform from the coordinate space of the gradient into device space.
+    pub transform: Affine,
+    pattern: ShadingPattern,
+}
+
+impl AxialGradient {
+    pub(crate) fn new(pattern: &ShadingPattern) -> Option<Self> {
+        let ShadingType::RadialAxial {
+            coords,
+            extend,
+            axial: true,
+            ..
+        } = pattern.shading.shading_type.as_ref()
+        else {
+            return None;
+        };
+
+        let [x0, y0, x1, y1, _, _] = coords.map(f64::from);
+
+        Some(Self {
+            start: Point::new(x0, y0),
+            end: Point::new(x1, y1),
+            extend: *extend,
+            transform: pattern.matrix,
+            pattern: pattern.clone(),
+        })
+    }
+
+    /// Return the color stops of the gradient.
+    ///
+    /// The stops approximate the shading function within the smoothness of the pattern.
+    pub fn stops(&self) -> Vec<SvgGradientStop> {
+        gradient_stops(&self.pattern)
+    }
+
+    /// Return the shading pattern the gradient was created from.
+    pub fn pattern(&self) -> &ShadingPattern {
+        &self.pattern
+    }
+}
+
+/// A radial gradient that is painted by a radial shading.
+#[derive(Clone)]
+pub struct RadialGradient {
+    /// The center point of the start circle.
+    pub start_center: Point,
+    /// The radius of the start circle.
+    pub start_radius: f32,
+    /// The center point of the end circle.
+    pub end_center: Point,
+    /// The radius of the end circle.
+    pub end_radius: f32,
+    /// Whether the gradient is extended beyond the start and the end circle.
+    pub extend: [bool; 2],
+    /// The transform from the coordinate space of the gradient into device space.
+    pub transform: Affine,
+    pattern: ShadingPattern,
+}
+
+impl RadialGradient {
+    pub(crate) fn new(pattern: &ShadingPattern) -> Option<Self> {
+        let ShadingType::RadialAxial {
+            coords,
+            extend,
+            axial: false,
+            ..
+        } = pattern.shading.shading_type.as_ref()
+        else {
+            return None;
+        };
+
+        let [x0, y0, r0, x1, y1, r1] = *coords;
+
+        Some(Self {
+            start_center: Point::new(x0 as f64, y0 as f64),
+            start_radius: r0,
+            end_center: Point::new(x1 as f64, y1 as f64),
+            end_radius: r1,
+            extend: *extend,
+            transform: pattern.matrix,
+            pattern: pattern.clone(),
+        })
+    }
+
+    /// Return the color stops of the gradient.
+    ///
+    /// The stops approximate the shading function within the smoothness of the pattern.
+    pub fn stops(&self) -> Vec<SvgGradientStop> {
+        gradient_stops(&self.pattern)
+    }
+
+    /// Return the shading pattern the gradient was created from.
+    pub fn pattern(&self) -> &ShadingPattern {
+        &self.pattern
+    }
+}
+
+/// A triangle mesh that is painted by a mesh shading.
+///
+/// Coons and tensor-product patches are subdivided into triangles, with a
+/// precision that depends on the smoothness of the pattern.
+#[derive(Clone)]
+pub struct Mesh {
+    /// The transform from the coordinate space of the mesh into device space.
+    pub transform: Affine,
+    pattern: ShadingPattern,
+}
+
+/// A triangle of a mesh with a color at each vertex.
+pub struct MeshTriangle {
+    /// The vertices of the triangle.
+    pub points: [Point; 3],
+    /// The RGBA colors of the vertices in sRGB, which should be interpolated
+    /// linearly across the triangle.
+    ///
+    /// If the shading has a function, it is evaluated at the vertices, so for
+    /// non-linear functions the interpolated colors are only an approximation.
+    pub colors: [[f32; 4]; 3],
+}
+
+impl Mesh {
+    pub(crate) fn new(pattern: &ShadingPattern) -> Option<Self> {
+        match pattern.shading.shading_type.as_ref() {
+            ShadingType::TriangleMesh { .. }
+            | ShadingType::CoonsPatchMesh { .. }
+            | ShadingType::TensorProductPatchMesh { .. } => Some(Self {
+                transform: pattern.matrix,
+                pattern: pattern.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Return the triangles of the mesh.
+    pub fn triangles(&self) -> Vec<MeshTriangle> {
+        let mut triangles = vec![];
+
+        let function = match self.pattern.shading.shading_type.as_ref() {
+            ShadingType::TriangleMesh {
+                triangles: t,
+                function,
+            } => {
+                triangles.extend(t.iter().cloned());
+                function
+            }
+            ShadingType::CoonsPatchMesh { patches, function } => {
+                for patch in patches {
+                    patch.to_triangles(
+                        self.pattern.patch_grid_size(&patch.control_points),
+                        &mut triangles,
+                    );
+                }
+                function
+            }
+            ShadingType::TensorProductPatchMesh { patches, function } => {
+                for patch in patches {
+                    patch.to_triangles(
+                        self.pattern.patch_grid_size(&patch.control_points),
+                        &mut triangles,
+                    );
+                }
+                function
+            }
+            _ => return vec![],
+        };
+
+        let color_space = &self.pattern.shading.color_space;
+        let color = |vertex: &TriangleVertex| {
+            let components = match function {
+                Some(function) => function.eval(&vertex.colors),
+                None => Some(vertex.colors.clone()),
+            };
+
+            components
+                .map(|c| {
+                    self.pattern
+                        .finish_color(color_space.to_rgba(&c, 1.0, false).components())
+                })
+                .unwrap_or([0.0, 0.0, 0.0, 0.0])
+        };
+
+        triangles
+            .iter()
+            .map(|t| MeshTriangle {
+                points: [t.p0.point, t.p1.point, t.p2.point],
+                colors: [color(&t.p0), color(&t.p1), color(&t.p2)],
+            })
+            .collect()
+    }
+
+    /// Return the shading pattern the mesh was created from.
+    pub fn pattern(&self) -> &ShadingPattern {
+        &self.pattern
+    }
+}
+
+pub(crate) fn gradient_stops(pattern: &ShadingPattern) -> Vec<SvgGradientStop> {
+    // Encoding other shadings is expensive, for example meshes are triangulated.
+    if !matches!(
+        pattern.shading.shading_type.as_ref(),
+        ShadingType::RadialAxial { .. }
+    ) {
+        return vec![];
+    }
+
+    let encoded = pattern.encode();
+    let EncodedShadingType::RadialAxial(shading) = &encoded.shading_type else {
+        return vec![];
+    };
+
+    approximate_gradient_stops(
+        |t| shading.sample_t(&encoded, t),
+        &shading.normalized_stitching_bounds(),
+        pattern.smoothness,
+        pattern.gradient_subdivisions,
+    )
+}
+
 impl EncodedRadialAxialShading {
     /// Convert an axial/radial PDF shading into an SVG-like gradient approximation.
+    ///
+    /// The intervals of the gradient are halved at most `max_subdivisions` times
+    /// until the colors are within the given tolerance.
     pub fn as_svg_gradient(
         &self,
         pattern: &EncodedShadingPattern,
         path_bbox: Rect,
         tolerance: f32,
+        max_subdivisions: u8,
     ) -> Option<SvgGradient> {
         // A couple of cases cannot be losslessly represented by an SVG gradient.
         match self.params {
@@ -84,6 +321,7 @@ impl EncodedRadialAxialShading {
                 |t| self.sample_t(pattern, t),
                 &self.normalized_stitching_bounds(),
                 tolerance,
+                max_subdivisions,
             ),
         })
     }
@@ -109,14 +347,8 @@ impl EncodedRadialAxialShading {
         let Some(out) = self.function.eval(&smallvec![t]) else {
             return [0.0, 0.0, 0.0, 0.0];
         };
-        let mut components = pattern.color_space.to_rgba(&out, 1.0, false).components();
-        components[3] *= pattern.opacity;
 
-        if let Some(tf) = &pattern.transfer_function {
-            return tf.apply(&AlphaColor::new(components)).components();
-        }
-
-        components
+        pattern.finish_color(pattern.color_space.to_rgba(&out, 1.0, false).components())
     }
 
     fn axial_extend_covers_bbox(&self, pattern: &EncodedShadingPattern, path_bbox: Rect) -> bool {
@@ -223,22 +455,31 @@ fn approximate_gradient_stops(
     mut sample: impl FnMut(f32) -> [f32; 4],
     breakpoints: &[f32],
     tolerance: f32,
+    max_subdivisions: u8,
 ) -> Vec<SvgGradientStop> {
     let mut interval_bounds = Vec::with_capacity(breakpoints.len() + 2);
     interval_bounds.push(0.0);
     interval_bounds.extend(breakpoints.iter().copied());
     interval_bounds.push(1.0);
 
-    let mut stops = Vec::new();
+    let mut stops: Vec<SvgGradientStop> = Vec::new();
     for interval in interval_bounds.windows(2) {
         let start_offset = interval[0];
         let end_offset = interval[1];
 
         let start_color = sample(start_offset);
-        stops.push(SvgGradientStop {
-            offset: start_offset,
-            color: start_color,
-        });
+
+        // The previous interval already ends with the same stop, unless the
+        // function jumps at the stitching bound.
+        if stops
+            .last()
+            .is_none_or(|last| last.offset != start_offset || last.color != start_color)
+        {
+            stops.push(SvgGradientStop {
+                offset: start_offset,
+                color: start_color,
+            });
+        }
 
         let end_color = sample(end_offset);
 
@@ -254,7 +495,7 @@ fn approximate_gradient_stops(
                 color: end_color,
             },
             tolerance.max(0.0),
-            0,
+            max_subdivisions,
         );
     }
 
@@ -273,7 +514,7 @@ fn approximate_gradient_interval(
     start: GradientSample,
     end: GradientSample,
     tolerance: f32,
-    depth: u8,
+    remaining_subdivisions: u8,
 ) {
     let offset_delta = end.offset - start.offset;
     let quarter_offset = start.offset + offset_delta * 0.25;
@@ -290,7 +531,7 @@ fn approximate_gradient_interval(
             three_quarter_color,
             lerp_color(start.color, end.color, 0.75),
         ) <= tolerance
-        || depth == MAX_GRADIENT_SUBDIVISION_DEPTH
+        || remaining_subdivisions == 0
     {
         stops.push(SvgGradientStop {
             offset: end.offset,
@@ -305,8 +546,9 @@ fn approximate_gradient_interval(
         color: mid_color,
     };
 
-    approximate_gradient_interval(sample, stops, start, mid, tolerance, depth + 1);
-    approximate_gradient_interval(sample, stops, mid, end, tolerance, depth + 1);
+    let remaining = remaining_subdivisions - 1;
+    approximate_gradient_interval(sample, stops, start, mid, tolerance, remaining);
+    approximate_gradient_interval(sample, stops, mid, end, tolerance, remaining);
 }
 
 fn lerp_color(c0: [f32; 4], c1: [f32; 4], t: f32) -> [f32; 4] {
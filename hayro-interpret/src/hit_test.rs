@@ -0,0 +1,385 @@
+//! Finding the objects at a position of a page.
+//!
+//! The [`HitTestDevice`] can be passed to [`interpret_page`] instead of a painting
+//! device. It records the glyph runs, images and paths that lie under a point or
+//! intersect a rectangle, which viewers can use to implement click-to-select or to pick
+//! figures. [`hit_test`] additionally reports the annotations of the page, like links.
+//!
+//! All coordinates are in device space, i.e. the default user space of the page if the
+//! context was created with an identity transform.
+
+use crate::font::{Glyph, GlyphRun, RunGlyph};
+use crate::util::{AffineExt, RectExt};
+use crate::{
+    BlendMode, ClipPath, Context, Device, DrawMode, DrawProps, FillRule, Image, ImageDrawProps,
+    SoftMask, TextRenderingMode, interpret_page,
+};
+use hayro_syntax::object::dict::keys::{ANNOTS, F, RECT, SUBTYPE};
+use hayro_syntax::object::{self, Array, Dict, Name};
+use hayro_syntax::page::Page;
+use kurbo::{Affine, BezPath, Point, Rect, Shape, Vec2};
+
+/// The ascent and descent used to approximate the box of a glyph, relative to the em
+/// square of 1000 units.
+const ASCENT: f64 = 800.0;
+const DESCENT: f64 = 200.0;
+
+/// An object that was hit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// The kind of the object.
+    pub kind: HitKind,
+    /// The bounding box of the object in device space, restricted to the active clip
+    /// paths.
+    pub bounds: Rect,
+}
+
+/// The kind of an object that was hit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HitKind {
+    /// A run of glyphs that were shown with a single text operator.
+    GlyphRun {
+        /// The text of the glyphs, as far as it could be determined.
+        text: String,
+        /// Whether the text is invisible, which for example is the case for the OCR
+        /// layer of scanned documents.
+        invisible: bool,
+    },
+    /// An image.
+    Image,
+    /// A filled or stroked path.
+    Path {
+        /// Whether the path was stroked instead of filled.
+        stroked: bool,
+    },
+    /// An annotation, like a link or a form field.
+    Annotation {
+        /// The index of the annotation in the `Annots` array of the page.
+        index: usize,
+        /// The subtype of the annotation, for example `Link`.
+        subtype: Option<String>,
+    },
+}
+
+/// The clip paths that are active while drawing.
+#[derive(Clone, Copy)]
+struct Clip {
+    /// The intersection of the bounding boxes of the clip paths.
+    bounds: Rect,
+    /// Whether the target lies within all clip paths.
+    contains_target: bool,
+}
+
+/// A device that collects the objects that lie under a point or intersect a
+/// rectangle.
+///
+/// For a point, the actual shape of filled paths and clip paths is taken into
+/// account, while glyphs are hit within their advance and an approximated ascent and
+/// descent, or the font bounding box for type3 glyphs. For a rectangle, it is only
+/// checked whether the bounding boxes intersect it. Strokes are always tested against
+/// their bounding box, and the contents of type3 glyphs, patterns and soft masks are
+/// not considered.
+pub struct HitTestDevice {
+    target: Rect,
+    clips: Vec<Clip>,
+    hits: Vec<Hit>,
+}
+
+impl HitTestDevice {
+    /// Create a new device that collects the objects intersecting the given rectangle.
+    pub fn new(target: Rect) -> Self {
+        Self {
+            target: target.abs(),
+            clips: vec![],
+            hits: vec![],
+        }
+    }
+
+    /// Create a new device that collects the objects under the given point.
+    pub fn at_point(point: Point) -> Self {
+        Self::new(Rect::from_points(point, point))
+    }
+
+    /// Return the objects that were hit, with the topmost one first.
+    pub fn into_hits(mut self) -> Vec<Hit> {
+        self.hits.reverse();
+        self.hits
+    }
+
+    fn is_point(&self) -> bool {
+        self.target.width() == 0.0 && self.target.height() == 0.0
+    }
+
+    fn clip(&self) -> Clip {
+        self.clips.last().copied().unwrap_or(Clip {
+            bounds: Rect::new(f64::MIN, f64::MIN, f64::MAX, f64::MAX),
+            contains_target: true,
+        })
+    }
+
+    /// Return whether the target intersects the given rectangle, after transforming it
+    /// into device space.
+    fn hits_rect(&self, rect: Rect, transform: Affine) -> bool {
+        if self.is_point()
+            && let Some(inverse) = transform.checked_inverse()
+        {
+            return contains(rect, inverse * self.target.origin());
+        }
+
+        overlaps(transform.transform_rect_bbox(rect), self.target)
+    }
+
+    /// Return whether the target lies within the given path, in the case of a point, or
+    /// intersects its bounding box otherwise.
+    fn hits_path(&self, path: &BezPath, transform: Affine, fill_rule: FillRule) -> bool {
+        if self.is_point()
+            && let Some(inverse) = transform.checked_inverse()
+        {
+            let winding = path.winding(inverse * self.target.origin());
+
+            return match fill_rule {
+                FillRule::NonZero => winding != 0,
+                FillRule::EvenOdd => winding % 2 != 0,
+            };
+        }
+
+        overlaps(
+            transform.transform_rect_bbox(path.bounding_box()),
+            self.target,
+        )
+    }
+
+    /// Record a hit for an object with the given bounding box, unless the target lies
+    /// outside of the active clip paths.
+    fn push(&mut self, kind: HitKind, bounds: Rect) {
+        let clip = self.clip();
+
+        if clip.contains_target {
+            self.hits.push(Hit {
+                kind,
+                bounds: bounds.intersect(clip.bounds),
+            });
+        }
+    }
+
+    fn hit_glyphs<'g, 'b: 'g>(
+        &mut self,
+        glyphs: impl IntoIterator<Item = &'g RunGlyph<'b>>,
+        transform: Affine,
+        invisible: bool,
+    ) {
+        let mut text = String::new();
+        let mut bounds: Option<Rect> = None;
+        let mut is_hit = false;
+
+        for glyph in glyphs {
+            let glyph_box = glyph_box(&glyph.glyph);
+            let glyph_transform = transform * glyph.transform;
+
+            is_hit |= self.hits_rect(glyph_box, glyph_transform);
+
+            let glyph_bounds = glyph_transform.transform_rect_bbox(glyph_box);
+            bounds = Some(bounds.map_or(glyph_bounds, |b| b.union(glyph_bounds)));
+
+            if let Some(unicode) = glyph.glyph.as_unicode() {
+                text.push_str(&unicode.to_string());
+            }
+        }
+
+        if let Some(bounds) = bounds.filter(|_| is_hit) {
+            self.push(HitKind::GlyphRun { text, invisible }, bounds);
+        }
+    }
+}
+
+impl<'a> Device<'a> for HitTestDevice {
+    fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        let (is_hit, bounds, stroked) = match draw_mode {
+            DrawMode::Fill(fill_rule) => (
+                self.hits_path(path, props.transform, *fill_rule),
+                path.bounding_box(),
+                false,
+            ),
+            DrawMode::Stroke(stroke_props) | DrawMode::FillAndStroke(_, stroke_props) => {
+                let half_width = stroke_props.line_width as f64 / 2.0;
+                let bounds = path.bounding_box().inflate(half_width, half_width);
+
+                (self.hits_rect(bounds, props.transform), bounds, true)
+            }
+            DrawMode::Invisible => return,
+        };
+
+        if is_hit {
+            self.push(
+                HitKind::Path { stroked },
+                props.transform.transform_rect_bbox(bounds),
+            );
+        }
+    }
+
+    fn push_clip_path(&mut self, clip_path: &ClipPath) {
+        let parent = self.clip();
+        let bounds = clip_path.path.bounding_box();
+
+        self.clips.push(Clip {
+            bounds: parent.bounds.intersect(bounds),
+            contains_target: parent.contains_target
+                && self.hits_path(&clip_path.path, Affine::IDENTITY, clip_path.fill),
+        });
+    }
+
+    fn push_clip_rect(&mut self, rect: &Rect) {
+        let parent = self.clip();
+
+        self.clips.push(Clip {
+            bounds: parent.bounds.intersect(*rect),
+            contains_target: parent.contains_target && self.hits_rect(*rect, Affine::IDENTITY),
+        });
+    }
+
+    fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'a>>, _: BlendMode) {}
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let glyph = RunGlyph {
+            glyph: glyph.clone(),
+            transform: glyph_transform,
+            advance: Vec2::ZERO,
+        };
+
+        self.hit_glyphs(
+            [&glyph],
+            props.transform,
+            matches!(draw_mode, DrawMode::Invisible),
+        );
+    }
+
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'a>) {
+        let Some(transform) = run
+            .fill
+            .as_ref()
+            .or(run.stroke.as_ref().map(|(props, _)| props))
+            .map(|props| props.transform)
+        else {
+            return;
+        };
+
+        self.hit_glyphs(
+            &run.glyphs,
+            transform,
+            run.render_mode == TextRenderingMode::Invisible,
+        );
+    }
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        // The transform maps from the pixel space of the image.
+        let rect = Rect::new(0.0, 0.0, image.width() as f64, image.height() as f64);
+
+        if self.hits_rect(rect, props.transform) {
+            self.push(HitKind::Image, props.transform.transform_rect_bbox(rect));
+        }
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn pop_transparency_group(&mut self) {}
+}
+
+/// Return the objects of a page that lie under a point or intersect a rectangle in
+/// device space, with the topmost one first.
+///
+/// In addition to what a [`HitTestDevice`] collects, this reports the annotations of
+/// the page that aren't hidden, even if they don't have an appearance, which is often
+/// the case for links. Since viewers usually want to handle them first, annotations
+/// are returned before all other objects.
+///
+/// A target with a width and height of zero is treated as a point.
+pub fn hit_test<'a>(page: &Page<'a>, context: &mut Context<'a>, target: Rect) -> Vec<Hit> {
+    const HIDDEN: u32 = 1 << 1;
+    const NO_VIEW: u32 = 1 << 5;
+
+    let transform = context.root_transform();
+    let mut device = HitTestDevice::new(target);
+    interpret_page(page, context, &mut device);
+
+    let mut hits = vec![];
+
+    if let Some(annots) = page.raw().get::<Array<'_>>(ANNOTS) {
+        for (index, annot) in annots.iter::<Dict<'_>>().enumerate() {
+            if annot.get::<u32>(F).unwrap_or(0) & (HIDDEN | NO_VIEW) != 0 {
+                continue;
+            }
+
+            let Some(rect) = annot.get::<object::Rect>(RECT) else {
+                continue;
+            };
+            let rect = rect.to_kurbo().abs();
+
+            if device.hits_rect(rect, transform) {
+                hits.push(Hit {
+                    kind: HitKind::Annotation {
+                        index,
+                        subtype: annot
+                            .get::<Name<'_>>(SUBTYPE)
+                            .map(|s| s.as_str().to_string()),
+                    },
+                    bounds: transform.transform_rect_bbox(rect),
+                });
+            }
+        }
+    }
+
+    hits.reverse();
+    hits.extend(device.into_hits());
+
+    hits
+}
+
+/// Return the approximate box of a glyph in glyph space.
+fn glyph_box(glyph: &Glyph<'_>) -> Rect {
+    match glyph {
+        Glyph::Outline(outline) => {
+            let advance = outline
+                .advance_width()
+                .map(f64::from)
+                .unwrap_or_else(|| outline.outline().bounding_box().x1);
+
+            Rect::new(0.0, -DESCENT, advance, ASCENT).abs()
+        }
+        Glyph::Type3(glyph) => {
+            // The glyphs of a Type3 font can extend arbitrarily far beyond the em
+            // square, so the font bounding box is used instead. Many fonts leave it
+            // empty, though.
+            let bbox = glyph.font.bbox();
+            let (y0, y1) = if bbox.height() > 0.0 {
+                (bbox.y0, bbox.y1)
+            } else {
+                (-DESCENT, ASCENT)
+            };
+            let advance = f64::from(glyph.font.glyph_width(glyph.char_code as u8));
+
+            if advance != 0.0 {
+                Rect::new(0.0, y0, advance, y1).abs()
+            } else {
+                Rect::new(bbox.x0, y0, bbox.x1, y1)
+            }
+        }
+    }
+}
+
+/// Return whether the rectangle contains the point, including its edges.
+fn contains(rect: Rect, point: Point) -> bool {
+    point.x >= rect.x0 && point.x <= rect.x1 && point.y >= rect.y0 && point.y <= rect.y1
+}
+
+/// Return whether the rectangles intersect, including their edges.
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
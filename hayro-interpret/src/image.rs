@@ -0,0 +1,204 @@
+//! Extracting the images of a page.
+//!
+//! The [`ImageDevice`] can be passed to [`interpret_page`]
+//! instead of a painting device. It decodes every image that is drawn and records
+//! where it was placed, without rasterizing anything. [`PageImagesExt`] and
+//! [`PdfImagesExt`] wrap it for the common case of exporting the images of a page or
+//! a whole document.
+
+use crate::color::ColorSpace;
+use crate::font::Glyph;
+use crate::{
+    BlendMode, ClipPath, Context, Device, DrawMode, DrawProps, Image, ImageData, ImageDrawProps,
+    InterpreterCache, InterpreterSettings, LumaData, SoftMask, interpret_page,
+};
+use hayro_syntax::Pdf;
+use hayro_syntax::page::Page;
+use kurbo::{Affine, BezPath, Rect};
+use rustc_hash::FxHashMap;
+
+/// An image collected by an [`ImageDevice`].
+#[derive(Clone)]
+pub struct ExtractedImage {
+    /// The decoded color data of the image, converted to RGB or grayscale.
+    ///
+    /// For stencil masks, this is the mask itself, where 255 marks the pixels
+    /// that are painted.
+    pub data: ImageData,
+    /// The alpha channel of the image, if it has a soft mask or color key mask.
+    ///
+    /// Note that the alpha channel can have a different resolution than the image,
+    /// in which case it needs to be stretched over the same area.
+    pub alpha: Option<LumaData>,
+    /// The color space of the image, or `None` for stencil masks and JPEG 2000
+    /// images that use their embedded color space.
+    pub color_space: Option<ColorSpace>,
+    /// The number of bits per component of the encoded image, if specified.
+    pub bits_per_component: Option<u8>,
+    /// Whether the image is a stencil mask.
+    pub stencil: bool,
+    /// The transforms from the pixels of [`ExtractedImage::data`] (with the origin in
+    /// the top left corner) to user space, one for each time the image is drawn.
+    pub transforms: Vec<Affine>,
+}
+
+/// A device that collects the images of a page.
+///
+/// Images that are drawn several times are only decoded and returned once, with
+/// all of their placements. Images in tiling patterns and type3 glyphs are ignored.
+#[derive(Default)]
+pub struct ImageDevice {
+    images: Vec<ExtractedImage>,
+    /// The index of each image in `images`, by its object.
+    indices: FxHashMap<u128, usize>,
+}
+
+impl ImageDevice {
+    /// Create a new, empty image device.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the images that have been collected so far, in the order they were
+    /// first drawn.
+    pub fn images(&self) -> &[ExtractedImage] {
+        &self.images
+    }
+
+    /// Consume the device and return the collected images.
+    pub fn into_images(self) -> Vec<ExtractedImage> {
+        self.images
+    }
+}
+
+impl<'a> Device<'a> for ImageDevice {
+    fn draw_path(&mut self, _: &BezPath, _: DrawProps<'a>, _: &DrawMode) {}
+
+    fn push_clip_path(&mut self, _: &ClipPath) {}
+
+    fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'a>>, _: BlendMode) {}
+
+    fn draw_glyph(&mut self, _: &Glyph<'a>, _: Affine, _: DrawProps<'a>, _: &DrawMode) {}
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        let key = match &image {
+            Image::Stencil(s) => s.source.object_key(),
+            Image::Raster(r) => r.0.object_key(),
+        };
+
+        if let Some(index) = self.indices.get(&key) {
+            let image = &mut self.images[*index];
+            let (x, y) = image.data.scale_factors();
+            image
+                .transforms
+                .push(props.transform * Affine::scale_non_uniform(x as f64, y as f64));
+
+            return;
+        }
+
+        let mut extracted = None;
+
+        match &image {
+            Image::Stencil(s) => s.with_stencil(
+                |luma, _| {
+                    extracted = Some(ExtractedImage {
+                        data: ImageData::Luma(luma),
+                        alpha: None,
+                        color_space: None,
+                        bits_per_component: Some(1),
+                        stencil: true,
+                        transforms: vec![],
+                    });
+                },
+                None,
+            ),
+            Image::Raster(r) => r.with_rgba(
+                |data, alpha| {
+                    extracted = Some(ExtractedImage {
+                        data,
+                        alpha,
+                        color_space: r.color_space().cloned(),
+                        bits_per_component: r.bits_per_component(),
+                        stencil: false,
+                        transforms: vec![],
+                    });
+                },
+                None,
+            ),
+        }
+
+        if let Some(mut extracted) = extracted {
+            let (x, y) = extracted.data.scale_factors();
+            extracted
+                .transforms
+                .push(props.transform * Affine::scale_non_uniform(x as f64, y as f64));
+
+            self.indices.insert(key, self.images.len());
+            self.images.push(extracted);
+        }
+    }
+
+    fn pop_clip(&mut self) {}
+
+    fn pop_transparency_group(&mut self) {}
+}
+
+/// Extension methods for extracting the images of a page.
+pub trait PageImagesExt {
+    /// Return the images of the page, with transforms into the default user space
+    /// of the page.
+    ///
+    /// This uses the default interpreter settings, use [`page_images`] if you need
+    /// to configure them.
+    fn images(&self) -> Vec<ExtractedImage>;
+}
+
+impl PageImagesExt for Page<'_> {
+    fn images(&self) -> Vec<ExtractedImage> {
+        page_images(self, InterpreterSettings::default())
+    }
+}
+
+/// Extension methods for extracting the images of a document.
+pub trait PdfImagesExt {
+    /// Return the images of each page of the document.
+    ///
+    /// See [`PageImagesExt::images`] for more information.
+    fn images(&self) -> Vec<Vec<ExtractedImage>>;
+}
+
+impl PdfImagesExt for Pdf {
+    fn images(&self) -> Vec<Vec<ExtractedImage>> {
+        let cache = InterpreterCache::new();
+
+        self.pages()
+            .iter()
+            .map(|page| extract_images(page, &cache, InterpreterSettings::default()).into_images())
+            .collect()
+    }
+}
+
+/// Return the images of the page, using the given settings.
+///
+/// See [`PageImagesExt::images`] for more information.
+pub fn page_images(page: &Page<'_>, settings: InterpreterSettings) -> Vec<ExtractedImage> {
+    extract_images(page, &InterpreterCache::new(), settings).into_images()
+}
+
+fn extract_images<'a>(
+    page: &Page<'a>,
+    cache: &InterpreterCache<'a>,
+    settings: InterpreterSettings,
+) -> ImageDevice {
+    let mut context = Context::new(
+        Affine::IDENTITY,
+        Rect::new(0.0, 0.0, 1.0, 1.0),
+        cache,
+        page.xref(),
+        settings,
+    );
+    let mut device = ImageDevice::new();
+    interpret_page(page, &mut context, &mut device);
+
+    device
+}
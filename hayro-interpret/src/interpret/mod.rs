@@ -1,35 +1,48 @@
-use crate::FillRule;
-use crate::color::ColorSpace;
+use crate::color::{ColorSpace, RenderingIntent};
 use crate::context::Context;
 use crate::convert::{convert_line_cap, convert_line_join};
 use crate::device::Device;
-use crate::font::{Font, FontData, FontQuery, StandardFont};
+use crate::font::{Font, FontData, FontFallback, FontQuery, StandardFont};
 use crate::interpret::path::{
     close_path, fill_path, fill_path_impl, fill_stroke_path, stroke_path,
 };
-use crate::interpret::state::{TextStateFont, handle_gs};
-use crate::interpret::text::TextRenderingMode;
+use crate::interpret::state::{TextStateFont, handle_gs, set_dash_pattern};
 use crate::pattern::{Pattern, ShadingPattern};
 use crate::shading::Shading;
-use crate::util::{OptionLog, RectExt};
+use crate::stats::Phase;
+use crate::util::{RectExt, decode_text_string, now};
 use crate::x_object::{
     FormXObject, ImageXObject, XObject, draw_form_xobject, draw_image_xobject, draw_xobject,
+    xobject_oc,
+};
+use crate::{
+    CorruptFontPolicy, FillRule, MarkedContent, RenderQuality, UnknownOperatorPolicy,
+    ZeroWidthLinePolicy,
 };
 use hayro_syntax::content::TypedIter;
 use hayro_syntax::content::ops::TypedInstruction;
-use hayro_syntax::object::dict::keys::{ANNOTS, AP, F, MCID, N, OC, RECT};
-use hayro_syntax::object::{Array, Dict, Name, Object, Rect, Stream, dict_or_stream};
+use hayro_syntax::object::dict::keys::{
+    ACTUAL_TEXT, ALT, ANNOTS, AP, AS, BASE_FONT, CS, F, GROUP, MCID, N, OC, RECT, SUBTYPE, WIDGET,
+};
+use hayro_syntax::object::{
+    self, Array, Dict, Name, Object, ObjectIdentifier, Rect, Stream, dict_or_stream,
+};
 use hayro_syntax::page::{Page, Resources};
 use kurbo::{Affine, Point, Shape};
 use rustc_hash::FxHashMap;
 use smallvec::smallvec;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 pub(crate) mod path;
 pub(crate) mod state;
 pub(crate) mod text;
+pub(crate) mod widget;
 
 pub use state::ActiveTransferFunction;
+pub use text::TextRenderingMode;
 
 /// A callback function for resolving font queries.
 ///
@@ -37,10 +50,17 @@ pub use state::ActiveTransferFunction;
 /// is a TTC, otherwise it should be 0.
 pub type FontResolverFn = Arc<dyn Fn(&FontQuery) -> Option<(FontData, u32)> + Send + Sync>;
 /// A callback function for resolving cmap names to their files.
+///
+/// Resolvers can return borrowed data for cmaps that live for the whole program,
+/// like the ones embedded by the `embed-cmaps` feature, and owned data otherwise.
 pub type CMapResolverFn =
-    Arc<dyn Fn(hayro_cmap::CMapName<'_>) -> Option<&'static [u8]> + Send + Sync>;
+    Arc<dyn Fn(hayro_cmap::CMapName<'_>) -> Option<Cow<'static, [u8]>> + Send + Sync>;
 /// A callback function for resolving warnings during interpretation.
-pub type WarningSinkFn = Arc<dyn Fn(InterpreterWarning) + Send + Sync>;
+pub type WarningSinkFn = Arc<dyn Fn(Warning) + Send + Sync>;
+/// A callback function for checking whether interpretation should be cancelled.
+pub type CancellationFn = Arc<dyn Fn() -> bool + Send + Sync>;
+/// A callback function for reporting the progress of interpreting a page.
+pub type ProgressFn = Arc<dyn Fn(f32) + Send + Sync>;
 
 #[derive(Clone)]
 /// Settings that should be applied during the interpretation process.
@@ -81,6 +101,19 @@ pub struct InterpreterSettings {
     /// If you don't want having to deal with this, you can just enable the `embed-fonts` feature
     /// and use the default implementation of the callback.
     pub font_resolver: FontResolverFn,
+    /// An ordered chain of fonts for text in scripts other than latin, which is
+    /// consulted before the font resolver for fonts that aren't embedded.
+    ///
+    /// The standard fonts only cover latin scripts, so non-embedded fonts with
+    /// Chinese, Japanese, Korean, Cyrillic or Arabic text can't be displayed
+    /// properly with them. For CID fonts, the script is derived from the character
+    /// collection, and for simple fonts from the glyph names in the `Differences`
+    /// array of their encoding. The first font in the chain that is meant for the
+    /// script and can be loaded is used. If there is none, the font resolver is
+    /// queried as usual.
+    ///
+    /// The chain is empty by default.
+    pub font_fallbacks: Vec<FontFallback>,
     /// A callback for resolving cmaps that aren't embedded.
     ///
     /// When the PDF requires using a cmap that is not directly embedded in the PDF,
@@ -91,17 +124,180 @@ pub struct InterpreterSettings {
     /// all 61 predefined cmaps
     /// that the PDF specification requires to be readily available on a system.
     /// Otherwise, you can implement your custom logic for lazily fetching the
-    /// data. If you are fine not supporting such PDFs, you can simply pass a closure
-    /// that always returns `None`.
+    /// data, or use a [`DirectoryCMapResolver`](crate::font::DirectoryCMapResolver)
+    /// to load the cmap files from a directory. If you are fine not supporting such
+    /// PDFs, you can simply pass a closure that always returns `None`.
     pub cmap_resolver: CMapResolverFn,
     /// In certain cases, `hayro` will emit a warning in case an issue was encountered while interpreting
     /// the PDF file. Providing a callback allows you to catch those warnings and handle them, if desired.
+    ///
+    /// Each [`Warning`] carries the index of the page that was being interpreted, so a
+    /// single sink can be shared by all pages of a document.
     pub warning_sink: WarningSinkFn,
     /// Whether annotations should be rendered as well.
     ///
     /// Note that this feature is currently not fully implemented yet, so some
     /// annotations might be missing.
     pub render_annotations: bool,
+    /// Whether images should be smoothed when they are scaled.
+    ///
+    /// By default, this is `None`, meaning that the `Interpolate` flag of each
+    /// image is respected. `Some(true)` enables interpolation for all images,
+    /// while `Some(false)` disables it, for example to get a pixel-exact
+    /// rendering of scanned documents.
+    pub image_interpolation: Option<bool>,
+    /// The maximum number of pixels of a decoded image.
+    ///
+    /// Images that are larger are downsampled by only keeping every n-th pixel
+    /// in each direction. JPEG 2000 images are directly decoded in a reduced
+    /// resolution and Flate-compressed images are downsampled row by row while
+    /// being decompressed, so they are never held in memory in full resolution.
+    /// All other images (for example JPEG images) are still decoded in full
+    /// resolution first and only downsampled afterwards, which bounds the memory
+    /// used for converting and drawing them, but not the peak memory of the
+    /// decoder itself. By default, this is `None`, meaning that images are always
+    /// decoded in full resolution.
+    pub max_image_pixels: Option<u64>,
+    /// The maximum number of bytes of decoded images that are kept in the
+    /// [`ImageCache`](crate::ImageCache) of the interpreter cache.
+    ///
+    /// A limit of zero disables caching. By default, this is 64 MiB.
+    pub image_cache_max_bytes: usize,
+    /// The maximum number of bytes of decoded form `XObject` content streams that
+    /// are kept in the [`FormCache`](crate::FormCache) of the interpreter cache.
+    ///
+    /// A limit of zero disables caching. By default, this is 16 MiB.
+    pub form_cache_max_bytes: usize,
+    /// Whether to approximate overprinting with the multiply blend mode.
+    ///
+    /// If enabled, objects that are painted with overprinting enabled (via the
+    /// `OP` and `op` entries of the graphics state) are composited using the
+    /// multiply blend mode if they use a `Separation` or `DeviceN` color space, or
+    /// a CMYK color space with an overprint mode (`OPM`) of 1. Other objects are
+    /// still painted normally.
+    ///
+    /// Note that this is only a heuristic and not a real overprint preview:
+    /// devices composite in RGB, so the colorants (plates) are not composited
+    /// separately, and per-colorant compositing isn't implemented. Multiplying
+    /// keeps the colors that are already painted visible where the object leaves
+    /// them untouched, but it also darkens colorants that the object paints
+    /// itself, which would be replaced on a press. The result is therefore only
+    /// close to a press proof if the object and what is painted below it use
+    /// different colorants, like a spot color on top of process colors. For
+    /// example, overprinting 50% cyan onto 100% cyan yields a darker cyan instead
+    /// of 50% cyan.
+    ///
+    /// By default, this is disabled.
+    pub approximate_overprint: bool,
+    /// The number of entries of the lookup tables used for evaluating shadings.
+    ///
+    /// Axial, radial and mesh shadings whose function takes a single input
+    /// normally evaluate the function and convert the result into RGB for every
+    /// pixel, which is slow for complex functions or ICC-based color spaces. If a
+    /// size is set, the colors are instead precomputed for the given number of
+    /// evenly spaced inputs once per shading and interpolated linearly in between.
+    /// The tables are kept in the interpreter cache. Note that sharp transitions in
+    /// a function, like in stitching functions, might be slightly softened by this.
+    ///
+    /// By default, this is `None`, meaning that the functions are always evaluated
+    /// exactly.
+    pub shading_lut_size: Option<usize>,
+    /// The maximum error that is allowed when approximating shadings, as a
+    /// fraction of the range of each color component.
+    ///
+    /// This determines how many stops are used when axial and radial shadings are
+    /// converted into native gradients, and how finely the patches of Coons and
+    /// tensor-product meshes are subdivided. Smaller values result in smoother
+    /// shadings without banding, for example for output with a high resolution,
+    /// while larger values make rendering faster. If the smoothness tolerance of
+    /// the graphics state (`SM`) is smaller, it is used instead.
+    ///
+    /// By default, this is 0.01.
+    pub shading_smoothness: f32,
+    /// The quality of paint sources that are approximated or rasterized, like
+    /// gradients, tiling patterns, soft masks and resampled images.
+    ///
+    /// This allows choosing between fast previews and print quality. The
+    /// resolution and filter settings are applied by the device that renders
+    /// the page. By default, the quality is suitable for viewing on screen.
+    pub render_quality: RenderQuality,
+    /// How strokes with a line width of zero should be drawn.
+    ///
+    /// By default, they are drawn as hairlines that are one device pixel wide,
+    /// so that thin rules in tables don't vanish at low zoom levels.
+    pub zero_width_lines: ZeroWidthLinePolicy,
+    /// How operators that are unknown or whose operands could not be read
+    /// should be treated outside of compatibility sections (`BX`/`EX`).
+    ///
+    /// By default, they are skipped and a warning is emitted.
+    pub unknown_operators: UnknownOperatorPolicy,
+    /// How fonts whose embedded font program can't be read should be treated.
+    ///
+    /// By default, a standard font is substituted and a warning is emitted.
+    pub corrupt_fonts: CorruptFontPolicy,
+    /// A callback for cancelling the interpretation of a page.
+    ///
+    /// The callback is called before each instruction of a content stream, and
+    /// if it returns `true`, interpretation stops as soon as possible, leaving
+    /// the page partially drawn. This allows viewers to abort rendering a page
+    /// that is no longer visible, for example by returning the value of an
+    /// `AtomicBool` that is set when the page is scrolled out of view.
+    ///
+    /// Note that single operations, like decoding a large image, are not
+    /// interrupted. By default, interpretation is never cancelled.
+    pub is_cancelled: CancellationFn,
+    /// The maximum number of instructions that are interpreted for a page.
+    ///
+    /// This includes the instructions of nested content streams, like the ones
+    /// of form `XObject`s, patterns, soft masks and type3 glyphs. If the limit is
+    /// exceeded, interpretation stops and an
+    /// [`InterpreterWarning::LimitExceeded`] warning is emitted, which protects
+    /// against broken or malicious PDFs with effectively unbounded content
+    /// streams. By default, this is `None`, meaning that there is no limit.
+    pub max_instructions: Option<u64>,
+    /// The maximum time that is spent interpreting a page.
+    ///
+    /// This behaves the same way as [`InterpreterSettings::max_instructions`].
+    /// Note that measuring the time is not supported on `wasm32`, where the limit
+    /// is ignored. By default, this is `None`, meaning that there is no limit.
+    pub max_duration: Option<Duration>,
+    /// The maximum depth of nested content streams.
+    ///
+    /// Form `XObject`s, patterns, soft masks and type3 glyphs can contain
+    /// further content streams, which in broken or malicious PDFs can be nested
+    /// very deeply. Form `XObject`s that would exceed this depth, or that
+    /// reference themselves directly or through other forms, are skipped.
+    ///
+    /// By default, this is 50.
+    pub max_nesting_depth: u32,
+    /// A callback for reporting the progress of interpreting a page.
+    ///
+    /// The callback is called with the fraction of the content stream of the
+    /// page that has been interpreted so far, as a value between 0 and 1,
+    /// whenever it increased by at least one percent, and with 1 once the page
+    /// is done. This allows showing a progress bar for very complex pages, like
+    /// maps or CAD drawings. Nested content streams, like the ones of form
+    /// `XObject`s, count as part of the instruction that draws them.
+    ///
+    /// By default, this is `None`.
+    pub progress: Option<ProgressFn>,
+    /// Overrides for the visibility of optional content groups (layers).
+    ///
+    /// The keys are the object identifiers of the groups, as returned by
+    /// [`optional_content_groups`](crate::optional_content_groups). Groups that
+    /// are not contained in the map keep the visibility of the default
+    /// configuration of the document. This allows toggling the layers of
+    /// layered PDFs like maps or CAD drawings. The map is shared, so that cloning
+    /// the settings for each page doesn't copy it.
+    ///
+    /// By default, the map is empty.
+    pub optional_content: Arc<HashMap<ObjectIdentifier, bool>>,
+    /// Whether statistics about the interpretation should be collected, which
+    /// can be queried with [`Context::stats`] afterwards.
+    ///
+    /// This is useful for profiling which constructs dominate the rendering time.
+    /// It adds some overhead, so it is disabled by default.
+    pub collect_stats: bool,
 }
 
 impl Default for InterpreterSettings {
@@ -114,25 +310,190 @@ impl Default for InterpreterSettings {
                 FontQuery::Standard(s) => Some(s.get_font_data()),
                 FontQuery::Fallback(f) => Some(f.pick_standard_font().get_font_data()),
             }),
+            font_fallbacks: Vec::new(),
             #[cfg(feature = "embed-cmaps")]
-            cmap_resolver: Arc::new(hayro_cmap::load_embedded),
+            cmap_resolver: Arc::new(|name| hayro_cmap::load_embedded(name).map(Cow::Borrowed)),
             #[cfg(not(feature = "embed-cmaps"))]
             cmap_resolver: Arc::new(|_| None),
             warning_sink: Arc::new(|_| {}),
             render_annotations: true,
+            image_interpolation: None,
+            max_image_pixels: None,
+            image_cache_max_bytes: 64 * 1024 * 1024,
+            form_cache_max_bytes: 16 * 1024 * 1024,
+            approximate_overprint: false,
+            shading_lut_size: None,
+            shading_smoothness: 0.01,
+            render_quality: RenderQuality::default(),
+            zero_width_lines: ZeroWidthLinePolicy::default(),
+            unknown_operators: UnknownOperatorPolicy::default(),
+            corrupt_fonts: CorruptFontPolicy::default(),
+            is_cancelled: Arc::new(|| false),
+            max_instructions: None,
+            max_duration: None,
+            max_nesting_depth: 50,
+            progress: None,
+            optional_content: Arc::default(),
+            collect_stats: false,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// A warning that occurred while interpreting a PDF file, together with the page
+/// it occurred on.
+///
+/// See [`InterpreterSettings::warning_sink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Warning {
+    /// The issue that was encountered.
+    pub kind: InterpreterWarning,
+    /// The index of the page that was being interpreted.
+    ///
+    /// This is `None` if the warning didn't occur in [`interpret_page`], for
+    /// example when interpreting an `XObject` on its own with [`interpret_xobject`].
+    pub page_index: Option<usize>,
+}
+
+impl From<InterpreterWarning> for Warning {
+    fn from(kind: InterpreterWarning) -> Self {
+        Self {
+            kind,
+            page_index: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 /// Warnings that can occur while interpreting a PDF file.
+///
+/// Where possible, warnings carry the object the issue was encountered in, so
+/// that it can be located in the file.
 pub enum InterpreterWarning {
     /// An unsupported font kind was encountered.
     ///
-    /// Currently, only CID fonts with non-identity encoding are unsupported.
-    UnsupportedFont,
+    /// Currently, this only happens for CID fonts that use a predefined cmap as their
+    /// encoding which couldn't be resolved by [`InterpreterSettings::cmap_resolver`].
+    UnsupportedFont {
+        /// The object identifier of the font dictionary, if it's an indirect object.
+        font: Option<ObjectIdentifier>,
+        /// The `BaseFont` name of the font.
+        name: Option<String>,
+    },
     /// An image failed to decode.
-    ImageDecodeFailure,
+    ImageDecodeFailure {
+        /// The object identifier of the image, or `None` for inline images.
+        image: Option<ObjectIdentifier>,
+        /// The name of the last filter of the image stream, for example `DCTDecode`.
+        filter: Option<String>,
+    },
+    /// An embedded font program could not be loaded, for example because its
+    /// glyph data is truncated. Unless configured otherwise with
+    /// [`InterpreterSettings::corrupt_fonts`], a substitute font is used instead.
+    CorruptEmbeddedFont {
+        /// The object identifier of the font dictionary, if it's an indirect object.
+        font: Option<ObjectIdentifier>,
+        /// The `BaseFont` name of the font.
+        name: Option<String>,
+    },
+    /// A font with a corrupt font program was selected, so the text that is shown
+    /// with it is skipped, see [`CorruptFontPolicy::Skip`].
+    SkippedFont {
+        /// The object identifier of the font dictionary, if it's an indirect object.
+        font: Option<ObjectIdentifier>,
+        /// The `BaseFont` name of the font.
+        name: Option<String>,
+    },
+    /// The instruction or time limit of the interpreter was exceeded, so the
+    /// page was only partially interpreted.
+    ///
+    /// See [`InterpreterSettings::max_instructions`] and
+    /// [`InterpreterSettings::max_duration`].
+    LimitExceeded,
+    /// An operator referenced a resource that doesn't exist in the resource
+    /// dictionary, so it was skipped.
+    MissingResource {
+        /// The operator that referenced the resource, for example `Do`.
+        operator: String,
+        /// The name of the resource.
+        name: String,
+    },
+    /// A function could not be read, so the shading or transfer function
+    /// using it was ignored.
+    InvalidFunction {
+        /// The object identifier of the function, if it's an indirect object.
+        function: Option<ObjectIdentifier>,
+    },
+    /// A shading has an unknown type.
+    UnsupportedShading {
+        /// The object identifier of the shading, if it's an indirect object.
+        shading: Option<ObjectIdentifier>,
+        /// The value of the `ShadingType` entry.
+        shading_type: u8,
+    },
+    /// An operator was unknown or its operands could not be read, so it was
+    /// skipped.
+    InvalidOperator {
+        /// The name of the operator.
+        operator: String,
+    },
+    /// A content stream could not be parsed to its end, so the instructions
+    /// before the issue were drawn and the rest of it was skipped.
+    MalformedContentStream,
+}
+
+impl InterpreterWarning {
+    pub(crate) fn unsupported_font(dict: &Dict<'_>) -> Self {
+        Self::UnsupportedFont {
+            font: dict.obj_id(),
+            name: base_font_name(dict),
+        }
+    }
+
+    pub(crate) fn corrupt_embedded_font(dict: &Dict<'_>) -> Self {
+        Self::CorruptEmbeddedFont {
+            font: dict.obj_id(),
+            name: base_font_name(dict),
+        }
+    }
+
+    pub(crate) fn skipped_font(dict: &Dict<'_>) -> Self {
+        Self::SkippedFont {
+            font: dict.obj_id(),
+            name: base_font_name(dict),
+        }
+    }
+}
+
+fn base_font_name(dict: &Dict<'_>) -> Option<String> {
+    dict.get::<Name<'_>>(BASE_FONT)
+        .map(|n| n.as_str().to_string())
+}
+
+/// Report that an operator referenced a resource that doesn't exist.
+fn missing_resource(context: &Context<'_>, operator: &str, name: &Name<'_>) {
+    warn!("missing resource {} for {}", name.as_str(), operator);
+
+    (context.settings.warning_sink)(
+        InterpreterWarning::MissingResource {
+            operator: operator.to_string(),
+            name: name.as_str().to_string(),
+        }
+        .into(),
+    );
+}
+
+/// An error that stopped the strict interpretation of a page.
+///
+/// See [`interpret_page_strict`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterpretError {
+    /// The interpreter encountered an issue that it would otherwise have worked
+    /// around, for example by substituting a font or skipping an operator.
+    Unfaithful(InterpreterWarning),
+    /// Interpretation was cancelled via [`InterpreterSettings::is_cancelled`].
+    Cancelled,
 }
 
 /// interpret the contents of the page and render them into the device.
@@ -141,78 +502,271 @@ pub fn interpret_page<'a>(
     context: &mut Context<'a>,
     device: &mut impl Device<'a>,
 ) {
+    // Only query the time if necessary, since it isn't available on all platforms.
+    let start = context.stats_collector().and_then(|_| now());
     let resources = page.resources();
-    interpret(page.typed_operations(), resources, context, device);
+    let warning_sink = context.settings.warning_sink.clone();
+
+    context.settings.warning_sink = {
+        let warning_sink = warning_sink.clone();
+        let page_index = page.index();
 
-    if context.settings.render_annotations
-        && let Some(annot_arr) = page.raw().get::<Array<'_>>(ANNOTS)
+        Arc::new(move |mut warning: Warning| {
+            warning.page_index.get_or_insert(page_index);
+            warning_sink(warning);
+        })
+    };
+
+    // The page group determines the color space in which the page is blended.
+    if let Some(cs) = page
+        .raw()
+        .get::<Dict<'_>>(GROUP)
+        .and_then(|g| g.get::<Object<'_>>(CS))
+        .and_then(|o| ColorSpace::new(o, &context.interpreter_cache.object_cache))
     {
-        for annot in annot_arr.iter::<Dict<'_>>() {
-            let flags = annot.get::<u32>(F).unwrap_or(0);
+        context.get_mut().graphics_state.blending_color_space = Some(cs);
+    }
 
-            // Annotation should be hidden.
-            if flags & 2 != 0 {
-                continue;
-            }
+    interpret(page.typed_operations(), resources, context, device);
 
-            if let Some(apx) = annot
-                .get::<Dict<'_>>(AP)
-                .and_then(|ap| ap.get::<Stream<'_>>(N))
-                .and_then(|o| FormXObject::new(&o))
-            {
-                let Some(rect) = annot.get::<Rect>(RECT) else {
-                    continue;
-                };
+    if context.settings.render_annotations && !context.should_stop() {
+        interpret_annotations(page, context, device);
+    }
 
-                let annot_rect = rect.to_kurbo();
-                // 12.5.5. Appearance streams
-                // "The algorithm outlined in this subclause shall be used
-                // to map from the coordinate system of the appearance XObject."
-
-                // 1) The appearance’s bounding box (specified by its BBox entry)
-                // shall be transformed, using Matrix, to produce a
-                // quadrilateral with arbitrary orientation. The transformed
-                // appearance box is the smallest upright rectangle that
-                // encompasses this quadrilateral.
-                let transformed_rect = (apx.matrix
-                    * kurbo::Rect::new(
-                        apx.bbox[0] as f64,
-                        apx.bbox[1] as f64,
-                        apx.bbox[2] as f64,
-                        apx.bbox[3] as f64,
-                    )
-                    .to_path(0.1))
-                .bounding_box();
-
-                // 2) A matrix A shall be computed that scales and translates
-                // the transformed appearance box to align with the edges
-                // of the annotation’s rectangle (specified by the Rect entry).
-                // A maps the lower-left corner (the corner with the smallest
-                // x and y coordinates) and the upper-right corner (the
-                // corner with the greatest x and y coordinates) of the
-                // transformed appearance box to the corresponding corners
-                // of the annotation’s rectangle.
-                let affine = Affine::new([
-                    annot_rect.width() / transformed_rect.width(),
-                    0.0,
-                    0.0,
-                    annot_rect.height() / transformed_rect.height(),
-                    annot_rect.x0 - transformed_rect.x0,
-                    annot_rect.y0 - transformed_rect.y0,
-                ]);
+    if let Some((stats, start)) = context.stats_collector().zip(start) {
+        stats.record_total(start.elapsed());
+    }
+
+    if let Some(progress) = &context.settings.progress {
+        progress(1.0);
+    }
 
-                // 3) Matrix shall be concatenated with A to form a matrix
-                // AA that maps from the appearance’s coordinate system to
-                // the annotation’s rectangle in default user space.
-                context.save_state();
-                context.pre_concat_affine(affine);
-                context.push_root_transform();
+    context.settings.warning_sink = warning_sink;
+}
 
-                draw_form_xobject(resources, &apx, context, device);
-                context.pop_root_transform();
-                context.restore_state(device);
+/// Interpret a single `XObject`, like a stamp or a letterhead, on its own and render
+/// it into the device.
+///
+/// The `XObject` is drawn with the current transformation matrix of the context, just
+/// like the `Do` operator would draw it. Resources that a form `XObject` doesn't define
+/// itself are looked up in `resources`, which usually are the resources of the page
+/// the `XObject` was taken from.
+pub fn interpret_xobject<'a>(
+    x_object: &XObject<'a>,
+    resources: &Resources<'a>,
+    context: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+) {
+    draw_xobject(x_object, resources, context, device);
+}
+
+/// Interpret the appearance streams of the annotations of the page and render
+/// them into the device.
+///
+/// The normal appearance of each annotation that isn't hidden is mapped into
+/// the rectangle of the annotation. If the annotation has several appearance
+/// states, like a checkbox, the one selected by its `AS` entry is drawn.
+/// Form field widgets without an appearance stream are drawn from their value,
+/// default appearance string and appearance characteristics instead.
+///
+/// [`interpret_page`] already calls this if [`InterpreterSettings::render_annotations`]
+/// is enabled.
+pub fn interpret_annotations<'a>(
+    page: &Page<'a>,
+    context: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+) {
+    const HIDDEN: u32 = 1 << 1;
+    const NO_VIEW: u32 = 1 << 5;
+
+    let Some(annot_arr) = page.raw().get::<Array<'_>>(ANNOTS) else {
+        return;
+    };
+    let resources = page.resources();
+
+    for annot in annot_arr.iter::<Dict<'_>>() {
+        let flags = annot.get::<u32>(F).unwrap_or(0);
+
+        if flags & (HIDDEN | NO_VIEW) != 0 {
+            continue;
+        }
+
+        let Some(rect) = annot.get::<Rect>(RECT) else {
+            continue;
+        };
+        let annot_rect = rect.to_kurbo();
+
+        let Some(apx) = normal_appearance(&annot).and_then(|s| FormXObject::new(&s, context))
+        else {
+            // Form fields that were filled without generating an appearance stream
+            // are drawn from their value instead.
+            if !annot.contains_key(AP) && annot.get::<Name<'_>>(SUBTYPE).as_deref() == Some(WIDGET)
+            {
+                let has_oc = xobject_oc(&annot, context);
+                widget::draw_widget(&annot, annot_rect, resources, context, device);
+
+                if has_oc {
+                    context.ocg_state.end_marked_content();
+                }
             }
+
+            continue;
+        };
+
+        // 12.5.5. Appearance streams
+        // "The algorithm outlined in this subclause shall be used
+        // to map from the coordinate system of the appearance XObject."
+
+        // 1) The appearance’s bounding box (specified by its BBox entry)
+        // shall be transformed, using Matrix, to produce a
+        // quadrilateral with arbitrary orientation. The transformed
+        // appearance box is the smallest upright rectangle that
+        // encompasses this quadrilateral.
+        let transformed_rect = (apx.matrix
+            * kurbo::Rect::new(
+                apx.bbox[0] as f64,
+                apx.bbox[1] as f64,
+                apx.bbox[2] as f64,
+                apx.bbox[3] as f64,
+            )
+            .to_path(0.1))
+        .bounding_box();
+
+        if transformed_rect.width() == 0.0 || transformed_rect.height() == 0.0 {
+            warn!("annotation has an empty appearance box");
+
+            continue;
+        }
+
+        // 2) A matrix A shall be computed that scales and translates
+        // the transformed appearance box to align with the edges
+        // of the annotation’s rectangle (specified by the Rect entry).
+        // A maps the lower-left corner (the corner with the smallest
+        // x and y coordinates) and the upper-right corner (the
+        // corner with the greatest x and y coordinates) of the
+        // transformed appearance box to the corresponding corners
+        // of the annotation’s rectangle.
+        let scale_x = annot_rect.width() / transformed_rect.width();
+        let scale_y = annot_rect.height() / transformed_rect.height();
+        let affine = Affine::new([
+            scale_x,
+            0.0,
+            0.0,
+            scale_y,
+            annot_rect.x0 - transformed_rect.x0 * scale_x,
+            annot_rect.y0 - transformed_rect.y0 * scale_y,
+        ]);
+
+        // 3) Matrix shall be concatenated with A to form a matrix
+        // AA that maps from the appearance’s coordinate system to
+        // the annotation’s rectangle in default user space.
+        context.save_state();
+        context.pre_concat_affine(affine);
+        context.push_root_transform();
+
+        // Annotations can belong to an optional content group as well.
+        let has_oc = xobject_oc(&annot, context);
+        draw_form_xobject(resources, &apx, context, device);
+
+        if has_oc {
+            context.ocg_state.end_marked_content();
         }
+
+        context.pop_root_transform();
+        context.restore_state(device);
+    }
+}
+
+/// Return the normal appearance stream of an annotation.
+fn normal_appearance<'a>(annot: &Dict<'a>) -> Option<Stream<'a>> {
+    match annot.get::<Dict<'_>>(AP)?.get::<Object<'_>>(N)? {
+        Object::Stream(stream) => Some(stream),
+        // The appearance has several states, one of which is selected by the
+        // annotation.
+        Object::Dict(states) => states.get::<Stream<'_>>(annot.get::<Name<'_>>(AS)?.as_ref()),
+        _ => None,
+    }
+}
+
+/// Interpret the contents of the page like [`interpret_page`], but stop at the
+/// first issue instead of working around it.
+///
+/// Returning `Ok` means that the page was interpreted without any guesses. Otherwise,
+/// the device has only received the contents up to the issue. Every warning is still
+/// passed to [`InterpreterSettings::warning_sink`].
+///
+/// Note that warnings about fonts are only emitted the first time a font is loaded,
+/// so an [`InterpreterCache`](crate::InterpreterCache) that was used for a previous,
+/// non-strict run might hide them.
+pub fn interpret_page_strict<'a>(
+    page: &Page<'a>,
+    context: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+) -> Result<(), InterpretError> {
+    let failure = Arc::new(OnceLock::new());
+    let warning_sink = context.settings.warning_sink.clone();
+    let is_cancelled = context.settings.is_cancelled.clone();
+
+    context.settings.warning_sink = {
+        let failure = failure.clone();
+        let warning_sink = warning_sink.clone();
+
+        Arc::new(move |warning: Warning| {
+            let _ = failure.set(warning.kind.clone());
+            warning_sink(warning);
+        })
+    };
+    context.settings.is_cancelled = {
+        let failure = failure.clone();
+        let is_cancelled = is_cancelled.clone();
+
+        Arc::new(move || failure.get().is_some() || is_cancelled())
+    };
+
+    interpret_page(page, context, device);
+
+    context.settings.warning_sink = warning_sink;
+    context.settings.is_cancelled = is_cancelled;
+
+    if let Some(warning) = failure.get() {
+        Err(InterpretError::Unfaithful(warning.clone()))
+    } else if (context.settings.is_cancelled)() {
+        Err(InterpretError::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Resolve the properties of a marked content operator.
+fn marked_content<'b>(
+    tag: &'b Name<'_>,
+    properties: &Object<'b>,
+    resources: &Resources<'b>,
+) -> MarkedContent<'b> {
+    // Properties can be either:
+    // 1. A Name that references an entry in the Resources/Properties dictionary
+    // 2. An inline dictionary
+    let properties = dict_or_stream(properties)
+        .map(|(props, _)| props.clone())
+        .or_else(|| {
+            properties
+                .clone()
+                .into_name()
+                .and_then(|name| resources.get_property(&name))
+        });
+    let text = |key| {
+        properties
+            .as_ref()
+            .and_then(|p| p.get::<object::String<'_>>(key))
+            .map(|s| decode_text_string(s.as_bytes()))
+    };
+
+    MarkedContent {
+        tag: tag.as_ref(),
+        mcid: properties.as_ref().and_then(|p| p.get::<i32>(MCID)),
+        actual_text: text(ACTUAL_TEXT),
+        alt: text(ALT),
+        properties,
     }
 }
 
@@ -225,25 +779,46 @@ pub fn interpret<'a>(
 ) {
     let num_states = context.num_states();
     let mut font_dict_cache = FxHashMap::<Name<'a>, Dict<'a>>::default();
+    // Only the progress of the content stream of the page itself is reported.
+    let progress = context
+        .settings
+        .progress
+        .clone()
+        .filter(|_| context.nesting_depth() == 0);
+    let mut reported_progress = 0.0;
+    // Unknown operators inside of `BX`/`EX` sections are expected and not worth
+    // a warning.
+    let mut compatibility_depth = 0_u32;
 
     context.save_state();
 
     while let Some(op) = ops.next() {
+        if context.should_stop() {
+            break;
+        }
+
+        if let Some(stats) = context.stats_collector() {
+            stats.record_operator(op.operator());
+        }
+
         match op {
             TypedInstruction::SaveState(_) => context.save_state(),
             TypedInstruction::StrokeColorDeviceRgb(s) => {
-                context.get_mut().graphics_state.stroke_cs = ColorSpace::device_rgb();
+                let cs = context.device_color_space(resources, ColorSpace::device_rgb());
+                context.get_mut().graphics_state.stroke_cs = cs;
                 context.get_mut().graphics_state.stroke_color =
                     smallvec![s.0.as_f32(), s.1.as_f32(), s.2.as_f32()];
                 context.get_mut().graphics_state.stroke_pattern = None;
             }
             TypedInstruction::StrokeColorDeviceGray(s) => {
-                context.get_mut().graphics_state.stroke_cs = ColorSpace::device_gray();
+                let cs = context.device_color_space(resources, ColorSpace::device_gray());
+                context.get_mut().graphics_state.stroke_cs = cs;
                 context.get_mut().graphics_state.stroke_color = smallvec![s.0.as_f32()];
                 context.get_mut().graphics_state.stroke_pattern = None;
             }
             TypedInstruction::StrokeColorCmyk(s) => {
-                context.get_mut().graphics_state.stroke_cs = ColorSpace::device_cmyk();
+                let cs = context.device_color_space(resources, ColorSpace::device_cmyk());
+                context.get_mut().graphics_state.stroke_cs = cs;
                 context.get_mut().graphics_state.stroke_color =
                     smallvec![s.0.as_f32(), s.1.as_f32(), s.2.as_f32(), s.3.as_f32()];
                 context.get_mut().graphics_state.stroke_pattern = None;
@@ -307,18 +882,21 @@ pub fn interpret<'a>(
                 fill_stroke_path(context, device, FillRule::NonZero);
             }
             TypedInstruction::NonStrokeColorDeviceGray(s) => {
-                context.get_mut().graphics_state.none_stroke_cs = ColorSpace::device_gray();
+                let cs = context.device_color_space(resources, ColorSpace::device_gray());
+                context.get_mut().graphics_state.none_stroke_cs = cs;
                 context.get_mut().graphics_state.non_stroke_color = smallvec![s.0.as_f32()];
                 context.get_mut().graphics_state.non_stroke_pattern = None;
             }
             TypedInstruction::NonStrokeColorDeviceRgb(s) => {
-                context.get_mut().graphics_state.none_stroke_cs = ColorSpace::device_rgb();
+                let cs = context.device_color_space(resources, ColorSpace::device_rgb());
+                context.get_mut().graphics_state.none_stroke_cs = cs;
                 context.get_mut().graphics_state.non_stroke_color =
                     smallvec![s.0.as_f32(), s.1.as_f32(), s.2.as_f32()];
                 context.get_mut().graphics_state.non_stroke_pattern = None;
             }
             TypedInstruction::NonStrokeColorCmyk(s) => {
-                context.get_mut().graphics_state.none_stroke_cs = ColorSpace::device_cmyk();
+                let cs = context.device_color_space(resources, ColorSpace::device_cmyk());
+                context.get_mut().graphics_state.none_stroke_cs = cs;
                 context.get_mut().graphics_state.non_stroke_color =
                     smallvec![s.0.as_f32(), s.1.as_f32(), s.2.as_f32(), s.3.as_f32()];
                 context.get_mut().graphics_state.non_stroke_pattern = None;
@@ -372,11 +950,10 @@ pub fn interpret<'a>(
                 close_path(context);
             }
             TypedInstruction::SetGraphicsState(gs) => {
-                if let Some(gs) = resources
-                    .get_ext_g_state(gs.0)
-                    .warn_none(&format!("failed to get extgstate {}", gs.0.as_str()))
-                {
-                    handle_gs(&gs, context, resources);
+                if let Some(gs_dict) = resources.get_ext_g_state(gs.0) {
+                    handle_gs(&gs_dict, context, resources);
+                } else {
+                    missing_resource(context, "gs", gs.0);
                 }
             }
             TypedInstruction::StrokePath(_) => {
@@ -411,12 +988,12 @@ pub fn interpret<'a>(
                 *(context.clip_mut()) = Some(FillRule::EvenOdd);
             }
             TypedInstruction::RestoreState(_) => context.restore_state(device),
-            TypedInstruction::FlatnessTolerance(_) => {
-                // Ignore for now.
+            TypedInstruction::FlatnessTolerance(f) => {
+                context.get_mut().graphics_state.flatness = Some(f.0.as_f32().clamp(0.0, 100.0));
             }
             TypedInstruction::ColorSpaceStroke(c) => {
                 let cs = if let Some(named) = ColorSpace::new_from_name(c.0) {
-                    named
+                    context.device_color_space(resources, named)
                 } else {
                     context
                         .get_color_space(resources, c.0)
@@ -431,7 +1008,7 @@ pub fn interpret<'a>(
             }
             TypedInstruction::ColorSpaceNonStroke(c) => {
                 let cs = if let Some(named) = ColorSpace::new_from_name(c.0) {
-                    named
+                    context.device_color_space(resources, named)
                 } else {
                     context
                         .get_color_space(resources, c.0)
@@ -445,51 +1022,48 @@ pub fn interpret<'a>(
                 context.get_mut().graphics_state.none_stroke_cs = cs;
             }
             TypedInstruction::DashPattern(p) => {
-                context.get_mut().graphics_state.stroke_props.dash_offset = p.1.as_f32();
-                // kurbo apparently cannot properly deal with offsets that are exactly 0.
-                context.get_mut().graphics_state.stroke_props.dash_array =
-                    p.0.iter::<f32>()
-                        .map(|n| if n == 0.0 { 0.01 } else { n })
-                        .collect();
+                set_dash_pattern(context, p.0.iter::<f32>().collect(), p.1.as_f32());
             }
-            TypedInstruction::RenderingIntent(_) => {
-                // Ignore for now.
+            TypedInstruction::RenderingIntent(ri) => {
+                context.get_mut().graphics_state.rendering_intent =
+                    Some(RenderingIntent::from_name(ri.0));
             }
             TypedInstruction::NonStrokeColorNamed(n) => {
                 context.get_mut().graphics_state.non_stroke_color =
                     n.0.into_iter().map(|n| n.as_f32()).collect();
                 context.get_mut().graphics_state.non_stroke_pattern = n.1.and_then(|name| {
-                    resources
-                        .get_pattern(name)
-                        .and_then(|d| Pattern::new(d, context, resources))
+                    let Some(pattern) = resources.get_pattern(name) else {
+                        missing_resource(context, "scn", name);
+
+                        return None;
+                    };
+
+                    Pattern::new(pattern, context, resources)
                 });
             }
             TypedInstruction::StrokeColorNamed(n) => {
                 context.get_mut().graphics_state.stroke_color =
                     n.0.into_iter().map(|n| n.as_f32()).collect();
                 context.get_mut().graphics_state.stroke_pattern = n.1.and_then(|name| {
-                    resources
-                        .get_pattern(name)
-                        .and_then(|d| Pattern::new(d, context, resources))
+                    let Some(pattern) = resources.get_pattern(name) else {
+                        missing_resource(context, "SCN", name);
+
+                        return None;
+                    };
+
+                    Pattern::new(pattern, context, resources)
                 });
             }
             TypedInstruction::BeginMarkedContentWithProperties(bdc) => {
-                // Properties can be either:
-                // 1. A Name that references an entry in the Resources/Properties dictionary
-                // 2. An inline dictionary with an OC key
-
-                let mcid = dict_or_stream(bdc.1).and_then(|(props, _)| props.get::<i32>(MCID));
+                let marked_content = marked_content(bdc.0, bdc.1, resources);
 
                 let oc = bdc
                     .1
                     .clone()
                     .into_name()
                     .and_then(|name| {
-                        let r = resources.properties.get_ref(name.as_ref())?;
-                        let d = resources
-                            .properties
-                            .get::<Dict<'_>>(name)
-                            .unwrap_or_default();
+                        let r = resources.get_property_ref(&name)?;
+                        let d = resources.get_property(&name).unwrap_or_default();
                         Some((d, r))
                     })
                     .or_else(|| {
@@ -505,17 +1079,27 @@ pub fn interpret<'a>(
                     context.ocg_state.begin_marked_content();
                 }
 
-                device.begin_marked_content(bdc.0, mcid);
+                device.begin_marked_content(&marked_content);
+            }
+            TypedInstruction::MarkedContentPointWithProperties(dp) => {
+                device.marked_content_point(&marked_content(dp.0, dp.1, resources));
             }
-            TypedInstruction::MarkedContentPointWithProperties(_) => {}
             TypedInstruction::EndMarkedContent(_) => {
                 context.ocg_state.end_marked_content();
                 device.end_marked_content();
             }
-            TypedInstruction::MarkedContentPoint(_) => {}
+            TypedInstruction::MarkedContentPoint(mp) => {
+                device.marked_content_point(&MarkedContent {
+                    tag: mp.0.as_ref(),
+                    ..Default::default()
+                });
+            }
             TypedInstruction::BeginMarkedContent(bmc) => {
                 context.ocg_state.begin_marked_content();
-                device.begin_marked_content(bmc.0, None);
+                device.begin_marked_content(&MarkedContent {
+                    tag: bmc.0.as_ref(),
+                    ..Default::default()
+                });
             }
             TypedInstruction::BeginText(_) => {
                 context.get_mut().text_state.text_matrix = Affine::IDENTITY;
@@ -565,6 +1149,8 @@ pub fn interpret<'a>(
                     font_dict_cache.insert(name.clone(), font_dict.clone());
                     context.resolve_font(&font_dict)
                 } else {
+                    missing_resource(context, "Tf", name);
+
                     Font::new_standard(StandardFont::Helvetica, &context.settings.font_resolver)
                         .map(TextStateFont::Fallback)
                 };
@@ -583,7 +1169,9 @@ pub fn interpret<'a>(
                     .map(TextStateFont::Fallback);
                 }
 
+                let group = text::push_knockout_group(context, device);
                 text::show_text_string(context, device, resources, s.0);
+                text::pop_knockout_group(context, device, group);
             }
             TypedInstruction::ShowTexts(s) => {
                 if context.get().text_state.font.is_none() {
@@ -596,6 +1184,8 @@ pub fn interpret<'a>(
                     .map(TextStateFont::Fallback);
                 }
 
+                let group = text::push_knockout_group(context, device);
+
                 for obj in s.0.iter::<Object<'_>>() {
                     match obj {
                         Object::Number(num) => {
@@ -607,6 +1197,8 @@ pub fn interpret<'a>(
                         _ => {}
                     }
                 }
+
+                text::pop_knockout_group(context, device, group);
             }
             TypedInstruction::HorizontalScaling(h) => {
                 context.get_mut().text_state.horizontal_scaling = h.0.as_f32();
@@ -629,7 +1221,9 @@ pub fn interpret<'a>(
             }
             TypedInstruction::NextLineAndShowText(n) => {
                 text::next_line(context, 0.0, -context.get().text_state.leading as f64);
+                let group = text::push_knockout_group(context, device);
                 text::show_text_string(context, device, resources, n.0);
+                text::pop_knockout_group(context, device, group);
             }
             TypedInstruction::TextRenderingMode(r) => {
                 let mode = match r.0.as_i64() {
@@ -655,19 +1249,24 @@ pub fn interpret<'a>(
                 context.get_mut().text_state.leading = -ty as f32;
                 text::next_line(context, tx, ty);
             }
-            TypedInstruction::ShapeGlyph(_) => {}
+            TypedInstruction::ShapeGlyph(s) => {
+                // Clip the glyph to its bounding box, like other viewers do. Quite a few
+                // documents specify an empty bounding box, which we ignore.
+                let bbox =
+                    kurbo::Rect::new(s.2.as_f64(), s.3.as_f64(), s.4.as_f64(), s.5.as_f64()).abs();
+
+                if bbox.area() > 0.0 {
+                    let clip_path = context.get().ctm * bbox.to_path(0.1);
+                    context.push_clip_path(clip_path, FillRule::NonZero, device);
+                }
+            }
             TypedInstruction::XObject(x) => {
-                let cache = context.interpreter_cache.object_cache.clone();
-                let transfer_function = context.get().graphics_state.transfer_function.clone();
-                if let Some(x_object) = resources.get_x_object(x.0).and_then(|s| {
-                    XObject::new(
-                        &s,
-                        &context.settings.warning_sink,
-                        &cache,
-                        transfer_function.clone(),
-                    )
-                }) {
-                    draw_xobject(&x_object, resources, context, device);
+                if let Some(stream) = resources.get_x_object(x.0) {
+                    if let Some(x_object) = XObject::from_stream(&stream, context) {
+                        draw_xobject(&x_object, resources, context, device);
+                    }
+                } else {
+                    missing_resource(context, "Do", x.0);
                 }
             }
             TypedInstruction::InlineImage(i) => {
@@ -682,7 +1281,9 @@ pub fn interpret<'a>(
                     false,
                     transfer_function,
                 ) {
-                    draw_image_xobject(&x_object, context, device);
+                    draw_image_xobject(&x_object, context, device, |device, image, props| {
+                        device.draw_image(image, props);
+                    });
                 }
             }
             TypedInstruction::TextRise(t) => {
@@ -695,11 +1296,20 @@ pub fn interpret<'a>(
 
                 let transfer_function = context.get().graphics_state.transfer_function.clone();
 
-                if let Some(sp) = resources
-                    .get_shading(s.0)
-                    .and_then(|o| {
-                        let (dict, stream) = dict_or_stream(&o)?;
-                        Shading::new(dict, stream, &context.interpreter_cache.object_cache)
+                let Some(shading) = resources.get_shading(s.0) else {
+                    missing_resource(context, "sh", s.0);
+
+                    continue;
+                };
+
+                if let Some(sp) = dict_or_stream(&shading)
+                    .and_then(|(dict, stream)| {
+                        Shading::new(
+                            dict,
+                            stream,
+                            &context.interpreter_cache.object_cache,
+                            &context.settings,
+                        )
                     })
                     .map(|s| {
                         Pattern::Shading(ShadingPattern {
@@ -707,6 +1317,12 @@ pub fn interpret<'a>(
                             matrix: Affine::IDENTITY,
                             opacity: context.get().graphics_state.non_stroke_alpha,
                             transfer_function: transfer_function.clone(),
+                            smoothness: context.smoothness(),
+                            flatness: context.flatness(),
+                            gradient_subdivisions: context
+                                .settings
+                                .render_quality
+                                .gradient_subdivisions,
                         })
                     })
                 {
@@ -718,7 +1334,9 @@ pub fn interpret<'a>(
 
                     let bbox = context.bbox().to_path(0.1);
                     let inverted_bbox = context.get().ctm.inverse() * bbox;
-                    fill_path_impl(context, device, FillRule::NonZero, Some(&inverted_bbox));
+                    context.measure(Phase::Shadings, |context| {
+                        fill_path_impl(context, device, FillRule::NonZero, Some(&inverted_bbox));
+                    });
 
                     context.pop_root_transform();
                     context.restore_state(device);
@@ -726,19 +1344,56 @@ pub fn interpret<'a>(
                     warn!("failed to process shading");
                 }
             }
-            TypedInstruction::BeginCompatibility(_) => {}
-            TypedInstruction::EndCompatibility(_) => {}
+            TypedInstruction::BeginCompatibility(_) => compatibility_depth += 1,
+            TypedInstruction::EndCompatibility(_) => {
+                compatibility_depth = compatibility_depth.saturating_sub(1);
+            }
+            // Whether a glyph is a shape or a color glyph is already determined before
+            // interpreting its char proc.
             TypedInstruction::ColorGlyph(_) => {}
             TypedInstruction::ShowTextWithParameters(t) => {
                 context.get_mut().text_state.word_space = t.0.as_f32();
                 context.get_mut().text_state.char_space = t.1.as_f32();
                 text::next_line(context, 0.0, -context.get().text_state.leading as f64);
+                let group = text::push_knockout_group(context, device);
                 text::show_text_string(context, device, resources, t.2);
+                text::pop_knockout_group(context, device, group);
             }
-            _ => {
-                warn!("failed to read an operator");
+            TypedInstruction::Fallback(op) => {
+                let policy = context.settings.unknown_operators;
+
+                if compatibility_depth == 0 && policy != UnknownOperatorPolicy::Ignore {
+                    let operator = String::from_utf8_lossy(op).into_owned();
+                    warn!("failed to read operator {}", operator);
+
+                    (context.settings.warning_sink)(
+                        InterpreterWarning::InvalidOperator { operator }.into(),
+                    );
+
+                    if policy == UnknownOperatorPolicy::Error {
+                        context.abort();
+                        break;
+                    }
+                }
             }
         }
+
+        if let Some(progress) = &progress {
+            let current = ops.progress();
+
+            // The end of the page is reported once it is done, including the
+            // annotations.
+            if current - reported_progress >= 0.01 && current < 1.0 {
+                progress(current);
+                reported_progress = current;
+            }
+        }
+    }
+
+    if ops.is_malformed() {
+        warn!("failed to parse content stream, skipping the rest of it");
+
+        (context.settings.warning_sink)(InterpreterWarning::MalformedContentStream.into());
     }
 
     while context.num_states() > num_states {
@@ -1,21 +1,29 @@
 use crate::context::{Context, path_as_rect};
 use crate::device::Device;
+use crate::gradient::{AxialGradient, Mesh, RadialGradient};
+use crate::pattern::Pattern;
+use crate::stats::Phase;
 use crate::util::{BezPathExt, Float32Ext};
-use crate::{DrawMode, FillRule, StrokeProps};
-use kurbo::{BezPath, Cap, Join, PathEl};
+use crate::{DrawMode, DrawProps, FillRule, Paint, StrokeProps};
+use kurbo::{Affine, BezPath, Cap, Join, PathEl, Rect};
+use std::f64::consts::SQRT_2;
 
 pub(crate) fn fill_path<'a>(
     context: &mut Context<'a>,
     device: &mut impl Device<'a>,
     fill_rule: FillRule,
 ) {
-    fill_path_impl(context, device, fill_rule, None);
+    context.measure(Phase::Paths, |context| {
+        fill_path_impl(context, device, fill_rule, None);
+    });
 
     context.path_mut().truncate(0);
 }
 
 pub(crate) fn stroke_path<'a>(context: &mut Context<'a>, device: &mut impl Device<'a>) {
-    stroke_path_impl(context, device, None);
+    context.measure(Phase::Paths, |context| {
+        stroke_path_impl(context, device, None);
+    });
 
     context.path_mut().truncate(0);
 }
@@ -25,8 +33,10 @@ pub(crate) fn fill_stroke_path<'a>(
     device: &mut impl Device<'a>,
     fill_rule: FillRule,
 ) {
-    fill_path_impl(context, device, fill_rule, None);
-    stroke_path_impl(context, device, None);
+    context.measure(Phase::Paths, |context| {
+        fill_path_impl(context, device, fill_rule, None);
+        stroke_path_impl(context, device, None);
+    });
 
     context.path_mut().truncate(0);
 }
@@ -48,7 +58,7 @@ pub(crate) fn fill_path_impl<'a>(
     fill_rule: FillRule,
     path: Option<&BezPath>,
 ) {
-    if !context.ocg_state.is_visible() {
+    if !context.ocg_state.is_visible() || !context.has_paint(false) {
         return;
     }
 
@@ -63,12 +73,13 @@ pub(crate) fn fill_path_impl<'a>(
             (bbox.height() as f32).is_nearly_zero(),
         ) {
             (false, false) => {
-                let draw_mode = DrawMode::Fill(fill_rule);
-                if let Some(rect) = path_as_rect(path) {
-                    device.draw_rect(&rect, props.clone(), &draw_mode);
-                } else {
-                    device.draw_path(path, props.clone(), &draw_mode);
-                }
+                draw_path(
+                    context,
+                    device,
+                    path,
+                    props.clone(),
+                    &DrawMode::Fill(fill_rule),
+                );
             }
             _ => {
                 let mut path = BezPath::new();
@@ -99,7 +110,7 @@ pub(crate) fn stroke_path_impl<'a>(
     device: &mut impl Device<'a>,
     path: Option<&BezPath>,
 ) {
-    if !context.ocg_state.is_visible() {
+    if !context.ocg_state.is_visible() || !context.has_paint(true) {
         return;
     }
 
@@ -107,11 +118,68 @@ pub(crate) fn stroke_path_impl<'a>(
     let props = context.draw_props(true);
 
     let path = path.unwrap_or(context.path());
-    let draw_mode = DrawMode::Stroke(stroke_props);
+    draw_path(
+        context,
+        device,
+        path,
+        props,
+        &DrawMode::Stroke(stroke_props),
+    );
+}
+
+/// Draw a path, passing shadings that devices can draw natively to the
+/// corresponding method of the device.
+///
+/// Paths that are completely clipped away are skipped.
+fn draw_path<'a>(
+    context: &Context<'a>,
+    device: &mut impl Device<'a>,
+    path: &BezPath,
+    props: DrawProps<'a>,
+    draw_mode: &DrawMode,
+) {
+    if context.is_clipped(draw_bounds(path, props.transform, draw_mode)) {
+        return;
+    }
+
+    if let Paint::Pattern(pattern) = &props.paint
+        && let Pattern::Shading(pattern) = pattern.as_ref()
+    {
+        if let Some(stats) = context.stats_collector() {
+            stats.record_shading();
+        }
+
+        if let Some(gradient) = AxialGradient::new(pattern) {
+            device.draw_axial_gradient(path, &gradient, props, draw_mode);
+            return;
+        } else if let Some(gradient) = RadialGradient::new(pattern) {
+            device.draw_radial_gradient(path, &gradient, props, draw_mode);
+            return;
+        } else if let Some(mesh) = Mesh::new(pattern) {
+            device.draw_mesh(path, &mesh, props, draw_mode);
+            return;
+        }
+    }
 
     if let Some(rect) = path_as_rect(path) {
-        device.draw_rect(&rect, props, &draw_mode);
+        device.draw_rect(&rect, props, draw_mode);
     } else {
-        device.draw_path(path, props, &draw_mode);
+        device.draw_path(path, props, draw_mode);
+    }
+}
+
+/// Return the area in device space that drawing the path covers.
+fn draw_bounds(path: &BezPath, transform: Affine, draw_mode: &DrawMode) -> Rect {
+    let mut bounds = path.fast_bounding_box();
+
+    if let DrawMode::Stroke(stroke_props) | DrawMode::FillAndStroke(_, stroke_props) = draw_mode {
+        // Miter joins can extend past the path by up to half the miter limit
+        // times the line width, and square caps by the half diagonal of a
+        // square with the line width.
+        let extent =
+            stroke_props.line_width as f64 / 2.0 * (stroke_props.miter_limit as f64).max(SQRT_2);
+        bounds = bounds.inflate(extent, extent);
     }
+
+    transform.transform_rect_bbox(bounds)
 }
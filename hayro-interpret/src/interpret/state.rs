@@ -1,20 +1,21 @@
+use crate::InterpreterWarning;
 use crate::StrokeProps;
-use crate::color::{AlphaColor, ColorComponents, ColorSpace};
+use crate::TextRenderingMode;
+use crate::color::{AlphaColor, ColorComponents, ColorSpace, RenderingIntent};
 use crate::context::Context;
 use crate::convert::{convert_line_cap, convert_line_join};
 use crate::font::{Font, UNITS_PER_EM};
 use crate::function::Function;
-use crate::interpret::text::TextRenderingMode;
 use crate::pattern::Pattern;
 use crate::soft_mask::SoftMask;
 use crate::types::BlendMode;
 use crate::util::OptionLog;
 use hayro_syntax::content::ops::{LineCap, LineJoin};
-use hayro_syntax::object::dict::keys::{FONT, SMASK, TR, TR2};
+use hayro_syntax::object::dict::keys::{FONT, OP_NS, SMASK, TR, TR2};
 use hayro_syntax::object::{Array, Dict, Name, Number, Object};
 use hayro_syntax::page::Resources;
 use kurbo::{Affine, BezPath, Vec2};
-use smallvec::smallvec;
+use smallvec::{SmallVec, smallvec};
 use std::ops::Deref;
 
 /// A transfer function.
@@ -22,8 +23,9 @@ use std::ops::Deref;
 pub enum ActiveTransferFunction {
     /// A single transfer function applied to all components.
     Single(Function),
-    /// Four transfer functions, one for each component.
-    Four([Function; 4]),
+    /// Four transfer functions, one for each component. `None` stands for the
+    /// identity function.
+    Four([Option<Function>; 4]),
 }
 
 impl ActiveTransferFunction {
@@ -32,25 +34,61 @@ impl ActiveTransferFunction {
     pub fn apply(&self, color: &AlphaColor) -> AlphaColor {
         let mut rgba = color.components();
 
-        match self {
-            Self::Single(f) => {
-                for c in &mut rgba[..3] {
-                    if let Some(out) = f.eval(smallvec![*c]) {
-                        *c = out[0];
-                    }
-                }
-            }
-            Self::Four(functions) => {
-                for (i, f) in functions[..3].iter().enumerate() {
-                    if let Some(out) = f.eval(smallvec![rgba[i]]) {
-                        rgba[i] = out[0];
-                    }
-                }
-            }
+        for (i, c) in rgba[..3].iter_mut().enumerate() {
+            *c = self.apply_channel(i, *c);
         }
 
         AlphaColor::new(rgba)
     }
+
+    /// Apply the transfer function for the channel with the given index to a value
+    /// between 0.0 and 1.0.
+    ///
+    /// The indices 0 to 2 refer to the red, green and blue channels, and 3 to
+    /// the gray channel.
+    pub(crate) fn apply_channel(&self, index: usize, value: f32) -> f32 {
+        let function = match self {
+            Self::Single(f) => Some(f),
+            Self::Four(functions) => functions.get(index).and_then(|f| f.as_ref()),
+        };
+
+        function
+            .and_then(|f| f.eval(smallvec![value]))
+            .and_then(|v| v.first().copied())
+            .map(|v| v.clamp(0.0, 1.0))
+            .unwrap_or(value)
+    }
+}
+
+/// Parse a transfer function from the `TR` or `TR2` entry of a graphics state.
+///
+/// Returns `Some(None)` for the `Identity` and `Default` transfer functions.
+fn parse_transfer_function(object: Object<'_>) -> Option<Option<ActiveTransferFunction>> {
+    // Only `Identity` and `Default` are valid names. Since the output device
+    // doesn't have a transfer function of its own, both mean the identity.
+    let single = |object: &Object<'_>| match object {
+        Object::Name(_) => Some(None),
+        o => Function::new(o).map(Some),
+    };
+
+    match object {
+        Object::Array(array) => {
+            let mut iter = array.iter::<Object<'_>>();
+            let functions = [
+                single(&iter.next()?)?,
+                single(&iter.next()?)?,
+                single(&iter.next()?)?,
+                single(&iter.next()?)?,
+            ];
+
+            if functions.iter().all(|f| f.is_none()) {
+                Some(None)
+            } else {
+                Some(Some(ActiveTransferFunction::Four(functions)))
+            }
+        }
+        o => Some(single(&o)?.map(ActiveTransferFunction::Single)),
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -97,6 +135,7 @@ impl<'a> State<'a> {
             color_space: self.graphics_state.stroke_cs.clone(),
             pattern: self.graphics_state.stroke_pattern.clone(),
             transfer_function: self.graphics_state.transfer_function.clone(),
+            rendering_intent: self.graphics_state.rendering_intent,
         }
     }
 
@@ -107,6 +146,7 @@ impl<'a> State<'a> {
             color_space: self.graphics_state.none_stroke_cs.clone(),
             pattern: self.graphics_state.non_stroke_pattern.clone(),
             transfer_function: self.graphics_state.transfer_function.clone(),
+            rendering_intent: self.graphics_state.rendering_intent,
         }
     }
 }
@@ -118,6 +158,10 @@ pub(crate) enum TextStateFont<'a> {
     Font(Font<'a>),
     /// The font was not set or an invalid font was set.
     Fallback(Font<'a>),
+    /// The embedded font program is corrupt and its text is skipped, see
+    /// [`CorruptFontPolicy::Skip`](crate::CorruptFontPolicy::Skip). The substitute
+    /// font is only used for advancing the text position.
+    Skipped(Font<'a>),
 }
 
 impl<'a> Deref for TextStateFont<'a> {
@@ -127,6 +171,7 @@ impl<'a> Deref for TextStateFont<'a> {
         match self {
             TextStateFont::Font(f) => f,
             TextStateFont::Fallback(f) => f,
+            TextStateFont::Skipped(f) => f,
         }
     }
 }
@@ -193,7 +238,9 @@ impl<'a> TextState<'a> {
         self.text_matrix *= Affine::new([1.0, 0.0, 0.0, 1.0, tx as f64, ty as f64]);
     }
 
-    pub(crate) fn apply_code_advance(&mut self, char_code: u32, code_len: usize) {
+    /// Advance the text matrix after showing the glyph with the given code, and return
+    /// the advance in text space.
+    pub(crate) fn apply_code_advance(&mut self, char_code: u32, code_len: usize) -> Vec2 {
         let glyph_advance = self
             .font
             .as_ref()
@@ -223,6 +270,8 @@ impl<'a> TextState<'a> {
         };
 
         self.text_matrix *= Affine::new([1.0, 0.0, 0.0, 1.0, tx as f64, ty as f64]);
+
+        Vec2::new(tx as f64, ty as f64)
     }
 
     pub(crate) fn full_transform(&self) -> Affine {
@@ -269,6 +318,25 @@ pub(crate) struct GraphicsState<'a> {
     pub(crate) soft_mask: Option<SoftMask<'a>>,
     pub(crate) transfer_function: Option<ActiveTransferFunction>,
     pub(crate) blend_mode: BlendMode,
+    /// The rendering intent, or `None` if it was never set, in which case the
+    /// default color conversions are used.
+    pub(crate) rendering_intent: Option<RenderingIntent>,
+
+    // Overprint parameters.
+    pub(crate) stroke_overprint: bool,
+    pub(crate) non_stroke_overprint: bool,
+    pub(crate) overprint_mode: u8,
+
+    /// The smoothness tolerance, or `None` if it was never set.
+    pub(crate) smoothness: Option<f32>,
+    /// The flatness tolerance in device pixels, or `None` if it was never set.
+    pub(crate) flatness: Option<f32>,
+    /// The color space in which the current transparency group is blended, or
+    /// `None` if neither the group nor one of its parents declares one.
+    pub(crate) blending_color_space: Option<ColorSpace>,
+    /// Whether glyphs of the same text operator knock each other out instead
+    /// of being composited onto each other.
+    pub(crate) text_knockout: bool,
 }
 
 impl Default for GraphicsState<'_> {
@@ -286,6 +354,14 @@ impl Default for GraphicsState<'_> {
             soft_mask: None,
             transfer_function: None,
             blend_mode: BlendMode::default(),
+            rendering_intent: None,
+            stroke_overprint: false,
+            non_stroke_overprint: false,
+            overprint_mode: 0,
+            smoothness: None,
+            flatness: None,
+            blending_color_space: None,
+            text_knockout: true,
         }
     }
 }
@@ -296,6 +372,7 @@ pub(crate) struct PaintData<'a> {
     pub(crate) color_space: ColorSpace,
     pub(crate) pattern: Option<Pattern<'a>>,
     pub(crate) transfer_function: Option<ActiveTransferFunction>,
+    pub(crate) rendering_intent: Option<RenderingIntent>,
 }
 
 pub(crate) fn handle_gs<'a>(
@@ -311,6 +388,27 @@ pub(crate) fn handle_gs<'a>(
     }
 }
 
+/// Set the dash array and phase of the graphics state.
+pub(crate) fn set_dash_pattern(
+    context: &mut Context<'_>,
+    mut dash_array: SmallVec<[f32; 4]>,
+    dash_phase: f32,
+) {
+    // Dash arrays with negative lengths or only zeros are invalid, so draw a
+    // solid line instead.
+    if dash_array.iter().any(|n| *n < 0.0) || dash_array.iter().all(|n| *n == 0.0) {
+        if !dash_array.is_empty() {
+            warn!("invalid dash array {:?}", dash_array);
+        }
+
+        dash_array.clear();
+    }
+
+    let stroke_props = &mut context.get_mut().graphics_state.stroke_props;
+    stroke_props.dash_offset = dash_phase;
+    stroke_props.dash_array = dash_array;
+}
+
 pub(crate) fn handle_gs_single<'a>(
     dict: &Dict<'a>,
     key: Name<'_>,
@@ -332,24 +430,23 @@ pub(crate) fn handle_gs_single<'a>(
         "CA" => context.get_mut().graphics_state.stroke_alpha = dict.get::<f32>(key)?,
         "ca" => context.get_mut().graphics_state.non_stroke_alpha = dict.get::<f32>(key)?,
         "TR" | "TR2" => {
-            let function = match dict
+            let function = dict
                 .get::<Object<'_>>(TR2)
-                .or_else(|| dict.get::<Object<'_>>(TR))?
-            {
-                Object::Array(array) => {
-                    let mut iter = array.iter::<Object<'_>>();
-                    let functions = [
-                        Function::new(&iter.next()?)?,
-                        Function::new(&iter.next()?)?,
-                        Function::new(&iter.next()?)?,
-                        Function::new(&iter.next()?)?,
-                    ];
-
-                    Some(ActiveTransferFunction::Four(functions))
-                }
-                // Only `Identity` and `Default` are valid, which both just reset it.
-                Object::Name(_) => None,
-                o => Some(ActiveTransferFunction::Single(Function::new(&o)?)),
+                .and_then(parse_transfer_function)
+                .or_else(|| dict.get::<Object<'_>>(TR).and_then(parse_transfer_function));
+
+            let Some(function) = function else {
+                (context.settings.warning_sink)(
+                    InterpreterWarning::InvalidFunction {
+                        function: dict
+                            .get_ref(TR2)
+                            .or_else(|| dict.get_ref(TR))
+                            .map(Into::into),
+                    }
+                    .into(),
+                );
+
+                return None;
             };
 
             context.get_mut().graphics_state.transfer_function = function;
@@ -401,12 +498,35 @@ pub(crate) fn handle_gs_single<'a>(
             let dash_array = iter.next()?.into_array()?;
             let dash_phase = iter.next()?.into_number()?.as_f32();
 
-            context.get_mut().graphics_state.stroke_props.dash_offset = dash_phase;
-            context.get_mut().graphics_state.stroke_props.dash_array = dash_array
-                .iter::<f32>()
-                // kurbo apparently cannot properly deal with offsets that are exactly 0.
-                .map(|n| if n == 0.0 { 0.01 } else { n })
-                .collect();
+            set_dash_pattern(context, dash_array.iter::<f32>().collect(), dash_phase);
+        }
+        "OP" => {
+            let overprint = dict.get::<bool>(key)?;
+            context.get_mut().graphics_state.stroke_overprint = overprint;
+
+            // If `op` is missing, `OP` applies to non-stroking operations as well.
+            if !dict.contains_key(OP_NS) {
+                context.get_mut().graphics_state.non_stroke_overprint = overprint;
+            }
+        }
+        "op" => context.get_mut().graphics_state.non_stroke_overprint = dict.get::<bool>(key)?,
+        "TK" => context.get_mut().graphics_state.text_knockout = dict.get::<bool>(key)?,
+        "OPM" => context.get_mut().graphics_state.overprint_mode = dict.get::<u8>(key)?,
+        "SA" => {
+            let stroke_props = &mut context.get_mut().graphics_state.stroke_props;
+            stroke_props.stroke_adjustment = dict.get::<bool>(key)?;
+        }
+        "SM" => {
+            context.get_mut().graphics_state.smoothness =
+                Some(dict.get::<f32>(key)?.clamp(0.0, 1.0));
+        }
+        "FL" => {
+            context.get_mut().graphics_state.flatness =
+                Some(dict.get::<f32>(key)?.clamp(0.0, 100.0));
+        }
+        "RI" => {
+            context.get_mut().graphics_state.rendering_intent =
+                Some(RenderingIntent::from_name(&dict.get::<Name<'_>>(key)?));
         }
         "Type" => {}
         _ => {}
@@ -1,17 +1,120 @@
 use crate::context::Context;
 use crate::device::Device;
-use crate::font::Glyph;
+use crate::font::{Glyph, GlyphRun, RunGlyph};
 use crate::interpret::state::TextStateFont;
-use crate::{DrawMode, FillRule};
+use crate::stats::Phase;
+use crate::{BlendMode, DrawProps, SoftMask, StrokeProps};
 use hayro_syntax::object;
 use hayro_syntax::page::Resources;
-use kurbo::Affine;
+use kurbo::{Affine, Join};
+use std::mem;
+
+/// The parts of the graphics state that are replaced while drawing the glyphs
+/// of a text operator into a knockout group.
+pub(crate) struct KnockoutGroup<'a> {
+    non_stroke_alpha: f32,
+    stroke_alpha: f32,
+    soft_mask: Option<SoftMask<'a>>,
+    blend_mode: BlendMode,
+}
+
+/// Push a knockout group for the glyphs of a text operator, if necessary.
+///
+/// If text knockout is enabled, glyphs that overlap each other are not
+/// composited onto each other, but only onto the backdrop of the text, so
+/// that for example overlapping glyphs with a low alpha don't appear darker.
+/// This is achieved by drawing the glyphs opaquely into a transparency group,
+/// which is then composited with the alpha, soft mask and blend mode of the
+/// graphics state.
+pub(crate) fn push_knockout_group<'a>(
+    ctx: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+) -> Option<KnockoutGroup<'a>> {
+    let render_mode = ctx.get().text_state.render_mode;
+    let gs = &ctx.get().graphics_state;
+
+    if !gs.text_knockout || !ctx.ocg_state.is_visible() {
+        return None;
+    }
+
+    let (fills, strokes) = (render_mode.fills(), render_mode.strokes());
+
+    // Patterns keep the alpha that was active when they were selected, so they
+    // can't be drawn opaquely.
+    if (fills && (gs.none_stroke_cs.is_pattern() || gs.non_stroke_pattern.is_some()))
+        || (strokes && (gs.stroke_cs.is_pattern() || gs.stroke_pattern.is_some()))
+    {
+        return None;
+    }
+
+    let opacity = match (fills, strokes) {
+        (true, true) if gs.non_stroke_alpha != gs.stroke_alpha => return None,
+        (true, _) => gs.non_stroke_alpha,
+        (false, true) => gs.stroke_alpha,
+        (false, false) => return None,
+    };
+
+    if opacity == 1.0 && gs.soft_mask.is_none() && gs.blend_mode == BlendMode::Normal {
+        return None;
+    }
+
+    let gs = &mut ctx.get_mut().graphics_state;
+    let group = KnockoutGroup {
+        non_stroke_alpha: mem::replace(&mut gs.non_stroke_alpha, 1.0),
+        stroke_alpha: mem::replace(&mut gs.stroke_alpha, 1.0),
+        soft_mask: gs.soft_mask.take(),
+        blend_mode: mem::take(&mut gs.blend_mode),
+    };
+
+    if let Some(color_space) = &gs.blending_color_space {
+        device.push_transparency_group_with_color_space(
+            opacity,
+            group.soft_mask.clone(),
+            group.blend_mode,
+            color_space,
+        );
+    } else {
+        device.push_transparency_group(opacity, group.soft_mask.clone(), group.blend_mode);
+    }
+
+    Some(group)
+}
+
+/// Pop a knockout group that was pushed with [`push_knockout_group`].
+pub(crate) fn pop_knockout_group<'a>(
+    ctx: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+    group: Option<KnockoutGroup<'a>>,
+) {
+    let Some(group) = group else {
+        return;
+    };
+
+    let gs = &mut ctx.get_mut().graphics_state;
+    gs.non_stroke_alpha = group.non_stroke_alpha;
+    gs.stroke_alpha = group.stroke_alpha;
+    gs.soft_mask = group.soft_mask;
+    gs.blend_mode = group.blend_mode;
+
+    device.pop_transparency_group();
+}
 
 pub(crate) fn show_text_string<'a>(
     ctx: &mut Context<'a>,
     device: &mut impl Device<'a>,
     resources: &Resources<'a>,
     text: &object::String<'_>,
+) {
+    ctx.measure(Phase::Text, |ctx| {
+        show_text_string_impl(ctx, device, resources, text);
+    });
+}
+
+fn show_text_string_impl<'a>(
+    ctx: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+    resources: &Resources<'a>,
+    text: &object::String<'_>,
 ) {
     let Some(font) = ctx.get().text_state.font.clone() else {
         warn!("tried to show text without active font");
@@ -27,24 +130,36 @@ pub(crate) fn show_text_string<'a>(
     let show_glyphs = matches!(font, TextStateFont::Font(_))
         || (matches!(font, TextStateFont::Fallback(_)) && bytes.is_ascii());
 
+    let text_matrix = ctx.get().text_state.text_matrix;
+    let mut glyphs = vec![];
     let mut cur_idx = 0;
 
     while cur_idx < bytes.len() {
         let (code, adv) = font.read_code(bytes, cur_idx);
         cur_idx += adv;
 
-        if show_glyphs {
-            let (glyph, glyph_transform) = font.get_glyph(
+        let glyph = show_glyphs.then(|| {
+            font.get_glyph(
                 font.map_code(code),
                 code,
                 ctx,
                 resources,
                 font.origin_displacement(code),
-            );
-            show_glyph(ctx, device, &glyph, glyph_transform);
+            )
+        });
+        let advance = ctx.get_mut().text_state.apply_code_advance(code, adv);
+
+        if let Some((glyph, transform)) = glyph {
+            glyphs.push(RunGlyph {
+                glyph,
+                transform,
+                advance,
+            });
         }
+    }
 
-        ctx.get_mut().text_state.apply_code_advance(code, adv);
+    if !glyphs.is_empty() {
+        show_glyph_run(ctx, device, glyphs, text_matrix);
     }
 }
 
@@ -54,100 +169,76 @@ pub(crate) fn next_line(ctx: &mut Context<'_>, tx: f64, ty: f64) {
     ctx.get_mut().text_state.text_matrix = new_matrix;
 }
 
-pub(crate) fn show_glyph<'a>(
+fn show_glyph_run<'a>(
     ctx: &mut Context<'a>,
     device: &mut impl Device<'a>,
-    glyph: &Glyph<'a>,
-    glyph_transform: Affine,
+    glyphs: Vec<RunGlyph<'a>>,
+    text_matrix: Affine,
 ) {
     if !ctx.ocg_state.is_visible() {
         return;
     }
 
-    let stroke_props = ctx.stroke_props();
+    let render_mode = ctx.get().text_state.render_mode;
 
-    match ctx.get().text_state.render_mode {
-        TextRenderingMode::Fill => {
-            let props = ctx.draw_props(false);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Fill(FillRule::NonZero),
-            );
-        }
-        TextRenderingMode::Stroke => {
-            let props = ctx.draw_props(true);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Stroke(stroke_props),
-            );
-        }
-        TextRenderingMode::FillStroke => {
-            let props = ctx.draw_props(false);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Fill(FillRule::NonZero),
-            );
-            let props = ctx.draw_props(true);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Stroke(stroke_props),
-            );
-        }
-        TextRenderingMode::Invisible => {
-            // Still call draw_glyph for invisible text, so that it can
-            // for example be used for text extraction.
-            let props = ctx.draw_props(false);
-            device.draw_glyph(glyph, glyph_transform, props, &DrawMode::Invisible);
-        }
-        TextRenderingMode::Clip => {
-            clip_glyph(ctx, glyph, glyph_transform);
-        }
-        TextRenderingMode::FillAndClip => {
-            clip_glyph(ctx, glyph, glyph_transform);
-            let props = ctx.draw_props(false);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Fill(FillRule::NonZero),
-            );
-        }
-        TextRenderingMode::StrokeAndClip => {
-            clip_glyph(ctx, glyph, glyph_transform);
-            let props = ctx.draw_props(true);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Stroke(stroke_props),
-            );
-        }
-        TextRenderingMode::FillAndStrokeAndClip => {
-            clip_glyph(ctx, glyph, glyph_transform);
-            let props = ctx.draw_props(false);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Fill(FillRule::NonZero),
-            );
-            let props = ctx.draw_props(true);
-            device.draw_glyph(
-                glyph,
-                glyph_transform,
-                props,
-                &DrawMode::Stroke(stroke_props),
-            );
+    if render_mode.clips() {
+        for glyph in &glyphs {
+            clip_glyph(ctx, &glyph.glyph, glyph.transform);
         }
     }
+
+    // Still draw invisible text, so that it can for example be used for text
+    // extraction.
+    let fill = (render_mode.fills() || matches!(render_mode, TextRenderingMode::Invisible))
+        .then(|| ctx.draw_props(false));
+    let stroke = if render_mode.strokes() {
+        Some((ctx.draw_props(true), ctx.stroke_props()))
+    } else if render_mode.fills() {
+        fill.as_ref()
+            .zip(glyphs.first())
+            .and_then(|(fill, glyph)| synthetic_bold_stroke(fill, glyph))
+    } else {
+        None
+    };
+
+    if fill.is_none() && stroke.is_none() {
+        return;
+    }
+
+    if let Some(stats) = ctx.stats_collector() {
+        stats.record_glyphs(glyphs.len());
+    }
+
+    device.draw_glyph_run(&GlyphRun {
+        glyphs,
+        text_matrix,
+        font_size: ctx.get().text_state.font_size,
+        render_mode,
+        fill,
+        stroke,
+    });
+}
+
+/// If the font lacks the requested bold style, simulate it by additionally stroking the
+/// filled glyphs with the fill paint.
+fn synthetic_bold_stroke<'a>(
+    fill: &DrawProps<'a>,
+    glyph: &RunGlyph<'a>,
+) -> Option<(DrawProps<'a>, StrokeProps)> {
+    let Glyph::Outline(o) = &glyph.glyph else {
+        return None;
+    };
+
+    let line_width = o.synthetic_style().embolden_width(glyph.transform)?;
+
+    Some((
+        fill.clone(),
+        StrokeProps {
+            line_width,
+            line_join: Join::Round,
+            ..Default::default()
+        },
+    ))
 }
 
 pub(crate) fn clip_glyph(context: &mut Context<'_>, glyph: &Glyph<'_>, transform: Affine) {
@@ -166,15 +257,51 @@ pub(crate) fn clip_glyph(context: &mut Context<'_>, glyph: &Glyph<'_>, transform
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-pub(crate) enum TextRenderingMode {
+/// A text rendering mode, which determines whether glyphs are filled, stroked or
+/// added to the clip path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextRenderingMode {
+    /// Fill the glyphs.
     #[default]
     Fill,
+    /// Stroke the glyphs.
     Stroke,
+    /// Fill, then stroke the glyphs.
     FillStroke,
+    /// Neither fill nor stroke the glyphs.
     Invisible,
+    /// Fill the glyphs and add them to the clip path.
     FillAndClip,
+    /// Stroke the glyphs and add them to the clip path.
     StrokeAndClip,
+    /// Fill, then stroke the glyphs and add them to the clip path.
     FillAndStrokeAndClip,
+    /// Add the glyphs to the clip path.
     Clip,
 }
+
+impl TextRenderingMode {
+    /// Whether glyphs are filled in this mode.
+    pub fn fills(&self) -> bool {
+        matches!(
+            self,
+            Self::Fill | Self::FillStroke | Self::FillAndClip | Self::FillAndStrokeAndClip
+        )
+    }
+
+    /// Whether glyphs are stroked in this mode.
+    pub fn strokes(&self) -> bool {
+        matches!(
+            self,
+            Self::Stroke | Self::FillStroke | Self::StrokeAndClip | Self::FillAndStrokeAndClip
+        )
+    }
+
+    /// Whether glyphs are added to the clip path in this mode.
+    pub fn clips(&self) -> bool {
+        matches!(
+            self,
+            Self::FillAndClip | Self::StrokeAndClip | Self::FillAndStrokeAndClip | Self::Clip
+        )
+    }
+}
@@ -0,0 +1,705 @@
+//! Synthesizing the appearance of form fields that don't have an appearance stream.
+
+use crate::context::Context;
+use crate::device::Device;
+use crate::font::{Font, StandardFont, UNITS_PER_EM};
+use crate::interpret::interpret;
+use crate::interpret::state::TextStateFont;
+use crate::util::decode_text_string;
+use hayro_syntax::content::TypedIter;
+use hayro_syntax::content::ops::TypedInstruction;
+use hayro_syntax::object::dict::keys::{
+    ACRO_FORM, AS, BC, BG, BS, CA, D, DA, DR, FF, FT, MK, OPT, PARENT, Q, R, S, V, W,
+};
+use hayro_syntax::object::{self, Array, Dict, Name, Object};
+use hayro_syntax::page::Resources;
+use kurbo::{Affine, Rect};
+
+/// The maximum number of parents that are visited when looking up inherited field attributes.
+const MAX_FIELD_DEPTH: usize = 32;
+/// The ratio between the height of a line and the font size.
+const LINE_FACTOR: f32 = 1.35;
+/// The ratio between the descent of a line and the font size.
+const DESCENT_FACTOR: f32 = 0.35;
+/// The space between the border and the contents of a field.
+const PADDING: f32 = 2.0;
+/// The font size used for multiline fields with an automatic font size.
+const MULTILINE_FONT_SIZE: f32 = 12.0;
+/// The color Acrobat uses to highlight the selected options of a list box.
+const SELECTION_COLOR: [f32; 3] = [0.6, 0.757, 0.855];
+
+// Field flags, see 12.7.4 Field types.
+const MULTILINE: u32 = 1 << 12;
+const PASSWORD: u32 = 1 << 13;
+const RADIO: u32 = 1 << 15;
+const PUSH_BUTTON: u32 = 1 << 16;
+const COMBO: u32 = 1 << 17;
+
+/// Draw a widget annotation without an appearance stream by generating a content
+/// stream from its field value, default appearance and appearance characteristics.
+pub(crate) fn draw_widget<'a>(
+    annot: &Dict<'a>,
+    rect: Rect,
+    resources: &Resources<'a>,
+    context: &mut Context<'a>,
+    device: &mut impl Device<'a>,
+) {
+    let acro_form = context
+        .xref
+        .get::<Dict<'_>>(context.xref.root_id())
+        .and_then(|catalog| catalog.get::<Dict<'_>>(ACRO_FORM));
+    // Fonts referenced by the default appearance are looked up in the default
+    // resources of the form.
+    let resources = match acro_form.as_ref().and_then(|f| f.get::<Dict<'_>>(DR)) {
+        Some(dr) => Resources::from_parent(dr, resources.clone()),
+        None => resources.clone(),
+    };
+    let mk = annot.get::<Dict<'_>>(MK);
+    let rect = rect.abs();
+
+    // The contents are laid out in a box that is rotated together with them.
+    let (w, h) = (rect.width(), rect.height());
+    let rotation = mk
+        .as_ref()
+        .and_then(|mk| mk.get::<i32>(R))
+        .unwrap_or(0)
+        .rem_euclid(360);
+    let (width, height, rotate) = match rotation {
+        90 => (h, w, Affine::new([0.0, 1.0, -1.0, 0.0, w, 0.0])),
+        180 => (w, h, Affine::new([-1.0, 0.0, 0.0, -1.0, w, h])),
+        270 => (h, w, Affine::new([0.0, -1.0, 1.0, 0.0, 0.0, h])),
+        _ => (w, h, Affine::IDENTITY),
+    };
+
+    let flags = inherited(annot, FF)
+        .and_then(|d| d.get::<u32>(FF))
+        .unwrap_or(0);
+    let field_type = inherited(annot, FT).and_then(|d| d.get::<Name<'_>>(FT));
+    let is_radio = field_type.as_deref() == Some(b"Btn") && flags & (RADIO | PUSH_BUTTON) == RADIO;
+
+    let mut widget = Widget {
+        annot,
+        acro_form: acro_form.as_ref(),
+        resources: &resources,
+        content: Content::default(),
+        width: width as f32,
+        height: height as f32,
+        border_width: 0.0,
+    };
+
+    widget.background_and_border(mk.as_ref(), is_radio);
+
+    match field_type.as_deref() {
+        Some(b"Tx") if flags & PASSWORD == 0 => {
+            if let Some(value) = inherited(annot, V).and_then(|d| d.get::<object::String<'_>>(V)) {
+                widget.text(context, &decode_text_string(&value), flags & MULTILINE != 0);
+            }
+        }
+        Some(b"Ch") if flags & COMBO != 0 => {
+            if let Some(value) = widget.selected_values().first() {
+                widget.text(context, &option_label(annot, value), false);
+            }
+        }
+        Some(b"Ch") => widget.list_box(context),
+        Some(b"Btn") if flags & PUSH_BUTTON != 0 => {
+            if let Some(caption) = mk.as_ref().and_then(|mk| mk.get::<object::String<'_>>(CA)) {
+                widget.caption(context, &decode_text_string(&caption));
+            }
+        }
+        Some(b"Btn") if is_checked(annot) => {
+            let symbol = mk
+                .as_ref()
+                .and_then(|mk| mk.get::<object::String<'_>>(CA))
+                .and_then(|ca| ca.first().copied())
+                .unwrap_or(if is_radio { b'l' } else { b'4' });
+            widget.symbol(symbol);
+        }
+        _ => {}
+    }
+
+    let content = widget.content.0;
+
+    context.save_state();
+    context.pre_concat_affine(Affine::translate((rect.x0, rect.y0)) * rotate);
+    context.push_root_transform();
+    interpret(TypedIter::new(&content), &resources, context, device);
+    context.pop_root_transform();
+    context.restore_state(device);
+}
+
+struct Widget<'a, 'b> {
+    annot: &'b Dict<'a>,
+    acro_form: Option<&'b Dict<'a>>,
+    resources: &'b Resources<'a>,
+    content: Content,
+    width: f32,
+    height: f32,
+    border_width: f32,
+}
+
+impl<'a> Widget<'a, '_> {
+    fn background_and_border(&mut self, mk: Option<&Dict<'_>>, is_radio: bool) {
+        let (width, height) = (self.width, self.height);
+        let content = &mut self.content;
+
+        if let Some(bg) = mk.and_then(|mk| mk.get::<Array<'_>>(BG))
+            && content.color(&bg, false)
+        {
+            if is_radio {
+                content.circle(width / 2.0, height / 2.0, width.min(height) / 2.0);
+            } else {
+                content.op(&[0.0, 0.0, width, height], "re");
+            }
+
+            content.op(&[], "f");
+        }
+
+        let Some(bc) = mk.and_then(|mk| mk.get::<Array<'_>>(BC)) else {
+            return;
+        };
+
+        let bs = self.annot.get::<Dict<'_>>(BS);
+        let border_width = bs.as_ref().and_then(|bs| bs.get::<f32>(W)).unwrap_or(1.0);
+
+        if border_width <= 0.0 || !content.color(&bc, true) {
+            return;
+        }
+
+        self.border_width = border_width;
+        let style = bs.as_ref().and_then(|bs| bs.get::<Name<'_>>(S));
+        let half = border_width / 2.0;
+        content.op(&[border_width], "w");
+
+        if style.as_deref() == Some(b"D") {
+            let dashes = bs
+                .as_ref()
+                .and_then(|bs| bs.get::<Array<'_>>(D))
+                .map(|d| d.iter::<f32>().collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![3.0]);
+            content.dash(&dashes);
+        }
+
+        if style.as_deref() == Some(b"U") {
+            content.op(&[0.0, half], "m");
+            content.op(&[width, half], "l");
+        } else if is_radio {
+            content.circle(width / 2.0, height / 2.0, width.min(height) / 2.0 - half);
+        } else {
+            content.op(
+                &[half, half, width - border_width, height - border_width],
+                "re",
+            );
+        }
+
+        content.op(&[], "S");
+    }
+
+    /// Draw the value of a text field or combo box.
+    fn text(&mut self, context: &mut Context<'a>, value: &str, multiline: bool) {
+        let quadding = self.quadding();
+        let da = self.default_appearance(context);
+        let text = encode_text(value);
+        let max_width = self.inner_width();
+
+        if multiline {
+            let size = if da.size > 0.0 {
+                da.size
+            } else {
+                MULTILINE_FONT_SIZE
+            };
+            let lines = wrap(&da.font, &text, size, max_width);
+            self.lines(&da, size, &lines, quadding, &[]);
+        } else {
+            let size = self.fitting_size(&da, &text);
+            let y = (self.height - size * LINE_FACTOR) / 2.0 + size * DESCENT_FACTOR;
+
+            self.begin_text(&da, size);
+            let x = self.line_start(&da.font, &text, size, quadding);
+            self.content.line(x, y, &text);
+            self.end_text();
+        }
+    }
+
+    /// Draw the caption of a push button.
+    fn caption(&mut self, context: &mut Context<'a>, caption: &str) {
+        let da = self.default_appearance(context);
+        let text = encode_text(caption);
+        let size = self.fitting_size(&da, &text);
+        let y = (self.height - size * LINE_FACTOR) / 2.0 + size * DESCENT_FACTOR;
+
+        self.begin_text(&da, size);
+        let x = self.line_start(&da.font, &text, size, 1);
+        self.content.line(x, y, &text);
+        self.end_text();
+    }
+
+    /// Draw the options of a list box, highlighting the selected ones.
+    fn list_box(&mut self, context: &mut Context<'a>) {
+        let Some(options) = inherited(self.annot, OPT).and_then(|d| d.get::<Array<'_>>(OPT)) else {
+            return;
+        };
+
+        let da = self.default_appearance(context);
+        let size = if da.size > 0.0 {
+            da.size
+        } else {
+            MULTILINE_FONT_SIZE
+        };
+        let selected = self.selected_values();
+        let mut lines = vec![];
+        let mut highlighted = vec![];
+
+        for option in options.iter::<Object<'_>>() {
+            let (export, label) = match option {
+                Object::String(s) => (s.to_vec(), s.to_vec()),
+                Object::Array(a) => {
+                    let mut iter = a.iter::<object::String<'_>>();
+                    let export = iter.next().map(|s| s.to_vec()).unwrap_or_default();
+                    let label = iter.next().map(|s| s.to_vec()).unwrap_or(export.clone());
+                    (export, label)
+                }
+                _ => continue,
+            };
+
+            highlighted.push(selected.contains(&export));
+            lines.push(encode_text(&decode_text_string(&label)));
+        }
+
+        let quadding = self.quadding();
+        self.lines(&da, size, &lines, quadding, &highlighted);
+    }
+
+    /// Draw the check mark or another symbol of a checked check box or radio button.
+    ///
+    /// The symbols are given as characters of the `ZapfDingbats` font, but are drawn as
+    /// paths, since the font is usually not part of the resources.
+    fn symbol(&mut self, symbol: u8) {
+        // The default appearance is only needed for the color, so text operators can be
+        // ignored.
+        let da = self
+            .default_appearance_string()
+            .unwrap_or_else(|| b"0 g".to_vec());
+        self.content.op(&[], "BT");
+        self.content.raw(&da);
+        self.content.op(&[], "ET");
+
+        let size = (self.width.min(self.height) - 2.0 * self.border_width).max(0.0);
+        let (cx, cy) = (self.width / 2.0, self.height / 2.0);
+        let content = &mut self.content;
+
+        match symbol {
+            // Circle.
+            b'l' => content.circle(cx, cy, size * 0.25),
+            // Square.
+            b'n' => content.op(
+                &[cx - size * 0.25, cy - size * 0.25, size * 0.5, size * 0.5],
+                "re",
+            ),
+            // Diamond.
+            b'u' => content.polygon(
+                cx,
+                cy,
+                size,
+                &[(0.0, -0.35), (0.35, 0.0), (0.0, 0.35), (-0.35, 0.0)],
+            ),
+            // Cross.
+            b'8' => {
+                content.polygon(
+                    cx,
+                    cy,
+                    size,
+                    &[(-0.3, -0.22), (-0.22, -0.3), (0.3, 0.22), (0.22, 0.3)],
+                );
+                content.polygon(
+                    cx,
+                    cy,
+                    size,
+                    &[(0.22, -0.3), (0.3, -0.22), (-0.22, 0.3), (-0.3, 0.22)],
+                );
+            }
+            // Check mark, which is also used for all other symbols.
+            _ => content.polygon(
+                cx,
+                cy,
+                size,
+                &[
+                    (-0.34, 0.02),
+                    (-0.24, 0.12),
+                    (-0.1, -0.02),
+                    (0.24, 0.32),
+                    (0.34, 0.22),
+                    (-0.1, -0.22),
+                ],
+            ),
+        }
+
+        content.op(&[], "f");
+    }
+
+    /// Draw several lines from the top of the field, optionally highlighting some of them.
+    fn lines(
+        &mut self,
+        da: &DefaultAppearance<'_>,
+        size: f32,
+        lines: &[Vec<u8>],
+        quadding: i32,
+        highlighted: &[bool],
+    ) {
+        let line_height = size * LINE_FACTOR;
+        let top = self.height - self.border_width - PADDING;
+        let baseline = |i: usize| top - (i + 1) as f32 * line_height + size * DESCENT_FACTOR;
+
+        for (i, _) in highlighted.iter().enumerate().filter(|(_, h)| **h) {
+            let content = &mut self.content;
+            content.op(&[], "q");
+            content.op(&SELECTION_COLOR, "rg");
+            content.op(
+                &[
+                    self.border_width,
+                    baseline(i) - size * DESCENT_FACTOR,
+                    self.width - 2.0 * self.border_width,
+                    line_height,
+                ],
+                "re",
+            );
+            content.op(&[], "f");
+            content.op(&[], "Q");
+        }
+
+        self.begin_text(da, size);
+
+        for (i, line) in lines.iter().enumerate() {
+            let x = self.line_start(&da.font, line, size, quadding);
+            self.content.line(x, baseline(i), line);
+        }
+
+        self.end_text();
+    }
+
+    /// Start a text object that is clipped to the inside of the border.
+    fn begin_text(&mut self, da: &DefaultAppearance<'_>, size: f32) {
+        let bw = self.border_width;
+        let content = &mut self.content;
+        content.op(&[], "q");
+        content.op(
+            &[bw, bw, self.width - 2.0 * bw, self.height - 2.0 * bw],
+            "re",
+        );
+        content.op(&[], "W");
+        content.op(&[], "n");
+        content.op(&[], "BT");
+        content.raw(&da.string);
+        content.name(&da.font_name);
+        content.op(&[size], "Tf");
+    }
+
+    fn end_text(&mut self) {
+        self.content.op(&[], "ET");
+        self.content.op(&[], "Q");
+    }
+
+    fn inner_width(&self) -> f32 {
+        self.width - 2.0 * (self.border_width + PADDING)
+    }
+
+    /// Return the x coordinate at which a line starts with the given quadding.
+    fn line_start(
+        &self,
+        font: &Option<TextStateFont<'_>>,
+        text: &[u8],
+        size: f32,
+        quadding: i32,
+    ) -> f32 {
+        let start = self.border_width + PADDING;
+        let remaining = self.inner_width() - text_width(font, text, size);
+
+        match quadding {
+            1 => start + remaining / 2.0,
+            2 => start + remaining,
+            _ => start,
+        }
+    }
+
+    /// Return the font size for a single line of text. An automatic font size fills the
+    /// height of the field, but shrinks to fit the text into its width.
+    fn fitting_size(&self, da: &DefaultAppearance<'_>, text: &[u8]) -> f32 {
+        if da.size > 0.0 {
+            return da.size;
+        }
+
+        let size = ((self.height - 2.0 * self.border_width) / LINE_FACTOR).max(0.0);
+        let width = text_width(&da.font, text, size);
+
+        if width > self.inner_width() && width > 0.0 {
+            (size * self.inner_width() / width).max(0.0)
+        } else {
+            size
+        }
+    }
+
+    /// Return the quadding of the field, where 0 means left-aligned, 1 centered and
+    /// 2 right-aligned.
+    fn quadding(&self) -> i32 {
+        inherited(self.annot, Q)
+            .or(self.acro_form.cloned())
+            .and_then(|d| d.get::<i32>(Q))
+            .unwrap_or(0)
+    }
+
+    /// Return the values that are selected in a choice field.
+    fn selected_values(&self) -> Vec<Vec<u8>> {
+        match inherited(self.annot, V).and_then(|d| d.get::<Object<'_>>(V)) {
+            Some(Object::String(s)) => vec![s.to_vec()],
+            Some(Object::Array(a)) => a.iter::<object::String<'_>>().map(|s| s.to_vec()).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn default_appearance_string(&self) -> Option<Vec<u8>> {
+        inherited(self.annot, DA)
+            .or(self.acro_form.cloned())
+            .and_then(|d| d.get::<object::String<'_>>(DA))
+            .map(|da| da.to_vec())
+    }
+
+    fn default_appearance(&self, context: &mut Context<'a>) -> DefaultAppearance<'a> {
+        let string = self
+            .default_appearance_string()
+            .unwrap_or_else(|| b"/Helv 0 Tf 0 g".to_vec());
+        let mut font_dict = None;
+        let mut font_name = b"Helv".to_vec();
+        let mut size = 0.0;
+
+        let mut ops = TypedIter::new(&string);
+
+        while let Some(op) = ops.next() {
+            if let TypedInstruction::TextFont(t) = op {
+                font_dict = self.resources.get_font(t.0);
+                font_name = t.0.to_vec();
+                size = t.1.as_f32();
+            }
+        }
+
+        // Like in content streams, fall back to Helvetica if the font doesn't exist.
+        let font = font_dict
+            .and_then(|dict| context.resolve_font(&dict))
+            .or_else(|| {
+                Font::new_standard(StandardFont::Helvetica, &context.settings.font_resolver)
+                    .map(TextStateFont::Fallback)
+            });
+
+        DefaultAppearance {
+            string,
+            font_name,
+            size,
+            font,
+        }
+    }
+}
+
+/// The parsed default appearance string of a field.
+struct DefaultAppearance<'a> {
+    string: Vec<u8>,
+    font_name: Vec<u8>,
+    /// The font size, where 0 means that it should be chosen automatically.
+    size: f32,
+    font: Option<TextStateFont<'a>>,
+}
+
+/// A content stream that is being generated.
+#[derive(Default)]
+struct Content(Vec<u8>);
+
+impl Content {
+    fn op(&mut self, operands: &[f32], operator: &str) {
+        for operand in operands {
+            self.0.extend_from_slice(format!("{operand} ").as_bytes());
+        }
+
+        self.0.extend_from_slice(operator.as_bytes());
+        self.0.push(b'\n');
+    }
+
+    fn raw(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+        self.0.push(b'\n');
+    }
+
+    fn name(&mut self, name: &[u8]) {
+        self.0.push(b'/');
+
+        for &b in name {
+            if b.is_ascii_graphic() && !b"()<>[]{}/%#".contains(&b) {
+                self.0.push(b);
+            } else {
+                self.0.extend_from_slice(format!("#{b:02X}").as_bytes());
+            }
+        }
+
+        self.0.push(b' ');
+    }
+
+    /// Show a line of text at the given position.
+    fn line(&mut self, x: f32, y: f32, text: &[u8]) {
+        self.op(&[1.0, 0.0, 0.0, 1.0, x, y], "Tm");
+        self.0.push(b'(');
+
+        for &b in text {
+            if matches!(b, b'(' | b')' | b'\\') {
+                self.0.push(b'\\');
+            }
+
+            self.0.push(b);
+        }
+
+        self.0.extend_from_slice(b") ");
+        self.op(&[], "Tj");
+    }
+
+    /// Set the fill or stroke color from a color array of an appearance characteristics
+    /// dictionary. Return `false` if the color is transparent or invalid.
+    fn color(&mut self, color: &Array<'_>, stroke: bool) -> bool {
+        let components = color.iter::<f32>().collect::<Vec<_>>();
+        let operator = match (components.len(), stroke) {
+            (1, false) => "g",
+            (1, true) => "G",
+            (3, false) => "rg",
+            (3, true) => "RG",
+            (4, false) => "k",
+            (4, true) => "K",
+            _ => return false,
+        };
+
+        self.op(&components, operator);
+
+        true
+    }
+
+    fn dash(&mut self, dashes: &[f32]) {
+        self.0.push(b'[');
+
+        for dash in dashes {
+            self.0.extend_from_slice(format!("{dash} ").as_bytes());
+        }
+
+        self.0.extend_from_slice(b"] ");
+        self.op(&[0.0], "d");
+    }
+
+    fn circle(&mut self, cx: f32, cy: f32, r: f32) {
+        // The distance of the control points for approximating a quarter circle.
+        let k = r * 0.5523;
+
+        self.op(&[cx + r, cy], "m");
+        self.op(&[cx + r, cy + k, cx + k, cy + r, cx, cy + r], "c");
+        self.op(&[cx - k, cy + r, cx - r, cy + k, cx - r, cy], "c");
+        self.op(&[cx - r, cy - k, cx - k, cy - r, cx, cy - r], "c");
+        self.op(&[cx + k, cy - r, cx + r, cy - k, cx + r, cy], "c");
+        self.op(&[], "h");
+    }
+
+    /// Add a polygon whose points are relative to a square with the given center and size.
+    fn polygon(&mut self, cx: f32, cy: f32, size: f32, points: &[(f32, f32)]) {
+        for (i, (x, y)) in points.iter().enumerate() {
+            let operator = if i == 0 { "m" } else { "l" };
+            self.op(&[cx + x * size, cy + y * size], operator);
+        }
+
+        self.op(&[], "h");
+    }
+}
+
+/// Return the first dictionary in the field hierarchy of the widget that contains the key.
+fn inherited<'a>(field: &Dict<'a>, key: &[u8]) -> Option<Dict<'a>> {
+    let mut field = field.clone();
+
+    for _ in 0..MAX_FIELD_DEPTH {
+        if field.contains_key(key) {
+            return Some(field);
+        }
+
+        field = field.get::<Dict<'_>>(PARENT)?;
+    }
+
+    None
+}
+
+/// Return whether a check box or radio button is checked.
+fn is_checked(annot: &Dict<'_>) -> bool {
+    let state = annot
+        .get::<Name<'_>>(AS)
+        .or_else(|| inherited(annot, V).and_then(|d| d.get::<Name<'_>>(V)));
+
+    state.is_some_and(|s| s.as_ref() != b"Off")
+}
+
+/// Return the text that is displayed for the option of a choice field with the given
+/// export value.
+fn option_label(field: &Dict<'_>, value: &[u8]) -> String {
+    let label = inherited(field, OPT)
+        .and_then(|d| d.get::<Array<'_>>(OPT))
+        .and_then(|options| {
+            options.iter::<Array<'_>>().find_map(|option| {
+                let mut iter = option.iter::<object::String<'_>>();
+                (iter.next()?.as_bytes() == value).then(|| iter.next())?
+            })
+        });
+
+    decode_text_string(label.as_deref().unwrap_or(value))
+}
+
+/// Encode text for a simple font. Characters outside of Latin-1 are replaced.
+fn encode_text(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+fn text_width(font: &Option<TextStateFont<'_>>, text: &[u8], size: f32) -> f32 {
+    let Some(font) = font else {
+        return 0.0;
+    };
+    let mut width = 0.0;
+    let mut offset = 0;
+
+    while offset < text.len() {
+        let (code, len) = font.read_code(text, offset);
+        width += font.code_advance(code).x as f32;
+        offset += len.max(1);
+    }
+
+    width / UNITS_PER_EM * size
+}
+
+/// Split text into lines at line breaks and wherever it exceeds the maximum width.
+fn wrap(font: &Option<TextStateFont<'_>>, text: &[u8], size: f32, max_width: f32) -> Vec<Vec<u8>> {
+    let mut lines = vec![];
+    let text = text
+        .split(|b| *b == b'\n')
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .flat_map(|l| l.split(|b| *b == b'\r'));
+
+    for paragraph in text {
+        let mut line: Vec<u8> = vec![];
+
+        for word in paragraph.split(|b| *b == b' ') {
+            let mut candidate = line.clone();
+
+            if !candidate.is_empty() {
+                candidate.push(b' ');
+            }
+
+            candidate.extend_from_slice(word);
+
+            if !line.is_empty() && text_width(font, &candidate, size) > max_width {
+                lines.push(std::mem::replace(&mut line, word.to_vec()));
+            } else {
+                line = candidate;
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
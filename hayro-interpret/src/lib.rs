@@ -18,11 +18,17 @@ the implementation of `hayro` and `hayro-svg` to get an idea on how to use this
 This crate forbids unsafe code via a crate-level attribute.
 
 # Cargo features
-This crate has one optional feature:
+This crate has the following optional features:
 - `embed-fonts`: PDF processors are required to support 14 predefined fonts that do not need to be
   embedded into a PDF file. If you enable this feature, hayro will embed a (permissively-licensed)
   substitute for each font, so that you don't have to implement your custom font loading logic. This
   will add around ~240KB to your binary.
+- `bidi`: Adds [`TextLine::reorder_bidi`](text_layout::TextLine::reorder_bidi), which converts
+  extracted right-to-left and mixed-direction text from visual into logical order.
+- `system-fonts`: Adds [`SystemFontResolver`](font::SystemFontResolver), a font resolver that looks up
+  fonts that aren't embedded in the PDF among the fonts installed on the system.
+- `serde`: Implements `Serialize` and `Deserialize` for [`OwnedDisplayList`], so that interpreted pages
+  can be cached or sent to other processes.
 */
 
 #![forbid(unsafe_code)]
@@ -35,10 +41,12 @@ mod cache;
 mod context;
 mod convert;
 mod device;
+mod display_list;
 mod function;
 mod interpret;
 mod ocg;
 mod soft_mask;
+mod stats;
 mod types;
 mod x_object;
 
@@ -46,17 +54,26 @@ pub mod color;
 pub mod encode;
 pub mod font;
 pub mod gradient;
+pub mod hit_test;
+pub mod image;
 pub mod pattern;
 pub mod shading;
+pub mod text;
+pub mod text_layout;
+pub mod text_search;
 pub mod util;
 
-pub use cache::CacheKey;
+pub use cache::{CacheKey, FormCache, ImageCache, SharedCache};
 pub use context::*;
 pub use device::*;
+pub use display_list::{DisplayList, OwnedDisplayList};
 pub use function::Function;
 pub use hayro_cmap;
 pub use hayro_syntax;
 pub use interpret::*;
+pub use ocg::{OptionalContentGroup, optional_content_groups};
 pub use soft_mask::*;
+pub use stats::{InterpreterStats, PhaseTimings};
 pub use types::*;
 pub use util::{RectExt, TransformExt};
+pub use x_object::XObject;
@@ -1,6 +1,103 @@
-use hayro_syntax::object::dict::keys::{BASE_STATE, D, OCGS, OCMD, OCPROPERTIES, OFF, ON, P, TYPE};
-use hayro_syntax::object::{Array, Dict, Name, ObjectIdentifier};
-use std::collections::HashSet;
+//! Optional content, also known as layers.
+
+use crate::util::decode_text_string;
+use hayro_syntax::Pdf;
+use hayro_syntax::object::dict::keys::{
+    BASE_STATE, D, NAME, OCGS, OCMD, OCPROPERTIES, OFF, ON, P, TYPE, VE,
+};
+use hayro_syntax::object::{Array, Dict, Name, Object, ObjectIdentifier};
+use std::collections::{HashMap, HashSet};
+
+/// An optional content group, which is usually shown as a layer in viewers.
+#[derive(Debug, Clone)]
+pub struct OptionalContentGroup {
+    /// The object identifier of the group.
+    ///
+    /// This can be used to override the visibility of the group with
+    /// [`InterpreterSettings::optional_content`](crate::InterpreterSettings::optional_content).
+    pub id: ObjectIdentifier,
+    /// The name of the group.
+    pub name: String,
+    /// Whether the group is visible in the default configuration of the document.
+    pub visible: bool,
+}
+
+/// Return the optional content groups of the document.
+pub fn optional_content_groups(pdf: &Pdf) -> Vec<OptionalContentGroup> {
+    let xref = pdf.xref();
+    let Some(oc_properties) = xref
+        .get::<Dict<'_>>(xref.root_id())
+        .and_then(|catalog| catalog.get::<Dict<'_>>(OCPROPERTIES))
+    else {
+        return vec![];
+    };
+
+    let inactive = default_inactive_ocgs(&oc_properties);
+    let Some(ocgs) = oc_properties.get::<Array<'_>>(OCGS) else {
+        return vec![];
+    };
+
+    ocgs.raw_iter()
+        .filter_map(|item| {
+            let id: ObjectIdentifier = item.as_obj_ref()?.into();
+            let name = xref
+                .get::<Dict<'_>>(id)?
+                .get::<hayro_syntax::object::String<'_>>(NAME)
+                .map(|n| decode_text_string(n.as_bytes()))
+                .unwrap_or_default();
+
+            Some(OptionalContentGroup {
+                id,
+                name,
+                visible: !inactive.contains(&id),
+            })
+        })
+        .collect()
+}
+
+/// Return the groups that are hidden in the default configuration.
+fn default_inactive_ocgs(oc_properties: &Dict<'_>) -> HashSet<ObjectIdentifier> {
+    let mut inactive = HashSet::new();
+
+    let Some(config) = oc_properties.get::<Dict<'_>>(D) else {
+        return inactive;
+    };
+
+    let base_state = config
+        .get::<Name<'_>>(BASE_STATE)
+        .and_then(|b| BaseState::from_name(b.as_ref()));
+
+    if base_state.unwrap_or(BaseState::On) == BaseState::Off
+        && let Some(ocgs) = oc_properties.get::<Array<'_>>(OCGS)
+    {
+        for item in ocgs.raw_iter() {
+            if let Some(ref_) = item.as_obj_ref() {
+                let id: ObjectIdentifier = ref_.into();
+                inactive.insert(id);
+            }
+        }
+    }
+
+    let mut read_ocg_array = |key, insert_active: bool| {
+        if let Some(arr) = config.get::<Array<'_>>(key) {
+            for item in arr.raw_iter() {
+                if let Some(ref_) = item.as_obj_ref() {
+                    let id: ObjectIdentifier = ref_.into();
+                    if insert_active {
+                        inactive.remove(&id);
+                    } else {
+                        inactive.insert(id);
+                    }
+                }
+            }
+        }
+    };
+
+    read_ocg_array(ON, true);
+    read_ocg_array(OFF, false);
+
+    inactive
+}
 
 pub(crate) struct OcgState {
     inactive_ocgs: HashSet<ObjectIdentifier>,
@@ -15,50 +112,25 @@ impl OcgState {
         }
     }
 
-    pub(crate) fn from_catalog(catalog: &Dict<'_>) -> Self {
-        let Some(oc_properties) = catalog.get::<Dict<'_>>(OCPROPERTIES) else {
-            return Self::dummy();
-        };
+    /// Create the state from the default configuration of the document, with
+    /// the visibility of the groups in `overrides` replaced.
+    pub(crate) fn from_catalog(
+        catalog: &Dict<'_>,
+        overrides: &HashMap<ObjectIdentifier, bool>,
+    ) -> Self {
+        let mut inactive = catalog
+            .get::<Dict<'_>>(OCPROPERTIES)
+            .map(|oc_properties| default_inactive_ocgs(&oc_properties))
+            .unwrap_or_default();
 
-        let Some(config) = oc_properties.get::<Dict<'_>>(D) else {
-            return Self::dummy();
-        };
-
-        let mut inactive = HashSet::new();
-
-        let base_state = config
-            .get::<Name<'_>>(BASE_STATE)
-            .and_then(|b| BaseState::from_name(b.as_ref()));
-
-        if base_state.unwrap_or(BaseState::On) == BaseState::Off
-            && let Some(ocgs) = oc_properties.get::<Array<'_>>(OCGS)
-        {
-            for item in ocgs.raw_iter() {
-                if let Some(ref_) = item.as_obj_ref() {
-                    let id: ObjectIdentifier = ref_.into();
-                    inactive.insert(id);
-                }
+        for (id, visible) in overrides {
+            if *visible {
+                inactive.remove(id);
+            } else {
+                inactive.insert(*id);
             }
         }
 
-        let mut read_ocg_array = |key, insert_active: bool| {
-            if let Some(arr) = config.get::<Array<'_>>(key) {
-                for item in arr.raw_iter() {
-                    if let Some(ref_) = item.as_obj_ref() {
-                        let id: ObjectIdentifier = ref_.into();
-                        if insert_active {
-                            inactive.remove(&id);
-                        } else {
-                            inactive.insert(id);
-                        }
-                    }
-                }
-            }
-        };
-
-        read_ocg_array(ON, true);
-        read_ocg_array(OFF, false);
-
         Self {
             inactive_ocgs: inactive,
             visibility_stack: Vec::new(),
@@ -72,6 +144,17 @@ impl OcgState {
     }
 
     pub(crate) fn begin_ocmd(&mut self, ocmd: &Dict<'_>) {
+        // A visibility expression takes precedence over the groups and policy.
+        if let Some(is_active) = ocmd
+            .get::<Array<'_>>(VE)
+            .and_then(|ve| self.evaluate_visibility_expression(&ve, 0))
+        {
+            let visible = self.is_visible() && is_active;
+            self.visibility_stack.push(visible);
+
+            return;
+        }
+
         let policy = ocmd
             .get::<Name<'_>>(P)
             .and_then(|n| OcmdPolicy::from_name(n.as_ref()))
@@ -104,6 +187,35 @@ impl OcgState {
         self.visibility_stack.push(visible);
     }
 
+    /// Evaluate a visibility expression, which is an array consisting of an
+    /// operator (`And`, `Or` or `Not`) followed by groups or nested expressions.
+    fn evaluate_visibility_expression(&self, expression: &Array<'_>, depth: u32) -> Option<bool> {
+        // Guard against cyclic expressions.
+        if depth > 32 {
+            return None;
+        }
+
+        let mut items = expression.raw_iter().zip(expression.iter::<Object<'_>>());
+        let operator = items.next()?.1.into_name()?;
+
+        let mut operands = items.map(|(raw, operand)| match operand {
+            // Groups are always given by reference.
+            Object::Dict(_) => {
+                let id: ObjectIdentifier = raw.as_obj_ref()?.into();
+                Some(!self.inactive_ocgs.contains(&id))
+            }
+            Object::Array(array) => self.evaluate_visibility_expression(&array, depth + 1),
+            _ => None,
+        });
+
+        match operator.as_ref() {
+            b"And" => operands.try_fold(true, |acc, v| Some(acc && v?)),
+            b"Or" => operands.try_fold(false, |acc, v| Some(acc || v?)),
+            b"Not" => operands.next()?.map(|v| !v),
+            _ => None,
+        }
+    }
+
     pub(crate) fn begin_ocg(&mut self, props: &Dict<'_>, ref_id: ObjectIdentifier) {
         match props.get::<Name<'_>>(TYPE).as_deref() {
             Some(OCMD) => self.begin_ocmd(props),
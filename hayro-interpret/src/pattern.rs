@@ -2,8 +2,9 @@
 
 use crate::cache::Cache;
 use crate::color::{Color, ColorSpace};
-use crate::context::{Context, InterpreterCache};
+use crate::context::{Budget, Context, InterpreterCache};
 use crate::device::Device;
+use crate::display_list::RecordedContent;
 use crate::font::Glyph;
 use crate::interpret::state::{ActiveTransferFunction, State};
 use crate::shading::Shading;
@@ -13,6 +14,7 @@ use crate::{BlendMode, CacheKey, ClipPath, DrawMode, DrawProps, Image, ImageDraw
 use crate::{FillRule, InterpreterSettings, Paint, interpret};
 use hayro_syntax::content::TypedIter;
 use hayro_syntax::object::Dict;
+use hayro_syntax::object::ObjectIdentifier;
 use hayro_syntax::object::Stream;
 use hayro_syntax::object::dict::keys::{
     BBOX, EXT_G_STATE, MATRIX, PAINT_TYPE, RESOURCES, SHADING, X_STEP, Y_STEP,
@@ -44,6 +46,7 @@ impl<'a> Pattern<'a> {
                 &dict,
                 &ctx.interpreter_cache.object_cache,
                 ctx.get().graphics_state.non_stroke_alpha,
+                &ctx.settings,
             )?)),
             Object::Stream(stream) => Some(Self::Tiling(Box::new(TilingPattern::new(
                 stream, ctx, resources,
@@ -68,6 +71,13 @@ impl<'a> Pattern<'a> {
             p.transfer_function = Some(tf);
         }
     }
+
+    pub(crate) fn set_tolerances(&mut self, smoothness: f32, flatness: f32) {
+        if let Self::Shading(p) = self {
+            p.smoothness = smoothness;
+            p.flatness = flatness;
+        }
+    }
 }
 
 impl CacheKey for Pattern<'_> {
@@ -90,14 +100,28 @@ pub struct ShadingPattern {
     pub opacity: f32,
     /// An optional transfer function to apply to the shading's output colors.
     pub transfer_function: Option<ActiveTransferFunction>,
+    /// The maximum error that is allowed when approximating the shading, as a
+    /// fraction of the range of each color component.
+    pub smoothness: f32,
+    /// The maximum distance in device pixels between the curved edges of patches
+    /// and the triangles they are approximated with.
+    pub flatness: f32,
+    /// The maximum number of times the intervals of axial and radial shadings
+    /// are halved when approximating them with gradient stops.
+    pub gradient_subdivisions: u8,
 }
 
 impl ShadingPattern {
-    pub(crate) fn new(dict: &Dict<'_>, cache: &Cache, opacity: f32) -> Option<Self> {
+    pub(crate) fn new(
+        dict: &Dict<'_>,
+        cache: &Cache,
+        opacity: f32,
+        settings: &InterpreterSettings,
+    ) -> Option<Self> {
         let shading = dict.get::<Object<'_>>(SHADING).and_then(|o| {
             let (dict, stream) = dict_or_stream(&o)?;
 
-            Shading::new(dict, stream, cache)
+            Shading::new(dict, stream, cache, settings)
         })?;
         let matrix = dict
             .get::<[f64; 6]>(MATRIX)
@@ -113,13 +137,22 @@ impl ShadingPattern {
             opacity,
             matrix,
             transfer_function: None,
+            smoothness: settings.shading_smoothness,
+            flatness: 1.0,
+            gradient_subdivisions: settings.render_quality.gradient_subdivisions,
         })
     }
 }
 
 impl CacheKey for ShadingPattern {
     fn cache_key(&self) -> u128 {
-        hash128(&(self.shading.cache_key(), self.matrix.cache_key()))
+        hash128(&(
+            self.shading.cache_key(),
+            self.matrix.cache_key(),
+            self.smoothness.to_bits(),
+            self.flatness.to_bits(),
+            self.gradient_subdivisions,
+        ))
     }
 }
 
@@ -127,7 +160,6 @@ impl CacheKey for ShadingPattern {
 #[derive(Clone)]
 pub struct TilingPattern<'a> {
     cache_key: u128,
-    ctx_bbox: Rect,
     /// The bbox of the tiling pattern.
     pub bbox: Rect,
     /// The step in the x direction.
@@ -136,15 +168,36 @@ pub struct TilingPattern<'a> {
     pub y_step: f32,
     /// A transformation to apply prior to rendering.
     pub matrix: Affine,
+    content: TilingContent<'a>,
+}
+
+#[derive(Clone)]
+enum TilingContent<'a> {
+    /// The content stream of the pattern in the PDF.
+    Stream(Box<TilingStream<'a>>),
+    /// Content that was recorded into an owned display list. Uncolored
+    /// patterns have separate content for stroking, since it depends on the
+    /// paint.
+    Recorded {
+        fill: RecordedContent,
+        stroke: Option<RecordedContent>,
+    },
+}
+
+#[derive(Clone)]
+struct TilingStream<'a> {
+    ctx_bbox: Rect,
     stream: Stream<'a>,
     is_color: bool,
-    pub(crate) stroke_paint: Color,
-    pub(crate) non_stroking_paint: Color,
-    pub(crate) parent_resources: Resources<'a>,
-    pub(crate) cache: InterpreterCache<'a>,
-    pub(crate) settings: InterpreterSettings,
-    pub(crate) xref: &'a XRef,
+    stroke_paint: Color,
+    non_stroking_paint: Color,
+    parent_resources: Resources<'a>,
+    cache: InterpreterCache<'a>,
+    settings: InterpreterSettings,
+    xref: &'a XRef,
     nesting_depth: u32,
+    x_object_stack: Vec<ObjectIdentifier>,
+    budget: Budget,
 }
 
 impl Debug for TilingPattern<'_> {
@@ -159,7 +212,6 @@ impl<'a> TilingPattern<'a> {
         ctx: &Context<'a>,
         resources: &Resources<'a>,
     ) -> Option<Self> {
-        let cache_key = stream.cache_key();
         let dict = stream.dict();
 
         let bbox = dict.get::<hayro_syntax::object::Rect>(BBOX)?.to_kurbo();
@@ -202,25 +254,71 @@ impl<'a> TilingPattern<'a> {
         );
         let nesting_depth = ctx.nesting_depth() + 1;
 
+        // The contents of uncolored patterns depend on the color they are painted
+        // with.
+        let cache_key = if is_color {
+            stream.cache_key()
+        } else {
+            hash128(&(
+                stream.cache_key(),
+                non_stroking_paint.to_rgba().components().map(f32::to_bits),
+                stroke_paint.to_rgba().components().map(f32::to_bits),
+            ))
+        };
+
         Some(Self {
             cache_key,
             bbox,
             x_step,
             y_step,
             matrix,
-            ctx_bbox,
-            is_color,
-            stream,
-            stroke_paint,
-            non_stroking_paint,
-            settings: ctx.settings.clone(),
-            parent_resources: resources.clone(),
-            cache: ctx.interpreter_cache.clone(),
-            xref: ctx.xref,
-            nesting_depth,
+            content: TilingContent::Stream(Box::new(TilingStream {
+                ctx_bbox,
+                is_color,
+                stream,
+                stroke_paint,
+                non_stroking_paint,
+                settings: ctx.settings.clone(),
+                parent_resources: resources.clone(),
+                cache: ctx.interpreter_cache.clone(),
+                xref: ctx.xref,
+                nesting_depth,
+                x_object_stack: ctx.x_object_stack().to_vec(),
+                budget: ctx.budget.clone(),
+            })),
         })
     }
 
+    /// Create a pattern whose contents are replayed from an
+    /// [`OwnedDisplayList`](crate::OwnedDisplayList).
+    pub(crate) fn recorded(
+        cache_key: u128,
+        bbox: Rect,
+        x_step: f32,
+        y_step: f32,
+        matrix: Affine,
+        fill: RecordedContent,
+        stroke: Option<RecordedContent>,
+    ) -> Self {
+        Self {
+            cache_key,
+            bbox,
+            x_step,
+            y_step,
+            matrix,
+            content: TilingContent::Recorded { fill, stroke },
+        }
+    }
+
+    /// Return whether the pattern specifies its own colors, as opposed to
+    /// being painted with the current color.
+    pub(crate) fn is_colored(&self) -> bool {
+        match &self.content {
+            TilingContent::Stream(stream) => stream.is_color,
+            TilingContent::Recorded { stroke, .. } => stroke.is_none(),
+        }
+    }
+
     /// Interpret the contents of the pattern into the given device.
     pub fn interpret(
         &self,
@@ -228,23 +326,39 @@ impl<'a> TilingPattern<'a> {
         initial_transform: Affine,
         is_stroke: bool,
     ) -> Option<()> {
+        let source = match &self.content {
+            TilingContent::Stream(stream) => stream,
+            TilingContent::Recorded { fill, stroke } => {
+                let content = if is_stroke {
+                    stroke.as_ref().unwrap_or(fill)
+                } else {
+                    fill
+                };
+                content.replay(device, initial_transform);
+
+                return Some(());
+            }
+        };
+
         let state = State::new(initial_transform);
 
         let mut context = Context::new_with(
             state.ctm,
             // TODO: bbox?
-            (initial_transform * self.ctx_bbox.to_path(0.1)).bounding_box(),
-            &self.cache,
-            self.xref,
-            self.settings.clone(),
+            (initial_transform * source.ctx_bbox.to_path(0.1)).bounding_box(),
+            &source.cache,
+            source.xref,
+            source.settings.clone(),
             state,
-            self.nesting_depth,
-        );
+            source.nesting_depth,
+        )
+        .with_budget(source.budget.clone())
+        .with_x_object_stack(source.x_object_stack.clone());
 
-        let decoded = self.stream.decoded().ok()?;
+        let decoded = source.stream.decoded().ok()?;
         let resources = Resources::from_parent(
-            self.stream.dict().get(RESOURCES).unwrap_or_default(),
-            self.parent_resources.clone(),
+            source.stream.dict().get(RESOURCES).unwrap_or_default(),
+            source.parent_resources.clone(),
         );
         let iter = TypedIter::new(decoded.as_ref());
 
@@ -254,13 +368,13 @@ impl<'a> TilingPattern<'a> {
         };
         device.push_clip_path(&clip_path);
 
-        if self.is_color {
+        if source.is_color {
             interpret(iter, &resources, &mut context, device);
         } else {
             let paint = if !is_stroke {
-                Paint::Color(self.non_stroking_paint.clone())
+                Paint::Color(source.non_stroking_paint.clone())
             } else {
-                Paint::Color(self.stroke_paint.clone())
+                Paint::Color(source.stroke_paint.clone())
             };
 
             let mut device = StencilPatternDevice::new(device, paint.clone());
@@ -309,6 +423,8 @@ impl<'a, T: Device<'a>> Device<'a> for StencilPatternDevice<'a, '_, T> {
 
     fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'_>>, _: BlendMode) {}
 
+    // Glyph runs are split into single glyphs by the default implementation of
+    // `draw_glyph_run`, so that they are painted with the color of the pattern as well.
     fn draw_glyph(
         &mut self,
         g: &Glyph<'a>,
@@ -316,6 +432,10 @@ impl<'a, T: Device<'a>> Device<'a> for StencilPatternDevice<'a, '_, T> {
         props: DrawProps<'a>,
         draw_mode: &DrawMode,
     ) {
+        let props = DrawProps {
+            paint: self.paint.clone(),
+            ..props
+        };
         self.inner.draw_glyph(g, glyph_transform, props, draw_mode);
     }
 
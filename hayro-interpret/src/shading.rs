@@ -2,11 +2,11 @@
 
 #![allow(clippy::needless_range_loop)]
 
-use crate::CacheKey;
 use crate::cache::Cache;
-use crate::color::{ColorComponents, ColorSpace};
+use crate::color::{AlphaColor, ColorComponents, ColorSpace};
 use crate::function::{Function, StitchingBounds, Values, interpolate};
-use crate::util::{Float32Ext, PointExt, RectExt};
+use crate::util::{Float32Ext, PointExt, RectExt, hash128};
+use crate::{CacheKey, InterpreterSettings, InterpreterWarning};
 use hayro_syntax::bit_reader::BitReader;
 use hayro_syntax::object::Array;
 use hayro_syntax::object::Dict;
@@ -65,6 +65,70 @@ impl ShadingFunction {
         bounds.dedup_by(|a, b| (*a - *b).abs() <= f32::EPSILON);
         bounds
     }
+
+    /// Return the input domain of the function, if it takes a single input.
+    fn input_domain(&self) -> Option<[f32; 2]> {
+        let function = match self {
+            Self::Single(function) => function,
+            Self::Multiple(functions) => functions.first()?,
+        };
+
+        match function.domain() {
+            [(min, max)] => Some([*min, *max]),
+            _ => None,
+        }
+    }
+}
+
+/// A lookup table with the precomputed colors of a shading function with a
+/// single input.
+#[derive(Debug)]
+pub(crate) struct ShadingLut {
+    domain: [f32; 2],
+    colors: Vec<AlphaColor>,
+}
+
+impl ShadingLut {
+    fn new(
+        function: &ShadingFunction,
+        color_space: &ColorSpace,
+        domain: [f32; 2],
+        size: usize,
+    ) -> Option<Self> {
+        let size = size.max(2);
+        let [min, max] = domain;
+
+        let colors = (0..size)
+            .map(|i| {
+                let t = min + (max - min) * i as f32 / (size - 1) as f32;
+                let val = function.eval(&smallvec![t])?;
+
+                Some(color_space.to_rgba(&val, 1.0, false))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { domain, colors })
+    }
+
+    /// Look up the color for the given input, interpolating linearly between
+    /// the two closest entries.
+    pub(crate) fn get(&self, t: f32) -> AlphaColor {
+        let [min, max] = self.domain;
+        let last = self.colors.len() - 1;
+
+        let pos = if (max - min).is_nearly_zero() {
+            0.0
+        } else {
+            ((t - min) / (max - min)).clamp(0.0, 1.0) * last as f32
+        };
+
+        let idx = (pos as usize).min(last - 1);
+        let frac = pos - idx as f32;
+        let c0 = self.colors[idx].components();
+        let c1 = self.colors[idx + 1].components();
+
+        AlphaColor::new(std::array::from_fn(|i| c0[i] + (c1[i] - c0[i]) * frac))
+    }
 }
 
 /// A type of shading.
@@ -135,10 +199,16 @@ pub struct Shading {
     pub clip_path: Option<BezPath>,
     /// The background color of the shading.
     pub background: Option<SmallVec<[f32; 4]>>,
+    pub(crate) lut: Option<Arc<ShadingLut>>,
 }
 
 impl Shading {
-    pub(crate) fn new(dict: &Dict<'_>, stream: Option<&Stream<'_>>, cache: &Cache) -> Option<Self> {
+    pub(crate) fn new(
+        dict: &Dict<'_>,
+        stream: Option<&Stream<'_>>,
+        cache: &Cache,
+        settings: &InterpreterSettings,
+    ) -> Option<Self> {
         let cache_key = dict.cache_key();
 
         let shading_num = dict.get::<u8>(SHADING_TYPE)?;
@@ -152,7 +222,7 @@ impl Shading {
                     .get::<[f64; 6]>(MATRIX)
                     .map(Affine::new)
                     .unwrap_or_default();
-                let function = read_function(dict, &color_space)?;
+                let function = read_function(dict, &color_space, settings)?;
 
                 ShadingType::FunctionBased {
                     domain,
@@ -162,7 +232,7 @@ impl Shading {
             }
             2 | 3 => {
                 let domain = dict.get::<[f32; 2]>(DOMAIN).unwrap_or([0.0, 1.0]);
-                let function = read_function(dict, &color_space)?;
+                let function = read_function(dict, &color_space, settings)?;
                 let extend = dict.get::<[bool; 2]>(EXTEND).unwrap_or([false, false]);
                 let (coords, invalid) = if shading_num == 2 {
                     let read = dict.get::<[f32; 4]>(COORDS)?;
@@ -197,7 +267,7 @@ impl Shading {
                 let bp_coord = dict.get::<u8>(BITS_PER_COORDINATE)?;
                 let bp_comp = dict.get::<u8>(BITS_PER_COMPONENT)?;
                 let bpf = dict.get::<u8>(BITS_PER_FLAG)?;
-                let function = read_function(dict, &color_space);
+                let function = read_function(dict, &color_space, settings);
                 let decode = dict
                     .get::<Array<'_>>(DECODE)?
                     .iter::<f32>()
@@ -222,7 +292,7 @@ impl Shading {
                 let stream_data = stream.decoded().ok()?;
                 let bp_coord = dict.get::<u8>(BITS_PER_COORDINATE)?;
                 let bp_comp = dict.get::<u8>(BITS_PER_COMPONENT)?;
-                let function = read_function(dict, &color_space);
+                let function = read_function(dict, &color_space, settings);
                 let decode = dict
                     .get::<Array<'_>>(DECODE)?
                     .iter::<f32>()
@@ -249,7 +319,7 @@ impl Shading {
                 let bp_coord = dict.get::<u8>(BITS_PER_COORDINATE)?;
                 let bp_comp = dict.get::<u8>(BITS_PER_COMPONENT)?;
                 let bpf = dict.get::<u8>(BITS_PER_FLAG)?;
-                let function = read_function(dict, &color_space);
+                let function = read_function(dict, &color_space, settings);
                 let decode = dict
                     .get::<Array<'_>>(DECODE)?
                     .iter::<f32>()
@@ -272,7 +342,7 @@ impl Shading {
                 let bp_coord = dict.get::<u8>(BITS_PER_COORDINATE)?;
                 let bp_comp = dict.get::<u8>(BITS_PER_COMPONENT)?;
                 let bpf = dict.get::<u8>(BITS_PER_FLAG)?;
-                let function = read_function(dict, &color_space);
+                let function = read_function(dict, &color_space, settings);
                 let decode = dict
                     .get::<Array<'_>>(DECODE)?
                     .iter::<f32>()
@@ -289,7 +359,19 @@ impl Shading {
 
                 ShadingType::TensorProductPatchMesh { patches, function }
             }
-            _ => return None,
+            _ => {
+                warn!("unsupported shading type {}", shading_num);
+
+                (settings.warning_sink)(
+                    InterpreterWarning::UnsupportedShading {
+                        shading: dict.obj_id(),
+                        shading_type: shading_num,
+                    }
+                    .into(),
+                );
+
+                return None;
+            }
         };
 
         let bbox = dict.get::<Rect>(BBOX).map(|r| r.to_kurbo());
@@ -297,14 +379,58 @@ impl Shading {
             .get::<Array<'_>>(BACKGROUND)
             .map(|a| a.iter::<f32>().collect::<SmallVec<_>>());
 
+        let lut = settings.shading_lut_size.and_then(|size| {
+            let (function, domain) = match &shading_type {
+                ShadingType::RadialAxial {
+                    function, domain, ..
+                } => (function, *domain),
+                ShadingType::TriangleMesh {
+                    function: Some(function),
+                    ..
+                }
+                | ShadingType::CoonsPatchMesh {
+                    function: Some(function),
+                    ..
+                }
+                | ShadingType::TensorProductPatchMesh {
+                    function: Some(function),
+                    ..
+                } => (function, function.input_domain()?),
+                _ => return None,
+            };
+
+            cache.get_or_insert_with(hash128(&(cache_key, "shading_lut", size)), || {
+                ShadingLut::new(function, &color_space, domain, size).map(Arc::new)
+            })
+        });
+
         Some(Self {
             cache_key,
             shading_type: Arc::new(shading_type),
             color_space,
             clip_path: bbox.map(|r| r.to_path(0.1)),
             background,
+            lut,
         })
     }
+
+    /// Create a shading in the `DeviceRGB` color space, for shadings that are
+    /// replayed from an [`OwnedDisplayList`](crate::OwnedDisplayList).
+    pub(crate) fn new_rgb(
+        cache_key: u128,
+        shading_type: ShadingType,
+        clip_path: Option<BezPath>,
+        background: Option<SmallVec<[f32; 4]>>,
+    ) -> Self {
+        Self {
+            cache_key,
+            shading_type: Arc::new(shading_type),
+            color_space: ColorSpace::device_rgb(),
+            clip_path,
+            background,
+            lut: None,
+        }
+    }
 }
 
 impl CacheKey for Shading {
@@ -410,6 +536,16 @@ pub struct TriangleVertex {
     pub colors: ColorComponents,
 }
 
+impl TriangleVertex {
+    pub(crate) fn new(point: Point, colors: ColorComponents) -> Self {
+        Self {
+            flag: 0,
+            point,
+            colors,
+        }
+    }
+}
+
 /// A coons patch.
 #[derive(Clone, Debug)]
 pub struct CoonsPatch {
@@ -448,9 +584,15 @@ impl CoonsPatch {
         (sc + sd - sb).to_point()
     }
 
-    /// Approximate the patch by triangles.
-    pub fn to_triangles(&self, buffer: &mut Vec<Triangle>) {
-        generate_patch_triangles(|p| self.map_coordinate(p), |p| self.interpolate(p), buffer);
+    /// Approximate the patch by triangles, using a grid with the given number of
+    /// points along each side.
+    pub fn to_triangles(&self, grid_size: usize, buffer: &mut Vec<Triangle>) {
+        generate_patch_triangles(
+            |p| self.map_coordinate(p),
+            |p| self.interpolate(p),
+            grid_size,
+            buffer,
+        );
     }
 
     /// Get the interpolated colors of the point from the patch.
@@ -532,9 +674,15 @@ impl TensorProductPatch {
         Point::new(x, y)
     }
 
-    /// Approximate the tensor product patch mesh by triangles.
-    pub fn to_triangles(&self, buffer: &mut Vec<Triangle>) {
-        generate_patch_triangles(|p| self.map_coordinate(p), |p| self.interpolate(p), buffer);
+    /// Approximate the tensor product patch mesh by triangles, using a grid with
+    /// the given number of points along each side.
+    pub fn to_triangles(&self, grid_size: usize, buffer: &mut Vec<Triangle>) {
+        generate_patch_triangles(
+            |p| self.map_coordinate(p),
+            |p| self.interpolate(p),
+            grid_size,
+            buffer,
+        );
     }
 
     /// Get the interpolated colors of the point from the patch.
@@ -584,34 +732,54 @@ fn read_free_form_triangles(
     let mut b = None;
     let mut c = None;
 
-    loop {
-        let Some(first) = read_single(&mut reader) else {
-            break;
-        };
+    // If the data is truncated or malformed, we still draw the triangles that
+    // were read successfully up to that point.
+    while let Some(first) = read_single(&mut reader) {
+        match first.flag {
+            0 => {
+                let (Some(second), Some(third)) =
+                    (read_single(&mut reader), read_single(&mut reader))
+                else {
+                    break;
+                };
 
-        if first.flag == 0 {
-            let second = read_single(&mut reader)?;
-            let third = read_single(&mut reader)?;
-
-            a = Some(first.clone());
-            b = Some(second.clone());
-            c = Some(third.clone());
-        } else if first.flag == 1 {
-            a = Some(b.clone()?);
-            b = Some(c.clone()?);
-            c = Some(first);
-        } else if first.flag == 2 {
-            b = Some(c.clone()?);
-            c = Some(first);
+                a = Some(first);
+                b = Some(second);
+                c = Some(third);
+            }
+            1 | 2 => {
+                if a.is_none() {
+                    warn!("triangle mesh continues a triangle that doesn't exist");
+
+                    break;
+                }
+
+                if first.flag == 1 {
+                    a = b.take();
+                }
+
+                b = c.take();
+                c = Some(first);
+            }
+            flag => {
+                warn!("unknown vertex flag {} in triangle mesh", flag);
+
+                break;
+            }
         }
 
-        let (p0, p1, p2) = (a.clone()?, b.clone()?, c.clone()?);
+        let (Some(p0), Some(p1), Some(p2)) = (&a, &b, &c) else {
+            break;
+        };
 
-        if p0.point.nearly_same(p1.point) || p1.point.nearly_same(p2.point) {
+        if p0.point.nearly_same(p1.point)
+            || p1.point.nearly_same(p2.point)
+            || p0.point.nearly_same(p2.point)
+        {
             continue;
         }
 
-        triangles.push(Triangle::new(a.clone()?, b.clone()?, c.clone()?));
+        triangles.push(Triangle::new(p0.clone(), p1.clone(), p2.clone()));
     }
 
     Some(triangles)
@@ -711,19 +879,23 @@ fn split_decode(decode: &[f32]) -> Option<([f32; 4], &[f32])> {
 }
 
 /// Generate triangles from a grid of points using a mapping function.
-fn generate_patch_triangles<F, I>(map_coordinate: F, interpolate: I, buffer: &mut Vec<Triangle>)
-where
+fn generate_patch_triangles<F, I>(
+    map_coordinate: F,
+    interpolate: I,
+    grid_size: usize,
+    buffer: &mut Vec<Triangle>,
+) where
     F: Fn(Point) -> Point,
     I: Fn(Point) -> ColorComponents,
 {
-    const GRID_SIZE: usize = 20;
-    let mut grid = vec![vec![Point::ZERO; GRID_SIZE]; GRID_SIZE];
+    let grid_size = grid_size.max(2);
+    let mut grid = vec![vec![Point::ZERO; grid_size]; grid_size];
 
     // Create grid by mapping unit square coordinates.
-    for i in 0..GRID_SIZE {
-        for j in 0..GRID_SIZE {
-            let u = i as f64 / (GRID_SIZE - 1) as f64; // 0.0 to 1.0 (left to right).
-            let v = j as f64 / (GRID_SIZE - 1) as f64; // 0.0 to 1.0 (top to bottom).
+    for i in 0..grid_size {
+        for j in 0..grid_size {
+            let u = i as f64 / (grid_size - 1) as f64; // 0.0 to 1.0 (left to right).
+            let v = j as f64 / (grid_size - 1) as f64; // 0.0 to 1.0 (top to bottom).
 
             // Map unit square coordinate to patch coordinate.
             let unit_point = Point::new(u, v);
@@ -731,18 +903,18 @@ where
         }
     }
 
-    for i in 0..(GRID_SIZE - 1) {
-        for j in 0..(GRID_SIZE - 1) {
+    for i in 0..(grid_size - 1) {
+        for j in 0..(grid_size - 1) {
             let p00 = grid[i][j];
             let p10 = grid[i + 1][j];
             let p01 = grid[i][j + 1];
             let p11 = grid[i + 1][j + 1];
 
             // Calculate unit square coordinates for color interpolation.
-            let u0 = i as f64 / (GRID_SIZE - 1) as f64;
-            let u1 = (i + 1) as f64 / (GRID_SIZE - 1) as f64;
-            let v0 = j as f64 / (GRID_SIZE - 1) as f64;
-            let v1 = (j + 1) as f64 / (GRID_SIZE - 1) as f64;
+            let u0 = i as f64 / (grid_size - 1) as f64;
+            let u1 = (i + 1) as f64 / (grid_size - 1) as f64;
+            let v0 = j as f64 / (grid_size - 1) as f64;
+            let v1 = (j + 1) as f64 / (grid_size - 1) as f64;
 
             // Create triangle vertices with interpolated colors.
             let v00 = TriangleVertex {
@@ -806,6 +978,12 @@ fn read_lattice_triangles(
 ) -> Option<Vec<Triangle>> {
     let mut lattices = vec![];
 
+    if vertices_per_row < 2 {
+        warn!("lattice-form mesh has fewer than two vertices per row");
+
+        return None;
+    }
+
     let ([x_min, x_max, y_min, y_max], decode) = split_decode(decode)?;
     let mut reader = BitReader::new(data);
     let helpers = InterpolationHelpers::new(bp_cord, bp_comp, x_min, x_max, y_min, y_max);
@@ -911,26 +1089,33 @@ where
     let mut prev_patch_colors: Option<[ColorComponents; 4]> = None;
     let mut patches = vec![];
 
-    while let Some(flag) = reader.read(bpf) {
+    // If the data is truncated or malformed, we still draw the patches that
+    // were read successfully up to that point.
+    let mut read_patch = |reader: &mut BitReader<'_>, flag: u32| -> Option<P> {
         let mut control_points = vec![Point::ZERO; 16]; // Always allocate 16, use subset as needed.
         let mut colors = [smallvec![], smallvec![], smallvec![], smallvec![]];
 
         match flag {
             0 => {
                 for i in 0..control_points_count {
-                    control_points[i] = helpers.read_point(&mut reader)?;
+                    control_points[i] = helpers.read_point(reader)?;
                 }
 
                 for i in 0..4 {
-                    colors[i] = read_colors(&mut reader)?;
+                    colors[i] = read_colors(reader)?;
                 }
 
                 prev_patch_points = Some(control_points.clone());
                 prev_patch_colors = Some(colors.clone());
             }
             1..=3 => {
-                let prev_points = prev_patch_points.as_ref()?;
-                let prev_colors = prev_patch_colors.as_ref()?;
+                let (Some(prev_points), Some(prev_colors)) =
+                    (prev_patch_points.as_ref(), prev_patch_colors.as_ref())
+                else {
+                    warn!("patch mesh continues a patch that doesn't exist");
+
+                    return None;
+                };
 
                 copy_patch_control_points(flag, prev_points, &mut control_points);
 
@@ -951,16 +1136,16 @@ where
                 }
 
                 for i in 4..control_points_count {
-                    control_points[i] = helpers.read_point(&mut reader)?;
+                    control_points[i] = helpers.read_point(reader)?;
                 }
 
-                colors[2] = read_colors(&mut reader)?;
-                colors[3] = read_colors(&mut reader)?;
+                colors[2] = read_colors(reader)?;
+                colors[3] = read_colors(reader)?;
 
                 prev_patch_points = Some(control_points.clone());
                 prev_patch_colors = Some(colors.clone());
             }
-            _ => break,
+            _ => return None,
         }
 
         let mut fixed_points = [Point::ZERO; 16];
@@ -970,8 +1155,17 @@ where
             }
         }
 
-        patches.push(create_patch(fixed_points, colors));
+        Some(create_patch(fixed_points, colors))
+    };
+
+    while let Some(flag) = reader.read(bpf) {
+        let Some(patch) = read_patch(&mut reader, flag) else {
+            break;
+        };
+
+        patches.push(patch);
     }
+
     Some(patches)
 }
 
@@ -1026,7 +1220,26 @@ fn read_tensor_product_patch_mesh(
     )
 }
 
-fn read_function(dict: &Dict<'_>, color_space: &ColorSpace) -> Option<ShadingFunction> {
+fn read_function(
+    dict: &Dict<'_>,
+    color_space: &ColorSpace,
+    settings: &InterpreterSettings,
+) -> Option<ShadingFunction> {
+    let function = read_function_impl(dict, color_space);
+
+    if function.is_none() && dict.contains_key(FUNCTION) {
+        (settings.warning_sink)(
+            InterpreterWarning::InvalidFunction {
+                function: dict.get_ref(FUNCTION).map(Into::into),
+            }
+            .into(),
+        );
+    }
+
+    function
+}
+
+fn read_function_impl(dict: &Dict<'_>, color_space: &ColorSpace) -> Option<ShadingFunction> {
     if let Some(arr) = dict.get::<Array<'_>>(FUNCTION) {
         let arr: Option<SmallVec<_>> = arr
             .iter::<Object<'_>>()
@@ -1,6 +1,7 @@
 use crate::color::{Color, ColorComponents, ColorSpace};
-use crate::context::{Context, InterpreterCache};
+use crate::context::{Budget, Context, InterpreterCache};
 use crate::device::Device;
+use crate::display_list::RecordedContent;
 use crate::function::Function;
 use crate::interpret::state::State;
 use crate::util::hash128;
@@ -22,6 +23,7 @@ use std::rc::Rc;
 
 /// Type type of mask.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MaskType {
     /// A luminosity mask.
     Luminosity,
@@ -30,42 +32,89 @@ pub enum MaskType {
 }
 
 /// A transfer function to apply to the opacity values of a mask.
-pub struct TransferFunction(Function);
+pub struct TransferFunction(TransferFunctionRepr);
+
+enum TransferFunctionRepr {
+    Function(Function),
+    /// Equidistant samples of a function, for masks that are replayed from an
+    /// [`OwnedDisplayList`](crate::OwnedDisplayList).
+    Sampled(Vec<f32>),
+}
 
 impl TransferFunction {
+    /// The number of samples that are taken when sampling a transfer function.
+    const SAMPLES: usize = 256;
+
+    pub(crate) fn from_samples(samples: Vec<f32>) -> Option<Self> {
+        (samples.len() >= 2).then_some(Self(TransferFunctionRepr::Sampled(samples)))
+    }
+
     /// Apply the transfer function to the given value.
     ///
     /// The input value needs to be between 0 and 1 and the return value is
     /// guaranteed to be between 0 and 1.
     #[inline]
     pub fn apply(&self, val: f32) -> f32 {
-        self.0
-            .eval(smallvec![val])
-            .and_then(|v| v.first().copied())
-            .unwrap_or(0.0)
-            .clamp(0.0, 1.0)
+        match &self.0 {
+            TransferFunctionRepr::Function(function) => function
+                .eval(smallvec![val])
+                .and_then(|v| v.first().copied())
+                .unwrap_or(0.0)
+                .clamp(0.0, 1.0),
+            TransferFunctionRepr::Sampled(samples) => {
+                let pos = val.clamp(0.0, 1.0) * (samples.len() - 1) as f32;
+                let idx = (pos as usize).min(samples.len() - 2);
+                let frac = pos - idx as f32;
+
+                (samples[idx] + (samples[idx + 1] - samples[idx]) * frac).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// Sample the transfer function at equidistant positions between 0 and 1.
+    pub(crate) fn samples(&self) -> Vec<f32> {
+        (0..Self::SAMPLES)
+            .map(|i| self.apply(i as f32 / (Self::SAMPLES - 1) as f32))
+            .collect()
     }
 }
 
 struct Repr<'a> {
     obj_id: ObjectIdentifier,
-    group: FormXObject<'a>,
     mask_type: MaskType,
+    transfer_function: Option<TransferFunction>,
+    background: Color,
+    content: MaskContent<'a>,
+}
+
+enum MaskContent<'a> {
+    /// The transparency group of the mask in the PDF.
+    Group(Box<MaskGroup<'a>>),
+    /// Content that was recorded into an owned display list.
+    Recorded(RecordedContent),
+}
+
+struct MaskGroup<'a> {
+    group: FormXObject<'a>,
     parent_resources: Resources<'a>,
     root_transform: Affine,
     bbox: kurbo::Rect,
     interpreter_cache: InterpreterCache<'a>,
-    transfer_function: Option<TransferFunction>,
     settings: InterpreterSettings,
-    background: Color,
     xref: &'a XRef,
     nesting_depth: u32,
+    x_object_stack: Vec<ObjectIdentifier>,
+    budget: Budget,
 }
 
 impl Hash for Repr<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.obj_id.hash(state);
-        self.root_transform.cache_key().hash(state);
+
+        match &self.content {
+            MaskContent::Group(group) => group.root_transform.cache_key().hash(state),
+            MaskContent::Recorded(content) => content.cache_key().hash(state),
+        }
     }
 }
 
@@ -103,20 +152,32 @@ impl<'a> SoftMask<'a> {
         // same xobject, the ID will be the same.
         let obj_id = dict.get_ref(G)?.into();
         let group_stream = dict.get::<Stream<'_>>(G)?;
-        let group = FormXObject::new(&group_stream)?;
-        let cs = ColorSpace::new(
-            group.dict.get::<Dict<'_>>(GROUP)?.get::<Object<'_>>(CS)?,
-            &context.interpreter_cache.object_cache,
-        )?;
+        let group = FormXObject::new(&group_stream, context)?;
+        let cs = group
+            .dict
+            .get::<Dict<'_>>(GROUP)
+            .and_then(|g| g.get::<Object<'_>>(CS))
+            .and_then(|o| ColorSpace::new(o, &context.interpreter_cache.object_cache));
         let transfer_function = dict
             .get::<Object<'_>>(TR)
             .and_then(|o| Function::new(&o))
-            .map(TransferFunction);
+            .map(|f| TransferFunction(TransferFunctionRepr::Function(f)));
         let (mask_type, background) = match dict.get::<Name<'_>>(S)?.deref() {
             LUMINOSITY => {
+                // The color space of the group is required for luminosity masks,
+                // but it is only needed to interpret the backdrop color. If it's
+                // missing, guess it from the number of components.
                 let color = dict
                     .get::<ColorComponents>(BC)
-                    .map(|c| Color::new(cs, c, 1.0))
+                    .map(|c| {
+                        let cs = cs.unwrap_or_else(|| match c.len() {
+                            3 => ColorSpace::device_rgb(),
+                            4 => ColorSpace::device_cmyk(),
+                            _ => ColorSpace::device_gray(),
+                        });
+
+                        Color::new(cs, c, 1.0)
+                    })
                     .unwrap_or(Color::new(ColorSpace::device_gray(), smallvec![0.0], 1.0));
 
                 (MaskType::Luminosity, color)
@@ -132,33 +193,75 @@ impl<'a> SoftMask<'a> {
 
         Some(Self(Rc::new(Repr {
             obj_id,
-            group,
             mask_type,
-            root_transform: context.get().ctm,
             transfer_function,
-            bbox: context.bbox(),
-            interpreter_cache: context.interpreter_cache.clone(),
-            settings: context.settings.clone(),
-            xref: context.xref,
             background,
-            parent_resources,
-            nesting_depth,
+            content: MaskContent::Group(Box::new(MaskGroup {
+                group,
+                parent_resources,
+                root_transform: context.get().ctm,
+                bbox: context.bbox(),
+                interpreter_cache: context.interpreter_cache.clone(),
+                settings: context.settings.clone(),
+                xref: context.xref,
+                nesting_depth,
+                x_object_stack: context.x_object_stack().to_vec(),
+                budget: context.budget.clone(),
+            })),
         })))
     }
 
+    /// Create a mask whose contents are replayed from an
+    /// [`OwnedDisplayList`](crate::OwnedDisplayList).
+    pub(crate) fn recorded(
+        obj_id: ObjectIdentifier,
+        mask_type: MaskType,
+        transfer_function: Option<TransferFunction>,
+        background: Color,
+        content: RecordedContent,
+    ) -> Self {
+        Self(Rc::new(Repr {
+            obj_id,
+            mask_type,
+            transfer_function,
+            background,
+            content: MaskContent::Recorded(content),
+        }))
+    }
+
     /// Interpret the contents of the mask into the given device.
     pub fn interpret(&self, device: &mut impl Device<'a>) {
-        let state = State::new(self.0.root_transform);
+        self.interpret_with_transform(device, Affine::IDENTITY);
+    }
+
+    /// Interpret the contents of the mask into the given device, applying an
+    /// additional transform in device space.
+    ///
+    /// This can be used to render the mask at a different resolution than the
+    /// page.
+    pub fn interpret_with_transform(&self, device: &mut impl Device<'a>, transform: Affine) {
+        let group = match &self.0.content {
+            MaskContent::Group(group) => group,
+            MaskContent::Recorded(content) => {
+                content.replay(device, transform);
+                return;
+            }
+        };
+
+        let root_transform = transform * group.root_transform;
+        let state = State::new(root_transform);
         let mut ctx = Context::new_with(
-            self.0.root_transform,
-            self.0.bbox,
-            &self.0.interpreter_cache,
-            self.0.xref,
-            self.0.settings.clone(),
+            root_transform,
+            transform.transform_rect_bbox(group.bbox),
+            &group.interpreter_cache,
+            group.xref,
+            group.settings.clone(),
             state,
-            self.0.nesting_depth,
-        );
-        draw_form_xobject(&self.0.parent_resources, &self.0.group, &mut ctx, device);
+            group.nesting_depth,
+        )
+        .with_budget(group.budget.clone())
+        .with_x_object_stack(group.x_object_stack.clone());
+        draw_form_xobject(&group.parent_resources, &group.group, &mut ctx, device);
     }
 
     /// Return the object identifier of the mask.
@@ -0,0 +1,125 @@
+//! Collecting statistics about the interpretation of a page.
+
+use crate::util::now;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Statistics about the interpretation of a page.
+///
+/// Collecting them is opt-in via
+/// [`InterpreterSettings::collect_stats`](crate::InterpreterSettings::collect_stats),
+/// and they can then be queried with [`Context::stats`](crate::Context::stats).
+/// The statistics include the content streams of form `XObject`s, patterns,
+/// soft masks and type3 glyphs.
+#[derive(Clone, Debug, Default)]
+pub struct InterpreterStats {
+    /// How often each operator was interpreted, keyed by its name, for example `Tj`.
+    pub operators: BTreeMap<String, u64>,
+    /// The number of glyphs that were passed to the device.
+    pub glyphs: u64,
+    /// The number of images that were passed to the device.
+    pub images: u64,
+    /// The number of shadings that were painted, either with the `sh` operator
+    /// or as the paint of a path.
+    pub shadings: u64,
+    /// The time spent in the different phases of the interpretation.
+    pub timings: PhaseTimings,
+}
+
+/// The time spent in the different phases of interpreting a page.
+///
+/// The phases include the time that the device takes to process the drawing
+/// calls. If phases are nested, for example because a type3 glyph draws an
+/// image, the time is only attributed to the outermost one. On `wasm32`, the
+/// time can't be measured and all timings are zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhaseTimings {
+    /// The time spent interpreting the whole page, including annotations.
+    pub total: Duration,
+    /// The time spent filling and stroking paths.
+    pub paths: Duration,
+    /// The time spent showing text.
+    pub text: Duration,
+    /// The time spent drawing images.
+    pub images: Duration,
+    /// The time spent painting shadings with the `sh` operator.
+    pub shadings: Duration,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Phase {
+    Paths,
+    Text,
+    Images,
+    Shadings,
+}
+
+#[derive(Default)]
+pub(crate) struct StatsCollector {
+    stats: RefCell<InterpreterStats>,
+    in_phase: Cell<bool>,
+}
+
+impl StatsCollector {
+    pub(crate) fn stats(&self) -> InterpreterStats {
+        self.stats.borrow().clone()
+    }
+
+    pub(crate) fn record_operator(&self, operator: &[u8]) {
+        let mut stats = self.stats.borrow_mut();
+
+        // Avoid allocating a new key for operators that have been seen before.
+        if let Some(count) = std::str::from_utf8(operator)
+            .ok()
+            .and_then(|o| stats.operators.get_mut(o))
+        {
+            *count += 1;
+        } else {
+            *stats
+                .operators
+                .entry(String::from_utf8_lossy(operator).into_owned())
+                .or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_glyphs(&self, count: usize) {
+        self.stats.borrow_mut().glyphs += count as u64;
+    }
+
+    pub(crate) fn record_image(&self) {
+        self.stats.borrow_mut().images += 1;
+    }
+
+    pub(crate) fn record_shading(&self) {
+        self.stats.borrow_mut().shadings += 1;
+    }
+
+    pub(crate) fn record_total(&self, duration: Duration) {
+        self.stats.borrow_mut().timings.total += duration;
+    }
+
+    /// Run `f` and attribute the time it takes to the given phase, unless
+    /// another phase is already being measured.
+    pub(crate) fn measure<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        if self.in_phase.replace(true) {
+            return f();
+        }
+
+        let start = now();
+        let result = f();
+        let elapsed = start.map(|start| start.elapsed()).unwrap_or_default();
+        self.in_phase.set(false);
+
+        let timings = &mut self.stats.borrow_mut().timings;
+        let duration = match phase {
+            Phase::Paths => &mut timings.paths,
+            Phase::Text => &mut timings.text,
+            Phase::Images => &mut timings.images,
+            Phase::Shadings => &mut timings.shadings,
+        };
+        *duration += elapsed;
+
+        result
+    }
+}
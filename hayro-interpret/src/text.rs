@@ -0,0 +1,261 @@
+//! Extracting text from PDF content streams.
+//!
+//! The [`TextDevice`] can be passed to [`interpret_page`](crate::interpret_page)
+//! instead of a painting device. It records the decoded text of each glyph together
+//! with its position, without rasterizing anything.
+
+use crate::font::Glyph;
+use crate::util::AffineExt;
+use crate::{
+    BlendMode, CacheKey, ClipPath, Device, DrawMode, DrawProps, Image, ImageDrawProps,
+    MarkedContent, SoftMask,
+};
+use kurbo::{Affine, BezPath, Point, Vec2};
+
+/// A glyph collected by a [`TextDevice`].
+#[derive(Clone, Debug)]
+pub struct TextGlyph {
+    /// The Unicode text of the glyph, or an empty string if it couldn't be determined.
+    pub text: String,
+    /// The character code that was used to select the glyph.
+    pub char_code: u32,
+    /// The transform from glyph space (assuming an upem value of 1000) to user space.
+    pub glyph_transform: Affine,
+    /// The position of the glyph in user space.
+    ///
+    /// For horizontal text, this is the origin of the glyph. For vertical text, glyphs
+    /// are positioned by their top center instead.
+    pub origin: Point,
+    /// The advance of the glyph in user space, if known.
+    pub advance: Option<Vec2>,
+}
+
+/// A sequence of glyphs that were drawn with the same font, font size, orientation and
+/// transform, along a common baseline.
+#[derive(Clone, Debug)]
+pub struct TextRun {
+    /// The transform from user space to device space that was active when the run was
+    /// drawn.
+    pub transform: Affine,
+    /// The font size in user space, taking the text matrix into account.
+    pub font_size: f32,
+    /// The cache key of the font that was used.
+    pub font: u128,
+    /// Whether the text is invisible, which for example is the case for the OCR layer
+    /// of scanned documents.
+    pub invisible: bool,
+    /// Whether the text was drawn in a vertical writing mode, in which case the glyphs
+    /// advance from top to bottom.
+    pub vertical: bool,
+    /// The glyphs of the run.
+    pub glyphs: Vec<TextGlyph>,
+}
+
+impl TextRun {
+    /// Return the text of the run.
+    pub fn text(&self) -> String {
+        self.glyphs.iter().map(|g| g.text.as_str()).collect()
+    }
+
+    /// Return the origin of the first glyph of the run in user space.
+    pub fn origin(&self) -> Point {
+        self.glyphs.first().map(|g| g.origin).unwrap_or_default()
+    }
+
+    fn accepts(&self, other: &Self) -> bool {
+        let (Some(last), Some(next)) = (self.glyphs.last(), other.glyphs.first()) else {
+            return false;
+        };
+
+        if self.font != other.font
+            || self.invisible != other.invisible
+            || self.vertical != other.vertical
+            || !self.transform.approx_eq(other.transform)
+            || !last
+                .glyph_transform
+                .linear()
+                .approx_eq(next.glyph_transform.linear())
+        {
+            return false;
+        }
+
+        // The new glyph needs to sit on the same baseline and must not move backwards.
+        let Some(inverse) = last.glyph_transform.linear().checked_inverse() else {
+            return false;
+        };
+        let offset = inverse * (next.origin - last.origin).to_point();
+
+        if self.vertical {
+            offset.x.abs() <= 1.0 && offset.y <= 0.0
+        } else {
+            offset.y.abs() <= 1.0 && offset.x >= 0.0
+        }
+    }
+}
+
+/// A device that collects the text of a page.
+///
+/// Consecutive glyphs are merged into [`TextRun`]s. Paths, images and the contents of
+/// type3 glyphs are ignored.
+///
+/// If a marked content sequence specifies an `/ActualText`, that text is assigned to the
+/// first glyph of the sequence, and the remaining glyphs get an empty text.
+#[derive(Default)]
+pub struct TextDevice {
+    runs: Vec<TextRun>,
+    alt_texts: Vec<String>,
+    /// The nesting level of marked content sequences.
+    depth: usize,
+    /// The nesting level of the active `/ActualText` sequence and its text, if it
+    /// hasn't been assigned to a glyph yet.
+    actual_text: Option<(usize, Option<String>)>,
+}
+
+impl TextDevice {
+    /// Create a new, empty text device.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the runs that have been collected so far.
+    pub fn runs(&self) -> &[TextRun] {
+        &self.runs
+    }
+
+    /// Consume the device and return the collected runs.
+    pub fn into_runs(self) -> Vec<TextRun> {
+        self.runs
+    }
+
+    /// Return the alternate descriptions (`/Alt`) of marked content sequences, such as
+    /// figures, in the order they appear in the content stream.
+    pub fn alt_texts(&self) -> &[String] {
+        &self.alt_texts
+    }
+
+    /// Return the collected text, with one line per run.
+    pub fn text(&self) -> String {
+        self.runs
+            .iter()
+            .map(TextRun::text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn push(&mut self, run: TextRun) {
+        if let Some(last) = self.runs.last_mut() {
+            let glyph = &run.glyphs[0];
+
+            // When filling and stroking text, each glyph is drawn twice.
+            if last.glyphs.last().is_some_and(|g| {
+                g.char_code == glyph.char_code && g.glyph_transform == glyph.glyph_transform
+            }) && last.transform == run.transform
+            {
+                return;
+            }
+
+            if last.accepts(&run) {
+                last.glyphs.extend(run.glyphs);
+
+                return;
+            }
+        }
+
+        self.runs.push(run);
+    }
+}
+
+impl<'a> Device<'a> for TextDevice {
+    fn draw_path(&mut self, _: &BezPath, _: DrawProps<'a>, _: &DrawMode) {}
+
+    fn push_clip_path(&mut self, _: &ClipPath) {}
+
+    fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'a>>, _: BlendMode) {}
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let (font, advance, displacement, vertical) = match glyph {
+            Glyph::Outline(o) => match o.vertical_advance() {
+                Some(advance) => (
+                    o.font_cache_key(),
+                    Some(Vec2::new(0.0, advance as f64)),
+                    o.origin_displacement(),
+                    true,
+                ),
+                None => (
+                    o.font_cache_key(),
+                    o.advance_width().map(|a| Vec2::new(a as f64, 0.0)),
+                    Vec2::ZERO,
+                    false,
+                ),
+            },
+            Glyph::Type3(t) => (t.font.cache_key(), None, Vec2::ZERO, false),
+        };
+
+        let text = match &mut self.actual_text {
+            Some((_, text)) => text.take().unwrap_or_default(),
+            None => glyph
+                .as_unicode()
+                .map(|u| u.to_string())
+                .unwrap_or_default(),
+        };
+
+        let glyph = TextGlyph {
+            text,
+            char_code: glyph.char_code(),
+            glyph_transform,
+            origin: glyph_transform * (-displacement).to_point(),
+            advance: advance.map(|a| (glyph_transform.linear() * a.to_point()).to_vec2()),
+        };
+
+        let [_, _, c, d, _, _] = glyph_transform.as_coeffs();
+
+        self.push(TextRun {
+            transform: props.transform,
+            font_size: (Vec2::new(c, d).length() * 1000.0) as f32,
+            font,
+            invisible: matches!(draw_mode, DrawMode::Invisible),
+            vertical,
+            glyphs: vec![glyph],
+        });
+    }
+
+    fn draw_image(&mut self, _: Image<'a, '_>, _: ImageDrawProps<'a>) {}
+
+    fn pop_clip(&mut self) {}
+
+    fn pop_transparency_group(&mut self) {}
+
+    fn begin_marked_content(&mut self, marked_content: &MarkedContent<'_>) {
+        self.depth += 1;
+
+        if let Some(alt) = &marked_content.alt {
+            self.alt_texts.push(alt.clone());
+        }
+
+        // Nested sequences are already covered by the outer replacement text.
+        if self.actual_text.is_none() {
+            self.actual_text = marked_content
+                .actual_text
+                .clone()
+                .map(|text| (self.depth, Some(text)));
+        }
+    }
+
+    fn end_marked_content(&mut self) {
+        if self
+            .actual_text
+            .as_ref()
+            .is_some_and(|(depth, _)| *depth == self.depth)
+        {
+            self.actual_text = None;
+        }
+
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
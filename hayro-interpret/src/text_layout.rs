@@ -0,0 +1,503 @@
+//! Grouping extracted text into words, lines and blocks.
+//!
+//! The runs collected by a [`TextDevice`](crate::text::TextDevice) are in content
+//! stream order, which often doesn't match the reading order of a page. [`TextLayout`]
+//! uses the baselines and the gaps between glyphs to reconstruct words, lines and
+//! blocks (paragraphs or columns), and sorts them from top to bottom.
+//!
+//! Text in a vertical writing mode is handled the same way, except that the lines run
+//! from top to bottom and are ordered from right to left.
+//!
+//! All coordinates are in the coordinate system the runs were transformed into (i.e.
+//! the default user space of the page, if the context was created with an identity
+//! transform), with the y-axis pointing up. Use [`TextLayout::transform`] to convert
+//! them into a different coordinate system, like the device space of a viewer.
+
+use crate::text::TextRun;
+use kurbo::{Affine, Point, Rect, Vec2};
+
+/// The fraction of the font size a gap between two glyphs needs to have to be treated
+/// as a word break.
+const WORD_GAP: f64 = 0.15;
+/// The maximum gap between two glyphs on the same baseline, relative to the font size,
+/// for them to still be considered part of the same line.
+const LINE_GAP: f64 = 1.5;
+/// The maximum baseline distance between two lines, relative to the font size, for
+/// them to be considered part of the same block.
+const BLOCK_LINE_SPACING: f64 = 2.0;
+/// The ascent and descent used to approximate the bounding box of a glyph.
+const ASCENT: f64 = 0.8;
+const DESCENT: f64 = 0.2;
+
+/// A word.
+#[derive(Clone, Debug)]
+pub struct TextWord {
+    /// The text of the word.
+    pub text: String,
+    /// The bounding box of the word.
+    pub bbox: Rect,
+    /// The characters of the word, in reading order.
+    pub chars: Vec<TextChar>,
+}
+
+/// A character, i.e. the text of a single glyph.
+#[derive(Clone, Debug)]
+pub struct TextChar {
+    /// The text of the glyph. Usually this is a single character, but it can also be
+    /// empty or consist of several characters (for example for ligatures).
+    pub text: String,
+    /// The corners of the glyph box, in the order bottom left, bottom right, top right
+    /// and top left, relative to the writing direction.
+    ///
+    /// Unlike a bounding box, the quad follows the rotation and skew of the text.
+    pub quad: [Point; 4],
+}
+
+/// A line of words that share a common baseline.
+///
+/// For vertical text, this is a single column of text.
+#[derive(Clone, Debug)]
+pub struct TextLine {
+    /// The words of the line, in reading order.
+    pub words: Vec<TextWord>,
+    /// The bounding box of the line.
+    pub bbox: Rect,
+}
+
+impl TextLine {
+    /// Return the text of the line, with the words separated by spaces.
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(feature = "bidi")]
+impl TextLine {
+    /// Reorder the words of the line and the characters of each word from visual into
+    /// logical order, using the Unicode Bidirectional Algorithm.
+    ///
+    /// Lines are assembled from left to right, which reverses the text of right-to-left
+    /// scripts like Arabic or Hebrew. The base direction of the line is the direction
+    /// of the majority of its strongly directional characters. Lines without any
+    /// right-to-left characters are left unchanged.
+    pub fn reorder_bidi(&mut self) {
+        use unicode_bidi::{BidiClass, Level, ParagraphBidiInfo, bidi_class};
+
+        let text = self.text();
+        let (mut ltr, mut rtl) = (0, 0);
+
+        for c in text.chars() {
+            match bidi_class(c) {
+                BidiClass::L => ltr += 1,
+                BidiClass::R | BidiClass::AL => rtl += 1,
+                _ => {}
+            }
+        }
+
+        if rtl == 0 {
+            return;
+        }
+
+        let base_level = if rtl > ltr {
+            Level::rtl()
+        } else {
+            Level::ltr()
+        };
+        let info = ParagraphBidiInfo::new(&text, Some(base_level));
+
+        // Split the line into units that are either a glyph or the space between two
+        // words, and determine the embedding level of each unit.
+        let mut units = Vec::new();
+        let mut levels = Vec::new();
+        let mut offset = 0;
+
+        for word in std::mem::take(&mut self.words) {
+            if !units.is_empty() {
+                levels.push(info.levels[offset]);
+                units.push(None);
+                offset += 1;
+            }
+
+            for c in word.chars {
+                levels.push(info.levels.get(offset).copied().unwrap_or(base_level));
+                offset += c.text.len();
+                units.push(Some(c));
+            }
+        }
+
+        let mut cur = None;
+
+        for idx in ParagraphBidiInfo::reorder_visual(&levels) {
+            match units[idx].take() {
+                Some(c) => push_char(&mut cur, c),
+                None => self.words.extend(cur.take()),
+            }
+        }
+
+        self.words.extend(cur);
+    }
+}
+
+/// A block of consecutive lines, such as a paragraph or a column.
+#[derive(Clone, Debug)]
+pub struct TextBlock {
+    /// The lines of the block, from top to bottom.
+    pub lines: Vec<TextLine>,
+    /// The bounding box of the block.
+    pub bbox: Rect,
+}
+
+impl TextBlock {
+    /// Return the text of the block, with one line per line of text.
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(TextLine::text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The result of a layout analysis of the text of a page.
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    /// The blocks of the page, in reading order.
+    pub blocks: Vec<TextBlock>,
+}
+
+impl TextLayout {
+    /// Analyze the given text runs.
+    pub fn new(runs: &[TextRun]) -> Self {
+        let chars = runs.iter().flat_map(chars).collect::<Vec<_>>();
+        let lines = group_lines(chars)
+            .into_iter()
+            .filter_map(LineBuilder::finish)
+            .collect();
+
+        Self {
+            blocks: group_blocks(lines),
+        }
+    }
+
+    /// Reorder the text of all lines from visual into logical order.
+    ///
+    /// See [`TextLine::reorder_bidi`] for more information.
+    #[cfg(feature = "bidi")]
+    pub fn reorder_bidi(&mut self) {
+        for line in self.blocks.iter_mut().flat_map(|b| &mut b.lines) {
+            line.reorder_bidi();
+        }
+    }
+
+    /// Transform all coordinates of the layout.
+    ///
+    /// This can for example be used to convert the layout into device space, using
+    /// [`Page::initial_transform`](hayro_syntax::page::Page::initial_transform).
+    /// Bounding boxes are replaced by the bounding boxes of the transformed boxes.
+    pub fn transform(&mut self, transform: Affine) {
+        for block in &mut self.blocks {
+            block.bbox = transform.transform_rect_bbox(block.bbox);
+
+            for line in &mut block.lines {
+                line.bbox = transform.transform_rect_bbox(line.bbox);
+
+                for word in &mut line.words {
+                    word.bbox = transform.transform_rect_bbox(word.bbox);
+
+                    for c in &mut word.chars {
+                        c.quad = c.quad.map(|p| transform * p);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return the text of the page, with blocks separated by an empty line.
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(TextBlock::text)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[derive(Debug)]
+struct Char {
+    text: String,
+    /// The unit vector of the writing direction.
+    dir: Vec2,
+    /// The position of the origin along the writing direction.
+    along: f64,
+    /// The position of the baseline perpendicular to the writing direction.
+    normal: f64,
+    width: f64,
+    size: f64,
+    quad: [Point; 4],
+}
+
+impl Char {
+    fn is_whitespace(&self) -> bool {
+        !self.text.is_empty() && self.text.chars().all(char::is_whitespace)
+    }
+
+    fn end(&self) -> f64 {
+        self.along + self.width
+    }
+}
+
+fn chars(run: &TextRun) -> impl Iterator<Item = Char> + '_ {
+    run.glyphs.iter().filter_map(|glyph| {
+        let transform = run.transform * glyph.glyph_transform;
+        let origin = run.transform * glyph.origin;
+        let x_axis = (transform * Point::new(1.0, 0.0)) - transform * Point::ZERO;
+        let y_axis = (transform * Point::new(0.0, 1.0)) - transform * Point::ZERO;
+        let size = y_axis.length() * 1000.0;
+
+        if x_axis.length() == 0.0 || size == 0.0 {
+            return None;
+        }
+
+        // Use the axes of the glyph instead of `up` so that skewed text gets skewed
+        // boxes.
+        let (dir, bottom_left, top_left) = if run.vertical {
+            // Vertical glyphs advance downwards and are centered on their position.
+            let em = x_axis * 1000.0;
+            (-y_axis.normalize(), origin - em * 0.5, origin + em * 0.5)
+        } else {
+            let em = y_axis * 1000.0;
+            (
+                x_axis.normalize(),
+                origin - em * DESCENT,
+                origin + em * ASCENT,
+            )
+        };
+        let up = normal(dir);
+        let width = glyph
+            .advance
+            .map(|a| (run.transform * a.to_point() - run.transform * Point::ZERO).length())
+            .unwrap_or(size * 0.5);
+
+        let quad = [
+            bottom_left,
+            bottom_left + dir * width,
+            top_left + dir * width,
+            top_left,
+        ];
+
+        Some(Char {
+            text: glyph.text.clone(),
+            dir,
+            along: origin.to_vec2().dot(dir),
+            normal: origin.to_vec2().dot(up),
+            width,
+            size,
+            quad,
+        })
+    })
+}
+
+/// Append a character to the current word, starting a new word if there is none.
+fn push_char(word: &mut Option<TextWord>, c: TextChar) {
+    let bbox = quad_bbox(&c.quad);
+    let word = word.get_or_insert_with(|| TextWord {
+        text: String::new(),
+        bbox,
+        chars: Vec::new(),
+    });
+
+    word.text.push_str(&c.text);
+    word.bbox = word.bbox.union(bbox);
+    word.chars.push(c);
+}
+
+pub(crate) fn quad_bbox(quad: &[Point; 4]) -> Rect {
+    quad[1..]
+        .iter()
+        .fold(Rect::from_points(quad[0], quad[0]), |r, p| r.union_pt(*p))
+}
+
+/// The direction that points "up", i.e. perpendicular to the writing direction.
+///
+/// For vertical text that is written downwards, this points to the right.
+fn normal(dir: Vec2) -> Vec2 {
+    Vec2::new(-dir.y, dir.x)
+}
+
+struct LineBuilder {
+    dir: Vec2,
+    normal: f64,
+    size: f64,
+    start: f64,
+    end: f64,
+    chars: Vec<Char>,
+}
+
+impl LineBuilder {
+    fn accepts(&self, c: &Char) -> bool {
+        let size = self.size.min(c.size);
+        let gap = LINE_GAP * size;
+
+        self.dir.dot(c.dir) > 0.99
+            && (self.normal - c.normal).abs() < 0.5 * size
+            && c.along >= self.start - gap
+            && c.along <= self.end + gap
+    }
+
+    fn finish(mut self) -> Option<Line> {
+        self.chars.sort_by(|a, b| a.along.total_cmp(&b.along));
+
+        let mut words = Vec::new();
+        let mut cur = None;
+        let mut prev_end = f64::NEG_INFINITY;
+
+        for c in self.chars {
+            if c.is_whitespace() {
+                words.extend(cur.take());
+            } else {
+                if c.along - prev_end > WORD_GAP * c.size {
+                    words.extend(cur.take());
+                }
+
+                push_char(
+                    &mut cur,
+                    TextChar {
+                        text: c.text,
+                        quad: c.quad,
+                    },
+                );
+            }
+
+            prev_end = prev_end.max(c.along + c.width);
+        }
+
+        words.extend(cur);
+
+        let bbox = words.iter().map(|w| w.bbox).reduce(|a, b| a.union(b))?;
+
+        Some(Line {
+            inner: TextLine { words, bbox },
+            dir: self.dir,
+            normal: self.normal,
+            size: self.size,
+            start: self.start,
+            end: self.end,
+        })
+    }
+}
+
+fn group_lines(chars: Vec<Char>) -> Vec<LineBuilder> {
+    let mut lines: Vec<LineBuilder> = Vec::new();
+
+    for c in chars {
+        // Prefer the most recent line, since text is usually drawn line by line.
+        if let Some(line) = lines.iter_mut().rev().find(|l| l.accepts(&c)) {
+            line.start = line.start.min(c.along);
+            line.end = line.end.max(c.end());
+            line.chars.push(c);
+        } else {
+            lines.push(LineBuilder {
+                dir: c.dir,
+                normal: c.normal,
+                size: c.size,
+                start: c.along,
+                end: c.end(),
+                chars: vec![c],
+            });
+        }
+    }
+
+    lines
+}
+
+struct Line {
+    inner: TextLine,
+    dir: Vec2,
+    normal: f64,
+    size: f64,
+    start: f64,
+    end: f64,
+}
+
+struct BlockBuilder {
+    lines: Vec<Line>,
+    start: f64,
+    end: f64,
+}
+
+impl BlockBuilder {
+    fn accepts(&self, line: &Line) -> bool {
+        let last = self.lines.last().unwrap();
+        let size = last.size.min(line.size);
+        let distance = last.normal - line.normal;
+
+        last.dir.dot(line.dir) > 0.99
+            // Headings and body text shouldn't end up in the same block.
+            && (last.size - line.size).abs() <= 0.2 * size
+            && distance > 0.0
+            && distance <= BLOCK_LINE_SPACING * size
+            && line.start < self.end
+            && line.end > self.start
+    }
+}
+
+fn group_blocks(mut lines: Vec<Line>) -> Vec<TextBlock> {
+    // Process the lines from top to bottom.
+    lines.sort_by(|a, b| b.normal.total_cmp(&a.normal));
+
+    let mut blocks: Vec<BlockBuilder> = Vec::new();
+
+    for line in lines {
+        if let Some(block) = blocks.iter_mut().rev().find(|b| b.accepts(&line)) {
+            block.start = block.start.min(line.start);
+            block.end = block.end.max(line.end);
+            block.lines.push(line);
+        } else {
+            blocks.push(BlockBuilder {
+                start: line.start,
+                end: line.end,
+                lines: vec![line],
+            });
+        }
+    }
+
+    let mut blocks = blocks
+        .into_iter()
+        .map(|b| {
+            let vertical = b.lines[0].dir.y < -0.99;
+            let lines = b.lines.into_iter().map(|l| l.inner).collect::<Vec<_>>();
+            let bbox = lines
+                .iter()
+                .map(|l| l.bbox)
+                .reduce(|a, b| a.union(b))
+                .unwrap_or_default();
+
+            (TextBlock { lines, bbox }, vertical)
+        })
+        .collect::<Vec<_>>();
+
+    // Sort from top to bottom, and from left to right for blocks that start at the
+    // same height (like columns). Vertical text is read from right to left instead,
+    // and comes after horizontal text at the same height, so that both are only ever
+    // compared with blocks of the same direction.
+    blocks.sort_by(|(a, a_vertical), (b, b_vertical)| {
+        b.bbox
+            .y1
+            .round()
+            .total_cmp(&a.bbox.y1.round())
+            .then(a_vertical.cmp(b_vertical))
+            .then_with(|| {
+                if *a_vertical {
+                    b.bbox.x1.total_cmp(&a.bbox.x1)
+                } else {
+                    a.bbox.x0.total_cmp(&b.bbox.x0)
+                }
+            })
+    });
+
+    blocks.into_iter().map(|(block, _)| block).collect()
+}
@@ -0,0 +1,171 @@
+//! Searching for text on a page.
+
+use crate::text::TextDevice;
+use crate::text_layout::{TextBlock, TextLayout};
+use crate::util::TransformExt;
+use crate::{Context, InterpreterCache, InterpreterSettings, interpret_page};
+use hayro_syntax::page::Page;
+use kurbo::{Affine, Point, Rect};
+
+/// Options for searching text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Whether to ignore the case when comparing text.
+    pub case_insensitive: bool,
+    /// Whether to only report matches that aren't part of a larger word.
+    pub whole_word: bool,
+}
+
+/// A match of a text search.
+#[derive(Debug, Clone)]
+pub struct TextHit {
+    /// The matched text, as it appears on the page.
+    pub text: String,
+    /// The quadrilaterals covering the match, one for each line the match spans.
+    ///
+    /// The points are in device space (with the y-axis pointing down, the rotation of
+    /// the page applied and a scale of 1, see [`Page::initial_transform`]), in the order
+    /// bottom left, bottom right, top right and top left, relative to the writing
+    /// direction of the text.
+    pub quads: Vec<[Point; 4]>,
+}
+
+/// Extension methods for searching the text of a page.
+pub trait PageSearchExt {
+    /// Search for all occurrences of `needle` on the page.
+    ///
+    /// Matches can span several lines of the same block, a line break is treated
+    /// as a single space. This uses the default interpreter settings, use [`search_page`]
+    /// if you need to configure them.
+    fn search(&self, needle: &str, options: &SearchOptions) -> Vec<TextHit>;
+}
+
+impl PageSearchExt for Page<'_> {
+    fn search(&self, needle: &str, options: &SearchOptions) -> Vec<TextHit> {
+        search_page(self, needle, options, InterpreterSettings::default())
+    }
+}
+
+/// Search for all occurrences of `needle` on the page, using the given settings.
+///
+/// See [`PageSearchExt::search`] for more information.
+pub fn search_page(
+    page: &Page<'_>,
+    needle: &str,
+    options: &SearchOptions,
+    settings: InterpreterSettings,
+) -> Vec<TextHit> {
+    let needle = needle
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(|c| normalize(c, options))
+        .collect::<Vec<_>>();
+
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let cache = InterpreterCache::new();
+    let mut context = Context::new(
+        Affine::IDENTITY,
+        Rect::new(0.0, 0.0, 1.0, 1.0),
+        &cache,
+        page.xref(),
+        settings,
+    );
+    let mut device = TextDevice::new();
+    interpret_page(page, &mut context, &mut device);
+
+    let mut layout = TextLayout::new(device.runs());
+    layout.transform(page.initial_transform(true).to_kurbo());
+
+    layout
+        .blocks
+        .iter()
+        .flat_map(|block| search_block(block, &needle, options))
+        .collect()
+}
+
+struct SearchChar<'a> {
+    c: char,
+    /// The line index and the quad of the glyph the character belongs to. `None` for
+    /// inserted spaces.
+    glyph: Option<(usize, &'a [Point; 4])>,
+}
+
+fn search_block(block: &TextBlock, needle: &[char], options: &SearchOptions) -> Vec<TextHit> {
+    let mut haystack = Vec::new();
+
+    for (line_idx, line) in block.lines.iter().enumerate() {
+        for word in &line.words {
+            if !haystack.is_empty() {
+                haystack.push(SearchChar {
+                    c: ' ',
+                    glyph: None,
+                });
+            }
+
+            for glyph in &word.chars {
+                haystack.extend(glyph.text.chars().map(|c| SearchChar {
+                    c,
+                    glyph: Some((line_idx, &glyph.quad)),
+                }));
+            }
+        }
+    }
+
+    let is_word_char = |idx: usize| haystack.get(idx).is_some_and(|c| c.c.is_alphanumeric());
+
+    let mut hits = Vec::new();
+    let mut start = 0;
+
+    while start + needle.len() <= haystack.len() {
+        let end = start + needle.len();
+        let matches = haystack[start..end]
+            .iter()
+            .zip(needle)
+            .all(|(h, n)| normalize(h.c, options) == *n);
+        let is_whole_word = (start == 0 || !is_word_char(start - 1)) && !is_word_char(end);
+
+        if matches && (!options.whole_word || is_whole_word) {
+            hits.push(TextHit {
+                text: haystack[start..end].iter().map(|c| c.c).collect(),
+                quads: line_quads(&haystack[start..end]),
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+
+    hits
+}
+
+/// Merge the glyph quads of each line into a single quad.
+fn line_quads(chars: &[SearchChar<'_>]) -> Vec<[Point; 4]> {
+    let mut quads: Vec<(usize, [Point; 4])> = Vec::new();
+
+    for (line_idx, quad) in chars.iter().filter_map(|c| c.glyph) {
+        match quads.last_mut() {
+            Some((last_idx, last)) if *last_idx == line_idx => {
+                last[1] = quad[1];
+                last[2] = quad[2];
+            }
+            _ => quads.push((line_idx, *quad)),
+        }
+    }
+
+    quads.into_iter().map(|(_, quad)| quad).collect()
+}
+
+fn normalize(c: char, options: &SearchOptions) -> char {
+    if c.is_whitespace() {
+        ' '
+    } else if options.case_insensitive {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
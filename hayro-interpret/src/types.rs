@@ -1,15 +1,18 @@
 use crate::CacheKey;
-use crate::color::Color;
+use crate::color::{Color, ColorSpace, RenderingIntent};
+use crate::display_list::{RecordedImage, RecordedImageData};
 use crate::pattern::Pattern;
 use crate::soft_mask::SoftMask;
 use crate::util::hash128;
-use crate::x_object::ImageXObject;
-use hayro_syntax::object::Stream;
+use crate::x_object::{DecodedRaster, ImageXObject};
+use hayro_syntax::object::{Dict, Stream};
 use kurbo::{Affine, BezPath, Cap, Join};
 use smallvec::{SmallVec, smallvec};
+use std::sync::Arc;
 
 /// A clip path.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClipPath {
     /// The clipping path.
     pub path: BezPath,
@@ -24,9 +27,10 @@ impl CacheKey for ClipPath {
 }
 
 /// A stencil image.
+#[derive(Clone)]
 pub struct StencilImage<'a, 'b> {
     pub(crate) paint: Paint<'a>,
-    pub(crate) image_xobject: ImageXObject<'b>,
+    pub(crate) source: ImageSource<'b>,
 }
 
 impl<'a, 'b> StencilImage<'a, 'b> {
@@ -37,37 +41,64 @@ impl<'a, 'b> StencilImage<'a, 'b> {
     /// mean that the resulting image will have that dimension. Instead, it allows
     /// the image decoder to extract a lower-resolution version of the image in
     /// certain cases.
+    ///
+    /// The image is only decoded when this method is called, and may be taken
+    /// from the [`ImageCache`](crate::ImageCache) of the interpreter cache. Use
+    /// [`InterpreterSettings::max_image_pixels`](crate::InterpreterSettings::max_image_pixels)
+    /// to bound the memory that is needed for decoding huge images, or
+    /// [`with_stencil_rows`](Self::with_stencil_rows) to convert them in bands.
     pub fn with_stencil(
         &self,
         func: impl FnOnce(LumaData, &Paint<'a>),
         target_dimension: Option<(u32, u32)>,
     ) {
-        if let Some(decoded) = self.image_xobject.decoded_mask(target_dimension) {
-            func(decoded.luma, &self.paint);
+        if let Some(decoded) = self.source.decoded_mask(target_dimension) {
+            func(decoded, &self.paint);
         }
     }
 
+    /// Perform some operation with the stencil data of the image, in bands of
+    /// at most `band_height` rows from top to bottom.
+    ///
+    /// The function is called with the index of the first row of each band and
+    /// the stencil data of the band, whose height is the number of rows in it.
+    /// Unlike with [`with_stencil`](Self::with_stencil), the mask is converted one
+    /// band at a time, so that band renderers don't need to hold the whole mask
+    /// in memory. The bands are never taken from or stored in the image cache.
+    pub fn with_stencil_rows(
+        &self,
+        band_height: u32,
+        mut func: impl FnMut(u32, LumaData, &Paint<'a>),
+        target_dimension: Option<(u32, u32)>,
+    ) {
+        self.source
+            .decoded_mask_rows(target_dimension, band_height, |y, luma| {
+                func(y, luma, &self.paint);
+            });
+    }
+
     // These are hidden since clients are supposed to call get the
     // width/height from `LumaData` instead.
     #[doc(hidden)]
     pub fn width(&self) -> u32 {
-        self.image_xobject.width()
+        self.source.width()
     }
 
     #[doc(hidden)]
     pub fn height(&self) -> u32 {
-        self.image_xobject.height()
+        self.source.height()
     }
 }
 
 impl CacheKey for StencilImage<'_, '_> {
     fn cache_key(&self) -> u128 {
-        self.image_xobject.cache_key()
+        self.source.cache_key()
     }
 }
 
 /// A raster image.
-pub struct RasterImage<'a>(pub(crate) ImageXObject<'a>);
+#[derive(Clone)]
+pub struct RasterImage<'a>(pub(crate) ImageSource<'a>);
 
 impl RasterImage<'_> {
     /// Perform some operation with the RGB and alpha channel of the image.
@@ -77,6 +108,12 @@ impl RasterImage<'_> {
     /// mean that the resulting image will have that dimension. Instead, it allows
     /// the image decoder to extract a lower-resolution version of the image in
     /// certain cases.
+    ///
+    /// The image is only decoded when this method is called, and may be taken
+    /// from the [`ImageCache`](crate::ImageCache) of the interpreter cache. Use
+    /// [`InterpreterSettings::max_image_pixels`](crate::InterpreterSettings::max_image_pixels)
+    /// to bound the memory that is needed for decoding huge images, or
+    /// [`with_rgba_rows`](Self::with_rgba_rows) to convert them in bands.
     pub fn with_rgba(
         &self,
         func: impl FnOnce(ImageData, Option<LumaData>),
@@ -87,13 +124,58 @@ impl RasterImage<'_> {
         }
     }
 
+    /// Perform some operation with the RGB and alpha channel of the image, in
+    /// bands of at most `band_height` rows from top to bottom.
+    ///
+    /// The function is called with the index of the first row of each band,
+    /// the color data of the band, whose height is the number of rows in it, and
+    /// the alpha channel of the whole image. The alpha channel isn't split into
+    /// bands because it can have a different resolution than the image.
+    ///
+    /// Unlike with [`with_rgba`](Self::with_rgba), the colors are converted one
+    /// band at a time, so that band renderers don't need to hold the whole
+    /// converted image in memory. The encoded image data is still decompressed
+    /// as a whole, and the bands are never taken from or stored in the image
+    /// cache.
+    pub fn with_rgba_rows(
+        &self,
+        band_height: u32,
+        func: impl FnMut(u32, ImageData, Option<&LumaData>),
+        target_dimension: Option<(u32, u32)>,
+    ) {
+        self.0
+            .decoded_raster_rows(target_dimension, band_height, func);
+    }
+
     /// Return the underlying stream object.
     ///
     /// This allows you to get access to the raw encoded image data, without doing any decoding.
+    /// Inline images that are replayed from a [`DisplayList`](crate::DisplayList) and
+    /// all images that are replayed from an [`OwnedDisplayList`](crate::OwnedDisplayList)
+    /// have already been decoded, so their stream is empty.
     pub fn stream(&self) -> &Stream<'_> {
         self.0.stream()
     }
 
+    /// Return the color space of the image.
+    ///
+    /// This is `None` for JPEG 2000 images that use their embedded color space.
+    pub fn color_space(&self) -> Option<&ColorSpace> {
+        self.0.color_space()
+    }
+
+    /// Return the number of bits per component of the encoded image, if specified.
+    pub fn bits_per_component(&self) -> Option<u8> {
+        self.0.bits_per_component()
+    }
+
+    /// Return the rendering intent of the image.
+    ///
+    /// The decoded colors of the image already take it into account.
+    pub fn rendering_intent(&self) -> RenderingIntent {
+        self.0.rendering_intent()
+    }
+
     // These are hidden since clients are supposed to call get the
     // width/height from `LumaData` instead.
     #[doc(hidden)]
@@ -113,7 +195,174 @@ impl CacheKey for RasterImage<'_> {
     }
 }
 
+/// The source of the data of an image.
+#[derive(Clone)]
+pub(crate) enum ImageSource<'a> {
+    /// An image `XObject` or inline image, which is decoded on demand.
+    XObject(ImageXObject<'a>),
+    /// An image that was decoded when it was recorded into a display list.
+    Recorded(Arc<RecordedImage>),
+}
+
+impl<'a> ImageSource<'a> {
+    pub(crate) fn decoded_mask(&self, target_dimension: Option<(u32, u32)>) -> Option<LumaData> {
+        match self {
+            Self::XObject(x) => x.decoded_mask(target_dimension).map(|d| d.luma),
+            Self::Recorded(r) => match &r.data {
+                RecordedImageData::Mask(luma) => Some(luma.clone()),
+                RecordedImageData::Raster(_) => None,
+            },
+        }
+    }
+
+    pub(crate) fn decoded_raster(
+        &self,
+        target_dimension: Option<(u32, u32)>,
+    ) -> Option<DecodedRaster> {
+        match self {
+            Self::XObject(x) => x.decoded_raster(target_dimension),
+            Self::Recorded(r) => match &r.data {
+                RecordedImageData::Raster(decoded) => Some(decoded.clone()),
+                RecordedImageData::Mask(_) => None,
+            },
+        }
+    }
+
+    pub(crate) fn decoded_mask_rows(
+        &self,
+        target_dimension: Option<(u32, u32)>,
+        band_height: u32,
+        mut func: impl FnMut(u32, LumaData),
+    ) {
+        match self {
+            Self::XObject(x) => {
+                x.decoded_mask_rows(target_dimension, band_height, func);
+            }
+            Self::Recorded(r) => {
+                let band_height = band_height.max(1);
+
+                if let RecordedImageData::Mask(luma) = &r.data {
+                    for y in (0..luma.height).step_by(band_height as usize) {
+                        func(y, luma.rows(y, band_height));
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn decoded_raster_rows(
+        &self,
+        target_dimension: Option<(u32, u32)>,
+        band_height: u32,
+        mut func: impl FnMut(u32, ImageData, Option<&LumaData>),
+    ) {
+        match self {
+            Self::XObject(x) => {
+                x.decoded_raster_rows(target_dimension, band_height, func);
+            }
+            Self::Recorded(r) => {
+                let band_height = band_height.max(1);
+
+                if let RecordedImageData::Raster(decoded) = &r.data {
+                    for y in (0..decoded.image.height()).step_by(band_height as usize) {
+                        func(
+                            y,
+                            decoded.image.rows(y, band_height),
+                            decoded.alpha.as_ref(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decode the image, so that it no longer borrows from the content stream.
+    pub(crate) fn to_recorded(&self) -> Option<ImageSource<'static>> {
+        let x_object = match self {
+            Self::XObject(x) => x,
+            Self::Recorded(r) => return Some(ImageSource::Recorded(r.clone())),
+        };
+
+        let data = match x_object.decoded_mask(None) {
+            Some(mask) => RecordedImageData::Mask(mask.luma),
+            None => RecordedImageData::Raster(x_object.decoded_raster(None)?),
+        };
+
+        Some(ImageSource::Recorded(Arc::new(RecordedImage {
+            width: x_object.width(),
+            height: x_object.height(),
+            color_space: x_object.color_space().cloned(),
+            bits_per_component: x_object.bits_per_component(),
+            rendering_intent: x_object.rendering_intent(),
+            cache_key: x_object.cache_key(),
+            object_key: x_object.object_key(),
+            stream: Stream::default(),
+            data,
+        })))
+    }
+
+    pub(crate) fn stream(&self) -> &Stream<'a> {
+        match self {
+            Self::XObject(x) => x.stream(),
+            Self::Recorded(r) => &r.stream,
+        }
+    }
+
+    pub(crate) fn color_space(&self) -> Option<&ColorSpace> {
+        match self {
+            Self::XObject(x) => x.color_space(),
+            Self::Recorded(r) => r.color_space.as_ref(),
+        }
+    }
+
+    pub(crate) fn bits_per_component(&self) -> Option<u8> {
+        match self {
+            Self::XObject(x) => x.bits_per_component(),
+            Self::Recorded(r) => r.bits_per_component,
+        }
+    }
+
+    pub(crate) fn rendering_intent(&self) -> RenderingIntent {
+        match self {
+            Self::XObject(x) => x.rendering_intent(),
+            Self::Recorded(r) => r.rendering_intent,
+        }
+    }
+
+    pub(crate) fn width(&self) -> u32 {
+        match self {
+            Self::XObject(x) => x.width(),
+            Self::Recorded(r) => r.width,
+        }
+    }
+
+    pub(crate) fn height(&self) -> u32 {
+        match self {
+            Self::XObject(x) => x.height(),
+            Self::Recorded(r) => r.height,
+        }
+    }
+
+    /// See `ImageXObject::object_key`.
+    pub(crate) fn object_key(&self) -> u128 {
+        match self {
+            Self::XObject(x) => x.object_key(),
+            Self::Recorded(r) => r.object_key,
+        }
+    }
+}
+
+impl CacheKey for ImageSource<'_> {
+    fn cache_key(&self) -> u128 {
+        match self {
+            Self::XObject(x) => x.cache_key(),
+            Self::Recorded(r) => r.cache_key,
+        }
+    }
+}
+
 /// A type of image.
+#[derive(Clone)]
 pub enum Image<'a, 'b> {
     /// A stencil image.
     Stencil(StencilImage<'a, 'b>),
@@ -154,9 +403,13 @@ impl CacheKey for Image<'_, '_> {
 
 /// A structure holding 3-channel RGB data.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RgbData {
     /// The actual data. It is guaranteed to have the length width * height * 3.
-    pub data: Vec<u8>,
+    ///
+    /// The data is shared between all copies of the image, so that images that
+    /// are cached or replayed from a display list aren't copied.
+    pub data: Arc<[u8]>,
     /// The width.
     pub width: u32,
     /// The height.
@@ -178,11 +431,28 @@ pub struct RgbData {
     pub scale_factors: (f32, f32),
 }
 
+impl RgbData {
+    /// Return a copy of at most `height` rows, starting at row `y`.
+    pub(crate) fn rows(&self, y: u32, height: u32) -> Self {
+        let height = height.min(self.height.saturating_sub(y));
+        let row_len = self.width as usize * 3;
+
+        Self {
+            data: self.data[y as usize * row_len..][..height as usize * row_len].into(),
+            height,
+            ..*self
+        }
+    }
+}
+
 /// A structure holding 1-channel luma data.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LumaData {
     /// The actual data. It is guaranteed to have the length width * height.
-    pub data: Vec<u8>,
+    ///
+    /// Like for [`RgbData`], the data is shared between all copies of the image.
+    pub data: Arc<[u8]>,
     /// The width.
     pub width: u32,
     /// The height.
@@ -204,8 +474,23 @@ pub struct LumaData {
     pub scale_factors: (f32, f32),
 }
 
+impl LumaData {
+    /// Return a copy of at most `height` rows, starting at row `y`.
+    pub(crate) fn rows(&self, y: u32, height: u32) -> Self {
+        let height = height.min(self.height.saturating_sub(y));
+        let row_len = self.width as usize;
+
+        Self {
+            data: self.data[y as usize * row_len..][..height as usize * row_len].into(),
+            height,
+            ..*self
+        }
+    }
+}
+
 /// The color data of a raster image, either 3-channel RGB or 1-channel luma.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImageData {
     /// 3-channel RGB data.
     Rgb(RgbData),
@@ -245,6 +530,14 @@ impl ImageData {
             Self::Luma(d) => d.scale_factors,
         }
     }
+
+    /// Return a copy of at most `height` rows, starting at row `y`.
+    pub(crate) fn rows(&self, y: u32, height: u32) -> Self {
+        match self {
+            Self::Rgb(d) => Self::Rgb(d.rows(y, height)),
+            Self::Luma(d) => Self::Luma(d.rows(y, height)),
+        }
+    }
 }
 
 /// A type of paint.
@@ -269,6 +562,33 @@ impl CacheKey for Paint<'_> {
     }
 }
 
+/// A marked content sequence.
+#[derive(Clone, Debug, Default)]
+pub struct MarkedContent<'a> {
+    /// The tag of the sequence (e.g. `b"P"` or `b"Span"`).
+    pub tag: &'a [u8],
+    /// The marked content identifier from the properties dictionary, if present.
+    pub mcid: Option<i32>,
+    /// The text that replaces the content of the sequence for text extraction
+    /// (`/ActualText`), for example to map a ligature or hyphenated word back to
+    /// the original text.
+    pub actual_text: Option<String>,
+    /// An alternate description of the content (`/Alt`), typically used for figures.
+    pub alt: Option<String>,
+    /// The properties dictionary, if present.
+    ///
+    /// Properties that are given by name are already resolved from the
+    /// `Properties` resource dictionary. They contain further information
+    /// about the sequence, like the type of an artifact (`/Type`) or the
+    /// optional content group it belongs to.
+    pub properties: Option<Dict<'a>>,
+}
+
+/// The smallest flatness tolerance in device pixels that curves should be
+/// approximated with. A flatness of 0 requests the finest approximation the
+/// device supports, which is clamped to this value.
+pub const MIN_FLATNESS: f32 = 0.1;
+
 /// Properties for a painted drawing operation.
 #[derive(Clone)]
 pub struct DrawProps<'a> {
@@ -280,6 +600,13 @@ pub struct DrawProps<'a> {
     pub soft_mask: Option<SoftMask<'a>>,
     /// The blend mode.
     pub blend_mode: BlendMode,
+    /// The flatness tolerance in device pixels, or `None` if it was never set.
+    ///
+    /// This corresponds to the `i` operator and the `FL` entry of the graphics
+    /// state. Devices that approximate curves with line segments should keep
+    /// them within this distance of the curve, and use their own tolerance if
+    /// none is given.
+    pub flatness: Option<f32>,
 }
 
 /// Properties for an image drawing operation.
@@ -295,6 +622,7 @@ pub struct ImageDrawProps<'a> {
 
 /// The draw mode.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DrawMode {
     /// Draw using a fill.
     Fill(FillRule),
@@ -306,8 +634,149 @@ pub enum DrawMode {
     Invisible,
 }
 
+/// How strokes with a line width of zero should be drawn.
+///
+/// The PDF specification defines such strokes as the thinnest line the
+/// device can render.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ZeroWidthLinePolicy {
+    /// Draw the strokes as hairlines with a width of one device pixel, no
+    /// matter the zoom level. The strokes are marked with
+    /// [`StrokeProps::hairline`].
+    #[default]
+    Hairline,
+    /// Draw the strokes with the given width in user space units, so that
+    /// they scale with the zoom level like other strokes.
+    MinimumWidth(f32),
+}
+
+/// How operators that are unknown or whose operands could not be read should
+/// be treated.
+///
+/// Inside of compatibility sections (between `BX` and `EX`), such operators
+/// are always ignored silently, as required by the PDF specification.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UnknownOperatorPolicy {
+    /// Skip the operators silently.
+    Ignore,
+    /// Skip the operators and emit an
+    /// [`InterpreterWarning::InvalidOperator`](crate::InterpreterWarning::InvalidOperator)
+    /// warning with the name of the operator.
+    #[default]
+    Warn,
+    /// Emit an
+    /// [`InterpreterWarning::InvalidOperator`](crate::InterpreterWarning::InvalidOperator)
+    /// warning and stop interpreting the page.
+    Error,
+}
+
+/// How fonts with an embedded font program that is truncated or otherwise
+/// corrupt should be treated.
+///
+/// Corrupt font programs are always reported with an
+/// [`InterpreterWarning::CorruptEmbeddedFont`](crate::InterpreterWarning::CorruptEmbeddedFont)
+/// warning. Fonts that are missing or unsupported are not affected by this
+/// policy and are always replaced with a standard font.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CorruptFontPolicy {
+    /// Substitute a standard font that is picked based on the name and the
+    /// flags of the font.
+    #[default]
+    Fallback,
+    /// Don't draw the text that is shown with the font, and emit an
+    /// [`InterpreterWarning::SkippedFont`](crate::InterpreterWarning::SkippedFont)
+    /// warning. The text position is still advanced, so that the remaining text
+    /// of the page isn't shifted.
+    Skip,
+    /// Stop interpreting the page.
+    Error,
+}
+
+/// Settings that trade off the quality of paint sources that are approximated
+/// or rasterized against rendering speed.
+///
+/// The presets [`RenderQuality::fast_preview`] and [`RenderQuality::print`]
+/// cover the common cases, while the default matches the quality that is
+/// appropriate for viewing a document on screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderQuality {
+    /// The maximum number of times the intervals of axial and radial shadings
+    /// are halved when approximating them with gradient stops.
+    ///
+    /// Each subdivision at most doubles the number of stops, until the
+    /// [shading smoothness](crate::InterpreterSettings::shading_smoothness) is
+    /// met. By default, this is 10.
+    pub gradient_subdivisions: u8,
+    /// The resolution at which the tiles of tiling patterns are rendered,
+    /// relative to the resolution of the device.
+    ///
+    /// By default, this is 1.
+    pub pattern_resolution: f32,
+    /// The resolution at which soft masks are rendered, relative to the
+    /// resolution of the device.
+    ///
+    /// Values above 1 have no effect. By default, this is 1.
+    pub soft_mask_resolution: f32,
+    /// The quality of the filters that are used for resampling images.
+    ///
+    /// By default, this is [`ImageFilterQuality::Medium`].
+    pub image_filter: ImageFilterQuality,
+}
+
+impl RenderQuality {
+    /// Settings for quickly rendering previews, like thumbnails.
+    pub fn fast_preview() -> Self {
+        Self {
+            gradient_subdivisions: 4,
+            pattern_resolution: 0.5,
+            soft_mask_resolution: 0.5,
+            image_filter: ImageFilterQuality::Low,
+        }
+    }
+
+    /// Settings for rendering with the highest quality, for example for printing.
+    pub fn print() -> Self {
+        Self {
+            gradient_subdivisions: 12,
+            pattern_resolution: 2.0,
+            soft_mask_resolution: 1.0,
+            image_filter: ImageFilterQuality::High,
+        }
+    }
+}
+
+impl Default for RenderQuality {
+    fn default() -> Self {
+        Self {
+            gradient_subdivisions: 10,
+            pattern_resolution: 1.0,
+            soft_mask_resolution: 1.0,
+            image_filter: ImageFilterQuality::default(),
+        }
+    }
+}
+
+/// The quality of the filters that are used for resampling images.
+///
+/// Images that should not be interpolated are always sampled with the nearest
+/// neighbor, see [`InterpreterSettings::image_interpolation`](crate::InterpreterSettings::image_interpolation).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ImageFilterQuality {
+    /// Sample interpolated images with the nearest neighbor and downscale
+    /// images with a bilinear filter.
+    Low,
+    /// Sample interpolated images with a bilinear filter and downscale images
+    /// with a bicubic filter.
+    #[default]
+    Medium,
+    /// Sample interpolated images with a bicubic filter and downscale images
+    /// with a Lanczos filter.
+    High,
+}
+
 /// Stroke properties.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StrokeProps {
     /// The line width.
     pub line_width: f32,
@@ -321,6 +790,19 @@ pub struct StrokeProps {
     pub dash_array: SmallVec<[f32; 4]>,
     /// The dash offset.
     pub dash_offset: f32,
+    /// Whether the stroke should be drawn as a hairline.
+    ///
+    /// This is the case for strokes with a line width of zero if the
+    /// [`ZeroWidthLinePolicy::Hairline`] policy is used. Such strokes should
+    /// be drawn as the thinnest line the device can render, usually one device
+    /// pixel wide, regardless of the current transform.
+    pub hairline: bool,
+    /// Whether stroke adjustment is enabled.
+    ///
+    /// This corresponds to the `SA` entry of the graphics state. Devices that
+    /// rasterize strokes should then align them to the pixel grid, so that thin
+    /// lines are drawn crisply and with a uniform width.
+    pub stroke_adjustment: bool,
 }
 
 impl Default for StrokeProps {
@@ -332,12 +814,63 @@ impl Default for StrokeProps {
             miter_limit: 10.0,
             dash_array: smallvec![],
             dash_offset: 0.0,
+            hairline: false,
+            stroke_adjustment: false,
+        }
+    }
+}
+
+impl StrokeProps {
+    /// Return the dash array for strokers that don't draw caps for dashes of
+    /// length zero, like the one of kurbo.
+    ///
+    /// Zero-length dashes are replaced by very short ones, so that they are
+    /// still drawn as dots with round caps and as squares with square caps.
+    pub fn dash_array_for_stroker(&self) -> SmallVec<[f64; 4]> {
+        // Relative to the line width, so that the added length isn't visible.
+        let min_length = (self.line_width as f64 * 1.0e-3).max(1.0e-6);
+        let mut dash_array = self
+            .dash_array
+            .iter()
+            .map(|length| *length as f64)
+            .collect::<SmallVec<[f64; 4]>>();
+
+        for i in 0..dash_array.len() {
+            let next = (i + 1) % dash_array.len();
+
+            // Take the added length from the following entry, so that the dashes
+            // stay at the same positions.
+            if dash_array[i] < min_length && dash_array[next] > 2.0 * min_length {
+                dash_array[next] -= min_length - dash_array[i];
+                dash_array[i] = min_length;
+            }
+        }
+
+        dash_array
+    }
+
+    /// Split a path into its dashes.
+    ///
+    /// This can be used by devices that can't dash paths themselves. The
+    /// returned path should be stroked with the same properties, but without
+    /// a dash array.
+    pub fn dash_path(&self, path: &BezPath) -> BezPath {
+        if self.dash_array.is_empty() {
+            return path.clone();
         }
+
+        kurbo::dash(
+            path.iter(),
+            self.dash_offset as f64,
+            &self.dash_array_for_stroker(),
+        )
+        .collect()
     }
 }
 
 /// A fill rule.
 #[derive(Clone, Debug, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FillRule {
     /// Non-zero filling.
     NonZero,
@@ -347,6 +880,7 @@ pub enum FillRule {
 
 /// A blend mode.
 #[derive(Clone, Debug, Copy, Hash, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlendMode {
     /// Normal blend mode (default).
     #[default]
@@ -1,9 +1,10 @@
 //! A number of utility methods.
 
-use kurbo::{BezPath, PathEl, Rect};
+use kurbo::{Affine, BezPath, PathEl, Rect};
 use siphasher::sip128::{Hasher128, SipHasher13};
 use std::hash::Hash;
 use std::ops::Sub;
+use std::time::Instant;
 
 pub(crate) trait OptionLog {
     fn warn_none(self, f: &str) -> Self;
@@ -116,6 +117,15 @@ pub(crate) fn hash128<T: Hash + ?Sized>(value: &T) -> u128 {
     state.finish128().as_u128()
 }
 
+/// Return the current time, or `None` on `wasm32`, where querying it panics.
+pub(crate) fn now() -> Option<Instant> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return Some(Instant::now());
+
+    #[cfg(target_arch = "wasm32")]
+    return None;
+}
+
 pub(crate) trait BezPathExt {
     fn fast_bounding_box(&self) -> Rect;
 }
@@ -158,15 +168,47 @@ impl BezPathExt for BezPath {
     }
 }
 
+/// Decode a PDF text string, which is either encoded as UTF-16BE or UTF-8 (both with a
+/// byte order mark) or using `PDFDocEncoding`.
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(data) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units = data
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+
+        String::from_utf16_lossy(&units)
+    } else if let Some(data) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(data).into_owned()
+    } else {
+        bytes.iter().map(|b| pdf_doc_char(*b)).collect()
+    }
+}
+
+fn pdf_doc_char(b: u8) -> char {
+    // `PDFDocEncoding` matches Latin-1, except for the ranges below.
+    const LOW: [char; 8] = ['˘', 'ˇ', 'ˆ', '˙', '˝', '˛', '˚', '˜'];
+    const HIGH: [char; 33] = [
+        '•', '†', '‡', '…', '—', '–', 'ƒ', '⁄', '‹', '›', '−', '‰', '„', '“', '”', '‘', '’', '‚',
+        '™', 'ﬁ', 'ﬂ', 'Ł', 'Œ', 'Š', 'Ÿ', 'Ž', 'ı', 'ł', 'œ', 'š', 'ž', '\u{FFFD}', '€',
+    ];
+
+    match b {
+        0x18..=0x1F => LOW[(b - 0x18) as usize],
+        0x80..=0xA0 => HIGH[(b - 0x80) as usize],
+        _ => b as char,
+    }
+}
+
 /// Extension methods for converting a [`hayro_syntax::transform::Transform`] to a [`kurbo::Affine`].
 pub trait TransformExt {
     /// Convert to a `kurbo::Affine`.
-    fn to_kurbo(&self) -> kurbo::Affine;
+    fn to_kurbo(&self) -> Affine;
 }
 
 impl TransformExt for hayro_syntax::transform::Transform {
-    fn to_kurbo(&self) -> kurbo::Affine {
-        kurbo::Affine::new(self.as_coeffs())
+    fn to_kurbo(&self) -> Affine {
+        Affine::new(self.as_coeffs())
     }
 }
 
@@ -181,3 +223,32 @@ impl RectExt for hayro_syntax::object::Rect {
         Rect::new(self.x0, self.y0, self.x1, self.y1)
     }
 }
+
+/// Extension methods for affine transforms.
+pub(crate) trait AffineExt {
+    /// Return the transform without its translation.
+    fn linear(self) -> Affine;
+    /// Return the inverse of the transform, or `None` if it isn't invertible.
+    fn checked_inverse(self) -> Option<Affine>;
+    /// Whether the coefficients of both transforms are equal, up to a small error
+    /// relative to their magnitude.
+    fn approx_eq(self, other: Affine) -> bool;
+}
+
+impl AffineExt for Affine {
+    fn linear(self) -> Affine {
+        let [a, b, c, d, _, _] = self.as_coeffs();
+        Self::new([a, b, c, d, 0.0, 0.0])
+    }
+
+    fn checked_inverse(self) -> Option<Affine> {
+        (self.determinant().abs() > f64::EPSILON).then(|| self.inverse())
+    }
+
+    fn approx_eq(self, other: Affine) -> bool {
+        let (a, b) = (self.as_coeffs(), other.as_coeffs());
+        let scale = a.iter().chain(&b).fold(1.0_f64, |m, v| m.max(v.abs()));
+
+        a.iter().zip(&b).all(|(a, b)| (a - b).abs() <= scale * 1e-6)
+    }
+}
@@ -1,13 +1,16 @@
-use crate::cache::Cache;
-use crate::color::{ColorComponents, ColorSpace, ToRgb};
+use crate::cache::{Cache, ImageCache};
+use crate::color::{ColorComponents, ColorSpace, RenderingIntent, ToRgb};
 use crate::context::Context;
 use crate::device::Device;
-use crate::function::{Function, interpolate};
+use crate::function::interpolate;
 use crate::interpret::state::ActiveTransferFunction;
+use crate::stats::Phase;
+use crate::types::ImageSource;
+use crate::util::hash128;
 use crate::{BlendMode, CacheKey, ClipPath, Image, ImageDrawProps, RasterImage, StencilImage};
 use crate::{FillRule, InterpreterWarning, WarningSinkFn, interpret};
 use crate::{ImageData, LumaData, RgbData};
-use hayro_syntax::bit_reader::BitReader;
+use hayro_syntax::bit_reader::{BitReader, BitWriter};
 use hayro_syntax::content::TypedIter;
 use hayro_syntax::object::Array;
 use hayro_syntax::object::Dict;
@@ -18,41 +21,45 @@ use hayro_syntax::object::dict::keys::*;
 use hayro_syntax::object::stream::{FilterResult, ImageColorSpace, ImageDecodeParams};
 use hayro_syntax::page::Resources;
 use kurbo::{Affine, Rect, Shape};
-use smallvec::{SmallVec, smallvec};
+use smallvec::SmallVec;
 use std::borrow::Cow;
 use std::iter;
 use std::ops::Deref;
+use std::sync::Arc;
 
-pub(crate) enum XObject<'a> {
-    FormXObject(FormXObject<'a>),
-    ImageXObject(ImageXObject<'a>),
+/// A form or image `XObject`.
+pub struct XObject<'a>(XObjectKind<'a>);
+
+enum XObjectKind<'a> {
+    Form(FormXObject<'a>),
+    Image(ImageXObject<'a>),
 }
 
 impl<'a> XObject<'a> {
-    pub(crate) fn new(
-        stream: &Stream<'a>,
-        warning_sink: &WarningSinkFn,
-        cache: &Cache,
-        transfer_function: Option<ActiveTransferFunction>,
-    ) -> Option<Self> {
+    /// Create a new `XObject` from its stream.
+    ///
+    /// Returns `None` if the stream is neither a valid form nor a valid image `XObject`.
+    pub fn from_stream(stream: &Stream<'a>, context: &Context<'a>) -> Option<Self> {
         let dict = stream.dict();
-        match dict.get::<Name<'_>>(SUBTYPE)?.deref() {
-            IMAGE => Some(Self::ImageXObject(ImageXObject::new(
+        let kind = match dict.get::<Name<'_>>(SUBTYPE)?.deref() {
+            IMAGE => XObjectKind::Image(ImageXObject::new(
                 stream,
                 |_| None,
-                warning_sink,
-                cache,
+                &context.settings.warning_sink,
+                &context.interpreter_cache.object_cache,
                 false,
-                transfer_function,
-            )?)),
-            FORM => Some(Self::FormXObject(FormXObject::new(stream)?)),
-            _ => None,
-        }
+                context.get().graphics_state.transfer_function.clone(),
+            )?),
+            FORM => XObjectKind::Form(FormXObject::new(stream, context)?),
+            _ => return None,
+        };
+
+        Some(Self(kind))
     }
 }
 
 pub(crate) struct FormXObject<'a> {
-    pub(crate) decoded: Cow<'a, [u8]>,
+    pub(crate) decoded: FormContent<'a>,
     pub(crate) matrix: Affine,
     pub(crate) bbox: [f32; 4],
     is_transparency_group: bool,
@@ -61,10 +68,28 @@ pub(crate) struct FormXObject<'a> {
 }
 
 impl<'a> FormXObject<'a> {
-    pub(crate) fn new(stream: &Stream<'a>) -> Option<Self> {
+    pub(crate) fn new(stream: &Stream<'a>, context: &Context<'_>) -> Option<Self> {
         let dict = stream.dict();
 
-        let decoded = stream.decoded().ok()?;
+        // Forms are often drawn several times, for example a letterhead on every
+        // page, so only decode their content stream once per document.
+        let decoded = if stream.filters().is_empty()
+            && let Cow::Borrowed(data) = stream.raw_data()
+        {
+            FormContent::Borrowed(data)
+        } else {
+            let key = match dict.obj_id() {
+                Some(id) => hash128(&(FORM, id)),
+                None => hash128(&(stream.cache_key(), stream.raw_data().as_ref())),
+            };
+
+            FormContent::Shared(context.interpreter_cache.form_cache.0.get_or_insert_with(
+                key,
+                context.settings.form_cache_max_bytes,
+                || stream.decoded().ok().map(Arc::<[u8]>::from),
+                |data| data.len(),
+            )?)
+        };
         let resources = dict.get::<Dict<'_>>(RESOURCES).unwrap_or_default();
 
         let matrix = Affine::new(
@@ -83,6 +108,33 @@ impl<'a> FormXObject<'a> {
             resources,
         })
     }
+
+    /// Return the color space declared by the group attributes of the form.
+    fn color_space(&self, context: &Context<'_>) -> Option<ColorSpace> {
+        let group = self.dict.get::<Dict<'_>>(GROUP)?;
+
+        ColorSpace::new(
+            group.get::<Object<'_>>(CS)?,
+            &context.interpreter_cache.object_cache,
+        )
+    }
+}
+
+/// The decoded content stream of a form `XObject`.
+pub(crate) enum FormContent<'a> {
+    Borrowed(&'a [u8]),
+    Shared(Arc<[u8]>),
+}
+
+impl Deref for FormContent<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Borrowed(data) => data,
+            Self::Shared(data) => data,
+        }
+    }
 }
 
 pub(crate) fn draw_xobject<'a>(
@@ -91,10 +143,12 @@ pub(crate) fn draw_xobject<'a>(
     context: &mut Context<'a>,
     device: &mut impl Device<'a>,
 ) {
-    match x_object {
-        XObject::FormXObject(f) => draw_form_xobject(resources, f, context, device),
-        XObject::ImageXObject(i) => {
-            draw_image_xobject(i, context, device);
+    match &x_object.0 {
+        XObjectKind::Form(f) => draw_form_xobject(resources, f, context, device),
+        XObjectKind::Image(i) => {
+            draw_image_xobject(i, context, device, |device, image, props| {
+                device.draw_xobject_image(image, props);
+            });
         }
     }
 }
@@ -109,7 +163,20 @@ pub(crate) fn draw_form_xobject<'a, 'b>(
         return;
     }
 
-    if !context.begin_nested_interpretation() {
+    // Skip the whole content stream if it would be clipped away anyway.
+    let bbox = Rect::new(
+        x_object.bbox[0] as f64,
+        x_object.bbox[1] as f64,
+        x_object.bbox[2] as f64,
+        x_object.bbox[3] as f64,
+    );
+    if context.is_clipped((context.get().ctm * x_object.matrix).transform_rect_bbox(bbox)) {
+        return;
+    }
+
+    let id = x_object.dict.obj_id();
+
+    if !context.begin_nested_interpretation(id) {
         return;
     }
 
@@ -118,11 +185,11 @@ pub(crate) fn draw_form_xobject<'a, 'b>(
         if has_oc {
             context.ocg_state.end_marked_content();
         }
-        context.end_nested_interpretation();
+        context.end_nested_interpretation(id);
         return;
     }
 
-    let iter = TypedIter::new(x_object.decoded.as_ref());
+    let iter = TypedIter::new(&x_object.decoded);
 
     context.path_mut().truncate(0);
     context.save_state();
@@ -130,25 +197,32 @@ pub(crate) fn draw_form_xobject<'a, 'b>(
     context.push_root_transform();
 
     if x_object.is_transparency_group {
-        device.push_transparency_group(
-            context.get().graphics_state.non_stroke_alpha,
-            std::mem::take(&mut context.get_mut().graphics_state.soft_mask),
-            std::mem::take(&mut context.get_mut().graphics_state.blend_mode),
-        );
+        // Groups without a color space are blended in the one of their parent.
+        if let Some(color_space) = x_object.color_space(context) {
+            context.get_mut().graphics_state.blending_color_space = Some(color_space);
+        }
+
+        let opacity = context.get().graphics_state.non_stroke_alpha;
+        let soft_mask = std::mem::take(&mut context.get_mut().graphics_state.soft_mask);
+        let blend_mode = std::mem::take(&mut context.get_mut().graphics_state.blend_mode);
+
+        if let Some(color_space) = &context.get().graphics_state.blending_color_space {
+            device.push_transparency_group_with_color_space(
+                opacity,
+                soft_mask,
+                blend_mode,
+                color_space,
+            );
+        } else {
+            device.push_transparency_group(opacity, soft_mask, blend_mode);
+        }
 
         context.get_mut().graphics_state.non_stroke_alpha = 1.0;
         context.get_mut().graphics_state.stroke_alpha = 1.0;
     }
 
     device.push_clip_path(&ClipPath {
-        path: context.get().ctm
-            * Rect::new(
-                x_object.bbox[0] as f64,
-                x_object.bbox[1] as f64,
-                x_object.bbox[2] as f64,
-                x_object.bbox[3] as f64,
-            )
-            .to_path(0.1),
+        path: context.get().ctm * bbox.to_path(0.1),
         fill: FillRule::NonZero,
     });
 
@@ -172,15 +246,35 @@ pub(crate) fn draw_form_xobject<'a, 'b>(
         context.ocg_state.end_marked_content();
     }
 
-    context.end_nested_interpretation();
+    context.end_nested_interpretation(id);
 }
 
-pub(crate) fn draw_image_xobject<'a, 'b>(
+/// Draw an image, passing it to the device with `draw`, which is either
+/// [`Device::draw_image`] or [`Device::draw_xobject_image`].
+pub(crate) fn draw_image_xobject<'a, 'b, D: Device<'a>>(
     x_object: &ImageXObject<'b>,
     context: &mut Context<'a>,
-    device: &mut impl Device<'a>,
+    device: &mut D,
+    draw: impl FnOnce(&mut D, Image<'a, 'b>, ImageDrawProps<'a>),
 ) {
-    if !context.ocg_state.is_visible() {
+    context.measure(Phase::Images, |context| {
+        draw_image_xobject_impl(x_object, context, device, draw);
+    });
+}
+
+fn draw_image_xobject_impl<'a, 'b, D: Device<'a>>(
+    x_object: &ImageXObject<'b>,
+    context: &mut Context<'a>,
+    device: &mut D,
+    draw: impl FnOnce(&mut D, Image<'a, 'b>, ImageDrawProps<'a>),
+) {
+    // Images are drawn into the unit square.
+    let bounds = context
+        .get()
+        .ctm
+        .transform_rect_bbox(Rect::new(0.0, 0.0, 1.0, 1.0));
+
+    if !context.ocg_state.is_visible() || context.is_clipped(bounds) {
         return;
     }
 
@@ -222,16 +316,44 @@ pub(crate) fn draw_image_xobject<'a, 'b>(
         blend_mode,
     );
 
+    let mut x_object = x_object.clone();
+    x_object.interpolation_override = context.settings.image_interpolation;
+    x_object.max_pixels = context.settings.max_image_pixels;
+    x_object.image_cache = Some((
+        context.interpreter_cache.image_cache.clone(),
+        context.settings.image_cache_max_bytes,
+    ));
+
+    // The intent of the image takes precedence over the one of the graphics state.
+    let rendering_intent = x_object
+        .stream
+        .dict()
+        .get::<Name<'_>>(INTENT)
+        .map(|n| RenderingIntent::from_name(&n))
+        .or(context.get().graphics_state.rendering_intent);
+
+    if let Some(intent) = rendering_intent {
+        x_object.rendering_intent = Some(intent);
+        x_object.color_space = x_object
+            .color_space
+            .map(|cs| cs.with_rendering_intent(intent));
+    }
+
     let image = if x_object.is_mask {
         Image::Stencil(StencilImage {
             paint: context.get_paint(false),
-            image_xobject: x_object.clone(),
+            source: ImageSource::XObject(x_object),
         })
     } else {
-        Image::Raster(RasterImage(x_object.clone()))
+        Image::Raster(RasterImage(ImageSource::XObject(x_object)))
     };
 
-    device.draw_image(
+    if let Some(stats) = context.stats_collector() {
+        stats.record_image();
+    }
+
+    draw(
+        device,
         image,
         ImageDrawProps {
             transform,
@@ -248,7 +370,7 @@ pub(crate) fn draw_image_xobject<'a, 'b>(
     }
 }
 
-fn xobject_oc(dict: &Dict<'_>, context: &mut Context<'_>) -> bool {
+pub(crate) fn xobject_oc(dict: &Dict<'_>, context: &mut Context<'_>) -> bool {
     let Some(oc_dict) = dict.get::<Dict<'_>>(OC) else {
         return false;
     };
@@ -269,6 +391,14 @@ pub(crate) struct ImageXObject<'a> {
     color_space: Option<ColorSpace>,
     cache: Cache,
     interpolate: bool,
+    /// Overrides the `Interpolate` flag of the image and its soft mask.
+    interpolation_override: Option<bool>,
+    /// The maximum number of pixels the decoded image should have.
+    max_pixels: Option<u64>,
+    /// The cache for the decoded image and its memory limit.
+    image_cache: Option<(ImageCache, usize)>,
+    /// The rendering intent, if one was set explicitly.
+    rendering_intent: Option<RenderingIntent>,
     is_mask: bool,
     is_stencil_mask: bool,
     stream: Stream<'a>,
@@ -332,6 +462,10 @@ impl<'a> ImageXObject<'a> {
             warning_sink: warning_sink.clone(),
             transfer_function,
             interpolate,
+            interpolation_override: None,
+            max_pixels: None,
+            image_cache: None,
+            rendering_intent: None,
             stream: stream.clone(),
             is_mask,
             is_stencil_mask,
@@ -343,7 +477,17 @@ impl<'a> ImageXObject<'a> {
             return None;
         }
 
-        decode_mask(self, target_dimension)
+        let mut decoded = self.cached(
+            target_dimension,
+            || decode_mask(self, target_dimension),
+            |d| d.luma.data.len(),
+        )?;
+
+        if let Some(interpolate) = self.interpolation_override {
+            decoded.luma.interpolate = interpolate;
+        }
+
+        Some(decoded)
     }
 
     pub(crate) fn decoded_raster(
@@ -354,7 +498,59 @@ impl<'a> ImageXObject<'a> {
             return None;
         }
 
-        decode_raster(self, target_dimension)
+        let mut decoded = self.cached(
+            target_dimension,
+            || decode_raster(self, target_dimension),
+            |d| {
+                let image_bytes = match &d.image {
+                    ImageData::Rgb(rgb) => rgb.data.len(),
+                    ImageData::Luma(luma) => luma.data.len(),
+                };
+
+                image_bytes + d.alpha.as_ref().map_or(0, |a| a.data.len())
+            },
+        )?;
+
+        if let Some(interpolate) = self.interpolation_override {
+            match &mut decoded.image {
+                ImageData::Rgb(rgb) => rgb.interpolate = interpolate,
+                ImageData::Luma(luma) => luma.interpolate = interpolate,
+            }
+
+            if let Some(alpha) = &mut decoded.alpha {
+                alpha.interpolate = interpolate;
+            }
+        }
+
+        Some(decoded)
+    }
+
+    /// Decode the mask in bands of at most `band_height` rows, without caching it.
+    pub(crate) fn decoded_mask_rows(
+        &self,
+        target_dimension: Option<(u32, u32)>,
+        band_height: u32,
+        func: impl FnMut(u32, LumaData),
+    ) -> Option<()> {
+        if !self.is_mask {
+            return None;
+        }
+
+        decode_mask_rows(self, target_dimension, band_height, func)
+    }
+
+    /// Decode the image in bands of at most `band_height` rows, without caching it.
+    pub(crate) fn decoded_raster_rows(
+        &self,
+        target_dimension: Option<(u32, u32)>,
+        band_height: u32,
+        func: impl FnMut(u32, ImageData, Option<&LumaData>),
+    ) -> Option<()> {
+        if self.is_mask {
+            return None;
+        }
+
+        decode_raster_rows(self, target_dimension, band_height, func)
     }
 
     pub(crate) fn width(&self) -> u32 {
@@ -369,6 +565,60 @@ impl<'a> ImageXObject<'a> {
         &self.stream
     }
 
+    /// Decode the image, or take it from the image cache.
+    fn cached<T: Clone + Send + Sync + 'static>(
+        &self,
+        target_dimension: Option<(u32, u32)>,
+        decode: impl FnOnce() -> Option<T>,
+        size: impl FnOnce(&T) -> usize,
+    ) -> Option<T> {
+        // Only images that are objects of their own are cached, inline images
+        // can't be shared anyway. Transfer functions are part of the graphics
+        // state, so the decoded data depends on where the image is drawn.
+        match &self.image_cache {
+            Some((cache, max_bytes))
+                if self.transfer_function.is_none() && self.stream.dict().obj_id().is_some() =>
+            {
+                let key = hash128(&(
+                    self.object_key(),
+                    self.is_mask,
+                    target_dimension,
+                    self.max_pixels,
+                    self.rendering_intent,
+                ));
+
+                cache.0.get_or_insert_with(key, *max_bytes, decode, size)
+            }
+            _ => decode(),
+        }
+    }
+
+    /// Return a key that identifies the image object.
+    ///
+    /// Unlike the cache key, this distinguishes images whose dictionaries happen
+    /// to be identical.
+    pub(crate) fn object_key(&self) -> u128 {
+        match self.stream.dict().obj_id() {
+            Some(id) => hash128(&id),
+            None => hash128(&(self.stream.cache_key(), self.stream.raw_data().as_ref())),
+        }
+    }
+
+    pub(crate) fn color_space(&self) -> Option<&ColorSpace> {
+        self.color_space.as_ref()
+    }
+
+    pub(crate) fn rendering_intent(&self) -> RenderingIntent {
+        self.rendering_intent.unwrap_or_default()
+    }
+
+    pub(crate) fn bits_per_component(&self) -> Option<u8> {
+        let dict = self.stream.dict();
+
+        dict.get::<u8>(BPC)
+            .or_else(|| dict.get::<u8>(BITS_PER_COMPONENT))
+    }
+
     fn has_mask(&self) -> bool {
         let dict = self.stream.dict();
 
@@ -376,10 +626,13 @@ impl<'a> ImageXObject<'a> {
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct DecodedMask {
     pub(crate) luma: LumaData,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct DecodedRaster {
     pub(crate) image: ImageData,
     pub(crate) alpha: Option<LumaData>,
@@ -400,30 +653,49 @@ fn decode_context<'a>(
     target_dimension: Option<(u32, u32)>,
 ) -> Option<DecodeContext<'a>> {
     let dict = obj.stream.dict();
-    let dict_bpc = dict
-        .get::<u8>(BPC)
-        .or_else(|| dict.get::<u8>(BITS_PER_COMPONENT));
+    let dict_bpc = obj.bits_per_component();
     let color_space = obj.color_space.clone();
     let is_indexed = obj.color_space.as_ref().is_some_and(|cs| cs.is_indexed());
 
+    // Some decoders (like the ones for JPX and Flate) can directly decode a
+    // reduced resolution, so let them know if the image is too large.
+    let subsampling = obj
+        .max_pixels
+        .and_then(|max_pixels| subsampling_factor(obj.width, obj.height, max_pixels));
+    let budget_dimension =
+        subsampling.map(|factor| (obj.width.div_ceil(factor), obj.height.div_ceil(factor)));
+    let target_dimension = match (target_dimension, budget_dimension) {
+        (Some((w1, h1)), Some((w2, h2))) => Some((w1.min(w2), h1.min(h2))),
+        (target, budget) => target.or(budget),
+    };
+
     let decode_params = ImageDecodeParams {
         is_indexed,
         bpc: dict_bpc,
         num_components: color_space.as_ref().map(|c| c.num_components()),
         target_dimension,
+        subsampling,
         width: obj.width,
         height: obj.height,
     };
 
-    let decoded = obj
+    let mut decoded = obj
         .stream
         .decoded_image(&decode_params)
-        .map_err(|_| (obj.warning_sink)(InterpreterWarning::ImageDecodeFailure))
+        .map_err(|_| {
+            (obj.warning_sink)(
+                InterpreterWarning::ImageDecodeFailure {
+                    image: obj.stream.dict().obj_id(),
+                    filter: obj.stream.filters().last().map(|f| f.name().to_string()),
+                }
+                .into(),
+            );
+        })
         .ok()?;
 
     let (mut scale_x, mut scale_y) = (1.0, 1.0);
 
-    let (width, height) = decoded
+    let (mut width, mut height) = decoded
         .image_data
         .as_ref()
         .map(|d| {
@@ -460,11 +732,47 @@ fn decode_context<'a>(
         .or(dict_bpc)
         .unwrap_or(fallback_bpc);
 
+    // Other decoders return the image in full resolution, so it's at least
+    // downsampled before being converted further.
+    if let Some(max_pixels) = obj.max_pixels
+        && let Some(factor) = subsampling_factor(width, height, max_pixels)
+    {
+        let num_components = color_space.num_components() as usize;
+        decoded.data = Cow::Owned(subsample(
+            &decoded.data,
+            width,
+            height,
+            num_components,
+            bits_per_component,
+            factor,
+        )?);
+
+        if let Some(alpha) = decoded.image_data.as_mut().and_then(|i| i.alpha.as_mut()) {
+            *alpha = subsample(alpha, width, height, 1, 8, factor)?;
+        }
+
+        width = width.div_ceil(factor);
+        height = height.div_ceil(factor);
+        scale_x = obj.width as f32 / width as f32;
+        scale_y = obj.height as f32 / height as f32;
+    }
+
+    let default_decode_arr = color_space.default_decode_arr(bits_per_component as f32);
     let decode_arr = dict
         .get::<Array<'_>>(D)
         .or_else(|| dict.get::<Array<'_>>(DECODE))
         .map(|a| a.iter::<(f32, f32)>().collect::<SmallVec<_>>())
-        .unwrap_or(color_space.default_decode_arr(bits_per_component as f32));
+        // Otherwise, the components of different pixels would get mixed up.
+        .filter(|d| {
+            let valid = d.len() == default_decode_arr.len();
+
+            if !valid {
+                warn!("ignoring decode array with {} entries", d.len() * 2);
+            }
+
+            valid
+        })
+        .unwrap_or(default_decode_arr);
 
     Some(DecodeContext {
         decoded,
@@ -502,7 +810,7 @@ fn decode_mask(
 
     Some(DecodedMask {
         luma: LumaData {
-            data,
+            data: data.into(),
             width: ctx.width,
             height,
             interpolate: obj.interpolate,
@@ -511,13 +819,188 @@ fn decode_mask(
     })
 }
 
+/// Decode the mask and convert it in bands of at most `band_height` rows,
+/// calling `func` with the index of the first row of each band.
+fn decode_mask_rows(
+    obj: &ImageXObject<'_>,
+    target_dimension: Option<(u32, u32)>,
+    band_height: u32,
+    mut func: impl FnMut(u32, LumaData),
+) -> Option<()> {
+    let ctx = decode_context(obj, target_dimension)?;
+    let row_len = (ctx.width as usize
+        * ctx.color_space.num_components() as usize
+        * ctx.bits_per_component as usize)
+        .div_ceil(8);
+    let data = &ctx.decoded.data;
+
+    for y in (0..ctx.height).step_by(band_height.max(1) as usize) {
+        let mut rows = band_height.max(1).min(ctx.height - y);
+        let start = (y as usize * row_len).min(data.len());
+        let end = (start + rows as usize * row_len).min(data.len());
+
+        let Some(data) = decode_mask_bytes(
+            Cow::Borrowed(&data[start..end]),
+            ctx.width,
+            &mut rows,
+            &ctx.color_space,
+            ctx.bits_per_component,
+            &ctx.decode_arr,
+            obj.is_stencil_mask,
+        ) else {
+            break;
+        };
+
+        func(
+            y,
+            LumaData {
+                data: data.into(),
+                width: ctx.width,
+                height: rows,
+                interpolate: obj.interpolation_override.unwrap_or(obj.interpolate),
+                scale_factors: ctx.scale_factors,
+            },
+        );
+    }
+
+    Some(())
+}
+
 fn decode_raster(
     obj: &ImageXObject<'_>,
     target_dimension: Option<(u32, u32)>,
 ) -> Option<DecodedRaster> {
     let mut ctx = decode_context(obj, target_dimension)?;
+
+    // Color key masks apply to the original color components of the image, so
+    // they need to be resolved before the data is converted.
+    let color_key_alpha = resolve_color_key_alpha(obj, &ctx);
+    let matte = resolve_matte(obj, &ctx.color_space, target_dimension)
+        .filter(|(alpha, _)| alpha.width == ctx.width && alpha.height == ctx.height);
+
+    let data = core::mem::take(&mut ctx.decoded.data);
+    let image = convert_rows(
+        obj,
+        &ctx,
+        data,
+        ctx.height,
+        matte
+            .as_ref()
+            .map(|(alpha, matte)| (alpha.data.as_ref(), matte)),
+    )?;
+    let mut height = image.height();
+
+    let alpha = if let Some((alpha, _)) = matte {
+        Some(alpha)
+    } else {
+        // Use flatten here, so in case the alpha channel is invalid we can still
+        // return the main image (see PDFJS-19611).
+        resolve_alpha(
+            obj,
+            &mut ctx.decoded,
+            color_key_alpha,
+            ctx.width,
+            &mut height,
+            ctx.scale_factors,
+            target_dimension,
+        )
+        .flatten()
+    };
+
+    Some(DecodedRaster { image, alpha })
+}
+
+/// Decode the image and convert it in bands of at most `band_height` rows,
+/// calling `func` with the index of the first row of each band.
+fn decode_raster_rows(
+    obj: &ImageXObject<'_>,
+    target_dimension: Option<(u32, u32)>,
+    band_height: u32,
+    mut func: impl FnMut(u32, ImageData, Option<&LumaData>),
+) -> Option<()> {
+    let mut ctx = decode_context(obj, target_dimension)?;
+
+    let color_key_alpha = resolve_color_key_alpha(obj, &ctx);
+    let mut matte = resolve_matte(obj, &ctx.color_space, target_dimension)
+        .filter(|(alpha, _)| alpha.width == ctx.width && alpha.height == ctx.height);
+
     let mut height = ctx.height;
+    let mut alpha = if matte.is_some() {
+        None
+    } else {
+        resolve_alpha(
+            obj,
+            &mut ctx.decoded,
+            color_key_alpha,
+            ctx.width,
+            &mut height,
+            ctx.scale_factors,
+            target_dimension,
+        )
+        .flatten()
+    };
+
+    if let Some(interpolate) = obj.interpolation_override {
+        for alpha in alpha
+            .iter_mut()
+            .chain(matte.iter_mut().map(|(alpha, _)| alpha))
+        {
+            alpha.interpolate = interpolate;
+        }
+    }
+
+    let alpha = alpha.as_ref().or(matte.as_ref().map(|(alpha, _)| alpha));
+
+    // Rows always start at a byte boundary.
+    let row_len = (ctx.width as usize
+        * ctx.color_space.num_components() as usize
+        * ctx.bits_per_component as usize)
+        .div_ceil(8);
+    let data = &ctx.decoded.data;
+
+    for y in (0..ctx.height).step_by(band_height.max(1) as usize) {
+        let rows = band_height.max(1).min(ctx.height - y);
+        let start = (y as usize * row_len).min(data.len());
+        let end = (start + rows as usize * row_len).min(data.len());
+        let matte = matte.as_ref().map(|(alpha, matte)| {
+            let width = ctx.width as usize;
+            (
+                &alpha.data[y as usize * width..][..rows as usize * width],
+                matte,
+            )
+        });
+
+        // Like for the whole image, bands that are completely missing from the
+        // data end the image, unless they are padded during the conversion.
+        let Some(mut image) =
+            convert_rows(obj, &ctx, Cow::Borrowed(&data[start..end]), rows, matte)
+        else {
+            break;
+        };
+
+        if let Some(interpolate) = obj.interpolation_override {
+            match &mut image {
+                ImageData::Rgb(rgb) => rgb.interpolate = interpolate,
+                ImageData::Luma(luma) => luma.interpolate = interpolate,
+            }
+        }
+
+        func(y, image, alpha);
+    }
+
+    Some(())
+}
 
+/// Convert `height` rows of the decoded image data to RGB or luma data.
+///
+/// The returned image might have fewer rows if the data is too short.
+fn convert_rows(
+    obj: &ImageXObject<'_>,
+    ctx: &DecodeContext<'_>,
+    mut data: Cow<'_, [u8]>,
+    mut height: u32,
+    matte: Option<(&[u8], &ColorComponents)>,
+) -> Option<ImageData> {
     let is_default_decode = ctx.decode_arr
         == ctx
             .color_space
@@ -527,33 +1010,28 @@ fn decode_raster(
             .color_space
             .inverted_default_decode_arr(ctx.bits_per_component as f32);
 
-    let image_data = if ctx.bits_per_component == 8
+    if ctx.bits_per_component == 8
         && ctx.color_space.supports_u8()
         && obj.transfer_function.is_none()
         && (is_default_decode || is_inverted_default_decode)
+        && matte.is_none()
     {
         // This is actually the most common case, where the PDF is embedded
         // in such a way where we don't need to decode. In this case,
         // we can prevent the round-trip from f32 back to u8 and just return
         // the raw decoded data, which will already be in
         // RGB8/gray-scale with values between 0 and 255.
-        fix_image_length(
-            ctx.decoded.data.to_mut(),
-            ctx.width,
-            &mut height,
-            0,
-            &ctx.color_space,
-        )?;
+        fix_image_length(data.to_mut(), ctx.width, &mut height, 0, &ctx.color_space)?;
 
         if is_inverted_default_decode {
-            for b in ctx.decoded.data.to_mut() {
+            for b in data.to_mut() {
                 *b = 255 - *b;
             }
         }
 
         if ctx.color_space.is_device_gray() {
             Some(ImageData::Luma(LumaData {
-                data: core::mem::take(&mut ctx.decoded.data).into_owned(),
+                data: data.into(),
                 width: ctx.width,
                 height,
                 interpolate: obj.interpolate,
@@ -561,7 +1039,7 @@ fn decode_raster(
             }))
         } else if ctx.color_space.is_device_rgb() {
             Some(ImageData::Rgb(RgbData {
-                data: core::mem::take(&mut ctx.decoded.data).into_owned(),
+                data: data.into(),
                 width: ctx.width,
                 height,
                 interpolate: obj.interpolate,
@@ -569,11 +1047,10 @@ fn decode_raster(
             }))
         } else {
             let mut output_buf = vec![0; ctx.width as usize * height as usize * 3];
-            ctx.color_space
-                .convert_u8(&ctx.decoded.data, &mut output_buf)?;
+            ctx.color_space.convert_u8(&data, &mut output_buf)?;
 
             Some(ImageData::Rgb(RgbData {
-                data: output_buf,
+                data: output_buf.into(),
                 width: ctx.width,
                 height,
                 interpolate: obj.interpolate,
@@ -582,7 +1059,7 @@ fn decode_raster(
         }
     } else {
         let components = get_components(
-            &ctx.decoded.data,
+            &data,
             ctx.width,
             height,
             &ctx.color_space,
@@ -598,73 +1075,22 @@ fn decode_raster(
 
         fix_image_length(&mut f32_data, ctx.width, &mut height, 0.0, &ctx.color_space)?;
 
-        let mut rgb_data = get_rgb_data(
+        if let Some((alpha, matte)) = matte {
+            unpremultiply(&mut f32_data, alpha, matte, &ctx.decode_arr);
+        }
+
+        let rgb_data = get_rgb_data(
             &f32_data,
             ctx.width,
             height,
             ctx.scale_factors,
             &ctx.color_space,
             obj.interpolate,
+            obj.transfer_function.as_ref(),
         );
 
-        if let Some(transfer_function) = &obj.transfer_function
-            && let Some(rgb_data) = &mut rgb_data
-        {
-            let apply_single = |data: u8, function: &Function| {
-                function
-                    .eval(smallvec![data as f32 / 255.0])
-                    .and_then(|v| v.first().copied())
-                    .map(|v| (v * 255.0 + 0.5) as u8)
-                    .unwrap_or(data)
-            };
-
-            match transfer_function {
-                ActiveTransferFunction::Single(s) => {
-                    for data in &mut rgb_data.data {
-                        *data = apply_single(*data, s);
-                    }
-                }
-                ActiveTransferFunction::Four(f) => {
-                    for data in rgb_data.data.chunks_exact_mut(3) {
-                        data[0] = apply_single(data[0], &f[0]);
-                        data[1] = apply_single(data[1], &f[1]);
-                        data[2] = apply_single(data[2], &f[2]);
-                    }
-                }
-            }
-        }
-
         rgb_data.map(ImageData::Rgb)
-    };
-
-    let mut image = image_data?;
-
-    let alpha = if let Some((alpha, matte_rgb)) =
-        resolve_matte(obj, &ctx.color_space, target_dimension)
-        && alpha.width == ctx.width
-        && alpha.height == height
-    {
-        unpremultiply(&mut image, &alpha.data, &matte_rgb);
-
-        Some(alpha)
-    } else {
-        // Use flatten here, so in case the alpha channel is invalid we can still
-        // return the main image (see PDFJS-19611).
-        resolve_alpha(
-            obj,
-            &mut ctx.decoded,
-            Some(&image),
-            &ctx.color_space,
-            ctx.bits_per_component,
-            ctx.width,
-            &mut height,
-            ctx.scale_factors,
-            target_dimension,
-        )
-        .flatten()
-    };
-
-    Some(DecodedRaster { image, alpha })
+    }
 }
 
 fn decode_mask_bytes(
@@ -723,9 +1149,7 @@ fn decode_mask_bytes(
 fn resolve_alpha(
     obj: &ImageXObject<'_>,
     decoded: &mut FilterResult<'_>,
-    image_data: Option<&ImageData>,
-    color_space: &ColorSpace,
-    bits_per_component: u8,
+    color_key_alpha: Option<LumaData>,
     width: u32,
     height: &mut u32,
     scale_factors: (f32, f32),
@@ -740,7 +1164,7 @@ fn resolve_alpha(
             fix_image_length(&mut data, width, height, 0, &ColorSpace::device_gray())?;
 
             Some(LumaData {
-                data,
+                data: data.into(),
                 width,
                 height: *height,
                 interpolate: obj.interpolate,
@@ -754,96 +1178,107 @@ fn resolve_alpha(
         .get::<Stream<'_>>(SMASK)
         .or_else(|| dict.get::<Stream<'_>>(MASK))
     {
-        let obj = ImageXObject::new(&s_mask, |_| None, &obj.warning_sink, &obj.cache, true, None)?;
-
-        decode_mask(&obj, target_dimension).map(|decoded| decoded.luma)
-    } else if let Some(color_key_mask) = dict.get::<SmallVec<[u16; 4]>>(MASK) {
-        let mut mask_data = vec![];
+        let mut mask_obj =
+            ImageXObject::new(&s_mask, |_| None, &obj.warning_sink, &obj.cache, true, None)?;
+        mask_obj.max_pixels = obj.max_pixels;
 
-        // TODO: Make this less ugly.
-        let raw_data = match image_data {
-            Some(ImageData::Luma(d)) if color_space.num_components() == 1 => &d.data,
-            Some(ImageData::Rgb(d)) if color_space.num_components() == 3 => &d.data,
-            _ => decoded.data.as_ref(),
-        };
+        decode_mask(&mask_obj, target_dimension).map(|decoded| decoded.luma)
+    } else {
+        color_key_alpha
+    };
 
-        let components = get_components(raw_data, width, *height, color_space, bits_per_component)?;
+    Some(alpha)
+}
 
-        for pixel in components.chunks_exact(color_space.num_components() as usize) {
-            let mut mask_val = 0;
+/// Resolve the alpha channel of an image with a color key mask (`Mask` entry
+/// with an array of ranges).
+///
+/// A pixel is masked out if each of its color components lies in the
+/// corresponding range.
+fn resolve_color_key_alpha(obj: &ImageXObject<'_>, ctx: &DecodeContext<'_>) -> Option<LumaData> {
+    let color_key_mask = obj.stream.dict().get::<SmallVec<[u16; 4]>>(MASK)?;
+    let num_components = ctx.color_space.num_components() as usize;
 
-            for (component, min_max) in pixel.iter().zip(color_key_mask.chunks_exact(2)) {
-                if *component > min_max[1] || *component < min_max[0] {
-                    mask_val = 255;
-                }
-            }
+    if num_components == 0 {
+        return None;
+    }
 
-            mask_data.push(mask_val);
-        }
+    let components = get_components(
+        &ctx.decoded.data,
+        ctx.width,
+        ctx.height,
+        &ctx.color_space,
+        ctx.bits_per_component,
+    )?;
 
-        fix_image_length(&mut mask_data, width, height, 0, &ColorSpace::device_gray())?;
+    let mut mask_data = components
+        .chunks_exact(num_components)
+        .map(|pixel| {
+            let masked = pixel
+                .iter()
+                .zip(color_key_mask.chunks_exact(2))
+                .all(|(component, min_max)| (min_max[0]..=min_max[1]).contains(component));
 
-        Some(LumaData {
-            data: mask_data,
-            width,
-            height: *height,
-            interpolate: obj.interpolate,
-            scale_factors,
+            if masked { 0 } else { 255 }
         })
-    } else {
-        None
-    };
+        .collect::<Vec<_>>();
 
-    Some(alpha)
+    let mut height = ctx.height;
+    fix_image_length(
+        &mut mask_data,
+        ctx.width,
+        &mut height,
+        0,
+        &ColorSpace::device_gray(),
+    )?;
+
+    Some(LumaData {
+        data: mask_data.into(),
+        width: ctx.width,
+        height,
+        interpolate: obj.interpolate,
+        scale_factors: ctx.scale_factors,
+    })
 }
 
 fn resolve_matte(
     obj: &ImageXObject<'_>,
     color_space: &ColorSpace,
     target_dimension: Option<(u32, u32)>,
-) -> Option<(LumaData, [u8; 3])> {
+) -> Option<(LumaData, ColorComponents)> {
     let dict = obj.stream.dict();
     let s_mask = dict.get::<Stream<'_>>(SMASK)?;
     let matte = s_mask.dict().get::<ColorComponents>(MATTE)?;
 
-    if matte.len() != color_space.num_components() as usize {
+    // Pre-blending is only meaningful for actual color components, not for
+    // indices into a palette.
+    if matte.len() != color_space.num_components() as usize || color_space.is_indexed() {
         return None;
     }
 
-    // In theory, matte needs to be applied in the image's original color space,
-    // but we always do it in RGB for now.
-    let mut matte_rgb = [0_u8; 3];
-    color_space.convert_f32(&matte, &mut matte_rgb, false);
-
-    let mask_obj = ImageXObject::new(&s_mask, |_| None, &obj.warning_sink, &obj.cache, true, None)?;
+    let mut mask_obj =
+        ImageXObject::new(&s_mask, |_| None, &obj.warning_sink, &obj.cache, true, None)?;
+    mask_obj.max_pixels = obj.max_pixels;
     let alpha = decode_mask(&mask_obj, target_dimension)?.luma;
 
-    Some((alpha, matte_rgb))
+    Some((alpha, matte))
 }
 
-fn unpremultiply(image: &mut ImageData, alpha: &[u8], matte_rgb: &[u8]) {
-    match image {
-        ImageData::Rgb(rgb) => {
-            for (pixel, &a) in rgb.data.chunks_exact_mut(3).zip(alpha.iter()) {
-                if a == 0 {
-                    continue;
-                }
-                let inv_alpha = 255.0 / a as f32;
-                for (c, &m) in pixel.iter_mut().zip(matte_rgb.iter()) {
-                    let m = m as f32;
-                    *c = (m + (*c as f32 - m) * inv_alpha) as u8;
-                }
-            }
+/// Undo the pre-blending of the color components of an image with the matte
+/// color of its soft mask.
+///
+/// This happens in the original color space of the image, before converting
+/// it to RGB.
+fn unpremultiply(components: &mut [f32], alpha: &[u8], matte: &[f32], decode: &[(f32, f32)]) {
+    for (pixel, &a) in components.chunks_exact_mut(matte.len()).zip(alpha.iter()) {
+        if a == 0 {
+            continue;
         }
-        ImageData::Luma(luma) => {
-            let m = matte_rgb[0] as f32;
-            for (c, &a) in luma.data.iter_mut().zip(alpha.iter()) {
-                if a == 0 {
-                    continue;
-                }
-                let inv_alpha = 255.0 / a as f32;
-                *c = (m + (*c as f32 - m) * inv_alpha) as u8;
-            }
+
+        let a = a as f32 / 255.0;
+
+        for ((c, &m), &(d_min, d_max)) in pixel.iter_mut().zip(matte).zip(decode) {
+            *c = (m + (*c - m) / a).clamp(d_min.min(d_max), d_min.max(d_max));
         }
     }
 }
@@ -855,6 +1290,7 @@ fn get_rgb_data(
     scale_factors: (f32, f32),
     cs: &ColorSpace,
     interpolate: bool,
+    transfer_function: Option<&ActiveTransferFunction>,
 ) -> Option<RgbData> {
     // To prevent a panic when calling the `chunks` method.
     if cs.num_components() == 0 {
@@ -864,8 +1300,20 @@ fn get_rgb_data(
     let mut output = vec![0; width as usize * height as usize * 3];
     cs.convert_f32(decoded, &mut output, false);
 
+    if let Some(transfer_function) = transfer_function {
+        let apply = |data: u8, channel: usize| {
+            (transfer_function.apply_channel(channel, data as f32 / 255.0) * 255.0 + 0.5) as u8
+        };
+
+        for data in output.chunks_exact_mut(3) {
+            data[0] = apply(data[0], 0);
+            data[1] = apply(data[1], 1);
+            data[2] = apply(data[2], 2);
+        }
+    }
+
     Some(RgbData {
-        data: output,
+        data: output.into(),
         width,
         height,
         interpolate,
@@ -908,6 +1356,60 @@ fn fix_image_length<T: Copy>(
     }
 }
 
+/// Return the factor by which an image needs to be subsampled so that it has
+/// at most `max_pixels` pixels, or `None` if it already fits.
+fn subsampling_factor(width: u32, height: u32, max_pixels: u64) -> Option<u32> {
+    let num_pixels = |factor: u32| width.div_ceil(factor) as u64 * height.div_ceil(factor) as u64;
+    let max_pixels = max_pixels.max(1);
+
+    if num_pixels(1) <= max_pixels {
+        return None;
+    }
+
+    let mut factor = ((num_pixels(1) as f64 / max_pixels as f64).sqrt().ceil() as u32).max(2);
+
+    while num_pixels(factor) > max_pixels {
+        factor += 1;
+    }
+
+    Some(factor)
+}
+
+/// Only keep every `factor`-th pixel in each direction of the (still encoded)
+/// image data.
+fn subsample(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    num_components: usize,
+    bits_per_component: u8,
+    factor: u32,
+) -> Option<Vec<u8>> {
+    let pixel_bits = num_components * bits_per_component as usize;
+    let row_len = (width as usize * pixel_bits).div_ceil(8);
+    let new_row_len = (width.div_ceil(factor) as usize * pixel_bits).div_ceil(8);
+    let mut output = vec![0; new_row_len * height.div_ceil(factor) as usize];
+
+    for (y, output_row) in (0..height as usize)
+        .step_by(factor as usize)
+        .zip(output.chunks_exact_mut(new_row_len))
+    {
+        // Missing data is padded with zeroes, like in `get_components`.
+        let row = data.get(y * row_len..).unwrap_or_default();
+        let mut writer = BitWriter::new(output_row, bits_per_component)?;
+
+        for x in (0..width as usize).step_by(factor as usize) {
+            let mut reader = BitReader::new_with(row, x * pixel_bits);
+
+            for _ in 0..num_components {
+                writer.write(reader.read(bits_per_component).unwrap_or(0))?;
+            }
+        }
+    }
+
+    Some(output)
+}
+
 fn get_components(
     data: &[u8],
     width: u32,
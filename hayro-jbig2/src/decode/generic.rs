@@ -301,6 +301,7 @@ pub(crate) fn decode_bitmap_mmr(bitmap: &mut Bitmap, data: &[u8]) -> Result<usiz
         // hayro-ccitt uses 1 for white, 0 for black by default, so we need to
         // invert to match JBIG2 convention.
         invert_black: true,
+        damaged_rows_before_error: 0,
     };
 
     // "An invocation of the generic region decoding procedure with MMR equal to
@@ -0,0 +1,21 @@
+//! Convert a PDF file into a simplified PDF file.
+
+use hayro_interpret::InterpreterSettings;
+use hayro_pdf::hayro_syntax::Pdf;
+use hayro_pdf::{ConvertSettings, RenderCache, convert};
+
+fn main() {
+    let pdf = std::fs::read(std::env::args().nth(1).unwrap()).unwrap();
+    let pdf = Pdf::new(pdf).unwrap();
+
+    let cache = RenderCache::new();
+    let interpreter_settings = InterpreterSettings::default();
+
+    let converted = convert(
+        pdf.pages(),
+        &cache,
+        &interpreter_settings,
+        &ConvertSettings::default(),
+    );
+    std::fs::write("converted.pdf", converted).unwrap();
+}
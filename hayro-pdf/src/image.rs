@@ -0,0 +1,236 @@
+use crate::mask::CachedMask;
+use crate::path::ContentExt;
+use crate::{Document, PdfDevice, deflate_encode};
+use hayro_interpret::{
+    BlendMode, CacheKey, FillRule, Image, ImageData, ImageDrawProps, LumaData, Paint, StencilImage,
+};
+use kurbo::{Affine, Rect, Shape};
+use pdf_writer::{Filter, Finish, Name, Ref};
+
+/// An image that has been written into the document.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct CachedImage {
+    id: Ref,
+    width: u32,
+    height: u32,
+    scale_factors: (f32, f32),
+}
+
+impl CachedImage {
+    /// Return the transform from the unit square of the image into the pixel space of
+    /// the original image.
+    fn transform(&self) -> Affine {
+        let (width, height) = (self.width as f64, self.height as f64);
+
+        // The first row of an image is at the top of its unit square, where y is 1,
+        // while pixel space is y-down.
+        Affine::scale_non_uniform(self.scale_factors.0 as f64, self.scale_factors.1 as f64)
+            * Affine::new([width, 0.0, 0.0, -height, 0.0, height])
+    }
+}
+
+impl PdfDevice<'_> {
+    pub(crate) fn draw_image<'a>(&mut self, image: Image<'a, '_>, props: &ImageDrawProps<'a>) {
+        let cache_key = image.cache_key();
+
+        match image {
+            Image::Raster(raster) => {
+                let cached = match self.document.images.get(&cache_key) {
+                    Some(cached) => *cached,
+                    None => {
+                        let mut cached = None;
+                        raster.with_rgba(
+                            |image, alpha| cached = Some(self.document.write_image(image, alpha)),
+                            None,
+                        );
+
+                        let Some(cached) = cached else {
+                            return;
+                        };
+                        self.document.images.insert(cache_key, cached);
+
+                        cached
+                    }
+                };
+
+                let mask = self.soft_mask(props.soft_mask.as_ref());
+                self.save_state(1.0, 1.0, mask, props.blend_mode);
+                self.draw_image_xobject(cached, props.transform);
+                self.content().restore_state();
+            }
+            Image::Stencil(stencil) => self.draw_stencil(&stencil, cache_key, props),
+        }
+    }
+
+    fn draw_stencil<'a>(
+        &mut self,
+        stencil: &StencilImage<'a, '_>,
+        cache_key: u128,
+        props: &ImageDrawProps<'a>,
+    ) {
+        stencil.with_stencil(
+            |luma, paint| {
+                if luma.width == 0 || luma.height == 0 {
+                    return;
+                }
+
+                let cached = match self.document.images.get(&cache_key) {
+                    Some(cached) => *cached,
+                    None => {
+                        let cached = self.document.write_image_mask(luma);
+                        self.document.images.insert(cache_key, cached);
+
+                        cached
+                    }
+                };
+
+                match paint {
+                    Paint::Color(color) => {
+                        let [r, g, b, a] = color.to_rgba().components();
+                        let mask = self.soft_mask(props.soft_mask.as_ref());
+                        self.save_state(a, 1.0, mask, props.blend_mode);
+                        self.content().set_fill_rgb(r, g, b);
+                        self.draw_image_xobject(cached, props.transform);
+                        self.content().restore_state();
+                    }
+                    Paint::Pattern(pattern) => {
+                        // Paint the pattern through a soft mask that is only visible where
+                        // the image mask is painted.
+                        self.with_group(props.soft_mask.clone(), props.blend_mode, |device| {
+                            let mask = device.stencil_mask(cached, props.transform);
+                            let bounds = Rect::new(0.0, 0.0, 1.0, 1.0).to_path(0.1);
+                            let path = props.transform * cached.transform() * bounds;
+
+                            device.paint_pattern(
+                                &path,
+                                FillRule::NonZero,
+                                pattern,
+                                Some(mask),
+                                BlendMode::Normal,
+                                false,
+                            );
+                        });
+                    }
+                }
+            },
+            None,
+        );
+    }
+
+    /// Return a luminosity mask that shows the pixels of an image mask.
+    fn stencil_mask(&mut self, image: CachedImage, transform: Affine) -> CachedMask {
+        let bbox = self.frame().bbox;
+        let frame = self.record(bbox, |device| {
+            device.content().set_fill_rgb(1.0, 1.0, 1.0);
+            device.draw_image_xobject(image, transform);
+        });
+
+        CachedMask {
+            group: self.write_form(frame, true),
+            luminosity: true,
+            backdrop: [0.0_f32; 3].map(f32::to_bits),
+            transfer_function: None,
+        }
+    }
+
+    pub(crate) fn draw_image_xobject(&mut self, image: CachedImage, transform: Affine) {
+        let name = self.frame().resources.x_objects.insert(image.id);
+        let content = self.content();
+        content.save_state();
+        content.transform_by(transform * image.transform());
+        content.x_object(Name(name.as_bytes()));
+        content.restore_state();
+    }
+}
+
+impl Document {
+    pub(crate) fn write_image(&mut self, image: ImageData, alpha: Option<LumaData>) -> CachedImage {
+        let alpha = alpha.filter(|_| self.settings.transparency);
+        let s_mask = alpha.map(|alpha| {
+            let id = self.new_ref();
+            let data = deflate_encode(&alpha.data);
+            let mut x_object = self.chunk.image_xobject(id, &data);
+            x_object.filter(Filter::FlateDecode);
+            x_object.width(alpha.width as i32);
+            x_object.height(alpha.height as i32);
+            x_object.color_space().device_gray();
+            x_object.bits_per_component(8);
+            x_object.interpolate(alpha.interpolate);
+            x_object.finish();
+
+            id
+        });
+
+        let id = self.new_ref();
+        let cached = CachedImage {
+            id,
+            width: image.width(),
+            height: image.height(),
+            scale_factors: image.scale_factors(),
+        };
+        let interpolate = image.interpolate();
+        let (data, is_gray) = match image {
+            ImageData::Rgb(rgb) => (deflate_encode(&rgb.data), false),
+            ImageData::Luma(luma) => (deflate_encode(&luma.data), true),
+        };
+
+        let mut x_object = self.chunk.image_xobject(id, &data);
+        x_object.filter(Filter::FlateDecode);
+        x_object.width(cached.width as i32);
+        x_object.height(cached.height as i32);
+
+        if is_gray {
+            x_object.color_space().device_gray();
+        } else {
+            x_object.color_space().device_rgb();
+        }
+
+        x_object.bits_per_component(8);
+        x_object.interpolate(interpolate);
+
+        if let Some(s_mask) = s_mask {
+            x_object.s_mask(s_mask);
+        }
+
+        x_object.finish();
+
+        cached
+    }
+
+    /// Write a stencil image as an image mask, which is painted with the fill color.
+    fn write_image_mask(&mut self, luma: LumaData) -> CachedImage {
+        let row_len = luma.width.div_ceil(8) as usize;
+        let mut bits = vec![0_u8; row_len * luma.height as usize];
+
+        for (row, bit_row) in luma
+            .data
+            .chunks_exact(luma.width as usize)
+            .zip(bits.chunks_exact_mut(row_len))
+        {
+            for (x, value) in row.iter().enumerate() {
+                // With the default decode array, a sample of 1 leaves the pixel unpainted.
+                if *value < 128 {
+                    bit_row[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+
+        let id = self.new_ref();
+        let data = deflate_encode(&bits);
+        let mut x_object = self.chunk.image_xobject(id, &data);
+        x_object.filter(Filter::FlateDecode);
+        x_object.width(luma.width as i32);
+        x_object.height(luma.height as i32);
+        x_object.image_mask(true);
+        x_object.bits_per_component(1);
+        x_object.interpolate(luma.interpolate);
+        x_object.finish();
+
+        CachedImage {
+            id,
+            width: luma.width,
+            height: luma.height,
+            scale_factors: luma.scale_factors,
+        }
+    }
+}
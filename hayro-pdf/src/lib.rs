@@ -0,0 +1,604 @@
+/*!
+A crate for converting PDF pages into simplified PDF files.
+
+The pages are interpreted with [`hayro-interpret`](https://crates.io/crates/hayro-interpret) and
+the resulting drawing instructions are written into a new PDF file via
+[`pdf-writer`](https://crates.io/crates/pdf-writer). The new file looks the same as the original
+one, but only uses a small set of constructs. This can be used to normalize problematic PDF files
+before passing them on to less capable PDF processors.
+
+To achieve this, the contents of the pages are simplified in the following ways:
+- Text is converted into paths, so it cannot be selected or extracted anymore. Invisible text
+  is dropped.
+- All colors are converted to `DeviceRGB`, and images are re-encoded as 8-bit images.
+- Axial, radial and mesh shadings are kept if their colors can be represented in `DeviceRGB`,
+  while all other shadings are rasterized.
+- Tiling patterns are expanded into their individual tiles, and strokes that are painted
+  with a pattern are converted into fills.
+- Transparency groups are only kept if they have an opacity, a blend mode or a soft mask.
+  Opacities, blend modes and soft masks are written as they are, so the output still requires a
+  processor that supports the transparency model of PDF 1.4. Disable
+  [`ConvertSettings::transparency`] to drop them instead, at the cost of painting transparent
+  content opaquely.
+- Annotations become part of the page contents if
+  [`InterpreterSettings::render_annotations`] is enabled, and marked content, optional content
+  and the document structure are dropped.
+
+## Safety
+This crate forbids unsafe code via a crate-level attribute.
+
+## Cargo features
+This crate has the following optional features:
+- `embed-fonts` (enabled by default): See the description of [`hayro-interpret`](https://docs.rs/hayro-interpret/latest/hayro_interpret/#cargo-features) for more information.
+- `embed-cmaps` (enabled by default): Embed the 61 predefined CMaps, which adds about ~250KB of
+  brotli-encoded data to your binary.
+- `logging`: Log warnings about malformed PDF files via the [`log`](https://crates.io/crates/log) crate.
+*/
+
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+use crate::mask::CachedMask;
+use crate::path::ContentExt;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use hayro_interpret::font::{Glyph, GlyphCache};
+use hayro_interpret::gradient::{AxialGradient, Mesh, RadialGradient};
+use hayro_interpret::hayro_syntax::page::Page;
+use hayro_interpret::util::TransformExt;
+use hayro_interpret::{
+    BlendMode, ClipPath, Context, Device, DrawMode, DrawProps, FillRule, Image, ImageDrawProps,
+    InterpreterCache, InterpreterSettings, SoftMask, interpret_page,
+};
+use kurbo::{Affine, BezPath, Rect};
+use pdf_writer::{Chunk, Content, Filter, Finish, Name, Ref};
+use rustc_hash::FxHashMap;
+
+pub use hayro_interpret;
+pub use hayro_interpret::hayro_syntax;
+
+mod image;
+mod mask;
+mod paint;
+mod path;
+
+/// A cache used by the PDF converter.
+///
+/// Ideally, such a cache should be constructed once per PDF and then reused across
+/// multiple conversion invocations on the same document.
+#[derive(Clone, Default)]
+pub struct RenderCache<'a> {
+    pub(crate) interpreter_cache: InterpreterCache<'a>,
+}
+
+impl<'a> RenderCache<'a> {
+    /// Create a new render cache.
+    pub fn new() -> Self {
+        Self {
+            interpreter_cache: InterpreterCache::new(),
+        }
+    }
+
+    /// Create a new render cache that uses the given cache for glyph outlines.
+    ///
+    /// Use this to configure the memory limit of the glyph cache.
+    pub fn with_glyph_cache(glyph_cache: GlyphCache) -> Self {
+        Self {
+            interpreter_cache: InterpreterCache::with_glyph_cache(glyph_cache),
+        }
+    }
+}
+
+/// Settings for converting pages.
+#[derive(Clone, Debug)]
+pub struct ConvertSettings {
+    /// Whether opacities, blend modes and soft masks are kept.
+    ///
+    /// They are written with the transparency model of PDF 1.4, which not all PDF
+    /// processors support. If this is disabled, they are dropped and everything is
+    /// painted opaquely instead, so pages that use transparency will look different.
+    ///
+    /// The default is `true`.
+    pub transparency: bool,
+}
+
+impl Default for ConvertSettings {
+    fn default() -> Self {
+        Self { transparency: true }
+    }
+}
+
+/// Convert the given pages into a new PDF file.
+pub fn convert<'a>(
+    pages: &[Page<'a>],
+    cache: &RenderCache<'a>,
+    interpreter_settings: &InterpreterSettings,
+    settings: &ConvertSettings,
+) -> Vec<u8> {
+    let mut document = Document::with_settings(settings.clone());
+
+    for page in pages {
+        document.add_page(page, cache, interpreter_settings);
+    }
+
+    document.finish()
+}
+
+/// A PDF file that is being written.
+///
+/// Pages can either be converted from existing pages with [`Document::add_page`], or be
+/// drawn manually by passing the device returned by [`Document::start_page`] to the
+/// interpreter. Images, shadings and soft masks are shared between all pages of the
+/// document.
+pub struct Document {
+    settings: ConvertSettings,
+    chunk: Chunk,
+    next_ref: Ref,
+    catalog_ref: Ref,
+    page_tree_ref: Ref,
+    page_refs: Vec<Ref>,
+    images: FxHashMap<u128, image::CachedImage>,
+    /// The shading objects of gradients and meshes, together with their opacity.
+    shadings: FxHashMap<u128, (Ref, f32)>,
+    tiles: FxHashMap<(u128, bool), Ref>,
+    masks: FxHashMap<(u128, [u32; 4]), CachedMask>,
+    ext_g_states: FxHashMap<mask::ExtGStateKey, Ref>,
+}
+
+impl Document {
+    /// Create a new document without any pages.
+    pub fn new() -> Self {
+        Self::with_settings(ConvertSettings::default())
+    }
+
+    /// Create a new document without any pages that uses the given settings.
+    pub fn with_settings(settings: ConvertSettings) -> Self {
+        let mut next_ref = Ref::new(1);
+        let catalog_ref = next_ref.bump();
+        let page_tree_ref = next_ref.bump();
+
+        Self {
+            settings,
+            chunk: Chunk::new(),
+            next_ref,
+            catalog_ref,
+            page_tree_ref,
+            page_refs: vec![],
+            images: FxHashMap::default(),
+            shadings: FxHashMap::default(),
+            tiles: FxHashMap::default(),
+            masks: FxHashMap::default(),
+            ext_g_states: FxHashMap::default(),
+        }
+    }
+
+    /// Convert the given page and append it to the document.
+    pub fn add_page<'a>(
+        &mut self,
+        page: &Page<'a>,
+        cache: &RenderCache<'a>,
+        interpreter_settings: &InterpreterSettings,
+    ) {
+        let (width, height) = page.render_dimensions();
+        let mut context = Context::new(
+            page.initial_transform(true).to_kurbo(),
+            Rect::new(0.0, 0.0, width as f64, height as f64),
+            &cache.interpreter_cache,
+            page.xref(),
+            interpreter_settings.clone(),
+        );
+
+        let mut device = self.start_page(width, height);
+        interpret_page(page, &mut context, &mut device);
+        device.finish();
+    }
+
+    /// Start a new page with the given dimensions and return the device that draws
+    /// into it.
+    ///
+    /// Like for the other devices, the origin of device space is in the top-left
+    /// corner of the page, with the y-axis pointing down. The page is only added to the
+    /// document once [`PdfDevice::finish`] is called.
+    pub fn start_page(&mut self, width: f32, height: f32) -> PdfDevice<'_> {
+        let bbox = Rect::new(0.0, 0.0, width as f64, height as f64);
+        let mut frame = Frame::new(bbox);
+        // Flip the y-axis, so that all following content can be written in device space.
+        frame.content.transform([1.0, 0.0, 0.0, -1.0, 0.0, height]);
+
+        PdfDevice {
+            document: self,
+            size: (width, height),
+            frames: vec![frame],
+            groups: vec![],
+        }
+    }
+
+    /// Finish the document and return the bytes of the PDF file.
+    pub fn finish(self) -> Vec<u8> {
+        let mut pdf = pdf_writer::Pdf::new();
+        pdf.catalog(self.catalog_ref).pages(self.page_tree_ref);
+        pdf.pages(self.page_tree_ref)
+            .count(self.page_refs.len() as i32)
+            .kids(self.page_refs);
+        pdf.extend(&self.chunk);
+
+        pdf.finish()
+    }
+
+    pub(crate) fn new_ref(&mut self) -> Ref {
+        self.next_ref.bump()
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A device that writes the drawing instructions it receives into a page of a
+/// [`Document`].
+pub struct PdfDevice<'d> {
+    pub(crate) document: &'d mut Document,
+    size: (f32, f32),
+    /// The content streams that are currently being written. The first one is the
+    /// content stream of the page, the other ones belong to transparency groups, soft
+    /// masks or pattern tiles.
+    pub(crate) frames: Vec<Frame>,
+    /// The transparency groups that are currently active, or `None` for groups that
+    /// have been merged into their parent.
+    groups: Vec<Option<Group>>,
+}
+
+impl PdfDevice<'_> {
+    /// Write the page into the document.
+    pub fn finish(mut self) {
+        let frame = self.frames.swap_remove(0);
+        let page_ref = self.document.new_ref();
+        let content_ref = self.document.new_ref();
+
+        let content = deflate_encode(frame.content.finish().as_slice());
+        self.document
+            .chunk
+            .stream(content_ref, &content)
+            .filter(Filter::FlateDecode);
+
+        let mut page = self.document.chunk.page(page_ref);
+        page.media_box(pdf_writer::Rect::new(0.0, 0.0, self.size.0, self.size.1));
+        page.parent(self.document.page_tree_ref);
+        page.contents(content_ref);
+        frame.resources.write(page.resources());
+        page.finish();
+
+        self.document.page_refs.push(page_ref);
+    }
+
+    pub(crate) fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    pub(crate) fn content(&mut self) -> &mut Content {
+        &mut self.frame().content
+    }
+
+    /// Run the function with a new content stream and return it.
+    pub(crate) fn record(&mut self, bbox: Rect, f: impl FnOnce(&mut Self)) -> Frame {
+        self.frames.push(Frame::new(bbox));
+        f(self);
+        self.frames.pop().unwrap()
+    }
+
+    /// Write a content stream as a form `XObject`, optionally marking it as a
+    /// transparency group.
+    pub(crate) fn write_form(&mut self, frame: Frame, transparency_group: bool) -> Ref {
+        let id = self.document.new_ref();
+        let content = deflate_encode(frame.content.finish().as_slice());
+        let bbox = frame.bbox;
+
+        let mut form = self.document.chunk.form_xobject(id, &content);
+        form.filter(Filter::FlateDecode);
+        form.bbox(pdf_writer::Rect::new(
+            bbox.x0 as f32,
+            bbox.y0 as f32,
+            bbox.x1 as f32,
+            bbox.y1 as f32,
+        ));
+
+        if transparency_group {
+            form.group().transparency().color_space().device_rgb();
+        }
+
+        frame.resources.write(form.resources());
+        form.finish();
+
+        id
+    }
+
+    /// Draw a form `XObject` with the given transform.
+    pub(crate) fn draw_form(&mut self, form: Ref, transform: Affine) {
+        let name = self.frame().resources.x_objects.insert(form);
+        let content = self.content();
+        content.save_state();
+        content.transform_by(transform);
+        content.x_object(Name(name.as_bytes()));
+        content.restore_state();
+    }
+
+    /// Save the graphics state and apply the given opacities, soft mask and blend
+    /// mode.
+    ///
+    /// The state must be restored with `restore_state` afterward. The transform
+    /// must only be changed after calling this, since soft masks are placed in the
+    /// current user space.
+    pub(crate) fn save_state(
+        &mut self,
+        fill_alpha: f32,
+        stroke_alpha: f32,
+        mask: Option<CachedMask>,
+        blend_mode: BlendMode,
+    ) {
+        self.content().save_state();
+
+        if !self.document.settings.transparency {
+            return;
+        }
+
+        if fill_alpha < 1.0
+            || stroke_alpha < 1.0
+            || mask.is_some()
+            || blend_mode != BlendMode::Normal
+        {
+            let id = self
+                .document
+                .ext_g_state(fill_alpha, stroke_alpha, mask, blend_mode);
+            let name = self.frame().resources.ext_g_states.insert(id);
+            self.content().set_parameters(Name(name.as_bytes()));
+        }
+    }
+
+    fn with_group(
+        &mut self,
+        mask: Option<SoftMask<'_>>,
+        blend_mode: BlendMode,
+        func: impl FnOnce(&mut Self),
+    ) {
+        let push_group = mask.is_some() || blend_mode != BlendMode::Normal;
+
+        if push_group {
+            self.push_transparency_group(1.0, mask, blend_mode);
+        }
+
+        func(self);
+
+        if push_group {
+            self.pop_transparency_group();
+        }
+    }
+}
+
+impl<'a> Device<'a> for PdfDevice<'_> {
+    fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        Self::draw_path(self, path, &props, draw_mode);
+    }
+
+    fn push_clip_path(&mut self, clip_path: &ClipPath) {
+        let content = self.content();
+        content.save_state();
+        content.path(&clip_path.path);
+        content.clip(clip_path.fill);
+    }
+
+    fn push_clip_rect(&mut self, rect: &Rect) {
+        let content = self.content();
+        content.save_state();
+        content.rect(
+            rect.x0 as f32,
+            rect.y0 as f32,
+            rect.width() as f32,
+            rect.height() as f32,
+        );
+        content.clip(FillRule::NonZero);
+    }
+
+    fn push_transparency_group(
+        &mut self,
+        opacity: f32,
+        mask: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+    ) {
+        // Groups that don't change how their contents are composited can be merged
+        // into their parent, and so can all groups if transparency is dropped.
+        if !self.document.settings.transparency
+            || (opacity >= 1.0 && mask.is_none() && blend_mode == BlendMode::Normal)
+        {
+            self.groups.push(None);
+
+            return;
+        }
+
+        let mask = self.soft_mask(mask.as_ref());
+        let bbox = self.frame().bbox;
+        self.groups.push(Some(Group {
+            opacity,
+            mask,
+            blend_mode,
+        }));
+        self.frames.push(Frame::new(bbox));
+    }
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        if matches!(draw_mode, DrawMode::Invisible) {
+            return;
+        }
+
+        match glyph {
+            Glyph::Outline(o) if matches!(draw_mode, DrawMode::Fill(_)) && o.is_color() => {
+                o.interpret_color(self, glyph_transform, &props);
+            }
+            Glyph::Outline(o) => {
+                Self::draw_path(self, &(glyph_transform * o.outline()), &props, draw_mode);
+            }
+            Glyph::Type3(t) => {
+                self.with_group(props.soft_mask.clone(), props.blend_mode, |d| {
+                    t.interpret(d, props.transform, glyph_transform, &props.paint);
+                });
+            }
+        }
+    }
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        Self::draw_image(self, image, &props);
+    }
+
+    fn pop_clip(&mut self) {
+        self.content().restore_state();
+    }
+
+    fn pop_transparency_group(&mut self) {
+        let Some(Some(group)) = self.groups.pop() else {
+            return;
+        };
+        let frame = self.frames.pop().unwrap();
+        let form = self.write_form(frame, true);
+
+        self.save_state(group.opacity, group.opacity, group.mask, group.blend_mode);
+        self.draw_form(form, Affine::IDENTITY);
+        self.content().restore_state();
+    }
+
+    fn draw_axial_gradient(
+        &mut self,
+        path: &BezPath,
+        gradient: &AxialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        Self::draw_axial_gradient(self, path, gradient, &props, draw_mode);
+    }
+
+    fn draw_radial_gradient(
+        &mut self,
+        path: &BezPath,
+        gradient: &RadialGradient,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        Self::draw_radial_gradient(self, path, gradient, &props, draw_mode);
+    }
+
+    fn draw_mesh(
+        &mut self,
+        path: &BezPath,
+        mesh: &Mesh,
+        props: DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        Self::draw_mesh(self, path, mesh, &props, draw_mode);
+    }
+}
+
+/// A transparency group that is written as a form `XObject`.
+struct Group {
+    opacity: f32,
+    mask: Option<CachedMask>,
+    blend_mode: BlendMode,
+}
+
+/// A content stream together with the resources it uses.
+pub(crate) struct Frame {
+    pub(crate) content: Content,
+    pub(crate) resources: ResourceNames,
+    /// The area that is visible in the coordinate system of the content stream.
+    pub(crate) bbox: Rect,
+}
+
+impl Frame {
+    fn new(bbox: Rect) -> Self {
+        Self {
+            content: Content::new(),
+            resources: ResourceNames::default(),
+            bbox,
+        }
+    }
+}
+
+/// The resources used by a content stream.
+pub(crate) struct ResourceNames {
+    pub(crate) x_objects: ResourceList,
+    pub(crate) ext_g_states: ResourceList,
+    pub(crate) shadings: ResourceList,
+}
+
+impl Default for ResourceNames {
+    fn default() -> Self {
+        Self {
+            x_objects: ResourceList::new('X'),
+            ext_g_states: ResourceList::new('G'),
+            shadings: ResourceList::new('S'),
+        }
+    }
+}
+
+impl ResourceNames {
+    fn write(&self, mut resources: pdf_writer::writers::Resources<'_>) {
+        if !self.x_objects.refs.is_empty() {
+            self.x_objects.write(resources.x_objects());
+        }
+
+        if !self.ext_g_states.refs.is_empty() {
+            self.ext_g_states.write(resources.ext_g_states());
+        }
+
+        if !self.shadings.refs.is_empty() {
+            self.shadings.write(resources.shadings());
+        }
+    }
+}
+
+/// A list of resources of the same type, which are named by their index.
+pub(crate) struct ResourceList {
+    prefix: char,
+    refs: Vec<Ref>,
+    indices: FxHashMap<Ref, usize>,
+}
+
+impl ResourceList {
+    fn new(prefix: char) -> Self {
+        Self {
+            prefix,
+            refs: vec![],
+            indices: FxHashMap::default(),
+        }
+    }
+
+    /// Add the object to the list and return its name.
+    pub(crate) fn insert(&mut self, id: Ref) -> String {
+        let index = *self.indices.entry(id).or_insert_with(|| {
+            self.refs.push(id);
+            self.refs.len() - 1
+        });
+
+        format!("{}{index}", self.prefix)
+    }
+
+    fn write(&self, mut dict: pdf_writer::Dict<'_>) {
+        for (index, id) in self.refs.iter().enumerate() {
+            dict.pair(Name(format!("{}{index}", self.prefix).as_bytes()), *id);
+        }
+    }
+}
+
+pub(crate) fn deflate_encode(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    const COMPRESSION_LEVEL: u8 = 6;
+    let mut e = ZlibEncoder::new(Vec::new(), Compression::new(COMPRESSION_LEVEL as u32));
+    e.write_all(data).unwrap();
+    e.finish().unwrap()
+}
@@ -0,0 +1,157 @@
+use crate::{Document, PdfDevice};
+use hayro_interpret::{BlendMode, CacheKey, MaskType, SoftMask};
+use pdf_writer::{Finish, Ref};
+
+/// A soft mask that has been written into the document.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct CachedMask {
+    /// The transparency group that defines the mask.
+    pub(crate) group: Ref,
+    pub(crate) luminosity: bool,
+    /// The backdrop color of a luminosity mask, as the bits of its RGB components.
+    pub(crate) backdrop: [u32; 3],
+    pub(crate) transfer_function: Option<Ref>,
+}
+
+/// The entries of an external graphics state dictionary.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct ExtGStateKey {
+    fill_alpha: u32,
+    stroke_alpha: u32,
+    mask: Option<CachedMask>,
+    blend_mode: BlendMode,
+}
+
+impl PdfDevice<'_> {
+    /// Write the soft mask into the document, if that hasn't happened before.
+    ///
+    /// Returns `None` if there is no soft mask or transparency is dropped.
+    pub(crate) fn soft_mask<'a>(&mut self, mask: Option<&SoftMask<'a>>) -> Option<CachedMask> {
+        let mask = mask.filter(|_| self.document.settings.transparency)?;
+
+        // The mask is drawn in device space, so its form needs to cover the visible area.
+        let bbox = self.frame().bbox;
+        let key = (
+            mask.cache_key(),
+            [bbox.x0, bbox.y0, bbox.x1, bbox.y1].map(|c| (c as f32).to_bits()),
+        );
+
+        if let Some(cached) = self.document.masks.get(&key) {
+            return Some(*cached);
+        }
+
+        let frame = self.record(bbox, |device| mask.interpret(device));
+        let group = self.write_form(frame, true);
+
+        let transfer_function = mask.transfer_function().map(|function| {
+            let samples = (0..=255_u8)
+                .map(|i| (function.apply(i as f32 / 255.0).clamp(0.0, 1.0) * 255.0 + 0.5) as u8)
+                .collect::<Vec<_>>();
+            let id = self.document.new_ref();
+
+            self.document
+                .chunk
+                .sampled_function(id, &samples)
+                .domain([0.0, 1.0])
+                .range([0.0, 1.0])
+                .size([256])
+                .bits_per_sample(8);
+
+            id
+        });
+
+        let [r, g, b, _] = mask.background_color().to_rgba().components();
+        let cached = CachedMask {
+            group,
+            luminosity: mask.mask_type() == MaskType::Luminosity,
+            backdrop: [r, g, b].map(f32::to_bits),
+            transfer_function,
+        };
+        self.document.masks.insert(key, cached);
+
+        Some(cached)
+    }
+}
+
+impl Document {
+    /// Return an external graphics state with the given entries.
+    pub(crate) fn ext_g_state(
+        &mut self,
+        fill_alpha: f32,
+        stroke_alpha: f32,
+        mask: Option<CachedMask>,
+        blend_mode: BlendMode,
+    ) -> Ref {
+        let key = ExtGStateKey {
+            fill_alpha: fill_alpha.to_bits(),
+            stroke_alpha: stroke_alpha.to_bits(),
+            mask,
+            blend_mode,
+        };
+
+        if let Some(id) = self.ext_g_states.get(&key) {
+            return *id;
+        }
+
+        let id = self.new_ref();
+        let mut ext_g_state = self.chunk.ext_graphics(id);
+
+        if fill_alpha < 1.0 {
+            ext_g_state.non_stroking_alpha(fill_alpha);
+        }
+
+        if stroke_alpha < 1.0 {
+            ext_g_state.stroking_alpha(stroke_alpha);
+        }
+
+        if blend_mode != BlendMode::Normal {
+            ext_g_state.blend_mode(convert_blend_mode(blend_mode));
+        }
+
+        if let Some(mask) = mask {
+            let mut soft_mask = ext_g_state.soft_mask();
+            soft_mask.group(mask.group);
+
+            if mask.luminosity {
+                soft_mask.subtype(pdf_writer::types::MaskType::Luminosity);
+                soft_mask.backdrop(mask.backdrop.map(f32::from_bits));
+            } else {
+                soft_mask.subtype(pdf_writer::types::MaskType::Alpha);
+            }
+
+            if let Some(function) = mask.transfer_function {
+                soft_mask.transfer_function(function);
+            }
+
+            soft_mask.finish();
+        }
+
+        ext_g_state.finish();
+        self.ext_g_states.insert(key, id);
+
+        id
+    }
+}
+
+fn convert_blend_mode(blend_mode: BlendMode) -> pdf_writer::types::BlendMode {
+    use pdf_writer::types::BlendMode as PdfBlendMode;
+
+    match blend_mode {
+        BlendMode::Normal => PdfBlendMode::Normal,
+        BlendMode::Multiply => PdfBlendMode::Multiply,
+        BlendMode::Screen => PdfBlendMode::Screen,
+        BlendMode::Overlay => PdfBlendMode::Overlay,
+        BlendMode::Darken => PdfBlendMode::Darken,
+        BlendMode::Lighten => PdfBlendMode::Lighten,
+        BlendMode::ColorDodge => PdfBlendMode::ColorDodge,
+        BlendMode::ColorBurn => PdfBlendMode::ColorBurn,
+        BlendMode::HardLight => PdfBlendMode::HardLight,
+        BlendMode::SoftLight => PdfBlendMode::SoftLight,
+        BlendMode::Difference => PdfBlendMode::Difference,
+        BlendMode::Exclusion => PdfBlendMode::Exclusion,
+        BlendMode::Hue => PdfBlendMode::Hue,
+        BlendMode::Saturation => PdfBlendMode::Saturation,
+        BlendMode::Color => PdfBlendMode::Color,
+        BlendMode::Luminosity => PdfBlendMode::Luminosity,
+    }
+}
@@ -0,0 +1,487 @@
+use crate::mask::CachedMask;
+use crate::path::{ContentExt, device_outlines};
+use crate::{Document, PdfDevice, deflate_encode};
+use hayro_interpret::gradient::{AxialGradient, Mesh, RadialGradient, SvgGradientStop};
+use hayro_interpret::pattern::{Pattern, ShadingPattern, TilingPattern};
+use hayro_interpret::{
+    BlendMode, CacheKey, DrawMode, DrawProps, FillRule, ImageData, LumaData, Paint, RgbData,
+};
+use kurbo::{Affine, BezPath, Point, Rect, Shape};
+use pdf_writer::types::{FunctionShadingType, StreamShadingType};
+use pdf_writer::{Filter, Finish, Name, Ref};
+
+/// The maximum number of tiles that are drawn for a single use of a tiling pattern.
+const MAX_TILES: f64 = 100_000.0;
+/// The number of pixels per device unit with which shadings are rasterized.
+const SHADING_SCALE: f64 = 2.0;
+/// The maximum number of pixels of a rasterized shading.
+const MAX_SHADING_PIXELS: f64 = 16_000_000.0;
+/// The tolerance for considering the alpha values of the colors of a shading equal.
+const ALPHA_TOLERANCE: f32 = 1.0 / 255.0;
+
+impl PdfDevice<'_> {
+    /// Fill an outline in device space with the pattern paint of the given properties.
+    pub(crate) fn fill_pattern<'a>(
+        &mut self,
+        outline: &BezPath,
+        fill_rule: FillRule,
+        props: &DrawProps<'a>,
+        is_stroke: bool,
+    ) {
+        let Paint::Pattern(pattern) = &props.paint else {
+            return;
+        };
+
+        let mask = self.soft_mask(props.soft_mask.as_ref());
+        self.paint_pattern(
+            outline,
+            fill_rule,
+            pattern,
+            mask,
+            props.blend_mode,
+            is_stroke,
+        );
+    }
+
+    /// Paint the area inside a path in device space with a pattern.
+    ///
+    /// Shading patterns are rasterized, while tiling patterns are expanded into their
+    /// tiles.
+    pub(crate) fn paint_pattern<'a>(
+        &mut self,
+        path: &BezPath,
+        fill_rule: FillRule,
+        pattern: &Pattern<'a>,
+        mask: Option<CachedMask>,
+        blend_mode: BlendMode,
+        is_stroke: bool,
+    ) {
+        let bbox = path.bounding_box().intersect(self.frame().bbox);
+
+        if bbox.is_zero_area() {
+            return;
+        }
+
+        self.save_state(1.0, 1.0, mask, blend_mode);
+        let content = self.content();
+        content.path(path);
+        content.clip(fill_rule);
+
+        match pattern {
+            Pattern::Shading(shading) => self.draw_rasterized_shading(shading, bbox),
+            Pattern::Tiling(tiling) => self.draw_tiling_pattern(tiling, bbox, is_stroke),
+        }
+
+        self.content().restore_state();
+    }
+
+    pub(crate) fn draw_axial_gradient<'a>(
+        &mut self,
+        path: &BezPath,
+        gradient: &AxialGradient,
+        props: &DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let coords = [
+            gradient.start.x,
+            gradient.start.y,
+            gradient.end.x,
+            gradient.end.y,
+        ];
+
+        self.draw_gradient(
+            path,
+            props,
+            draw_mode,
+            gradient.pattern(),
+            gradient.transform,
+            NativeGradient {
+                kind: FunctionShadingType::Axial,
+                coords: coords.iter().map(|c| *c as f32).collect(),
+                extend: gradient.extend,
+                stops: gradient.stops(),
+            },
+        );
+    }
+
+    pub(crate) fn draw_radial_gradient<'a>(
+        &mut self,
+        path: &BezPath,
+        gradient: &RadialGradient,
+        props: &DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let coords = [
+            gradient.start_center.x as f32,
+            gradient.start_center.y as f32,
+            gradient.start_radius,
+            gradient.end_center.x as f32,
+            gradient.end_center.y as f32,
+            gradient.end_radius,
+        ];
+
+        self.draw_gradient(
+            path,
+            props,
+            draw_mode,
+            gradient.pattern(),
+            gradient.transform,
+            NativeGradient {
+                kind: FunctionShadingType::Radial,
+                coords: coords.to_vec(),
+                extend: gradient.extend,
+                stops: gradient.stops(),
+            },
+        );
+    }
+
+    pub(crate) fn draw_mesh<'a>(
+        &mut self,
+        path: &BezPath,
+        mesh: &Mesh,
+        props: &DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let pattern = mesh.pattern();
+        let cache_key = pattern.cache_key();
+
+        let shading = match self.document.shadings.get(&cache_key) {
+            Some(shading) => Some(*shading),
+            None => {
+                let triangles = mesh.triangles();
+                let colors = triangles.iter().flat_map(|t| t.colors);
+
+                uniform_alpha(colors)
+                    .filter(|_| pattern.shading.background.is_none())
+                    .map(|alpha| {
+                        let id = self.document.write_mesh(&triangles);
+                        let shading = (id, alpha);
+                        self.document.shadings.insert(cache_key, shading);
+
+                        shading
+                    })
+            }
+        };
+
+        // Meshes whose colors can't be represented in `DeviceRGB` are rasterized.
+        let Some((shading, alpha)) = shading else {
+            self.draw_path(path, props, draw_mode);
+
+            return;
+        };
+
+        self.draw_shading(
+            path,
+            props,
+            draw_mode,
+            pattern,
+            shading,
+            alpha,
+            mesh.transform,
+        );
+    }
+
+    fn draw_gradient<'a>(
+        &mut self,
+        path: &BezPath,
+        props: &DrawProps<'a>,
+        draw_mode: &DrawMode,
+        pattern: &ShadingPattern,
+        transform: Affine,
+        gradient: NativeGradient,
+    ) {
+        let cache_key = pattern.cache_key();
+
+        let shading = match self.document.shadings.get(&cache_key) {
+            Some(shading) => Some(*shading),
+            None => uniform_alpha(gradient.stops.iter().map(|stop| stop.color))
+                .filter(|_| pattern.shading.background.is_none())
+                .map(|alpha| {
+                    let id = self.document.write_gradient(&gradient);
+                    let shading = (id, alpha);
+                    self.document.shadings.insert(cache_key, shading);
+
+                    shading
+                }),
+        };
+
+        // Gradients whose colors can't be represented in `DeviceRGB` are rasterized.
+        let Some((shading, alpha)) = shading else {
+            self.draw_path(path, props, draw_mode);
+
+            return;
+        };
+
+        self.draw_shading(path, props, draw_mode, pattern, shading, alpha, transform);
+    }
+
+    /// Paint the area covered by drawing the path with a shading object.
+    fn draw_shading<'a>(
+        &mut self,
+        path: &BezPath,
+        props: &DrawProps<'a>,
+        draw_mode: &DrawMode,
+        pattern: &ShadingPattern,
+        shading: Ref,
+        alpha: f32,
+        transform: Affine,
+    ) {
+        let mask = self.soft_mask(props.soft_mask.as_ref());
+
+        for (outline, fill_rule, _) in device_outlines(path, props, draw_mode) {
+            self.save_state(alpha, 1.0, mask, props.blend_mode);
+            let name = self.frame().resources.shadings.insert(shading);
+            let content = self.content();
+            content.path(&outline);
+            content.clip(fill_rule);
+
+            if let Some(clip_path) = &pattern.shading.clip_path {
+                content.path(clip_path);
+                content.clip(FillRule::NonZero);
+            }
+
+            content.transform_by(transform);
+            content.shading(Name(name.as_bytes()));
+            content.restore_state();
+        }
+    }
+
+    /// Draw a shading as an image that covers the given area in device space.
+    fn draw_rasterized_shading(&mut self, pattern: &ShadingPattern, bbox: Rect) {
+        if let Some(clip_path) = &pattern.shading.clip_path {
+            let content = self.content();
+            content.path(clip_path);
+            content.clip(FillRule::NonZero);
+        }
+
+        let scale = SHADING_SCALE.min((MAX_SHADING_PIXELS / bbox.area()).sqrt());
+        let width = (bbox.width() * scale).ceil().max(1.0) as u32;
+        let height = (bbox.height() * scale).ceil().max(1.0) as u32;
+        let pixel_transform = Affine::translate((bbox.x0, bbox.y0)) * Affine::scale(1.0 / scale);
+
+        let encoded = pattern.encode();
+        let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+        let mut alpha = Vec::with_capacity(width as usize * height as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let point = pixel_transform * Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                let sample = encoded.sample(encoded.base_transform * point);
+                let [r, g, b, a] = sample.map(|c| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8);
+
+                rgb.extend([r, g, b]);
+                alpha.push(a);
+            }
+        }
+
+        let alpha = alpha.iter().any(|a| *a != 255).then_some(LumaData {
+            data: alpha.into(),
+            width,
+            height,
+            interpolate: true,
+            scale_factors: (1.0, 1.0),
+        });
+        let image = ImageData::Rgb(RgbData {
+            data: rgb.into(),
+            width,
+            height,
+            interpolate: true,
+            scale_factors: (1.0, 1.0),
+        });
+
+        let image = self.document.write_image(image, alpha);
+        self.draw_image_xobject(image, pixel_transform);
+    }
+
+    /// Draw all tiles of a tiling pattern that intersect the given area in device
+    /// space.
+    fn draw_tiling_pattern<'a>(
+        &mut self,
+        pattern: &TilingPattern<'a>,
+        bbox: Rect,
+        is_stroke: bool,
+    ) {
+        if pattern.matrix.determinant().abs() <= f64::EPSILON {
+            return;
+        }
+
+        let area = pattern.matrix.inverse().transform_rect_bbox(bbox);
+        let x_step = pattern.x_step.abs() as f64;
+        let y_step = pattern.y_step.abs() as f64;
+        let tile = pattern.bbox;
+
+        // The offsets of the first and last tiles whose bounding box intersects the area.
+        let (x0, x1) = (
+            ((area.x0 - tile.x1) / x_step).floor(),
+            ((area.x1 - tile.x0) / x_step).ceil(),
+        );
+        let (y0, y1) = (
+            ((area.y0 - tile.y1) / y_step).floor(),
+            ((area.y1 - tile.y0) / y_step).ceil(),
+        );
+
+        // Give up on patterns with tiny tiles, which would otherwise result in huge
+        // content streams.
+        if (x1 - x0 + 1.0) * (y1 - y0 + 1.0) > MAX_TILES {
+            return;
+        }
+
+        // The tile is written once in pattern space and then drawn at each offset.
+        let cache_key = (pattern.cache_key(), is_stroke);
+        let form = match self.document.tiles.get(&cache_key) {
+            Some(form) => *form,
+            None => {
+                let frame = self.record(tile, |device| {
+                    pattern.interpret(device, Affine::IDENTITY, is_stroke);
+                });
+                let form = self.write_form(frame, false);
+                self.document.tiles.insert(cache_key, form);
+
+                form
+            }
+        };
+
+        for row in y0 as i64..=y1 as i64 {
+            for column in x0 as i64..=x1 as i64 {
+                let offset = Affine::translate((column as f64 * x_step, row as f64 * y_step));
+                self.draw_form(form, pattern.matrix * offset);
+            }
+        }
+    }
+}
+
+/// An axial or radial gradient that can be written as a shading object.
+struct NativeGradient {
+    kind: FunctionShadingType,
+    coords: Vec<f32>,
+    extend: [bool; 2],
+    stops: Vec<SvgGradientStop>,
+}
+
+impl Document {
+    fn write_gradient(&mut self, gradient: &NativeGradient) -> Ref {
+        let function = self.write_stops(&gradient.stops);
+        let id = self.new_ref();
+
+        let mut shading = self.chunk.function_shading(id);
+        shading.shading_type(gradient.kind);
+        shading.color_space().device_rgb();
+        shading.coords(gradient.coords.iter().copied());
+        shading.extend(gradient.extend);
+        shading.function(function);
+        shading.finish();
+
+        id
+    }
+
+    /// Write a function that interpolates linearly between the given stops.
+    fn write_stops(&mut self, stops: &[SvgGradientStop]) -> Ref {
+        let mut interpolate = |c0: [f32; 4], c1: [f32; 4]| {
+            let id = self.new_ref();
+            self.chunk
+                .exponential_function(id)
+                .domain([0.0, 1.0])
+                .c0(c0[..3].iter().copied())
+                .c1(c1[..3].iter().copied())
+                .n(1.0);
+
+            id
+        };
+
+        match stops {
+            [stop] => interpolate(stop.color, stop.color),
+            [first, second] => interpolate(first.color, second.color),
+            _ => {
+                let functions = stops
+                    .windows(2)
+                    .map(|w| interpolate(w[0].color, w[1].color))
+                    .collect::<Vec<_>>();
+                let id = self.new_ref();
+
+                self.chunk
+                    .stitching_function(id)
+                    .domain([0.0, 1.0])
+                    .bounds(stops[1..stops.len() - 1].iter().map(|stop| stop.offset))
+                    .encode(functions.iter().flat_map(|_| [0.0, 1.0]))
+                    .functions(functions);
+
+                id
+            }
+        }
+    }
+
+    /// Write the triangles of a mesh as a free-form Gouraud-shaded triangle mesh.
+    fn write_mesh(&mut self, triangles: &[hayro_interpret::gradient::MeshTriangle]) -> Ref {
+        let bounds = triangles
+            .iter()
+            .flat_map(|t| t.points)
+            .map(|p| Rect::from_points(p, p))
+            .reduce(|a, b| a.union(b))
+            .unwrap_or_default();
+        // The coordinates are encoded relative to the bounds, which must not be empty.
+        let bounds = Rect::new(
+            bounds.x0,
+            bounds.y0,
+            bounds.x1.max(bounds.x0 + 1.0),
+            bounds.y1.max(bounds.y0 + 1.0),
+        );
+
+        let mut data = vec![];
+
+        for triangle in triangles {
+            for (point, color) in triangle.points.iter().zip(triangle.colors) {
+                let x = (point.x - bounds.x0) / bounds.width();
+                let y = (point.y - bounds.y0) / bounds.height();
+
+                // Each vertex starts a new triangle, so that they don't need to share
+                // edges.
+                data.push(0);
+                data.extend(((x * u32::MAX as f64).round() as u32).to_be_bytes());
+                data.extend(((y * u32::MAX as f64).round() as u32).to_be_bytes());
+
+                for component in &color[..3] {
+                    let component = (component.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+                    data.extend(component.to_be_bytes());
+                }
+            }
+        }
+
+        let id = self.new_ref();
+        let data = deflate_encode(&data);
+        let mut shading = self.chunk.stream_shading(id, &data);
+        shading.filter(Filter::FlateDecode);
+        shading.shading_type(StreamShadingType::FreeformGouraud);
+        shading.color_space().device_rgb();
+        shading.bits_per_coordinate(32);
+        shading.bits_per_component(16);
+        shading.bits_per_flag(8);
+        shading.decode([
+            bounds.x0 as f32,
+            bounds.x1 as f32,
+            bounds.y0 as f32,
+            bounds.y1 as f32,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        ]);
+        shading.finish();
+
+        id
+    }
+}
+
+/// Return the alpha value of the given colors if all of them have the same one.
+///
+/// Shading objects can't have varying alpha values, so this is used to check
+/// whether a shading can be written natively.
+fn uniform_alpha(colors: impl IntoIterator<Item = [f32; 4]>) -> Option<f32> {
+    let mut colors = colors.into_iter();
+    let alpha = colors.next()?[3];
+
+    colors
+        .all(|color| (color[3] - alpha).abs() <= ALPHA_TOLERANCE)
+        .then_some(alpha)
+}
@@ -0,0 +1,227 @@
+use crate::PdfDevice;
+use hayro_interpret::{DrawMode, DrawProps, FillRule, Paint, StrokeProps};
+use kurbo::{Affine, BezPath, Cap, Join, PathEl, Point, Stroke, StrokeOpts};
+use pdf_writer::Content;
+use pdf_writer::types::{LineCapStyle, LineJoinStyle};
+
+/// The tolerance in device space that is used for converting strokes into fills.
+const STROKE_TOLERANCE: f64 = 0.1;
+
+pub(crate) trait ContentExt {
+    fn path(&mut self, path: &BezPath);
+    fn fill(&mut self, fill_rule: FillRule);
+    fn clip(&mut self, fill_rule: FillRule);
+    fn transform_by(&mut self, transform: Affine);
+    fn stroke_properties(&mut self, stroke_props: &StrokeProps);
+}
+
+impl ContentExt for Content {
+    fn path(&mut self, path: &BezPath) {
+        let mut start = Point::ZERO;
+        let mut last = Point::ZERO;
+
+        for el in path.elements() {
+            match *el {
+                PathEl::MoveTo(p) => {
+                    self.move_to(p.x as f32, p.y as f32);
+                    start = p;
+                    last = p;
+                }
+                PathEl::LineTo(p) => {
+                    self.line_to(p.x as f32, p.y as f32);
+                    last = p;
+                }
+                PathEl::QuadTo(p1, p2) => {
+                    // PDF has no quadratic curves, so they need to be elevated to cubic ones.
+                    let c1 = last + (p1 - last) * (2.0 / 3.0);
+                    let c2 = p2 + (p1 - p2) * (2.0 / 3.0);
+                    self.cubic_to(
+                        c1.x as f32,
+                        c1.y as f32,
+                        c2.x as f32,
+                        c2.y as f32,
+                        p2.x as f32,
+                        p2.y as f32,
+                    );
+                    last = p2;
+                }
+                PathEl::CurveTo(p1, p2, p3) => {
+                    self.cubic_to(
+                        p1.x as f32,
+                        p1.y as f32,
+                        p2.x as f32,
+                        p2.y as f32,
+                        p3.x as f32,
+                        p3.y as f32,
+                    );
+                    last = p3;
+                }
+                PathEl::ClosePath => {
+                    self.close_path();
+                    last = start;
+                }
+            }
+        }
+    }
+
+    fn fill(&mut self, fill_rule: FillRule) {
+        match fill_rule {
+            FillRule::NonZero => self.fill_nonzero(),
+            FillRule::EvenOdd => self.fill_even_odd(),
+        };
+    }
+
+    fn clip(&mut self, fill_rule: FillRule) {
+        match fill_rule {
+            FillRule::NonZero => self.clip_nonzero(),
+            FillRule::EvenOdd => self.clip_even_odd(),
+        };
+        self.end_path();
+    }
+
+    fn transform_by(&mut self, transform: Affine) {
+        self.transform(transform.as_coeffs().map(|c| c as f32));
+    }
+
+    fn stroke_properties(&mut self, stroke_props: &StrokeProps) {
+        // A line width of zero requests the thinnest line the device can render.
+        let line_width = if stroke_props.hairline {
+            0.0
+        } else {
+            stroke_props.line_width
+        };
+
+        self.set_line_width(line_width);
+        self.set_line_cap(match stroke_props.line_cap {
+            Cap::Butt => LineCapStyle::ButtCap,
+            Cap::Round => LineCapStyle::RoundCap,
+            Cap::Square => LineCapStyle::ProjectingSquareCap,
+        });
+        self.set_line_join(match stroke_props.line_join {
+            Join::Bevel => LineJoinStyle::BevelJoin,
+            Join::Miter => LineJoinStyle::MiterJoin,
+            Join::Round => LineJoinStyle::RoundJoin,
+        });
+        self.set_miter_limit(stroke_props.miter_limit);
+
+        if !stroke_props.dash_array.is_empty() {
+            self.set_dash_pattern(
+                stroke_props.dash_array.iter().copied(),
+                stroke_props.dash_offset,
+            );
+        }
+    }
+}
+
+impl PdfDevice<'_> {
+    pub(crate) fn draw_path<'a>(
+        &mut self,
+        path: &BezPath,
+        props: &DrawProps<'a>,
+        draw_mode: &DrawMode,
+    ) {
+        let Paint::Color(color) = &props.paint else {
+            for (outline, fill_rule, is_stroke) in device_outlines(path, props, draw_mode) {
+                self.fill_pattern(&outline, fill_rule, props, is_stroke);
+            }
+
+            return;
+        };
+
+        let [r, g, b, a] = color.to_rgba().components();
+
+        match draw_mode {
+            DrawMode::Fill(fill_rule) => {
+                let mask = self.soft_mask(props.soft_mask.as_ref());
+                self.save_state(a, 1.0, mask, props.blend_mode);
+                let content = self.content();
+                content.transform_by(props.transform);
+                content.set_fill_rgb(r, g, b);
+                content.path(path);
+                content.fill(*fill_rule);
+                content.restore_state();
+            }
+            DrawMode::Stroke(stroke_props) => {
+                let mask = self.soft_mask(props.soft_mask.as_ref());
+                self.save_state(1.0, a, mask, props.blend_mode);
+                let content = self.content();
+                content.transform_by(props.transform);
+                content.set_stroke_rgb(r, g, b);
+                content.stroke_properties(stroke_props);
+                content.path(path);
+                content.stroke();
+                content.restore_state();
+            }
+            DrawMode::FillAndStroke(fill_rule, stroke_props) => {
+                self.draw_path(path, props, &DrawMode::Fill(*fill_rule));
+                self.draw_path(path, props, &DrawMode::Stroke(stroke_props.clone()));
+            }
+            DrawMode::Invisible => {}
+        }
+    }
+}
+
+/// Return the outlines in device space that are covered when drawing the path with
+/// the given mode, together with their fill rule and whether they are a stroke.
+///
+/// Strokes are converted into fills, which is needed for painting them with
+/// something else than a solid color.
+pub(crate) fn device_outlines(
+    path: &BezPath,
+    props: &DrawProps<'_>,
+    draw_mode: &DrawMode,
+) -> Vec<(BezPath, FillRule, bool)> {
+    let transform = props.transform;
+
+    match draw_mode {
+        DrawMode::Fill(fill_rule) => vec![(transform * path.clone(), *fill_rule, false)],
+        DrawMode::Stroke(stroke_props) => {
+            vec![(
+                stroke_outline(path, stroke_props, transform),
+                FillRule::NonZero,
+                true,
+            )]
+        }
+        DrawMode::FillAndStroke(fill_rule, stroke_props) => vec![
+            (transform * path.clone(), *fill_rule, false),
+            (
+                stroke_outline(path, stroke_props, transform),
+                FillRule::NonZero,
+                true,
+            ),
+        ],
+        DrawMode::Invisible => vec![],
+    }
+}
+
+/// Return the outline of the stroke of a path in device space.
+fn stroke_outline(path: &BezPath, stroke_props: &StrokeProps, transform: Affine) -> BezPath {
+    let scale = transform.determinant().abs().sqrt();
+
+    if scale <= f64::EPSILON {
+        return BezPath::new();
+    }
+
+    let line_width = if stroke_props.hairline {
+        1.0 / scale
+    } else {
+        stroke_props.line_width as f64
+    };
+
+    let stroke = Stroke::new(line_width)
+        .with_caps(stroke_props.line_cap)
+        .with_join(stroke_props.line_join)
+        .with_miter_limit(stroke_props.miter_limit as f64)
+        .with_dashes(
+            stroke_props.dash_offset as f64,
+            stroke_props.dash_array.iter().map(|d| *d as f64),
+        );
+
+    transform
+        * kurbo::stroke(
+            path.iter(),
+            &stroke,
+            &StrokeOpts::default(),
+            STROKE_TOLERANCE / scale,
+        )
+}
@@ -31,6 +31,9 @@ impl<'a> SvgRenderer<'a> {
         }
 
         match glyph {
+            Glyph::Outline(o) if matches!(mode, DrawMode::Fill(_)) && o.is_color() => {
+                o.interpret_color(self, glyph_transform, &props);
+            }
             Glyph::Outline(o) => {
                 let outline = o.outline();
                 let glyph_id = o.identifier().cache_key();
@@ -22,7 +22,7 @@ impl<'a> SvgRenderer<'a> {
                 let w = rgb.width;
                 let h = rgb.height;
                 let i = rgb.interpolate;
-                (rgb.data, w, h, i)
+                (rgb.data.to_vec(), w, h, i)
             }
             // TODO: Store as gray-scale.
             ImageData::Luma(luma) => {
@@ -42,8 +42,8 @@ impl<'a> SvgRenderer<'a> {
             if alpha.interpolate == interpolate && alpha.width == width && alpha.height == height {
                 let interleaved = rgb_data
                     .chunks(3)
-                    .zip(alpha.data)
-                    .flat_map(|(rgb, a)| [rgb[0], rgb[1], rgb[2], a])
+                    .zip(alpha.data.iter())
+                    .flat_map(|(rgb, a)| [rgb[0], rgb[1], rgb[2], *a])
                     .collect::<Vec<u8>>();
 
                 let image = DynamicImage::ImageRgba8(
@@ -58,7 +58,8 @@ impl<'a> SvgRenderer<'a> {
 
                 let alpha = {
                     let image = DynamicImage::ImageLuma8(
-                        ImageBuffer::from_raw(alpha.width, alpha.height, alpha.data).unwrap(),
+                        ImageBuffer::from_raw(alpha.width, alpha.height, alpha.data.to_vec())
+                            .unwrap(),
                     );
 
                     let transform = transform
@@ -117,7 +118,8 @@ impl<'a> SvgRenderer<'a> {
             Paint::Pattern(_) => {
                 let mask = {
                     let image = DynamicImage::ImageLuma8(
-                        ImageBuffer::from_raw(stencil.width, stencil.height, stencil.data).unwrap(),
+                        ImageBuffer::from_raw(stencil.width, stencil.height, stencil.data.to_vec())
+                            .unwrap(),
                     );
 
                     ImageLuminanceMask {
@@ -139,6 +141,7 @@ impl<'a> SvgRenderer<'a> {
                         paint: paint.clone(),
                         soft_mask: None,
                         blend_mode: BlendMode::Normal,
+                        flatness: None,
                     },
                     &DrawMode::Fill(FillRule::NonZero),
                 );
@@ -22,7 +22,7 @@ use crate::mask::MaskKind;
 use crate::paint::{
     CachedNativeGradient, CachedShading, CachedShadingPattern, CachedTilingPattern,
 };
-use hayro_interpret::font::Glyph;
+use hayro_interpret::font::{Glyph, GlyphCache};
 use hayro_interpret::hayro_syntax::page::Page;
 use hayro_interpret::util::{Float32Ext, TransformExt};
 use hayro_interpret::{
@@ -63,6 +63,15 @@ impl<'a> RenderCache<'a> {
             interpreter_cache: InterpreterCache::new(),
         }
     }
+
+    /// Create a new render cache that uses the given cache for glyph outlines.
+    ///
+    /// Use this to configure the memory limit of the glyph cache.
+    pub fn with_glyph_cache(glyph_cache: GlyphCache) -> Self {
+        Self {
+            interpreter_cache: InterpreterCache::with_glyph_cache(glyph_cache),
+        }
+    }
 }
 
 /// Convert the given page into an SVG string.
@@ -200,11 +209,19 @@ impl<'a> SvgRenderer<'a> {
     }
 
     pub(crate) fn write_stroke_properties(&mut self, stroke_props: &StrokeProps) {
-        if !stroke_props.line_width.is_nearly_equal(1.0) {
+        if stroke_props.hairline {
+            // A width of one in screen units, regardless of the transform.
+            self.xml
+                .write_attribute("vector-effect", "non-scaling-stroke");
+        } else if !stroke_props.line_width.is_nearly_equal(1.0) {
             self.xml
                 .write_attribute("stroke-width", &stroke_props.line_width);
         }
 
+        if stroke_props.stroke_adjustment {
+            self.xml.write_attribute("shape-rendering", "crispEdges");
+        }
+
         match stroke_props.line_cap {
             Cap::Butt => {}
             Cap::Square => self.xml.write_attribute("stroke-linecap", "square"),
@@ -103,6 +103,7 @@ impl<'a> SvgRenderer<'a> {
                                 paint,
                                 soft_mask: None,
                                 blend_mode: BlendMode::Normal,
+                                flatness: None,
                             },
                             &DrawMode::Fill(FillRule::NonZero),
                         );
@@ -12,6 +12,7 @@ use kurbo::{Affine, Point, Rect, Shape, Vec2};
 pub(crate) struct CachedTilingPattern<'a> {
     pub(crate) transform: Affine,
     pub(crate) tiling_pattern: TilingPattern<'a>,
+    pub(crate) is_stroke: bool,
 }
 
 pub(crate) struct CachedShadingPattern {
@@ -107,8 +108,6 @@ impl<'a> SvgRenderer<'a> {
             Paint::Pattern(p) => {
                 let id = match p.as_ref() {
                     Pattern::Shading(s) => {
-                        const NATIVE_GRADIENT_TOLERANCE: f32 = 0.01;
-
                         let mut basic_bbox = path_bbox();
 
                         if let Some(stroke_width) = stroke_props.map(|s| s.line_width) {
@@ -128,9 +127,12 @@ impl<'a> SvgRenderer<'a> {
                         let encoded = s.encode();
                         let shading_paint = if let EncodedShadingType::RadialAxial(gradient) =
                             &encoded.shading_type
-                            && let Some(native) =
-                                gradient.as_svg_gradient(&encoded, bbox, NATIVE_GRADIENT_TOLERANCE)
-                        {
+                            && let Some(native) = gradient.as_svg_gradient(
+                                &encoded,
+                                bbox,
+                                s.smoothness,
+                                s.gradient_subdivisions,
+                            ) {
                             let gradient_key = gradient_key(shading_key, &native);
                             let gradient_id =
                                 self.gradients
@@ -176,14 +178,18 @@ impl<'a> SvgRenderer<'a> {
                     Pattern::Tiling(t) => {
                         let inverse_transform = path_transform.inverse();
                         let pattern = *t.clone();
-                        let cache_key = (pattern.clone(), inverse_transform).cache_key();
+                        // Uncolored patterns are painted with the stroke color when
+                        // they are used for stroking.
+                        let is_stroke = stroke_props.is_some();
+                        let cache_key =
+                            hash128(&((pattern.clone(), inverse_transform).cache_key(), is_stroke));
 
                         if !self.tiling_patterns.contains(cache_key) {
                             self.with_dummy(|r| {
                                 t.interpret(
                                     r,
                                     Affine::translate((-pattern.bbox.x0, -pattern.bbox.y0)),
-                                    false,
+                                    is_stroke,
                                 );
                             });
                         }
@@ -192,6 +198,7 @@ impl<'a> SvgRenderer<'a> {
                             .insert_with(cache_key, || CachedTilingPattern {
                                 transform: inverse_transform,
                                 tiling_pattern: pattern,
+                                is_stroke,
                             })
                     }
                 };
@@ -310,7 +317,7 @@ impl<'a> SvgRenderer<'a> {
                     -pattern.tiling_pattern.bbox.x0,
                     -pattern.tiling_pattern.bbox.y0,
                 )),
-                false,
+                pattern.is_stroke,
             );
 
             self.xml.end_element();
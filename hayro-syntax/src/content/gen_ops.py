@@ -177,10 +177,16 @@ def gen_dispatch_match(code, name, types):
     return f'b"{escaped_code}" => {name}::from_stack(instruction.operands)?.into(),'
 
 
+def gen_operator_match(code, name):
+    escaped_code = code.replace('"', '\\"')
+    return f'Self::{name}(_) => b"{escaped_code}",'
+
+
 # Generate all code pieces
 structs = []
 enum_variants = []
 dispatch_arms = []
+operator_arms = []
 
 for category in ops.values():
     for code, name, types in category:
@@ -188,6 +194,7 @@ for category in ops.values():
             structs.append(gen_struct(name, code, types))
         enum_variants.append(gen_enum_variant(name, types))
         dispatch_arms.append(gen_dispatch_match(code, name, types))
+        operator_arms.append(gen_operator_match(code, name))
 
 # Build the final Rust code blocks
 struct_block = "\n\n".join(structs)
@@ -210,6 +217,16 @@ dispatch_block = (
     "            _ => return Some(Self::Fallback(instruction.operator)),\n"
     "        })\n"
     "    }\n"
+    "\n"
+    "    /// Return the name of the operator of the instruction.\n"
+    "    pub fn operator(&self) -> &[u8] {\n"
+    "        match self {\n"
+    + "            "
+    + "\n            ".join(operator_arms)
+    + "\n"
+    "            Self::Fallback(op) => op,\n"
+    "        }\n"
+    "    }\n"
     "}"
 )
 
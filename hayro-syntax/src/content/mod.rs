@@ -34,10 +34,12 @@ assert!(matches!(iter.next(), Some(TypedInstruction::FillPathNonZero(_))));
 pub mod ops;
 
 use crate::content::ops::TypedInstruction;
+use crate::filter::Filter;
 use crate::object;
 use crate::object::dict::InlineImageDict;
+use crate::object::dict::keys::{L, LENGTH};
 use crate::object::name::{Name, skip_name_like};
-use crate::object::{Array, Null, Number, Object, Stream};
+use crate::object::{Array, Dict, Null, Number, Object, Stream};
 use crate::reader::Reader;
 use crate::reader::{Readable, ReaderContext, ReaderExt, Skippable};
 use crate::trivia::is_white_space_character;
@@ -97,6 +99,7 @@ pub struct UntypedIter<'a> {
     reader: Reader<'a>,
     stack: Stack<'a>,
     operator: Option<Operator<'a>>,
+    malformed: bool,
 }
 
 impl<'a> UntypedIter<'a> {
@@ -106,6 +109,7 @@ impl<'a> UntypedIter<'a> {
             reader: Reader::new(data),
             stack: Stack::new(),
             operator: None,
+            malformed: false,
         }
     }
 
@@ -115,12 +119,43 @@ impl<'a> UntypedIter<'a> {
             reader: Reader::new(&[]),
             stack: Stack::new(),
             operator: None,
+            malformed: false,
         }
     }
 
+    /// Return the fraction of the content stream that has been read so far, as a
+    /// value between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        if self.reader.is_empty() {
+            return 1.0;
+        }
+
+        self.reader.offset() as f32 / self.reader.len() as f32
+    }
+
+    /// Return whether the iterator stopped before the end of the content stream,
+    /// because the rest of it could not be parsed.
+    pub fn is_malformed(&self) -> bool {
+        self.malformed
+    }
+
     /// Return the next instruction.
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Instruction<'_, 'a>> {
+        if self.read_next().is_none() {
+            self.malformed |= !self.reader.at_end();
+
+            return None;
+        }
+
+        Some(Instruction {
+            operands: &self.stack,
+            operator: self.operator.as_ref()?,
+        })
+    }
+
+    /// Read the next instruction into the stack and the operator.
+    fn read_next(&mut self) -> Option<()> {
         self.stack.clear();
         self.operator = None;
 
@@ -153,6 +188,7 @@ impl<'a> UntypedIter<'a> {
                         warn!("failed to read operator in content stream");
 
                         self.reader.jump_to_end();
+                        self.malformed = true;
                         return None;
                     }
                 };
@@ -169,7 +205,21 @@ impl<'a> UntypedIter<'a> {
                     let stream_data = self.reader.tail()?;
                     let start_offset = self.reader.offset();
 
-                    'outer: while let Some(pos) = find_needle(self.reader.tail()?, b"EI") {
+                    let known_length = inline_image_length(&dict, stream_data);
+
+                    if let Some(length) = known_length {
+                        let stream = Stream::new(&stream_data[..length], dict.clone());
+                        self.stack.push(Object::Stream(stream))?;
+
+                        self.reader.read_bytes(length)?;
+                        self.reader.skip_white_spaces();
+                        self.reader.read_bytes(2)?;
+                        self.reader.skip_white_spaces();
+                    }
+
+                    'outer: while known_length.is_none()
+                        && let Some(pos) = find_needle(self.reader.tail()?, b"EI")
+                    {
                         self.reader.read_bytes(pos)?;
 
                         if self.reader.peek_bytes(2) == Some(b"EI") {
@@ -193,8 +243,8 @@ impl<'a> UntypedIter<'a> {
                             // contains the "EI" operator as part of the data, in which case we
                             // cannot confidently know whether we have hit the actual end of the
                             // stream. See also <https://github.com/pdf-association/pdf-issues/issues/543>
-                            // PDF 2.0 does have a `/Length` attribute we can read, but since it's relatively
-                            // new we don't bother trying to read it.
+                            // If the length is known from the `/Length` attribute (PDF 2.0) or an
+                            // end-of-data marker, we don't get here.
                             let tail = &self.reader.tail()?[2..];
                             let mut find_reader = Reader::new(tail);
 
@@ -295,10 +345,7 @@ impl<'a> UntypedIter<'a> {
                 }
 
                 self.operator = Some(operator);
-                return Some(Instruction {
-                    operands: &self.stack,
-                    operator: self.operator.as_ref().unwrap(),
-                });
+                return Some(());
             }
 
             self.reader.skip_white_spaces_and_comments();
@@ -308,11 +355,44 @@ impl<'a> UntypedIter<'a> {
     }
 }
 
+/// Try to determine the length of the data of an inline image without searching
+/// for the `EI` operator, which might also be part of binary image data.
+///
+/// This is possible if the image dictionary specifies the length (PDF 2.0), or if
+/// the outermost filter has an end-of-data marker.
+fn inline_image_length(dict: &Dict<'_>, data: &[u8]) -> Option<usize> {
+    let length = if let Some(length) = dict.get::<usize>(L).or_else(|| dict.get::<usize>(LENGTH)) {
+        length
+    } else {
+        match Stream::new(data, dict.clone()).filters().first()? {
+            Filter::DctDecode => find_needle(data, &[0xFF, 0xD9])? + 2,
+            Filter::Ascii85Decode => find_needle(data, b"~>")? + 2,
+            Filter::AsciiHexDecode => find_needle(data, b">")? + 1,
+            _ => return None,
+        }
+    };
+
+    // Only trust the length if it is followed by the `EI` operator.
+    let mut reader = Reader::new(data.get(length..)?);
+    reader.skip_white_spaces();
+
+    if reader.read_bytes(2)? != b"EI"
+        || reader
+            .peek_byte()
+            .is_some_and(|b| !is_white_space_character(b))
+    {
+        return None;
+    }
+
+    Some(length)
+}
+
 /// An iterator over PDF content streams that provide access to the instructions
 /// in a typed fashion.
 #[derive(Clone)]
 pub struct TypedIter<'a> {
     untyped: UntypedIter<'a>,
+    malformed: bool,
 }
 
 impl<'a> TypedIter<'a> {
@@ -320,11 +400,27 @@ impl<'a> TypedIter<'a> {
     pub fn new(data: &'a [u8]) -> Self {
         Self {
             untyped: UntypedIter::new(data),
+            malformed: false,
         }
     }
 
     pub(crate) fn from_untyped(untyped: UntypedIter<'a>) -> Self {
-        Self { untyped }
+        Self {
+            untyped,
+            malformed: false,
+        }
+    }
+
+    /// Return the fraction of the content stream that has been read so far, as a
+    /// value between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        self.untyped.progress()
+    }
+
+    /// Return whether the iterator stopped before the end of the content stream,
+    /// because the rest of it could not be parsed.
+    pub fn is_malformed(&self) -> bool {
+        self.malformed || self.untyped.is_malformed()
     }
 
     /// Return the next typed instruction.
@@ -350,6 +446,7 @@ impl<'a> TypedIter<'a> {
                 ]
                 .contains(&op.operator.0.deref())
                 {
+                    self.malformed = true;
                     None
                 } else {
                     Some(TypedInstruction::Fallback(op.operator))
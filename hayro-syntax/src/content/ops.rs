@@ -72,9 +72,10 @@ op_impl!(
 mod tests {
     use crate::content::TypedIter;
     use crate::content::ops::{
-        BeginMarkedContentWithProperties, ClosePath, EndMarkedContent, FillPathNonZero, LineTo,
-        MarkedContentPointWithProperties, MoveTo, NonStrokeColorDeviceRgb, NonStrokeColorNamed,
-        SetGraphicsState, StrokeColorNamed, Transform, TypedInstruction,
+        BeginMarkedContentWithProperties, ClosePath, EndMarkedContent, FillPathNonZero,
+        InlineImage, LineTo, MarkedContentPointWithProperties, MoveTo, NonStrokeColorDeviceRgb,
+        NonStrokeColorNamed, RestoreState, SetGraphicsState, StrokeColorNamed, Transform,
+        TypedInstruction,
     };
     use crate::object::Name;
     use crate::object::Number;
@@ -256,4 +257,57 @@ f
         ));
         assert!(iter.next().is_none());
     }
+
+    fn inline_image_data(input: &[u8]) -> Vec<u8> {
+        let mut iter = TypedIter::new(input);
+
+        let data = match iter.next() {
+            Some(TypedInstruction::InlineImage(InlineImage(stream))) => stream.raw_data().to_vec(),
+            other => panic!("unexpected instruction: {other:?}"),
+        };
+
+        assert!(matches!(
+            iter.next(),
+            Some(TypedInstruction::RestoreState(RestoreState))
+        ));
+        assert!(iter.next().is_none());
+
+        data
+    }
+
+    #[test]
+    fn inline_image_with_length() {
+        let input = b"BI /W 11 /H 1 /BPC 8 /CS /G /L 11 ID 1 EI (a) Tj EI Q";
+
+        assert_eq!(inline_image_data(input), b"1 EI (a) Tj");
+    }
+
+    #[test]
+    fn inline_image_with_end_marker() {
+        let input = b"BI /W 1 /H 1 /BPC 8 /CS /G /F /A85 ID 9jqo^EI (a) Tj~> EI Q";
+
+        assert_eq!(inline_image_data(input), b"9jqo^EI (a) Tj~>");
+    }
+
+    #[test]
+    fn inline_image_with_wrong_length() {
+        // The length is ignored if it doesn't point to the `EI` operator.
+        let input = b"BI /W 2 /H 1 /BPC 8 /CS /G /L 1 ID \x01\x02 EI Q";
+
+        assert_eq!(inline_image_data(input), b"\x01\x02 ");
+    }
+
+    #[test]
+    fn malformed_content() {
+        let mut iter = TypedIter::new(b"0 0 m 10 10 l S ");
+        while iter.next().is_some() {}
+        assert!(!iter.is_malformed());
+
+        // The operand of the `l` operator is not a number, so the rest of the
+        // content stream is skipped.
+        let mut iter = TypedIter::new(b"0 0 m (a) l S");
+        assert!(matches!(iter.next(), Some(TypedInstruction::MoveTo(_))));
+        assert!(iter.next().is_none());
+        assert!(iter.is_malformed());
+    }
 }
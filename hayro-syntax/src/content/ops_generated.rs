@@ -525,4 +525,82 @@ impl<'b, 'a> TypedInstruction<'b, 'a> {
             _ => return Some(Self::Fallback(instruction.operator)),
         })
     }
+
+    /// Return the name of the operator of the instruction.
+    pub fn operator(&self) -> &[u8] {
+        match self {
+            Self::BeginCompatibility(_) => b"BX",
+            Self::EndCompatibility(_) => b"EX",
+            Self::SaveState(_) => b"q",
+            Self::RestoreState(_) => b"Q",
+            Self::Transform(_) => b"cm",
+            Self::LineWidth(_) => b"w",
+            Self::LineCap(_) => b"J",
+            Self::LineJoin(_) => b"j",
+            Self::MiterLimit(_) => b"M",
+            Self::DashPattern(_) => b"d",
+            Self::RenderingIntent(_) => b"ri",
+            Self::FlatnessTolerance(_) => b"i",
+            Self::SetGraphicsState(_) => b"gs",
+            Self::MoveTo(_) => b"m",
+            Self::LineTo(_) => b"l",
+            Self::CubicTo(_) => b"c",
+            Self::CubicStartTo(_) => b"v",
+            Self::CubicEndTo(_) => b"y",
+            Self::ClosePath(_) => b"h",
+            Self::RectPath(_) => b"re",
+            Self::StrokePath(_) => b"S",
+            Self::CloseAndStrokePath(_) => b"s",
+            Self::FillPathNonZero(_) => b"f",
+            Self::FillPathNonZeroCompatibility(_) => b"F",
+            Self::FillPathEvenOdd(_) => b"f*",
+            Self::FillAndStrokeNonZero(_) => b"B",
+            Self::FillAndStrokeEvenOdd(_) => b"B*",
+            Self::CloseFillAndStrokeNonZero(_) => b"b",
+            Self::CloseFillAndStrokeEvenOdd(_) => b"b*",
+            Self::EndPath(_) => b"n",
+            Self::ClipNonZero(_) => b"W",
+            Self::ClipEvenOdd(_) => b"W*",
+            Self::ColorSpaceStroke(_) => b"CS",
+            Self::ColorSpaceNonStroke(_) => b"cs",
+            Self::StrokeColor(_) => b"SC",
+            Self::StrokeColorNamed(_) => b"SCN",
+            Self::NonStrokeColor(_) => b"sc",
+            Self::NonStrokeColorNamed(_) => b"scn",
+            Self::StrokeColorDeviceGray(_) => b"G",
+            Self::NonStrokeColorDeviceGray(_) => b"g",
+            Self::StrokeColorDeviceRgb(_) => b"RG",
+            Self::NonStrokeColorDeviceRgb(_) => b"rg",
+            Self::StrokeColorCmyk(_) => b"K",
+            Self::NonStrokeColorCmyk(_) => b"k",
+            Self::Shading(_) => b"sh",
+            Self::XObject(_) => b"Do",
+            Self::InlineImage(_) => b"BI",
+            Self::CharacterSpacing(_) => b"Tc",
+            Self::WordSpacing(_) => b"Tw",
+            Self::HorizontalScaling(_) => b"Tz",
+            Self::TextLeading(_) => b"TL",
+            Self::TextFont(_) => b"Tf",
+            Self::TextRenderingMode(_) => b"Tr",
+            Self::TextRise(_) => b"Ts",
+            Self::BeginText(_) => b"BT",
+            Self::EndText(_) => b"ET",
+            Self::NextLine(_) => b"Td",
+            Self::NextLineAndSetLeading(_) => b"TD",
+            Self::SetTextMatrix(_) => b"Tm",
+            Self::NextLineUsingLeading(_) => b"T*",
+            Self::ShowText(_) => b"Tj",
+            Self::NextLineAndShowText(_) => b"'",
+            Self::ShowTextWithParameters(_) => b"\"",
+            Self::ShowTexts(_) => b"TJ",
+            Self::ColorGlyph(_) => b"d0",
+            Self::ShapeGlyph(_) => b"d1",
+            Self::MarkedContentPoint(_) => b"MP",
+            Self::MarkedContentPointWithProperties(_) => b"DP",
+            Self::BeginMarkedContent(_) => b"BMC",
+            Self::BeginMarkedContentWithProperties(_) => b"BDC",
+            Self::EndMarkedContent(_) => b"EMC",
+            Self::Fallback(op) => op,
+        }
+    }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use crate::object::Dict;
 use crate::object::dict::keys::{
-    BLACK_IS_1, COLUMNS, ENCODED_BYTE_ALIGN, END_OF_BLOCK, END_OF_LINE, K, ROWS,
+    BLACK_IS_1, COLUMNS, DAMAGED_ROWS_BEFORE_ERROR, ENCODED_BYTE_ALIGN, END_OF_BLOCK, END_OF_LINE,
+    K, ROWS,
 };
 use crate::object::stream::{FilterResult, ImageColorSpace, ImageData, ImageDecodeParams};
 use alloc::borrow::Cow;
@@ -35,6 +36,7 @@ pub(crate) fn decode(
             EncodingMode::Group3_2D { k: k as u32 }
         },
         invert_black: params.get::<bool>(BLACK_IS_1).unwrap_or(false),
+        damaged_rows_before_error: params.get::<u32>(DAMAGED_ROWS_BEFORE_ERROR).unwrap_or(0),
     };
 
     // Whenever possible (if we don't have an indexed color space), we convert
@@ -183,4 +185,44 @@ mod tests {
         assert_eq!(decoded.data.as_ref(), &[0; 8]);
         assert_eq!(decoded.image_data.unwrap().height, 1);
     }
+
+    #[test]
+    fn damaged_rows_before_error() {
+        // Three rows, the second of which ends after only four pixels.
+        let data = [0x00, 0x17, 0x20, 0x01, 0xB0, 0x01, 0x98];
+        let strict = Dict::from_bytes(b"<< /K 0 /Columns 8 /Rows 3 /EndOfLine true >>").unwrap();
+        let decoded = decode(&data, &strict, &ImageDecodeParams::default()).unwrap();
+        assert_eq!(&decoded.data[..8], &[0xFF, 0xFF, 0, 0, 0, 0, 0, 0]);
+        assert!(decoded.data.len() < 24);
+
+        let lenient = Dict::from_bytes(
+            b"<< /K 0 /Columns 8 /Rows 3 /EndOfLine true /DamagedRowsBeforeError 1 >>",
+        )
+        .unwrap();
+        let decoded = decode(&data, &lenient, &ImageDecodeParams::default()).unwrap();
+        // The rest of the damaged row is taken from the previous row.
+        assert_eq!(
+            decoded.data.as_ref(),
+            &[
+                0xFF, 0xFF, 0, 0, 0, 0, 0, 0, //
+                0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, //
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ]
+        );
+    }
+
+    #[test]
+    fn encoded_byte_align_with_end_of_line() {
+        // The first row ends in the middle of a byte, and the EOL of the
+        // second row starts right after it.
+        let data = [0x00, 0x03, 0x30, 0x01, 0x98];
+        let params = Dict::from_bytes(
+            b"<< /K 0 /Columns 8 /Rows 2 /EndOfLine true /EncodedByteAlign true >>",
+        )
+        .unwrap();
+
+        let decoded = decode(&data, &params, &ImageDecodeParams::default()).unwrap();
+
+        assert_eq!(decoded.data.as_ref(), &[0xFF; 16]);
+    }
 }
@@ -30,8 +30,18 @@ pub(crate) fn decode(
     let mut decoder = zune_jpeg::JpegDecoder::new_with_options(ZCursor::new(&*data), options);
     decoder.decode_headers().ok()?;
 
-    let color_transform = params.get::<u8>(COLOR_TRANSFORM);
     let input_color_space = decoder.input_colorspace().unwrap();
+    let num_components = decoder.info().unwrap().components;
+
+    // If the Adobe marker is present, its transform flag takes precedence over the
+    // `ColorTransform` entry. Otherwise, only images with three components are
+    // transformed by default.
+    let color_transform = match find_adobe_transform(&data) {
+        Some(transform) => transform != 0,
+        None => params
+            .get::<u8>(COLOR_TRANSFORM)
+            .map_or(num_components == 3, |c| c == 1),
+    };
 
     let mut out_colorspace = if let Some(num_components) = image_params.num_components
         && !matches!(num_components, 1 | 3 | 4)
@@ -40,7 +50,7 @@ pub(crate) fn decode(
     } else {
         match input_color_space {
             ColorSpace::YCbCr => {
-                if color_transform.is_none_or(|c| c == 1) {
+                if color_transform {
                     ColorSpace::RGB
                 } else {
                     ColorSpace::YCbCr
@@ -48,7 +58,7 @@ pub(crate) fn decode(
             }
             ColorSpace::RGB | ColorSpace::RGBA => ColorSpace::RGB,
             ColorSpace::Luma | ColorSpace::LumaA => ColorSpace::Luma,
-            // TODO: Find test case with color transform on cmyk
+            // The YCCK conversion is done manually below, so we need the raw data.
             CMYK => CMYK,
             ColorSpace::YCCK => ColorSpace::YCCK,
             _ => ColorSpace::RGB,
@@ -56,14 +66,16 @@ pub(crate) fn decode(
     };
 
     // In case image had APP14 marker, we might have to override the colorspace.
-    if input_color_space == CMYK && decoder.info().unwrap().components == 3 {
+    if input_color_space == CMYK && num_components == 3 {
         out_colorspace = ColorSpace::RGB;
     }
 
     decoder.set_options(DecoderOptions::default().jpeg_set_out_colorspace(out_colorspace));
     let mut decoded = decoder.decode().ok()?;
 
-    if out_colorspace == ColorSpace::YCCK {
+    if matches!(out_colorspace, CMYK | ColorSpace::YCCK) && color_transform {
+        // Like in other viewers, this also inverts the result, as Adobe applications
+        // store YCCK data of inverted CMYK colors.
         // See <https://github.com/mozilla/pdf.js/blob/69595a29192b7704733404a42a2ebb537601117b/src/core/jpg.js#L1331>
         for c in decoded.chunks_mut(4) {
             let y = c[0] as f32;
@@ -135,6 +147,33 @@ fn maybe_patch_jpeg_dimensions<'a>(
 }
 
 fn find_sof_marker(data: &[u8]) -> Option<usize> {
+    find_marker(data, |marker, _| {
+        // All SOF markers carry dimensions: SOF0–SOF15, excluding
+        // 0xC4 (DHT), 0xC8 (JPG), 0xCC (DAC) which are not frame markers.
+        matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC)
+    })
+}
+
+/// Return the transform flag of the Adobe APP14 marker, if present.
+///
+/// 0 means that the data is stored as RGB or CMYK, 1 means YCbCr and 2 means YCCK.
+fn find_adobe_transform(data: &[u8]) -> Option<u8> {
+    const ADOBE_ID: &[u8] = b"Adobe";
+    // The transform flag comes after the marker, segment length, identifier,
+    // version and two flag fields.
+    const TRANSFORM_OFFSET: usize = 2 + 2 + ADOBE_ID.len() + 6;
+
+    let offset = find_marker(data, |marker, segment| {
+        marker == 0xEE && segment.get(4..4 + ADOBE_ID.len()) == Some(ADOBE_ID)
+    })?;
+
+    data.get(offset.checked_add(TRANSFORM_OFFSET)?).copied()
+}
+
+/// Return the offset of the first marker (before the start of the scan) that
+/// matches the given predicate, which gets the marker and the data starting at
+/// that marker.
+fn find_marker(data: &[u8], predicate: impl Fn(u8, &[u8]) -> bool) -> Option<usize> {
     let mut i = 0_usize;
 
     while i.checked_add(1).is_some_and(|next| next < data.len()) {
@@ -145,13 +184,14 @@ fn find_sof_marker(data: &[u8]) -> Option<usize> {
 
         let marker = data[i + 1];
 
+        if predicate(marker, &data[i..]) {
+            return Some(i);
+        }
+
         // Note: Not sure if 100% correct/robust, is AI-generated.
         match marker {
-            // All SOF markers carry dimensions: SOF0–SOF15, excluding
-            // 0xC4 (DHT), 0xC8 (JPG), 0xCC (DAC) which are not frame markers.
-            0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
-                return Some(i);
-            }
+            // Start of scan, the entropy-coded data follows.
+            0xDA => return None,
             // Skip padding bytes (0xFF followed by 0xFF).
             0xFF => {
                 i += 1;
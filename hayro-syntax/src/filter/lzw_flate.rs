@@ -9,6 +9,12 @@ pub(crate) mod flate {
     use super::*;
     use crate::filter::lzw_flate::{PredictorParams, apply_predictor};
     use crate::object::Dict;
+    #[cfg(feature = "unsafe")]
+    use crate::object::stream::{FilterResult, ImageData, ImageDecodeParams};
+    #[cfg(feature = "unsafe")]
+    use alloc::borrow::Cow;
+    #[cfg(feature = "unsafe")]
+    use alloc::boxed::Box;
 
     #[cfg(feature = "unsafe")]
     pub(crate) fn decode(data: &[u8], params: &Dict<'_>) -> Option<Vec<u8>> {
@@ -38,6 +44,139 @@ pub(crate) mod flate {
         apply_predictor(decoded, &params)
     }
 
+    /// Decode a flate-compressed image row by row, only keeping every n-th
+    /// pixel in each direction, so that the image is never held in memory in
+    /// full resolution.
+    ///
+    /// Returns `None` if no subsampling was requested, if the rows of the
+    /// predictor don't line up with the rows of the image or if the stream is
+    /// broken. In that case, the stream should be decoded as a whole instead.
+    #[cfg(feature = "unsafe")]
+    pub(crate) fn decode_subsampled(
+        data: &[u8],
+        params: &Dict<'_>,
+        image_params: &ImageDecodeParams,
+    ) -> Option<FilterResult<'static>> {
+        use flate2::read::{DeflateDecoder, ZlibDecoder};
+        use std::io::{ErrorKind, Read};
+
+        let factor = image_params.subsampling.filter(|f| *f > 1)?;
+        let bits_per_component = image_params.bpc?;
+        let num_components = image_params.num_components?;
+
+        if !matches!(bits_per_component, 1 | 2 | 4 | 8 | 16) {
+            return None;
+        }
+
+        let (width, height) = (image_params.width, image_params.height);
+        let pixel_bits = bits_per_component as usize * num_components as usize;
+        let row_len = (width as usize * pixel_bits).div_ceil(8);
+
+        let params = PredictorParams::from_params(params);
+        let row_filter = match params.predictor {
+            1 => None,
+            _ if params.row_length_in_bytes() != row_len => {
+                warn!("predictor doesn't match the image dimensions");
+
+                return None;
+            }
+            i => {
+                let chunk_len = if i >= 10 {
+                    params.bits_per_pixel().div_ceil(8) as usize
+                } else if i == 2 && params.bits_per_component == 8 {
+                    params.colors as usize
+                } else {
+                    // Only byte-aligned predictors can be applied row by row.
+                    return None;
+                };
+
+                Some((i >= 10, BytesPerPixel::from_row_len(row_len, chunk_len)?))
+            }
+        };
+
+        let mut reader: Box<dyn Read + '_> = if has_zlib_header(data) {
+            Box::new(ZlibDecoder::new(data))
+        } else {
+            Box::new(DeflateDecoder::new(data))
+        };
+
+        let new_width = width.div_ceil(factor);
+        let new_height = height.div_ceil(factor);
+        let new_row_len = (new_width as usize * pixel_bits).div_ceil(8);
+        let mut output = vec![0; new_row_len * new_height as usize];
+
+        let is_png_predictor = row_filter.is_some_and(|(png, _)| png);
+        let mut in_row = vec![0; row_len + usize::from(is_png_predictor)];
+        let mut row = vec![0; row_len];
+        let mut prev_row = vec![];
+
+        for y in 0..height as usize {
+            match reader.read_exact(&mut in_row) {
+                Ok(()) => {}
+                // Missing data is padded with zeroes.
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof && y > 0 => break,
+                Err(_) => return None,
+            }
+
+            match row_filter {
+                None => row.copy_from_slice(&in_row),
+                Some((true, tbpp)) => {
+                    row.copy_from_slice(&in_row[1..]);
+
+                    if let Some(filter) = RowFilter::from_u8(in_row[0]) {
+                        png::unfilter(filter, tbpp, &prev_row, &mut row);
+                    }
+                }
+                Some((false, tbpp)) => {
+                    row.copy_from_slice(&in_row);
+                    png::unfilter(RowFilter::Sub, tbpp, &prev_row, &mut row);
+                }
+            }
+
+            // All rows need to be decoded because the predictor might refer to
+            // them, but only every n-th row is kept.
+            if y.is_multiple_of(factor as usize) {
+                let start = y / factor as usize * new_row_len;
+                let output_row = output.get_mut(start..start + new_row_len)?;
+                let mut writer = BitWriter::new(output_row, bits_per_component)?;
+
+                for x in (0..width as usize).step_by(factor as usize) {
+                    let mut reader = BitReader::new_with(&row, x * pixel_bits);
+
+                    for _ in 0..num_components {
+                        writer.write(reader.read(bits_per_component)?)?;
+                    }
+                }
+            }
+
+            core::mem::swap(&mut row, &mut prev_row);
+            row.resize(row_len, 0);
+        }
+
+        Some(FilterResult {
+            data: Cow::Owned(output),
+            image_data: Some(ImageData {
+                alpha: None,
+                color_space: None,
+                bits_per_component,
+                width: new_width,
+                height: new_height,
+            }),
+        })
+    }
+
+    /// Whether the data starts with a valid zlib header, instead of being a
+    /// raw deflate stream.
+    fn has_zlib_header(data: &[u8]) -> bool {
+        let [cmf, flg, ..] = *data else {
+            return false;
+        };
+
+        (cmf & 0x0f) == 0x08
+            && ((cmf as u16) << 8 | flg as u16).is_multiple_of(31)
+            && (flg & 0x20) == 0
+    }
+
     #[cfg(not(feature = "unsafe"))]
     pub(crate) fn decode(data: &[u8], params: &Dict<'_>) -> Option<Vec<u8>> {
         let decoded = fallback::decode(data)?;
@@ -56,17 +195,9 @@ pub(crate) mod flate {
         }
 
         fn flate_decode(data: &[u8]) -> Option<Vec<u8>> {
-            if data.len() >= 2 {
-                let cmf = data[0];
-                let flg = data[1];
-
-                if (cmf & 0x0f) == 0x08
-                    && ((cmf as u16) << 8 | flg as u16).is_multiple_of(31)
-                    && (flg & 0x20) == 0
-                {
-                    let mut stream = FlateStream::new(&data[2..]);
-                    return stream.decode();
-                }
+            if super::has_zlib_header(data) {
+                let mut stream = FlateStream::new(&data[2..]);
+                return stream.decode();
             }
 
             let mut stream = FlateStream::new(data);
@@ -1097,6 +1228,41 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "unsafe")]
+    #[test]
+    fn decode_flate_subsampled() {
+        use crate::object::stream::ImageDecodeParams;
+        use crate::reader::{Reader, ReaderContext, ReaderExt};
+
+        // The input of `predictor_sub`, wrapped in an uncompressed deflate block.
+        let mut input = vec![0x01, 30, 0, !30, !0];
+        input.extend_from_slice(&[
+            1, 127, 127, 127, 254, 2, 0, 254, 1, 1,
+            1, 128, 129, 126, 254, 3, 254, 251, 251, 2,
+            1, 131, 130, 122, 2, 255, 6, 250, 227, 254,
+        ]);
+
+        let params = Reader::new(b"<< /Predictor 11 /Colors 3 /Columns 3 >>")
+            .read_with_context::<Dict<'_>>(&ReaderContext::dummy())
+            .unwrap();
+        let image_params = ImageDecodeParams {
+            bpc: Some(8),
+            num_components: Some(3),
+            subsampling: Some(2),
+            width: 3,
+            height: 3,
+            ..Default::default()
+        };
+
+        let decoded = flate::decode_subsampled(&input, &params, &image_params).unwrap();
+        let image_data = decoded.image_data.unwrap();
+        assert_eq!((image_data.width, image_data.height), (2, 2));
+        assert_eq!(
+            decoded.data.as_ref(),
+            &[127, 127, 127, 123, 130, 128, 131, 130, 122, 127, 100, 126]
+        );
+    }
+
     #[test]
     fn predictor_up() {
         predictor_test(
@@ -46,6 +46,22 @@ pub enum Filter {
 }
 
 impl Filter {
+    /// Return the name of the filter as it is written in a PDF file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AsciiHexDecode => "ASCIIHexDecode",
+            Self::Ascii85Decode => "ASCII85Decode",
+            Self::LzwDecode => "LZWDecode",
+            Self::FlateDecode => "FlateDecode",
+            Self::RunLengthDecode => "RunLengthDecode",
+            Self::CcittFaxDecode => "CCITTFaxDecode",
+            Self::Jbig2Decode => "JBIG2Decode",
+            Self::DctDecode => "DCTDecode",
+            Self::JpxDecode => "JPXDecode",
+            Self::Crypt => "Crypt",
+        }
+    }
+
     fn debug_name(&self) -> &'static str {
         match self {
             Self::AsciiHexDecode => "ascii_hex",
@@ -100,9 +116,18 @@ impl Filter {
             Self::LzwDecode => lzw_flate::lzw::decode(data, params)
                 .map(FilterResult::from_data)
                 .ok_or(DecodeFailure::StreamDecode),
-            Self::FlateDecode => lzw_flate::flate::decode(data, params)
-                .map(FilterResult::from_data)
-                .ok_or(DecodeFailure::StreamDecode),
+            Self::FlateDecode => {
+                #[cfg(feature = "unsafe")]
+                if let Some(result) =
+                    lzw_flate::flate::decode_subsampled(data, params, image_params)
+                {
+                    return Ok(result);
+                }
+
+                lzw_flate::flate::decode(data, params)
+                    .map(FilterResult::from_data)
+                    .ok_or(DecodeFailure::StreamDecode)
+            }
             #[cfg(feature = "images")]
             Self::DctDecode => {
                 dct::decode(data, params, image_params).ok_or(DecodeFailure::ImageDecode)
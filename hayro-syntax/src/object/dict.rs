@@ -503,6 +503,7 @@ pub mod keys {
     // D
     key!(D, b"D");
     key!(DA, b"DA");
+    key!(DAMAGED_ROWS_BEFORE_ERROR, b"DamagedRowsBeforeError");
     key!(DARKEN, b"Darken");
     key!(DATE, b"Date");
     key!(DCT_DECODE, b"DCTDecode");
@@ -35,6 +35,13 @@ impl PartialEq for Stream<'_> {
     }
 }
 
+/// An empty stream with an empty dictionary.
+impl Default for Stream<'_> {
+    fn default() -> Self {
+        Self::new(&[], Dict::default())
+    }
+}
+
 /// Additional parameters for decoding images.
 #[derive(Clone, PartialEq, Default)]
 pub struct ImageDecodeParams {
@@ -48,6 +55,14 @@ pub struct ImageDecodeParams {
     /// in case it's possible, a version of the image will be extracted that
     /// is as close as possible to the hinted dimension.
     pub target_dimension: Option<(u32, u32)>,
+    /// Only keep every n-th pixel of the image in each direction.
+    ///
+    /// Like `target_dimension`, this is only a hint for filters that can
+    /// downsample an image while decoding it, so that it never needs to be
+    /// held in memory in full resolution (currently, this is only the case
+    /// for Flate-compressed images). If the image was downsampled, the
+    /// returned [`ImageData`] contains the reduced dimensions.
+    pub subsampling: Option<u32>,
     /// The width of the image as indicated by the image dictionary.
     pub width: u32,
     /// The height of the image as indicated by the image dictionary.
@@ -171,12 +186,25 @@ impl<'a> Stream<'a> {
         let filters_and_params = self.filters_and_params();
 
         let mut current: Option<FilterResult<'a>> = None;
-
-        for (filter, params) in filters_and_params
+        let num_filters = filters_and_params.filters.len();
+        // Only the output of the last filter is the actual image data, so
+        // the others must not be downsampled.
+        let inner_params = ImageDecodeParams {
+            subsampling: None,
+            ..image_params.clone()
+        };
+
+        for (i, (filter, params)) in filters_and_params
             .filters
             .iter()
             .zip(filters_and_params.params.iter())
+            .enumerate()
         {
+            let image_params = if i + 1 == num_filters {
+                image_params
+            } else {
+                &inner_params
+            };
             let new = filter.apply(
                 current.as_ref().map(|c| c.data.as_ref()).unwrap_or(&data),
                 params,
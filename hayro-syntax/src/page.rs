@@ -4,6 +4,8 @@ use crate::content::{TypedIter, UntypedIter};
 use crate::object::Array;
 use crate::object::Dict;
 use crate::object::Name;
+use crate::object::ObjRef;
+use crate::object::ObjectIdentifier;
 use crate::object::Rect;
 use crate::object::Stream;
 use crate::object::dict::keys::*;
@@ -14,6 +16,7 @@ use crate::transform::Transform;
 use crate::util::FloatExt;
 use crate::xref::XRef;
 use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::ops::Deref;
@@ -74,6 +77,7 @@ impl<'a> Pages<'a> {
                     &dict,
                     &PagesContext::new(),
                     Resources::new(Dict::empty(), None, ctx),
+                    pages.len(),
                     true,
                 )
             {
@@ -135,7 +139,8 @@ fn resolve_pages<'a>(
             // Let's be lenient and assume it's a `Page` in case it's `None` or something else
             // (see corpus test case 0083781).
             _ => {
-                if let Some(page) = Page::new(&dict, &ctx, resources.clone(), false) {
+                if let Some(page) = Page::new(&dict, &ctx, resources.clone(), entries.len(), false)
+                {
                     entries.push(page);
                 }
             }
@@ -164,6 +169,7 @@ pub struct Page<'a> {
     media_box: Rect,
     crop_box: Rect,
     rotation: Rotation,
+    index: usize,
     page_streams: OnceLock<Option<Vec<u8>>>,
     resources: Resources<'a>,
     ctx: ReaderContext<'a>,
@@ -174,6 +180,7 @@ impl<'a> Page<'a> {
         dict: &Dict<'a>,
         ctx: &PagesContext,
         resources: Resources<'a>,
+        index: usize,
         brute_force: bool,
     ) -> Option<Self> {
         // In general, pages without content are allowed, but in case we are brute-forcing
@@ -213,6 +220,7 @@ impl<'a> Page<'a> {
             media_box,
             crop_box,
             rotation,
+            index,
             page_streams: OnceLock::new(),
             resources,
             ctx,
@@ -227,6 +235,10 @@ impl<'a> Page<'a> {
     }
 
     /// Return the decoded content stream of the page.
+    ///
+    /// The content stream is only decoded the first time, and then cached for as
+    /// long as the page lives. Since [`Pdf::pages`](crate::Pdf::pages) always returns
+    /// the same pages, this means that it is decoded once per document.
     pub fn page_stream(&self) -> Option<&[u8]> {
         let convert_single = |s: Stream<'_>| {
             let data = s.decoded().ok()?;
@@ -269,6 +281,11 @@ impl<'a> Page<'a> {
         self.media_box
     }
 
+    /// Get the index of the page in the document, starting at 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
     /// Get the rotation of the page.
     pub fn rotation(&self) -> Rotation {
         self.rotation
@@ -332,6 +349,28 @@ impl<'a> Page<'a> {
         self.ctx.xref()
     }
 
+    /// Return all fonts in the resources of the page.
+    ///
+    /// This includes the fonts used by form `XObject`s, tiling patterns and Type3
+    /// fonts on the page. Since the content streams are not inspected, it may
+    /// also include fonts that are never actually drawn. Each font is only
+    /// returned once, even if it is referenced multiple times.
+    pub fn fonts(&self) -> Vec<PageFont<'a>> {
+        let mut collector = FontCollector {
+            fonts: vec![],
+            visited: BTreeSet::new(),
+        };
+
+        let mut resources = Some(&self.resources);
+
+        while let Some(r) = resources {
+            collector.collect(&r.fonts, &r.x_objects, &r.patterns);
+            resources = r.parent();
+        }
+
+        collector.fonts
+    }
+
     /// Return a typed iterator over the operators of the page's content stream.
     pub fn typed_operations(&self) -> TypedIter<'_> {
         TypedIter::from_untyped(self.operations())
@@ -482,6 +521,178 @@ impl<'a> Resources<'a> {
         self.get_resource::<Object<'_>>(name, &self.shadings)
             .or_else(|| self.parent.as_ref().and_then(|p| p.get_shading(name)))
     }
+
+    /// Get a property list by name.
+    pub fn get_property(&self, name: &Name<'_>) -> Option<Dict<'a>> {
+        self.get_resource::<Dict<'_>>(name, &self.properties)
+            .or_else(|| self.parent.as_ref().and_then(|p| p.get_property(name)))
+    }
+
+    /// Get the reference of a property list by name.
+    ///
+    /// Returns `None` if the property list is stored directly in the resources
+    /// instead of being an indirect object.
+    pub fn get_property_ref(&self, name: &Name<'_>) -> Option<ObjRef> {
+        if self.properties.contains_key(name.deref()) {
+            self.properties.get_ref(name.deref())
+        } else {
+            self.parent.as_ref().and_then(|p| p.get_property_ref(name))
+        }
+    }
+}
+
+/// A font in the resources of a page.
+#[derive(Debug, Clone)]
+pub struct PageFont<'a> {
+    /// The name under which the font is stored in the resources.
+    pub resource_name: Name<'a>,
+    /// The `BaseFont` entry of the font, including the subset tag.
+    pub base_font: Option<Name<'a>>,
+    /// The subtype of the font, like `Type1` or `TrueType`.
+    pub subtype: Option<Name<'a>>,
+    /// Whether the font program is embedded in the document.
+    ///
+    /// This is always the case for Type3 fonts.
+    pub embedded: bool,
+    /// Whether the font is a subset, as indicated by a tag like `ABCDEF+`
+    /// in front of its name.
+    pub subset: bool,
+    /// The encoding of the font, or `None` if the font uses its built-in encoding.
+    pub encoding: Option<PageFontEncoding<'a>>,
+    /// The raw dictionary of the font.
+    pub dict: Dict<'a>,
+}
+
+impl<'a> PageFont<'a> {
+    fn new(resource_name: Name<'a>, dict: Dict<'a>) -> Self {
+        let base_font = dict.get::<Name<'_>>(BASE_FONT);
+        let subtype = dict.get::<Name<'_>>(SUBTYPE);
+        let subset = base_font
+            .as_deref()
+            .is_some_and(|name| subset_tag(name).is_some());
+
+        let embedded = match subtype.as_deref() {
+            Some(TYPE3) => true,
+            Some(TYPE0) => dict
+                .get::<Array<'_>>(DESCENDANT_FONTS)
+                .and_then(|a| a.iter::<Dict<'_>>().next())
+                .is_some_and(|d| has_font_file(&d)),
+            _ => has_font_file(&dict),
+        };
+
+        let encoding = if let Some(name) = dict.get::<Name<'_>>(ENCODING) {
+            Some(PageFontEncoding::Named(name))
+        } else if let Some(stream) = dict.get::<Stream<'_>>(ENCODING) {
+            Some(PageFontEncoding::EmbeddedCMap(
+                stream.dict().get::<Name<'_>>(CMAPNAME),
+            ))
+        } else {
+            dict.get::<Dict<'_>>(ENCODING)
+                .map(|d| PageFontEncoding::Differences(d.get::<Name<'_>>(BASE_ENCODING)))
+        };
+
+        Self {
+            resource_name,
+            base_font,
+            subtype,
+            embedded,
+            subset,
+            encoding,
+            dict,
+        }
+    }
+
+    /// Return the name of the font without the subset tag.
+    pub fn name(&self) -> Option<&[u8]> {
+        let name = self.base_font.as_deref()?;
+
+        Some(subset_tag(name).map_or(name, |_| &name[7..]))
+    }
+}
+
+/// The encoding of a font in the resources of a page.
+#[derive(Debug, Clone)]
+pub enum PageFontEncoding<'a> {
+    /// A predefined encoding like `WinAnsiEncoding`, or a predefined cmap like
+    /// `Identity-H` in the case of Type0 fonts.
+    Named(Name<'a>),
+    /// An encoding dictionary with the given base encoding, which is modified
+    /// by its `Differences` array.
+    Differences(Option<Name<'a>>),
+    /// An embedded cmap with the given name.
+    EmbeddedCMap(Option<Name<'a>>),
+}
+
+/// Return the subset tag of a font name, if it has one.
+fn subset_tag(name: &[u8]) -> Option<&[u8]> {
+    let tag = name.get(..7)?;
+
+    (tag[..6].iter().all(u8::is_ascii_uppercase) && tag[6] == b'+').then_some(tag)
+}
+
+fn has_font_file(dict: &Dict<'_>) -> bool {
+    dict.get::<Dict<'_>>(FONT_DESC).is_some_and(|d| {
+        d.contains_key(FONT_FILE) || d.contains_key(FONT_FILE2) || d.contains_key(FONT_FILE3)
+    })
+}
+
+struct FontCollector<'a> {
+    fonts: Vec<PageFont<'a>>,
+    /// The fonts, `XObject`s and patterns that have already been visited.
+    visited: BTreeSet<ObjectIdentifier>,
+}
+
+impl<'a> FontCollector<'a> {
+    fn collect(&mut self, fonts: &Dict<'a>, x_objects: &Dict<'a>, patterns: &Dict<'a>) {
+        for name in fonts.keys() {
+            let Some(dict) = fonts.get::<Dict<'_>>(name.deref()) else {
+                continue;
+            };
+
+            if !self.visit(dict.obj_id()) {
+                continue;
+            }
+
+            if dict.get::<Name<'_>>(SUBTYPE).as_deref() == Some(TYPE3) {
+                self.collect_nested(&dict);
+            }
+
+            self.fonts.push(PageFont::new(name, dict));
+        }
+
+        for name in x_objects.keys() {
+            if let Some(stream) = x_objects.get::<Stream<'_>>(name.deref())
+                && stream.dict().get::<Name<'_>>(SUBTYPE).as_deref() == Some(FORM)
+                && self.visit(Some(stream.obj_id()))
+            {
+                self.collect_nested(stream.dict());
+            }
+        }
+
+        for name in patterns.keys() {
+            // Only tiling patterns are streams with their own resources.
+            if let Some(stream) = patterns.get::<Stream<'_>>(name.deref())
+                && self.visit(Some(stream.obj_id()))
+            {
+                self.collect_nested(stream.dict());
+            }
+        }
+    }
+
+    fn collect_nested(&mut self, dict: &Dict<'a>) {
+        if let Some(resources) = dict.get::<Dict<'_>>(RESOURCES) {
+            let get = |key| resources.get::<Dict<'_>>(key).unwrap_or_default();
+
+            self.collect(&get(FONT), &get(XOBJECT), &get(PATTERN));
+        }
+    }
+
+    /// Mark an object as visited and return whether it hasn't been visited before.
+    ///
+    /// Direct objects can't be referenced multiple times, so they are always visited.
+    fn visit(&mut self, id: Option<ObjectIdentifier>) -> bool {
+        id.is_none_or(|id| self.visited.insert(id))
+    }
 }
 
 // <https://github.com/apache/pdfbox/blob/a53a70db16ea3133994120bcf1e216b9e760c05b/pdfbox/src/main/java/org/apache/pdfbox/pdmodel/common/PDRectangle.java#L38>
@@ -289,7 +289,7 @@ fn check_cmaps(folder: &str) {
 
                     cmap_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-                    let result = catch_unwind(|| CMap::parse(&decoded, |_| None));
+                    let result = catch_unwind(|| CMap::parse(&decoded, |_| None::<&[u8]>));
 
                     match result {
                         Ok(Some(_)) => {}
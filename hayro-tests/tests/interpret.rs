@@ -0,0 +1,2943 @@
+use crate::WORKSPACE_PATH;
+use hayro::hayro_interpret::color::{AlphaColor, ColorSpace, RenderingIntent};
+use hayro::hayro_interpret::font::{
+    DirectoryCMapResolver, FallbackFontQuery, Font, FontData, FontFallback, FontQuery, Glyph,
+    GlyphCache, GlyphId, GlyphRun, Script, StandardFont, SystemFontResolver,
+};
+use hayro::hayro_interpret::gradient::{AxialGradient, Mesh, RadialGradient};
+use hayro::hayro_interpret::hayro_cmap::{self, CMapName};
+use hayro::hayro_interpret::hit_test::{HitKind, hit_test};
+use hayro::hayro_interpret::image::PdfImagesExt;
+use hayro::hayro_interpret::pattern::Pattern;
+use hayro::hayro_interpret::{
+    BlendMode, ClipPath, Context, CorruptFontPolicy, Device, DisplayList, DrawMode, DrawProps,
+    FillRule, Image, ImageData, ImageDrawProps, InterpretError, InterpreterCache,
+    InterpreterSettings, InterpreterWarning, MarkedContent, OwnedDisplayList, Paint, RenderQuality,
+    SharedCache, SoftMask, StrokeProps, TeeDevice, TextRenderingMode, UnknownOperatorPolicy,
+    Warning, XObject, ZeroWidthLinePolicy, interpret_page, interpret_page_strict,
+    interpret_xobject,
+};
+use hayro_syntax::Pdf;
+use hayro_syntax::object::{Dict, Name, ObjectIdentifier};
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape, Vec2};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn interpret_with<'a, T: Device<'a>>(
+    pdf: &'a Pdf,
+    settings: InterpreterSettings,
+    cache: &'a InterpreterCache<'a>,
+    device: &mut T,
+) {
+    let mut context = Context::new(
+        Affine::IDENTITY,
+        Rect::new(0.0, 0.0, 1.0, 1.0),
+        cache,
+        pdf.xref(),
+        settings,
+    );
+
+    interpret_page(&pdf.pages()[0], &mut context, device);
+}
+
+fn load(file: &str) -> Pdf {
+    let data = std::fs::read(WORKSPACE_PATH.join(file)).unwrap();
+    Pdf::new(data).unwrap()
+}
+
+fn record(file: &str, settings: InterpreterSettings) -> Recorder {
+    let pdf = load(file);
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, settings, &cache, &mut recorder);
+
+    recorder
+}
+
+fn record_glyphs(file: &str) -> Vec<(usize, Point)> {
+    record(file, InterpreterSettings::default()).glyphs
+}
+
+#[test]
+fn type3_char_proc_does_not_affect_parent_text_state() {
+    let glyphs = record_glyphs("pdfs/custom/font_type3_text_state_isolation.pdf");
+
+    // The char procs draw text of their own, which must not be confused
+    // with the glyphs of the page.
+    assert!(glyphs.iter().any(|(depth, _)| *depth == 1));
+
+    let parent = glyphs
+        .iter()
+        .filter(|(depth, _)| *depth == 0)
+        .map(|(_, p)| *p)
+        .collect::<Vec<_>>();
+
+    // The char procs change the text matrix, leading, character spacing
+    // and horizontal scaling, none of which may leak into the page's text
+    // state.
+    assert_eq!(
+        parent,
+        vec![
+            Point::new(10.0, 50.0),
+            Point::new(30.0, 50.0),
+            Point::new(10.0, 25.0),
+        ]
+    );
+}
+
+#[test]
+fn corrupt_embedded_font_falls_back_to_standard_font() {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+        },
+        ..Default::default()
+    };
+
+    // The `glyf` table of the embedded font is truncated, so none of its
+    // glyphs can be read.
+    let recorder = record("pdfs/custom/font_truetype_corrupt_glyf.pdf", settings);
+
+    assert!(
+        warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|w| matches!(w, InterpreterWarning::CorruptEmbeddedFont { .. }))
+    );
+
+    // "Hello" must still be drawn, using a substitute font.
+    assert_eq!(recorder.outlines.len(), 5);
+    assert!(recorder.outlines.iter().all(|o| o.area() != 0.0));
+}
+
+#[test]
+fn corrupt_embedded_font_policy() {
+    for policy in [CorruptFontPolicy::Skip, CorruptFontPolicy::Error] {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let settings = InterpreterSettings {
+            corrupt_fonts: policy,
+            warning_sink: {
+                let warnings = warnings.clone();
+                Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+            },
+            ..Default::default()
+        };
+
+        let recorder = record("pdfs/custom/font_truetype_corrupt_glyf.pdf", settings);
+
+        // The font is still reported, but no substitute font is used.
+        let warnings = warnings.lock().unwrap();
+        assert!(
+            warnings
+                .iter()
+                .any(|w| matches!(w, InterpreterWarning::CorruptEmbeddedFont { .. }))
+        );
+        assert_eq!(
+            warnings
+                .iter()
+                .any(|w| matches!(w, InterpreterWarning::SkippedFont { .. })),
+            policy == CorruptFontPolicy::Skip
+        );
+        assert!(recorder.glyphs.is_empty());
+    }
+}
+
+#[test]
+fn corrupt_embedded_font_policy_ignores_unsupported_fonts() {
+    for policy in [CorruptFontPolicy::Skip, CorruptFontPolicy::Error] {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let settings = InterpreterSettings {
+            corrupt_fonts: policy,
+            warning_sink: {
+                let warnings = warnings.clone();
+                Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+            },
+            ..Default::default()
+        };
+
+        let recorder = record("pdfs/custom/font_unsupported_subtype.pdf", settings);
+
+        // Fonts that aren't corrupt but can't be loaded at all still fall back
+        // to Helvetica.
+        assert!(warnings.lock().unwrap().is_empty());
+        assert_eq!(recorder.glyphs.len(), 2);
+    }
+}
+
+#[test]
+fn invalid_widths_are_not_reported_as_corrupt_font() {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+        },
+        ..Default::default()
+    };
+
+    // The embedded font program is intact, but `LastChar` is smaller than
+    // `FirstChar`, so the font is still replaced by a substitute font.
+    let recorder = record(
+        "pdfs/custom/font_truetype_inverted_char_range.pdf",
+        settings,
+    );
+
+    assert!(
+        !warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|w| matches!(w, InterpreterWarning::CorruptEmbeddedFont { .. }))
+    );
+    assert!(!recorder.outlines.is_empty());
+}
+
+#[test]
+fn type1_font_split_by_stream_lengths() {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+        },
+        ..Default::default()
+    };
+
+    // The font program has an unusual header and a hex-encoded encrypted portion,
+    // so it can only be read with the help of `Length1` and `Length2`.
+    let recorder = record("pdfs/custom/font_type1_nonstandard_header.pdf", settings);
+    assert!(warnings.lock().unwrap().is_empty());
+    assert_eq!(recorder.outlines.len(), 4);
+
+    // The outlines must match the ones of the original font program.
+    let pdf = load("pdfs/custom/font_type1_nonstandard_header.pdf");
+    let original_pdf = load("pdfs/custom/font_type1_1.pdf");
+    let font = load_font(&pdf, "F1");
+    let original = load_font(&original_pdf, "F28");
+
+    for gid in 1..20 {
+        let outline = font.glyph_outline(GlyphId::new(gid)).unwrap();
+        assert!(outline.area() != 0.0);
+        assert_eq!(Some(outline), original.glyph_outline(GlyphId::new(gid)));
+    }
+}
+
+#[test]
+fn cid_font_with_predefined_cmap() {
+    let file = "pdfs/custom/font_cid_predefined_cmap.pdf";
+
+    // `UniJIS-UCS2-H` maps "A" and "B" to the CIDs 34 and 35.
+    let recorder = record(file, InterpreterSettings::default());
+    assert_eq!(recorder.outlines.len(), 2);
+    assert!(recorder.outlines.iter().all(|o| o.area() != 0.0));
+
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        cmap_resolver: Arc::new(|_| None),
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+        },
+        ..Default::default()
+    };
+
+    // Without the cmap, the font can't be used, and the substitute font reads
+    // the string as single-byte codes instead.
+    let recorder = record(file, settings);
+    assert_eq!(recorder.outlines.len(), 4);
+    assert!(
+        warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|w| matches!(w, InterpreterWarning::UnsupportedFont { .. }))
+    );
+}
+
+#[test]
+fn truetype_symbolic_cmap_lookup() {
+    let recorder = record(
+        "pdfs/custom/font_truetype_symbolic_cmap.pdf",
+        InterpreterSettings::default(),
+    );
+
+    // Glyph n of the font is 100 * n units high. The (1, 0) cmap maps "A" to the
+    // first glyph, the (3, 0) cmap maps 0xF041 to the second one and the (3, 1)
+    // cmap maps U+0041 to the third one.
+    let glyphs = recorder
+        .outlines
+        .iter()
+        .map(|o| (o.bounding_box().height() / 100.0).round() as u32)
+        .collect::<Vec<_>>();
+
+    // The (3, 0) cmap takes precedence, unless `Differences` assigns a glyph name.
+    assert_eq!(glyphs, [2, 2, 3]);
+}
+
+#[test]
+fn synthetic_bold_and_oblique() {
+    let file = "pdfs/custom/font_synthetic_style.pdf";
+    // The resolver only provides a regular face, even for Helvetica-BoldOblique.
+    let settings = || InterpreterSettings {
+        font_resolver: Arc::new(|_| {
+            let data: FontData =
+                Arc::new(&include_bytes!("../assets/LiberationSans-Regular.ttf")[..]);
+            Some((data, 0))
+        }),
+        ..Default::default()
+    };
+
+    let pdf = load(file);
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, settings(), &cache, &mut recorder);
+
+    // The bold glyph is stroked in addition to being filled.
+    assert_eq!(
+        recorder
+            .runs
+            .iter()
+            .map(|r| (r.2, r.4, r.5))
+            .collect::<Vec<_>>(),
+        [
+            (TextRenderingMode::Fill, true, true),
+            (TextRenderingMode::Fill, true, false)
+        ]
+    );
+
+    // The oblique glyph is drawn twice and is a skewed version of the regular one.
+    let recorder = record(file, settings());
+    assert_eq!(recorder.outlines.len(), 3);
+    let oblique = recorder.outlines[0].bounding_box();
+    let expected = (Affine::skew(0.2126, 0.0) * recorder.outlines[2].clone()).bounding_box();
+    assert!(oblique.x1 > recorder.outlines[2].bounding_box().x1 + 100.0);
+    assert!((oblique.origin() - expected.origin()).hypot() < 1e-3);
+    assert!((oblique.size() - expected.size()).to_vec2().hypot() < 1e-3);
+}
+
+#[test]
+fn font_fallback_chain() {
+    let file = "pdfs/custom/font_fallback_chain.pdf";
+    let font = |data: &'static [u8]| -> FontData { Arc::new(data) };
+    let queries = Arc::new(Mutex::new(Vec::new()));
+    let settings = |font_fallbacks| InterpreterSettings {
+        font_resolver: {
+            let queries = queries.clone();
+            Arc::new(move |query| {
+                let (standard, script) = match query {
+                    FontQuery::Standard(s) => (*s, None),
+                    FontQuery::Fallback(f) => (f.pick_standard_font(), f.script),
+                };
+                queries.lock().unwrap().push(script);
+
+                Some(standard.get_font_data())
+            })
+        },
+        font_fallbacks,
+        ..Default::default()
+    };
+
+    // The standard fonts have no glyphs for the Cyrillic text of the first font.
+    let recorder = record(file, settings(Vec::new()));
+    assert_eq!(recorder.outlines.len(), 3);
+    assert!(recorder.outlines[..2].iter().all(|o| o.area() == 0.0));
+    // The script of the Japanese CID font is passed on to the resolver.
+    assert_eq!(
+        std::mem::take(&mut *queries.lock().unwrap()),
+        [None, Some(Script::Japanese)]
+    );
+
+    let fallbacks = vec![
+        FontFallback {
+            script: Script::Greek,
+            data: font(b"not a font"),
+            index: 0,
+        },
+        FontFallback {
+            script: Script::Cyrillic,
+            data: font(b"not a font"),
+            index: 0,
+        },
+        FontFallback {
+            script: Script::Cyrillic,
+            data: font(include_bytes!("../assets/LiberationSans-Regular.ttf")),
+            index: 0,
+        },
+        FontFallback {
+            script: Script::Japanese,
+            data: font(include_bytes!("../assets/LiberationMono-Regular.ttf")),
+            index: 0,
+        },
+    ];
+
+    // Fonts in the chain that can't be loaded are skipped, and the resolver
+    // isn't consulted at all.
+    let recorder = record(file, settings(fallbacks));
+    assert_eq!(recorder.outlines.len(), 3);
+    assert!(recorder.outlines.iter().all(|o| o.area() != 0.0));
+    assert!(queries.lock().unwrap().is_empty());
+}
+
+#[test]
+fn glyph_cache_respects_memory_limit() {
+    let file = "pdfs/custom/text_extraction_runs.pdf";
+    let pdf = load(file);
+    let expected = record(file, InterpreterSettings::default()).outlines;
+    assert!(!expected.is_empty());
+
+    let record_with = |glyph_cache: GlyphCache| {
+        let cache = InterpreterCache::with_glyph_cache(glyph_cache.clone());
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+        assert_eq!(recorder.outlines, expected);
+
+        glyph_cache.memory_usage()
+    };
+
+    let unlimited = record_with(GlyphCache::default());
+    assert!(unlimited > 0);
+    // Outlines are reused across interpreter runs.
+    let glyph_cache = GlyphCache::default();
+    record_with(glyph_cache.clone());
+    assert_eq!(record_with(glyph_cache), unlimited);
+
+    let limited = record_with(GlyphCache::new(unlimited / 2));
+    assert!(limited > 0 && limited <= unlimited / 2);
+    assert_eq!(record_with(GlyphCache::new(0)), 0);
+}
+
+#[test]
+fn glyph_runs_carry_text_state() {
+    let pdf = load("pdfs/custom/text_extraction_runs.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    let runs = &recorder.runs;
+    // The glyphs are only drawn as part of their runs.
+    assert_eq!(
+        recorder.glyphs.len(),
+        runs.iter()
+            .map(|r| r.3.len() * (usize::from(r.4) + usize::from(r.5)))
+            .sum::<usize>()
+    );
+
+    // "Hello", " World", "Ke", "rn", "Both", "Hidden" and "Up".
+    assert_eq!(
+        runs.iter().map(|r| r.3.len()).collect::<Vec<_>>(),
+        [5, 6, 2, 2, 4, 6, 2]
+    );
+
+    // The second string starts where the advances of the first one end.
+    let end = runs[0].3.iter().fold(Vec2::ZERO, |a, b| a + *b);
+    assert_eq!(runs[0].0, Affine::translate((10.0, 170.0)));
+    assert!((runs[1].0.translation() - (Vec2::new(10.0, 170.0) + end)).hypot() < 1e-3);
+
+    // The `TJ` adjustment is applied between the runs.
+    let end = runs[2].3.iter().fold(Vec2::ZERO, |a, b| a + *b);
+    let kern = runs[3].0.translation() - runs[2].0.translation() - end;
+    assert!((kern - Vec2::new(2.4, 0.0)).hypot() < 1e-3);
+
+    assert_eq!(
+        runs.iter()
+            .map(|r| (r.1, r.2, r.4, r.5))
+            .collect::<Vec<_>>(),
+        [
+            (12.0, TextRenderingMode::Fill, true, false),
+            (12.0, TextRenderingMode::Fill, true, false),
+            (12.0, TextRenderingMode::Fill, true, false),
+            (12.0, TextRenderingMode::Fill, true, false),
+            (12.0, TextRenderingMode::FillStroke, true, true),
+            (8.0, TextRenderingMode::Invisible, true, false),
+            // The rendering mode isn't reset at the end of a text object.
+            (10.0, TextRenderingMode::Invisible, true, false),
+        ]
+    );
+}
+
+fn load_font<'a>(pdf: &'a Pdf, name: &str) -> Font<'a> {
+    let dict = pdf.pages()[0]
+        .resources()
+        .fonts
+        .get::<Dict<'_>>(name)
+        .unwrap();
+
+    Font::from_dict(&dict, &InterpreterSettings::default()).unwrap()
+}
+
+#[test]
+fn glyph_outlines_from_fonts() {
+    let file = "pdfs/custom/text_extraction_vertical.pdf";
+    let pdf = load(file);
+    let font = load_font(&pdf, "F1");
+    let outline = font.glyph_outline(GlyphId::new(1)).unwrap();
+
+    assert_eq!(outline.bounding_box(), Rect::new(50.0, 0.0, 450.0, 700.0));
+    assert_eq!(font.font_matrix(), Affine::scale(0.001));
+
+    // The fonts of drawn glyphs return the same outlines.
+    let recorder = record(file, InterpreterSettings::default());
+    assert_eq!(recorder.outlines.len(), 6);
+    for (outline, font_outline) in recorder.outlines.iter().zip(&recorder.font_outlines) {
+        assert_eq!(Some(outline), font_outline.as_ref());
+    }
+
+    // Type3 glyphs don't have outlines.
+    let pdf = load("pdfs/custom/font_type3_text_state_isolation.pdf");
+    let font = load_font(&pdf, "T3");
+    assert!(font.glyph_outline(GlyphId::new(1)).is_none());
+    assert_eq!(font.font_matrix(), Affine::scale(0.001));
+}
+
+#[test]
+fn system_font_resolver_matches_queries() {
+    let font =
+        |name: &str| std::fs::read(WORKSPACE_PATH.join(format!("assets/{name}.ttf"))).unwrap();
+
+    let mut resolver = SystemFontResolver::new();
+    for name in [
+        "LiberationSans-Regular",
+        "LiberationSans-Bold",
+        "LiberationSerif-Italic",
+        "LiberationMono-Regular",
+        "LiberationMono-BoldItalic",
+    ] {
+        resolver.load_font_data(font(name));
+    }
+    assert_eq!(resolver.len(), 5);
+
+    let resolve = |query: FontQuery| {
+        resolver
+            .resolve(&query)
+            .map(|(data, _)| data.as_ref().as_ref().to_vec())
+    };
+
+    // Liberation Sans is metric-compatible with Helvetica.
+    assert_eq!(
+        resolve(FontQuery::Standard(StandardFont::HelveticaBold)),
+        Some(font("LiberationSans-Bold"))
+    );
+
+    // An exact match of the PostScript name.
+    assert_eq!(
+        resolve(FontQuery::Fallback(FallbackFontQuery {
+            post_script_name: Some("LiberationSerif-Italic".to_string()),
+            ..Default::default()
+        })),
+        Some(font("LiberationSerif-Italic"))
+    );
+
+    // The family is derived from the name, the style from the flags.
+    assert_eq!(
+        resolve(FontQuery::Fallback(FallbackFontQuery {
+            post_script_name: Some("LiberationMonoPSMT,BoldItalic".to_string()),
+            is_bold: true,
+            is_italic: true,
+            ..Default::default()
+        })),
+        Some(font("LiberationMono-BoldItalic"))
+    );
+
+    assert_eq!(
+        resolve(FontQuery::Fallback(FallbackFontQuery {
+            font_family: Some("Liberation Sans".to_string()),
+            ..Default::default()
+        })),
+        Some(font("LiberationSans-Regular"))
+    );
+}
+
+#[test]
+fn differences_resolve_glyph_list_names() {
+    let recorder = record(
+        "pdfs/custom/font_differences_glyph_list_names.pdf",
+        InterpreterSettings::default(),
+    );
+
+    // The first font maps A to D to the glyphs `uni0042`, `C.sc` and `u0044`,
+    // which the substitute font only knows as B, C and D.
+    let outlines = &recorder.outlines;
+    assert_eq!(outlines.len(), 9);
+    assert!(outlines[..3].iter().all(|o| !o.elements().is_empty()));
+    assert_eq!(outlines[..3], outlines[5..8]);
+}
+
+#[test]
+fn predefined_cmaps_are_parsed_once() {
+    let requested = Arc::new(Mutex::new(Vec::new()));
+    let default_resolver = InterpreterSettings::default().cmap_resolver;
+    let settings = InterpreterSettings {
+        cmap_resolver: {
+            let requested = requested.clone();
+            Arc::new(move |name| {
+                let requested_name = String::from_utf8_lossy(name.to_bytes()).into_owned();
+                requested.lock().unwrap().push(requested_name);
+                default_resolver(name)
+            })
+        },
+        ..Default::default()
+    };
+
+    // Both fonts use `UniGB-UCS2-H` as their encoding and `Adobe-GB1-UCS2` to
+    // map their CIDs to Unicode, but each cmap only needs to be loaded once.
+    let recorder = record("pdfs/custom/font_cid_shared_predefined_cmap.pdf", settings);
+    assert_eq!(recorder.outlines.len(), 2);
+    assert_eq!(
+        *requested.lock().unwrap(),
+        ["UniGB-UCS2-H", "Adobe-GB1-UCS2"]
+    );
+}
+
+#[test]
+fn directory_cmap_resolver_loads_files() {
+    let dir = std::env::temp_dir().join(format!("hayro-cmaps-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // The predefined cmaps in the binary format of `embed-cmaps`.
+    let ucs2 = hayro_cmap::load_embedded(CMapName::UniJisUcs2H).unwrap();
+    std::fs::write(dir.join("UniJIS-UCS2-H"), ucs2).unwrap();
+    std::fs::write(dir.join("secret"), b"secret").unwrap();
+
+    let resolver = DirectoryCMapResolver::new(dir.join("nested"));
+    assert!(resolver.resolve(CMapName::UniJisUcs2H).is_none());
+    // Names that try to escape the directory are rejected.
+    assert!(
+        resolver
+            .resolve(CMapName::from_bytes(b"../secret"))
+            .is_none()
+    );
+
+    let resolver = DirectoryCMapResolver::new(&dir);
+    // Failed lookups aren't cached, so the cmap is found once the file exists.
+    assert!(resolver.resolve(CMapName::UniJisUcs2V).is_none());
+    std::fs::write(dir.join("UniJIS-UCS2-V.cmap"), ucs2).unwrap();
+    assert!(resolver.resolve(CMapName::UniJisUcs2V).is_some());
+
+    let first = resolver.resolve(CMapName::UniJisUcs2H).unwrap();
+    assert_eq!(*first, *ucs2);
+    // The file is only read once.
+    std::fs::remove_file(dir.join("UniJIS-UCS2-H")).unwrap();
+    assert!(Arc::ptr_eq(
+        &first,
+        &resolver.resolve(CMapName::UniJisUcs2H).unwrap()
+    ));
+
+    let settings = InterpreterSettings {
+        cmap_resolver: resolver.into_resolver(),
+        ..Default::default()
+    };
+    let recorder = record("pdfs/custom/font_cid_predefined_cmap.pdf", settings);
+    assert_eq!(recorder.outlines.len(), 2);
+    assert!(recorder.outlines.iter().all(|o| o.area() != 0.0));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn vertical_metrics_from_w2_and_dw2() {
+    let glyphs = record_glyphs("pdfs/custom/font_vertical_metrics.pdf")
+        .into_iter()
+        .map(|(_, p)| p)
+        .collect::<Vec<_>>();
+
+    // The glyph origins are displaced from the current point by the position
+    // vector. CID 2 has its own metrics in `W2`, the others use `DW2` and half
+    // of their horizontal width. The advances are -800 and -500 units.
+    let expected = [
+        Point::new(140.0, 162.0),
+        Point::new(144.0, 150.0),
+        Point::new(140.0, 136.0),
+    ];
+
+    assert_eq!(glyphs.len(), expected.len());
+    for (actual, expected) in glyphs.iter().zip(expected) {
+        assert!(
+            (*actual - expected).hypot() < 1e-3,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+}
+
+/// A device that records everything that is drawn, and draws color glyphs and type3
+/// glyphs the same way a real renderer would.
+#[derive(Default)]
+struct Recorder {
+    /// The kind and the device-space position of each path and image.
+    drawn: Vec<String>,
+    /// The paint of each path, with the contents of tiling patterns.
+    paints: Vec<String>,
+    /// The color and the bounding box in device space of each path with a solid color,
+    /// including the ones in tiling patterns.
+    paths: Vec<([u8; 4], Rect)>,
+    /// The rendering intent of each path with a solid color.
+    intents: Vec<RenderingIntent>,
+    /// The colorants and their tints of each path with a separation color.
+    colorants: Vec<(Vec<String>, Vec<f32>)>,
+    /// The stroke properties of each stroked path.
+    strokes: Vec<StrokeProps>,
+    /// The flatness tolerance of each path.
+    flatness: Vec<Option<f32>>,
+    /// The blend mode of each path, image and transparency group.
+    blend_modes: Vec<(&'static str, BlendMode)>,
+    /// The number of components of the blending color space of each transparency
+    /// group.
+    group_color_spaces: Vec<Option<u8>>,
+    /// Each marked content event, with the number of things drawn before it.
+    marked_content: Vec<(usize, String)>,
+    /// The glyph IDs of the glyphs that are filled with their outline.
+    outline_glyphs: Vec<u32>,
+    /// The color of each glyph that is filled with its outline.
+    glyph_colors: Vec<[u8; 4]>,
+    /// The color and alpha channel of each raster image.
+    images: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    /// The color data of each image, as a whole and put together from bands of
+    /// two rows.
+    image_rows: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Whether the color and alpha channel of each raster image should be interpolated.
+    interpolate: Vec<(bool, Option<bool>)>,
+    /// The length of the encoded data of each raster image.
+    image_streams: Vec<usize>,
+    /// The color data of each raster image, without copying it.
+    image_buffers: Vec<Arc<[u8]>>,
+    /// The start and end point of each axial gradient in device space, and the
+    /// colors of its stops.
+    axial: Vec<(Point, Point, Vec<[u8; 4]>)>,
+    /// The circles of each radial gradient in device space.
+    radial: Vec<(Point, f32, Point, f32)>,
+    /// The vertices of the triangles of each mesh.
+    mesh_points: Vec<Vec<[Point; 3]>>,
+    /// The colors of the vertices of the triangles of each mesh.
+    mesh_colors: Vec<Vec<[[u8; 4]; 3]>>,
+    /// The colors of each shading pattern, sampled at a couple of points along the
+    /// x axis.
+    shading_samples: Vec<[u8; 4]>,
+    /// The bounding box of the clip path of each shading pattern, if it has one.
+    shading_clips: Vec<Option<Rect>>,
+    /// The smoothness tolerance of each shading pattern.
+    shading_smoothness: Vec<f32>,
+    /// The type3 nesting depth and the device-space origin of each glyph.
+    glyphs: Vec<(usize, Point)>,
+    type3_depth: usize,
+    /// The outline of each glyph that has one.
+    outlines: Vec<BezPath>,
+    /// The outlines of the glyphs, as returned by their fonts.
+    font_outlines: Vec<Option<BezPath>>,
+    /// The text matrix, font size, rendering mode, glyph advances and whether the run
+    /// is filled and stroked, of each glyph run.
+    runs: Vec<(Affine, f32, TextRenderingMode, Vec<Vec2>, bool, bool)>,
+    /// A flag that is set once the first path has been drawn, to request
+    /// cancellation.
+    cancel: Option<Arc<AtomicBool>>,
+    clips: usize,
+}
+
+impl Recorder {
+    fn record_marked_content(&mut self, kind: &str, marked_content: &MarkedContent<'_>) {
+        let properties = marked_content.properties.as_ref();
+        let ty = properties.and_then(|p| p.get::<Name<'_>>(b"Type".as_slice()));
+        let lang =
+            properties.and_then(|p| p.get::<hayro_syntax::object::String<'_>>(b"Lang".as_slice()));
+
+        self.marked_content.push((
+            self.drawn.len(),
+            format!(
+                "{kind} {} {:?} {:?} {:?}",
+                std::str::from_utf8(marked_content.tag).unwrap(),
+                marked_content.mcid,
+                ty.map(|t| t.as_str().to_string()),
+                lang.map(|l| String::from_utf8_lossy(l.as_bytes()).to_string()),
+            ),
+        ));
+    }
+
+    /// Sample the shading pattern `props` are painted with, if any.
+    fn sample_shading(&mut self, props: &DrawProps<'_>) {
+        let Paint::Pattern(pattern) = &props.paint else {
+            return;
+        };
+        let Pattern::Shading(pattern) = pattern.as_ref() else {
+            return;
+        };
+        let encoded = pattern.encode();
+        self.shading_smoothness.push(pattern.smoothness);
+        self.shading_clips.push(
+            pattern
+                .shading
+                .clip_path
+                .as_ref()
+                .map(|path| path.bounding_box()),
+        );
+
+        for x in [0.0, 25.0, 50.0, 75.0, 100.0] {
+            let point = encoded.base_transform * Point::new(x, 50.0);
+            self.shading_samples
+                .push(AlphaColor::new(encoded.sample(point)).to_rgba8());
+        }
+    }
+}
+
+impl<'a> Device<'a> for Recorder {
+    fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, mode: &DrawMode) {
+        let kind = match mode {
+            DrawMode::Fill(_) => "fill",
+            DrawMode::Stroke(stroke_props) => {
+                self.strokes.push(stroke_props.clone());
+                "stroke"
+            }
+            _ => "other",
+        };
+        let bounds = props.transform.transform_rect_bbox(path.bounding_box());
+        self.drawn
+            .push(format!("{kind} {} {}", bounds.x0, bounds.y0));
+        self.blend_modes.push((kind, props.blend_mode));
+        self.flatness.push(props.flatness);
+
+        if let Some(cancel) = &self.cancel {
+            cancel.store(true, Ordering::Relaxed);
+        }
+
+        let paint = match &props.paint {
+            Paint::Color(color) => {
+                let bbox = (props.transform * path.clone()).bounding_box();
+                self.paths.push((color.to_rgba().to_rgba8(), bbox));
+                self.intents.push(color.rendering_intent());
+
+                if let Some(colorants) = color.color_space().colorants() {
+                    self.colorants
+                        .push((colorants.to_vec(), color.components().to_vec()));
+                }
+
+                format!("color {:?}", color.to_rgba().to_rgba8())
+            }
+            Paint::Pattern(pattern) => match pattern.as_ref() {
+                Pattern::Shading(_) => {
+                    self.sample_shading(&props);
+                    "shading".to_string()
+                }
+                Pattern::Tiling(tiling) => {
+                    let mut contents = Self::default();
+                    tiling.interpret(
+                        &mut contents,
+                        Affine::IDENTITY,
+                        matches!(mode, DrawMode::Stroke(_)),
+                    );
+
+                    // The colors of the contents are the ones that end up on the page.
+                    self.paths.extend(contents.paths);
+                    self.intents.extend(contents.intents);
+                    self.colorants.extend(contents.colorants);
+                    self.outline_glyphs.extend(contents.outline_glyphs);
+                    self.glyph_colors.extend(contents.glyph_colors);
+
+                    format!("tiling {:?} {:?}", tiling.matrix, contents.paints)
+                }
+            },
+        };
+        self.paints.push(paint);
+    }
+
+    fn draw_axial_gradient(
+        &mut self,
+        _: &BezPath,
+        gradient: &AxialGradient,
+        props: DrawProps<'a>,
+        _: &DrawMode,
+    ) {
+        self.sample_shading(&props);
+        let stops = gradient
+            .stops()
+            .iter()
+            .map(|stop| AlphaColor::new(stop.color).to_rgba8())
+            .collect();
+        self.axial.push((
+            gradient.transform * gradient.start,
+            gradient.transform * gradient.end,
+            stops,
+        ));
+    }
+
+    fn draw_radial_gradient(
+        &mut self,
+        _: &BezPath,
+        gradient: &RadialGradient,
+        props: DrawProps<'a>,
+        _: &DrawMode,
+    ) {
+        self.sample_shading(&props);
+        self.radial.push((
+            gradient.transform * gradient.start_center,
+            gradient.start_radius,
+            gradient.transform * gradient.end_center,
+            gradient.end_radius,
+        ));
+    }
+
+    fn draw_mesh(&mut self, _: &BezPath, mesh: &Mesh, props: DrawProps<'a>, _: &DrawMode) {
+        self.sample_shading(&props);
+        let triangles = mesh.triangles();
+        self.mesh_points.push(
+            triangles
+                .iter()
+                .map(|t| t.points.map(|p| mesh.transform * p))
+                .collect(),
+        );
+        self.mesh_colors.push(
+            triangles
+                .iter()
+                .map(|t| t.colors.map(|c| AlphaColor::new(c).to_rgba8()))
+                .collect(),
+        );
+    }
+
+    fn push_clip_path(&mut self, _: &ClipPath) {
+        self.clips += 1;
+    }
+
+    fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'a>>, blend_mode: BlendMode) {
+        self.blend_modes.push(("group", blend_mode));
+        self.group_color_spaces.push(None);
+    }
+
+    fn push_transparency_group_with_color_space(
+        &mut self,
+        _: f32,
+        _: Option<SoftMask<'a>>,
+        blend_mode: BlendMode,
+        color_space: &ColorSpace,
+    ) {
+        self.blend_modes.push(("group", blend_mode));
+        self.group_color_spaces
+            .push(Some(color_space.num_components()));
+    }
+
+    fn draw_glyph(
+        &mut self,
+        glyph: &Glyph<'a>,
+        glyph_transform: Affine,
+        props: DrawProps<'a>,
+        _: &DrawMode,
+    ) {
+        let origin = (props.transform * glyph_transform) * Point::ZERO;
+        self.glyphs.push((self.type3_depth, origin));
+
+        if let Glyph::Outline(o) = glyph {
+            self.outlines.push(o.outline());
+            self.font_outlines
+                .push(glyph.font().glyph_outline(o.glyph_id()));
+        }
+
+        match glyph {
+            Glyph::Outline(o) if o.is_color() => {
+                o.interpret_color(self, glyph_transform, &props);
+            }
+            Glyph::Outline(o) => {
+                self.outline_glyphs.push(o.glyph_id().to_u32());
+
+                if let Paint::Color(color) = &props.paint {
+                    self.glyph_colors.push(color.to_rgba().to_rgba8());
+                }
+            }
+            Glyph::Type3(t) => {
+                self.type3_depth += 1;
+                t.interpret(self, props.transform, glyph_transform, &props.paint);
+                self.type3_depth -= 1;
+            }
+        }
+    }
+
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'a>) {
+        self.runs.push((
+            run.text_matrix,
+            run.font_size,
+            run.render_mode,
+            run.glyphs.iter().map(|g| g.advance).collect(),
+            run.fill.is_some(),
+            run.stroke.is_some(),
+        ));
+
+        let fill_mode = if run.render_mode == TextRenderingMode::Invisible {
+            DrawMode::Invisible
+        } else {
+            DrawMode::Fill(FillRule::NonZero)
+        };
+
+        for glyph in &run.glyphs {
+            if let Some(props) = &run.fill {
+                self.draw_glyph(&glyph.glyph, glyph.transform, props.clone(), &fill_mode);
+            }
+
+            if let Some((props, stroke_props)) = &run.stroke {
+                self.draw_glyph(
+                    &glyph.glyph,
+                    glyph.transform,
+                    props.clone(),
+                    &DrawMode::Stroke(stroke_props.clone()),
+                );
+            }
+        }
+    }
+
+    fn draw_image(&mut self, image: Image<'a, '_>, props: ImageDrawProps<'a>) {
+        let bounds = props
+            .transform
+            .transform_rect_bbox(Rect::new(0.0, 0.0, 1.0, 1.0));
+        self.drawn
+            .push(format!("image {} {}", bounds.x0, bounds.y0));
+        self.blend_modes.push(("image", props.blend_mode));
+
+        let raster = match image {
+            Image::Raster(raster) => raster,
+            Image::Stencil(stencil) => {
+                let (mut whole, mut rows) = (vec![], vec![]);
+                stencil.with_stencil(|luma, _| whole = luma.data.to_vec(), None);
+                stencil.with_stencil_rows(2, |_, luma, _| rows.extend(luma.data.iter()), None);
+                self.image_rows.push((whole, rows));
+
+                return;
+            }
+        };
+
+        self.image_streams.push(raster.stream().raw_data().len());
+
+        let mut rows = vec![];
+        raster.with_rgba_rows(
+            2,
+            |_, data, _| match data {
+                ImageData::Rgb(rgb) => rows.extend(rgb.data.iter()),
+                ImageData::Luma(luma) => rows.extend(luma.data.iter()),
+            },
+            None,
+        );
+
+        raster.with_rgba(
+            |data, alpha| {
+                self.interpolate
+                    .push((data.interpolate(), alpha.as_ref().map(|a| a.interpolate)));
+
+                let data = match data {
+                    ImageData::Rgb(rgb) => rgb.data,
+                    ImageData::Luma(luma) => luma.data,
+                };
+                self.image_buffers.push(data.clone());
+                let data = data.to_vec();
+
+                self.image_rows
+                    .push((data.clone(), core::mem::take(&mut rows)));
+                self.images.push((data, alpha.map(|a| a.data.to_vec())));
+            },
+            None,
+        );
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips -= 1;
+    }
+
+    fn pop_transparency_group(&mut self) {}
+
+    fn begin_marked_content(&mut self, marked_content: &MarkedContent<'_>) {
+        self.record_marked_content("begin", marked_content);
+    }
+
+    fn end_marked_content(&mut self) {
+        self.marked_content
+            .push((self.drawn.len(), "end".to_string()));
+    }
+
+    fn marked_content_point(&mut self, marked_content: &MarkedContent<'_>) {
+        self.record_marked_content("point", marked_content);
+    }
+}
+
+#[test]
+fn color_glyph_layers_from_colr_table() {
+    let pdf = load("pdfs/custom/font_color_glyph.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // The first glyph consists of a red layer from the palette and a layer in the
+    // blue text color, the second one isn't a color glyph.
+    let round = |r: Rect| (r.x0.round(), r.y0.round(), r.x1.round(), r.y1.round());
+    let paths = recorder
+        .paths
+        .iter()
+        .map(|(color, bbox)| (*color, round(*bbox)))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        paths,
+        vec![
+            ([255, 0, 0, 255], (25.0, 50.0, 45.0, 120.0)),
+            ([0, 0, 255, 255], (45.0, 50.0, 65.0, 120.0)),
+        ]
+    );
+    assert_eq!(recorder.outline_glyphs, vec![2]);
+    assert_eq!(recorder.clips, 0);
+}
+
+#[test]
+fn color_glyph_blend_mode() {
+    let pdf = load("pdfs/custom/font_color_glyph_blend_mode.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    use BlendMode::{Multiply, Normal};
+
+    // The blend mode of the text applies to the glyph as a whole, so its layers
+    // are drawn into a group instead of being blended one by one.
+    assert_eq!(
+        recorder.blend_modes,
+        vec![("group", Multiply), ("fill", Normal), ("fill", Normal)]
+    );
+}
+
+#[test]
+fn color_glyph_gradients() {
+    let pdf = load("pdfs/custom/font_color_glyph_gradients.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let round = |p: Point| (p.x.round(), p.y.round());
+
+    // The glyphs are filled with a linear, a radial and a sweep gradient from red
+    // to blue, instead of a solid color.
+    assert!(recorder.paths.is_empty());
+
+    let [(start, end, stops)] = recorder.axial.as_slice() else {
+        panic!("expected one axial gradient");
+    };
+    assert_eq!((round(*start), round(*end)), ((25.0, 50.0), (65.0, 50.0)));
+    assert_eq!((stops[0], stops[stops.len() - 1]), (red, blue));
+
+    let [(start_center, start_radius, end_center, end_radius)] = recorder.radial.as_slice() else {
+        panic!("expected one radial gradient");
+    };
+    assert_eq!(round(*start_center), (95.0, 85.0));
+    assert_eq!(round(*end_center), (95.0, 85.0));
+    assert_eq!((*start_radius, *end_radius), (0.0, 200.0));
+
+    // The font defines the sweep gradient counter-clockwise from red at 180 degrees
+    // to blue at 360 degrees, which skrifa reports as going clockwise from blue at 0
+    // degrees to red at 180 degrees. The other half of the circle is padded with red.
+    let [colors] = recorder.mesh_colors.as_slice() else {
+        panic!("expected one mesh");
+    };
+    assert_eq!(colors[0][1], blue);
+    assert!(
+        colors
+            .iter()
+            .rev()
+            .take(10)
+            .all(|c| c.iter().all(|c| *c == red))
+    );
+}
+
+#[test]
+fn variable_fallback_font_instances() {
+    // The weight axis of the font moves the glyph to the right by up to 100 units.
+    let settings = InterpreterSettings {
+        font_resolver: Arc::new(|_| {
+            let data: &'static [u8] = include_bytes!("../assets/variable_weight.ttf");
+            Some((Arc::new(data), 0))
+        }),
+        ..Default::default()
+    };
+
+    let recorder = record("pdfs/custom/font_variable_fallback.pdf", settings);
+    let x0 = recorder
+        .outlines
+        .iter()
+        .map(|o| o.bounding_box().x0.round())
+        .collect::<Vec<_>>();
+
+    // The first font requests a weight of 700 in its descriptor, the second one
+    // names the "Black" instance of the font.
+    assert_eq!(x0, vec![110.0, 150.0]);
+}
+
+#[test]
+fn standard_font_advances_without_widths() {
+    // The substitute font is monospaced, but the advances of fonts without a
+    // `Widths` array still come from the metrics of the standard fonts.
+    let settings = InterpreterSettings {
+        font_resolver: Arc::new(|_| {
+            let data: &'static [u8] = include_bytes!("../assets/LiberationMono-Regular.ttf");
+            Some((Arc::new(data), 0))
+        }),
+        ..Default::default()
+    };
+
+    let pdf = load("pdfs/custom/font_standard_without_widths.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, settings, &cache, &mut recorder);
+
+    let advances = recorder
+        .runs
+        .iter()
+        .map(|r| {
+            r.3.iter()
+                .map(|a| (a.x * 100.0).round())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Helvetica and Helvetica-Bold (for "Arial,Bold").
+    assert_eq!(advances, [[222.0, 944.0], [278.0, 944.0]]);
+}
+
+fn record_images(file: &str) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+    let pdf = load(file);
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    recorder.images
+}
+
+#[test]
+fn image_decode_arrays() {
+    let images = record_images("pdfs/custom/image_decode_arrays.pdf");
+
+    assert_eq!(
+        images,
+        vec![
+            // The decode array has the wrong length and is therefore ignored.
+            (vec![255, 0, 0, 0, 0, 255], None),
+            (vec![255, 255, 255, 128, 128, 128], None),
+            (vec![0, 0, 255, 255, 0, 0], None),
+        ]
+    );
+}
+
+#[test]
+fn image_dct_color_transform() {
+    let images = record_images("pdfs/custom/image_dct_color_transform.pdf");
+
+    // The Adobe marker takes precedence over `ColorTransform`.
+    assert_eq!(images[0], images[1]);
+    // A CMYK image without Adobe marker uses `ColorTransform` to decide whether it
+    // is YCCK.
+    assert_eq!(images[2], images[3]);
+    assert_ne!(images[3], images[5]);
+    assert_eq!(images[4], images[5]);
+}
+
+#[test]
+fn image_color_key_mask() {
+    let images = record_images("pdfs/custom/image_color_key_mask.pdf");
+    let alphas = images.into_iter().map(|(_, a)| a).collect::<Vec<_>>();
+
+    // The ranges are compared with the original color components, i.e. before
+    // applying the decode array and for the actual bit depth of the image.
+    assert_eq!(
+        alphas,
+        vec![
+            Some(vec![0, 0, 255]),
+            Some(vec![0, 255]),
+            Some(vec![0, 255]),
+        ]
+    );
+}
+
+#[test]
+fn image_smask_matte() {
+    let images = record_images("pdfs/custom/image_smask_matte.pdf");
+
+    // The first image is pure cyan, pre-blended with white at half opacity. After
+    // undoing that in CMYK, it needs to match the second image.
+    assert_eq!(images[0].0[..3], images[1].0[..3]);
+    assert_eq!(images[0].1, Some(vec![128, 0]));
+}
+
+#[test]
+fn image_max_pixels() {
+    let pdf = load("pdfs/custom/image_max_pixels.pdf");
+    let cache = InterpreterCache::new();
+
+    let images = |max_image_pixels| {
+        let settings = InterpreterSettings {
+            max_image_pixels,
+            ..Default::default()
+        };
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+
+        recorder.images
+    };
+
+    assert_eq!(images(None)[0].0.len(), 16);
+    assert_eq!(images(Some(16))[0].0.len(), 16);
+    // Only every second pixel in each direction is kept. The third image is
+    // the same as the first one, but Flate-compressed with a predictor, so it's
+    // downsampled while being decompressed.
+    assert_eq!(
+        images(Some(4)),
+        vec![
+            (vec![0, 20, 80, 100], None),
+            (vec![255, 255, 255, 0, 0, 0, 0, 0, 0, 255, 255, 255], None),
+            (vec![0, 20, 80, 100], None),
+        ]
+    );
+    assert_eq!(images(Some(9))[0].0.len(), 4);
+    assert_eq!(images(Some(3))[0].0.len(), 1);
+    assert_eq!(
+        images(Some(1)),
+        vec![
+            (vec![0], None),
+            (vec![255, 255, 255], None),
+            (vec![0], None)
+        ]
+    );
+}
+
+#[test]
+fn image_rows() {
+    for file in [
+        "image_1_bit_per_component.pdf",
+        "image_color_key_mask.pdf",
+        "image_decode_arrays.pdf",
+        "image_mask.pdf",
+        "image_rgb16.pdf",
+        "image_smask_matte.pdf",
+    ] {
+        let pdf = load(&format!("pdfs/custom/{file}"));
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+        // Converting the images in bands gives the same data as converting
+        // them as a whole.
+        assert!(!recorder.image_rows.is_empty());
+        assert!(
+            recorder
+                .image_rows
+                .iter()
+                .all(|(whole, rows)| whole == rows)
+        );
+    }
+}
+
+#[test]
+fn page_images() {
+    let pdf = load("pdfs/custom/page_images.pdf");
+    let pages = pdf.images();
+    let images = &pages[0];
+
+    assert_eq!(pages.len(), 1);
+    assert_eq!(images.len(), 3);
+
+    let ImageData::Rgb(rgb) = &images[0].data else {
+        panic!("expected an RGB image");
+    };
+    assert_eq!(*rgb.data, [255, 0, 0, 0, 0, 255]);
+    assert_eq!(
+        images[0].color_space.as_ref().map(|cs| cs.family()),
+        Some("DeviceRGB")
+    );
+    assert_eq!(images[0].bits_per_component, Some(8));
+    assert!(!images[0].stencil);
+    // The image is drawn twice.
+    assert_eq!(
+        images[0].transforms,
+        vec![
+            Affine::new([10.0, 0.0, 0.0, -10.0, 10.0, 20.0]),
+            Affine::new([20.0, 0.0, 0.0, -20.0, 50.0, 120.0]),
+        ]
+    );
+
+    let ImageData::Luma(luma) = &images[1].data else {
+        panic!("expected a stencil mask");
+    };
+    assert_eq!(*luma.data, [255, 0, 0, 255]);
+    assert!(images[1].stencil);
+    assert!(images[1].color_space.is_none());
+    assert_eq!(
+        images[1].transforms,
+        vec![Affine::new([5.0, 0.0, 0.0, -5.0, 100.0, 20.0])]
+    );
+
+    // A different image with the same dictionary as the first one.
+    let ImageData::Rgb(rgb) = &images[2].data else {
+        panic!("expected an RGB image");
+    };
+    assert_eq!(*rgb.data, [0, 255, 0, 0, 255, 0]);
+    assert_eq!(images[2].transforms.len(), 1);
+}
+
+#[test]
+fn image_cache_respects_memory_limit() {
+    fn record_with<'a>(
+        pdf: &'a Pdf,
+        cache: &'a InterpreterCache<'a>,
+        image_cache_max_bytes: usize,
+    ) -> usize {
+        let settings = InterpreterSettings {
+            image_cache_max_bytes,
+            ..Default::default()
+        };
+        let mut recorder = Recorder::default();
+        interpret_with(pdf, settings, cache, &mut recorder);
+        assert_eq!(
+            recorder.images,
+            record_images("pdfs/custom/image_max_pixels.pdf")
+        );
+
+        cache.image_cache().memory_usage()
+    }
+
+    let pdf = load("pdfs/custom/image_max_pixels.pdf");
+
+    // A 4x4 grayscale image, a 4x4 bilevel image that is converted to RGB and a
+    // Flate-compressed copy of the first image.
+    let cache = InterpreterCache::new();
+    assert_eq!(record_with(&pdf, &cache, usize::MAX), 16 + 48 + 16);
+    // Decoded images are reused across interpreter runs.
+    assert_eq!(record_with(&pdf, &cache, usize::MAX), 16 + 48 + 16);
+    // The images that were used least recently are evicted.
+    assert_eq!(record_with(&pdf, &InterpreterCache::new(), 70), 48 + 16);
+    assert_eq!(record_with(&pdf, &InterpreterCache::new(), 0), 0);
+
+    // Images that are taken from the cache share their data instead of copying it.
+    let cache = InterpreterCache::new();
+    let buffers = || {
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+        recorder.image_buffers
+    };
+    let (first, second) = (buffers(), buffers());
+    assert_eq!(first.len(), 3);
+    assert!(first.iter().zip(&second).all(|(a, b)| Arc::ptr_eq(a, b)));
+}
+
+#[test]
+fn shared_cache_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedCache>();
+
+    let pdf = load("pdfs/custom/image_max_pixels.pdf");
+    let text_pdf = load("pdfs/custom/text_extraction_runs.pdf");
+    let shared = SharedCache::new();
+
+    let glyph_cache = GlyphCache::default();
+    let cache = InterpreterCache::with_glyph_cache(glyph_cache.clone());
+    interpret_with(
+        &text_pdf,
+        InterpreterSettings::default(),
+        &cache,
+        &mut Recorder::default(),
+    );
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let cache = InterpreterCache::with_shared_cache(shared.clone());
+                let mut recorder = Recorder::default();
+                interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+                assert_eq!(
+                    recorder.images,
+                    record_images("pdfs/custom/image_max_pixels.pdf")
+                );
+                interpret_with(
+                    &text_pdf,
+                    InterpreterSettings::default(),
+                    &cache,
+                    &mut Recorder::default(),
+                );
+            });
+        }
+    });
+
+    // All threads stored their decoded images and glyph outlines in the same cache.
+    assert_eq!(shared.image_cache().memory_usage(), 16 + 48 + 16);
+    assert_eq!(
+        shared.glyph_cache().memory_usage(),
+        glyph_cache.memory_usage()
+    );
+    let cache = InterpreterCache::with_shared_cache(shared.clone());
+    assert_eq!(
+        cache.shared_cache().image_cache().memory_usage(),
+        16 + 48 + 16
+    );
+}
+
+#[test]
+fn image_interpolation_override() {
+    let pdf = load("pdfs/custom/image_interpolation_override.pdf");
+    let cache = InterpreterCache::new();
+
+    let interpolate = |image_interpolation| {
+        let settings = InterpreterSettings {
+            image_interpolation,
+            ..Default::default()
+        };
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+
+        recorder.interpolate
+    };
+
+    assert_eq!(interpolate(None), vec![(true, Some(false)), (false, None)]);
+    assert_eq!(
+        interpolate(Some(true)),
+        vec![(true, Some(true)), (true, None)]
+    );
+    assert_eq!(
+        interpolate(Some(false)),
+        vec![(false, Some(false)), (false, None)]
+    );
+}
+
+#[test]
+fn devicen_colorants_without_tint_transform() {
+    let pdf = load("pdfs/custom/color_devicen_colorants.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // The first color space is an `NChannel` color space with a cyan process colorant
+    // and a red spot colorant, the second one only uses CMYK colorants. Both have a
+    // broken tint transform.
+    let colors = recorder.paths.iter().map(|p| p.0).collect::<Vec<_>>();
+    assert_eq!(
+        colors,
+        vec![
+            [0, 174, 239, 255],
+            [255, 0, 0, 255],
+            [255, 255, 255, 255],
+            [46, 48, 146, 255],
+        ]
+    );
+
+    // The original tints are still available, for example for ink coverage reports.
+    let names = |n: &[&str]| n.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        recorder.colorants,
+        vec![
+            (names(&["Cyan", "Spot"]), vec![1.0, 0.0]),
+            (names(&["Cyan", "Spot"]), vec![0.0, 1.0]),
+            (names(&["Cyan", "Spot"]), vec![0.0, 0.0]),
+            (names(&["Cyan", "Magenta"]), vec![1.0, 1.0]),
+        ]
+    );
+}
+
+#[test]
+fn indexed_color_spaces() {
+    let pdf = load("pdfs/custom/color_indexed_bases.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // The first color space is based on a Lab color space with a custom range and
+    // uses an indirect string as lookup table, the lookup table of the second one
+    // only contains the first of its entries.
+    let colors = recorder.paths.iter().map(|p| p.0).collect::<Vec<_>>();
+    assert_eq!(
+        colors,
+        vec![
+            [253, 0, 125, 255],
+            [119, 119, 119, 255],
+            [255, 0, 0, 255],
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+        ]
+    );
+}
+
+#[test]
+fn rendering_intents() {
+    let pdf = load("pdfs/custom/color_rendering_intent.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // The intent is set with the `ri` operator and in a graphics state that is
+    // restored afterwards. Unknown intents are treated as relative colorimetric.
+    assert_eq!(
+        recorder.intents,
+        vec![
+            RenderingIntent::RelativeColorimetric,
+            RenderingIntent::Perceptual,
+            RenderingIntent::Saturation,
+            RenderingIntent::Perceptual,
+            RenderingIntent::RelativeColorimetric,
+        ]
+    );
+}
+
+#[test]
+fn default_rendering_intent() {
+    let pdf = load("pdfs/custom/color_rendering_intent_lab.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // Without an explicit intent, colors are converted with relative colorimetric.
+    // The profile used for Lab colors only has a perceptual table, which is used
+    // for all intents instead.
+    let colors = recorder.paths.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+    assert_eq!(colors, vec![[219, 84, 250, 255]; 4]);
+    assert_eq!(recorder.intents[0], RenderingIntent::RelativeColorimetric);
+}
+
+#[test]
+fn overprint_approximation() {
+    let pdf = load("pdfs/custom/color_overprint.pdf");
+    let cache = InterpreterCache::new();
+    let blend_modes = |approximate_overprint| {
+        let settings = InterpreterSettings {
+            approximate_overprint,
+            ..Default::default()
+        };
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+
+        recorder
+            .blend_modes
+            .into_iter()
+            .map(|(_, blend_mode)| blend_mode)
+            .collect::<Vec<_>>()
+    };
+
+    use BlendMode::{Multiply, Normal};
+
+    // With the approximation enabled, CMYK colors in overprint mode 1 and separation
+    // colors are multiplied, RGB colors are not affected. Afterwards, overprinting
+    // is only disabled for non-stroking operations and the mode is reset to 0.
+    assert_eq!(blend_modes(false), vec![Normal; 5]);
+    assert_eq!(
+        blend_modes(true),
+        vec![Multiply, Normal, Multiply, Normal, Normal]
+    );
+}
+
+#[test]
+fn default_color_spaces() {
+    let pdf = load("pdfs/custom/color_default_color_spaces.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // Gray colors are remapped to a `CalGray` color space, both for the `g` and `G`
+    // operators and when selecting `DeviceGray` by name. The `DefaultCMYK` color
+    // space has the wrong number of components and is ignored.
+    let colors = recorder.paths.iter().map(|p| p.0).collect::<Vec<_>>();
+    assert_eq!(
+        colors,
+        vec![
+            [137, 137, 137, 255],
+            [137, 137, 137, 255],
+            [137, 137, 137, 255],
+            [35, 31, 32, 255],
+            [128, 128, 128, 255],
+        ]
+    );
+}
+
+#[test]
+fn transfer_functions() {
+    let pdf = load("pdfs/custom/transfer_functions.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // The first graphics state inverts the green channel and uses the identity for
+    // the others, the second one inverts all channels and in the third one, `TR2`
+    // takes precedence over `TR`.
+    let colors = recorder.paths.iter().map(|p| p.0).collect::<Vec<_>>();
+    assert_eq!(
+        colors,
+        vec![
+            [255, 0, 255, 255],
+            [204, 204, 204, 255],
+            [255, 255, 255, 255],
+        ]
+    );
+
+    // Images pass through the transfer functions as well.
+    let images = record_images("pdfs/custom/transfer_functions.pdf");
+    assert_eq!(images, vec![(vec![255, 0, 255], None)]);
+}
+
+#[test]
+fn shading_lookup_tables() {
+    let pdf = load("pdfs/custom/shading_lut.pdf");
+    let sample = |lut_size| {
+        let cache = InterpreterCache::new();
+        let mut sampler = Recorder::default();
+        let settings = InterpreterSettings {
+            shading_lut_size: lut_size,
+            ..Default::default()
+        };
+        interpret_with(&pdf, settings, &cache, &mut sampler);
+        sampler.shading_samples
+    };
+
+    // The shading goes from red to blue with an exponent of 2.
+    let exact = sample(None);
+    assert_eq!(
+        exact,
+        vec![
+            [255, 0, 0, 255],
+            [239, 0, 16, 255],
+            [191, 0, 64, 255],
+            [112, 0, 143, 255],
+            [0, 0, 255, 255],
+        ]
+    );
+
+    // A fine lookup table doesn't visibly change the result.
+    assert_eq!(sample(Some(1024)), exact);
+
+    // With only two entries, the colors are interpolated linearly.
+    let coarse = sample(Some(2));
+    assert_eq!(
+        coarse,
+        vec![
+            [255, 0, 0, 255],
+            [191, 0, 64, 255],
+            [128, 0, 128, 255],
+            [64, 0, 191, 255],
+            [0, 0, 255, 255],
+        ]
+    );
+}
+
+#[test]
+fn function_based_shading_domain() {
+    let pdf = load("pdfs/custom/shading_function_based_domain.pdf");
+    let cache = InterpreterCache::new();
+    let mut sampler = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut sampler);
+
+    // The domain of the shading is mapped onto the whole page and goes from blue
+    // to red. The right edge of the page still lies within the domain.
+    assert_eq!(
+        sampler.shading_samples,
+        vec![
+            [0, 0, 255, 255],
+            [64, 0, 191, 255],
+            [128, 0, 128, 255],
+            [191, 0, 64, 255],
+            [255, 0, 0, 255],
+        ]
+    );
+}
+
+#[test]
+fn radial_shading_touching_circles() {
+    let pdf = load("pdfs/custom/shading_radial_touching.pdf");
+    let cache = InterpreterCache::new();
+    let mut sampler = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut sampler);
+
+    // The start circle is a point on the edge of the end circle and the shading
+    // goes from black to white without being extended.
+    assert_eq!(
+        sampler.shading_samples,
+        vec![
+            [0, 0, 0, 0],
+            [63, 63, 63, 255],
+            [168, 168, 168, 255],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+        ]
+    );
+}
+
+#[test]
+fn malformed_mesh_shadings() {
+    let sample = |file| {
+        let pdf = load(file);
+        let cache = InterpreterCache::new();
+        let mut sampler = Recorder::default();
+        interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut sampler);
+        sampler.shading_samples
+    };
+
+    // The data of the last vertex is truncated, but the two triangles before it
+    // should still be drawn. The vertex in the top right corner is blue, all
+    // other ones are red.
+    let samples = sample("pdfs/custom/shading_type4_truncated.pdf");
+    assert_eq!(
+        samples,
+        vec![
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+            [191, 0, 64, 255],
+            [0, 0, 0, 0],
+        ]
+    );
+
+    // A lattice needs at least two vertices per row.
+    assert!(sample("pdfs/custom/shading_type5_one_vertex_per_row.pdf").is_empty());
+
+    // The second patch is truncated, but the first one covering the whole page
+    // should still be drawn.
+    let samples = sample("pdfs/custom/shading_type6_truncated.pdf");
+    assert_eq!(samples, vec![[255, 0, 0, 255]; 5]);
+}
+
+#[test]
+fn shading_operator_background_and_bbox() {
+    let pdf = load("pdfs/custom/shading_operator_background.pdf");
+    let cache = InterpreterCache::new();
+    let mut sampler = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut sampler);
+
+    // The axial shading from red to blue is not extended, so the remaining area is
+    // filled with the green background.
+    assert_eq!(
+        sampler.shading_samples,
+        vec![
+            [0, 255, 0, 255],
+            [0, 255, 0, 255],
+            [128, 0, 128, 255],
+            [0, 255, 0, 255],
+            [0, 255, 0, 255],
+        ]
+    );
+
+    // The bounding box of the shading is given in the coordinate system that was
+    // active when the shading was painted.
+    assert_eq!(
+        sampler.shading_clips,
+        vec![Some(Rect::new(10.0, 10.0, 60.0, 60.0))]
+    );
+}
+
+#[test]
+fn shading_smoothness() {
+    let pdf = load("pdfs/custom/shading_smoothness.pdf");
+    let smoothness = |settings| {
+        let cache = InterpreterCache::new();
+        let mut sampler = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut sampler);
+        sampler.shading_smoothness
+    };
+
+    // The shading is painted once with the default smoothness and once after
+    // setting a smaller one in the graphics state.
+    assert_eq!(
+        smoothness(InterpreterSettings::default()),
+        vec![0.01, 0.005]
+    );
+
+    // A smaller smoothness in the settings takes precedence.
+    let settings = InterpreterSettings {
+        shading_smoothness: 0.001,
+        ..Default::default()
+    };
+    assert_eq!(smoothness(settings), vec![0.001, 0.001]);
+}
+
+#[test]
+fn uncolored_tiling_pattern_ignores_colors() {
+    let pdf = load("pdfs/custom/pattern_tiling_uncolored_text.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // The pattern contains a green square and some text, but as it is uncolored, both
+    // are painted in the red color that is used with the pattern.
+    let colors = recorder.paths.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+    assert_eq!(colors, vec![[255, 0, 0, 255]]);
+    assert_eq!(recorder.glyph_colors, vec![[255, 0, 0, 255]]);
+}
+
+#[test]
+fn native_gradients_and_meshes() {
+    let record = |file| {
+        let pdf = load(file);
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+        recorder
+    };
+
+    // An axial shading from red to blue with an exponent of 2. The stops should
+    // follow the curve of the function.
+    let recorder = record("pdfs/custom/shading_lut.pdf");
+    assert!(recorder.drawn.is_empty());
+    assert_eq!(
+        recorder.axial,
+        vec![(
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            vec![
+                [255, 0, 0, 255],
+                [251, 0, 4, 255],
+                [239, 0, 16, 255],
+                [219, 0, 36, 255],
+                [191, 0, 64, 255],
+                [155, 0, 100, 255],
+                [112, 0, 143, 255],
+                [60, 0, 195, 255],
+                [0, 0, 255, 255],
+            ]
+        )]
+    );
+
+    let recorder = record("pdfs/custom/shading_radial_touching.pdf");
+    assert!(recorder.drawn.is_empty());
+    assert_eq!(
+        recorder.radial,
+        vec![(
+            Point::new(10.3f32 as f64, 50.2f32 as f64),
+            0.0,
+            Point::new(40.6f32 as f64, 90.6f32 as f64),
+            50.5
+        )]
+    );
+
+    // Two triangles, where the vertex in the top right corner is blue.
+    let recorder = record("pdfs/custom/shading_type4_truncated.pdf");
+    assert!(recorder.drawn.is_empty());
+    assert_eq!(
+        recorder.mesh_points,
+        vec![vec![
+            [
+                Point::new(0.0, 0.0),
+                Point::new(100.0, 0.0),
+                Point::new(0.0, 100.0)
+            ],
+            [
+                Point::new(100.0, 0.0),
+                Point::new(0.0, 100.0),
+                Point::new(100.0, 100.0)
+            ],
+        ]]
+    );
+    assert_eq!(
+        recorder.mesh_colors,
+        vec![vec![
+            [[255, 0, 0, 255]; 3],
+            [[255, 0, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255]],
+        ]]
+    );
+}
+
+#[test]
+fn patch_mesh_flatness() {
+    let record = |transform: Affine| {
+        let pdf = load("pdfs/custom/shading_curved_patch.pdf");
+        let cache = InterpreterCache::new();
+        let mut context = Context::new(
+            transform,
+            Rect::new(0.0, 0.0, 2000.0, 2000.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        );
+        let mut recorder = Recorder::default();
+        interpret_page(&pdf.pages()[0], &mut context, &mut recorder);
+
+        recorder
+            .mesh_points
+            .iter()
+            .map(|triangles| triangles.len())
+            .collect::<Vec<_>>()
+    };
+
+    // The same patch with a curved edge is painted with the default flatness
+    // of 1 and a flatness of 50. At the original size, the grid of 20x20 points
+    // that is used for the colors is fine enough in both cases.
+    assert_eq!(record(Affine::IDENTITY), vec![722, 722]);
+    // When zooming in, the curved edge needs a finer grid, unless the flatness
+    // tolerance is large enough.
+    assert_eq!(record(Affine::scale(20.0)), vec![1250, 722]);
+}
+
+#[test]
+fn path_flatness() {
+    let recorder = record(
+        "pdfs/custom/path_flatness.pdf",
+        InterpreterSettings::default(),
+    );
+
+    // The flatness is unset by default, set with `i` for the curve and restored
+    // afterwards, and then set by an ExtGState, where it is clamped to 100.
+    assert_eq!(recorder.flatness, [None, Some(5.0), Some(100.0)]);
+}
+
+#[test]
+fn gradient_subdivisions() {
+    let pdf = load("pdfs/custom/shading_lut.pdf");
+    let stops = |gradient_subdivisions| {
+        let settings = InterpreterSettings {
+            render_quality: RenderQuality {
+                gradient_subdivisions,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+
+        recorder.axial[0].2.clone()
+    };
+
+    // The gradient needs nine stops to follow the curve of the function, but
+    // with a single subdivision, only the middle one is added.
+    assert_eq!(stops(10).len(), 9);
+    assert_eq!(
+        stops(1),
+        vec![[255, 0, 0, 255], [191, 0, 64, 255], [0, 0, 255, 255]]
+    );
+    assert_eq!(stops(0), vec![[255, 0, 0, 255], [0, 0, 255, 255]]);
+}
+
+#[test]
+fn blend_modes() {
+    let pdf = load("pdfs/custom/blend_modes.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    use BlendMode::{Multiply, Normal, Screen};
+
+    // Images are drawn in a group with the blend mode of the graphics state, and
+    // the blend mode is reset inside of transparency groups. The first supported
+    // blend mode of an array is used, and unknown blend modes fall back to `Normal`.
+    assert_eq!(
+        recorder.blend_modes,
+        vec![
+            ("fill", Multiply),
+            ("stroke", Multiply),
+            ("group", Multiply),
+            ("image", Normal),
+            ("group", Multiply),
+            ("fill", Normal),
+            ("fill", Screen),
+            ("fill", Normal),
+        ]
+    );
+}
+
+#[test]
+fn transparency_group_color_spaces() {
+    let color_spaces = |file| {
+        let pdf = load(file);
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+        recorder.group_color_spaces
+    };
+
+    // The first group is blended in CMYK, which is inherited by the nested group
+    // without a color space. The second group doesn't declare a color space.
+    assert_eq!(
+        color_spaces("pdfs/custom/transparency_group_color_space.pdf"),
+        vec![Some(4), Some(4), None]
+    );
+
+    // Groups without a color space inherit the one of the page group.
+    assert_eq!(
+        color_spaces("pdfs/custom/transparency_group_color_space_page.pdf"),
+        vec![Some(4), Some(4), Some(3)]
+    );
+}
+
+#[test]
+fn dash_arrays() {
+    let pdf = load("pdfs/custom/dash_invalid.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    // Zero-length dashes are passed as-is, while dash arrays with negative
+    // lengths or only zeros produce solid lines.
+    assert_eq!(
+        recorder
+            .strokes
+            .iter()
+            .map(|s| (s.dash_array.to_vec(), s.dash_offset))
+            .collect::<Vec<_>>(),
+        vec![
+            (vec![0.0, 4.0], 0.0),
+            (vec![], 0.0),
+            (vec![], 0.0),
+            (vec![3.0], 1.0),
+        ]
+    );
+}
+
+#[test]
+fn dash_path_fallback() {
+    let stroke_props = StrokeProps {
+        line_width: 2.0,
+        dash_array: [0.0, 10.0].into_iter().collect(),
+        ..Default::default()
+    };
+    let mut path = BezPath::new();
+    path.move_to((0.0, 0.0));
+    path.line_to((25.0, 0.0));
+
+    // Each zero-length dash becomes a short segment, so that it gets caps when
+    // it is stroked.
+    let dashed = stroke_props.dash_path(&path);
+    let starts = dashed
+        .elements()
+        .iter()
+        .filter_map(|el| match el {
+            PathEl::MoveTo(p) => Some(p.x),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(starts, vec![10.0, 20.0, 0.0]);
+}
+
+#[test]
+fn zero_width_line_policy() {
+    let pdf = load("pdfs/custom/zero_width_lines.pdf");
+    let record = |zero_width_lines| {
+        let settings = InterpreterSettings {
+            zero_width_lines,
+            ..Default::default()
+        };
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+        recorder
+            .strokes
+            .iter()
+            .map(|s| (s.line_width, s.hairline))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        record(ZeroWidthLinePolicy::Hairline),
+        vec![(0.0, true), (2.0, false)]
+    );
+    assert_eq!(
+        record(ZeroWidthLinePolicy::MinimumWidth(0.5)),
+        vec![(0.5, false), (2.0, false)]
+    );
+}
+
+#[test]
+fn cancellation() {
+    let pdf = load("pdfs/custom/zero_width_lines.pdf");
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    let settings = InterpreterSettings {
+        is_cancelled: Arc::new(move || flag.load(Ordering::Relaxed)),
+        ..Default::default()
+    };
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder {
+        cancel: Some(cancelled),
+        ..Default::default()
+    };
+    interpret_with(&pdf, settings, &cache, &mut recorder);
+
+    // The page has three paths, but interpretation stops after the first one.
+    assert_eq!(recorder.drawn.len(), 1);
+}
+
+#[test]
+fn instruction_and_time_limits() {
+    let pdf = load("pdfs/custom/zero_width_lines.pdf");
+    let record = |max_instructions, max_duration| {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let settings = InterpreterSettings {
+            warning_sink: {
+                let warnings = warnings.clone();
+                Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+            },
+            max_instructions,
+            max_duration,
+            ..Default::default()
+        };
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+
+        let warnings = warnings.lock().unwrap();
+        let exceeded = warnings
+            .iter()
+            .filter(|w| matches!(w, InterpreterWarning::LimitExceeded))
+            .count();
+
+        (recorder.strokes.len(), exceeded)
+    };
+
+    assert_eq!(record(None, None), (2, 0));
+    // The first stroke needs five instructions.
+    assert_eq!(record(Some(5), None), (1, 1));
+    assert_eq!(record(None, Some(Duration::ZERO)), (0, 1));
+}
+
+#[test]
+fn x_object_cycles() {
+    let pdf = load("pdfs/custom/xobject_cycle.pdf");
+    let record = |max_nesting_depth| {
+        let settings = InterpreterSettings {
+            max_nesting_depth,
+            ..Default::default()
+        };
+        let cache = InterpreterCache::new();
+        let mut recorder = Recorder::default();
+        interpret_with(&pdf, settings, &cache, &mut recorder);
+        recorder.strokes.len()
+    };
+
+    // The first form draws itself, and the second one draws a form that draws
+    // the second one again. Each form is only drawn once.
+    assert_eq!(record(50), 3);
+    // The forms are not drawn past the maximum depth.
+    assert_eq!(record(1), 2);
+    assert_eq!(record(0), 0);
+
+    // The form is filled with a pattern that draws the form again, so only the
+    // line in the pattern is drawn.
+    let pdf = load("pdfs/custom/xobject_cycle_pattern.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+    assert_eq!(recorder.paths.len(), 1);
+}
+
+#[test]
+fn progress() {
+    let pdf = load("pdfs/custom/zero_width_lines.pdf");
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        progress: {
+            let reported = reported.clone();
+            Some(Arc::new(move |p| reported.lock().unwrap().push(p)))
+        },
+        ..Default::default()
+    };
+    let cache = InterpreterCache::new();
+    interpret_with(&pdf, settings, &cache, &mut Recorder::default());
+
+    // The progress is reported for the instructions of the page, but not the
+    // ones of the tiling pattern, and increases up to 1.
+    let reported = reported.lock().unwrap();
+    assert!(reported.len() > 10);
+    assert!(reported.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(reported.last(), Some(&1.0));
+}
+
+#[test]
+fn marked_content_events() {
+    let pdf = load("pdfs/custom/marked_content_events.pdf");
+    let cache = InterpreterCache::new();
+    let mut recorder = Recorder::default();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut recorder);
+
+    assert_eq!(
+        recorder.marked_content,
+        vec![
+            (
+                0,
+                "begin Artifact None Some(\"Pagination\") None".to_string()
+            ),
+            (1, "end".to_string()),
+            (1, "point Break None None None".to_string()),
+            (1, "point Span Some(3) None Some(\"en\")".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn structured_warnings() {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+        },
+        ..Default::default()
+    };
+
+    // The unknown operator inside of the compatibility section must not be
+    // reported.
+    record("pdfs/custom/structured_warnings.pdf", settings);
+
+    let missing = |operator: &str, name: &str| InterpreterWarning::MissingResource {
+        operator: operator.to_string(),
+        name: name.to_string(),
+    };
+
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        [
+            missing("gs", "GS9"),
+            missing("Do", "Missing"),
+            missing("Tf", "F9"),
+            InterpreterWarning::UnsupportedShading {
+                shading: Some(ObjectIdentifier::new(5, 0)),
+                shading_type: 9,
+            },
+            InterpreterWarning::InvalidFunction {
+                function: Some(ObjectIdentifier::new(7, 0)),
+            },
+            InterpreterWarning::InvalidOperator {
+                operator: "foo".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn malformed_content_stream_warnings() {
+    let pdf = load("pdfs/custom/malformed_content_stream.pdf");
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w| warnings.lock().unwrap().push(w))
+        },
+        ..Default::default()
+    };
+    let cache = InterpreterCache::new();
+    let mut drawn = vec![];
+
+    for page in pdf.pages().iter() {
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 100.0, 100.0),
+            &cache,
+            pdf.xref(),
+            settings.clone(),
+        );
+        let mut recorder = Recorder::default();
+        interpret_page(page, &mut context, &mut recorder);
+        drawn.push(recorder.drawn);
+    }
+
+    // The operand of a path operator on the second page is not a number, so
+    // the rest of its content stream is skipped. The warning carries the index
+    // of the page.
+    assert_eq!(drawn, [["fill 0 0"], ["fill 0 0"]]);
+    assert_eq!(
+        warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|w| (w.kind.clone(), w.page_index))
+            .collect::<Vec<_>>(),
+        [(InterpreterWarning::MalformedContentStream, Some(1))]
+    );
+}
+
+#[test]
+fn unknown_operator_policy() {
+    let pdf = load("pdfs/custom/unknown_operators.pdf");
+    let record = |unknown_operators| {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let settings = InterpreterSettings {
+            warning_sink: {
+                let warnings = warnings.clone();
+                Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+            },
+            unknown_operators,
+            ..Default::default()
+        };
+        let cache = InterpreterCache::new();
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            &cache,
+            pdf.xref(),
+            settings,
+        );
+        let mut recorder = Recorder::default();
+        interpret_page(&pdf.pages()[0], &mut context, &mut recorder);
+
+        let warnings = warnings.lock().unwrap().clone();
+        (recorder.drawn, warnings)
+    };
+
+    let foo = InterpreterWarning::InvalidOperator {
+        operator: "foo".to_string(),
+    };
+
+    // The unknown operator inside of the compatibility section is always
+    // ignored silently.
+    let (drawn, warnings) = record(UnknownOperatorPolicy::Ignore);
+    assert_eq!(drawn, ["fill 0 0", "fill 20 20", "fill 40 40"]);
+    assert_eq!(warnings, []);
+
+    let (drawn, warnings) = record(UnknownOperatorPolicy::Warn);
+    assert_eq!(drawn, ["fill 0 0", "fill 20 20", "fill 40 40"]);
+    assert_eq!(warnings, std::slice::from_ref(&foo));
+
+    // Interpretation stops at the unknown operator.
+    let (drawn, warnings) = record(UnknownOperatorPolicy::Error);
+    assert_eq!(drawn, ["fill 0 0", "fill 20 20"]);
+    assert_eq!(warnings, [foo]);
+}
+
+#[test]
+fn strict_interpretation() {
+    let interpret = |file: &str, settings: InterpreterSettings| {
+        let pdf = load(file);
+        let cache = InterpreterCache::new();
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 1.0, 1.0),
+            &cache,
+            pdf.xref(),
+            settings,
+        );
+        let mut recorder = Recorder::default();
+
+        interpret_page_strict(&pdf.pages()[0], &mut context, &mut recorder)
+    };
+
+    assert_eq!(
+        interpret(
+            "pdfs/custom/font_type1_nonstandard_header.pdf",
+            InterpreterSettings::default()
+        ),
+        Ok(())
+    );
+
+    // Interpretation stops at the first warning, which is still reported.
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let settings = InterpreterSettings {
+        warning_sink: {
+            let warnings = warnings.clone();
+            Arc::new(move |w: Warning| warnings.lock().unwrap().push(w.kind))
+        },
+        ..Default::default()
+    };
+    let missing = InterpreterWarning::MissingResource {
+        operator: "gs".to_string(),
+        name: "GS9".to_string(),
+    };
+    assert_eq!(
+        interpret("pdfs/custom/structured_warnings.pdf", settings),
+        Err(InterpretError::Unfaithful(missing.clone()))
+    );
+    assert_eq!(*warnings.lock().unwrap(), [missing]);
+
+    let settings = InterpreterSettings {
+        is_cancelled: Arc::new(|| true),
+        ..Default::default()
+    };
+    assert_eq!(
+        interpret("pdfs/custom/font_type1_nonstandard_header.pdf", settings),
+        Err(InterpretError::Cancelled)
+    );
+}
+
+#[test]
+fn interpreter_stats() {
+    let pdf = load("pdfs/custom/interpreter_stats.pdf");
+    let cache = InterpreterCache::new();
+    let interpret = |settings| {
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 1.0, 1.0),
+            &cache,
+            pdf.xref(),
+            settings,
+        );
+        interpret_page(&pdf.pages()[0], &mut context, &mut Recorder::default());
+
+        context.stats()
+    };
+
+    assert!(interpret(InterpreterSettings::default()).is_none());
+
+    let stats = interpret(InterpreterSettings {
+        collect_stats: true,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let operators = stats
+        .operators
+        .iter()
+        .map(|(op, count)| format!("{op}:{count}"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        operators,
+        [
+            "BT:1", "Do:1", "ET:1", "Q:2", "Td:1", "Tf:1", "Tj:1", "cm:1", "f:1", "q:2", "re:1",
+            "rg:1", "sh:1",
+        ]
+    );
+    assert_eq!(stats.glyphs, 2);
+    assert_eq!(stats.images, 1);
+    assert_eq!(stats.shadings, 1);
+
+    let timings = stats.timings;
+    assert!(timings.total >= timings.paths + timings.text + timings.images + timings.shadings);
+}
+
+#[test]
+fn clip_culling() {
+    let pdf = load("pdfs/custom/clip_culling.pdf");
+    let cache = InterpreterCache::new();
+    let mut context = Context::new(
+        Affine::IDENTITY,
+        Rect::new(0.0, 0.0, 300.0, 300.0),
+        &cache,
+        pdf.xref(),
+        InterpreterSettings::default(),
+    );
+    let mut recorder = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context, &mut recorder);
+
+    // Everything that doesn't intersect the clip rectangle from (0, 0) to
+    // (10, 10) is skipped. The thick stroke is kept, because half of its line
+    // width reaches into the clip rectangle.
+    assert_eq!(
+        recorder.drawn,
+        ["fill 5 5", "stroke 20 0", "image 2 2", "fill 0 0"]
+    );
+}
+
+#[test]
+fn annotation_appearances() {
+    let pdf = load("pdfs/custom/annotation_appearances.pdf");
+    let cache = InterpreterCache::new();
+    let record = |render_annotations| {
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings {
+                render_annotations,
+                ..Default::default()
+            },
+        );
+        let mut recorder = Recorder::default();
+        interpret_page(&pdf.pages()[0], &mut context, &mut recorder);
+
+        recorder.drawn
+    };
+
+    // The checkbox uses its `Yes` state, the hidden annotations are skipped and
+    // the rotated appearance is mapped into its rectangle as well.
+    assert_eq!(record(true), ["fill 100 100", "fill 200 200", "fill 50 50"]);
+    assert!(record(false).is_empty());
+}
+
+#[test]
+fn form_field_appearances() {
+    let pdf = load("pdfs/custom/form_field_appearances.pdf");
+    let cache = InterpreterCache::new();
+    let context = || {
+        Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        )
+    };
+
+    let mut bounds = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut bounds);
+    let mut glyphs = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut glyphs);
+    let glyphs = glyphs
+        .glyphs
+        .iter()
+        .map(|(_, p)| ((p.x * 100.0).round() / 100.0, (p.y * 100.0).round() / 100.0))
+        .collect::<Vec<_>>();
+
+    // The text field has a background, a border and a right-aligned value, the
+    // check mark of the checked check box is drawn as a path, and the unchecked
+    // one is empty.
+    assert_eq!(bounds.drawn.len(), 3);
+    assert_eq!(bounds.drawn[..2], ["fill 10 10", "stroke 10.5 10.5"]);
+    assert!(bounds.drawn[2].starts_with("fill 153.1"));
+    // The inherited value uses an automatic font size that fills the field.
+    assert_eq!(
+        glyphs,
+        [
+            (97.56, 16.75),
+            (104.78, 16.75),
+            (12.0, 105.19),
+            (21.88, 105.19)
+        ]
+    );
+}
+
+#[test]
+fn tee_device() {
+    let pdf = load("pdfs/custom/form_field_appearances.pdf");
+    let cache = InterpreterCache::new();
+    let context = || {
+        Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        )
+    };
+
+    let mut bounds = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut bounds);
+    let mut glyphs = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut glyphs);
+
+    // A single pass records the same operations as two separate ones.
+    let mut tee = TeeDevice::new(Recorder::default(), Recorder::default());
+    interpret_page(&pdf.pages()[0], &mut context(), &mut tee);
+    let (tee_bounds, tee_glyphs) = tee.into_inner();
+    assert_eq!(tee_bounds.drawn, bounds.drawn);
+    assert_eq!(tee_glyphs.glyphs, glyphs.glyphs);
+
+    // Images are passed to both devices.
+    let pdf = load("pdfs/custom/image_decode_arrays.pdf");
+    let cache = InterpreterCache::new();
+    let mut tee = TeeDevice::new(Recorder::default(), Recorder::default());
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut tee);
+    assert_eq!(tee.first.images.len(), 3);
+    assert_eq!(tee.first.images, tee.second.images);
+}
+
+#[test]
+fn display_list_replay() {
+    let pdf = load("pdfs/custom/form_field_appearances.pdf");
+    let cache = InterpreterCache::new();
+    let context = || {
+        Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        )
+    };
+
+    let mut bounds = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut bounds);
+    let mut glyphs = Recorder::default();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut glyphs);
+
+    let mut list = DisplayList::new();
+    interpret_page(&pdf.pages()[0], &mut context(), &mut list);
+    assert!(!list.is_empty());
+
+    // Replaying the list draws the same as interpreting the page, as often as needed.
+    for _ in 0..2 {
+        let mut replayed = TeeDevice::new(Recorder::default(), Recorder::default());
+        list.replay(&mut replayed);
+        assert_eq!(replayed.first.drawn, bounds.drawn);
+        assert_eq!(replayed.second.glyphs, glyphs.glyphs);
+    }
+
+    // Inline images are decoded when they are recorded, since they borrow from
+    // the content stream.
+    let file = "pdfs/custom/image_inline_5.pdf";
+    let pdf = load(file);
+    let cache = InterpreterCache::new();
+    let mut list = DisplayList::new();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut list);
+    let mut replayed = Recorder::default();
+    list.replay(&mut replayed);
+    assert!(!replayed.images.is_empty());
+    assert_eq!(replayed.images, record_images(file));
+    assert!(replayed.image_streams.iter().all(|len| *len == 0));
+
+    // Image XObjects are recorded as they are and keep their stream.
+    let pdf = load("pdfs/custom/page_images.pdf");
+    let cache = InterpreterCache::new();
+    let mut list = DisplayList::new();
+    interpret_with(&pdf, InterpreterSettings::default(), &cache, &mut list);
+    let mut replayed = Recorder::default();
+    list.replay(&mut replayed);
+    assert_eq!(replayed.image_streams.len(), 3);
+    assert!(replayed.image_streams.iter().all(|len| *len > 0));
+}
+
+#[test]
+fn owned_display_list() {
+    fn interpret<'a>(pdf: &'a Pdf, cache: &'a InterpreterCache<'a>, device: &mut impl Device<'a>) {
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        );
+        interpret_page(&pdf.pages()[0], &mut context, device);
+    }
+
+    let round_trip = |list: &OwnedDisplayList| {
+        let json = serde_json::to_string(list).unwrap();
+        let deserialized: OwnedDisplayList = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+
+        deserialized
+    };
+
+    // Paths and images are replayed like from a borrowed display list, also
+    // after the list has been serialized and the PDF is gone.
+    let file = "pdfs/custom/image_inline_5.pdf";
+    let list = {
+        let pdf = load(file);
+        let cache = InterpreterCache::new();
+        let mut list = OwnedDisplayList::new();
+        interpret(&pdf, &cache, &mut list);
+        round_trip(&list)
+    };
+    assert!(!list.is_empty());
+    let mut replayed = Recorder::default();
+    list.replay(&mut replayed);
+    assert_eq!(replayed.images, record_images(file));
+
+    // Glyphs are converted into paths.
+    let pdf = load("pdfs/custom/form_field_appearances.pdf");
+    let cache = InterpreterCache::new();
+    let mut glyphs = Recorder::default();
+    interpret(&pdf, &cache, &mut glyphs);
+    assert!(!glyphs.glyphs.is_empty());
+    let mut borrowed = DisplayList::new();
+    interpret(&pdf, &cache, &mut borrowed);
+    let list = OwnedDisplayList::from(&borrowed);
+    let mut replayed = TeeDevice::new(Recorder::default(), Recorder::default());
+    list.replay(&mut replayed);
+    assert!(replayed.second.glyphs.is_empty());
+    let mut deserialized = Recorder::default();
+    round_trip(&list).replay(&mut deserialized);
+    assert_eq!(deserialized.drawn, replayed.first.drawn);
+
+    // The contents of tiling patterns are recorded, once for each color that an
+    // uncolored pattern is painted with.
+    let pdf = load("pdfs/custom/pattern_tiling_uncolored_reused.pdf");
+    let cache = InterpreterCache::new();
+    let mut paints = Recorder::default();
+    interpret(&pdf, &cache, &mut paints);
+    let mut list = OwnedDisplayList::new();
+    interpret(&pdf, &cache, &mut list);
+    let mut replayed = Recorder::default();
+    round_trip(&list).replay(&mut replayed);
+    assert_eq!(replayed.paints, paints.paints);
+
+    // Patterns can only contain patterns that were recorded before them, so that
+    // data that makes a pattern contain itself is rejected.
+    let json = serde_json::to_string(&list).unwrap();
+    let nested = |color: &str| {
+        let json = json.replacen(
+            &format!("\"paint\":{{\"Color\":{color}}}"),
+            "\"paint\":{\"Pattern\":0}",
+            1,
+        );
+        serde_json::from_str::<OwnedDisplayList>(&json).map(|_| ())
+    };
+    assert!(nested("[0.0,0.0,1.0,1.0]").is_ok());
+    let Err(error) = nested("[1.0,0.0,0.0,1.0]") else {
+        panic!("a pattern that contains itself was deserialized");
+    };
+    assert!(error.to_string().contains("isn't recorded before it"));
+
+    // Shadings are approximated in `DeviceRGB`, but can still be drawn natively.
+    let pdf = load("pdfs/custom/shading_lut.pdf");
+    let cache = InterpreterCache::new();
+    let mut gradients = Recorder::default();
+    interpret(&pdf, &cache, &mut gradients);
+    let mut list = OwnedDisplayList::new();
+    interpret(&pdf, &cache, &mut list);
+    let mut replayed = Recorder::default();
+    round_trip(&list).replay(&mut replayed);
+    assert!(replayed.paints.is_empty());
+    assert_eq!(replayed.axial, gradients.axial);
+
+    // Lists from other versions are rejected.
+    let json = serde_json::to_string(&list).unwrap().replacen(
+        &format!("\"version\":{}", OwnedDisplayList::VERSION),
+        "\"version\":0",
+        1,
+    );
+    let Err(error) = serde_json::from_str::<OwnedDisplayList>(&json) else {
+        panic!("a display list with a different version was deserialized");
+    };
+    assert!(
+        error
+            .to_string()
+            .contains("unsupported display list version 0")
+    );
+}
+
+#[test]
+fn hit_testing() {
+    let pdf = load("pdfs/custom/hit_testing.pdf");
+    let cache = InterpreterCache::new();
+    let hits = |target: Rect| {
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 200.0, 200.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        );
+
+        hit_test(&pdf.pages()[0], &mut context, target)
+            .into_iter()
+            .map(|hit| (hit.kind, hit.bounds))
+            .collect::<Vec<_>>()
+    };
+    let at = |x, y| hits(Rect::new(x, y, x, y));
+    let fill = HitKind::Path { stroked: false };
+
+    // The link annotation without an appearance lies on top of the blue square.
+    // The second link is hidden.
+    assert_eq!(
+        at(30.0, 30.0),
+        [
+            (
+                HitKind::Annotation {
+                    index: 0,
+                    subtype: Some("Link".to_string()),
+                },
+                Rect::new(10.0, 10.0, 50.0, 50.0),
+            ),
+            (fill.clone(), Rect::new(10.0, 10.0, 90.0, 90.0)),
+        ]
+    );
+    assert_eq!(
+        at(150.0, 50.0),
+        [(HitKind::Image, Rect::new(120.0, 20.0, 180.0, 80.0))]
+    );
+    assert_eq!(
+        at(100.0, 171.0),
+        [(
+            HitKind::Path { stroked: true },
+            Rect::new(17.5, 167.5, 182.5, 172.5)
+        )]
+    );
+
+    // Glyphs are hit within their advance, and the whole run is reported.
+    let text = at(60.0, 125.0);
+    assert_eq!(text.len(), 1);
+    assert_eq!(
+        text[0].0,
+        HitKind::GlyphRun {
+            text: "Hello".to_string(),
+            invisible: false,
+        }
+    );
+    assert_eq!(text[0].1.x0, 20.0);
+    assert_eq!(text[0].1.y0, 116.0);
+    assert!(at(70.0, 125.0).is_empty());
+
+    // The green rectangle is only hit within its clip path.
+    assert_eq!(
+        at(125.0, 125.0),
+        [(fill, Rect::new(100.0, 100.0, 150.0, 150.0))]
+    );
+    assert!(at(175.0, 125.0).is_empty());
+
+    // A rectangle hits everything that intersects it, topmost first.
+    let kinds = hits(Rect::new(0.0, 0.0, 200.0, 200.0))
+        .into_iter()
+        .map(|(kind, _)| kind)
+        .collect::<Vec<_>>();
+    assert_eq!(kinds.len(), 6);
+    assert!(matches!(kinds[0], HitKind::Annotation { index: 0, .. }));
+    assert_eq!(kinds[1], HitKind::Path { stroked: false });
+    assert_eq!(kinds[2], HitKind::Path { stroked: true });
+    assert!(matches!(kinds[3], HitKind::GlyphRun { .. }));
+    assert_eq!(kinds[4], HitKind::Image);
+    assert_eq!(kinds[5], HitKind::Path { stroked: false });
+}
+
+#[test]
+fn hit_testing_type3() {
+    let pdf = load("pdfs/custom/hit_testing_type3.pdf");
+    let cache = InterpreterCache::new();
+    let at = |x, y| {
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 200.0, 200.0),
+            &cache,
+            pdf.xref(),
+            InterpreterSettings::default(),
+        );
+
+        hit_test(&pdf.pages()[0], &mut context, Rect::new(x, y, x, y))
+            .into_iter()
+            .map(|hit| hit.bounds)
+            .collect::<Vec<_>>()
+    };
+
+    // The glyph space of the font has 100 units per em. The glyph is hit within its
+    // advance of half an em, and vertically within the font bounding box, which
+    // extends half an em below the baseline.
+    let bounds = Rect::new(20.0, 0.0, 70.0, 150.0);
+    assert_eq!(at(40.0, 10.0), [bounds]);
+    assert_eq!(at(40.0, 140.0), [bounds]);
+    assert!(at(90.0, 60.0).is_empty());
+}
+
+#[test]
+fn standalone_xobject() {
+    let pdf = load("pdfs/custom/interpret_xobject.pdf");
+    let cache = InterpreterCache::new();
+    let mut context = Context::new(
+        Affine::translate((100.0, 0.0)),
+        Rect::new(0.0, 0.0, 300.0, 300.0),
+        &cache,
+        pdf.xref(),
+        InterpreterSettings::default(),
+    );
+    let page = &pdf.pages()[0];
+    let stream = page
+        .resources()
+        .get_x_object(&Name::new(b"Stamp").unwrap())
+        .unwrap();
+    let x_object = XObject::from_stream(&stream, &context).unwrap();
+    let mut recorder = Recorder::default();
+    interpret_xobject(&x_object, page.resources(), &mut context, &mut recorder);
+
+    // The form is drawn with its matrix in the transform of the context, and the
+    // nested form is taken from the resources of the page.
+    assert_eq!(recorder.drawn, ["fill 105 5", "fill 155 55"]);
+}
+
+#[test]
+fn cached_form_content() {
+    let pdf = load("pdfs/custom/cached_form_content.pdf");
+    let record = |cache: &_, form_cache_max_bytes| {
+        let settings = InterpreterSettings {
+            form_cache_max_bytes,
+            ..Default::default()
+        };
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            cache,
+            pdf.xref(),
+            settings,
+        );
+        let mut recorder = Recorder::default();
+        interpret_page(&pdf.pages()[0], &mut context, &mut recorder);
+        assert_eq!(recorder.drawn, ["fill 0 0", "fill 50 50"]);
+
+        cache.form_cache().memory_usage()
+    };
+
+    // The compressed form is decoded once and then reused, both within the page
+    // and when the page is interpreted again. Its content stream is
+    // `0 0 10 10 re f`.
+    let cache = InterpreterCache::new();
+    assert_eq!(record(&cache, usize::MAX), 14);
+    assert_eq!(record(&cache, usize::MAX), 14);
+    // Content streams that exceed the limit are not cached.
+    assert_eq!(record(&InterpreterCache::new(), 13), 0);
+    assert_eq!(record(&InterpreterCache::new(), 0), 0);
+}
@@ -5,6 +5,7 @@ use hayro_jpeg2000::{DecodeSettings, Image};
 use hayro_syntax::Pdf;
 use hayro_syntax::metadata::Metadata;
 use hayro_syntax::object::DateTime;
+use hayro_syntax::page::PageFontEncoding;
 
 fn load_pdf(file: &[u8]) {
     let pdf = Pdf::new(file.to_vec());
@@ -678,6 +679,62 @@ fn metadata_in_object_stream() {
     assert_eq!(pdf.metadata(), &expected);
 }
 
+#[test]
+fn page_fonts() {
+    let file = include_bytes!("../pdfs/custom/page_fonts.pdf");
+    let pdf = Pdf::new(file.to_vec()).unwrap();
+    let page = &pdf.pages()[0];
+
+    let fonts = page
+        .fonts()
+        .iter()
+        .map(|f| {
+            let encoding = match &f.encoding {
+                Some(PageFontEncoding::Named(n)) => n.as_str().to_string(),
+                Some(PageFontEncoding::Differences(base)) => {
+                    format!("Differences({})", base.as_ref().map_or("", |b| b.as_str()))
+                }
+                Some(PageFontEncoding::EmbeddedCMap(_)) => "EmbeddedCMap".to_string(),
+                None => "Builtin".to_string(),
+            };
+
+            (
+                f.resource_name.as_str().to_string(),
+                String::from_utf8(f.name().unwrap().to_vec()).unwrap(),
+                f.subtype.as_ref().unwrap().as_str().to_string(),
+                f.embedded,
+                f.subset,
+                encoding,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let expected = [
+        ("F1", "Helvetica", "Type1", false, false, "WinAnsiEncoding"),
+        (
+            "F2",
+            "Foo-Bold",
+            "TrueType",
+            true,
+            true,
+            "Differences(MacRomanEncoding)",
+        ),
+        ("F3", "Bar", "Type0", false, false, "Identity-H"),
+    ]
+    .map(|(a, b, c, d, e, f)| {
+        (
+            a.to_string(),
+            b.to_string(),
+            c.to_string(),
+            d,
+            e,
+            f.to_string(),
+        )
+    });
+
+    assert_eq!(fonts, expected);
+}
+
 #[test]
 fn generic_refinement_decoding_overflow() {
     let file = include_bytes!("../pdfs/load/generic_refinement_decoding_overflow.jb2");
@@ -16,8 +16,12 @@ use std::sync::{Arc, LazyLock};
 #[rustfmt::skip]
 #[allow(non_snake_case)]
 mod render;
+mod interpret;
 mod load;
+mod raster;
 mod svg;
+mod text;
+mod transcode;
 mod write;
 
 const REPLACE: Option<&str> = option_env!("REPLACE");
@@ -0,0 +1,374 @@
+use crate::load_pdf;
+use hayro::hayro_interpret::{InterpreterSettings, RenderQuality, optional_content_groups};
+use hayro::{AlphaMode, RenderCache, RenderSettings, RgbaImage, render};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn pixel(image: &RgbaImage, x: usize, y: usize) -> [u8; 4] {
+    let idx = (y * image.width as usize + x) * 4;
+    <[u8; 4]>::try_from(&image.data[idx..idx + 4]).unwrap()
+}
+
+/// Rotate the image clockwise by 90 degrees.
+fn rotate_clockwise(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = (image.height, image.width);
+    let mut data = Vec::with_capacity(image.data.len());
+
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            data.extend(pixel(image, y, image.height as usize - 1 - x));
+        }
+    }
+
+    RgbaImage {
+        width,
+        height,
+        alpha_mode: image.alpha_mode,
+        data,
+    }
+}
+
+fn render_page(file: &str, page_idx: usize, render_settings: &RenderSettings) -> RgbaImage {
+    let pdf = load_pdf(file);
+    let cache = RenderCache::new();
+    let pages = pdf.pages();
+
+    render(
+        &pages[page_idx],
+        &cache,
+        &InterpreterSettings::default(),
+        render_settings,
+    )
+}
+
+#[test]
+fn alpha_mode_semi_transparent_edge() {
+    let file = "pdfs/custom/alpha_semi_transparent_edge.pdf";
+    let pdf = load_pdf(file);
+    let render_with = |alpha_mode| {
+        render(
+            &pdf.pages()[0],
+            &RenderCache::new(),
+            &InterpreterSettings::default(),
+            &RenderSettings {
+                alpha_mode,
+                ..Default::default()
+            },
+        )
+    };
+    let premultiplied = render_with(AlphaMode::Premultiplied);
+    let straight = render_with(AlphaMode::Straight);
+
+    assert_eq!(premultiplied.alpha_mode, AlphaMode::Premultiplied);
+    assert_eq!(straight.alpha_mode, AlphaMode::Straight);
+    assert_eq!(
+        (premultiplied.width, premultiplied.height),
+        (straight.width, straight.height)
+    );
+
+    // The rectangle ends in the middle of the 11th column, so that column
+    // only has partial coverage on top of the fill opacity.
+    let inner_premul = pixel(&premultiplied, 5, 10);
+    let inner_straight = pixel(&straight, 5, 10);
+    let edge_premul = pixel(&premultiplied, 10, 10);
+    let edge_straight = pixel(&straight, 10, 10);
+
+    assert!(inner_premul[3].abs_diff(128) <= 1);
+    assert!(edge_premul[3] > 0 && edge_premul[3] < inner_premul[3]);
+
+    for (premul, straight) in [(inner_premul, inner_straight), (edge_premul, edge_straight)] {
+        // Both modes must agree on the coverage.
+        assert_eq!(premul[3], straight[3]);
+        // In premultiplied mode, the red channel can never exceed the alpha.
+        assert!(premul[0].abs_diff(premul[3]) <= 1);
+        // In straight mode, the color must stay pure red, regardless of how
+        // transparent the pixel is.
+        assert!(straight[0] >= 250, "edge was darkened: {straight:?}");
+        assert_eq!(&straight[1..3], &[0, 0]);
+    }
+
+    // Fully transparent pixels are identical in both modes.
+    assert_eq!(pixel(&premultiplied, 15, 10), [0, 0, 0, 0]);
+    assert_eq!(pixel(&straight, 15, 10), [0, 0, 0, 0]);
+}
+
+#[test]
+fn dash_phase_consistent_under_rotation() {
+    let file = "pdfs/custom/path_rendering_dash_phase_rotated.pdf";
+    let settings = RenderSettings::default();
+
+    // The pages of the document have the same content, only `/Rotate`
+    // differs between them (0, 90, 180 and 270 degrees).
+    let mut expected = render_page(file, 0, &settings);
+    assert!(expected.data.chunks_exact(4).any(|p| p[3] == 255));
+
+    for (page_idx, rotation) in [(1, 90), (2, 180), (3, 270)] {
+        expected = rotate_clockwise(&expected);
+        let actual = render_page(file, page_idx, &settings);
+
+        assert_eq!(
+            (actual.width, actual.height),
+            (expected.width, expected.height),
+            "wrong dimensions for rotation {rotation}"
+        );
+
+        let max_diff = actual
+            .data
+            .iter()
+            .zip(&expected.data)
+            .map(|(a, e)| a.abs_diff(*e))
+            .max()
+            .unwrap();
+
+        assert!(
+            max_diff <= 1,
+            "dash pattern differs for rotation {rotation} (max diff {max_diff})"
+        );
+    }
+}
+
+#[test]
+fn type3_shape_glyph_clipped_to_bbox() {
+    let image = render_page(
+        "pdfs/custom/font_type3_d1_bbox_clip.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // Both glyphs fill their whole em square, but the first one declares a
+    // bounding box that only covers its lower left quarter.
+    let painted = |x, y| pixel(&image, x, y)[3] == 255;
+
+    assert!(painted(20, 80));
+    assert!(!painted(40, 60));
+    // The second glyph declares an empty bounding box, which is ignored.
+    assert!(painted(70, 60));
+    assert!(painted(60, 80));
+}
+
+#[test]
+fn uncolored_tiling_pattern_reused_with_different_colors() {
+    let image = render_page(
+        "pdfs/custom/pattern_tiling_uncolored_reused.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // The same uncolored pattern of squares is used to fill the left half in red,
+    // the right half in blue and then a horizontal band in red again.
+    assert_eq!(pixel(&image, 2, 97), [255, 0, 0, 255]);
+    assert_eq!(pixel(&image, 52, 97), [0, 0, 255, 255]);
+    assert_eq!(pixel(&image, 52, 47), [255, 0, 0, 255]);
+    assert_eq!(pixel(&image, 7, 97)[3], 0);
+}
+
+#[test]
+fn uncolored_tiling_pattern_on_stroke() {
+    let image = render_page(
+        "pdfs/custom/pattern_tiling_uncolored_stroke.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // The outline of the square is stroked with the pattern in blue, while the
+    // red fill color is not used.
+    assert_eq!(pixel(&image, 20, 50), [0, 0, 255, 255]);
+    assert_eq!(pixel(&image, 50, 20), [0, 0, 255, 255]);
+    assert_eq!(pixel(&image, 50, 50)[3], 0);
+}
+
+#[test]
+fn resources_inherited_from_page() {
+    let image = render_page(
+        "pdfs/custom/resources_inherited.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // Neither the form XObject nor the tiling pattern have their own resources,
+    // so the hidden optional content group and the green color space are looked
+    // up in the resources of the page.
+    assert_eq!(pixel(&image, 25, 75)[3], 0);
+    assert_eq!(pixel(&image, 75, 75), [0, 255, 0, 255]);
+    assert_eq!(pixel(&image, 50, 25), [0, 255, 0, 255]);
+}
+
+#[test]
+fn luminosity_soft_mask_with_backdrop_and_transfer_function() {
+    let image = render_page(
+        "pdfs/custom/soft_mask_luminosity_backdrop.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // The group of the mask has no color space, so the white backdrop color is
+    // interpreted as RGB. The left half of the group is black, and the transfer
+    // function inverts the luminosity, so only the left half is visible.
+    assert_eq!(pixel(&image, 25, 50), [255, 0, 0, 255]);
+    assert_eq!(pixel(&image, 75, 50)[3], 0);
+}
+
+#[test]
+fn alpha_soft_mask_with_constant_alpha() {
+    let image = render_page(
+        "pdfs/custom/soft_mask_alpha_constant_alpha.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // Only the alpha channel of the black group is used for the mask, and it is
+    // combined with the constant alpha of the same graphics state.
+    assert_eq!(pixel(&image, 25, 50), [128, 0, 0, 128]);
+    assert_eq!(pixel(&image, 75, 50)[3], 0);
+}
+
+#[test]
+fn fast_preview_quality() {
+    let render_file = |file: &str| {
+        let pdf = load_pdf(file);
+        let settings = InterpreterSettings {
+            render_quality: RenderQuality::fast_preview(),
+            ..Default::default()
+        };
+        let cache = RenderCache::new();
+        render(
+            &pdf.pages()[0],
+            &cache,
+            &settings,
+            &RenderSettings::default(),
+        )
+    };
+
+    // The soft mask is rendered at half the resolution, which doesn't affect
+    // its uniform areas.
+    let image = render_file("pdfs/custom/soft_mask_luminosity_backdrop.pdf");
+    assert_eq!(pixel(&image, 25, 50), [255, 0, 0, 255]);
+    assert_eq!(pixel(&image, 75, 50)[3], 0);
+
+    // The same applies to the tiles of the pattern.
+    let image = render_file("pdfs/custom/pattern_tiling_uncolored_reused.pdf");
+    assert_eq!(pixel(&image, 2, 97), [255, 0, 0, 255]);
+    assert_eq!(pixel(&image, 52, 97), [0, 0, 255, 255]);
+    assert_eq!(pixel(&image, 7, 97)[3], 0);
+}
+
+#[test]
+fn optional_content_layers() {
+    let pdf = load_pdf("pdfs/custom/optional_content_layers.pdf");
+    let layers = optional_content_groups(&pdf);
+    let names = layers
+        .iter()
+        .map(|l| (l.name.as_str(), l.visible))
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![("Roads", true), ("Labels", false)]);
+
+    let render_with = |optional_content| {
+        let settings = InterpreterSettings {
+            optional_content,
+            ..Default::default()
+        };
+        let cache = RenderCache::new();
+        render(
+            &pdf.pages()[0],
+            &cache,
+            &settings,
+            &RenderSettings::default(),
+        )
+    };
+
+    // The first line belongs to "Roads", the second one to "Labels", and the
+    // third one is only visible if "Roads" is hidden.
+    let visible = |image: &RgbaImage| [10, 50, 90].map(|y| pixel(image, 50, y)[3] > 0);
+
+    assert_eq!(visible(&render_with(Arc::default())), [true, false, false]);
+
+    let overrides = Arc::new(HashMap::from([(layers[0].id, false), (layers[1].id, true)]));
+    assert_eq!(visible(&render_with(overrides)), [false, true, true]);
+}
+
+#[test]
+fn zero_width_lines() {
+    let image = render_page(
+        "pdfs/custom/zero_width_lines.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // Lines with a width of zero are drawn one device pixel wide.
+    assert_eq!(pixel(&image, 50, 50), [255, 0, 0, 255]);
+    assert_eq!(pixel(&image, 50, 49)[3], 0);
+    assert_eq!(pixel(&image, 50, 51)[3], 0);
+
+    // This also applies inside of patterns, where the line width otherwise
+    // isn't enforced.
+    for y in [75, 85, 95] {
+        assert_eq!(pixel(&image, 50, y), [0, 0, 255, 255]);
+    }
+    assert_eq!(pixel(&image, 50, 80)[3], 0);
+}
+
+#[test]
+fn stroke_adjustment() {
+    let image = render_page(
+        "pdfs/custom/stroke_adjustment.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // With stroke adjustment, a line with a width of one pixel that lies between
+    // two rows of pixels covers exactly one of them.
+    assert_eq!(pixel(&image, 50, 49), [0, 0, 0, 255]);
+    assert_eq!(pixel(&image, 50, 48)[3], 0);
+    assert_eq!(pixel(&image, 50, 50)[3], 0);
+
+    // The width is rounded to whole pixels.
+    assert_eq!(pixel(&image, 29, 20), [0, 0, 0, 255]);
+    assert_eq!(pixel(&image, 30, 20), [0, 0, 0, 255]);
+    assert_eq!(pixel(&image, 28, 20)[3], 0);
+    assert_eq!(pixel(&image, 31, 20)[3], 0);
+
+    // Without it, the line is spread over two rows.
+    assert!(pixel(&image, 50, 79)[3] < 255);
+    assert!(pixel(&image, 50, 80)[3] > 0);
+}
+
+#[test]
+fn text_knockout() {
+    let image = render_page(
+        "pdfs/custom/text_knockout.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // Both lines draw the same glyph twice at the same position with an alpha of
+    // 0.5. With text knockout, the second glyph replaces the first one.
+    assert_eq!(pixel(&image, 16, 30), [128, 0, 0, 128]);
+
+    // Without text knockout, the glyphs are composited onto each other.
+    assert_eq!(pixel(&image, 16, 80), [191, 0, 0, 191]);
+}
+
+#[test]
+fn zero_length_dashes() {
+    let image = render_page(
+        "pdfs/custom/dash_zero_length.pdf",
+        0,
+        &RenderSettings::default(),
+    );
+
+    // With round caps, zero-length dashes are drawn as dots, including the one
+    // at the start of the line.
+    for x in [10, 30, 50, 70] {
+        assert_eq!(pixel(&image, x, 50), [0, 0, 0, 255]);
+    }
+    assert_eq!(pixel(&image, 40, 50)[3], 0);
+
+    // With butt caps, they are not visible.
+    assert!((0..100).all(|x| pixel(&image, x, 20)[3] < 8));
+
+    // With square caps, they are drawn as squares. The dash phase moves them by
+    // half of the gap.
+    assert_eq!(pixel(&image, 20, 80), [0, 0, 0, 255]);
+    assert_eq!(pixel(&image, 22, 80), [0, 0, 0, 255]);
+    assert_eq!(pixel(&image, 30, 80)[3], 0);
+}
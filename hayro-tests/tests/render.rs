@@ -290,6 +290,11 @@ use crate::{run_render_test, run_render_test_with_password};
 #[test] fn issue994() { run_render_test("issue994", "pdfs/custom/issue994.pdf", None); }
 #[test] fn font_standard_widths_array() { run_render_test("font_standard_widths_array", "pdfs/custom/font_standard_widths_array.pdf", None); }
 #[test] fn issue1023() { run_render_test("issue1023", "pdfs/custom/issue1023.pdf", None); }
+#[test] fn pattern_cs_without_pattern() { run_render_test("pattern_cs_without_pattern", "pdfs/custom/pattern_cs_without_pattern.pdf", None); }
+#[test] fn font_type3_text_state_isolation() { run_render_test("font_type3_text_state_isolation", "pdfs/custom/font_type3_text_state_isolation.pdf", None); }
+#[test] fn alpha_semi_transparent_edge() { run_render_test("alpha_semi_transparent_edge", "pdfs/custom/alpha_semi_transparent_edge.pdf", None); }
+#[test] fn path_rendering_dash_phase_rotated() { run_render_test("path_rendering_dash_phase_rotated", "pdfs/custom/path_rendering_dash_phase_rotated.pdf", None); }
+#[test] fn font_truetype_corrupt_glyf() { run_render_test("font_truetype_corrupt_glyf", "pdfs/custom/font_truetype_corrupt_glyf.pdf", None); }
 #[test] fn pdfjs_20130226130259() { run_render_test("pdfjs_20130226130259", "downloads/pdfjs/20130226130259.pdf", Some("0..=0")); }
 #[test] fn pdfjs_ContentStreamNoCycleType3insideType3() { run_render_test("pdfjs_ContentStreamNoCycleType3insideType3", "downloads/pdfjs/ContentStreamNoCycleType3insideType3.pdf", None); }
 #[test] fn pdfjs_High_Pressure_Measurement_WP_001287() { run_render_test("pdfjs_High_Pressure_Measurement_WP_001287", "downloads/pdfjs/High-Pressure-Measurement-WP-001287.pdf", Some("2..=2")); }
@@ -1,4 +1,6 @@
-use crate::run_svg_test;
+use crate::{load_pdf, run_svg_test};
+use hayro::hayro_interpret::InterpreterSettings;
+use hayro_svg::SvgRenderSettings;
 
 // TODO: Ideally those tests are also generated from the manifest files so they stay in sync.
 
@@ -268,3 +270,19 @@ fn issue_typst_7269() {
 fn issue_986() {
     run_svg_test("issue968", "pdfs/custom/issue968.pdf", None);
 }
+
+#[test]
+fn uncolored_tiling_pattern_on_stroke() {
+    let pdf = load_pdf("pdfs/custom/pattern_tiling_uncolored_stroke.pdf");
+    let cache = hayro_svg::RenderCache::new();
+    let svg = hayro_svg::convert(
+        &pdf.pages()[0],
+        &cache,
+        &InterpreterSettings::default(),
+        &SvgRenderSettings::default(),
+    );
+
+    // The pattern is only used for stroking, so it should be painted in blue.
+    assert!(svg.contains("#0000ff"));
+    assert!(!svg.contains("#ff0000"));
+}
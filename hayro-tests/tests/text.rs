@@ -0,0 +1,349 @@
+use crate::load_pdf;
+use hayro::hayro_interpret::text::{TextDevice, TextRun};
+use hayro::hayro_interpret::text_layout::TextLayout;
+use hayro::hayro_interpret::text_search::{PageSearchExt, SearchOptions, TextHit};
+use hayro::hayro_interpret::{
+    Context, InterpreterCache, InterpreterSettings, TransformExt, interpret_page,
+};
+use kurbo::{Affine, Point, Rect};
+
+fn extract(file: &str) -> TextDevice {
+    let pdf = load_pdf(file);
+    let cache = InterpreterCache::new();
+    let mut context = Context::new(
+        Affine::IDENTITY,
+        Rect::new(0.0, 0.0, 1.0, 1.0),
+        &cache,
+        pdf.xref(),
+        InterpreterSettings::default(),
+    );
+
+    let mut device = TextDevice::new();
+    interpret_page(&pdf.pages()[0], &mut context, &mut device);
+
+    device
+}
+
+fn extract_runs(file: &str) -> Vec<TextRun> {
+    extract(file).into_runs()
+}
+
+fn assert_point_eq(actual: Point, expected: Point) {
+    assert!(
+        (actual - expected).hypot() < 1e-3,
+        "expected {expected:?}, got {actual:?}"
+    );
+}
+
+#[test]
+fn text_device_collects_runs() {
+    let runs = extract_runs("pdfs/custom/text_extraction_runs.pdf");
+
+    assert_eq!(
+        runs.iter().map(TextRun::text).collect::<Vec<_>>(),
+        ["Hello World", "Kern", "Both", "Hidden", "Up"]
+    );
+
+    // Two show-text operators on the same baseline form a single run.
+    assert_point_eq(runs[0].origin(), Point::new(10.0, 170.0));
+    assert!((runs[0].font_size - 12.0).abs() < 1e-3);
+    assert!(!runs[0].invisible);
+
+    // Glyphs advance from left to right.
+    let xs = runs[0]
+        .glyphs
+        .iter()
+        .map(|g| g.origin.x)
+        .collect::<Vec<_>>();
+    assert!(xs.windows(2).all(|w| w[0] < w[1]));
+    let advance = runs[0].glyphs[0].advance.unwrap();
+    assert!((runs[0].glyphs[1].origin.x - runs[0].glyphs[0].origin.x - advance.x).abs() < 1e-3);
+
+    // Fill and stroke text must not show up twice.
+    assert_eq!(runs[2].glyphs.len(), 4);
+
+    assert!(runs[3].invisible);
+    assert!((runs[3].font_size - 8.0).abs() < 1e-3);
+
+    // Positions are in user space, the CTM is stored separately.
+    assert_eq!(
+        runs[4].transform,
+        Affine::new([0.0, 1.0, -1.0, 0.0, 150.0, 20.0])
+    );
+    assert_point_eq(runs[4].origin(), Point::ZERO);
+    assert_point_eq(
+        runs[4].transform * runs[4].origin(),
+        Point::new(150.0, 20.0),
+    );
+    assert!((runs[4].font_size - 10.0).abs() < 1e-3);
+}
+
+#[test]
+fn cid_font_without_to_unicode_uses_font_cmap() {
+    let runs = extract_runs("pdfs/custom/text_extraction_cid_without_to_unicode.pdf");
+
+    assert_eq!(runs.len(), 1);
+    assert_eq!(
+        runs[0]
+            .glyphs
+            .iter()
+            .map(|g| g.char_code)
+            .collect::<Vec<_>>(),
+        [3, 1, 2]
+    );
+    assert_eq!(runs[0].text(), "CAB");
+}
+
+#[test]
+fn cid_font_with_predefined_cmap_uses_ucs2_cmap() {
+    let runs = extract_runs("pdfs/custom/font_cid_predefined_cmap.pdf");
+
+    assert_eq!(runs.len(), 1);
+    assert_eq!(
+        runs[0]
+            .glyphs
+            .iter()
+            .map(|g| g.char_code)
+            .collect::<Vec<_>>(),
+        [0x41, 0x42]
+    );
+    assert_eq!(runs[0].text(), "AB");
+}
+
+#[test]
+fn text_layout_groups_words_lines_and_blocks() {
+    let runs = extract_runs("pdfs/custom/text_extraction_layout.pdf");
+    let layout = TextLayout::new(&runs);
+
+    // The second column is drawn interleaved with the first one and the
+    // lines of the second row are drawn from right to left.
+    assert_eq!(
+        layout.blocks.iter().map(|b| b.text()).collect::<Vec<_>>(),
+        [
+            "Title Here",
+            "Left one\nleft two\nGap Word",
+            "Right one\nright two"
+        ]
+    );
+
+    let left = &layout.blocks[1];
+    let right = &layout.blocks[2];
+    assert!(left.bbox.x1 < right.bbox.x0);
+    assert!((left.bbox.x0 - 10.0).abs() < 1e-3);
+    assert!(layout.blocks[0].bbox.y0 > left.bbox.y1);
+
+    // The gap in the `TJ` operator splits the words even without a space.
+    let words = &left.lines[2].words;
+    assert_eq!(words.len(), 2);
+    assert!(words[0].bbox.x1 < words[1].bbox.x0);
+    assert!(words[0].bbox.y0 < 126.0 && words[0].bbox.y1 > 126.0);
+
+    assert_eq!(
+        layout.text(),
+        "Title Here\n\nLeft one\nleft two\nGap Word\n\nRight one\nright two"
+    );
+}
+
+fn search(file: &str, needle: &str, options: SearchOptions) -> Vec<TextHit> {
+    let pdf = load_pdf(file);
+    pdf.pages()[0].search(needle, &options)
+}
+
+#[test]
+fn search_page_text() {
+    let file = "pdfs/custom/text_extraction_layout.pdf";
+    let texts = |hits: Vec<TextHit>| hits.into_iter().map(|h| h.text).collect::<Vec<_>>();
+
+    assert_eq!(
+        texts(search(file, "left", SearchOptions::default())),
+        ["left"]
+    );
+    assert_eq!(
+        texts(search(
+            file,
+            "left",
+            SearchOptions {
+                case_insensitive: true,
+                ..Default::default()
+            }
+        )),
+        ["Left", "left"]
+    );
+    assert_eq!(texts(search(file, "on", SearchOptions::default())).len(), 2);
+    assert!(
+        search(
+            file,
+            "on",
+            SearchOptions {
+                whole_word: true,
+                ..Default::default()
+            }
+        )
+        .is_empty()
+    );
+
+    // The page is 200pt high, so in device space the y-axis is flipped.
+    let title = search(file, "Title", SearchOptions::default());
+    assert_eq!(title.len(), 1);
+    let [bottom_left, _, _, top_left] = title[0].quads[0];
+    assert_point_eq(bottom_left, Point::new(10.0, 200.0 - (180.0 - 0.2 * 16.0)));
+    assert_point_eq(top_left, Point::new(10.0, 200.0 - (180.0 + 0.8 * 16.0)));
+
+    // Matches can span multiple lines of a block.
+    let hits = search(file, "one  left", SearchOptions::default());
+    assert_eq!(texts(hits.clone()), ["one left"]);
+    assert_eq!(hits[0].quads.len(), 2);
+    assert!(hits[0].quads[0][0].y < hits[0].quads[1][0].y);
+}
+
+#[test]
+fn actual_text_and_alt_of_marked_content() {
+    let device = extract("pdfs/custom/text_extraction_actual_text.pdf");
+    let runs = device.runs();
+
+    // The replacement text is assigned to the first glyph of the sequence, while the
+    // other glyphs keep their positions.
+    assert_eq!(
+        runs.iter().map(TextRun::text).collect::<Vec<_>>(),
+        ["prefix", "", "\u{FB01} end"]
+    );
+    assert_eq!(runs[0].glyphs.len(), 4);
+    assert_eq!(runs[1].glyphs.len(), 3);
+
+    assert_eq!(device.alt_texts(), ["Pie \u{2013} chart"]);
+}
+
+#[test]
+fn bidi_reordering_of_extracted_lines() {
+    let runs = extract_runs("pdfs/custom/text_extraction_bidi.pdf");
+    let mut layout = TextLayout::new(&runs);
+
+    // Lines are assembled in visual order.
+    assert_eq!(
+        layout.text(),
+        "Hello \u{5D3}\u{5D2}\u{5D1} world\n12 \u{5D1}\u{5D0}"
+    );
+
+    layout.reorder_bidi();
+
+    let lines = &layout.blocks[0].lines;
+    // A right-to-left word embedded in left-to-right text.
+    assert_eq!(lines[0].text(), "Hello \u{5D1}\u{5D2}\u{5D3} world");
+    // A right-to-left line, where the number keeps its order.
+    assert_eq!(lines[1].text(), "\u{5D0}\u{5D1} 12");
+    assert_eq!(lines[1].words[0].text, "\u{5D0}\u{5D1}");
+    assert_eq!(lines[1].words[1].text, "12");
+}
+
+#[test]
+fn vertical_text_is_grouped_into_columns() {
+    let runs = extract_runs("pdfs/custom/text_extraction_vertical.pdf");
+
+    assert_eq!(
+        runs.iter().map(TextRun::text).collect::<Vec<_>>(),
+        ["ABC", "CBA"]
+    );
+    assert!(runs.iter().all(|r| r.vertical));
+    assert!((runs[0].font_size - 20.0).abs() < 1e-3);
+
+    // Vertical glyphs are positioned by their top center and advance downwards.
+    assert_point_eq(runs[0].origin(), Point::new(150.0, 180.0));
+    assert_point_eq(runs[0].glyphs[1].origin, Point::new(150.0, 160.0));
+    let advance = runs[0].glyphs[0].advance.unwrap();
+    assert_point_eq(advance.to_point(), Point::new(0.0, -20.0));
+
+    // Columns are read from right to left, and the `TJ` offset starts a new word.
+    let layout = TextLayout::new(&runs);
+    assert_eq!(layout.text(), "ABC\nC BA");
+
+    let bbox = layout.blocks[0].lines[0].bbox;
+    assert_point_eq(bbox.origin(), Point::new(140.0, 120.0));
+    assert_point_eq(Point::new(bbox.x1, bbox.y1), Point::new(160.0, 180.0));
+    assert!((layout.blocks[0].lines[1].words[1].bbox.y1 - 140.0).abs() < 1e-3);
+}
+
+#[test]
+fn mixed_directions_are_ordered_consistently() {
+    let runs = extract_runs("pdfs/custom/text_extraction_mixed_directions.pdf");
+    let layout = TextLayout::new(&runs);
+
+    // The horizontal line on the left and the vertical column on the right start at
+    // the same height, so the horizontal one comes first regardless of their
+    // positions. The single letter below is read last.
+    let blocks = layout
+        .blocks
+        .iter()
+        .map(|b| (b.lines[0].text(), b.bbox.x0.round()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        blocks,
+        [
+            ("ABC".to_string(), 10.0),
+            ("ABC".to_string(), 140.0),
+            ("C".to_string(), 10.0)
+        ]
+    );
+}
+
+#[test]
+fn char_quads_in_device_space() {
+    let file = "pdfs/custom/text_extraction_quads.pdf";
+    let pdf = load_pdf(file);
+    let mut layout = TextLayout::new(&extract_runs(file));
+    layout.transform(pdf.pages()[0].initial_transform(true).to_kurbo());
+
+    let chars = layout
+        .blocks
+        .iter()
+        .flat_map(|b| &b.lines)
+        .flat_map(|l| &l.words)
+        .flat_map(|w| &w.chars)
+        .map(|c| (c.text.as_str(), c.quad))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        chars.iter().map(|(text, _)| *text).collect::<Vec<_>>(),
+        ["A", "B", "C"]
+    );
+
+    let assert_quad_eq = |actual: [Point; 4], expected: [(f64, f64); 4]| {
+        for (a, e) in actual.into_iter().zip(expected) {
+            assert_point_eq(a, e.into());
+        }
+    };
+
+    // Skewed text keeps the slant of the glyphs.
+    assert_quad_eq(
+        chars[0].1,
+        [(19.0, 102.0), (25.67, 102.0), (30.67, 92.0), (24.0, 92.0)],
+    );
+    // Rotated text runs from bottom to top.
+    assert_quad_eq(
+        chars[2].1,
+        [
+            (152.0, 150.0),
+            (152.0, 142.78),
+            (142.0, 142.78),
+            (142.0, 150.0),
+        ],
+    );
+}
+
+#[test]
+fn to_unicode_fallback_uses_glyph_list_names() {
+    let runs = extract_runs("pdfs/custom/font_differences_glyph_list_names.pdf");
+
+    assert_eq!(
+        runs.iter().map(TextRun::text).collect::<Vec<_>>(),
+        ["BCDffDC", "BCDA"]
+    );
+}
+
+#[test]
+fn cid_font_with_use_cmap_entry() {
+    let runs = extract_runs("pdfs/custom/font_cid_use_cmap_entry.pdf");
+
+    // The encoding only maps A itself, B and C are mapped by the cmap that
+    // is referenced via `UseCMap`.
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].text(), "CAB");
+}
@@ -0,0 +1,178 @@
+use crate::load_pdf;
+use hayro::hayro_interpret::InterpreterSettings;
+use hayro::{RenderCache, RenderSettings, RgbaImage, render};
+use hayro_pdf::ConvertSettings;
+use hayro_syntax::Pdf;
+
+/// Convert all pages of the file into a new PDF file.
+fn transcode(file: &str) -> Vec<u8> {
+    transcode_with(file, &ConvertSettings::default())
+}
+
+fn transcode_with(file: &str, settings: &ConvertSettings) -> Vec<u8> {
+    let pdf = load_pdf(file);
+    let cache = hayro_pdf::RenderCache::new();
+
+    hayro_pdf::convert(
+        pdf.pages(),
+        &cache,
+        &InterpreterSettings::default(),
+        settings,
+    )
+}
+
+fn render_first_page(pdf: &Pdf) -> RgbaImage {
+    render(
+        &pdf.pages()[0],
+        &RenderCache::new(),
+        &InterpreterSettings::default(),
+        &RenderSettings::default(),
+    )
+}
+
+/// Check that the first page of the converted file looks like the first page of the
+/// original one.
+///
+/// Since edges are anti-aliased a bit differently when paths are written with a lower
+/// precision or strokes are converted into fills, a small fraction of pixels is allowed
+/// to differ.
+fn check_transcode(file: &str) {
+    let original = load_pdf(file);
+    let transcoded = Pdf::new(transcode(file)).unwrap();
+
+    let expected = render_first_page(&original);
+    let actual = render_first_page(&transcoded);
+
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height)
+    );
+
+    let differing = expected
+        .data
+        .chunks_exact(4)
+        .zip(actual.data.chunks_exact(4))
+        .filter(|(e, a)| e.iter().zip(a.iter()).any(|(e, a)| e.abs_diff(*a) > 32))
+        .count();
+    let total = expected.width as usize * expected.height as usize;
+
+    assert!(
+        differing * 100 <= total,
+        "{differing} of {total} pixels differ for {file}"
+    );
+}
+
+#[test]
+fn transcode_clip_path() {
+    check_transcode("pdfs/custom/clip_path_evenodd.pdf");
+}
+
+#[test]
+fn transcode_text() {
+    check_transcode("pdfs/custom/font_truetype_1.pdf");
+}
+
+#[test]
+fn transcode_type3_text() {
+    check_transcode("pdfs/custom/font_type3_filled_glyphs.pdf");
+}
+
+#[test]
+fn transcode_stroked_text_with_pattern() {
+    check_transcode("pdfs/custom/text_stroked_complex_paint.pdf");
+}
+
+#[test]
+fn transcode_image() {
+    check_transcode("pdfs/custom/image_rgba8.pdf");
+}
+
+#[test]
+fn transcode_image_mask() {
+    check_transcode("pdfs/custom/image_mask.pdf");
+}
+
+#[test]
+fn transcode_axial_shading() {
+    check_transcode("pdfs/custom/pattern_shading_type2_3_stops.pdf");
+}
+
+#[test]
+fn transcode_radial_shading() {
+    check_transcode("pdfs/custom/pattern_shading_type3_1.pdf");
+}
+
+#[test]
+fn transcode_shading_with_background() {
+    check_transcode("pdfs/custom/pattern_shading_background.pdf");
+}
+
+#[test]
+fn transcode_function_shading() {
+    check_transcode("pdfs/custom/pattern_shading_type1_1.pdf");
+}
+
+#[test]
+fn transcode_mesh_shading() {
+    check_transcode("pdfs/custom/pattern_shading_type4_2.pdf");
+}
+
+#[test]
+fn transcode_tiling_pattern() {
+    check_transcode("pdfs/custom/pattern_tiling_rotated.pdf");
+}
+
+#[test]
+fn transcode_uncolored_tiling_pattern() {
+    check_transcode("pdfs/custom/pattern_tiling_uncolored_stroke.pdf");
+}
+
+#[test]
+fn transcode_soft_mask() {
+    check_transcode("pdfs/custom/mask_luminosity.pdf");
+}
+
+#[test]
+fn transcode_blend_modes() {
+    check_transcode("pdfs/custom/blend_modes.pdf");
+}
+
+#[test]
+fn transcode_without_transparency() {
+    let settings = ConvertSettings {
+        transparency: false,
+    };
+
+    for file in [
+        "pdfs/custom/mask_luminosity.pdf",
+        "pdfs/custom/blend_modes.pdf",
+    ] {
+        let transcoded = Pdf::new(transcode(file)).unwrap();
+        assert!(!transcoded.pages()[0].resources().ext_g_states.is_empty());
+
+        let transcoded = Pdf::new(transcode_with(file, &settings)).unwrap();
+        assert!(transcoded.pages()[0].resources().ext_g_states.is_empty());
+    }
+}
+
+#[test]
+fn transcode_without_fonts() {
+    let transcoded = Pdf::new(transcode("pdfs/custom/font_truetype_1.pdf")).unwrap();
+
+    for page in transcoded.pages().iter() {
+        assert!(page.resources().fonts.is_empty());
+    }
+}
+
+#[test]
+fn transcode_all_pages() {
+    let file = "pdfs/custom/text_rendering_1.pdf";
+    let original = load_pdf(file);
+    let transcoded = Pdf::new(transcode(file)).unwrap();
+
+    assert_eq!(original.pages().len(), transcoded.pages().len());
+
+    for (original, transcoded) in original.pages().iter().zip(transcoded.pages().iter()) {
+        assert_eq!(original.render_dimensions(), transcoded.render_dimensions());
+    }
+}
@@ -4,7 +4,7 @@ use hayro::hayro_interpret::InterpreterSettings;
 use hayro::hayro_interpret::font::{FontData, FontQuery, StandardFont};
 use hayro::hayro_interpret::hayro_cmap::CidFamily;
 use hayro::hayro_syntax::Pdf;
-use hayro::{RenderCache, RenderSettings, render};
+use hayro::{AlphaMode, RenderCache, RenderSettings, render};
 use std::path::Path;
 use std::sync::Arc;
 use vello_cpu::color::palette::css::WHITE;
@@ -86,14 +86,18 @@ fn main() {
         x_scale: scale,
         y_scale: scale,
         bg_color: WHITE,
+        alpha_mode: AlphaMode::Straight,
         ..Default::default()
     };
     let cache = RenderCache::new();
 
     for (idx, page) in pdf.pages().iter().enumerate() {
-        let pixmap = render(page, &cache, &interpreter_settings, &render_settings);
+        let rendered = render(page, &cache, &interpreter_settings, &render_settings);
         let output_path = format!("{}/rendered_{idx}.png", output_dir);
-        std::fs::write(output_path, pixmap.into_png().unwrap()).unwrap();
+        image::RgbaImage::from_raw(rendered.width as u32, rendered.height as u32, rendered.data)
+            .unwrap()
+            .save(output_path)
+            .unwrap();
     }
 }
 
@@ -14,7 +14,9 @@ from the `pdf.js` and `PDFBOX` test suites and therefore represent a very large
 of PDF files.
 
 As mentioned, there are still some serious limitations, including lack of support for
-encrypted/password-protected PDF files, blending and isolation, knockout groups as well as a range
+encrypted/password-protected PDF files, blending and isolation, knockout groups, blending in the
+color space of transparency groups (all groups are blended in sRGB), overprinting (which
+can only be approximated with `approximate_overprint`) as well as a range
 of smaller features such as color key masking. But you should be able to render the vast majority
 of PDF files without too many issues.
 
@@ -26,8 +28,11 @@ For usage examples, see the [example](https://github.com/LaurenzV/hayro/tree/mas
 the GitHub repository.
 
 ## Cargo features
-This crate has one optional feature:
+This crate has the following optional features:
 - `embed-fonts`: See the description of [`hayro-interpret`](https://docs.rs/hayro-interpret/latest/hayro_interpret/#cargo-features) for more information.
+- `bidi`: See the description of [`hayro-interpret`](https://docs.rs/hayro-interpret/latest/hayro_interpret/#cargo-features) for more information.
+- `system-fonts`: See the description of [`hayro-interpret`](https://docs.rs/hayro-interpret/latest/hayro_interpret/#cargo-features) for more information.
+- `serde`: See the description of [`hayro-interpret`](https://docs.rs/hayro-interpret/latest/hayro_interpret/#cargo-features) for more information.
 */
 
 #![forbid(unsafe_code)]
@@ -38,16 +43,14 @@ use hayro_interpret::Device;
 use hayro_interpret::FillRule;
 use hayro_interpret::InterpreterCache;
 use hayro_interpret::InterpreterSettings;
+use hayro_interpret::font::GlyphCache;
 use hayro_interpret::hayro_syntax::Pdf;
 use hayro_interpret::hayro_syntax::page::Page;
 use hayro_interpret::util::{RectExt, TransformExt};
 use hayro_interpret::{BlendMode, Context};
 use hayro_interpret::{ClipPath, interpret_page};
 use kurbo::{Affine, Rect, Shape};
-use rustc_hash::FxHashMap;
-use std::cell::RefCell;
 use std::ops::RangeInclusive;
-use std::rc::Rc;
 
 pub use hayro_interpret;
 pub use hayro_interpret::hayro_syntax;
@@ -68,7 +71,6 @@ mod renderer;
 #[derive(Clone, Default)]
 pub struct RenderCache<'a> {
     pub(crate) interpreter_cache: InterpreterCache<'a>,
-    pub(crate) outline_cache: Rc<RefCell<FxHashMap<u128, Rc<kurbo::BezPath>>>>,
 }
 
 impl<'a> RenderCache<'a> {
@@ -76,7 +78,15 @@ impl<'a> RenderCache<'a> {
     pub fn new() -> Self {
         Self {
             interpreter_cache: InterpreterCache::new(),
-            outline_cache: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    /// Create a new render cache that uses the given cache for glyph outlines.
+    ///
+    /// Use this to configure the memory limit of the glyph cache.
+    pub fn with_glyph_cache(glyph_cache: GlyphCache) -> Self {
+        Self {
+            interpreter_cache: InterpreterCache::with_glyph_cache(glyph_cache),
         }
     }
 }
@@ -97,6 +107,8 @@ pub struct RenderSettings {
     /// The background color. Determines the color of the base
     /// rectangle during rendering to a pixmap.
     pub bg_color: AlphaColor<Srgb>,
+    /// The alpha mode of the rendered pixel data.
+    pub alpha_mode: AlphaMode,
 }
 
 impl Default for RenderSettings {
@@ -107,16 +119,67 @@ impl Default for RenderSettings {
             width: None,
             height: None,
             bg_color: TRANSPARENT,
+            alpha_mode: AlphaMode::default(),
         }
     }
 }
 
-/// Render the page with the given settings to a pixmap.
+/// How the alpha channel of rendered pixel data is represented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// The color channels have already been multiplied by the alpha channel.
+    ///
+    /// This is the representation used internally by the renderer, and what
+    /// most compositors expect.
+    #[default]
+    Premultiplied,
+    /// The color channels are independent of the alpha channel, as expected
+    /// by most image formats such as PNG.
+    Straight,
+}
+
+/// A rendered page in the form of raw RGBA8 pixel data.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    /// The width of the image.
+    pub width: u16,
+    /// The height of the image.
+    pub height: u16,
+    /// The alpha mode of the pixel data.
+    pub alpha_mode: AlphaMode,
+    /// The pixel data in row-major order, with each pixel consisting of four
+    /// bytes in the order `[r, g, b, a]`.
+    pub data: Vec<u8>,
+}
+
+/// Render the page with the given settings to raw RGBA8 data.
 pub fn render<'a>(
     page: &'a Page<'a>,
     cache: &RenderCache<'a>,
     interpreter_settings: &InterpreterSettings,
     render_settings: &RenderSettings,
+) -> RgbaImage {
+    let pixmap = render_pixmap(page, cache, interpreter_settings, render_settings);
+    let (width, height) = (pixmap.width(), pixmap.height());
+
+    let data = match render_settings.alpha_mode {
+        AlphaMode::Premultiplied => bytemuck::cast_vec(pixmap.take()),
+        AlphaMode::Straight => bytemuck::cast_vec(pixmap.take_unpremultiplied()),
+    };
+
+    RgbaImage {
+        width,
+        height,
+        alpha_mode: render_settings.alpha_mode,
+        data,
+    }
+}
+
+fn render_pixmap<'a>(
+    page: &'a Page<'a>,
+    cache: &RenderCache<'a>,
+    interpreter_settings: &InterpreterSettings,
+    render_settings: &RenderSettings,
 ) -> Pixmap {
     let (x_scale, y_scale) = (render_settings.x_scale, render_settings.y_scale);
     let (width, height) = page.render_dimensions();
@@ -143,7 +206,12 @@ pub fn render<'a>(
         num_threads: 0,
     };
 
-    let mut device = Renderer::new(pix_width, pix_height, vc_settings, cache);
+    let mut device = Renderer::new(
+        pix_width,
+        pix_height,
+        vc_settings,
+        interpreter_settings.render_quality,
+    );
 
     device.ctx.set_paint(render_settings.bg_color);
     device
@@ -188,7 +256,7 @@ pub fn render_pdf(
                 return None;
             }
 
-            let pixmap = render(
+            let pixmap = render_pixmap(
                 page,
                 &cache,
                 &settings,
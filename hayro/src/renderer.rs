@@ -1,18 +1,19 @@
-use crate::{RenderCache, derive_settings};
+use crate::derive_settings;
 use hayro_interpret::encode::{EncodedShadingPattern, EncodedShadingType};
 use hayro_interpret::font::Glyph;
 use hayro_interpret::gradient::SvgGradientKind;
 use hayro_interpret::pattern::Pattern;
 use hayro_interpret::{
     BlendMode, CacheKey, ClipPath, Device, DrawMode, DrawProps, FillRule, ImageData,
-    ImageDrawProps, LumaData, MaskType, Paint, RgbData, SoftMask, StrokeProps,
+    ImageDrawProps, ImageFilterQuality, LumaData, MIN_FLATNESS, MaskType, Paint, RenderQuality,
+    RgbData, SoftMask, StrokeProps,
 };
-use kurbo::{Affine, BezPath, Point, Rect, Shape, Vec2};
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape, Vec2};
 use pic_scale::{
     ImageSize, ImageStore, ImageStoreMut, PicScaleError, Resampling, ResamplingFunction, Scaler,
 };
 use rustc_hash::FxHashMap;
-use std::rc::Rc;
+use std::borrow::Cow;
 use std::sync::Arc;
 use vello_cpu::color::palette::css::BLACK;
 use vello_cpu::color::{AlphaColor, DynamicColor, PremulRgba8, Srgb};
@@ -21,13 +22,19 @@ use vello_cpu::{
     Image, ImageSource, Mask, PaintType, Pixmap, RenderContext, RenderSettings, peniko,
 };
 
+/// The tolerance in device pixels that `vello_cpu` flattens curves with.
+const VELLO_FLATNESS: f32 = 0.25;
+
 pub(crate) struct Renderer {
     pub(crate) ctx: RenderContext,
     pub(crate) inside_pattern: bool,
     pub(crate) soft_mask_cache: FxHashMap<u128, Mask>,
-    pub(crate) outline_cache: Rc<std::cell::RefCell<FxHashMap<u128, Rc<BezPath>>>>,
+    /// Rendered tiles of tiling patterns, keyed by the pattern, the scale of the
+    /// tile and whether it is used for stroking.
+    pub(crate) tile_cache: FxHashMap<(u128, u32, u32, bool), Arc<Pixmap>>,
     pub(crate) in_type3_glyph: bool,
     pub(crate) scaler: Scaler,
+    pub(crate) quality: RenderQuality,
 }
 
 #[derive(Clone, Copy)]
@@ -96,19 +103,22 @@ impl Renderer {
         width: u16,
         height: u16,
         settings: RenderSettings,
-        cache: &RenderCache<'_>,
+        quality: RenderQuality,
     ) -> Self {
         Self {
             ctx: RenderContext::new_with(width, height, settings),
             inside_pattern: false,
             soft_mask_cache: FxHashMap::default(),
-            outline_cache: cache.outline_cache.clone(),
+            tile_cache: FxHashMap::default(),
             in_type3_glyph: false,
-            scaler: Scaler::new(ResamplingFunction::CatmullRom),
+            scaler: Scaler::new(resampling_function(quality.image_filter)),
+            quality,
         }
     }
 
-    fn set_stroke_properties(&mut self, stroke_props: &StrokeProps, is_text: bool) {
+    /// Set the stroke properties and return the resulting line width in device
+    /// space.
+    fn set_stroke_properties(&mut self, stroke_props: &StrokeProps, is_text: bool) -> f32 {
         let threshold = if is_text { 0.25 } else { 1.0 };
 
         // Best-effort attempt to ensure a line width of at least 1.0, as required by the PDF
@@ -118,23 +128,34 @@ impl Renderer {
         let mut line_width = stroke_props.line_width.max(0.01);
         let transformed_width = line_width * min_factor;
 
-        // Only enforce line width if not inside of pattern or type 3 glyph.
-        if transformed_width < threshold && !self.inside_pattern && !self.in_type3_glyph {
+        if stroke_props.hairline {
+            // Hairlines are always exactly one device pixel wide, also inside of
+            // patterns and type 3 glyphs.
+            line_width = 1.0 / min_factor;
+        } else if transformed_width < threshold && !self.inside_pattern && !self.in_type3_glyph {
+            // Only enforce line width if not inside of pattern or type 3 glyph.
             line_width /= transformed_width;
             line_width *= threshold;
         }
 
+        // With stroke adjustment, strokes should cover a whole number of pixels.
+        if stroke_props.stroke_adjustment && is_axis_aligned(self.ctx.transform()) {
+            line_width = (line_width * min_factor).round().max(1.0) / min_factor;
+        }
+
         let stroke = kurbo::Stroke {
             width: line_width as f64,
             join: stroke_props.line_join,
             miter_limit: stroke_props.miter_limit as f64,
             start_cap: stroke_props.line_cap,
             end_cap: stroke_props.line_cap,
-            dash_pattern: stroke_props.dash_array.iter().map(|n| *n as f64).collect(),
+            dash_pattern: stroke_props.dash_array_for_stroker().into_iter().collect(),
             dash_offset: stroke_props.dash_offset as f64,
         };
 
         self.ctx.set_stroke(stroke);
+
+        line_width * min_factor
     }
 
     fn draw_image_with_alpha_mask(&mut self, image_data: RenderImageData, alpha_data: LumaData) {
@@ -152,13 +173,14 @@ impl Renderer {
                 ),
                 inside_pattern: false,
                 soft_mask_cache: FxHashMap::default(),
-                outline_cache: self.outline_cache.clone(),
+                tile_cache: FxHashMap::default(),
                 in_type3_glyph: false,
                 scaler: self.scaler,
+                quality: self.quality,
             };
             let mut mask_pix = Pixmap::new(self.ctx.width(), self.ctx.height());
             let rgb_data = ImageData::Rgb(RgbData {
-                data: vec![0; alpha_data.width as usize * alpha_data.height as usize * 3],
+                data: vec![0; alpha_data.width as usize * alpha_data.height as usize * 3].into(),
                 width: alpha_data.width,
                 height: alpha_data.height,
                 interpolate: alpha_data.interpolate,
@@ -182,7 +204,7 @@ impl Renderer {
 
     fn resize_image_data(
         &self,
-        data: Vec<u8>,
+        data: &[u8],
         src_width: u32,
         src_height: u32,
         new_width: u32,
@@ -225,7 +247,7 @@ impl Renderer {
 
     fn resize_image_data_impl<const N: usize>(
         &self,
-        data: Vec<u8>,
+        data: &[u8],
         src_width: u32,
         src_height: u32,
         new_width: u32,
@@ -238,8 +260,8 @@ impl Renderer {
     ) -> Vec<u8> {
         let source_size = ImageSize::new(src_width as usize, src_height as usize);
         let target_size = ImageSize::new(new_width as usize, new_height as usize);
-        let src = ImageStore::<u8, N>::from_slice(&data, src_width as usize, src_height as usize)
-            .unwrap();
+        let src =
+            ImageStore::<u8, N>::from_slice(data, src_width as usize, src_height as usize).unwrap();
         let mut out = vec![0; new_width as usize * new_height as usize * N];
         let mut dst =
             ImageStoreMut::<u8, N>::from_slice(&mut out, new_width as usize, new_height as usize)
@@ -269,7 +291,11 @@ impl Renderer {
         }
 
         let mut quality = if interpolate {
-            ImageQuality::Medium
+            match self.quality.image_filter {
+                ImageFilterQuality::Low => ImageQuality::Low,
+                ImageFilterQuality::Medium => ImageQuality::Medium,
+                ImageFilterQuality::High => ImageQuality::High,
+            }
         } else {
             ImageQuality::Low
         };
@@ -286,7 +312,7 @@ impl Renderer {
                 .ceil()
                 .max(1.0)
                 .min((u16::MAX / 2) as f32) as u32;
-            if self.in_type3_glyph {
+            if self.in_type3_glyph && self.quality.image_filter != ImageFilterQuality::Low {
                 quality = ImageQuality::High;
             }
             (w, h)
@@ -303,10 +329,10 @@ impl Renderer {
             let alpha = alpha_data.unwrap();
 
             let alpha_data = if !needs_resize {
-                alpha.data
+                Cow::Borrowed(&*alpha.data)
             } else {
                 let resized_alpha = self.resize_image_data(
-                    alpha.data,
+                    &alpha.data,
                     img_width,
                     img_height,
                     new_width,
@@ -319,12 +345,12 @@ impl Renderer {
                 );
                 img_width = new_width;
                 img_height = new_height;
-                resized_alpha
+                Cow::Owned(resized_alpha)
             };
 
             let mut out = Vec::with_capacity(img_width as usize * img_height as usize * 4);
-            for a in alpha_data {
-                out.extend_from_slice(&[solid.color[0], solid.color[1], solid.color[2], a]);
+            for a in alpha_data.iter() {
+                out.extend_from_slice(&[solid.color[0], solid.color[1], solid.color[2], *a]);
             }
             out
         } else if matches!(&image_data, RenderImageData::Luma(_)) && !has_alpha {
@@ -334,10 +360,10 @@ impl Renderer {
             };
 
             let luma_data = if !needs_resize {
-                luma.data
+                Cow::Borrowed(&*luma.data)
             } else {
                 let resized = self.resize_image_data(
-                    luma.data,
+                    &luma.data,
                     img_width,
                     img_height,
                     new_width,
@@ -350,7 +376,7 @@ impl Renderer {
                 );
                 img_width = new_width;
                 img_height = new_height;
-                resized
+                Cow::Owned(resized)
             };
 
             luma_data
@@ -364,10 +390,10 @@ impl Renderer {
             let alpha = alpha_data.unwrap();
 
             let (luma_data, alpha_data) = if !needs_resize {
-                (luma.data, alpha.data)
+                (Cow::Borrowed(&*luma.data), Cow::Borrowed(&*alpha.data))
             } else {
                 let resized_luma = self.resize_image_data(
-                    luma.data,
+                    &luma.data,
                     img_width,
                     img_height,
                     new_width,
@@ -375,7 +401,7 @@ impl Renderer {
                     ImagePixelFormat::Luma,
                 );
                 let resized_alpha = self.resize_image_data(
-                    alpha.data,
+                    &alpha.data,
                     img_width,
                     img_height,
                     new_width,
@@ -388,12 +414,12 @@ impl Renderer {
                 );
                 img_width = new_width;
                 img_height = new_height;
-                (resized_luma, resized_alpha)
+                (Cow::Owned(resized_luma), Cow::Owned(resized_alpha))
             };
 
             let mut out = Vec::with_capacity(img_width as usize * img_height as usize * 4);
-            for (g, a) in luma_data.iter().zip(alpha_data) {
-                out.extend_from_slice(&[*g, *g, *g, a]);
+            for (g, a) in luma_data.iter().zip(alpha_data.iter()) {
+                out.extend_from_slice(&[*g, *g, *g, *a]);
             }
             out
         } else if matches!(&image_data, RenderImageData::Rgb(_)) && !has_alpha && needs_resize {
@@ -402,7 +428,7 @@ impl Renderer {
             };
 
             let resized = self.resize_image_data(
-                rgb.data,
+                &rgb.data,
                 img_width,
                 img_height,
                 new_width,
@@ -422,15 +448,16 @@ impl Renderer {
             }
             out
         } else {
-            let (rgb_data, alpha_data) = match image_data {
-                RenderImageData::Rgb(rgb) => (rgb.data, alpha_data.map(|a| a.data)),
+            let alpha_data = alpha_data.as_ref().map(|a| &*a.data);
+            let rgb_data = match &image_data {
+                RenderImageData::Rgb(rgb) => Cow::Borrowed(&*rgb.data),
                 RenderImageData::Luma(luma) => {
                     let rgb = luma
                         .data
                         .iter()
                         .flat_map(|g| [*g, *g, *g])
                         .collect::<Vec<_>>();
-                    (rgb, alpha_data.map(|a| a.data))
+                    Cow::Owned(rgb)
                 }
                 RenderImageData::Solid(solid) => {
                     let mut rgb =
@@ -438,7 +465,7 @@ impl Renderer {
                     for _ in 0..solid.width as usize * solid.height as usize {
                         rgb.extend_from_slice(&solid.color);
                     }
-                    (rgb, alpha_data.map(|a| a.data))
+                    Cow::Owned(rgb)
                 }
             };
 
@@ -449,8 +476,8 @@ impl Renderer {
                     .collect::<Vec<_>>(),
                 Some(alpha) => rgb_data
                     .chunks_exact(3)
-                    .zip(alpha)
-                    .flat_map(|(rgb, a)| [rgb[0], rgb[1], rgb[2], a])
+                    .zip(alpha.iter())
+                    .flat_map(|(rgb, a)| [rgb[0], rgb[1], rgb[2], *a])
                     .collect::<Vec<_>>(),
             };
 
@@ -458,7 +485,7 @@ impl Renderer {
                 rgba_data
             } else {
                 let resized = self.resize_image_data(
-                    rgba_data,
+                    &rgba_data,
                     img_width,
                     img_height,
                     new_width,
@@ -553,13 +580,14 @@ impl Renderer {
 
     fn apply_soft_mask(&mut self, mask: Option<&SoftMask<'_>>) {
         let settings = *self.ctx.render_settings();
+        let quality = self.quality;
         let mask = mask.map(|m| {
             let width = self.ctx.width();
             let height = self.ctx.height();
 
             self.soft_mask_cache
                 .entry(m.cache_key())
-                .or_insert_with(|| draw_soft_mask(m, settings, width, height))
+                .or_insert_with(|| draw_soft_mask(m, settings, quality, width, height))
                 .clone()
         });
 
@@ -604,8 +632,6 @@ impl Renderer {
 
                 match *p {
                     Pattern::Shading(s) => {
-                        const NATIVE_GRADIENT_TOLERANCE: f32 = 0.01;
-
                         clip_path = s.shading.clip_path.clone();
                         let encoded = s.encode();
                         let mut bbox = (*path_transform * path_bbox().to_path(0.0)).bounding_box();
@@ -625,8 +651,12 @@ impl Renderer {
                         ));
 
                         if let EncodedShadingType::RadialAxial(gradient) = &encoded.shading_type
-                            && let Some(native) =
-                                gradient.as_svg_gradient(&encoded, bbox, NATIVE_GRADIENT_TOLERANCE)
+                            && let Some(native) = gradient.as_svg_gradient(
+                                &encoded,
+                                bbox,
+                                s.smoothness,
+                                s.gradient_subdivisions,
+                            )
                         {
                             paint_transform = path_transform.inverse()
                                 * Affine::translate((-0.5, -0.5))
@@ -702,7 +732,11 @@ impl Renderer {
 
                         let (mut xs, mut ys) = {
                             let (x, y) = x_y_advances(&(t.matrix));
-                            (x.length() as f32, y.length() as f32)
+                            let resolution = self.quality.pattern_resolution;
+                            (
+                                x.length() as f32 * resolution,
+                                y.length() as f32 * resolution,
+                            )
                         };
                         xs = xs.max(min_x_scale).min(max_x_scale);
                         ys = ys.max(min_y_scale).min(max_y_scale);
@@ -715,25 +749,39 @@ impl Renderer {
                         let pix_width = x_step.abs().round() as u16;
                         let pix_height = y_step.abs().round() as u16;
 
-                        let mut renderer = Self {
-                            ctx: RenderContext::new_with(
-                                pix_width,
-                                pix_height,
-                                derive_settings(self.ctx.render_settings()),
-                            ),
-                            inside_pattern: true,
-                            soft_mask_cache: FxHashMap::default(),
-                            outline_cache: self.outline_cache.clone(),
-                            in_type3_glyph: false,
-                            scaler: self.scaler,
-                        };
                         let mut initial_transform = Affine::scale_non_uniform(xs as f64, ys as f64)
                             * Affine::translate((-bbox.x0, -bbox.y0));
-                        t.interpret(&mut renderer, initial_transform, is_stroke);
-                        let mut pix = Pixmap::new(pix_width, pix_height);
-                        renderer.ctx.flush();
-                        let mut resources = vello_cpu::Resources::default();
-                        renderer.ctx.render(&mut pix, &mut resources);
+
+                        // Patterns are often used for many fills on the same page, so only
+                        // render each tile once.
+                        let tile_key = (t.cache_key(), xs.to_bits(), ys.to_bits(), is_stroke);
+                        let pix = if let Some(pix) = self.tile_cache.get(&tile_key) {
+                            pix.clone()
+                        } else {
+                            let mut renderer = Self {
+                                ctx: RenderContext::new_with(
+                                    pix_width,
+                                    pix_height,
+                                    derive_settings(self.ctx.render_settings()),
+                                ),
+                                inside_pattern: true,
+                                soft_mask_cache: FxHashMap::default(),
+                                tile_cache: FxHashMap::default(),
+                                in_type3_glyph: false,
+                                scaler: self.scaler,
+                                quality: self.quality,
+                            };
+                            t.interpret(&mut renderer, initial_transform, is_stroke);
+                            let mut pix = Pixmap::new(pix_width, pix_height);
+                            renderer.ctx.flush();
+                            let mut resources = vello_cpu::Resources::default();
+                            renderer.ctx.render(&mut pix, &mut resources);
+
+                            let pix = Arc::new(pix);
+                            self.tile_cache.insert(tile_key, pix.clone());
+
+                            pix
+                        };
 
                         // TODO: Fix these
                         if x_step < 0.0 {
@@ -750,7 +798,7 @@ impl Renderer {
                             path_transform.inverse() * t.matrix * initial_transform.inverse();
 
                         let image = Image {
-                            image: ImageSource::Pixmap(Arc::new(pix)),
+                            image: ImageSource::Pixmap(pix),
                             sampler: ImageSampler {
                                 x_extend: peniko::Extend::Repeat,
                                 y_extend: peniko::Extend::Repeat,
@@ -779,7 +827,13 @@ impl Renderer {
         is_text: bool,
     ) {
         self.apply_draw_props(&props);
-        self.set_stroke_properties(stroke_props, is_text);
+        let width = self.set_stroke_properties(stroke_props, is_text);
+
+        let adjusted = stroke_props
+            .stroke_adjustment
+            .then(|| adjust_stroke(path, *self.ctx.transform(), width))
+            .flatten();
+        let path = adjusted.as_ref().unwrap_or(path);
 
         let clip_path = self.set_paint(&props.paint, || path.bounding_box(), true);
         if let Some(clip_path) = clip_path.as_ref() {
@@ -809,6 +863,9 @@ impl Renderer {
 
     fn fill_glyph<'a>(&mut self, glyph: &Glyph<'a>, props: DrawProps<'a>, glyph_transform: Affine) {
         match glyph {
+            Glyph::Outline(o) if o.is_color() => {
+                o.interpret_color(self, glyph_transform, &props);
+            }
             Glyph::Outline(o) => {
                 let base_outline = self.cached_outline(o);
                 let props = DrawProps {
@@ -850,16 +907,8 @@ impl Renderer {
         }
     }
 
-    fn cached_outline(&self, glyph: &hayro_interpret::font::OutlineGlyph) -> Rc<BezPath> {
-        let id = glyph.identifier().cache_key();
-
-        if let Some(path) = self.outline_cache.borrow().get(&id) {
-            return path.clone();
-        }
-
-        let path = Rc::new(glyph.outline());
-        self.outline_cache.borrow_mut().insert(id, path.clone());
-        path
+    fn cached_outline(&self, glyph: &hayro_interpret::font::OutlineGlyph) -> Arc<BezPath> {
+        glyph.glyph_cache().outline(glyph)
     }
 }
 
@@ -940,7 +989,8 @@ impl<'a> Device<'a> for Renderer {
                                             stencil.width as usize
                                                 * stencil.height as usize
                                                 * 3
-                                        ],
+                                        ]
+                                        .into(),
                                         width: stencil.width,
                                         height: stencil.height,
                                         interpolate: stencil.interpolate,
@@ -954,9 +1004,10 @@ impl<'a> Device<'a> for Renderer {
                                         ),
                                         inside_pattern: false,
                                         soft_mask_cache: FxHashMap::default(),
-                                        outline_cache: self.outline_cache.clone(),
+                                        tile_cache: FxHashMap::default(),
                                         in_type3_glyph: false,
                                         scaler: self.scaler,
+                                        quality: self.quality,
                                     };
                                     let mut sub_pix = Pixmap::new(width, height);
                                     sub_renderer.ctx.set_transform(transform);
@@ -1023,6 +1074,7 @@ impl<'a> Device<'a> for Renderer {
         blend_mode: BlendMode,
     ) {
         let settings = *self.ctx.render_settings();
+        let quality = self.quality;
         self.ctx.push_layer(
             None,
             Some(convert_blend_mode(blend_mode)),
@@ -1034,7 +1086,7 @@ impl<'a> Device<'a> for Renderer {
 
                 self.soft_mask_cache
                     .entry(m.cache_key())
-                    .or_insert_with(|| draw_soft_mask(&m, settings, width, height))
+                    .or_insert_with(|| draw_soft_mask(&m, settings, quality, width, height))
                     .clone()
             }),
             None,
@@ -1050,6 +1102,11 @@ impl<'a> Device<'a> for Renderer {
     }
 
     fn draw_path(&mut self, path: &BezPath, props: DrawProps<'a>, draw_mode: &DrawMode) {
+        let flattened = props
+            .flatness
+            .and_then(|flatness| flatten_path(path, &props.transform, flatness));
+        let path = flattened.as_ref().unwrap_or(path);
+
         match draw_mode {
             DrawMode::Fill(f) => {
                 Self::fill_path(self, path, props, *f);
@@ -1160,14 +1217,26 @@ fn render_shading_texture(
     )
 }
 
-fn draw_soft_mask(mask: &SoftMask<'_>, settings: RenderSettings, width: u16, height: u16) -> Mask {
+fn draw_soft_mask(
+    mask: &SoftMask<'_>,
+    settings: RenderSettings,
+    quality: RenderQuality,
+    width: u16,
+    height: u16,
+) -> Mask {
+    // Masks can be rendered at a lower resolution and are scaled up afterwards.
+    let resolution = quality.soft_mask_resolution.clamp(0.01, 1.0);
+    let mask_width = ((width as f32 * resolution).ceil() as u16).max(1);
+    let mask_height = ((height as f32 * resolution).ceil() as u16).max(1);
+
     let mut renderer = Renderer {
-        ctx: RenderContext::new_with(width, height, derive_settings(&settings)),
+        ctx: RenderContext::new_with(mask_width, mask_height, derive_settings(&settings)),
         inside_pattern: false,
         soft_mask_cache: FxHashMap::default(),
-        outline_cache: Rc::new(std::cell::RefCell::new(FxHashMap::default())),
+        tile_cache: FxHashMap::default(),
         in_type3_glyph: false,
-        scaler: Scaler::new(ResamplingFunction::CatmullRom),
+        scaler: Scaler::new(resampling_function(quality.image_filter)),
+        quality,
     };
 
     let bg_color = mask.background_color().to_rgba();
@@ -1179,17 +1248,23 @@ fn draw_soft_mask(mask: &SoftMask<'_>, settings: RenderSettings, width: u16, hei
             .set_paint(AlphaColor::<Srgb>::new(bg_color.components()));
         renderer
             .ctx
-            .fill_rect(&Rect::new(0.0, 0.0, width as f64, height as f64));
+            .fill_rect(&Rect::new(0.0, 0.0, mask_width as f64, mask_height as f64));
         renderer.ctx.push_layer(None, None, None, None, None);
     }
 
-    mask.interpret(&mut renderer);
+    mask.interpret_with_transform(
+        &mut renderer,
+        Affine::scale_non_uniform(
+            mask_width as f64 / width as f64,
+            mask_height as f64 / height as f64,
+        ),
+    );
 
     if apply_bg {
         renderer.ctx.pop_layer();
     }
 
-    let mut pix = Pixmap::new(width, height);
+    let mut pix = Pixmap::new(mask_width, mask_height);
     renderer.ctx.flush();
     let mut resources = vello_cpu::Resources::default();
     renderer.ctx.render(&mut pix, &mut resources);
@@ -1214,9 +1289,132 @@ fn draw_soft_mask(mask: &SoftMask<'_>, settings: RenderSettings, width: u16, hei
         rendered_mask = Mask::from_parts(map, rendered_mask.width(), rendered_mask.height());
     }
 
+    if (mask_width, mask_height) != (width, height) {
+        rendered_mask = upscale_mask(&rendered_mask, width, height);
+    }
+
     rendered_mask
 }
 
+/// Scale the mask up to the given size, using bilinear interpolation.
+fn upscale_mask(mask: &Mask, width: u16, height: u16) -> Mask {
+    let (src_width, src_height) = (mask.width(), mask.height());
+    let x_factor = src_width as f32 / width as f32;
+    let y_factor = src_height as f32 / height as f32;
+
+    // Return the two neighboring source pixels of a target pixel and the weight
+    // of the second one.
+    let neighbors = |pos: u16, factor: f32, max: u16| {
+        let src = ((pos as f32 + 0.5) * factor - 0.5).max(0.0);
+        let low = (src as u16).min(max - 1);
+        let high = (low + 1).min(max - 1);
+
+        (low, high, src - low as f32)
+    };
+
+    let mut data = Vec::with_capacity(width as usize * height as usize);
+
+    for y in 0..height {
+        let (y0, y1, ty) = neighbors(y, y_factor, src_height);
+
+        for x in 0..width {
+            let (x0, x1, tx) = neighbors(x, x_factor, src_width);
+            let sample = |x, y| mask.sample(x, y) as f32;
+
+            let top = sample(x0, y0) * (1.0 - tx) + sample(x1, y0) * tx;
+            let bottom = sample(x0, y1) * (1.0 - tx) + sample(x1, y1) * tx;
+            data.push((top * (1.0 - ty) + bottom * ty + 0.5) as u8);
+        }
+    }
+
+    Mask::from_parts(data, width, height)
+}
+
+fn resampling_function(filter: ImageFilterQuality) -> ResamplingFunction {
+    match filter {
+        ImageFilterQuality::Low => ResamplingFunction::Bilinear,
+        ImageFilterQuality::Medium => ResamplingFunction::CatmullRom,
+        ImageFilterQuality::High => ResamplingFunction::Lanczos3,
+    }
+}
+
+/// Return whether lines that are parallel to the axes stay parallel to them
+/// under the given transform.
+fn is_axis_aligned(transform: &Affine) -> bool {
+    let [a, b, c, d, _, _] = transform.as_coeffs();
+
+    (b.abs() < 1e-6 && c.abs() < 1e-6) || (a.abs() < 1e-6 && d.abs() < 1e-6)
+}
+
+/// Align the points of a path to the pixel grid, so that a stroke with the
+/// given width in device space covers whole pixels.
+///
+/// Returns `None` if the path contains curves or the transform isn't axis-aligned,
+/// in which case the path should be stroked as it is.
+fn adjust_stroke(path: &BezPath, transform: Affine, width: f32) -> Option<BezPath> {
+    if !is_axis_aligned(&transform) || transform.determinant().abs() < 1e-12 {
+        return None;
+    }
+
+    let inverse = transform.inverse();
+    // Strokes with an odd width are centered on pixel centers, and strokes with
+    // an even width on pixel edges.
+    let offset = if width.round() as i64 % 2 == 1 {
+        0.5
+    } else {
+        0.0
+    };
+    let snap = |p: Point| {
+        let p = transform * p;
+        inverse
+            * Point::new(
+                (p.x - offset).round() + offset,
+                (p.y - offset).round() + offset,
+            )
+    };
+
+    let mut adjusted = BezPath::new();
+
+    for el in path.elements() {
+        match *el {
+            PathEl::MoveTo(p) => adjusted.move_to(snap(p)),
+            PathEl::LineTo(p) => adjusted.line_to(snap(p)),
+            PathEl::ClosePath => adjusted.close_path(),
+            PathEl::QuadTo(..) | PathEl::CurveTo(..) => return None,
+        }
+    }
+
+    Some(adjusted)
+}
+
+/// Approximate the curves of a path with line segments that stay within the
+/// given flatness tolerance in device space.
+///
+/// Returns `None` if the path contains no curves, the transform is degenerate or
+/// the tolerance is not finer than the one of `vello_cpu`, in which case the path
+/// should be drawn as it is. Coarser tolerances are not honored, since they would
+/// only make curves look worse.
+fn flatten_path(path: &BezPath, transform: &Affine, flatness: f32) -> Option<BezPath> {
+    let has_curves = path
+        .elements()
+        .iter()
+        .any(|el| matches!(el, PathEl::QuadTo(..) | PathEl::CurveTo(..)));
+    let scale = max_factor(transform);
+    let flatness = flatness.max(MIN_FLATNESS);
+
+    if !has_curves || scale < 1e-6 || flatness >= VELLO_FLATNESS {
+        return None;
+    }
+
+    // The path is drawn in user space, so the tolerance shrinks when zooming in
+    // and grows when zooming out.
+    let tolerance = flatness / scale;
+    let mut flattened = BezPath::new();
+    kurbo::flatten(path.iter(), tolerance as f64, |el| flattened.push(el));
+
+    Some(flattened)
+}
+
 pub(crate) fn max_factor(transform: &Affine) -> f32 {
     let scale_skew_transform = {
         let c = transform.as_coeffs();